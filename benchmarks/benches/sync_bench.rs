@@ -0,0 +1,122 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use matrix_sdk_base::{store::StoreConfig, BaseClient, SessionMeta};
+use matrix_sdk_sqlite::SqliteStateStore;
+use matrix_sdk_test::SyncLoadGenerator;
+use ruma::{device_id, user_id, UserId};
+use tokio::runtime::Builder;
+
+fn criterion() -> Criterion {
+    #[cfg(target_os = "linux")]
+    let criterion = Criterion::default().with_profiler(pprof::criterion::PProfProfiler::new(
+        100,
+        pprof::criterion::Output::Flamegraph(None),
+    ));
+
+    #[cfg(not(target_os = "linux"))]
+    let criterion = Criterion::default();
+
+    criterion
+}
+
+fn alice_id() -> &'static UserId {
+    user_id!("@alice:example.org")
+}
+
+/// Number of joined rooms in the benchmark.
+const NUM_ROOMS: usize = 500;
+
+/// Number of members of every room in the benchmark.
+const NUM_MEMBERS_PER_ROOM: usize = 50;
+
+/// Number of new messages of every room in each sync response.
+const NUM_EVENTS_PER_ROOM: usize = 20;
+
+async fn logged_in_client(config: StoreConfig) -> BaseClient {
+    let client = BaseClient::with_store_config(config);
+    client
+        .set_session_meta(SessionMeta {
+            user_id: alice_id().to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .expect("Can't set the session");
+    client
+}
+
+pub fn receive_sync(c: &mut Criterion) {
+    let runtime = Builder::new_multi_thread().build().expect("Can't create runtime");
+
+    let mut generator = SyncLoadGenerator::new(alice_id())
+        .rooms(NUM_ROOMS)
+        .members_per_room(NUM_MEMBERS_PER_ROOM)
+        .events_per_room(NUM_EVENTS_PER_ROOM)
+        .membership_churn(5)
+        .receipts_per_room(5);
+    let initial_response = generator.build_sync_response();
+    let next_response = generator.build_sync_response();
+
+    let mut group = c.benchmark_group("Sync response handling");
+    group.throughput(Throughput::Elements(NUM_ROOMS as u64));
+
+    let initial_name = format!("initial sync of {NUM_ROOMS} rooms");
+    let next_name = format!("incremental sync of {NUM_ROOMS} rooms");
+
+    // Benchmark memory store.
+
+    group.bench_function(BenchmarkId::new("memory store", &initial_name), |b| {
+        b.to_async(&runtime).iter_batched(
+            || initial_response.clone(),
+            |response| async {
+                let client = logged_in_client(StoreConfig::new()).await;
+                client.receive_sync_response(response).await.unwrap();
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function(BenchmarkId::new("memory store", &next_name), |b| {
+        let client = runtime.block_on(async {
+            let client = logged_in_client(StoreConfig::new()).await;
+            client.receive_sync_response(initial_response.clone()).await.unwrap();
+            client
+        });
+
+        b.to_async(&runtime).iter_batched(
+            || next_response.clone(),
+            |response| async { client.receive_sync_response(response).await.unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+
+    // Benchmark sqlite store.
+
+    group.bench_function(BenchmarkId::new("sqlite store", &next_name), |b| {
+        let dir = tempfile::tempdir().unwrap();
+        let client = runtime.block_on(async {
+            let store = SqliteStateStore::open(dir.path(), None).await.unwrap();
+            let client = logged_in_client(StoreConfig::new().state_store(store)).await;
+            client.receive_sync_response(initial_response.clone()).await.unwrap();
+            client
+        });
+
+        b.to_async(&runtime).iter_batched(
+            || next_response.clone(),
+            |response| async { client.receive_sync_response(response).await.unwrap() },
+            BatchSize::SmallInput,
+        );
+
+        {
+            let _guard = runtime.enter();
+            drop(client);
+        }
+    });
+
+    group.finish()
+}
+
+criterion_group! {
+    name = benches;
+    config = criterion();
+    targets = receive_sync
+}
+criterion_main!(benches);
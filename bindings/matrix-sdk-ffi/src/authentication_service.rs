@@ -340,6 +340,8 @@ impl AuthenticationService {
         let code = match response {
             AuthorizationResponse::Success(code) => code,
             AuthorizationResponse::Error(err) => {
+                RUNTIME.block_on(oidc.abort_authorization(&err.state));
+
                 if err.error.error == AccessDenied {
                     // The user cancelled the login in the web view.
                     return Err(AuthenticationError::OidcCancelled);
@@ -367,6 +369,16 @@ impl AuthenticationService {
             client.inner.oidc().full_session().ok_or(AuthenticationError::SessionMissing)?;
         self.finalize_client(client, session, user_id)
     }
+
+    /// Cancels the OIDC login process started with `url_for_oidc_login`, when
+    /// the web view was dismissed without returning a callback.
+    pub fn abort_oidc_login(&self, authentication_data: Arc<OidcAuthenticationData>) {
+        let Some(client) = self.client.read().unwrap().clone() else {
+            return;
+        };
+
+        RUNTIME.block_on(client.inner.oidc().abort_authorization(&authentication_data.state));
+    }
 }
 
 impl AuthenticationService {
@@ -0,0 +1,237 @@
+use std::{sync::Arc, time::Duration};
+
+use matrix_sdk::room::{
+    CallSignalingEvent as SdkCallSignalingEvent,
+    CallSignalingEventContent as SdkCallSignalingEventContent,
+    CallSignalingSession as SdkCallSignalingSession, CallSignalingState as SdkCallSignalingState,
+};
+use ruma::{
+    events::call::{
+        candidates::Candidate as RumaCandidate, hangup::Reason,
+        SessionDescription as RumaSessionDescription,
+    },
+    UserId, VoipId,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{error::ClientError, utils::u64_to_uint, TaskHandle, RUNTIME};
+
+/// Tracks the 1:1 VoIP calls of a room and sends their signalling events.
+#[derive(uniffi::Object)]
+pub struct CallSignalingSession {
+    inner: SdkCallSignalingSession,
+}
+
+impl CallSignalingSession {
+    pub(crate) fn new(inner: SdkCallSignalingSession) -> Self {
+        Self { inner }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl CallSignalingSession {
+    /// The party ID of the current device in the calls of this session.
+    pub fn party_id(&self) -> String {
+        self.inner.party_id().to_string()
+    }
+
+    /// The state of the call with the given ID, if it is known.
+    pub fn state(&self, call_id: String) -> Option<CallSignalingState> {
+        self.inner.state(<&VoipId>::from(call_id.as_str())).map(Into::into)
+    }
+
+    /// Subscribe to the signalling events received for the calls of the room.
+    pub fn subscribe(&self, listener: Box<dyn CallSignalingListener>) -> Arc<TaskHandle> {
+        let mut events = self.inner.subscribe();
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => listener.on_event(event.into()),
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })))
+    }
+
+    /// Start a new call by sending an invite.
+    ///
+    /// Returns the ID of the new call.
+    pub async fn invite(
+        &self,
+        invitee: Option<String>,
+        offer: SessionDescription,
+        lifetime_ms: u64,
+    ) -> Result<String, ClientError> {
+        let invitee = invitee.map(UserId::parse).transpose()?;
+        let call_id =
+            self.inner.invite(invitee, offer.into(), Duration::from_millis(lifetime_ms)).await?;
+        Ok(call_id.to_string())
+    }
+
+    /// Answer the ringing call with the given ID.
+    pub async fn answer(
+        &self,
+        call_id: String,
+        answer: SessionDescription,
+    ) -> Result<(), ClientError> {
+        Ok(self.inner.answer(<&VoipId>::from(call_id.as_str()), answer.into()).await?)
+    }
+
+    /// Reject the ringing call with the given ID.
+    pub async fn reject(&self, call_id: String) -> Result<(), ClientError> {
+        Ok(self.inner.reject(<&VoipId>::from(call_id.as_str())).await?)
+    }
+
+    /// Send ICE candidates for the call with the given ID.
+    pub async fn send_candidates(
+        &self,
+        call_id: String,
+        candidates: Vec<CallCandidate>,
+    ) -> Result<(), ClientError> {
+        let candidates = candidates.into_iter().map(Into::into).collect();
+        Ok(self.inner.send_candidates(<&VoipId>::from(call_id.as_str()), candidates).await?)
+    }
+
+    /// Hang up the call with the given ID.
+    ///
+    /// `reason` is the reason of the hangup as defined in the spec, e.g.
+    /// `user_hangup`.
+    pub async fn hangup(&self, call_id: String, reason: String) -> Result<(), ClientError> {
+        let reason = Reason::from(reason.as_str());
+        Ok(self.inner.hangup(<&VoipId>::from(call_id.as_str()), reason).await?)
+    }
+}
+
+#[uniffi::export(callback_interface)]
+pub trait CallSignalingListener: Sync + Send {
+    fn on_event(&self, event: CallSignalingEvent);
+}
+
+/// The state of a 1:1 call, from the point of view of the current device.
+#[derive(uniffi::Enum)]
+pub enum CallSignalingState {
+    Inviting,
+    Ringing,
+    Answered,
+    Connected { opponent_party_id: Option<String> },
+    HandledElsewhere,
+    Ended,
+}
+
+impl From<SdkCallSignalingState> for CallSignalingState {
+    fn from(value: SdkCallSignalingState) -> Self {
+        match value {
+            SdkCallSignalingState::Inviting => Self::Inviting,
+            SdkCallSignalingState::Ringing => Self::Ringing,
+            SdkCallSignalingState::Answered => Self::Answered,
+            SdkCallSignalingState::Connected { opponent_party_id } => {
+                Self::Connected { opponent_party_id: opponent_party_id.map(|id| id.to_string()) }
+            }
+            SdkCallSignalingState::HandledElsewhere => Self::HandledElsewhere,
+            SdkCallSignalingState::Ended => Self::Ended,
+        }
+    }
+}
+
+/// A signalling event received for a 1:1 call.
+#[derive(uniffi::Record)]
+pub struct CallSignalingEvent {
+    pub sender: String,
+    pub call_id: String,
+    /// The party ID of the sender, if it uses version 1 of the signalling.
+    pub party_id: Option<String>,
+    pub content: CallSignalingEventContent,
+    /// The state of the call after this event was processed.
+    pub state: CallSignalingState,
+}
+
+impl From<SdkCallSignalingEvent> for CallSignalingEvent {
+    fn from(value: SdkCallSignalingEvent) -> Self {
+        Self {
+            sender: value.sender.to_string(),
+            call_id: value.content.call_id().to_string(),
+            party_id: value.content.party_id().map(ToString::to_string),
+            content: value.content.into(),
+            state: value.state.into(),
+        }
+    }
+}
+
+#[derive(uniffi::Enum)]
+pub enum CallSignalingEventContent {
+    Invite { offer: SessionDescription, lifetime_ms: u64, invitee: Option<String> },
+    Answer { answer: SessionDescription },
+    Candidates { candidates: Vec<CallCandidate> },
+    SelectAnswer { selected_party_id: String },
+    Reject,
+    Hangup { reason: String },
+}
+
+impl From<SdkCallSignalingEventContent> for CallSignalingEventContent {
+    fn from(value: SdkCallSignalingEventContent) -> Self {
+        match value {
+            SdkCallSignalingEventContent::Invite(c) => Self::Invite {
+                offer: c.offer.into(),
+                lifetime_ms: c.lifetime.into(),
+                invitee: c.invitee.map(|user_id| user_id.to_string()),
+            },
+            SdkCallSignalingEventContent::Answer(c) => Self::Answer { answer: c.answer.into() },
+            SdkCallSignalingEventContent::Candidates(c) => {
+                Self::Candidates { candidates: c.candidates.into_iter().map(Into::into).collect() }
+            }
+            SdkCallSignalingEventContent::SelectAnswer(c) => {
+                Self::SelectAnswer { selected_party_id: c.selected_party_id.to_string() }
+            }
+            SdkCallSignalingEventContent::Reject(_) => Self::Reject,
+            SdkCallSignalingEventContent::Hangup(c) => {
+                Self::Hangup { reason: c.reason.as_str().to_owned() }
+            }
+        }
+    }
+}
+
+/// A WebRTC session description.
+#[derive(uniffi::Record)]
+pub struct SessionDescription {
+    /// The type of the session description, e.g. `offer` or `answer`.
+    pub session_type: String,
+    /// The SDP text of the session description.
+    pub sdp: String,
+}
+
+impl From<RumaSessionDescription> for SessionDescription {
+    fn from(value: RumaSessionDescription) -> Self {
+        Self { session_type: value.session_type, sdp: value.sdp }
+    }
+}
+
+impl From<SessionDescription> for RumaSessionDescription {
+    fn from(value: SessionDescription) -> Self {
+        Self::new(value.session_type, value.sdp)
+    }
+}
+
+/// An ICE candidate.
+#[derive(uniffi::Record)]
+pub struct CallCandidate {
+    pub candidate: String,
+    pub sdp_mid: String,
+    pub sdp_m_line_index: u64,
+}
+
+impl From<RumaCandidate> for CallCandidate {
+    fn from(value: RumaCandidate) -> Self {
+        Self {
+            candidate: value.candidate,
+            sdp_mid: value.sdp_mid,
+            sdp_m_line_index: value.sdp_m_line_index.into(),
+        }
+    }
+}
+
+impl From<CallCandidate> for RumaCandidate {
+    fn from(value: CallCandidate) -> Self {
+        Self::new(value.candidate, value.sdp_mid, u64_to_uint(value.sdp_m_line_index))
+    }
+}
@@ -1,12 +1,19 @@
 use std::{
     collections::HashMap,
+    io::Cursor,
     mem::ManuallyDrop,
     sync::{Arc, RwLock},
 };
 
 use anyhow::{anyhow, Context as _};
+use eyeball::SharedObservable;
+use futures_util::{pin_mut, StreamExt};
 use matrix_sdk::{
-    media::{MediaFileHandle as SdkMediaFileHandle, MediaFormat, MediaRequest, MediaThumbnailSize},
+    media::{
+        MediaFileHandle as SdkMediaFileHandle, MediaFormat, MediaRequest, MediaThumbnailSize,
+        UrlPreview as SdkUrlPreview,
+    },
+    message_search::MessageSearchOptions as SdkMessageSearchOptions,
     oidc::{
         types::{
             client_credentials::ClientCredentials,
@@ -16,6 +23,7 @@ use matrix_sdk::{
         },
         OidcAccountManagementAction, OidcSession,
     },
+    room_creation::{PowerLevelsPreset as SdkPowerLevelsPreset, RoomCreationBuilder},
     ruma::{
         api::client::{
             account::whoami,
@@ -26,15 +34,15 @@ use matrix_sdk::{
             user_directory::search_users,
         },
         events::{
-            room::{
-                avatar::RoomAvatarEventContent, encryption::RoomEncryptionEventContent, MediaSource,
-            },
-            AnyInitialStateEvent, AnyToDeviceEvent, InitialStateEvent,
+            direct::DirectEventContent, room::MediaSource, AnyGlobalAccountDataEvent,
+            AnyToDeviceEvent,
         },
         serde::Raw,
-        EventEncryptionAlgorithm, TransactionId, UInt, UserId,
+        MilliSecondsSinceUnixEpoch, OwnedDeviceId, RoomId, RoomOrAliasId, ServerName,
+        TransactionId, UInt, UserId,
     },
-    AuthApi, AuthSession, Client as MatrixClient, SessionChange, SessionTokens,
+    AuthApi, AuthSession, Client as MatrixClient, PusherSettings, ReloginCredentials,
+    SessionChange, SessionTokens,
 };
 use matrix_sdk_ui::notification_client::NotificationProcessSetup as MatrixNotificationProcessSetup;
 use mime::Mime;
@@ -45,16 +53,31 @@ use ruma::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::sync::broadcast::error::RecvError;
+use tokio::{
+    sync::{broadcast::error::RecvError, mpsc, Mutex},
+    task::{AbortHandle, JoinHandle},
+};
 use tracing::{debug, error};
 use url::Url;
 
-use super::{room::Room, session_verification::SessionVerificationController, RUNTIME};
+use super::{
+    room::{IncomingCall, Room},
+    session_verification::SessionVerificationController,
+    RUNTIME,
+};
 use crate::{
     client,
+    devices::{DeleteDevicesAuthListener, OwnDevice, PasswordUiaaHandler},
     encryption::Encryption,
+    homeserver_info::HomeserverInfo,
+    identity_server::IdentityServer,
+    message_search::{MessageSearchResponse, SearchOrder},
     notification::NotificationClientBuilder,
     notification_settings::NotificationSettings,
+    room_directory_search::RoomDirectorySearch,
+    room_preview::RoomPreview,
+    ruma::ImageInfo,
+    space::SpaceHierarchy,
     sync_service::{SyncService, SyncServiceBuilder},
     task_handle::TaskHandle,
     ClientError,
@@ -120,6 +143,60 @@ impl From<PushFormat> for RumaPushFormat {
     }
 }
 
+/// A pusher of the account, as returned by the homeserver.
+#[derive(Clone, uniffi::Record)]
+pub struct PusherDetails {
+    pub identifiers: PusherIdentifiers,
+    pub kind: PusherKind,
+    pub app_display_name: String,
+    pub device_display_name: String,
+    pub profile_tag: Option<String>,
+    pub lang: String,
+    /// Whether the homeserver sends notifications to this pusher.
+    pub enabled: bool,
+    /// The device which created the pusher, if the homeserver supports
+    /// MSC3881.
+    pub device_id: Option<String>,
+}
+
+impl PusherDetails {
+    /// Convert the settings of a pusher, or return `None` if it has a custom
+    /// kind.
+    fn from_settings(settings: PusherSettings) -> Option<Self> {
+        let pusher = settings.pusher;
+
+        let kind = match pusher.kind {
+            RumaPusherKind::Http(data) => PusherKind::Http {
+                data: HttpPusherData {
+                    url: data.url,
+                    format: data.format.and_then(|format| match format {
+                        RumaPushFormat::EventIdOnly => Some(PushFormat::EventIdOnly),
+                        _ => None,
+                    }),
+                    default_payload: (!data.default_payload.is_null())
+                        .then(|| data.default_payload.to_string()),
+                },
+            },
+            RumaPusherKind::Email(_) => PusherKind::Email,
+            _ => return None,
+        };
+
+        Some(Self {
+            identifiers: PusherIdentifiers {
+                pushkey: pusher.ids.pushkey,
+                app_id: pusher.ids.app_id,
+            },
+            kind,
+            app_display_name: pusher.app_display_name,
+            device_display_name: pusher.device_display_name,
+            profile_tag: pusher.profile_tag,
+            lang: pusher.lang,
+            enabled: settings.enabled,
+            device_id: settings.device_id.map(|device_id| device_id.to_string()),
+        })
+    }
+}
+
 #[uniffi::export(callback_interface)]
 pub trait ClientDelegate: Sync + Send {
     fn did_receive_auth_error(&self, is_soft_logout: bool);
@@ -137,6 +214,77 @@ pub trait ProgressWatcher: Send + Sync {
     fn transmission_progress(&self, progress: TransmissionProgress);
 }
 
+#[uniffi::export(callback_interface)]
+pub trait IgnoredUsersListener: Sync + Send {
+    fn call(&self, ignored_user_ids: Vec<String>);
+}
+
+#[uniffi::export(callback_interface)]
+pub trait AccountDataListener: Sync + Send {
+    /// Called with the new content of the account data, as a JSON string.
+    fn call(&self, content: String);
+}
+
+#[uniffi::export(callback_interface)]
+pub trait UnreadCountsListener: Sync + Send {
+    fn on_update(&self, unread_counts: UnreadCounts);
+}
+
+#[uniffi::export(callback_interface)]
+pub trait IncomingCallListener: Sync + Send {
+    fn on_incoming_call(&self, incoming_call: IncomingCall);
+}
+
+#[derive(Clone, Copy, uniffi::Record)]
+pub struct UnreadCounts {
+    pub num_rooms_with_notifications: u64,
+    pub num_notifications: u64,
+    pub num_mentions: u64,
+    pub num_unread_messages: u64,
+}
+
+impl From<matrix_sdk::UnreadCounts> for UnreadCounts {
+    fn from(value: matrix_sdk::UnreadCounts) -> Self {
+        Self {
+            num_rooms_with_notifications: value.num_rooms_with_notifications,
+            num_notifications: value.num_notifications,
+            num_mentions: value.num_mentions,
+            num_unread_messages: value.num_unread_messages,
+        }
+    }
+}
+
+/// A preview of a URL, generated by the homeserver.
+#[derive(Clone, uniffi::Record)]
+pub struct UrlPreview {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// The image of the page, that can be downloaded with the media methods.
+    pub image: Option<Arc<MediaSource>>,
+    pub image_mimetype: Option<String>,
+    pub image_size: Option<u64>,
+    pub image_width: Option<u64>,
+    pub image_height: Option<u64>,
+}
+
+impl From<SdkUrlPreview> for UrlPreview {
+    fn from(value: SdkUrlPreview) -> Self {
+        Self {
+            url: value.url,
+            title: value.title,
+            description: value.description,
+            site_name: value.site_name,
+            image: value.image.map(|uri| Arc::new(MediaSource::Plain(uri))),
+            image_mimetype: value.image_mimetype,
+            image_size: value.image_size.map(Into::into),
+            image_width: value.image_width.map(Into::into),
+            image_height: value.image_height.map(Into::into),
+        }
+    }
+}
+
 #[derive(Clone, Copy, uniffi::Record)]
 pub struct TransmissionProgress {
     pub current: u64,
@@ -158,6 +306,7 @@ pub struct Client {
     delegate: RwLock<Option<Arc<dyn ClientDelegate>>>,
     session_verification_controller:
         Arc<tokio::sync::RwLock<Option<SessionVerificationController>>>,
+    homeserver_info: Mutex<Option<HomeserverInfo>>,
 }
 
 impl Drop for Client {
@@ -197,6 +346,7 @@ impl Client {
             inner: ManuallyDrop::new(sdk_client),
             delegate: RwLock::new(None),
             session_verification_controller,
+            homeserver_info: Mutex::new(None),
         });
 
         if let Some(process_id) = cross_process_refresh_lock_id {
@@ -234,6 +384,15 @@ impl Client {
 
         Ok(client)
     }
+
+    async fn download_media(
+        &self,
+        request: MediaRequest,
+        progress_watcher: Option<Box<dyn ProgressWatcher>>,
+    ) -> Result<Vec<u8>, ClientError> {
+        let progress = progress_observable(progress_watcher);
+        Ok(self.inner.media().get_media_content_with_progress(&request, true, progress).await?)
+    }
 }
 
 #[uniffi::export(async_runtime = "tokio")]
@@ -259,6 +418,53 @@ impl Client {
         })
     }
 
+    /// Log back into the session with the password of the user, after it was
+    /// soft logged out by the server.
+    ///
+    /// The device ID and the crypto identity of the session are kept.
+    pub async fn relogin_with_password(&self, password: String) -> Result<(), ClientError> {
+        self.inner.relogin(ReloginCredentials::Password(password)).await?;
+        Ok(())
+    }
+
+    /// Log back into the session with a login token, after it was soft logged
+    /// out by the server.
+    pub async fn relogin_with_token(&self, token: String) -> Result<(), ClientError> {
+        self.inner.relogin(ReloginCredentials::Token(token)).await?;
+        Ok(())
+    }
+
+    /// Get all the devices of the current user, for example to list them in
+    /// the settings.
+    pub async fn devices(&self) -> Result<Vec<OwnDevice>, ClientError> {
+        let devices = self.inner.devices().await?;
+        Ok(devices.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn rename_device(
+        &self,
+        device_id: String,
+        display_name: String,
+    ) -> Result<(), ClientError> {
+        self.inner.rename_device(device_id.as_str().into(), &display_name).await?;
+        Ok(())
+    }
+
+    /// Delete the given devices, asking the user for their password with the
+    /// listener if the server requires it.
+    pub async fn delete_devices(
+        &self,
+        device_ids: Vec<String>,
+        auth_listener: Box<dyn DeleteDevicesAuthListener>,
+    ) -> Result<(), ClientError> {
+        let user_id = self.inner.user_id().context("Not logged in")?.to_string();
+        let device_ids: Vec<OwnedDeviceId> = device_ids.into_iter().map(Into::into).collect();
+        let handler = PasswordUiaaHandler { user_id, listener: auth_listener.into() };
+
+        self.inner.delete_devices_with_uiaa(&device_ids, &handler).await?;
+        Ok(())
+    }
+
     pub async fn get_media_file(
         &self,
         media_source: Arc<MediaSource>,
@@ -459,8 +665,8 @@ impl Client {
         let client = self.inner.clone();
 
         RUNTIME.block_on(async move {
-            let response = client.create_room(request.into()).await?;
-            Ok(String::from(response.room_id()))
+            let room = request.into_builder(&client)?.create().await?;
+            Ok(String::from(room.room_id()))
         })
     }
 
@@ -486,6 +692,23 @@ impl Client {
         })
     }
 
+    /// Get the images of the personal image pack of the user that can be sent
+    /// as stickers.
+    pub fn user_stickers(&self) -> Result<Vec<PackSticker>, ClientError> {
+        RUNTIME.block_on(async move {
+            let pack = self.inner.account().image_pack().await?;
+            Ok(pack
+                .stickers()
+                .map(|(shortcode, image)| PackSticker {
+                    shortcode: shortcode.to_owned(),
+                    body: image.body.clone().unwrap_or_else(|| shortcode.to_owned()),
+                    url: image.url.to_string(),
+                    info: image.info.as_ref().map(Into::into),
+                })
+                .collect())
+        })
+    }
+
     pub async fn upload_media(
         &self,
         mime_type: String,
@@ -509,6 +732,12 @@ impl Client {
         Ok(String::from(response.content_uri))
     }
 
+    /// Whether the homeserver supports the authenticated media endpoints,
+    /// which are then used to download media.
+    pub async fn supports_authenticated_media(&self) -> bool {
+        self.inner.media().supports_authenticated_media().await
+    }
+
     pub async fn get_media_content(
         &self,
         media_source: Arc<MediaSource>,
@@ -547,6 +776,101 @@ impl Client {
             .await?)
     }
 
+    /// Get a preview of the given URL, generated by the homeserver.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to preview.
+    ///
+    /// * `ts` - The preferred point in time of the preview, in milliseconds
+    ///   since the Unix epoch. Usually the timestamp of the event containing
+    ///   the URL.
+    pub async fn get_url_preview(&self, url: String, ts: u64) -> Result<UrlPreview, ClientError> {
+        let ts = MilliSecondsSinceUnixEpoch(UInt::new(ts).context("Invalid timestamp")?);
+        Ok(self.inner.get_url_preview(&url, ts).await?.into())
+    }
+
+    /// Upload a media file, reporting the progress to the watcher.
+    ///
+    /// If `encrypt` is set, the file is encrypted before being uploaded, like
+    /// the attachments sent in encrypted rooms, and the returned media source
+    /// contains the keys needed to decrypt it.
+    ///
+    /// The upload can be cancelled with the returned handle.
+    pub fn start_media_upload(
+        self: Arc<Self>,
+        mime_type: String,
+        data: Vec<u8>,
+        encrypt: bool,
+        progress_watcher: Option<Box<dyn ProgressWatcher>>,
+    ) -> Arc<MediaUploadHandle> {
+        MediaUploadHandle::new(RUNTIME.spawn(async move {
+            let mime_type: Mime = mime_type.parse()?;
+            let progress = progress_observable(progress_watcher);
+
+            let source = if encrypt {
+                let mut reader = Cursor::new(data);
+                let file = self
+                    .inner
+                    .prepare_encrypted_file(&mime_type, &mut reader)
+                    .with_send_progress_observable(progress)
+                    .await?;
+                MediaSource::Encrypted(Box::new(file))
+            } else {
+                let response = self
+                    .inner
+                    .media()
+                    .upload(&mime_type, data)
+                    .with_send_progress_observable(progress)
+                    .await?;
+                MediaSource::Plain(response.content_uri)
+            };
+
+            Ok(Arc::new(source))
+        }))
+    }
+
+    /// Download the content of a media file, reporting the progress to the
+    /// watcher.
+    ///
+    /// Encrypted media files are decrypted. The download can be cancelled with
+    /// the returned handle.
+    pub fn start_media_download(
+        self: Arc<Self>,
+        media_source: Arc<MediaSource>,
+        progress_watcher: Option<Box<dyn ProgressWatcher>>,
+    ) -> Arc<MediaDownloadHandle> {
+        MediaDownloadHandle::new(RUNTIME.spawn(async move {
+            let request =
+                MediaRequest { source: (*media_source).clone(), format: MediaFormat::File };
+            self.download_media(request, progress_watcher).await
+        }))
+    }
+
+    /// Download a thumbnail of a media file, reporting the progress to the
+    /// watcher.
+    ///
+    /// The download can be cancelled with the returned handle.
+    pub fn start_media_thumbnail_download(
+        self: Arc<Self>,
+        media_source: Arc<MediaSource>,
+        width: u64,
+        height: u64,
+        progress_watcher: Option<Box<dyn ProgressWatcher>>,
+    ) -> Arc<MediaDownloadHandle> {
+        MediaDownloadHandle::new(RUNTIME.spawn(async move {
+            let request = MediaRequest {
+                source: (*media_source).clone(),
+                format: MediaFormat::Thumbnail(MediaThumbnailSize {
+                    method: Method::Scale,
+                    width: UInt::new(width).context("Invalid thumbnail width")?,
+                    height: UInt::new(height).context("Invalid thumbnail height")?,
+                }),
+            };
+            self.download_media(request, progress_watcher).await
+        }))
+    }
+
     pub fn get_session_verification_controller(
         &self,
     ) -> Result<Arc<SessionVerificationController>, ClientError> {
@@ -624,7 +948,50 @@ impl Client {
                 profile_tag,
                 lang,
             };
-            self.inner.set_pusher(pusher_init.into()).await?;
+            self.inner.pushers().set(PusherSettings::new(pusher_init.into())).await?;
+            Ok(())
+        })
+    }
+
+    /// Get the pushers of the account.
+    ///
+    /// Pushers of a custom kind are left out.
+    pub fn pushers(&self) -> Result<Vec<PusherDetails>, ClientError> {
+        RUNTIME.block_on(async move {
+            let pushers = self.inner.pushers().list().await?;
+            Ok(pushers.into_iter().filter_map(PusherDetails::from_settings).collect())
+        })
+    }
+
+    /// Get the pushers created by the current device.
+    ///
+    /// This is always empty if the homeserver doesn't support MSC3881.
+    pub fn pushers_for_current_device(&self) -> Result<Vec<PusherDetails>, ClientError> {
+        RUNTIME.block_on(async move {
+            let device_id = self.inner.device_id().context("Not logged in")?;
+            let pushers = self.inner.pushers().list_for_device(device_id).await?;
+            Ok(pushers.into_iter().filter_map(PusherDetails::from_settings).collect())
+        })
+    }
+
+    /// Enable or disable the pusher with the given identifiers.
+    ///
+    /// Returns `false` if the account doesn't have such a pusher. The
+    /// homeserver ignores this if it doesn't support MSC3881.
+    pub fn set_pusher_enabled(
+        &self,
+        identifiers: PusherIdentifiers,
+        enabled: bool,
+    ) -> Result<bool, ClientError> {
+        RUNTIME.block_on(async move {
+            Ok(self.inner.pushers().set_enabled(&identifiers.into(), enabled).await?)
+        })
+    }
+
+    /// Deletes the pusher with the given identifiers.
+    pub fn delete_pusher(&self, identifiers: PusherIdentifiers) -> Result<(), ClientError> {
+        RUNTIME.block_on(async move {
+            self.inner.pushers().delete(identifiers.into()).await?;
             Ok(())
         })
     }
@@ -638,6 +1005,104 @@ impl Client {
         self.inner.rooms().into_iter().map(|room| Arc::new(Room::new(room))).collect()
     }
 
+    /// Knock on a room, i.e. ask its members to be invited to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The ID or an alias of the room to knock on.
+    ///
+    /// * `reason` - The reason for knocking, shown to the members of the room.
+    ///
+    /// * `server_names` - The servers to attempt to knock on the room through.
+    pub async fn knock(
+        &self,
+        room_id_or_alias: String,
+        reason: Option<String>,
+        server_names: Vec<String>,
+    ) -> Result<Arc<Room>, ClientError> {
+        let room_id_or_alias = RoomOrAliasId::parse(room_id_or_alias)?;
+        let server_names =
+            server_names.into_iter().map(ServerName::parse).collect::<Result<_, _>>()?;
+        let room = self.inner.knock(&room_id_or_alias, reason, server_names).await?;
+        Ok(Arc::new(Room::new(room)))
+    }
+
+    /// Get a preview of a room that the user didn't join, e.g. to display
+    /// an invite or a link to the room.
+    pub async fn get_room_preview(
+        &self,
+        room_id_or_alias: String,
+        via: Vec<String>,
+    ) -> Result<RoomPreview, ClientError> {
+        let room_id_or_alias = RoomOrAliasId::parse(room_id_or_alias)?;
+        let via = via.into_iter().map(ServerName::parse).collect::<Result<_, _>>()?;
+        Ok(self.inner.get_room_preview(&room_id_or_alias, via).await?.into())
+    }
+
+    /// Get the identity server of the user, if any.
+    pub async fn identity_server(&self) -> Result<Option<Arc<IdentityServer>>, ClientError> {
+        let identity_server = self.inner.identity_server().await?;
+        Ok(identity_server.map(|inner| Arc::new(IdentityServer::new(inner))))
+    }
+
+    /// Report a room to the administrators of the homeserver, e.g. a room
+    /// of the room directory or of an invite.
+    pub async fn report_room(&self, room_id: String, reason: String) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        Ok(self.inner.report_room(&room_id, reason).await?)
+    }
+
+    /// Report a user to the administrators of the homeserver.
+    ///
+    /// Fails if the homeserver doesn't support reporting users.
+    pub async fn report_user(&self, user_id: String, reason: String) -> Result<(), ClientError> {
+        let user_id = UserId::parse(user_id)?;
+        Ok(self.inner.report_user(&user_id, reason).await?)
+    }
+
+    /// Get what the homeserver supports.
+    ///
+    /// The information is fetched the first time, and cached until
+    /// [`Client::refresh_homeserver_info`] is called.
+    pub async fn homeserver_info(&self) -> Result<HomeserverInfo, ClientError> {
+        let mut homeserver_info = self.homeserver_info.lock().await;
+
+        if let Some(info) = &*homeserver_info {
+            return Ok(info.clone());
+        }
+
+        let info = HomeserverInfo::fetch(&self.inner).await?;
+        *homeserver_info = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Fetch again what the homeserver supports, and update the cache.
+    pub async fn refresh_homeserver_info(&self) -> Result<HomeserverInfo, ClientError> {
+        let mut homeserver_info = self.homeserver_info.lock().await;
+
+        let info = HomeserverInfo::fetch(&self.inner).await?;
+        *homeserver_info = Some(info.clone());
+        Ok(info)
+    }
+
+    /// Create a search in the room directory.
+    pub fn room_directory_search(&self) -> Arc<RoomDirectorySearch> {
+        Arc::new(RoomDirectorySearch::new((*self.inner).clone()))
+    }
+
+    /// Get the hierarchy of a space, to browse the rooms it contains.
+    ///
+    /// If `suggested_only` is set, only the rooms the spaces suggest to join
+    /// are included.
+    pub fn space_hierarchy(
+        &self,
+        space_id: String,
+        suggested_only: bool,
+    ) -> Result<Arc<SpaceHierarchy>, ClientError> {
+        let space_id = RoomId::parse(space_id)?;
+        Ok(Arc::new(SpaceHierarchy::new((*self.inner).clone(), space_id, suggested_only)))
+    }
+
     pub fn get_dm_room(&self, user_id: String) -> Result<Option<Arc<Room>>, ClientError> {
         let user_id = UserId::parse(user_id)?;
         let sdk_room = self.inner.get_dm_room(&user_id);
@@ -645,6 +1110,13 @@ impl Client {
         Ok(dm)
     }
 
+    /// Get the DM room with the given user, or create it if there is none.
+    pub async fn dm_with(&self, user_id: String) -> Result<Arc<Room>, ClientError> {
+        let user_id = UserId::parse(user_id)?;
+        let room = self.inner.dm_with(&user_id).await?;
+        Ok(Arc::new(Room::new(room)))
+    }
+
     pub fn ignore_user(&self, user_id: String) -> Result<(), ClientError> {
         RUNTIME.block_on(async move {
             let user_id = UserId::parse(user_id)?;
@@ -661,6 +1133,158 @@ impl Client {
         })
     }
 
+    /// Get the users in the ignore list of the account.
+    pub async fn ignored_users(&self) -> Result<Vec<String>, ClientError> {
+        let ignored_users = self.inner.account().ignored_users().await?;
+        Ok(ignored_users.into_iter().map(|user_id| user_id.to_string()).collect())
+    }
+
+    /// Subscribe to the ignore list of the account.
+    ///
+    /// The listener is called with the new list every time it changes, either
+    /// with `ignore_user()` and `unignore_user()`, or from another session.
+    pub fn subscribe_to_ignored_users(
+        self: Arc<Self>,
+        listener: Box<dyn IgnoredUsersListener>,
+    ) -> Arc<TaskHandle> {
+        let mut subscriber = self.inner.subscribe_to_ignore_user_list_changes();
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            while subscriber.next().await.is_some() {
+                match self.ignored_users().await {
+                    Ok(ignored_users) => listener.call(ignored_users),
+                    Err(e) => error!("Failed to load the ignored users: {e}"),
+                }
+            }
+        })))
+    }
+
+    /// Subscribe to the changes of the account data of the given event type,
+    /// received with the sync.
+    ///
+    /// The listener is called with the new content, as a JSON string.
+    pub fn subscribe_to_account_data(
+        &self,
+        event_type: String,
+        listener: Box<dyn AccountDataListener>,
+    ) -> Arc<TaskHandle> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        let handle = self.inner.add_event_handler(move |event: Raw<AnyGlobalAccountDataEvent>| {
+            let sender = sender.clone();
+            let event_type = event_type.clone();
+
+            async move {
+                if event.get_field::<String>("type").ok().flatten() != Some(event_type) {
+                    return;
+                }
+
+                match event.get_field::<Value>("content") {
+                    Ok(Some(content)) => _ = sender.send(content.to_string()),
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to read the content of the account data: {e}"),
+                }
+            }
+        });
+        let drop_guard = self.inner.event_handler_drop_guard(handle);
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            // The event handler is removed when the task is cancelled.
+            let _drop_guard = drop_guard;
+
+            while let Some(content) = receiver.recv().await {
+                listener.call(content);
+            }
+        })))
+    }
+
+    /// Get the rooms marked as direct chats in the account data, by user ID of
+    /// the other members.
+    pub async fn direct_rooms(&self) -> Result<HashMap<String, Vec<String>>, ClientError> {
+        let content = self
+            .inner
+            .account()
+            .account_data::<DirectEventContent>()
+            .await?
+            .map(|content| content.deserialize())
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(content
+            .0
+            .into_iter()
+            .map(|(user_id, room_ids)| {
+                (user_id.to_string(), room_ids.into_iter().map(|id| id.to_string()).collect())
+            })
+            .collect())
+    }
+
+    /// Mark a room as a direct chat with the given users, in the account
+    /// data.
+    pub async fn mark_as_dm(
+        &self,
+        room_id: String,
+        user_ids: Vec<String>,
+    ) -> Result<(), ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let user_ids = user_ids.into_iter().map(UserId::parse).collect::<Result<Vec<_>, _>>()?;
+
+        self.inner.account().mark_as_dm(&room_id, &user_ids).await?;
+        Ok(())
+    }
+
+    /// Whether read receipts are sent privately, so they are not shown to
+    /// the other members of the rooms.
+    pub fn private_read_receipts(&self) -> bool {
+        self.inner.private_read_receipts()
+    }
+
+    /// Set whether read receipts should be sent privately.
+    pub fn set_private_read_receipts(&self, enabled: bool) {
+        self.inner.set_private_read_receipts(enabled);
+    }
+
+    /// Get the unread counts aggregated over all the joined rooms.
+    pub fn unread_counts(&self) -> UnreadCounts {
+        self.inner.unread_counts().into()
+    }
+
+    /// Subscribe to the aggregated unread counts, e.g. to update the
+    /// application badge.
+    ///
+    /// The listener is called with the current value first, and then every
+    /// time the counts change.
+    pub fn subscribe_to_unread_counts(
+        &self,
+        listener: Box<dyn UnreadCountsListener>,
+    ) -> Arc<TaskHandle> {
+        let mut subscriber = self.inner.unread_counts_stream();
+        listener.on_update(subscriber.get().into());
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            while let Some(unread_counts) = subscriber.next().await {
+                listener.on_update(unread_counts.into());
+            }
+        })))
+    }
+
+    /// Subscribe to the calls that should make the current device ring, i.e.
+    /// invites to 1:1 calls and ring notifications for the calls of rooms,
+    /// received with the sync.
+    pub fn subscribe_to_incoming_calls(
+        &self,
+        listener: Box<dyn IncomingCallListener>,
+    ) -> Arc<TaskHandle> {
+        let stream = self.inner.incoming_calls_stream();
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            pin_mut!(stream);
+            while let Some(incoming_call) = stream.next().await {
+                listener.on_incoming_call(incoming_call.into());
+            }
+        })))
+    }
+
     pub fn search_users(
         &self,
         search_term: String,
@@ -672,6 +1296,30 @@ impl Client {
         })
     }
 
+    /// Search messages with the server-side search API.
+    ///
+    /// `rooms` are the rooms to search, or all the joined rooms if it is
+    /// `None`. `from` is the `next_batch` token of the previous page, if any.
+    pub fn search_messages(
+        &self,
+        query: String,
+        rooms: Option<Vec<String>>,
+        order: SearchOrder,
+        from: Option<String>,
+    ) -> Result<MessageSearchResponse, ClientError> {
+        let rooms = rooms
+            .map(|rooms| rooms.into_iter().map(RoomId::parse).collect::<Result<_, _>>())
+            .transpose()?;
+        let mut options = SdkMessageSearchOptions::default();
+        options.order = order.into();
+        options.from = from;
+
+        RUNTIME.block_on(async move {
+            let response = self.inner.search_messages(&query, rooms, options).await?;
+            Ok(response.into())
+        })
+    }
+
     pub fn get_profile(&self, user_id: String) -> Result<UserProfile, ClientError> {
         RUNTIME.block_on(async move {
             let owned_user_id = UserId::parse(user_id.clone())?;
@@ -733,6 +1381,15 @@ impl From<NotificationProcessSetup> for MatrixNotificationProcessSetup {
     }
 }
 
+/// An image of an image pack that can be sent as a sticker.
+#[derive(uniffi::Record)]
+pub struct PackSticker {
+    pub shortcode: String,
+    pub body: String,
+    pub url: String,
+    pub info: Option<ImageInfo>,
+}
+
 #[derive(uniffi::Record)]
 pub struct SearchUsersResults {
     pub results: Vec<UserProfile>,
@@ -905,57 +1562,84 @@ pub struct CreateRoomParameters {
     pub avatar: Option<String>,
     #[uniffi(default = None)]
     pub power_level_content_override: Option<PowerLevels>,
+    /// The reason of the invites, if any.
+    #[uniffi(default = None)]
+    pub invite_reason: Option<String>,
+    /// A template for the power levels, which can't be combined with
+    /// `power_level_content_override`.
+    #[uniffi(default = None)]
+    pub power_levels_preset: Option<PowerLevelsPreset>,
+    /// The ID of the space to add the room to.
+    #[uniffi(default = None)]
+    pub space_parent: Option<String>,
+    /// The localpart of the alias of the room.
+    #[uniffi(default = None)]
+    pub alias: Option<String>,
 }
 
-impl From<CreateRoomParameters> for create_room::v3::Request {
-    fn from(value: CreateRoomParameters) -> create_room::v3::Request {
-        let mut request = create_room::v3::Request::new();
-        request.name = value.name;
-        request.topic = value.topic;
-        request.is_direct = value.is_direct;
-        request.visibility = value.visibility.into();
-        request.preset = Some(value.preset.into());
-        request.invite = match value.invite {
-            Some(invite) => invite
-                .iter()
-                .filter_map(|user_id| match UserId::parse(user_id) {
-                    Ok(id) => Some(id),
-                    Err(e) => {
-                        error!(user_id, "Skipping invalid user ID, error: {e}");
-                        None
-                    }
-                })
-                .collect(),
-            None => vec![],
-        };
-
-        let mut initial_state: Vec<Raw<AnyInitialStateEvent>> = vec![];
+impl CreateRoomParameters {
+    fn into_builder(self, client: &MatrixClient) -> Result<RoomCreationBuilder, ClientError> {
+        let mut builder = client
+            .build_room()
+            .encrypted(self.is_encrypted)
+            .direct(self.is_direct)
+            .visibility(self.visibility.into())
+            .preset(self.preset.into());
+
+        if let Some(name) = self.name {
+            builder = builder.name(name);
+        }
+        if let Some(topic) = self.topic {
+            builder = builder.topic(topic);
+        }
+        if let Some(alias) = self.alias {
+            builder = builder.alias_localpart(alias);
+        }
+        if let Some(url) = self.avatar {
+            builder = builder.avatar_url(url.into());
+        }
 
-        if value.is_encrypted {
-            let content =
-                RoomEncryptionEventContent::new(EventEncryptionAlgorithm::MegolmV1AesSha2);
-            initial_state.push(InitialStateEvent::new(content).to_raw_any());
+        for user_id in self.invite.unwrap_or_default() {
+            let user_id = UserId::parse(user_id)?;
+            builder = match &self.invite_reason {
+                Some(reason) => builder.invite_with_reason(user_id, reason.clone()),
+                None => builder.invite(user_id),
+            };
         }
 
-        if let Some(url) = value.avatar {
-            let mut content = RoomAvatarEventContent::new();
-            content.url = Some(url.into());
-            initial_state.push(InitialStateEvent::new(content).to_raw_any());
+        if let Some(preset) = self.power_levels_preset {
+            builder = builder.power_levels_preset(preset.into());
+        }
+        if let Some(power_levels) = self.power_level_content_override {
+            let content: RoomPowerLevelsEventContent = power_levels.into();
+            builder = builder.power_level_content_override(Raw::new(&content)?);
         }
-        request.initial_state = initial_state;
 
-        if let Some(power_levels) = value.power_level_content_override {
-            match Raw::new(&power_levels.into()) {
-                Ok(power_levels) => {
-                    request.power_level_content_override = Some(power_levels);
-                }
-                Err(e) => {
-                    error!("Failed to serialize power levels, error: {e}");
-                }
-            }
+        if let Some(space_id) = self.space_parent {
+            builder = builder.space_parent(RoomId::parse(space_id)?);
         }
 
-        request
+        Ok(builder)
+    }
+}
+
+/// A template for the power levels of a new room.
+#[derive(uniffi::Enum)]
+pub enum PowerLevelsPreset {
+    /// Only moderators can send messages and invite users.
+    Announcement,
+
+    /// All members can send messages and invite users, moderators can manage
+    /// the room.
+    Community,
+}
+
+impl From<PowerLevelsPreset> for SdkPowerLevelsPreset {
+    fn from(value: PowerLevelsPreset) -> Self {
+        match value {
+            PowerLevelsPreset::Announcement => Self::Announcement,
+            PowerLevelsPreset::Community => Self::Community,
+        }
     }
 }
 
@@ -1216,6 +1900,92 @@ fn gen_transaction_id() -> String {
     TransactionId::new().to_string()
 }
 
+/// Create an observable for the progress of a media transfer, forwarding its
+/// updates to the watcher.
+fn progress_observable(
+    progress_watcher: Option<Box<dyn ProgressWatcher>>,
+) -> SharedObservable<matrix_sdk::TransmissionProgress> {
+    let progress = SharedObservable::<matrix_sdk::TransmissionProgress>::default();
+
+    if let Some(progress_watcher) = progress_watcher {
+        let mut subscriber = progress.subscribe();
+        RUNTIME.spawn(async move {
+            while let Some(progress) = subscriber.next().await {
+                progress_watcher.transmission_progress(progress.into());
+            }
+        });
+    }
+
+    progress
+}
+
+/// Wait for the task of a media transfer, which fails if it was cancelled.
+async fn join_media_transfer<T: Send + 'static>(
+    join_hdl: Arc<Mutex<JoinHandle<Result<T, ClientError>>>>,
+) -> Result<T, ClientError> {
+    RUNTIME
+        .spawn(async move { (&mut *join_hdl.lock().await).await })
+        .await
+        .unwrap()
+        .map_err(|error| ClientError::Generic { msg: error.to_string() })?
+}
+
+/// A media upload started with [`Client::start_media_upload()`].
+#[derive(uniffi::Object)]
+pub struct MediaUploadHandle {
+    join_hdl: Arc<Mutex<JoinHandle<Result<Arc<MediaSource>, ClientError>>>>,
+    abort_hdl: AbortHandle,
+}
+
+impl MediaUploadHandle {
+    fn new(join_hdl: JoinHandle<Result<Arc<MediaSource>, ClientError>>) -> Arc<Self> {
+        let abort_hdl = join_hdl.abort_handle();
+        let join_hdl = Arc::new(Mutex::new(join_hdl));
+        Arc::new(Self { join_hdl, abort_hdl })
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl MediaUploadHandle {
+    /// Wait for the upload to finish, and get the source of the uploaded
+    /// media.
+    pub async fn join(&self) -> Result<Arc<MediaSource>, ClientError> {
+        join_media_transfer(self.join_hdl.clone()).await
+    }
+
+    pub fn cancel(&self) {
+        self.abort_hdl.abort();
+    }
+}
+
+/// A media download started with [`Client::start_media_download()`] or
+/// [`Client::start_media_thumbnail_download()`].
+#[derive(uniffi::Object)]
+pub struct MediaDownloadHandle {
+    join_hdl: Arc<Mutex<JoinHandle<Result<Vec<u8>, ClientError>>>>,
+    abort_hdl: AbortHandle,
+}
+
+impl MediaDownloadHandle {
+    fn new(join_hdl: JoinHandle<Result<Vec<u8>, ClientError>>) -> Arc<Self> {
+        let abort_hdl = join_hdl.abort_handle();
+        let join_hdl = Arc::new(Mutex::new(join_hdl));
+        Arc::new(Self { join_hdl, abort_hdl })
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl MediaDownloadHandle {
+    /// Wait for the download to finish, and get the content of the media.
+    pub async fn join(&self) -> Result<Vec<u8>, ClientError> {
+        join_media_transfer(self.join_hdl.clone()).await
+    }
+
+    pub fn cancel(&self) {
+        self.abort_hdl.abort();
+    }
+}
+
 /// A file handle that takes ownership of a media file on disk. When the handle
 /// is dropped, the file will be removed from the disk.
 #[derive(uniffi::Object)]
@@ -1,6 +1,7 @@
 use std::{fs, path::PathBuf, sync::Arc};
 
 use matrix_sdk::{
+    config::RequestCategory,
     encryption::{BackupDownloadStrategy, EncryptionSettings},
     ruma::{
         api::{error::UnknownVersionError, MatrixVersion},
@@ -21,6 +22,17 @@ pub(crate) enum UrlScheme {
     Https,
 }
 
+/// A listener notified when the certificate chain of a server is rejected
+/// because none of its public keys are pinned.
+#[uniffi::export(callback_interface)]
+pub trait CertificatePinFailureListener: Send + Sync {
+    /// * `server_name` - The name of the server.
+    ///
+    /// * `pins` - The base64-encoded SHA-256 hashes of the public keys of the
+    ///   certificates presented by the server.
+    fn on_failure(&self, server_name: String, pins: Vec<String>);
+}
+
 #[derive(Clone, uniffi::Object)]
 pub struct ClientBuilder {
     base_path: Option<String>,
@@ -32,6 +44,13 @@ pub struct ClientBuilder {
     user_agent: Option<String>,
     sliding_sync_proxy: Option<String>,
     proxy: Option<String>,
+    http_proxy: Option<String>,
+    https_proxy: Option<String>,
+    proxy_credentials: Option<(String, String)>,
+    no_proxy: Vec<String>,
+    additional_root_certificates: Vec<Vec<u8>>,
+    certificate_pins: Vec<String>,
+    certificate_pin_failure_listener: Option<Arc<dyn CertificatePinFailureListener>>,
     disable_ssl_verification: bool,
     disable_automatic_token_refresh: bool,
     inner: MatrixClientBuilder,
@@ -118,6 +137,69 @@ impl ClientBuilder {
         Arc::new(builder)
     }
 
+    /// Set the proxy for the requests to `http` URLs, which takes precedence
+    /// over the one set with `proxy()`.
+    pub fn http_proxy(self: Arc<Self>, url: String) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.http_proxy = Some(url);
+        Arc::new(builder)
+    }
+
+    /// Set the proxy for the requests to `https` URLs, which takes precedence
+    /// over the one set with `proxy()`.
+    pub fn https_proxy(self: Arc<Self>, url: String) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.https_proxy = Some(url);
+        Arc::new(builder)
+    }
+
+    /// Set the credentials to authenticate with the proxies.
+    pub fn proxy_credentials(self: Arc<Self>, username: String, password: String) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.proxy_credentials = Some((username, password));
+        Arc::new(builder)
+    }
+
+    /// Set the hosts that are reached without going through the proxies, in
+    /// the format of the `NO_PROXY` environment variable.
+    pub fn no_proxy(self: Arc<Self>, hosts: Vec<String>) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.no_proxy = hosts;
+        Arc::new(builder)
+    }
+
+    /// Trust the given DER-encoded certificates, in addition to the default
+    /// root certificates.
+    pub fn add_root_certificates(self: Arc<Self>, certificates: Vec<Vec<u8>>) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.additional_root_certificates.extend(certificates);
+        Arc::new(builder)
+    }
+
+    /// Only accept the servers whose certificate chain contains one of the
+    /// given public keys.
+    ///
+    /// The pins are the base64-encoded SHA-256 hashes of the DER-encoded
+    /// `SubjectPublicKeyInfo` of the accepted certificates. Pinning is only
+    /// supported on Android, building the client fails on the other
+    /// platforms.
+    pub fn pin_certificates(self: Arc<Self>, pins: Vec<String>) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.certificate_pins.extend(pins);
+        Arc::new(builder)
+    }
+
+    /// Set a listener to notify when a server is rejected because of the
+    /// pinning, to show a security error rather than a connection one.
+    pub fn certificate_pin_failure_listener(
+        self: Arc<Self>,
+        listener: Box<dyn CertificatePinFailureListener>,
+    ) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.certificate_pin_failure_listener = Some(listener.into());
+        Arc::new(builder)
+    }
+
     pub fn disable_ssl_verification(self: Arc<Self>) -> Arc<Self> {
         let mut builder = unwrap_or_clone_arc(self);
         builder.disable_ssl_verification = true;
@@ -130,6 +212,31 @@ impl ClientBuilder {
         Arc::new(builder)
     }
 
+    /// Set the maximum number of requests that can be in flight at the same
+    /// time.
+    pub fn max_concurrent_requests(self: Arc<Self>, max: u32) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.inner = builder.inner.max_concurrent_requests(max as usize);
+        Arc::new(builder)
+    }
+
+    /// Set the maximum number of requests to the media repository that can be
+    /// in flight at the same time.
+    pub fn max_concurrent_media_requests(self: Arc<Self>, max: u32) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.inner =
+            builder.inner.max_concurrent_requests_for(RequestCategory::Media, max as usize);
+        Arc::new(builder)
+    }
+
+    /// Block the requests that would modify the state of the homeserver, while
+    /// still allowing to sync and decrypt events.
+    pub fn read_only(self: Arc<Self>) -> Arc<Self> {
+        let mut builder = unwrap_or_clone_arc(self);
+        builder.inner = builder.inner.read_only();
+        Arc::new(builder)
+    }
+
     pub fn build(self: Arc<Self>) -> Result<Arc<Client>, ClientError> {
         Ok(self.build_inner()?)
     }
@@ -200,6 +307,35 @@ impl ClientBuilder {
             inner_builder = inner_builder.proxy(proxy);
         }
 
+        if let Some(proxy) = builder.http_proxy {
+            inner_builder = inner_builder.http_proxy(proxy);
+        }
+
+        if let Some(proxy) = builder.https_proxy {
+            inner_builder = inner_builder.https_proxy(proxy);
+        }
+
+        if let Some((username, password)) = builder.proxy_credentials {
+            inner_builder = inner_builder.proxy_credentials(username, password);
+        }
+
+        if !builder.no_proxy.is_empty() {
+            inner_builder = inner_builder.no_proxy(builder.no_proxy);
+        }
+
+        if !builder.additional_root_certificates.is_empty() {
+            inner_builder =
+                inner_builder.add_root_certificates(builder.additional_root_certificates);
+        }
+
+        if !builder.certificate_pins.is_empty() {
+            inner_builder = pin_certificates(
+                inner_builder,
+                &builder.certificate_pins,
+                builder.certificate_pin_failure_listener,
+            )?;
+        }
+
         if builder.disable_ssl_verification {
             inner_builder = inner_builder.disable_ssl_verification();
         }
@@ -267,6 +403,13 @@ impl Default for ClientBuilder {
             user_agent: None,
             sliding_sync_proxy: None,
             proxy: None,
+            http_proxy: None,
+            https_proxy: None,
+            proxy_credentials: None,
+            no_proxy: Vec::new(),
+            additional_root_certificates: Vec::new(),
+            certificate_pins: Vec::new(),
+            certificate_pin_failure_listener: None,
             disable_ssl_verification: false,
             disable_automatic_token_refresh: false,
             inner,
@@ -275,3 +418,42 @@ impl Default for ClientBuilder {
         }
     }
 }
+
+/// Pinning requires the `rustls-tls` feature of the SDK, which is only enabled
+/// on Android.
+#[cfg(target_os = "android")]
+fn pin_certificates(
+    builder: MatrixClientBuilder,
+    pins: &[String],
+    failure_listener: Option<Arc<dyn CertificatePinFailureListener>>,
+) -> anyhow::Result<MatrixClientBuilder> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let pins = pins
+        .iter()
+        .map(|pin| {
+            let hash = STANDARD.decode(pin)?;
+            <[u8; 32]>::try_from(hash)
+                .map_err(|_| anyhow::anyhow!("The certificate pin {pin} isn't a SHA-256 hash"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let builder = builder.pin_certificates(pins);
+
+    Ok(match failure_listener {
+        Some(listener) => builder.on_certificate_pin_failure(move |failure| {
+            let pins = failure.hashes.iter().map(|hash| STANDARD.encode(hash)).collect();
+            listener.on_failure(failure.server_name, pins);
+        }),
+        None => builder,
+    })
+}
+
+#[cfg(not(target_os = "android"))]
+fn pin_certificates(
+    _builder: MatrixClientBuilder,
+    _pins: &[String],
+    _failure_listener: Option<Arc<dyn CertificatePinFailureListener>>,
+) -> anyhow::Result<MatrixClientBuilder> {
+    anyhow::bail!("Certificate pinning is not supported on this platform")
+}
@@ -0,0 +1,90 @@
+use std::{fmt::Debug, sync::Arc};
+
+use matrix_sdk::{
+    async_trait,
+    devices::{DeviceVerificationState as SdkDeviceVerificationState, OwnDevice as SdkOwnDevice},
+    ruma::{
+        api::client::uiaa::{AuthData, Password, UiaaInfo, UserIdentifier},
+        assign,
+    },
+    uiaa::UiaaHandler,
+};
+
+use crate::RUNTIME;
+
+/// The verification state of a device of the current user.
+#[derive(uniffi::Enum)]
+pub enum DeviceVerificationState {
+    Verified,
+    Unverified,
+    Unknown,
+}
+
+impl From<SdkDeviceVerificationState> for DeviceVerificationState {
+    fn from(value: SdkDeviceVerificationState) -> Self {
+        match value {
+            SdkDeviceVerificationState::Verified => Self::Verified,
+            SdkDeviceVerificationState::Unverified => Self::Unverified,
+            SdkDeviceVerificationState::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// A device of the current user, as listed by the homeserver.
+#[derive(uniffi::Record)]
+pub struct OwnDevice {
+    pub device_id: String,
+    pub display_name: Option<String>,
+    pub last_seen_ip: Option<String>,
+    /// When the device was last seen, in milliseconds since the Unix epoch.
+    pub last_seen_ts: Option<u64>,
+    /// Whether this is the device of the client.
+    pub is_current: bool,
+    pub verification_state: DeviceVerificationState,
+}
+
+impl From<SdkOwnDevice> for OwnDevice {
+    fn from(value: SdkOwnDevice) -> Self {
+        Self {
+            device_id: value.device_id.to_string(),
+            display_name: value.display_name,
+            last_seen_ip: value.last_seen_ip,
+            last_seen_ts: value.last_seen_ts.map(|ts| ts.0.into()),
+            is_current: value.is_current,
+            verification_state: value.verification_state.into(),
+        }
+    }
+}
+
+/// Asks the user to authenticate when the server requires it to delete
+/// devices.
+#[uniffi::export(callback_interface)]
+pub trait DeleteDevicesAuthListener: Send + Sync + Debug {
+    /// Get the password of the user, or `None` to cancel the deletion.
+    fn password_required(&self) -> Option<String>;
+}
+
+#[derive(Debug)]
+pub(crate) struct PasswordUiaaHandler {
+    pub(crate) user_id: String,
+    pub(crate) listener: Arc<dyn DeleteDevicesAuthListener>,
+}
+
+#[async_trait]
+impl UiaaHandler for PasswordUiaaHandler {
+    async fn auth_data(&self, uiaa_info: &UiaaInfo) -> Option<AuthData> {
+        let listener = self.listener.clone();
+        // This requires a prompt to the user, so use one of tokio's blocking
+        // task threads.
+        let password = RUNTIME
+            .spawn_blocking(move || listener.password_required())
+            .await
+            // propagate panics from the blocking task
+            .unwrap()?;
+
+        Some(AuthData::Password(assign!(
+            Password::new(UserIdentifier::UserIdOrLocalpart(self.user_id.clone()), password),
+            { session: uiaa_info.session.clone() }
+        )))
+    }
+}
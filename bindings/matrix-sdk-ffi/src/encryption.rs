@@ -252,26 +252,19 @@ impl Encryption {
         let backups = self.inner.backups();
         let wait_for_steady_state = backups.wait_for_steady_state();
 
-        let task = if let Some(listener) = progress_listener {
+        // The task is cancelled when it's dropped, even if this future is.
+        let _progress_task = progress_listener.map(|listener| {
             let mut progress_stream = wait_for_steady_state.subscribe_to_progress();
 
-            Some(RUNTIME.spawn(async move {
+            TaskHandle::new(RUNTIME.spawn(async move {
                 while let Some(progress) = progress_stream.next().await {
                     let Ok(progress) = progress else { continue };
                     listener.on_update(progress.into());
                 }
             }))
-        } else {
-            None
-        };
-
-        let result = wait_for_steady_state.await;
-
-        if let Some(task) = task {
-            task.abort();
-        }
+        });
 
-        Ok(result?)
+        Ok(wait_for_steady_state.await?)
     }
 
     pub async fn enable_recovery(
@@ -289,18 +282,14 @@ impl Encryption {
 
         let mut progress_stream = enable.subscribe_to_progress();
 
-        let task = RUNTIME.spawn(async move {
+        let _progress_task = TaskHandle::new(RUNTIME.spawn(async move {
             while let Some(progress) = progress_stream.next().await {
                 let Ok(progress) = progress else { continue };
                 progress_listener.on_update(progress.into());
             }
-        });
-
-        let ret = enable.await?;
-
-        task.abort();
+        }));
 
-        Ok(ret)
+        Ok(enable.await?)
     }
 
     pub async fn disable_recovery(&self) -> Result<()> {
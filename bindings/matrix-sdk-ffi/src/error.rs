@@ -151,6 +151,9 @@ pub enum NotificationSettingsError {
     /// Invalid room id.
     #[error("Invalid room ID {room_id}")]
     InvalidRoomId { room_id: String },
+    /// Invalid user id.
+    #[error("Invalid user ID {user_id}")]
+    InvalidUserId { user_id: String },
     /// Rule not found
     #[error("Rule not found: {rule_id}")]
     RuleNotFound { rule_id: String },
@@ -163,6 +166,12 @@ pub enum NotificationSettingsError {
     /// Unable to save the push rules
     #[error("Unable to save push rules")]
     UnableToSavePushRules,
+    /// Unable to save the temporary mutes
+    #[error("Unable to save temporary mutes")]
+    UnableToSaveTemporaryMutes,
+    /// Unable to save the invite notification policy
+    #[error("Unable to save invite notification policy")]
+    UnableToSaveInviteNotificationPolicy,
     /// Unable to update push rule.
     #[error("Unable to update push rule")]
     UnableToUpdatePushRule,
@@ -175,6 +184,12 @@ impl From<SdkNotificationSettingsError> for NotificationSettingsError {
             SdkNotificationSettingsError::UnableToAddPushRule => Self::UnableToAddPushRule,
             SdkNotificationSettingsError::UnableToRemovePushRule => Self::UnableToRemovePushRule,
             SdkNotificationSettingsError::UnableToSavePushRules => Self::UnableToSavePushRules,
+            SdkNotificationSettingsError::UnableToSaveTemporaryMutes => {
+                Self::UnableToSaveTemporaryMutes
+            }
+            SdkNotificationSettingsError::UnableToSaveInviteNotificationPolicy => {
+                Self::UnableToSaveInviteNotificationPolicy
+            }
             SdkNotificationSettingsError::InvalidParameter(msg) => Self::InvalidParameter { msg },
             SdkNotificationSettingsError::UnableToUpdatePushRule => Self::UnableToUpdatePushRule,
         }
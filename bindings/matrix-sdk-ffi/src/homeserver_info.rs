@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use matrix_sdk::{ruma::api::client::discovery::get_supported_versions, Client as MatrixClient};
+use serde::{de::DeserializeOwned, Deserialize};
+use tracing::debug;
+
+use crate::error::ClientError;
+
+/// What the homeserver and its domain advertise about the features they
+/// support, so the applications can adapt their interface to them.
+#[derive(Clone, uniffi::Record)]
+pub struct HomeserverInfo {
+    /// The versions of the specification supported by the homeserver.
+    pub versions: Vec<String>,
+    /// The unstable features of the homeserver, with whether they are enabled.
+    pub unstable_features: HashMap<String, bool>,
+    pub can_change_password: bool,
+    pub can_change_display_name: bool,
+    pub can_change_avatar: bool,
+    pub can_change_third_party_ids: bool,
+    /// The version of the new rooms, if they don't ask for a specific one.
+    pub default_room_version: String,
+    /// The URL of the style of the maps to display locations with.
+    pub map_style_url: Option<String>,
+    pub sliding_sync_proxy: Option<String>,
+    /// The URL of the widget to use for the calls.
+    pub call_widget_url: Option<String>,
+    /// The URL of the web page to register an account on the homeserver, if
+    /// the registration is not supported by the application itself.
+    pub registration_helper_url: Option<String>,
+}
+
+/// The fields of `/.well-known/matrix/client` which are not used by the SDK.
+#[derive(Default, Deserialize)]
+struct ClientWellKnown {
+    #[serde(rename = "m.tile_server")]
+    tile_server: Option<TileServer>,
+    #[serde(rename = "org.matrix.msc3575.proxy")]
+    sliding_sync_proxy: Option<SlidingSyncProxy>,
+}
+
+#[derive(Deserialize)]
+struct TileServer {
+    map_style_url: String,
+}
+
+#[derive(Deserialize)]
+struct SlidingSyncProxy {
+    url: String,
+}
+
+/// The content of `/.well-known/element/element.json`.
+#[derive(Default, Deserialize)]
+struct ElementWellKnown {
+    call: Option<ElementCall>,
+    registration_helper_url: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ElementCall {
+    widget_url: String,
+}
+
+impl HomeserverInfo {
+    pub(crate) async fn fetch(client: &MatrixClient) -> Result<Self, ClientError> {
+        let versions = client.send(get_supported_versions::Request::new(), None).await?;
+        let capabilities = client.get_capabilities().await?;
+
+        // The well-known files are served by the domain of the user IDs, which
+        // may not be the one of the homeserver.
+        let homeserver = client.homeserver();
+        let domain = match client.user_id() {
+            Some(user_id) => user_id.server_name().to_string(),
+            None => homeserver.host_str().unwrap_or_default().to_owned(),
+        };
+        let base_url = format!("{}://{domain}/.well-known", homeserver.scheme());
+
+        let http_client = reqwest::Client::new();
+        let client_well_known: ClientWellKnown =
+            fetch_well_known(&http_client, format!("{base_url}/matrix/client")).await;
+        let element_well_known: ElementWellKnown =
+            fetch_well_known(&http_client, format!("{base_url}/element/element.json")).await;
+
+        Ok(Self {
+            versions: versions.versions,
+            unstable_features: versions.unstable_features.into_iter().collect(),
+            can_change_password: capabilities.change_password.enabled,
+            can_change_display_name: capabilities.set_displayname.enabled,
+            can_change_avatar: capabilities.set_avatar_url.enabled,
+            can_change_third_party_ids: capabilities.thirdparty_id_changes.enabled,
+            default_room_version: capabilities.room_versions.default.to_string(),
+            map_style_url: client_well_known.tile_server.map(|server| server.map_style_url),
+            sliding_sync_proxy: client_well_known.sliding_sync_proxy.map(|proxy| proxy.url),
+            call_widget_url: element_well_known.call.map(|call| call.widget_url),
+            registration_helper_url: element_well_known.registration_helper_url,
+        })
+    }
+}
+
+/// Fetch a well-known file.
+///
+/// The well-known files are optional, so a missing or invalid file is
+/// treated like an empty one.
+async fn fetch_well_known<T: DeserializeOwned + Default>(
+    http_client: &reqwest::Client,
+    url: String,
+) -> T {
+    let response = match http_client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            debug!("Couldn't fetch {url}: {}", response.status());
+            return T::default();
+        }
+        Err(error) => {
+            debug!("Couldn't fetch {url}: {error}");
+            return T::default();
+        }
+    };
+
+    match response.bytes().await.map(|body| serde_json::from_slice(&body)) {
+        Ok(Ok(content)) => content,
+        Ok(Err(error)) => {
+            debug!("Invalid content in {url}: {error}");
+            T::default()
+        }
+        Err(error) => {
+            debug!("Couldn't fetch {url}: {error}");
+            T::default()
+        }
+    }
+}
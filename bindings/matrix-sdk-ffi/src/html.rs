@@ -0,0 +1,63 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::html::{self, HtmlSanitizerMode as SdkHtmlSanitizerMode, RemoveReplyFallback};
+
+/// The mode of the HTML sanitizer.
+#[derive(uniffi::Enum)]
+pub enum HtmlSanitizerMode {
+    /// Only the elements and attributes recommended by the spec are kept.
+    Strict,
+
+    /// Like `Strict`, but the deprecated elements and attributes that are
+    /// still used by some clients are kept too.
+    Compat,
+}
+
+impl From<HtmlSanitizerMode> for SdkHtmlSanitizerMode {
+    fn from(value: HtmlSanitizerMode) -> Self {
+        match value {
+            HtmlSanitizerMode::Strict => Self::Strict,
+            HtmlSanitizerMode::Compat => Self::Compat,
+        }
+    }
+}
+
+/// Sanitize the given HTML with the same sanitizer as the rest of the SDK.
+#[uniffi::export]
+pub fn sanitize_html(html: String, mode: HtmlSanitizerMode, remove_reply_fallback: bool) -> String {
+    let remove_reply_fallback =
+        if remove_reply_fallback { RemoveReplyFallback::Yes } else { RemoveReplyFallback::No };
+    html::sanitize_html(&html, mode.into(), remove_reply_fallback)
+}
+
+/// Convert the given Markdown to sanitized HTML.
+///
+/// Returns `None` if the Markdown doesn't contain any formatting.
+#[uniffi::export]
+pub fn markdown_to_html(markdown: String) -> Option<String> {
+    html::markdown_to_html(&markdown)
+}
+
+/// Convert the given plain text to HTML.
+#[uniffi::export]
+pub fn plain_text_to_html(text: String) -> String {
+    html::plain_text_to_html(&text)
+}
+
+/// Convert the given HTML to plain text.
+#[uniffi::export]
+pub fn html_to_plain_text(html: String) -> String {
+    html::html_to_plain_text(&html)
+}
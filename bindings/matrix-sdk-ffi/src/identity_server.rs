@@ -0,0 +1,74 @@
+use matrix_sdk::{
+    identity_server::{
+        IdentityServer as SdkIdentityServer, IdentityServerPolicy as SdkIdentityServerPolicy,
+    },
+    room::PendingThirdPartyInvite as SdkPendingThirdPartyInvite,
+};
+
+use crate::error::ClientError;
+
+/// An identity server, used to invite users to rooms by email.
+#[derive(uniffi::Object)]
+pub struct IdentityServer {
+    pub(crate) inner: SdkIdentityServer,
+}
+
+impl IdentityServer {
+    pub(crate) fn new(inner: SdkIdentityServer) -> Self {
+        Self { inner }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl IdentityServer {
+    pub fn base_url(&self) -> String {
+        self.inner.base_url().to_string()
+    }
+
+    /// Get the policies that the user must accept before using the identity
+    /// server, in the given language if available.
+    pub async fn policies(
+        &self,
+        language: Option<String>,
+    ) -> Result<Vec<IdentityServerPolicy>, ClientError> {
+        let policies = self.inner.policies(language.as_deref()).await?;
+        Ok(policies.into_iter().map(Into::into).collect())
+    }
+
+    /// Accept the policies with the given URLs on behalf of the user.
+    pub async fn accept_policies(&self, urls: Vec<String>) -> Result<(), ClientError> {
+        Ok(self.inner.accept_policies(urls).await?)
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct IdentityServerPolicy {
+    pub id: String,
+    pub version: String,
+    pub name: String,
+    pub url: String,
+}
+
+impl From<SdkIdentityServerPolicy> for IdentityServerPolicy {
+    fn from(value: SdkIdentityServerPolicy) -> Self {
+        Self { id: value.id, version: value.version, name: value.name, url: value.url }
+    }
+}
+
+/// An invite to an email address that was not accepted yet.
+#[derive(uniffi::Record)]
+pub struct PendingThirdPartyInvite {
+    pub token: String,
+    pub display_name: String,
+    pub sender: String,
+}
+
+impl From<SdkPendingThirdPartyInvite> for PendingThirdPartyInvite {
+    fn from(value: SdkPendingThirdPartyInvite) -> Self {
+        Self {
+            token: value.token,
+            display_name: value.display_name,
+            sender: value.sender.to_string(),
+        }
+    }
+}
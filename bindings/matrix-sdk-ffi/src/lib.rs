@@ -21,26 +21,38 @@ macro_rules! unwrap_or_clone_arc_into_variant {
 }
 
 mod authentication_service;
+mod call_signaling;
 mod chunk_iterator;
 mod client;
 mod client_builder;
+mod devices;
 mod encryption;
 mod error;
 mod event;
 mod helpers;
+mod homeserver_info;
+mod html;
+mod identity_server;
+mod message_search;
 mod notification;
 mod notification_settings;
 mod platform;
+mod power_levels;
+mod rageshake;
 mod room;
+mod room_directory_search;
 mod room_info;
 mod room_list;
 mod room_member;
+mod room_preview;
 mod ruma;
 mod session_verification;
+mod space;
 mod sync_service;
 mod task_handle;
 mod timeline;
 mod tracing;
+mod uri;
 mod utils;
 mod widget;
 
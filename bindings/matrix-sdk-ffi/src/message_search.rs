@@ -0,0 +1,102 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::message_search as sdk;
+
+#[derive(uniffi::Enum)]
+pub enum SearchOrder {
+    Rank,
+    Recent,
+}
+
+impl From<SearchOrder> for sdk::SearchOrder {
+    fn from(value: SearchOrder) -> Self {
+        match value {
+            SearchOrder::Rank => Self::Rank,
+            SearchOrder::Recent => Self::Recent,
+        }
+    }
+}
+
+/// A byte range of the text of a snippet.
+#[derive(uniffi::Record)]
+pub struct HighlightRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(uniffi::Record)]
+pub struct MessageSearchResult {
+    pub room_id: String,
+    pub event_id: String,
+    pub sender: String,
+    pub timestamp: u64,
+    pub rank: Option<f64>,
+    pub snippet: Option<String>,
+    pub highlights: Vec<HighlightRange>,
+}
+
+impl From<sdk::MessageSearchResult> for MessageSearchResult {
+    fn from(value: sdk::MessageSearchResult) -> Self {
+        let (snippet, highlights) = match value.snippet {
+            Some(snippet) => (
+                Some(snippet.text),
+                snippet
+                    .highlights
+                    .into_iter()
+                    .map(|range| HighlightRange {
+                        start: range.start as u64,
+                        end: range.end as u64,
+                    })
+                    .collect(),
+            ),
+            None => (None, Vec::new()),
+        };
+
+        Self {
+            room_id: value.room_id.to_string(),
+            event_id: value.event_id.to_string(),
+            sender: value.sender.to_string(),
+            timestamp: value.origin_server_ts.0.into(),
+            rank: value.rank,
+            snippet,
+            highlights,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct MessageSearchResponse {
+    pub results: Vec<MessageSearchResult>,
+    pub count: Option<u64>,
+    pub next_batch: Option<String>,
+    /// The encrypted rooms that were part of the search, whose messages can't
+    /// be searched by the server.
+    pub unsearched_encrypted_rooms: Vec<String>,
+}
+
+impl From<sdk::MessageSearchResponse> for MessageSearchResponse {
+    fn from(value: sdk::MessageSearchResponse) -> Self {
+        Self {
+            results: value.results.into_iter().map(Into::into).collect(),
+            count: value.count,
+            next_batch: value.next_batch,
+            unsearched_encrypted_rooms: value
+                .unsearched_encrypted_rooms
+                .into_iter()
+                .map(|room_id| room_id.to_string())
+                .collect(),
+        }
+    }
+}
@@ -1,14 +1,17 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use matrix_sdk_ui::notification_client::{
     NotificationClient as MatrixNotificationClient,
     NotificationClientBuilder as MatrixNotificationClientBuilder,
-    NotificationItem as MatrixNotificationItem, NotificationProcessSetup,
+    NotificationFileInfo as MatrixNotificationFileInfo, NotificationItem as MatrixNotificationItem,
+    NotificationProcessSetup, NotificationThumbnail as MatrixNotificationThumbnail,
 };
-use ruma::{EventId, RoomId};
+use ruma::{EventId, OwnedEventId, OwnedRoomId, RoomId};
+use tracing::warn;
 
 use crate::{
-    client::Client, error::ClientError, event::TimelineEvent, helpers::unwrap_or_clone_arc, RUNTIME,
+    client::Client, error::ClientError, event::TimelineEvent, helpers::unwrap_or_clone_arc,
+    room::IncomingCall, RUNTIME,
 };
 
 #[derive(uniffi::Enum)]
@@ -33,6 +36,30 @@ pub struct NotificationRoomInfo {
     pub is_direct: bool,
 }
 
+#[derive(uniffi::Record)]
+pub struct NotificationThumbnail {
+    pub data: Vec<u8>,
+    pub mimetype: Option<String>,
+}
+
+impl From<MatrixNotificationThumbnail> for NotificationThumbnail {
+    fn from(value: MatrixNotificationThumbnail) -> Self {
+        Self { data: value.data, mimetype: value.mimetype }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct NotificationFileInfo {
+    pub filename: String,
+    pub size: Option<u64>,
+}
+
+impl From<MatrixNotificationFileInfo> for NotificationFileInfo {
+    fn from(value: MatrixNotificationFileInfo) -> Self {
+        Self { filename: value.filename, size: value.size }
+    }
+}
+
 #[derive(uniffi::Record)]
 pub struct NotificationItem {
     pub event: NotificationEvent,
@@ -45,6 +72,15 @@ pub struct NotificationItem {
     /// information to create a push context.
     pub is_noisy: Option<bool>,
     pub has_mention: Option<bool>,
+
+    /// A thumbnail of the image, video or sticker of the event, if it could be
+    /// fetched within the size limit of the client.
+    pub thumbnail: Option<NotificationThumbnail>,
+    pub file_info: Option<NotificationFileInfo>,
+    pub poll_question: Option<String>,
+    /// The call the event rings for, if any. The caller is the sender of the
+    /// event.
+    pub incoming_call: Option<IncomingCall>,
 }
 
 impl NotificationItem {
@@ -74,6 +110,10 @@ impl NotificationItem {
             },
             is_noisy: item.is_noisy,
             has_mention: item.has_mention,
+            thumbnail: item.thumbnail.map(Into::into),
+            file_info: item.file_info.map(Into::into),
+            poll_question: item.poll_question,
+            incoming_call: item.incoming_call.map(Into::into),
         }
     }
 }
@@ -106,6 +146,22 @@ impl NotificationClientBuilder {
         Arc::new(Self { builder, client: this.client })
     }
 
+    /// Set the maximum size of the thumbnails fetched for the notifications, in
+    /// bytes. Set it to 0 to never fetch thumbnails.
+    pub fn max_thumbnail_size(self: Arc<Self>, max_size: u64) -> Arc<Self> {
+        let this = unwrap_or_clone_arc(self);
+        let builder = this.builder.max_thumbnail_size(max_size);
+        Arc::new(Self { builder, client: this.client })
+    }
+
+    /// Set the maximum duration of the download of the thumbnails fetched for
+    /// the notifications, in milliseconds.
+    pub fn thumbnail_timeout_ms(self: Arc<Self>, timeout_ms: u64) -> Arc<Self> {
+        let this = unwrap_or_clone_arc(self);
+        let builder = this.builder.thumbnail_timeout(Duration::from_millis(timeout_ms));
+        Arc::new(Self { builder, client: this.client })
+    }
+
     pub fn finish(self: Arc<Self>) -> Arc<NotificationClient> {
         let this = unwrap_or_clone_arc(self);
         Arc::new(NotificationClient { inner: this.builder.build(), _client: this.client })
@@ -148,4 +204,49 @@ impl NotificationClient {
             }
         })
     }
+    /// Get several notifications at once, given the IDs of the events for each
+    /// room.
+    ///
+    /// The result is keyed by event ID. It only contains the notifications
+    /// which could be resolved and weren't filtered out by the push rules.
+    ///
+    /// See also documentation of
+    /// `MatrixNotificationClient::get_notifications`.
+    pub fn get_notifications(
+        &self,
+        room_to_event_ids: HashMap<String, Vec<String>>,
+    ) -> Result<HashMap<String, NotificationItem>, ClientError> {
+        let room_to_event_ids = room_to_event_ids
+            .into_iter()
+            .map(|(room_id, event_ids)| {
+                let room_id = OwnedRoomId::try_from(room_id)?;
+                let event_ids = event_ids
+                    .into_iter()
+                    .map(OwnedEventId::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((room_id, event_ids))
+            })
+            .collect::<Result<HashMap<_, _>, ClientError>>()?;
+
+        RUNTIME.block_on(async move {
+            let results = self
+                .inner
+                .get_notifications(&room_to_event_ids)
+                .await
+                .map_err(ClientError::from)?;
+
+            Ok(results
+                .into_iter()
+                .filter_map(|(event_id, result)| match result {
+                    Ok(item) => {
+                        item.map(|item| (event_id.to_string(), NotificationItem::from_inner(item)))
+                    }
+                    Err(error) => {
+                        warn!("Couldn't resolve the notification for {event_id}: {error}");
+                        None
+                    }
+                })
+                .collect())
+        })
+    }
 }
@@ -1,22 +1,26 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
 
 use matrix_sdk::{
-    event_handler::EventHandlerHandle,
     notification_settings::{
+        InviteNotificationPolicy as SdkInviteNotificationPolicy,
         NotificationSettings as SdkNotificationSettings,
         RoomNotificationMode as SdkRoomNotificationMode,
+        RoomNotificationSettings as SdkRoomNotificationSettings,
     },
     ruma::events::push_rules::PushRulesEvent,
     Client as MatrixClient,
 };
 use ruma::{
     push::{PredefinedOverrideRuleId, PredefinedUnderrideRuleId, RuleKind},
-    RoomId,
+    MilliSecondsSinceUnixEpoch, RoomId, UInt, UserId,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 
 use super::RUNTIME;
-use crate::error::NotificationSettingsError;
+use crate::{error::NotificationSettingsError, TaskHandle};
 
 /// Enum representing the push notification modes for a room.
 #[derive(Clone, uniffi::Enum)]
@@ -49,6 +53,38 @@ impl From<RoomNotificationMode> for SdkRoomNotificationMode {
     }
 }
 
+/// Which invites generate notifications.
+#[derive(Clone, uniffi::Enum)]
+pub enum InviteNotificationPolicy {
+    /// Notify for all the invites.
+    Always,
+    /// Only notify for the invites sent by users sharing a room with the
+    /// current user.
+    KnownUsersOnly,
+    /// Never notify for invites.
+    Never,
+}
+
+impl From<SdkInviteNotificationPolicy> for InviteNotificationPolicy {
+    fn from(value: SdkInviteNotificationPolicy) -> Self {
+        match value {
+            SdkInviteNotificationPolicy::Always => Self::Always,
+            SdkInviteNotificationPolicy::KnownUsersOnly => Self::KnownUsersOnly,
+            SdkInviteNotificationPolicy::Never => Self::Never,
+        }
+    }
+}
+
+impl From<InviteNotificationPolicy> for SdkInviteNotificationPolicy {
+    fn from(value: InviteNotificationPolicy) -> Self {
+        match value {
+            InviteNotificationPolicy::Always => Self::Always,
+            InviteNotificationPolicy::KnownUsersOnly => Self::KnownUsersOnly,
+            InviteNotificationPolicy::Never => Self::Never,
+        }
+    }
+}
+
 /// Delegate to notify of changes in push rules
 #[uniffi::export(callback_interface)]
 pub trait NotificationSettingsDelegate: Sync + Send {
@@ -62,11 +98,19 @@ pub struct RoomNotificationSettings {
     mode: RoomNotificationMode,
     /// Whether the mode is the default one
     is_default: bool,
+    /// The sound of the notifications, if it is not the default one
+    sound: Option<String>,
 }
 
 impl RoomNotificationSettings {
-    fn new(mode: RoomNotificationMode, is_default: bool) -> Self {
-        RoomNotificationSettings { mode, is_default }
+    fn new(mode: RoomNotificationMode, is_default: bool, sound: Option<String>) -> Self {
+        RoomNotificationSettings { mode, is_default, sound }
+    }
+}
+
+impl From<SdkRoomNotificationSettings> for RoomNotificationSettings {
+    fn from(value: SdkRoomNotificationSettings) -> Self {
+        Self::new(value.mode.into(), value.is_default, value.sound)
     }
 }
 
@@ -74,7 +118,11 @@ impl RoomNotificationSettings {
 pub struct NotificationSettings {
     sdk_client: MatrixClient,
     sdk_notification_settings: Arc<RwLock<SdkNotificationSettings>>,
-    pushrules_event_handler: Arc<RwLock<Option<EventHandlerHandle>>>,
+    /// The task notifying the current delegate, if it is still running.
+    delegate_task: Arc<Mutex<Weak<TaskHandle>>>,
+    /// The task restoring the notification mode of the rooms muted
+    /// temporarily when their mutes expire.
+    _temporary_mutes_task: Arc<TaskHandle>,
 }
 
 impl NotificationSettings {
@@ -82,51 +130,60 @@ impl NotificationSettings {
         sdk_client: MatrixClient,
         sdk_notification_settings: SdkNotificationSettings,
     ) -> Self {
+        let temporary_mutes_task = RUNTIME.spawn({
+            let sdk_notification_settings = sdk_notification_settings.clone();
+            async move { sdk_notification_settings.watch_temporary_mutes().await }
+        });
+
         let sdk_notification_settings = Arc::new(RwLock::new(sdk_notification_settings));
         Self {
             sdk_client,
             sdk_notification_settings,
-            pushrules_event_handler: Arc::new(RwLock::new(None)),
+            delegate_task: Default::default(),
+            _temporary_mutes_task: Arc::new(TaskHandle::new(temporary_mutes_task)),
         }
     }
 }
 
-impl Drop for NotificationSettings {
-    fn drop(&mut self) {
-        // Remove the event handler on the sdk_client.
-        RUNTIME.block_on(async move {
-            if let Some(event_handler) = self.pushrules_event_handler.read().await.as_ref() {
-                self.sdk_client.remove_event_handler(event_handler.clone());
-            }
-        });
-    }
-}
-
 #[uniffi::export(async_runtime = "tokio")]
 impl NotificationSettings {
-    pub fn set_delegate(&self, delegate: Option<Box<dyn NotificationSettingsDelegate>>) {
-        if let Some(delegate) = delegate {
-            let delegate: Arc<dyn NotificationSettingsDelegate> = Arc::from(delegate);
-
-            // Add an event handler to listen to `PushRulesEvent`
-            let event_handler =
-                self.sdk_client.add_event_handler(move |_: PushRulesEvent| async move {
-                    delegate.settings_did_change();
-                });
-
-            RUNTIME.block_on(async move {
-                *self.pushrules_event_handler.write().await = Some(event_handler);
-            });
-        } else {
-            // Remove the event handler if there is no delegate
-            RUNTIME.block_on(async move {
-                let event_handler = &mut *self.pushrules_event_handler.write().await;
-                if let Some(event_handler) = event_handler {
-                    self.sdk_client.remove_event_handler(event_handler.clone());
-                }
-                *event_handler = None;
-            });
+    /// Set the delegate notified when the notification settings change.
+    ///
+    /// The delegate is notified until the returned task handle is cancelled
+    /// or dropped, or until another delegate is set.
+    pub fn set_delegate(
+        &self,
+        delegate: Option<Box<dyn NotificationSettingsDelegate>>,
+    ) -> Option<Arc<TaskHandle>> {
+        let mut delegate_task = self.delegate_task.lock().unwrap();
+
+        if let Some(task) = delegate_task.upgrade() {
+            task.cancel();
         }
+        *delegate_task = Weak::new();
+
+        let delegate = delegate?;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        // Listen to `PushRulesEvent`, until the task is cancelled.
+        let event_handler = self.sdk_client.add_event_handler(move |_: PushRulesEvent| {
+            let sender = sender.clone();
+            async move {
+                _ = sender.send(());
+            }
+        });
+        let drop_guard = self.sdk_client.event_handler_drop_guard(event_handler);
+
+        let task = Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            let _drop_guard = drop_guard;
+
+            while receiver.recv().await.is_some() {
+                delegate.settings_did_change();
+            }
+        })));
+
+        *delegate_task = Arc::downgrade(&task);
+        Some(task)
     }
 
     /// Get the notification settings for a room.
@@ -150,7 +207,8 @@ impl NotificationSettings {
         if let Some(mode) =
             notification_settings.get_user_defined_room_notification_mode(&parsed_room_id).await
         {
-            return Ok(RoomNotificationSettings::new(mode.into(), false));
+            let sound = notification_settings.get_room_sound(&parsed_room_id).await;
+            return Ok(RoomNotificationSettings::new(mode.into(), false, sound));
         }
 
         // If the user has not defined a notification mode, return the default one for
@@ -158,7 +216,38 @@ impl NotificationSettings {
         let mode = notification_settings
             .get_default_room_notification_mode(is_encrypted.into(), is_one_to_one.into())
             .await;
-        Ok(RoomNotificationSettings::new(mode.into(), true))
+        Ok(RoomNotificationSettings::new(mode.into(), true, None))
+    }
+
+    /// Get the notification settings of many rooms at once, e.g. for a room
+    /// list.
+    ///
+    /// Whether a room is encrypted and a direct chat involving two people is
+    /// taken from the local state of the room. The rooms without a
+    /// user-defined mode that are unknown to the client are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_ids` - the IDs of the rooms
+    pub async fn get_room_notification_modes(
+        &self,
+        room_ids: Vec<String>,
+    ) -> Result<HashMap<String, RoomNotificationSettings>, NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_room_ids = room_ids
+            .into_iter()
+            .map(|room_id| {
+                RoomId::parse(&room_id)
+                    .map_err(|_e| NotificationSettingsError::InvalidRoomId { room_id })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(notification_settings
+            .get_room_notification_modes(&parsed_room_ids)
+            .await
+            .into_iter()
+            .map(|(room_id, settings)| (room_id.to_string(), settings.into()))
+            .collect())
     }
 
     /// Set the notification mode for a room.
@@ -174,6 +263,66 @@ impl NotificationSettings {
         Ok(())
     }
 
+    /// Set the sound of the notifications of a room, or restore the default
+    /// sound with `None`.
+    ///
+    /// A room using the default mode is switched to the `AllMessages` mode.
+    /// Setting a sound for a room with another user-defined mode fails.
+    pub async fn set_room_sound(
+        &self,
+        room_id: String,
+        sound: Option<String>,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_room_id = RoomId::parse(&room_id)
+            .map_err(|_e| NotificationSettingsError::InvalidRoomId { room_id })?;
+        notification_settings.set_room_sound(&parsed_room_id, sound).await?;
+        Ok(())
+    }
+
+    /// Mute a room until the given time.
+    ///
+    /// When the mute expires, the mode the room had before is restored, and
+    /// the delegate is notified once the server has acknowledged the change.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - the room to mute
+    /// * `expires_at` - when the mute should expire, in milliseconds since the
+    ///   Unix epoch
+    pub async fn mute_room_until(
+        &self,
+        room_id: String,
+        expires_at: u64,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_room_id = RoomId::parse(&room_id)
+            .map_err(|_e| NotificationSettingsError::InvalidRoomId { room_id })?;
+        let expires_at =
+            UInt::new(expires_at).ok_or_else(|| NotificationSettingsError::InvalidParameter {
+                msg: "the expiry time is out of range".to_owned(),
+            })?;
+        notification_settings
+            .mute_room_until(&parsed_room_id, MilliSecondsSinceUnixEpoch(expires_at))
+            .await?;
+        Ok(())
+    }
+
+    /// Get when the mute of a room expires, in milliseconds since the Unix
+    /// epoch, if it is muted temporarily.
+    pub async fn get_room_mute_expiry(
+        &self,
+        room_id: String,
+    ) -> Result<Option<u64>, NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_room_id = RoomId::parse(&room_id)
+            .map_err(|_e| NotificationSettingsError::InvalidRoomId { room_id })?;
+        Ok(notification_settings
+            .get_room_mute_expiry(&parsed_room_id)
+            .await
+            .map(|expires_at| expires_at.0.into()))
+    }
+
     /// Get the user defined room notification mode
     pub async fn get_user_defined_room_notification_mode(
         &self,
@@ -263,6 +412,129 @@ impl NotificationSettings {
         notification_settings.contains_keyword_rules().await
     }
 
+    /// Get the keywords for which an enabled rule exists.
+    pub async fn get_keywords(&self) -> Vec<String> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.enabled_keywords().await.into_iter().collect()
+    }
+
+    /// Add or enable a rule for the given keyword.
+    ///
+    /// The delegate is notified once the server has acknowledged the change.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyword` - the keyword to match
+    pub async fn add_keyword(&self, keyword: String) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.add_keyword(keyword).await?;
+        Ok(())
+    }
+
+    /// Remove the rules for the given keyword.
+    ///
+    /// The delegate is notified once the server has acknowledged the change.
+    ///
+    /// # Arguments
+    ///
+    /// * `keyword` - the keyword to unmatch
+    pub async fn remove_keyword(&self, keyword: String) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.remove_keyword(&keyword).await?;
+        Ok(())
+    }
+
+    /// Get the users whose events are all muted.
+    pub async fn get_muted_users(&self) -> Vec<String> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.muted_users().await.into_iter().map(|u| u.to_string()).collect()
+    }
+
+    /// Get whether the events sent by a user are all muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - the sender of the events
+    pub async fn is_user_muted(&self, user_id: String) -> Result<bool, NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_user_id = UserId::parse(&user_id)
+            .map_err(|_e| NotificationSettingsError::InvalidUserId { user_id })?;
+        Ok(notification_settings.is_user_muted(&parsed_user_id).await)
+    }
+
+    /// Set whether the events sent by a user are all muted, including the ones
+    /// mentioning the current user.
+    ///
+    /// Contrary to ignoring the user, the events are still displayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - the sender of the events
+    /// * `muted` - whether the events should be muted
+    pub async fn set_user_muted(
+        &self,
+        user_id: String,
+        muted: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_user_id = UserId::parse(&user_id)
+            .map_err(|_e| NotificationSettingsError::InvalidUserId { user_id })?;
+        notification_settings.set_user_muted(&parsed_user_id, muted).await?;
+        Ok(())
+    }
+
+    /// Get whether the mentions of the current user sent by a user are muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - the sender of the mentions
+    pub async fn is_user_mention_muted(
+        &self,
+        user_id: String,
+    ) -> Result<bool, NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_user_id = UserId::parse(&user_id)
+            .map_err(|_e| NotificationSettingsError::InvalidUserId { user_id })?;
+        Ok(notification_settings.is_user_mention_muted(&parsed_user_id).await)
+    }
+
+    /// Set whether the mentions of the current user sent by a user are muted.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - the sender of the mentions
+    /// * `muted` - whether the mentions should be muted
+    pub async fn set_user_mention_mute(
+        &self,
+        user_id: String,
+        muted: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        let parsed_user_id = UserId::parse(&user_id)
+            .map_err(|_e| NotificationSettingsError::InvalidUserId { user_id })?;
+        notification_settings.set_user_mention_mute(&parsed_user_id, muted).await?;
+        Ok(())
+    }
+
+    /// Get which invites generate notifications.
+    pub async fn get_invite_notification_policy(&self) -> InviteNotificationPolicy {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.invite_notification_policy().await.into()
+    }
+
+    /// Set which invites generate notifications.
+    ///
+    /// Restricting the notifications to known users is only applied by a
+    /// `NotificationClient` filtering by push rules.
+    pub async fn set_invite_notification_policy(
+        &self,
+        policy: InviteNotificationPolicy,
+    ) -> Result<(), NotificationSettingsError> {
+        let notification_settings = self.sdk_notification_settings.read().await;
+        notification_settings.set_invite_notification_policy(policy.into()).await?;
+        Ok(())
+    }
+
     /// Get whether room mentions are enabled.
     pub async fn is_room_mention_enabled(&self) -> Result<bool, NotificationSettingsError> {
         let notification_settings = self.sdk_notification_settings.read().await;
@@ -1,21 +1,43 @@
-use std::{collections::HashMap, fmt::Debug, pin::Pin};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    pin::Pin,
+    sync::Mutex,
+};
 
+use anyhow::Context as _;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use futures_core::future::BoxFuture;
+use once_cell::sync::OnceCell;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::{Protocol, WithExportConfig};
 use opentelemetry_sdk::{runtime::RuntimeChannel, trace::Tracer, Resource};
 use tokio::runtime::Handle;
+use tracing::error;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_core::Subscriber;
 use tracing_subscriber::{
-    fmt::{self, time::FormatTime, FormatEvent, FormatFields, FormattedFields},
+    fmt::{
+        self, time::FormatTime, writer::BoxMakeWriter, FormatEvent, FormatFields, FormattedFields,
+    },
     layer::SubscriberExt,
     registry::LookupSpan,
     util::SubscriberInitExt,
     EnvFilter, Layer,
 };
 
-use crate::RUNTIME;
+use crate::{tracing::LogLevel, RUNTIME};
+
+/// The number of log files that are kept by default when they are rotated by
+/// size.
+const DEFAULT_MAX_LOG_FILES: usize = 10;
+
+/// The directory and the prefix of the log files, once file logging is set
+/// up.
+static LOG_FILES_LOCATION: OnceCell<(PathBuf, String)> = OnceCell::new();
 
 #[derive(Clone, Debug)]
 struct TokioRuntime {
@@ -95,7 +117,12 @@ pub fn log_panics() {
     log_panics::init();
 }
 
-fn text_layers<S>(config: TracingConfiguration) -> impl Layer<S>
+/// Build the layers writing the logs as text.
+///
+/// If the log files can't be opened, the logs are written to the standard
+/// output or the system logs instead, and the error is returned so it can be
+/// logged once the subscriber is set up.
+fn text_layers<S>(config: TracingConfiguration) -> (impl Layer<S>, Option<anyhow::Error>)
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
@@ -201,19 +228,29 @@ where
         }
     }
 
-    let file_layer = config.write_to_files.map(|c| {
-        fmt::layer()
-            .event_format(EventFormatter::new())
-            // EventFormatter doesn't support ANSI colors anyways, but the
-            // default field formatter does, which is unhelpful for iOS +
-            // Android logs, but enabled by default.
-            .with_ansi(false)
-            .with_writer(tracing_appender::rolling::hourly(c.path, c.file_prefix))
+    let mut write_to_stdout_or_system = config.write_to_stdout_or_system;
+    let mut file_error = None;
+
+    let file_layer = config.write_to_files.and_then(|c| match file_writer(c) {
+        Ok(writer) => Some(
+            fmt::layer()
+                .event_format(EventFormatter::new())
+                // EventFormatter doesn't support ANSI colors anyways, but the
+                // default field formatter does, which is unhelpful for iOS +
+                // Android logs, but enabled by default.
+                .with_ansi(false)
+                .with_writer(writer),
+        ),
+        Err(error) => {
+            write_to_stdout_or_system = true;
+            file_error = Some(error);
+            None
+        }
     });
 
-    Layer::and_then(
+    let layers = Layer::and_then(
         file_layer,
-        config.write_to_stdout_or_system.then(|| {
+        write_to_stdout_or_system.then(|| {
             #[cfg(not(target_os = "android"))]
             return fmt::layer()
                 .event_format(EventFormatter::new())
@@ -230,18 +267,189 @@ where
                     "org.matrix.rust.sdk".to_owned(),
                 ));
         }),
-    )
+    );
+
+    (layers, file_error)
+}
+
+fn file_writer(config: TracingFileConfiguration) -> anyhow::Result<BoxMakeWriter> {
+    let path = PathBuf::from(config.path);
+    let max_files = config.max_files.filter(|max_files| *max_files > 0).map(|n| n as usize);
+    let file_prefix = config.file_prefix.clone();
+
+    let writer = match config.max_file_size {
+        Some(max_file_size) => {
+            let file = SizeRollingFile::new(
+                path.clone(),
+                config.file_prefix,
+                max_file_size,
+                max_files.unwrap_or(DEFAULT_MAX_LOG_FILES),
+            )
+            .context("Couldn't open the log file")?;
+
+            BoxMakeWriter::new(Mutex::new(file))
+        }
+        None => {
+            let mut builder = RollingFileAppender::builder()
+                .rotation(Rotation::HOURLY)
+                .filename_prefix(config.file_prefix);
+
+            if let Some(max_files) = max_files {
+                builder = builder.max_log_files(max_files);
+            }
+
+            BoxMakeWriter::new(
+                builder.build(&path).context("Couldn't create the log file appender")?,
+            )
+        }
+    };
+
+    _ = LOG_FILES_LOCATION.set((path, file_prefix));
+
+    Ok(writer)
+}
+
+/// A log file which is rotated when it reaches a maximum size.
+///
+/// The logs are written to the `{prefix}` file, the previous files are
+/// renamed to `{prefix}.1`, `{prefix}.2`, and so on, up to the maximum number
+/// of files.
+struct SizeRollingFile {
+    directory: PathBuf,
+    prefix: String,
+    max_file_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRollingFile {
+    fn new(
+        directory: PathBuf,
+        prefix: String,
+        max_file_size: u64,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&directory)?;
+
+        let file = OpenOptions::new().create(true).append(true).open(directory.join(&prefix))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self { directory, prefix, max_file_size, max_files, file, size })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.directory.join(&self.prefix)
+        } else {
+            self.directory.join(format!("{}.{index}", self.prefix))
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        // Make room for the current file by dropping the oldest one.
+        _ = fs::remove_file(self.path(self.max_files - 1));
+
+        for index in (0..self.max_files - 1).rev() {
+            let path = self.path(index);
+            if path.exists() {
+                fs::rename(path, self.path(index + 1))?;
+            }
+        }
+
+        self.file =
+            OpenOptions::new().create(true).write(true).truncate(true).open(self.path(0))?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_file_size {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Build the filter of the events, from the generic filter and the levels of
+/// specific targets.
+fn env_filter(filter: &str, target_levels: &HashMap<String, LogLevel>) -> EnvFilter {
+    let directives: Vec<_> = std::iter::once(filter.to_owned())
+        .chain(
+            target_levels
+                .iter()
+                .map(|(target, level)| format!("{target}={}", level.to_tracing_level())),
+        )
+        .filter(|directive| !directive.is_empty())
+        .collect();
+
+    EnvFilter::new(directives.join(","))
+}
+
+/// The paths of the log files, from the oldest to the most recent.
+///
+/// Empty if the logs are not written to files.
+pub(crate) fn log_files() -> Vec<PathBuf> {
+    let Some((directory, prefix)) = LOG_FILES_LOCATION.get() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            file_name == prefix.as_str() || file_name.starts_with(&format!("{prefix}."))
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (metadata.modified().ok(), entry.path()))
+        })
+        .collect();
+    files.sort();
+
+    files.into_iter().map(|(_, path)| path).collect()
 }
 
 #[derive(uniffi::Record)]
 pub struct TracingFileConfiguration {
     path: String,
     file_prefix: String,
+    /// The maximum size of a log file, in bytes.
+    ///
+    /// If this is set, the log files are rotated when they reach this size,
+    /// otherwise they are rotated every hour.
+    max_file_size: Option<u64>,
+    /// The maximum number of log files to keep, the oldest ones are deleted
+    /// when it is exceeded.
+    ///
+    /// If this is not set, all the files are kept when they are rotated every
+    /// hour, and 10 files are kept when they are rotated by size.
+    max_files: Option<u64>,
 }
 
 #[derive(uniffi::Record)]
 pub struct TracingConfiguration {
     filter: String,
+    /// The log levels of specific targets, which take precedence over the
+    /// `filter`.
+    target_levels: HashMap<String, LogLevel>,
     /// Controls whether to print to stdout or, equivalent, the system logs on
     /// Android.
     write_to_stdout_or_system: bool,
@@ -253,10 +461,29 @@ pub fn setup_tracing(config: TracingConfiguration) {
     #[cfg(target_os = "android")]
     log_panics();
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::new(&config.filter))
-        .with(text_layers(config))
-        .init();
+    let filter = env_filter(&config.filter, &config.target_levels);
+    let (layers, file_error) = text_layers(config);
+
+    tracing_subscriber::registry().with(filter).with(layers).init();
+
+    log_file_error(file_error);
+}
+
+/// Log the error that prevented the logs from being written to files, if any.
+fn log_file_error(file_error: Option<anyhow::Error>) {
+    if let Some(error) = file_error {
+        error!(
+            "Couldn't write the logs to files, writing them to the standard output or the \
+             system logs instead: {error:#}"
+        );
+    }
+}
+
+/// Get the paths of the files the logs are written to, from the oldest to the
+/// most recent.
+#[uniffi::export]
+pub fn get_log_files() -> Vec<String> {
+    log_files().into_iter().map(|path| path.to_string_lossy().into_owned()).collect()
 }
 
 #[derive(uniffi::Record)]
@@ -266,6 +493,9 @@ pub struct OtlpTracingConfiguration {
     password: String,
     otlp_endpoint: String,
     filter: String,
+    /// The log levels of specific targets, which take precedence over the
+    /// `filter`.
+    target_levels: HashMap<String, LogLevel>,
     /// Controls whether to print to stdout or, equivalent, the system logs on
     /// Android.
     write_to_stdout_or_system: bool,
@@ -282,13 +512,15 @@ pub fn setup_otlp_tracing(config: OtlpTracingConfiguration) {
             .expect("Couldn't configure the OpenTelemetry tracer");
     let otlp_layer = tracing_opentelemetry::layer().with_tracer(otlp_tracer);
 
-    tracing_subscriber::registry()
-        .with(EnvFilter::new(&config.filter))
-        .with(text_layers(TracingConfiguration {
-            filter: config.filter,
-            write_to_stdout_or_system: config.write_to_stdout_or_system,
-            write_to_files: config.write_to_files,
-        }))
-        .with(otlp_layer)
-        .init();
+    let filter = env_filter(&config.filter, &config.target_levels);
+    let (layers, file_error) = text_layers(TracingConfiguration {
+        filter: config.filter,
+        target_levels: config.target_levels,
+        write_to_stdout_or_system: config.write_to_stdout_or_system,
+        write_to_files: config.write_to_files,
+    });
+
+    tracing_subscriber::registry().with(filter).with(layers).with(otlp_layer).init();
+
+    log_file_error(file_error);
 }
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+use matrix_sdk::room::Room as SdkRoom;
+use ruma::{
+    events::{
+        room::power_levels::RoomPowerLevels as RumaRoomPowerLevels,
+        MessageLikeEventType as RumaMessageLikeEventType, TimelineEventType,
+    },
+    Int, UserId,
+};
+
+use crate::{
+    error::ClientError,
+    room_member::{MessageLikeEventType, StateEventType},
+};
+
+/// The values of the power levels of a room.
+#[derive(uniffi::Record)]
+pub struct RoomPowerLevelsValues {
+    pub ban: i64,
+    pub invite: i64,
+    pub kick: i64,
+    pub redact: i64,
+    pub events_default: i64,
+    pub state_default: i64,
+    pub users_default: i64,
+    /// The level required to notify the whole room with `@room`.
+    pub room_notifications: i64,
+    /// The levels of the users who don't have the default level.
+    pub users: HashMap<String, i64>,
+    /// The levels required to send the events of specific types.
+    pub events: HashMap<String, i64>,
+}
+
+/// The power levels of a room, as they were known when they were requested
+/// with `Room::power_levels()`.
+///
+/// The permission checks are computed with the rules of the specification, so
+/// they don't need to be duplicated in the applications. The setters send the
+/// changes to the homeserver, the power levels have to be requested again to
+/// see them.
+#[derive(uniffi::Object)]
+pub struct RoomPowerLevels {
+    room: SdkRoom,
+    power_levels: RumaRoomPowerLevels,
+}
+
+impl RoomPowerLevels {
+    pub(crate) fn new(room: SdkRoom, power_levels: RumaRoomPowerLevels) -> Self {
+        Self { room, power_levels }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl RoomPowerLevels {
+    pub fn values(&self) -> RoomPowerLevelsValues {
+        let power_levels = &self.power_levels;
+
+        RoomPowerLevelsValues {
+            ban: power_levels.ban.into(),
+            invite: power_levels.invite.into(),
+            kick: power_levels.kick.into(),
+            redact: power_levels.redact.into(),
+            events_default: power_levels.events_default.into(),
+            state_default: power_levels.state_default.into(),
+            users_default: power_levels.users_default.into(),
+            room_notifications: power_levels.notifications.room.into(),
+            users: power_levels
+                .users
+                .iter()
+                .map(|(user_id, level)| (user_id.to_string(), (*level).into()))
+                .collect(),
+            events: power_levels
+                .events
+                .iter()
+                .map(|(event_type, level)| (event_type.to_string(), (*level).into()))
+                .collect(),
+        }
+    }
+
+    /// Get the power level of a user, falling back to the default level.
+    pub fn user_power_level(&self, user_id: String) -> Result<i64, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.for_user(&user_id).into())
+    }
+
+    pub fn can_user_ban(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_ban(&user_id))
+    }
+
+    pub fn can_user_invite(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_invite(&user_id))
+    }
+
+    pub fn can_user_kick(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_kick(&user_id))
+    }
+
+    /// Whether the user can redact their own events, which only requires to
+    /// be allowed to send redactions.
+    pub fn can_user_redact_own(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self
+            .power_levels
+            .user_can_send_message(&user_id, RumaMessageLikeEventType::RoomRedaction))
+    }
+
+    /// Whether the user can redact the events of other users.
+    pub fn can_user_redact_other(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self
+            .power_levels
+            .user_can_send_message(&user_id, RumaMessageLikeEventType::RoomRedaction)
+            && self.power_levels.user_can_redact(&user_id))
+    }
+
+    pub fn can_user_send_state(
+        &self,
+        user_id: String,
+        state_event: StateEventType,
+    ) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_send_state(&user_id, state_event.into()))
+    }
+
+    pub fn can_user_send_message(
+        &self,
+        user_id: String,
+        message: MessageLikeEventType,
+    ) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_send_message(&user_id, message.into()))
+    }
+
+    pub fn can_user_trigger_room_notification(&self, user_id: String) -> Result<bool, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.power_levels.user_can_trigger_room_notification(&user_id))
+    }
+
+    /// Change the power level of a user.
+    ///
+    /// Setting the default level removes the user from the list of users.
+    pub async fn set_user_power_level(
+        &self,
+        user_id: String,
+        level: i64,
+    ) -> Result<(), ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        self.room.update_power_levels(vec![(&user_id, to_int(level)?)]).await?;
+        Ok(())
+    }
+
+    /// Change the power level required to send state events of the given
+    /// type.
+    pub async fn set_state_event_power_level(
+        &self,
+        state_event: StateEventType,
+        level: i64,
+    ) -> Result<(), ClientError> {
+        let event_type = ruma::events::StateEventType::from(state_event);
+        self.set_event_power_level(event_type.into(), level).await
+    }
+
+    /// Change the power level required to send message-like events of the
+    /// given type.
+    pub async fn set_message_like_event_power_level(
+        &self,
+        message: MessageLikeEventType,
+        level: i64,
+    ) -> Result<(), ClientError> {
+        let event_type = RumaMessageLikeEventType::from(message);
+        self.set_event_power_level(event_type.into(), level).await
+    }
+}
+
+impl RoomPowerLevels {
+    async fn set_event_power_level(
+        &self,
+        event_type: TimelineEventType,
+        level: i64,
+    ) -> Result<(), ClientError> {
+        self.room.update_event_power_levels(vec![(event_type, to_int(level)?)]).await?;
+        Ok(())
+    }
+}
+
+fn to_int(level: i64) -> Result<Int, ClientError> {
+    Int::new(level).ok_or_else(|| ClientError::Generic {
+        msg: format!("the power level {level} is out of range"),
+    })
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+
+use crate::{error::ClientError, platform::log_files};
+
+/// A bug report to send to a rageshake server.
+#[derive(uniffi::Record)]
+pub struct RageshakeReport {
+    /// The URL of the submission endpoint, usually ending with `/api/submit`.
+    pub submit_url: String,
+    /// The description of the problem.
+    pub text: String,
+    /// The identifier of the application, as known by the server.
+    pub app: String,
+    pub version: String,
+    pub user_agent: String,
+    pub labels: Vec<String>,
+    /// Additional data about the context of the report.
+    pub data: HashMap<String, String>,
+    /// Whether to attach the log files written by the tracing setup.
+    pub include_logs: bool,
+}
+
+#[derive(Deserialize)]
+struct SubmitResponse {
+    report_url: Option<String>,
+}
+
+/// Send a bug report to a rageshake server.
+///
+/// Returns the URL of the issue created for the report, if the server created
+/// one.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn submit_rageshake(report: RageshakeReport) -> Result<Option<String>, ClientError> {
+    let mut form = Form::new()
+        .text("text", report.text)
+        .text("app", report.app)
+        .text("version", report.version)
+        .text("user_agent", report.user_agent);
+
+    for label in report.labels {
+        form = form.text("label", label);
+    }
+
+    for (key, value) in report.data {
+        form = form.text(key, value);
+    }
+
+    if report.include_logs {
+        for path in log_files() {
+            let content = std::fs::read(&path)
+                .with_context(|| format!("Couldn't read the log file {}", path.display()))?;
+            let file_name = path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            form = form.part("log", Part::bytes(content).file_name(file_name));
+        }
+    }
+
+    let response = reqwest::Client::new()
+        .post(report.submit_url)
+        .multipart(form)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Couldn't submit the report")?;
+    let body = response.bytes().await.context("Couldn't read the response")?;
+
+    let response: SubmitResponse = serde_json::from_slice(&body)?;
+    Ok(response.report_url)
+}
@@ -1,22 +1,46 @@
-use std::{convert::TryFrom, sync::Arc};
+use std::{convert::TryFrom, sync::Arc, time::Duration};
 
 use anyhow::{Context, Result};
-use matrix_sdk::{room::Room as SdkRoom, RoomMemberships, RoomState};
+use futures_util::{pin_mut, StreamExt};
+use matrix_sdk::{
+    room::{
+        ActiveCall as SdkActiveCall, CallParticipant as SdkCallParticipant,
+        IncomingCall as SdkIncomingCall, IncomingCallKind as SdkIncomingCallKind,
+        MembershipChangeReport as SdkMembershipChangeReport, Room as SdkRoom,
+        SpaceChild as SdkSpaceChild, SpaceChildOptions, TypingNoticeGuard as SdkTypingNoticeGuard,
+        UpdateDelayedEventAction as SdkUpdateDelayedEventAction,
+    },
+    ComposerDraft as SdkComposerDraft, ComposerDraftType as SdkComposerDraftType, RoomMemberships,
+    RoomState, ScheduledMessage as SdkScheduledMessage,
+};
 use matrix_sdk_ui::timeline::RoomExt;
 use mime::Mime;
 use ruma::{
-    api::client::room::report_content,
     assign,
-    events::room::{avatar::ImageInfo as RumaAvatarImageInfo, MediaSource},
-    EventId, UserId,
+    events::{
+        call::member::{Focus, LivekitFocus},
+        room::{
+            avatar::ImageInfo as RumaAvatarImageInfo,
+            guest_access::GuestAccess as RumaGuestAccess,
+            history_visibility::HistoryVisibility as RumaHistoryVisibility,
+            join_rules::{AllowRule, JoinRule as RumaJoinRule, Restricted},
+            message::RoomMessageEventContentWithoutRelation,
+            MediaSource,
+        },
+    },
+    EventId, IdParseError, MilliSecondsSinceUnixEpoch, OwnedTransactionId, OwnedUserId, RoomId,
+    UserId,
 };
 use tokio::sync::RwLock;
 use tracing::error;
 
 use super::RUNTIME;
 use crate::{
+    call_signaling::CallSignalingSession,
     chunk_iterator::ChunkIterator,
     error::{ClientError, MediaInfoError, RoomError},
+    identity_server::{IdentityServer, PendingThirdPartyInvite},
+    power_levels::RoomPowerLevels,
     room_info::RoomInfo,
     room_member::{MessageLikeEventType, RoomMember, StateEventType},
     ruma::ImageInfo,
@@ -30,6 +54,7 @@ pub enum Membership {
     Invited,
     Joined,
     Left,
+    Knocked,
 }
 
 impl From<RoomState> for Membership {
@@ -38,6 +63,7 @@ impl From<RoomState> for Membership {
             RoomState::Invited => Membership::Invited,
             RoomState::Joined => Membership::Joined,
             RoomState::Left => Membership::Left,
+            RoomState::Knocked => Membership::Knocked,
         }
     }
 }
@@ -82,6 +108,184 @@ impl Room {
         RUNTIME.block_on(async move { self.inner.is_direct().await.unwrap_or(false) })
     }
 
+    /// Mark or unmark the room as a direct chat, in the account data.
+    ///
+    /// The room is marked as a direct chat with all its active members, and
+    /// unmarked for all the users.
+    pub async fn set_is_direct(&self, is_direct: bool) -> Result<(), ClientError> {
+        self.inner.set_is_direct(is_direct).await?;
+        Ok(())
+    }
+
+    /// Add or remove the `m.favourite` tag of the room.
+    ///
+    /// Marking the room as a favourite removes its `m.lowpriority` tag.
+    pub async fn set_is_favourite(
+        &self,
+        is_favourite: bool,
+        tag_order: Option<f64>,
+    ) -> Result<(), ClientError> {
+        self.inner.set_is_favourite(is_favourite, tag_order).await?;
+        Ok(())
+    }
+
+    /// Add or remove the `m.lowpriority` tag of the room.
+    ///
+    /// Marking the room as low priority removes its `m.favourite` tag.
+    pub async fn set_is_low_priority(
+        &self,
+        is_low_priority: bool,
+        tag_order: Option<f64>,
+    ) -> Result<(), ClientError> {
+        self.inner.set_is_low_priority(is_low_priority, tag_order).await?;
+        Ok(())
+    }
+
+    /// Change the join rule of the room.
+    ///
+    /// Fails without sending a request if a restricted join rule has no
+    /// allowed room, if the join rule is not supported by the version of the
+    /// room, or if the user is not allowed to change it.
+    pub async fn set_join_rule(&self, join_rule: JoinRule) -> Result<(), ClientError> {
+        self.inner.set_join_rule(join_rule.try_into()?).await?;
+        Ok(())
+    }
+
+    /// Change who can read the history of the room.
+    pub async fn set_history_visibility(
+        &self,
+        visibility: HistoryVisibility,
+    ) -> Result<(), ClientError> {
+        self.inner.set_history_visibility(visibility.into()).await?;
+        Ok(())
+    }
+
+    /// Change whether guest users can join the room.
+    pub async fn set_guest_access(&self, guest_access: GuestAccess) -> Result<(), ClientError> {
+        self.inner.set_guest_access(guest_access.into()).await?;
+        Ok(())
+    }
+
+    /// The IDs of the events pinned in the room, in order.
+    pub fn pinned_event_ids(&self) -> Vec<String> {
+        self.inner.pinned_event_ids().into_iter().map(|id| id.to_string()).collect()
+    }
+
+    /// Pin the given event in the room.
+    ///
+    /// Returns `false` if the event was already pinned.
+    pub async fn pin_event(&self, event_id: String) -> Result<bool, ClientError> {
+        let event_id = EventId::parse(event_id)?;
+        Ok(self.inner.pin_event(&event_id).await?)
+    }
+
+    /// Unpin the given event in the room.
+    ///
+    /// Returns `false` if the event was not pinned.
+    pub async fn unpin_event(&self, event_id: String) -> Result<bool, ClientError> {
+        let event_id = EventId::parse(event_id)?;
+        Ok(self.inner.unpin_event(&event_id).await?)
+    }
+
+    /// Subscribe to the pinned events of the room.
+    ///
+    /// The listener is called with the IDs of the pinned events every time
+    /// they change.
+    pub fn subscribe_to_pinned_event_ids(
+        &self,
+        listener: Box<dyn PinnedEventIdsListener>,
+    ) -> Arc<TaskHandle> {
+        let stream = self.inner.subscribe_to_pinned_event_ids();
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            pin_mut!(stream);
+            while let Some(event_ids) = stream.next().await {
+                listener.call(event_ids.into_iter().map(|id| id.to_string()).collect());
+            }
+        })))
+    }
+
+    /// Whether previews should be shown for the URLs in this room.
+    ///
+    /// Unless the user chose otherwise, they are disabled in encrypted rooms
+    /// and enabled in other rooms.
+    pub async fn url_previews_enabled(&self) -> Result<bool, ClientError> {
+        Ok(self.inner.url_previews_enabled().await?)
+    }
+
+    /// Set whether previews should be shown for the URLs in this room, in the
+    /// account data.
+    pub async fn set_url_previews_enabled(&self, enabled: bool) -> Result<(), ClientError> {
+        self.inner.set_url_previews_enabled(enabled).await?;
+        Ok(())
+    }
+
+    /// Save the message being written in the composer, to restore it after a
+    /// restart of the app.
+    pub async fn save_draft(&self, draft: ComposerDraft) -> Result<(), ClientError> {
+        self.inner.save_draft(draft.try_into()?).await?;
+        Ok(())
+    }
+
+    /// Get the draft saved in the composer, if any.
+    pub async fn load_draft(&self) -> Result<Option<ComposerDraft>, ClientError> {
+        Ok(self.inner.load_draft().await?.map(Into::into))
+    }
+
+    /// Remove the draft saved in the composer.
+    pub async fn clear_draft(&self) -> Result<(), ClientError> {
+        self.inner.clear_draft().await?;
+        Ok(())
+    }
+
+    /// Schedule a message to be sent at the given time, in milliseconds since
+    /// the unix epoch.
+    ///
+    /// Returns the transaction ID identifying the scheduled message.
+    pub async fn schedule_message(
+        &self,
+        msg: Arc<RoomMessageEventContentWithoutRelation>,
+        send_at: u64,
+    ) -> Result<String, ClientError> {
+        let content = (*msg).clone().with_relation(None);
+        let send_at = MilliSecondsSinceUnixEpoch(u64_to_uint(send_at));
+        Ok(self.inner.schedule_message(content, send_at).await?.to_string())
+    }
+
+    /// Get the messages scheduled in this room that were not sent yet.
+    pub async fn scheduled_messages(&self) -> Result<Vec<ScheduledMessage>, ClientError> {
+        Ok(self.inner.scheduled_messages().await?.into_iter().map(Into::into).collect())
+    }
+
+    /// Cancel a scheduled message.
+    ///
+    /// Returns `false` if there was no such scheduled message.
+    pub async fn cancel_scheduled_message(
+        &self,
+        transaction_id: String,
+    ) -> Result<bool, ClientError> {
+        let transaction_id = OwnedTransactionId::from(transaction_id);
+        Ok(self.inner.cancel_scheduled_message(&transaction_id).await?)
+    }
+
+    /// Get a `matrix.to` permalink to this room.
+    ///
+    /// The alias of the room is used if it has one, otherwise the servers
+    /// that should know the room are picked from its members.
+    pub async fn matrix_to_permalink(&self) -> Result<String, ClientError> {
+        Ok(self.inner.matrix_to_permalink().await?.to_string())
+    }
+
+    /// Get a `matrix.to` permalink to an event in this room.
+    pub async fn matrix_to_event_permalink(&self, event_id: String) -> Result<String, ClientError> {
+        let event_id = EventId::parse(event_id)?;
+        Ok(self.inner.matrix_to_event_permalink(event_id).await?.to_string())
+    }
+
+    /// The users this room is a direct chat with.
+    pub fn direct_targets(&self) -> Vec<String> {
+        self.inner.direct_targets().into_iter().map(|user_id| user_id.to_string()).collect()
+    }
+
     pub fn is_public(&self) -> bool {
         self.inner.is_public()
     }
@@ -167,8 +371,32 @@ impl Room {
         })
     }
 
+    /// Get an iterator over the members of the room.
+    ///
+    /// Only the IDs of the members are loaded first, the members themselves
+    /// are loaded by chunks with `RoomMembersIterator::next_chunk()`.
     pub async fn members(&self) -> Result<Arc<RoomMembersIterator>, ClientError> {
-        Ok(Arc::new(RoomMembersIterator::new(self.inner.members(RoomMemberships::empty()).await?)))
+        self.inner.sync_members().await?;
+        let user_ids = self.inner.member_user_ids(RoomMemberships::empty()).await?;
+        Ok(Arc::new(RoomMembersIterator::new(self.inner.clone(), user_ids)))
+    }
+
+    /// Search the joined members of the room whose display name or user ID
+    /// starts with the given query, e.g. to autocomplete mentions.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The start of the display name or the user ID to search
+    ///
+    /// * `limit` - The maximum number of members to return
+    pub async fn search_members(
+        &self,
+        query: String,
+        limit: u32,
+    ) -> Result<Vec<Arc<RoomMember>>, ClientError> {
+        let members =
+            self.inner.search_members(&query, RoomMemberships::JOIN, limit as usize).await?;
+        Ok(members.into_iter().map(RoomMember::new).map(Arc::new).collect())
     }
 
     pub async fn member(&self, user_id: String) -> Result<Arc<RoomMember>, ClientError> {
@@ -297,22 +525,20 @@ impl Room {
         let int_score = score.map(|value| value.into());
         RUNTIME.block_on(async move {
             let event_id = EventId::parse(event_id)?;
-            self.inner
-                .client()
-                .send(
-                    report_content::v3::Request::new(
-                        self.inner.room_id().into(),
-                        event_id,
-                        int_score,
-                        reason,
-                    ),
-                    None,
-                )
-                .await?;
+            self.inner.report_event(&event_id, int_score, reason).await?;
             Ok(())
         })
     }
 
+    /// Reports this room to the administrators of the homeserver.
+    ///
+    /// # Arguments
+    ///
+    /// * `reason` - The reason for the room being reported, which can be empty.
+    pub async fn report_room(&self, reason: String) -> Result<(), ClientError> {
+        Ok(self.inner.report(reason).await?)
+    }
+
     /// Ignores a user.
     ///
     /// # Arguments
@@ -414,6 +640,24 @@ impl Room {
         })
     }
 
+    /// Invite several users to the room, a few at a time.
+    ///
+    /// A failure to invite a user doesn't stop the invites of the others.
+    pub async fn invite_users(
+        &self,
+        user_ids: Vec<String>,
+    ) -> Result<MembershipChangeReport, ClientError> {
+        let user_ids = user_ids.into_iter().map(UserId::parse).collect::<Result<Vec<_>, _>>()?;
+        Ok(self.inner.invite_users(&user_ids).await.into())
+    }
+
+    /// Get the power levels of the room, with the permission checks computed
+    /// from them.
+    pub async fn power_levels(&self) -> Result<Arc<RoomPowerLevels>, ClientError> {
+        let power_levels = self.inner.power_levels().await?;
+        Ok(Arc::new(RoomPowerLevels::new(self.inner.clone(), power_levels)))
+    }
+
     pub async fn can_user_redact(&self, user_id: String) -> Result<bool, ClientError> {
         let user_id = UserId::parse(&user_id)?;
         Ok(self.inner.can_user_redact(&user_id).await?)
@@ -433,6 +677,18 @@ impl Room {
         Ok(self.inner.ban_user(&user_id, reason.as_deref()).await?)
     }
 
+    /// Ban several users from the room, a few at a time.
+    ///
+    /// A failure to ban a user doesn't stop the bans of the others.
+    pub async fn ban_users(
+        &self,
+        user_ids: Vec<String>,
+        reason: Option<String>,
+    ) -> Result<MembershipChangeReport, ClientError> {
+        let user_ids = user_ids.into_iter().map(UserId::parse).collect::<Result<Vec<_>, _>>()?;
+        Ok(self.inner.ban_users(&user_ids, reason.as_deref()).await.into())
+    }
+
     pub async fn unban_user(
         &self,
         user_id: String,
@@ -461,6 +717,149 @@ impl Room {
         Ok(self.inner.kick_user(&user_id, reason.as_deref()).await?)
     }
 
+    /// Get the users who knocked on this room and are waiting for a
+    /// moderator to accept or decline their request.
+    pub async fn knock_requests(&self) -> Result<Vec<Arc<RoomMember>>, ClientError> {
+        let members = self.inner.knock_requests().await?;
+        Ok(members.into_iter().map(RoomMember::new).map(Arc::new).collect())
+    }
+
+    /// Accept the knock of the given user, by inviting them to the room.
+    pub async fn accept_knock(&self, user_id: String) -> Result<(), ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.inner.accept_knock(&user_id).await?)
+    }
+
+    /// Decline the knock of the given user, by kicking them from the room.
+    pub async fn decline_knock(
+        &self,
+        user_id: String,
+        reason: Option<String>,
+    ) -> Result<(), ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        Ok(self.inner.decline_knock(&user_id, reason.as_deref()).await?)
+    }
+
+    /// Invite the owner of the given email address to this room, through the
+    /// given identity server.
+    pub async fn invite_user_by_email(
+        &self,
+        identity_server: Arc<IdentityServer>,
+        email: String,
+    ) -> Result<(), ClientError> {
+        Ok(self.inner.invite_user_by_email(&identity_server.inner, &email).await?)
+    }
+
+    /// Get the invites to email addresses that were not accepted yet.
+    pub async fn pending_third_party_invites(
+        &self,
+    ) -> Result<Vec<PendingThirdPartyInvite>, ClientError> {
+        let invites = self.inner.pending_third_party_invites().await?;
+        Ok(invites.into_iter().map(Into::into).collect())
+    }
+
+    /// Redact the most recent messages sent by a user in this room.
+    ///
+    /// Returns the IDs of the redacted events.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user whose messages should be redacted
+    ///
+    /// * `limit` - The maximum number of messages to redact
+    ///
+    /// * `reason` - The reason for the redactions (optional)
+    pub async fn redact_recent_messages(
+        &self,
+        user_id: String,
+        limit: u32,
+        reason: Option<String>,
+    ) -> Result<Vec<String>, ClientError> {
+        let user_id = UserId::parse(&user_id)?;
+        let event_ids =
+            self.inner.redact_recent_messages(&user_id, limit as usize, reason.as_deref()).await?;
+        Ok(event_ids.into_iter().map(|event_id| event_id.to_string()).collect())
+    }
+
+    /// Add a room to the children of this space.
+    ///
+    /// The room can be joined through the servers of its route if it is known
+    /// locally, or else through the homeserver of the current user.
+    pub async fn add_space_child(
+        &self,
+        child_id: String,
+        suggested: bool,
+    ) -> Result<(), ClientError> {
+        let child_id = RoomId::parse(child_id)?;
+
+        let via = match self.inner.client().get_room(&child_id) {
+            Some(child) => child.route().await?,
+            None => Vec::new(),
+        };
+        let via = if via.is_empty() {
+            vec![self.inner.own_user_id().server_name().to_owned()]
+        } else {
+            via
+        };
+
+        self.inner.add_space_child(&child_id, via, suggested).await?;
+        Ok(())
+    }
+
+    /// Remove a room from the children of this space.
+    pub async fn remove_space_child(&self, child_id: String) -> Result<(), ClientError> {
+        let child_id = RoomId::parse(child_id)?;
+        self.inner.remove_space_child(&child_id).await?;
+        Ok(())
+    }
+
+    /// Add a room to the children of this space, or update its order and
+    /// suggested flag.
+    ///
+    /// Returns whether this space was also added to the parents of the
+    /// child.
+    pub async fn set_space_child(
+        &self,
+        child_id: String,
+        order: Option<String>,
+        suggested: bool,
+    ) -> Result<bool, ClientError> {
+        let child_id = RoomId::parse(child_id)?;
+        let options = assign!(SpaceChildOptions::default(), { order, suggested });
+        Ok(self.inner.set_space_child(&child_id, options).await?)
+    }
+
+    /// Add a space to the parents of this room.
+    ///
+    /// Returns whether this room was also added to the children of the
+    /// space.
+    pub async fn set_space_parent(
+        &self,
+        parent_id: String,
+        canonical: bool,
+    ) -> Result<bool, ClientError> {
+        let parent_id = RoomId::parse(parent_id)?;
+        Ok(self.inner.set_space_parent(&parent_id, canonical).await?)
+    }
+
+    /// Remove a space from the parents of this room.
+    pub async fn remove_space_parent(&self, parent_id: String) -> Result<(), ClientError> {
+        let parent_id = RoomId::parse(parent_id)?;
+        self.inner.remove_space_parent(&parent_id).await?;
+        Ok(())
+    }
+
+    /// Get the children of this space, in the order they should be displayed.
+    pub async fn space_children(&self) -> Result<Vec<SpaceChild>, ClientError> {
+        Ok(self.inner.space_children().await?.into_iter().map(Into::into).collect())
+    }
+
+    /// Get the IDs of the spaces this room lists as its parents, the
+    /// canonical one first.
+    pub async fn parent_space_ids(&self) -> Result<Vec<String>, ClientError> {
+        Ok(self.inner.parent_space_ids().await?.into_iter().map(|id| id.to_string()).collect())
+    }
+
     pub async fn can_user_send_state(
         &self,
         user_id: String,
@@ -494,6 +893,404 @@ impl Room {
     pub async fn typing_notice(&self, is_typing: bool) -> Result<(), ClientError> {
         Ok(self.inner.typing_notice(is_typing).await?)
     }
+
+    /// Create a helper to send the typing notices of the composer of this
+    /// room, that refreshes them while the user is typing.
+    pub fn typing_notice_guard(&self) -> Arc<TypingNoticeGuard> {
+        Arc::new(TypingNoticeGuard { inner: Some(self.inner.typing_notice_guard()) })
+    }
+
+    /// The call ongoing in this room, if any.
+    pub fn active_call(&self) -> Option<ActiveCall> {
+        self.inner.active_call().map(Into::into)
+    }
+
+    /// Subscribe to the call ongoing in this room.
+    ///
+    /// The listener is called every time a call starts, ends, or its
+    /// participants change.
+    pub fn subscribe_to_active_call(
+        &self,
+        listener: Box<dyn ActiveCallListener>,
+    ) -> Arc<TaskHandle> {
+        let stream = self.inner.subscribe_to_active_call();
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            pin_mut!(stream);
+            while let Some(call) = stream.next().await {
+                listener.call(call.map(Into::into));
+            }
+        })))
+    }
+
+    /// Join the call of this room with the current device, using a LiveKit
+    /// focus.
+    ///
+    /// The membership must be refreshed with `refresh_room_call_membership`
+    /// for as long as the device stays in the call.
+    pub async fn join_room_call(
+        &self,
+        livekit_service_url: String,
+        livekit_alias: String,
+    ) -> Result<(), ClientError> {
+        let focus = Focus::Livekit(LivekitFocus::new(livekit_alias, livekit_service_url));
+        Ok(self.inner.join_room_call(vec![focus]).await?)
+    }
+
+    /// Refresh the membership of the current device in the call of this room.
+    ///
+    /// Returns `false` if the current device is not in the call.
+    pub async fn refresh_room_call_membership(&self) -> Result<bool, ClientError> {
+        Ok(self.inner.refresh_room_call_membership().await?)
+    }
+
+    /// Leave the call of this room with the current device.
+    pub async fn leave_room_call(&self) -> Result<(), ClientError> {
+        Ok(self.inner.leave_room_call().await?)
+    }
+
+    /// Schedule the current device to leave the call of this room after the
+    /// given delay, in milliseconds, unless the scheduled leave is restarted
+    /// or cancelled with `update_delayed_event`.
+    ///
+    /// Returns the ID of the delayed event.
+    pub async fn schedule_room_call_leave(&self, delay_ms: u64) -> Result<String, ClientError> {
+        Ok(self.inner.schedule_room_call_leave(Duration::from_millis(delay_ms)).await?)
+    }
+
+    /// Cancel, restart or immediately send the delayed event with the given
+    /// ID.
+    pub async fn update_delayed_event(
+        &self,
+        delay_id: String,
+        action: UpdateDelayedEventAction,
+    ) -> Result<(), ClientError> {
+        Ok(self.inner.update_delayed_event(&delay_id, action.into()).await?)
+    }
+
+    /// Start tracking the 1:1 VoIP calls of this room.
+    ///
+    /// The session listens to the call events of the room until it is
+    /// dropped.
+    pub fn call_signaling_session(&self) -> Arc<CallSignalingSession> {
+        Arc::new(CallSignalingSession::new(self.inner.call_signaling_session()))
+    }
+}
+
+/// Who can join a room.
+#[derive(uniffi::Enum)]
+pub enum JoinRule {
+    /// Anyone can join the room.
+    Public,
+    /// Users can join the room if they are invited.
+    Invite,
+    /// Users can request an invite to the room.
+    Knock,
+    /// Users can join the room if they are members of one of the allowed
+    /// rooms, or if they are invited.
+    Restricted { allowed_room_ids: Vec<String> },
+    /// Users can join the room like with `Restricted`, or request an invite.
+    KnockRestricted { allowed_room_ids: Vec<String> },
+}
+
+impl TryFrom<JoinRule> for RumaJoinRule {
+    type Error = ClientError;
+
+    fn try_from(value: JoinRule) -> Result<Self, Self::Error> {
+        let restricted = |room_ids: Vec<String>| -> Result<Restricted, ClientError> {
+            let allow = room_ids
+                .into_iter()
+                .map(|room_id| Ok(AllowRule::room_membership(RoomId::parse(room_id)?)))
+                .collect::<Result<_, ClientError>>()?;
+            Ok(Restricted::new(allow))
+        };
+
+        Ok(match value {
+            JoinRule::Public => Self::Public,
+            JoinRule::Invite => Self::Invite,
+            JoinRule::Knock => Self::Knock,
+            JoinRule::Restricted { allowed_room_ids } => {
+                Self::Restricted(restricted(allowed_room_ids)?)
+            }
+            JoinRule::KnockRestricted { allowed_room_ids } => {
+                Self::KnockRestricted(restricted(allowed_room_ids)?)
+            }
+        })
+    }
+}
+
+/// Who can read the history of a room.
+#[derive(uniffi::Enum)]
+pub enum HistoryVisibility {
+    /// Only the members, from the point they were invited.
+    Invited,
+    /// Only the members, from the point they joined.
+    Joined,
+    /// All the members, including the history before they joined.
+    Shared,
+    /// Anyone, including users who are not members.
+    WorldReadable,
+}
+
+impl From<HistoryVisibility> for RumaHistoryVisibility {
+    fn from(value: HistoryVisibility) -> Self {
+        match value {
+            HistoryVisibility::Invited => Self::Invited,
+            HistoryVisibility::Joined => Self::Joined,
+            HistoryVisibility::Shared => Self::Shared,
+            HistoryVisibility::WorldReadable => Self::WorldReadable,
+        }
+    }
+}
+
+/// Whether guest users can join a room.
+#[derive(uniffi::Enum)]
+pub enum GuestAccess {
+    CanJoin,
+    Forbidden,
+}
+
+impl From<GuestAccess> for RumaGuestAccess {
+    fn from(value: GuestAccess) -> Self {
+        match value {
+            GuestAccess::CanJoin => Self::CanJoin,
+            GuestAccess::Forbidden => Self::Forbidden,
+        }
+    }
+}
+
+/// What to do with a delayed event.
+#[derive(uniffi::Enum)]
+pub enum UpdateDelayedEventAction {
+    /// Cancel the delayed event, so it is never sent.
+    Cancel,
+    /// Restart the timeout of the delayed event.
+    Restart,
+    /// Send the delayed event immediately.
+    Send,
+}
+
+impl From<UpdateDelayedEventAction> for SdkUpdateDelayedEventAction {
+    fn from(value: UpdateDelayedEventAction) -> Self {
+        match value {
+            UpdateDelayedEventAction::Cancel => Self::Cancel,
+            UpdateDelayedEventAction::Restart => Self::Restart,
+            UpdateDelayedEventAction::Send => Self::Send,
+        }
+    }
+}
+
+#[uniffi::export(callback_interface)]
+pub trait ActiveCallListener: Sync + Send {
+    fn call(&self, active_call: Option<ActiveCall>);
+}
+
+#[uniffi::export(callback_interface)]
+pub trait PinnedEventIdsListener: Sync + Send {
+    fn call(&self, event_ids: Vec<String>);
+}
+
+/// A call ongoing in a room.
+/// A message that was being written in the composer of a room.
+#[derive(uniffi::Record)]
+pub struct ComposerDraft {
+    /// The draft content in plain text.
+    pub plain_text: String,
+    /// The draft content in HTML, if the composer supports rich text.
+    pub html_text: Option<String>,
+    pub draft_type: ComposerDraftType,
+}
+
+#[derive(uniffi::Enum)]
+pub enum ComposerDraftType {
+    NewMessage,
+    Reply { event_id: String },
+    Edit { event_id: String },
+}
+
+impl TryFrom<ComposerDraft> for SdkComposerDraft {
+    type Error = IdParseError;
+
+    fn try_from(value: ComposerDraft) -> Result<Self, Self::Error> {
+        let draft_type = match value.draft_type {
+            ComposerDraftType::NewMessage => SdkComposerDraftType::NewMessage,
+            ComposerDraftType::Reply { event_id } => {
+                SdkComposerDraftType::Reply { event_id: EventId::parse(event_id)? }
+            }
+            ComposerDraftType::Edit { event_id } => {
+                SdkComposerDraftType::Edit { event_id: EventId::parse(event_id)? }
+            }
+        };
+
+        Ok(Self { plain_text: value.plain_text, html_text: value.html_text, draft_type })
+    }
+}
+
+impl From<SdkComposerDraft> for ComposerDraft {
+    fn from(value: SdkComposerDraft) -> Self {
+        let draft_type = match value.draft_type {
+            SdkComposerDraftType::NewMessage => ComposerDraftType::NewMessage,
+            SdkComposerDraftType::Reply { event_id } => {
+                ComposerDraftType::Reply { event_id: event_id.to_string() }
+            }
+            SdkComposerDraftType::Edit { event_id } => {
+                ComposerDraftType::Edit { event_id: event_id.to_string() }
+            }
+        };
+
+        Self { plain_text: value.plain_text, html_text: value.html_text, draft_type }
+    }
+}
+
+/// The result of a membership change applied to several users.
+#[derive(uniffi::Record)]
+pub struct MembershipChangeReport {
+    /// The users whose membership was changed.
+    pub succeeded: Vec<String>,
+    /// The users whose membership could not be changed.
+    pub failed: Vec<MembershipChangeFailure>,
+}
+
+#[derive(uniffi::Record)]
+pub struct MembershipChangeFailure {
+    pub user_id: String,
+    pub error: String,
+}
+
+impl From<SdkMembershipChangeReport> for MembershipChangeReport {
+    fn from(value: SdkMembershipChangeReport) -> Self {
+        Self {
+            succeeded: value.succeeded.iter().map(ToString::to_string).collect(),
+            failed: value
+                .failed
+                .into_iter()
+                .map(|(user_id, error)| MembershipChangeFailure {
+                    user_id: user_id.to_string(),
+                    error: error.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct SpaceChild {
+    pub room_id: String,
+    pub via: Vec<String>,
+    pub order: Option<String>,
+    pub suggested: bool,
+}
+
+impl From<SdkSpaceChild> for SpaceChild {
+    fn from(value: SdkSpaceChild) -> Self {
+        Self {
+            room_id: value.room_id.to_string(),
+            via: value.via.iter().map(ToString::to_string).collect(),
+            order: value.order,
+            suggested: value.suggested,
+        }
+    }
+}
+
+/// A message scheduled to be sent later.
+#[derive(uniffi::Record)]
+pub struct ScheduledMessage {
+    pub transaction_id: String,
+    /// When the message will be sent, in milliseconds since the unix epoch.
+    pub send_at: u64,
+    pub event_type: String,
+}
+
+impl From<SdkScheduledMessage> for ScheduledMessage {
+    fn from(value: SdkScheduledMessage) -> Self {
+        Self {
+            transaction_id: value.transaction_id.to_string(),
+            send_at: value.send_at.0.into(),
+            event_type: value.event_type,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct ActiveCall {
+    /// The participants of the call, ordered from the oldest membership to
+    /// the newest.
+    pub participants: Vec<CallParticipant>,
+}
+
+impl From<SdkActiveCall> for ActiveCall {
+    fn from(value: SdkActiveCall) -> Self {
+        Self { participants: value.participants.into_iter().map(Into::into).collect() }
+    }
+}
+
+/// A participant of an `ActiveCall`.
+#[derive(uniffi::Record)]
+pub struct CallParticipant {
+    pub user_id: String,
+    pub device_id: String,
+    /// When the user joined the call, in milliseconds since the unix epoch.
+    pub joined_at: Option<u64>,
+    /// When the membership of the user expires, in milliseconds since the
+    /// unix epoch.
+    pub expires_at: Option<u64>,
+}
+
+impl From<SdkCallParticipant> for CallParticipant {
+    fn from(value: SdkCallParticipant) -> Self {
+        Self {
+            user_id: value.user_id.to_string(),
+            device_id: value.device_id.to_string(),
+            joined_at: value.joined_at.map(|ts| ts.0.into()),
+            expires_at: value.expires_at.map(|ts| ts.0.into()),
+        }
+    }
+}
+
+/// A call that should make the current device ring.
+#[derive(uniffi::Record)]
+pub struct IncomingCall {
+    pub room_id: String,
+    pub event_id: String,
+    pub caller: String,
+    pub kind: IncomingCallKind,
+    /// When the call event was sent, in milliseconds since the unix epoch.
+    pub sent_at: u64,
+    /// When the device should stop ringing, in milliseconds since the unix
+    /// epoch.
+    pub expires_at: u64,
+}
+
+impl From<SdkIncomingCall> for IncomingCall {
+    fn from(value: SdkIncomingCall) -> Self {
+        Self {
+            room_id: value.room_id.to_string(),
+            event_id: value.event_id.to_string(),
+            caller: value.caller.to_string(),
+            kind: value.kind.into(),
+            sent_at: value.sent_at.0.into(),
+            expires_at: value.expires_at.0.into(),
+        }
+    }
+}
+
+/// The kind of an `IncomingCall`.
+#[derive(uniffi::Enum)]
+pub enum IncomingCallKind {
+    /// A 1:1 call.
+    Legacy { call_id: String, is_video: bool },
+    /// A ring notification for a call of the room.
+    Ring { call_id: String },
+}
+
+impl From<SdkIncomingCallKind> for IncomingCallKind {
+    fn from(value: SdkIncomingCallKind) -> Self {
+        match value {
+            SdkIncomingCallKind::Legacy { call_id, is_video } => {
+                Self::Legacy { call_id: call_id.to_string(), is_video }
+            }
+            SdkIncomingCallKind::Ring { call_id } => Self::Ring { call_id },
+        }
+    }
 }
 
 #[uniffi::export(callback_interface)]
@@ -501,27 +1298,73 @@ pub trait RoomInfoListener: Sync + Send {
     fn call(&self, room_info: RoomInfo);
 }
 
+/// A helper to send the typing notices of a composer.
+///
+/// The typing notice is stopped when this object is destroyed.
+#[derive(uniffi::Object)]
+pub struct TypingNoticeGuard {
+    // Only an `Option` to be able to drop it inside the runtime.
+    inner: Option<SdkTypingNoticeGuard>,
+}
+
+impl TypingNoticeGuard {
+    fn inner(&self) -> &SdkTypingNoticeGuard {
+        self.inner.as_ref().expect("the guard is only taken on drop")
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl TypingNoticeGuard {
+    /// Notify that the user typed something in the composer.
+    pub async fn keystroke(&self) -> Result<(), ClientError> {
+        Ok(self.inner().keystroke().await?)
+    }
+
+    /// Notify that the user stopped typing, because the composer was cleared
+    /// or its message was sent.
+    pub async fn stop(&self) -> Result<(), ClientError> {
+        Ok(self.inner().stop().await?)
+    }
+}
+
+impl Drop for TypingNoticeGuard {
+    fn drop(&mut self) {
+        // Stopping the typing notice spawns a task.
+        let _guard = RUNTIME.enter();
+        self.inner.take();
+    }
+}
+
+/// An iterator over the members of a room, which loads them from the store by
+/// chunks.
 #[derive(uniffi::Object)]
 pub struct RoomMembersIterator {
-    chunk_iterator: ChunkIterator<matrix_sdk::room::RoomMember>,
+    room: SdkRoom,
+    chunk_iterator: ChunkIterator<OwnedUserId>,
 }
 
 impl RoomMembersIterator {
-    fn new(members: Vec<matrix_sdk::room::RoomMember>) -> Self {
-        Self { chunk_iterator: ChunkIterator::new(members) }
+    fn new(room: SdkRoom, user_ids: Vec<OwnedUserId>) -> Self {
+        Self { room, chunk_iterator: ChunkIterator::new(user_ids) }
     }
 }
 
-#[uniffi::export]
+#[uniffi::export(async_runtime = "tokio")]
 impl RoomMembersIterator {
     fn len(&self) -> u32 {
         self.chunk_iterator.len()
     }
 
-    fn next_chunk(&self, chunk_size: u32) -> Option<Vec<Arc<RoomMember>>> {
-        self.chunk_iterator
-            .next(chunk_size)
-            .map(|members| members.into_iter().map(RoomMember::new).map(Arc::new).collect())
+    async fn next_chunk(
+        &self,
+        chunk_size: u32,
+    ) -> Result<Option<Vec<Arc<RoomMember>>>, ClientError> {
+        let Some(user_ids) = self.chunk_iterator.next(chunk_size) else {
+            return Ok(None);
+        };
+
+        let members = self.room.members_with_user_ids(&user_ids).await?;
+        Ok(Some(members.into_iter().map(RoomMember::new).map(Arc::new).collect()))
     }
 }
 
@@ -0,0 +1,215 @@
+use std::{fmt::Debug, sync::Arc};
+
+use eyeball_im::VectorDiff;
+use futures_util::{pin_mut, StreamExt};
+use matrix_sdk::{
+    room_directory_search::{
+        RoomDescription as SdkRoomDescription, RoomDirectorySearch as SdkRoomDirectorySearch,
+    },
+    ruma::{
+        directory::{PublicRoomJoinRule as RumaPublicRoomJoinRule, RoomNetwork},
+        RoomId, ServerName,
+    },
+    Client as MatrixClient,
+};
+use tokio::sync::RwLock;
+
+use crate::{error::ClientError, room::Room, task_handle::TaskHandle, RUNTIME};
+
+#[derive(uniffi::Enum)]
+pub enum PublicRoomJoinRule {
+    Public,
+    Knock,
+}
+
+impl TryFrom<RumaPublicRoomJoinRule> for PublicRoomJoinRule {
+    type Error = String;
+
+    fn try_from(value: RumaPublicRoomJoinRule) -> Result<Self, Self::Error> {
+        match value {
+            RumaPublicRoomJoinRule::Public => Ok(Self::Public),
+            RumaPublicRoomJoinRule::Knock => Ok(Self::Knock),
+            rule => Err(format!("unsupported join rule: {rule:?}")),
+        }
+    }
+}
+
+/// A room published in the room directory.
+#[derive(uniffi::Record)]
+pub struct RoomDescription {
+    pub room_id: String,
+    pub name: Option<String>,
+    pub topic: Option<String>,
+    pub alias: Option<String>,
+    pub avatar_url: Option<String>,
+    /// The rule to join the room, or `None` if it isn't supported.
+    pub join_rule: Option<PublicRoomJoinRule>,
+    pub is_world_readable: bool,
+    pub joined_members: u64,
+}
+
+impl From<SdkRoomDescription> for RoomDescription {
+    fn from(value: SdkRoomDescription) -> Self {
+        Self {
+            room_id: value.room_id.to_string(),
+            name: value.name,
+            topic: value.topic,
+            alias: value.alias.map(|alias| alias.to_string()),
+            avatar_url: value.avatar_url.map(|url| url.to_string()),
+            join_rule: value.join_rule.try_into().ok(),
+            is_world_readable: value.is_world_readable,
+            joined_members: value.joined_members,
+        }
+    }
+}
+
+/// The network in which the room directory is searched.
+#[derive(uniffi::Enum)]
+pub enum RoomDirectoryNetwork {
+    /// The rooms of the Matrix network.
+    Matrix,
+    /// The rooms of all the networks the server knows about.
+    All,
+    /// The rooms of the third-party network with the given instance ID,
+    /// bridged by the server.
+    ThirdParty { instance_id: String },
+}
+
+impl From<RoomDirectoryNetwork> for RoomNetwork {
+    fn from(value: RoomDirectoryNetwork) -> Self {
+        match value {
+            RoomDirectoryNetwork::Matrix => Self::Matrix,
+            RoomDirectoryNetwork::All => Self::All,
+            RoomDirectoryNetwork::ThirdParty { instance_id } => Self::ThirdParty(instance_id),
+        }
+    }
+}
+
+#[derive(uniffi::Enum)]
+pub enum RoomDirectorySearchEntryUpdate {
+    Append { values: Vec<RoomDescription> },
+    Clear,
+    PushFront { value: RoomDescription },
+    PushBack { value: RoomDescription },
+    PopFront,
+    PopBack,
+    Insert { index: u32, value: RoomDescription },
+    Set { index: u32, value: RoomDescription },
+    Remove { index: u32 },
+    Truncate { length: u32 },
+    Reset { values: Vec<RoomDescription> },
+}
+
+impl From<VectorDiff<SdkRoomDescription>> for RoomDirectorySearchEntryUpdate {
+    fn from(other: VectorDiff<SdkRoomDescription>) -> Self {
+        match other {
+            VectorDiff::Append { values } => {
+                Self::Append { values: values.into_iter().map(Into::into).collect() }
+            }
+            VectorDiff::Clear => Self::Clear,
+            VectorDiff::PushFront { value } => Self::PushFront { value: value.into() },
+            VectorDiff::PushBack { value } => Self::PushBack { value: value.into() },
+            VectorDiff::PopFront => Self::PopFront,
+            VectorDiff::PopBack => Self::PopBack,
+            VectorDiff::Insert { index, value } => {
+                Self::Insert { index: u32::try_from(index).unwrap(), value: value.into() }
+            }
+            VectorDiff::Set { index, value } => {
+                Self::Set { index: u32::try_from(index).unwrap(), value: value.into() }
+            }
+            VectorDiff::Remove { index } => Self::Remove { index: u32::try_from(index).unwrap() },
+            VectorDiff::Truncate { length } => {
+                Self::Truncate { length: u32::try_from(length).unwrap() }
+            }
+            VectorDiff::Reset { values } => {
+                Self::Reset { values: values.into_iter().map(Into::into).collect() }
+            }
+        }
+    }
+}
+
+#[uniffi::export(callback_interface)]
+pub trait RoomDirectorySearchEntriesListener: Send + Sync + Debug {
+    fn on_update(&self, room_entries_update: Vec<RoomDirectorySearchEntryUpdate>);
+}
+
+/// A paginated search in the room directory.
+#[derive(uniffi::Object)]
+pub struct RoomDirectorySearch {
+    client: MatrixClient,
+    inner: RwLock<SdkRoomDirectorySearch>,
+}
+
+impl RoomDirectorySearch {
+    pub(crate) fn new(client: MatrixClient) -> Self {
+        Self { inner: RwLock::new(SdkRoomDirectorySearch::new(client.clone())), client }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl RoomDirectorySearch {
+    /// Start a new search and load its first page, the results of the
+    /// previous search are cleared.
+    ///
+    /// * `filter` - The term to search for, or `None` to list all the rooms.
+    ///
+    /// * `batch_size` - The number of rooms to load with each page.
+    ///
+    /// * `server` - The server whose directory is searched, or `None` for the
+    ///   homeserver of the client.
+    ///
+    /// * `network` - The network to search in.
+    pub async fn search(
+        &self,
+        filter: Option<String>,
+        batch_size: u32,
+        server: Option<String>,
+        network: RoomDirectoryNetwork,
+    ) -> Result<(), ClientError> {
+        let server = server.map(ServerName::parse).transpose()?;
+        self.inner.write().await.search(filter, batch_size, server, network.into()).await?;
+        Ok(())
+    }
+
+    /// Load the next page of results, if there is one.
+    pub async fn next_page(&self) -> Result<(), ClientError> {
+        self.inner.write().await.next_page().await?;
+        Ok(())
+    }
+
+    pub async fn is_at_last_page(&self) -> bool {
+        self.inner.read().await.is_at_last_page()
+    }
+
+    /// Get the results loaded so far, and listen to their updates.
+    ///
+    /// The listener is called with the current results first, as a reset.
+    pub async fn results(
+        &self,
+        listener: Box<dyn RoomDirectorySearchEntriesListener>,
+    ) -> Arc<TaskHandle> {
+        let (results, results_stream) = self.inner.read().await.results();
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            listener.on_update(vec![RoomDirectorySearchEntryUpdate::Reset {
+                values: results.into_iter().map(Into::into).collect(),
+            }]);
+
+            pin_mut!(results_stream);
+
+            while let Some(diffs) = results_stream.next().await {
+                listener.on_update(diffs.into_iter().map(Into::into).collect());
+            }
+        })))
+    }
+
+    /// Join a room found by the search, through the server whose directory
+    /// was searched.
+    pub async fn join(&self, room_id: String) -> Result<Arc<Room>, ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let via: Vec<_> = self.inner.read().await.server().cloned().into_iter().collect();
+
+        let room = self.client.join_room_by_id_or_alias((&*room_id).into(), &via).await?;
+        Ok(Arc::new(Room::new(room)))
+    }
+}
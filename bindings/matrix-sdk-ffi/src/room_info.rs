@@ -40,6 +40,17 @@ pub struct RoomInfo {
     /// Events causing mentions/highlights for the user, according to their
     /// notification settings.
     num_unread_mentions: u64,
+    /// The roots of the threads that have unread messages, according to the
+    /// threaded read receipts of the user.
+    threads_with_unread_messages: Vec<String>,
+    is_favourite: bool,
+    is_low_priority: bool,
+    /// The order of the room among the favourite or low priority rooms,
+    /// depending on its tag.
+    tag_order: Option<f64>,
+    /// Whether the room was joined with a partial state, so its members are
+    /// not all known yet.
+    is_partial_state: bool,
 }
 
 impl RoomInfo {
@@ -87,6 +98,19 @@ impl RoomInfo {
             num_unread_messages: room.num_unread_messages(),
             num_unread_notifications: room.num_unread_notifications(),
             num_unread_mentions: room.num_unread_mentions(),
+            threads_with_unread_messages: room
+                .threads_with_unread_messages()
+                .iter()
+                .map(|thread_root| thread_root.to_string())
+                .collect(),
+            is_favourite: room.is_favourite(),
+            is_low_priority: room.is_low_priority(),
+            tag_order: if room.is_favourite() {
+                room.favourite_order()
+            } else {
+                room.low_priority_order()
+            },
+            is_partial_state: room.is_partial_state(),
         })
     }
 }
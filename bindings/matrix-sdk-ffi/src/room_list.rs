@@ -1,4 +1,8 @@
-use std::{fmt::Debug, sync::Arc, time::Duration};
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use eyeball_im::VectorDiff;
 use futures_util::{pin_mut, StreamExt};
@@ -12,9 +16,18 @@ use matrix_sdk::{
     },
     RoomListEntry as MatrixRoomListEntry,
 };
-use matrix_sdk_ui::room_list_service::filters::{
-    new_filter_all, new_filter_all_non_left, new_filter_fuzzy_match_room_name, new_filter_none,
-    new_filter_normalized_match_room_name,
+use matrix_sdk_ui::room_list_service::{
+    filters::{
+        new_filter_all, new_filter_all_non_left, new_filter_and, new_filter_category,
+        new_filter_favourite, new_filter_fuzzy_match_room_name, new_filter_invite,
+        new_filter_knock, new_filter_low_priority, new_filter_none,
+        new_filter_normalized_match_room_name, new_filter_not, new_filter_or, new_filter_space,
+        new_filter_unread, BoxedFilterFn, RoomCategory,
+    },
+    sorters::{
+        new_sorter_favourites_first, new_sorter_lexicographic, new_sorter_manual, new_sorter_name,
+        new_sorter_recency, new_sorter_unread_first, BoxedSorterFn,
+    },
 };
 use tokio::sync::RwLock;
 
@@ -22,6 +35,7 @@ use crate::{
     error::ClientError,
     room::Room,
     room_info::RoomInfo,
+    room_member::RoomMember,
     timeline::{EventTimelineItem, Timeline},
     TaskHandle, RUNTIME,
 };
@@ -38,6 +52,8 @@ pub enum RoomListError {
     RoomNotFound { room_name: String },
     #[error("invalid room ID: {error}")]
     InvalidRoomId { error: String },
+    #[error("space hierarchy error: {error}")]
+    SpaceHierarchy { error: String },
 }
 
 impl From<matrix_sdk_ui::room_list_service::Error> for RoomListError {
@@ -49,6 +65,7 @@ impl From<matrix_sdk_ui::room_list_service::Error> for RoomListError {
             UnknownList(list_name) => Self::UnknownList { list_name },
             InputCannotBeApplied(_) => Self::InputCannotBeApplied,
             RoomNotFound(room_id) => Self::RoomNotFound { room_name: room_id.to_string() },
+            SpaceHierarchy(error) => Self::SpaceHierarchy { error: error.to_string() },
         }
     }
 }
@@ -121,6 +138,28 @@ impl RoomListService {
         }))
     }
 
+    /// Search in all the joined rooms.
+    ///
+    /// The listener first receives a `Reset` with the initial results, and
+    /// then the updates of the results.
+    fn search(&self, query: String, listener: Box<dyn RoomListEntriesListener>) -> Arc<TaskHandle> {
+        let search_stream = self.inner.search(&query);
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            pin_mut!(search_stream);
+
+            while let Some(diff) = search_stream.next().await {
+                listener.on_update(diff.into_iter().map(Into::into).collect());
+            }
+        })))
+    }
+
+    async fn space(&self, space_id: String) -> Result<Arc<RoomListSpace>, RoomListError> {
+        let space_id = <&RoomId>::try_from(space_id.as_str()).map_err(RoomListError::from)?;
+
+        Ok(Arc::new(RoomListSpace { inner: Arc::new(self.inner.space(space_id).await?) }))
+    }
+
     async fn apply_input(&self, input: RoomListInput) -> Result<(), RoomListError> {
         self.inner.apply_input(input.into()).await.map(|_| ()).map_err(Into::into)
     }
@@ -146,6 +185,46 @@ impl RoomListService {
     }
 }
 
+#[derive(uniffi::Object)]
+pub struct RoomListSpace {
+    inner: Arc<matrix_sdk_ui::room_list_service::Space>,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl RoomListSpace {
+    fn id(&self) -> String {
+        self.inner.space_id().to_string()
+    }
+
+    fn children(&self) -> Vec<String> {
+        self.inner.children().iter().map(ToString::to_string).collect()
+    }
+
+    fn subscribe_to_children(
+        &self,
+        listener: Box<dyn RoomListSpaceChildrenListener>,
+    ) -> Arc<TaskHandle> {
+        let children_stream = self.inner.children_stream();
+
+        Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            pin_mut!(children_stream);
+
+            while let Some(children) = children_stream.next().await {
+                listener.on_update(children.iter().map(ToString::to_string).collect());
+            }
+        })))
+    }
+
+    async fn refresh(&self) -> Result<(), RoomListError> {
+        Ok(self.inner.refresh().await?)
+    }
+}
+
+#[uniffi::export(callback_interface)]
+pub trait RoomListSpaceChildrenListener: Send + Sync + Debug {
+    fn on_update(&self, children: Vec<String>);
+}
+
 #[derive(uniffi::Object)]
 pub struct RoomList {
     room_list_service: Arc<RoomListService>,
@@ -359,8 +438,11 @@ pub trait RoomListEntriesListener: Send + Sync + Debug {
 
 #[derive(uniffi::Object)]
 pub struct RoomListDynamicEntriesController {
-    inner: matrix_sdk_ui::room_list_service::RoomListDynamicEntriesController,
+    inner: Arc<matrix_sdk_ui::room_list_service::RoomListDynamicEntriesController>,
     client: matrix_sdk::Client,
+    /// The task refreshing the filter when the children of the space change,
+    /// if the filter is a space filter.
+    space_filter_task: Mutex<Option<TaskHandle>>,
 }
 
 impl RoomListDynamicEntriesController {
@@ -368,26 +450,79 @@ impl RoomListDynamicEntriesController {
         dynamic_entries_controller: matrix_sdk_ui::room_list_service::RoomListDynamicEntriesController,
         client: &matrix_sdk::Client,
     ) -> Self {
-        Self { inner: dynamic_entries_controller, client: client.clone() }
+        Self {
+            inner: Arc::new(dynamic_entries_controller),
+            client: client.clone(),
+            space_filter_task: Mutex::new(None),
+        }
     }
 }
 
 #[uniffi::export]
 impl RoomListDynamicEntriesController {
     fn set_filter(&self, kind: RoomListEntriesDynamicFilterKind) -> bool {
-        use RoomListEntriesDynamicFilterKind as Kind;
+        *self.space_filter_task.lock().unwrap() = None;
 
-        match kind {
-            Kind::All => self.inner.set_filter(new_filter_all()),
-            Kind::AllNonLeft => self.inner.set_filter(new_filter_all_non_left(&self.client)),
-            Kind::None => self.inner.set_filter(new_filter_none()),
-            Kind::NormalizedMatchRoomName { pattern } => {
-                self.inner.set_filter(new_filter_normalized_match_room_name(&self.client, &pattern))
-            }
-            Kind::FuzzyMatchRoomName { pattern } => {
-                self.inner.set_filter(new_filter_fuzzy_match_room_name(&self.client, &pattern))
+        self.inner.set_filter(kind.into_filter(&self.client))
+    }
+
+    /// Only show the children of the given space.
+    ///
+    /// The list is updated when the children of the space change.
+    fn set_space_filter(&self, space: Arc<RoomListSpace>) -> bool {
+        let mut space_filter_task = self.space_filter_task.lock().unwrap();
+        *space_filter_task = None;
+
+        if !self.inner.set_filter(new_filter_space(&space.inner)) {
+            return false;
+        }
+
+        let controller = self.inner.clone();
+        let children_stream = space.inner.children_stream();
+
+        *space_filter_task = Some(TaskHandle::new(RUNTIME.spawn(async move {
+            // Keep the space alive, so that its children are kept up to date.
+            let _space = space;
+
+            pin_mut!(children_stream);
+
+            while children_stream.next().await.is_some() {
+                controller.refresh_filter();
             }
+        })));
+
+        true
+    }
+
+    /// Set the sorters used to sort the entries.
+    ///
+    /// The first sorter is used, and the next ones are only used to order
+    /// entries the previous ones consider equal. An empty list removes the
+    /// sorter, i.e. the entries are kept in the server order.
+    fn set_sorter(&self, kinds: Vec<RoomListEntriesDynamicSorterKind>) {
+        use RoomListEntriesDynamicSorterKind as Kind;
+
+        if kinds.is_empty() {
+            self.inner.reset_sorter();
+            return;
         }
+
+        let sorters = kinds
+            .into_iter()
+            .map(|kind| -> BoxedSorterFn {
+                match kind {
+                    Kind::Recency => Box::new(new_sorter_recency()),
+                    Kind::UnreadFirst => Box::new(new_sorter_unread_first(&self.client)),
+                    Kind::Name => Box::new(new_sorter_name(&self.client)),
+                    Kind::FavouritesFirst => Box::new(new_sorter_favourites_first(&self.client)),
+                    Kind::Manual { room_ids } => Box::new(new_sorter_manual(
+                        room_ids.into_iter().filter_map(|id| RoomId::parse(id).ok()).collect(),
+                    )),
+                }
+            })
+            .collect();
+
+        self.inner.set_sorter(new_sorter_lexicographic(sorters));
     }
 
     fn add_one_page(&self) {
@@ -404,8 +539,87 @@ pub enum RoomListEntriesDynamicFilterKind {
     All,
     AllNonLeft,
     None,
-    NormalizedMatchRoomName { pattern: String },
-    FuzzyMatchRoomName { pattern: String },
+    NormalizedMatchRoomName {
+        pattern: String,
+    },
+    FuzzyMatchRoomName {
+        pattern: String,
+    },
+    Unread,
+    Favourite,
+    LowPriority,
+    Invite,
+    Knock,
+    Category {
+        expect: RoomListFilterCategory,
+    },
+    /// Accept the entries accepted by all the given filters.
+    And {
+        filters: Vec<RoomListEntriesDynamicFilterKind>,
+    },
+    /// Accept the entries accepted by at least one of the given filters.
+    Or {
+        filters: Vec<RoomListEntriesDynamicFilterKind>,
+    },
+    /// Accept the entries rejected by the given filter.
+    Not {
+        filter: Box<RoomListEntriesDynamicFilterKind>,
+    },
+}
+
+impl RoomListEntriesDynamicFilterKind {
+    fn into_filter(self, client: &matrix_sdk::Client) -> BoxedFilterFn {
+        use RoomListEntriesDynamicFilterKind as Kind;
+
+        match self {
+            Kind::All => Box::new(new_filter_all()),
+            Kind::AllNonLeft => Box::new(new_filter_all_non_left(client)),
+            Kind::None => Box::new(new_filter_none()),
+            Kind::NormalizedMatchRoomName { pattern } => {
+                Box::new(new_filter_normalized_match_room_name(client, &pattern))
+            }
+            Kind::FuzzyMatchRoomName { pattern } => {
+                Box::new(new_filter_fuzzy_match_room_name(client, &pattern))
+            }
+            Kind::Unread => Box::new(new_filter_unread(client)),
+            Kind::Favourite => Box::new(new_filter_favourite(client)),
+            Kind::LowPriority => Box::new(new_filter_low_priority(client)),
+            Kind::Invite => Box::new(new_filter_invite(client)),
+            Kind::Knock => Box::new(new_filter_knock(client)),
+            Kind::Category { expect } => Box::new(new_filter_category(client, expect.into())),
+            Kind::And { filters } => Box::new(new_filter_and(
+                filters.into_iter().map(|filter| filter.into_filter(client)).collect(),
+            )),
+            Kind::Or { filters } => Box::new(new_filter_or(
+                filters.into_iter().map(|filter| filter.into_filter(client)).collect(),
+            )),
+            Kind::Not { filter } => Box::new(new_filter_not(filter.into_filter(client))),
+        }
+    }
+}
+
+#[derive(uniffi::Enum)]
+pub enum RoomListFilterCategory {
+    Group,
+    People,
+}
+
+impl From<RoomListFilterCategory> for RoomCategory {
+    fn from(value: RoomListFilterCategory) -> Self {
+        match value {
+            RoomListFilterCategory::Group => Self::Group,
+            RoomListFilterCategory::People => Self::People,
+        }
+    }
+}
+
+#[derive(uniffi::Enum)]
+pub enum RoomListEntriesDynamicSorterKind {
+    Recency,
+    UnreadFirst,
+    Name,
+    FavouritesFirst,
+    Manual { room_ids: Vec<String> },
 }
 
 #[derive(uniffi::Object)]
@@ -469,6 +683,11 @@ impl RoomListItem {
         self.inner.latest_event().await.map(EventTimelineItem).map(Arc::new)
     }
 
+    /// Get a ready-to-render preview of the latest event.
+    async fn latest_event_preview(&self) -> Option<RoomListLatestEventPreview> {
+        self.inner.latest_event_preview().await.map(Into::into)
+    }
+
     fn has_unread_notifications(&self) -> bool {
         self.inner.has_unread_notifications()
     }
@@ -476,6 +695,71 @@ impl RoomListItem {
     fn unread_notifications(&self) -> Arc<UnreadNotificationsCount> {
         Arc::new(self.inner.unread_notifications().into())
     }
+
+    /// Get what's needed to present the invite to this room.
+    ///
+    /// Only invited rooms have an invite preview.
+    async fn invite_preview(&self) -> Result<RoomListInvitePreview, ClientError> {
+        Ok(self.inner.invite_preview().await?.into())
+    }
+
+    /// Decline the invite to this room and ignore the user who sent it.
+    ///
+    /// If a `report_reason` is given, the invite is also reported to the
+    /// homeserver administrators, when possible.
+    async fn decline_and_block(&self, report_reason: Option<String>) -> Result<(), ClientError> {
+        Ok(self.inner.decline_and_block(report_reason).await?)
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct RoomListLatestEventPreview {
+    pub sender_id: String,
+    pub sender_display_name: Option<String>,
+    pub body: String,
+    pub timestamp: u64,
+    pub is_own: bool,
+    pub is_mention: bool,
+}
+
+impl From<matrix_sdk_ui::room_list_service::LatestEventPreview> for RoomListLatestEventPreview {
+    fn from(value: matrix_sdk_ui::room_list_service::LatestEventPreview) -> Self {
+        Self {
+            sender_id: value.sender_id.to_string(),
+            sender_display_name: value.sender_display_name,
+            body: value.body,
+            timestamp: value.timestamp.0.into(),
+            is_own: value.is_own,
+            is_mention: value.is_mention,
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct RoomListInvitePreview {
+    pub inviter_id: String,
+    pub inviter: Option<Arc<RoomMember>>,
+    pub room_name: Option<String>,
+    pub topic: Option<String>,
+    pub avatar_url: Option<String>,
+    pub canonical_alias: Option<String>,
+    pub joined_members_count: u64,
+    pub is_direct: bool,
+}
+
+impl From<matrix_sdk_ui::room_list_service::InvitePreview> for RoomListInvitePreview {
+    fn from(value: matrix_sdk_ui::room_list_service::InvitePreview) -> Self {
+        Self {
+            inviter_id: value.inviter_id.to_string(),
+            inviter: value.inviter.map(|inviter| Arc::new(RoomMember::new(inviter))),
+            room_name: value.room_name,
+            topic: value.topic,
+            avatar_url: value.avatar_url.map(|uri| uri.to_string()),
+            canonical_alias: value.canonical_alias.map(|alias| alias.to_string()),
+            joined_members_count: value.joined_members_count,
+            is_direct: value.is_direct,
+        }
+    }
 }
 
 #[derive(Clone, Debug, uniffi::Enum)]
@@ -0,0 +1,40 @@
+use matrix_sdk::{room_preview::RoomPreview as SdkRoomPreview, ruma::room::RoomType};
+
+use crate::room::Membership;
+
+/// The information about a room that can be displayed before joining it.
+#[derive(uniffi::Record)]
+pub struct RoomPreview {
+    pub room_id: String,
+    pub canonical_alias: Option<String>,
+    pub name: Option<String>,
+    pub topic: Option<String>,
+    pub avatar_url: Option<String>,
+    pub num_joined_members: u64,
+    pub is_space: bool,
+    /// The join rule of the room, e.g. `public`, `knock` or `invite`.
+    pub join_rule: String,
+    pub is_world_readable: bool,
+    /// Whether the room is encrypted, if known.
+    pub is_encrypted: Option<bool>,
+    /// The membership of the current user, if the room is known locally.
+    pub membership: Option<Membership>,
+}
+
+impl From<SdkRoomPreview> for RoomPreview {
+    fn from(value: SdkRoomPreview) -> Self {
+        Self {
+            room_id: value.room_id.to_string(),
+            canonical_alias: value.canonical_alias.map(|alias| alias.to_string()),
+            name: value.name,
+            topic: value.topic,
+            avatar_url: value.avatar_url.map(|url| url.to_string()),
+            num_joined_members: value.num_joined_members,
+            is_space: value.room_type == Some(RoomType::Space),
+            join_rule: value.join_rule.as_str().to_owned(),
+            is_world_readable: value.is_world_readable,
+            is_encrypted: value.is_encrypted,
+            membership: value.state.map(Into::into),
+        }
+    }
+}
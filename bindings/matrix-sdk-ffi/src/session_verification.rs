@@ -1,4 +1,4 @@
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 
 use anyhow::Context as _;
 use futures_util::StreamExt;
@@ -10,9 +10,10 @@ use matrix_sdk::{
     },
     ruma::events::{key::verification::VerificationMethod, AnyToDeviceEvent},
 };
+use tokio::sync::mpsc;
 
 use super::RUNTIME;
-use crate::error::ClientError;
+use crate::{error::ClientError, task_handle::TaskHandle};
 
 #[derive(uniffi::Object)]
 pub struct SessionVerificationEmoji {
@@ -47,15 +48,38 @@ pub trait SessionVerificationControllerDelegate: Sync + Send {
     fn did_finish(&self);
 }
 
-pub type Delegate = Arc<RwLock<Option<Box<dyn SessionVerificationControllerDelegate>>>>;
+/// A notification for the [`SessionVerificationControllerDelegate`].
+enum DelegateUpdate {
+    AcceptedVerificationRequest,
+    StartedSasVerification,
+    ReceivedVerificationData(SessionVerificationData),
+    Failed,
+    Cancelled,
+    Finished,
+}
+
+/// The sender of the notifications to the task of the current delegate, if
+/// any.
+type Delegate = Arc<RwLock<Option<mpsc::UnboundedSender<DelegateUpdate>>>>;
+
+/// Send a notification to the current delegate, if any.
+fn notify_delegate(delegate: &Delegate, update: DelegateUpdate) {
+    if let Some(sender) = &*delegate.read().unwrap() {
+        _ = sender.send(update);
+    }
+}
 
 #[derive(Clone, uniffi::Object)]
 pub struct SessionVerificationController {
     encryption: Encryption,
     user_identity: UserIdentity,
     delegate: Delegate,
+    /// The task notifying the current delegate, if it is still running.
+    delegate_task: Arc<Mutex<Weak<TaskHandle>>>,
     verification_request: Arc<RwLock<Option<VerificationRequest>>>,
     sas_verification: Arc<RwLock<Option<SasVerification>>>,
+    /// The task listening to the changes of the current SAS verification.
+    sas_changes_task: Arc<RwLock<Option<TaskHandle>>>,
 }
 
 #[uniffi::export(async_runtime = "tokio")]
@@ -67,8 +91,45 @@ impl SessionVerificationController {
         Ok(device.is_cross_signed_by_owner())
     }
 
-    pub fn set_delegate(&self, delegate: Option<Box<dyn SessionVerificationControllerDelegate>>) {
-        *self.delegate.write().unwrap() = delegate;
+    /// Set the delegate notified of the progress of the verification.
+    ///
+    /// The delegate is notified until the returned [`TaskHandle`] is cancelled
+    /// or dropped, or until another delegate is set.
+    pub fn set_delegate(
+        &self,
+        delegate: Option<Box<dyn SessionVerificationControllerDelegate>>,
+    ) -> Option<Arc<TaskHandle>> {
+        let mut delegate_task = self.delegate_task.lock().unwrap();
+
+        if let Some(task) = delegate_task.upgrade() {
+            task.cancel();
+        }
+        *delegate_task = Weak::new();
+        *self.delegate.write().unwrap() = None;
+
+        let delegate = delegate?;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        *self.delegate.write().unwrap() = Some(sender);
+
+        let task = Arc::new(TaskHandle::new(RUNTIME.spawn(async move {
+            while let Some(update) = receiver.recv().await {
+                match update {
+                    DelegateUpdate::AcceptedVerificationRequest => {
+                        delegate.did_accept_verification_request()
+                    }
+                    DelegateUpdate::StartedSasVerification => delegate.did_start_sas_verification(),
+                    DelegateUpdate::ReceivedVerificationData(data) => {
+                        delegate.did_receive_verification_data(data)
+                    }
+                    DelegateUpdate::Failed => delegate.did_fail(),
+                    DelegateUpdate::Cancelled => delegate.did_cancel(),
+                    DelegateUpdate::Finished => delegate.did_finish(),
+                }
+            }
+        })));
+
+        *delegate_task = Arc::downgrade(&task);
+        Some(task)
     }
 
     pub async fn request_verification(&self) -> Result<(), ClientError> {
@@ -91,18 +152,11 @@ impl SessionVerificationController {
                 Ok(Some(verification)) => {
                     *self.sas_verification.write().unwrap() = Some(verification.clone());
 
-                    if let Some(delegate) = &*self.delegate.read().unwrap() {
-                        delegate.did_start_sas_verification()
-                    }
+                    notify_delegate(&self.delegate, DelegateUpdate::StartedSasVerification);
 
-                    let delegate = self.delegate.clone();
-                    RUNTIME.spawn(Self::listen_to_changes(delegate, verification));
-                }
-                _ => {
-                    if let Some(delegate) = &*self.delegate.read().unwrap() {
-                        delegate.did_fail()
-                    }
+                    self.spawn_sas_changes_listener(verification);
                 }
+                _ => notify_delegate(&self.delegate, DelegateUpdate::Failed),
             }
         }
 
@@ -143,8 +197,10 @@ impl SessionVerificationController {
             encryption,
             user_identity,
             delegate: Arc::new(RwLock::new(None)),
+            delegate_task: Default::default(),
             verification_request: Arc::new(RwLock::new(None)),
             sas_verification: Arc::new(RwLock::new(None)),
+            sas_changes_task: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -168,14 +224,11 @@ impl SessionVerificationController {
                         *self.sas_verification.write().unwrap() = Some(sas_verification.clone());
 
                         if sas_verification.accept().await.is_ok() {
-                            if let Some(delegate) = &*self.delegate.read().unwrap() {
-                                delegate.did_start_sas_verification()
-                            }
-
-                            let delegate = self.delegate.clone();
-                            RUNTIME.spawn(Self::listen_to_changes(delegate, sas_verification));
-                        } else if let Some(delegate) = &*self.delegate.read().unwrap() {
-                            delegate.did_fail()
+                            notify_delegate(&self.delegate, DelegateUpdate::StartedSasVerification);
+
+                            self.spawn_sas_changes_listener(sas_verification);
+                        } else {
+                            notify_delegate(&self.delegate, DelegateUpdate::Failed);
                         }
                     }
                 }
@@ -185,9 +238,7 @@ impl SessionVerificationController {
                     return;
                 }
 
-                if let Some(delegate) = &*self.delegate.read().unwrap() {
-                    delegate.did_accept_verification_request()
-                }
+                notify_delegate(&self.delegate, DelegateUpdate::AcceptedVerificationRequest);
             }
             _ => (),
         }
@@ -200,50 +251,49 @@ impl SessionVerificationController {
         }
     }
 
+    /// Listen to the changes of a SAS verification, instead of the previous
+    /// one.
+    fn spawn_sas_changes_listener(&self, sas: SasVerification) {
+        let task = RUNTIME.spawn(Self::listen_to_changes(self.delegate.clone(), sas));
+        *self.sas_changes_task.write().unwrap() = Some(TaskHandle::new(task));
+    }
+
     async fn listen_to_changes(delegate: Delegate, sas: SasVerification) {
         let mut stream = sas.changes();
 
         while let Some(state) = stream.next().await {
             match state {
                 SasState::KeysExchanged { emojis, decimals } => {
-                    if let Some(delegate) = &*delegate.read().unwrap() {
-                        if let Some(emojis) = emojis {
-                            delegate.did_receive_verification_data(
-                                SessionVerificationData::Emojis {
-                                    emojis: emojis
-                                        .emojis
-                                        .into_iter()
-                                        .map(|emoji| {
-                                            Arc::new(SessionVerificationEmoji {
-                                                symbol: emoji.symbol.to_owned(),
-                                                description: emoji.description.to_owned(),
-                                            })
-                                        })
-                                        .collect(),
-                                    indices: emojis.indices.to_vec(),
-                                },
-                            );
-                        } else {
-                            delegate.did_receive_verification_data(
-                                SessionVerificationData::Decimals {
-                                    values: vec![decimals.0, decimals.1, decimals.2],
-                                },
-                            )
+                    let data = if let Some(emojis) = emojis {
+                        SessionVerificationData::Emojis {
+                            emojis: emojis
+                                .emojis
+                                .into_iter()
+                                .map(|emoji| {
+                                    Arc::new(SessionVerificationEmoji {
+                                        symbol: emoji.symbol.to_owned(),
+                                        description: emoji.description.to_owned(),
+                                    })
+                                })
+                                .collect(),
+                            indices: emojis.indices.to_vec(),
                         }
-                    }
+                    } else {
+                        SessionVerificationData::Decimals {
+                            values: vec![decimals.0, decimals.1, decimals.2],
+                        }
+                    };
+
+                    notify_delegate(&delegate, DelegateUpdate::ReceivedVerificationData(data));
                 }
                 SasState::Done { .. } => {
-                    if let Some(delegate) = &*delegate.read().unwrap() {
-                        delegate.did_finish()
-                    }
+                    notify_delegate(&delegate, DelegateUpdate::Finished);
                     break;
                 }
                 SasState::Cancelled(_cancel_info) => {
                     // TODO: The cancel_info is usable, we should tell the user why we were
                     // cancelled.
-                    if let Some(delegate) = &*delegate.read().unwrap() {
-                        delegate.did_cancel()
-                    }
+                    notify_delegate(&delegate, DelegateUpdate::Cancelled);
                     break;
                 }
                 SasState::Started { .. } | SasState::Accepted { .. } | SasState::Confirmed => (),
@@ -0,0 +1,155 @@
+use std::{collections::HashMap, sync::Arc};
+
+use matrix_sdk::{
+    ruma::{
+        api::client::space::{get_hierarchy, SpaceHierarchyRoomsChunk},
+        assign,
+        room::RoomType,
+        OwnedRoomId, OwnedServerName, RoomId,
+    },
+    Client as MatrixClient,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    error::ClientError,
+    room::{Membership, Room},
+};
+
+/// A room found in the hierarchy of a space.
+#[derive(uniffi::Record)]
+pub struct SpaceHierarchyRoom {
+    pub room_id: String,
+    pub name: Option<String>,
+    pub topic: Option<String>,
+    pub canonical_alias: Option<String>,
+    pub avatar_url: Option<String>,
+    pub num_joined_members: u64,
+    pub world_readable: bool,
+    pub guest_can_join: bool,
+    pub is_space: bool,
+    /// The membership of the current user, if the room is known locally.
+    pub membership: Option<Membership>,
+    /// Whether the parent space suggests its members to join the room.
+    pub suggested: bool,
+    /// The IDs of the children of the room, if it is a space.
+    pub children_ids: Vec<String>,
+}
+
+/// What a parent space declares about one of its children.
+struct SpaceChildInfo {
+    via: Vec<OwnedServerName>,
+    suggested: bool,
+}
+
+#[derive(Default)]
+struct PaginationState {
+    next_batch: Option<String>,
+    is_at_end: bool,
+    /// The children declared by the spaces loaded so far. A space always
+    /// comes before its children in the hierarchy, so they are known by the
+    /// time the children are loaded.
+    children: HashMap<OwnedRoomId, SpaceChildInfo>,
+}
+
+/// The hierarchy of a space, loaded page by page from the homeserver.
+///
+/// The rooms are returned in depth-first order, starting with the space
+/// itself, and include the rooms the user hasn't joined.
+#[derive(uniffi::Object)]
+pub struct SpaceHierarchy {
+    client: MatrixClient,
+    space_id: OwnedRoomId,
+    suggested_only: bool,
+    state: Mutex<PaginationState>,
+}
+
+impl SpaceHierarchy {
+    pub(crate) fn new(client: MatrixClient, space_id: OwnedRoomId, suggested_only: bool) -> Self {
+        Self { client, space_id, suggested_only, state: Default::default() }
+    }
+
+    fn room(
+        &self,
+        chunk: SpaceHierarchyRoomsChunk,
+        state: &mut PaginationState,
+    ) -> SpaceHierarchyRoom {
+        let children_ids = chunk
+            .children_state
+            .iter()
+            .filter_map(|event| event.deserialize().ok())
+            // Children without servers to join them through are invalid.
+            .filter(|event| !event.content.via.is_empty())
+            .map(|event| {
+                let child_id = event.state_key.to_string();
+                state.children.insert(
+                    event.state_key,
+                    SpaceChildInfo { via: event.content.via, suggested: event.content.suggested },
+                );
+                child_id
+            })
+            .collect();
+
+        SpaceHierarchyRoom {
+            suggested: state.children.get(&chunk.room_id).is_some_and(|child| child.suggested),
+            membership: self.client.get_room(&chunk.room_id).map(|room| room.state().into()),
+            room_id: chunk.room_id.to_string(),
+            name: chunk.name,
+            topic: chunk.topic,
+            canonical_alias: chunk.canonical_alias.map(|alias| alias.to_string()),
+            avatar_url: chunk.avatar_url.map(|url| url.to_string()),
+            num_joined_members: chunk.num_joined_members.into(),
+            world_readable: chunk.world_readable,
+            guest_can_join: chunk.guest_can_join,
+            is_space: chunk.room_type == Some(RoomType::Space),
+            children_ids,
+        }
+    }
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl SpaceHierarchy {
+    pub fn space_id(&self) -> String {
+        self.space_id.to_string()
+    }
+
+    /// Load the next page of the hierarchy.
+    ///
+    /// Returns an empty list once the whole hierarchy has been loaded.
+    pub async fn paginate(&self, limit: u32) -> Result<Vec<SpaceHierarchyRoom>, ClientError> {
+        let mut state = self.state.lock().await;
+
+        if state.is_at_end {
+            return Ok(Vec::new());
+        }
+
+        let request = assign!(get_hierarchy::v1::Request::new(self.space_id.clone()), {
+            from: state.next_batch.clone(),
+            limit: Some(limit.into()),
+            suggested_only: self.suggested_only,
+        });
+        let response = self.client.send(request, None).await?;
+
+        state.is_at_end = response.next_batch.is_none();
+        state.next_batch = response.next_batch;
+
+        Ok(response.rooms.into_iter().map(|chunk| self.room(chunk, &mut state)).collect())
+    }
+
+    pub async fn is_at_end(&self) -> bool {
+        self.state.lock().await.is_at_end
+    }
+
+    /// Join a room of the hierarchy, through the servers declared by its
+    /// parent space.
+    pub async fn join_room(&self, room_id: String) -> Result<Arc<Room>, ClientError> {
+        let room_id = RoomId::parse(room_id)?;
+        let via = match self.state.lock().await.children.get(&room_id) {
+            Some(child) => child.via.clone(),
+            None => Vec::new(),
+        };
+
+        let room = self.client.join_room_by_id_or_alias((&*room_id).into(), &via).await?;
+        Ok(Arc::new(Room::new(room)))
+    }
+}
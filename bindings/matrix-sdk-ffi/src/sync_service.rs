@@ -32,6 +32,7 @@ pub enum SyncServiceState {
     Running,
     Terminated,
     Error,
+    Paused,
 }
 
 impl From<MatrixSyncServiceState> for SyncServiceState {
@@ -41,6 +42,7 @@ impl From<MatrixSyncServiceState> for SyncServiceState {
             MatrixSyncServiceState::Running => Self::Running,
             MatrixSyncServiceState::Terminated => Self::Terminated,
             MatrixSyncServiceState::Error => Self::Error,
+            MatrixSyncServiceState::Paused => Self::Paused,
         }
     }
 }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use matrix_sdk_ui::timeline::{PollResult, TimelineDetails};
 use tracing::warn;
@@ -40,6 +40,14 @@ impl TimelineItemContent {
                 }
             }
             Content::Poll(poll_state) => TimelineItemContentKind::from(poll_state.results()),
+            Content::Voice(voice) => TimelineItemContentKind::Voice {
+                duration: voice.duration(),
+                waveform: voice
+                    .waveform()
+                    .iter()
+                    .map(|amplitude| u16::try_from(amplitude.get()).unwrap_or(0))
+                    .collect(),
+            },
             Content::UnableToDecrypt(msg) => {
                 TimelineItemContentKind::UnableToDecrypt { msg: EncryptedMessage::new(msg) }
             }
@@ -85,11 +93,22 @@ impl TimelineItemContent {
                     error: error.to_string(),
                 }
             }
+            Content::Custom(custom) => TimelineItemContentKind::Custom {
+                event_type: custom.event_type().to_owned(),
+                state_key: custom.state_key().map(ToOwned::to_owned),
+            },
         }
     }
 
+    /// Get the message of this content, if it is a message or a voice
+    /// message.
     pub fn as_message(self: Arc<Self>) -> Option<Arc<Message>> {
         use matrix_sdk_ui::timeline::TimelineItemContent as Content;
+
+        if let Content::Voice(voice) = &self.0 {
+            return Some(Arc::new(Message(voice.message().clone())));
+        }
+
         unwrap_or_clone_arc_into_variant!(self, .0, Content::Message(msg) => Arc::new(Message(msg)))
     }
 }
@@ -109,9 +128,17 @@ pub enum TimelineItemContentKind {
         max_selections: u64,
         answers: Vec<PollAnswer>,
         votes: HashMap<String, Vec<String>>,
+        /// The IDs of the answers the current user voted for.
+        own_votes: Vec<String>,
         end_time: Option<u64>,
         has_been_edited: bool,
     },
+    /// A voice message, whose message can be retrieved with
+    /// [`TimelineItemContent::as_message()`].
+    Voice {
+        duration: Option<Duration>,
+        waveform: Vec<u16>,
+    },
     UnableToDecrypt {
         msg: EncryptedMessage,
     },
@@ -138,6 +165,10 @@ pub enum TimelineItemContentKind {
         state_key: String,
         error: String,
     },
+    Custom {
+        event_type: String,
+        state_key: Option<String>,
+    },
 }
 
 #[derive(Clone, uniffi::Object)]
@@ -378,6 +409,7 @@ impl From<&matrix_sdk_ui::timeline::AnyOtherFullStateEventContent> for OtherStat
 pub struct PollAnswer {
     pub id: String,
     pub text: String,
+    pub vote_count: u64,
 }
 
 impl From<PollResult> for TimelineItemContentKind {
@@ -389,9 +421,10 @@ impl From<PollResult> for TimelineItemContentKind {
             answers: value
                 .answers
                 .into_iter()
-                .map(|i| PollAnswer { id: i.id, text: i.text })
+                .map(|i| PollAnswer { id: i.id, text: i.text, vote_count: i.vote_count })
                 .collect(),
             votes: value.votes,
+            own_votes: value.own_votes,
             end_time: value.end_time,
             has_been_edited: value.has_been_edited,
         }
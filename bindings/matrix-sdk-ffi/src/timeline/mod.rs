@@ -26,7 +26,6 @@ use matrix_sdk_ui::timeline::{BackPaginationStatus, EventItemOrigin, Profile, Ti
 use mime::Mime;
 use ruma::{
     events::{
-        location::{AssetType as RumaAssetType, LocationContent, ZoomLevel},
         poll::{
             unstable_end::UnstablePollEndEventContent,
             unstable_response::UnstablePollResponseEventContent,
@@ -37,10 +36,7 @@ use ruma::{
         },
         receipt::ReceiptThread,
         relation::Annotation,
-        room::message::{
-            ForwardThread, LocationMessageEventContent, MessageType,
-            RoomMessageEventContentWithoutRelation,
-        },
+        room::message::{ForwardThread, RoomMessageEventContentWithoutRelation},
         AnyMessageLikeEventContent,
     },
     EventId,
@@ -56,7 +52,9 @@ use crate::{
     client::ProgressWatcher,
     error::{ClientError, RoomError},
     helpers::unwrap_or_clone_arc,
-    ruma::{AssetType, AudioInfo, FileInfo, ImageInfo, PollKind, ThumbnailInfo, VideoInfo},
+    ruma::{
+        AssetType, AudioInfo, FileInfo, ImageInfo, MessageType, PollKind, ThumbnailInfo, VideoInfo,
+    },
     task_handle::TaskHandle,
     RUNTIME,
 };
@@ -112,14 +110,14 @@ impl Timeline {
         progress_watcher: Option<Box<dyn ProgressWatcher>>,
     ) -> Result<(), RoomError> {
         let request = self.inner.send_attachment(url, mime_type, attachment_config);
-        if let Some(progress_watcher) = progress_watcher {
+        let _progress_task = progress_watcher.map(|progress_watcher| {
             let mut subscriber = request.subscribe_to_send_progress();
-            RUNTIME.spawn(async move {
+            TaskHandle::new(RUNTIME.spawn(async move {
                 while let Some(progress) = subscriber.next().await {
                     progress_watcher.transmission_progress(progress.into());
                 }
-            });
-        }
+            }))
+        });
 
         request.await.map_err(|_| RoomError::FailedSendingAttachment)?;
         Ok(())
@@ -196,6 +194,28 @@ impl Timeline {
         })
     }
 
+    /// Send a read receipt for the thread with the given root.
+    pub fn send_threaded_read_receipt(
+        &self,
+        receipt_type: ReceiptType,
+        thread_root: String,
+        event_id: String,
+    ) -> Result<(), ClientError> {
+        let thread_root = EventId::parse(thread_root)?;
+        let event_id = EventId::parse(event_id)?;
+
+        RUNTIME.block_on(async {
+            self.inner
+                .send_single_receipt(
+                    receipt_type.into(),
+                    ReceiptThread::Thread(thread_root),
+                    event_id,
+                )
+                .await?;
+            Ok(())
+        })
+    }
+
     pub fn send(self: Arc<Self>, msg: Arc<RoomMessageEventContentWithoutRelation>) {
         RUNTIME.spawn(async move {
             self.inner.send((*msg).to_owned().with_relation(None).into()).await;
@@ -438,30 +458,46 @@ impl Timeline {
     }
 
     pub fn send_location(
-        self: Arc<Self>,
-        body: String,
+        &self,
         geo_uri: String,
         description: Option<String>,
         zoom_level: Option<u8>,
         asset_type: Option<AssetType>,
-    ) {
-        let mut location_event_message_content =
-            LocationMessageEventContent::new(body, geo_uri.clone());
+    ) -> Result<(), ClientError> {
+        RUNTIME.block_on(async {
+            self.inner
+                .send_location(&geo_uri, description, zoom_level, asset_type.map(Into::into))
+                .await?;
+            Ok(())
+        })
+    }
 
-        if let Some(asset_type) = asset_type {
-            location_event_message_content =
-                location_event_message_content.with_asset_type(RumaAssetType::from(asset_type));
-        }
+    pub fn send_sticker(self: Arc<Self>, body: String, info: ImageInfo, url: String) {
+        RUNTIME.spawn(async move {
+            self.inner.send_sticker(body, info.into(), url.into()).await;
+        });
+    }
 
-        let mut location_content = LocationContent::new(geo_uri);
-        location_content.description = description;
-        location_content.zoom_level = zoom_level.and_then(ZoomLevel::new);
-        location_event_message_content.location = Some(location_content);
+    /// Forward the given event to the room of the `target` timeline.
+    pub fn forward(
+        &self,
+        item: Arc<EventTimelineItem>,
+        target: Arc<Timeline>,
+    ) -> Result<(), ClientError> {
+        RUNTIME.block_on(async {
+            self.inner.forward(&item.0, &target.inner).await?;
+            Ok(())
+        })
+    }
 
-        let room_message_event_content = RoomMessageEventContentWithoutRelation::new(
-            MessageType::Location(location_event_message_content),
-        );
-        self.send(Arc::new(room_message_event_content))
+    pub fn fetch_edit_history(
+        &self,
+        item: Arc<EventTimelineItem>,
+    ) -> Result<Vec<EditHistoryItem>, ClientError> {
+        RUNTIME.block_on(async {
+            let history = self.inner.fetch_edit_history(&item.0).await?;
+            Ok(history.into_iter().map(Into::into).collect())
+        })
     }
 
     pub fn toggle_reaction(&self, event_id: String, key: String) -> Result<(), ClientError> {
@@ -845,6 +881,25 @@ impl From<ruma::events::receipt::Receipt> for Receipt {
     }
 }
 
+#[derive(uniffi::Record)]
+pub struct EditHistoryItem {
+    pub event_id: String,
+    pub sender: String,
+    pub timestamp: u64,
+    pub content: MessageType,
+}
+
+impl From<matrix_sdk_ui::timeline::EditHistoryItem> for EditHistoryItem {
+    fn from(value: matrix_sdk_ui::timeline::EditHistoryItem) -> Self {
+        Self {
+            event_id: value.event_id.to_string(),
+            sender: value.sender.to_string(),
+            timestamp: value.timestamp.0.into(),
+            content: value.content.into(),
+        }
+    }
+}
+
 #[derive(uniffi::Record)]
 pub struct EventTimelineItemDebugInfo {
     model: String,
@@ -991,7 +1046,7 @@ impl From<ReceiptType> for ruma::api::client::receipt::create_receipt::v3::Recei
     fn from(value: ReceiptType) -> Self {
         match value {
             ReceiptType::Read => Self::Read,
-            ReceiptType::ReadPrivate => Self::Read,
+            ReceiptType::ReadPrivate => Self::ReadPrivate,
             ReceiptType::FullyRead => Self::FullyRead,
         }
     }
@@ -0,0 +1,94 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::uri::UriTarget as SdkUriTarget;
+use ruma::{EventId, OwnedServerName, RoomOrAliasId, ServerName, UserId};
+
+use crate::error::ClientError;
+
+/// The target of a `matrix.to` link or a `matrix:` URI.
+#[derive(uniffi::Enum)]
+pub enum UriTarget {
+    /// A room, by ID or alias.
+    Room { room: String, via: Vec<String> },
+    /// An event in a room, by ID or alias.
+    Event { room: String, event_id: String, via: Vec<String> },
+    /// A user.
+    User { user_id: String },
+}
+
+impl From<SdkUriTarget> for UriTarget {
+    fn from(value: SdkUriTarget) -> Self {
+        match value {
+            SdkUriTarget::Room { room, via } => {
+                Self::Room { room: room.to_string(), via: via_to_strings(via) }
+            }
+            SdkUriTarget::Event { room, event_id, via } => Self::Event {
+                room: room.to_string(),
+                event_id: event_id.to_string(),
+                via: via_to_strings(via),
+            },
+            SdkUriTarget::User { user_id } => Self::User { user_id: user_id.to_string() },
+        }
+    }
+}
+
+impl TryFrom<UriTarget> for SdkUriTarget {
+    type Error = ClientError;
+
+    fn try_from(value: UriTarget) -> Result<Self, Self::Error> {
+        Ok(match value {
+            UriTarget::Room { room, via } => {
+                Self::Room { room: RoomOrAliasId::parse(room)?, via: parse_via(via)? }
+            }
+            UriTarget::Event { room, event_id, via } => Self::Event {
+                room: RoomOrAliasId::parse(room)?,
+                event_id: EventId::parse(event_id)?,
+                via: parse_via(via)?,
+            },
+            UriTarget::User { user_id } => Self::User { user_id: UserId::parse(user_id)? },
+        })
+    }
+}
+
+fn via_to_strings(via: Vec<OwnedServerName>) -> Vec<String> {
+    via.into_iter().map(|server| server.to_string()).collect()
+}
+
+fn parse_via(via: Vec<String>) -> Result<Vec<OwnedServerName>, ClientError> {
+    Ok(via.into_iter().map(ServerName::parse).collect::<Result<_, _>>()?)
+}
+
+/// Parse a `matrix.to` link or a `matrix:` URI.
+///
+/// Returns `None` if the string is not a valid URI of either kind.
+#[uniffi::export]
+pub fn parse_matrix_uri(uri: String) -> Option<UriTarget> {
+    SdkUriTarget::parse(&uri).map(Into::into)
+}
+
+/// Construct the `matrix.to` link to the given target.
+#[uniffi::export]
+pub fn matrix_to_uri(target: UriTarget) -> Result<String, ClientError> {
+    Ok(SdkUriTarget::try_from(target)?.matrix_to_uri().to_string())
+}
+
+/// Construct the `matrix:` URI to the given target.
+///
+/// `action` is whether the user should join the room, or start a chat with
+/// the user. It is ignored for an event.
+#[uniffi::export]
+pub fn matrix_uri(target: UriTarget, action: bool) -> Result<String, ClientError> {
+    Ok(SdkUriTarget::try_from(target)?.matrix_uri(action).to_string())
+}
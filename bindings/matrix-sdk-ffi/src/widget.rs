@@ -7,7 +7,7 @@ use matrix_sdk::{
 };
 use tracing::error;
 
-use crate::{room::Room, RUNTIME};
+use crate::{client::Client, error::ClientError, room::Room, RUNTIME};
 
 #[derive(uniffi::Record)]
 pub struct WidgetDriverAndHandle {
@@ -261,29 +261,234 @@ pub fn new_virtual_element_call_widget(
 /// adjusted
 #[uniffi::export]
 pub fn get_element_call_required_permissions() -> WidgetCapabilities {
-    use ruma::events::StateEventType;
-
-    WidgetCapabilities {
-        read: vec![
-            WidgetEventFilter::StateWithType { event_type: StateEventType::CallMember.to_string() },
-            WidgetEventFilter::StateWithType { event_type: StateEventType::RoomMember.to_string() },
-            WidgetEventFilter::MessageLikeWithType {
-                event_type: "org.matrix.rageshake_request".to_owned(),
-            },
-            WidgetEventFilter::MessageLikeWithType {
-                event_type: "io.element.call.encryption_keys".to_owned(),
-            },
-        ],
-        send: vec![
-            WidgetEventFilter::StateWithType { event_type: StateEventType::CallMember.to_string() },
-            WidgetEventFilter::StateWithType {
-                event_type: "org.matrix.rageshake_request".to_owned(),
-            },
-            WidgetEventFilter::StateWithType {
-                event_type: "io.element.call.encryption_keys".to_owned(),
-            },
-        ],
-        requires_client: true,
+    matrix_sdk::widget::element_call_capabilities().into()
+}
+
+/// Forget the capabilities that were approved and denied for the widget with
+/// the given ID, so the `WidgetCapabilitiesProvider` is asked about them again
+/// the next time they are requested.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn forget_widget_capabilities(
+    client: Arc<Client>,
+    widget_id: String,
+) -> Result<(), ClientError> {
+    Ok(matrix_sdk::widget::forget_capabilities(&client.inner, &widget_id).await?)
+}
+
+/// A widget of a room, as defined by its state event.
+#[derive(uniffi::Record)]
+pub struct RoomWidget {
+    pub widget_id: String,
+    /// The type of the widget, e.g. `m.jitsi` or `m.custom`.
+    pub widget_type: String,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    /// The user who sent the state event of the widget.
+    pub sender: String,
+    /// The URL of the widget, with the template variables of its data
+    /// resolved.
+    pub url: String,
+    /// The data of the widget, serialized as a JSON object.
+    pub data: String,
+    /// The data of the Jitsi conference, if this is a Jitsi conference widget.
+    pub jitsi: Option<JitsiWidgetData>,
+    /// The settings to run the widget with `make_widget_driver`, if its URL is
+    /// valid.
+    pub settings: Option<WidgetSettings>,
+}
+
+impl From<matrix_sdk::widget::RoomWidget> for RoomWidget {
+    fn from(value: matrix_sdk::widget::RoomWidget) -> Self {
+        let content = &value.content;
+        let url = content.url_with_data().map_or_else(|_| content.url.clone(), |url| url.into());
+
+        Self {
+            widget_id: value.id.clone(),
+            widget_type: content.widget_type.clone(),
+            name: content.name.clone(),
+            avatar_url: content.avatar_url.as_ref().map(ToString::to_string),
+            sender: value.sender.to_string(),
+            url,
+            data: serde_json::to_string(&content.data).unwrap_or_default(),
+            jitsi: content.jitsi_data().map(Into::into),
+            settings: value.settings().ok().map(Into::into),
+        }
+    }
+}
+
+/// The data of a Jitsi conference widget.
+#[derive(uniffi::Record)]
+pub struct JitsiWidgetData {
+    pub domain: String,
+    pub conference_id: String,
+    pub is_audio_only: bool,
+    pub room_name: Option<String>,
+    pub auth: Option<String>,
+}
+
+impl From<matrix_sdk::widget::JitsiWidgetData> for JitsiWidgetData {
+    fn from(value: matrix_sdk::widget::JitsiWidgetData) -> Self {
+        let matrix_sdk::widget::JitsiWidgetData {
+            domain,
+            conference_id,
+            is_audio_only,
+            room_name,
+            auth,
+        } = value;
+        Self { domain, conference_id, is_audio_only, room_name, auth }
+    }
+}
+
+/// Get the widgets of the given room, ordered by ID.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn get_room_widgets(room: Arc<Room>) -> Result<Vec<RoomWidget>, ClientError> {
+    let widgets = matrix_sdk::widget::RoomWidget::list(&room.inner).await?;
+    Ok(widgets.into_iter().map(Into::into).collect())
+}
+
+/// Add a widget to the given room, or replace the widget with the same ID.
+///
+/// # Arguments
+///
+/// * `data` - The data of the widget, as a JSON object, if any.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn add_room_widget(
+    room: Arc<Room>,
+    widget_id: String,
+    widget_type: String,
+    url: String,
+    name: Option<String>,
+    data: Option<String>,
+) -> Result<(), ClientError> {
+    let mut content = matrix_sdk::widget::WidgetEventContent::new(widget_type, url);
+    content.name = name;
+    content.creator_user_id = Some(room.inner.own_user_id().to_owned());
+    if let Some(data) = data {
+        content.data = serde_json::from_str(&data)?;
+    }
+
+    Ok(matrix_sdk::widget::RoomWidget::add(&room.inner, &widget_id, content).await?)
+}
+
+/// Remove the widget with the given ID from the given room.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn remove_room_widget(room: Arc<Room>, widget_id: String) -> Result<(), ClientError> {
+    Ok(matrix_sdk::widget::RoomWidget::remove(&room.inner, &widget_id).await?)
+}
+
+/// Options to embed Element Call in a room with `new_element_call_widget`.
+#[derive(uniffi::Record)]
+pub struct ElementCallOptions {
+    /// The url to the app.
+    ///
+    /// E.g. <https://call.element.io>, <https://call.element.dev>
+    pub element_call_url: String,
+
+    /// The widget id.
+    pub widget_id: String,
+
+    /// The url that is used as the target for the PostMessages sent
+    /// by the widget (to the client).
+    ///
+    /// Defaults to `element_call_url` for the non-iframe (dedicated webview)
+    /// usecase.
+    pub parent_url: Option<String>,
+
+    /// The font scale which will be used inside element call.
+    ///
+    /// Default: `1`
+    pub font_scale: Option<f64>,
+
+    /// The font to use, to adapt to the system font.
+    pub font: Option<String>,
+
+    /// Don't show the lobby and join the call immediately.
+    ///
+    /// Default: `false`
+    pub skip_lobby: Option<bool>,
+
+    /// The PostHog id to pass to element call.
+    ///
+    /// Must be `None` if the user opted out of analytics.
+    pub analytics_id: Option<String>,
+
+    /// The encryption system to use.
+    ///
+    /// Default: `PerParticipantKeys` in encrypted rooms, and `Unencrypted` in
+    /// other rooms.
+    pub encryption: Option<EncryptionSystem>,
+}
+
+impl From<ElementCallOptions> for matrix_sdk::widget::ElementCallOptions {
+    fn from(value: ElementCallOptions) -> Self {
+        Self {
+            element_call_url: value.element_call_url,
+            widget_id: value.widget_id,
+            parent_url: value.parent_url,
+            font_scale: value.font_scale,
+            font: value.font,
+            skip_lobby: value.skip_lobby,
+            analytics_id: value.analytics_id,
+            encryption: value.encryption.map(Into::into),
+        }
+    }
+}
+
+/// An Element Call widget for a room, ready to be embedded in a webview.
+///
+/// The webview must load `url()`, and the messages between the widget and the
+/// client must be forwarded with `handle()`, while `run()` is running.
+#[derive(uniffi::Object)]
+pub struct ElementCallWidget {
+    url: String,
+    handle: Arc<WidgetDriverHandle>,
+    inner: Mutex<Option<matrix_sdk::widget::ElementCallWidget>>,
+}
+
+/// Generate the settings and the url of an Element Call widget for the given
+/// room.
+#[uniffi::export(async_runtime = "tokio")]
+pub async fn new_element_call_widget(
+    room: Arc<Room>,
+    options: ElementCallOptions,
+    props: ClientProperties,
+) -> Result<Arc<ElementCallWidget>, ParseError> {
+    let widget =
+        matrix_sdk::widget::ElementCallWidget::new(&room.inner, options.into(), props.into())
+            .await?;
+
+    Ok(Arc::new(ElementCallWidget {
+        url: widget.url().to_string(),
+        handle: Arc::new(WidgetDriverHandle(widget.handle())),
+        inner: Mutex::new(Some(widget)),
+    }))
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl ElementCallWidget {
+    /// The url to load in the webview.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// The handle to forward the messages between the widget and the client.
+    pub fn handle(&self) -> Arc<WidgetDriverHandle> {
+        self.handle.clone()
+    }
+
+    /// Run the widget driver, granting the capabilities that Element Call
+    /// needs.
+    ///
+    /// Returns once the widget is disconnected.
+    pub async fn run(&self) {
+        let Some(widget) = self.inner.lock().unwrap().take() else {
+            error!("Can't call run multiple times on an ElementCallWidget");
+            return;
+        };
+
+        if let Err(()) = widget.run().await {
+            error!("The Element Call widget driver stopped with an error");
+        }
     }
 }
 
@@ -345,6 +550,13 @@ pub struct WidgetCapabilities {
     /// This means clients should not offer to open the widget in a separate
     /// browser/tab/webview that is not connected to the postmessage widget-api.
     pub requires_client: bool,
+    /// Types of the to-device messages that a widget wants to be able to send.
+    pub send_to_device: Vec<String>,
+    /// Whether a widget wants to be able to send delayed events.
+    pub send_delayed_event: bool,
+    /// Whether a widget wants to be able to cancel, restart or immediately
+    /// send its delayed events.
+    pub update_delayed_event: bool,
 }
 
 impl From<WidgetCapabilities> for matrix_sdk::widget::Capabilities {
@@ -353,6 +565,9 @@ impl From<WidgetCapabilities> for matrix_sdk::widget::Capabilities {
             read: value.read.into_iter().map(Into::into).collect(),
             send: value.send.into_iter().map(Into::into).collect(),
             requires_client: value.requires_client,
+            send_to_device: value.send_to_device.into_iter().map(Into::into).collect(),
+            send_delayed_event: value.send_delayed_event,
+            update_delayed_event: value.update_delayed_event,
         }
     }
 }
@@ -363,6 +578,9 @@ impl From<matrix_sdk::widget::Capabilities> for WidgetCapabilities {
             read: value.read.into_iter().map(Into::into).collect(),
             send: value.send.into_iter().map(Into::into).collect(),
             requires_client: value.requires_client,
+            send_to_device: value.send_to_device.iter().map(ToString::to_string).collect(),
+            send_delayed_event: value.send_delayed_event,
+            update_delayed_event: value.update_delayed_event,
         }
     }
 }
@@ -0,0 +1,61 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error::Error as StdError;
+
+use matrix_sdk::{ruma::IdParseError, ClientBuildError, HttpError};
+use thiserror::Error;
+
+/// Result type of the application service.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The errors of the application service.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The user isn't in the namespace of the application service.
+    #[error("the user is not in the namespace of the application service")]
+    UserNotInNamespace,
+
+    /// An error occurred in the SDK.
+    #[error(transparent)]
+    Sdk(#[from] matrix_sdk::Error),
+
+    /// An HTTP error occurred.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    /// The client of the application service couldn't be built.
+    #[error(transparent)]
+    ClientBuild(#[from] ClientBuildError),
+
+    /// An identifier couldn't be parsed.
+    #[error(transparent)]
+    IdParse(#[from] IdParseError),
+
+    /// A regular expression of the namespaces is invalid.
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
+    /// The registration couldn't be parsed or serialized.
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    /// An I/O error occurred.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// The webserver failed.
+    #[error("the webserver failed: {0}")]
+    Server(Box<dyn StdError + Send + Sync>),
+}
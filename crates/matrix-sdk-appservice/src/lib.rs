@@ -0,0 +1,508 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+#![cfg_attr(docsrs, feature(doc_auto_cfg))]
+#![warn(missing_docs, missing_debug_implementations)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::SocketAddr,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use matrix_sdk::{
+    appservice::VirtualUserBuilder,
+    config::RequestConfig,
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    ruma::{
+        api::{
+            appservice::event::push_events,
+            client::{
+                account::register::{self, LoginType},
+                error::ErrorKind,
+            },
+        },
+        assign, DeviceId, OwnedDeviceId, OwnedServerName, OwnedUserId, UserId,
+    },
+    Client, ClientBuilder, SessionMeta,
+};
+use regex::Regex;
+use tracing::{debug, warn};
+use url::Url;
+
+mod error;
+pub mod registration;
+mod webserver;
+
+pub use error::{Error, Result};
+pub use matrix_sdk;
+pub use registration::AppServiceRegistration;
+
+/// The number of transaction IDs that are remembered to ignore the
+/// transactions that the homeserver sends again.
+const TRANSACTION_IDS_CAPACITY: usize = 100;
+
+/// The key of the custom value of the store holding the ID of the device of the
+/// main client.
+const DEVICE_ID_KEY: &[u8] = b"appservice_device_id";
+
+/// An application service, acting as its main user and the users of its
+/// namespace.
+///
+/// It is cheap to clone, all the clones share the same state.
+#[derive(Clone)]
+pub struct AppService {
+    inner: Arc<AppServiceInner>,
+}
+
+struct AppServiceInner {
+    registration: AppServiceRegistration,
+    user_namespaces: Vec<Regex>,
+    server_name: OwnedServerName,
+    client: Client,
+    virtual_users: StdMutex<HashMap<OwnedUserId, Client>>,
+    transaction_ids: StdMutex<VecDeque<String>>,
+}
+
+impl AppService {
+    /// Create a builder for an application service.
+    ///
+    /// # Arguments
+    ///
+    /// * `homeserver_url` - The URL of the homeserver.
+    ///
+    /// * `server_name` - The server name of the homeserver, used to build the
+    ///   IDs of the users.
+    ///
+    /// * `registration` - The registration of the application service.
+    pub fn builder(
+        homeserver_url: Url,
+        server_name: OwnedServerName,
+        registration: AppServiceRegistration,
+    ) -> AppServiceBuilder {
+        AppServiceBuilder::new(homeserver_url, server_name, registration)
+    }
+
+    /// Get the registration of the application service.
+    pub fn registration(&self) -> &AppServiceRegistration {
+        &self.inner.registration
+    }
+
+    /// Get the client of the main user of the application service, the one
+    /// with the `sender_localpart` of the registration.
+    ///
+    /// The events of all the transactions are passed to its event handlers.
+    pub fn client(&self) -> &Client {
+        &self.inner.client
+    }
+
+    /// Whether the given user is in the namespace of the application service.
+    pub fn is_user_in_namespace(&self, user_id: &UserId) -> bool {
+        user_id.server_name() == self.inner.server_name
+            && (user_id.localpart() == self.inner.registration.sender_localpart
+                || self.inner.user_namespaces.iter().any(|regex| regex.is_match(user_id.as_str())))
+    }
+
+    /// Get the client of the user with the given localpart.
+    ///
+    /// The client is created with the default settings of
+    /// [`Client::for_user()`] the first time, and reused afterwards. Use
+    /// [`AppService::virtual_user_builder()`] and
+    /// [`AppService::add_virtual_user()`] to configure it.
+    pub async fn virtual_user(&self, localpart: &str) -> Result<Client> {
+        let user_id = self.user_id(localpart)?;
+
+        if let Some(client) = self.inner.virtual_users.lock().unwrap().get(&user_id) {
+            return Ok(client.clone());
+        }
+
+        let client = self.inner.client.for_user(&user_id).build().await?;
+
+        Ok(self.inner.virtual_users.lock().unwrap().entry(user_id).or_insert(client).clone())
+    }
+
+    /// Create a builder for the client of the user with the given localpart.
+    ///
+    /// The client must be added with [`AppService::add_virtual_user()`] to
+    /// receive the transactions.
+    pub fn virtual_user_builder(&self, localpart: &str) -> Result<VirtualUserBuilder> {
+        let user_id = self.user_id(localpart)?;
+        Ok(self.inner.client.for_user(&user_id))
+    }
+
+    /// Add the client of a user of the namespace, replacing the existing one.
+    pub fn add_virtual_user(&self, client: Client) -> Result<()> {
+        let user_id = client.user_id().ok_or(matrix_sdk::Error::AuthenticationRequired)?;
+
+        if !self.is_user_in_namespace(user_id) {
+            return Err(Error::UserNotInNamespace);
+        }
+
+        self.inner.virtual_users.lock().unwrap().insert(user_id.to_owned(), client);
+        Ok(())
+    }
+
+    /// Register the user with the given localpart on the homeserver.
+    ///
+    /// Succeeds if the user already exists.
+    pub async fn register_virtual_user(&self, localpart: &str) -> Result<()> {
+        self.user_id(localpart)?;
+
+        let request = assign!(register::v3::Request::new(), {
+            username: Some(localpart.to_owned()),
+            login_type: Some(LoginType::ApplicationService),
+            inhibit_login: true,
+        });
+
+        // The token of the application service must be sent for this login type,
+        // even though the endpoint doesn't require authentication.
+        match self.inner.client.send(request, Some(RequestConfig::new().force_auth())).await {
+            Ok(_) => Ok(()),
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::UserInUse) => {
+                debug!(localpart, "The user is already registered");
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Process a transaction pushed by the homeserver.
+    ///
+    /// The transaction is passed to the main client, and to the clients of the
+    /// virtual users that it concerns. A transaction that is being processed
+    /// or that was processed successfully is ignored.
+    ///
+    /// This is called by the webserver, but can be used to receive the
+    /// transactions by other means.
+    pub async fn receive_transaction(&self, transaction: push_events::v1::Request) -> Result<()> {
+        let txn_id = transaction.txn_id.to_string();
+
+        // Reserve the transaction ID before processing it, so the same transaction
+        // received concurrently isn't processed twice.
+        {
+            let mut transaction_ids = self.inner.transaction_ids.lock().unwrap();
+
+            if transaction_ids.contains(&txn_id) {
+                debug!(txn_id, "Ignoring a transaction that was already received");
+                return Ok(());
+            }
+
+            if transaction_ids.len() == TRANSACTION_IDS_CAPACITY {
+                transaction_ids.pop_front();
+            }
+            transaction_ids.push_back(txn_id.clone());
+        }
+
+        if let Err(error) = self.inner.client.receive_transaction(&transaction).await {
+            // Forget the transaction, so it is processed when the homeserver sends it
+            // again.
+            self.inner.transaction_ids.lock().unwrap().retain(|id| *id != txn_id);
+            return Err(error.into());
+        }
+
+        let virtual_users: Vec<_> =
+            self.inner.virtual_users.lock().unwrap().values().cloned().collect();
+
+        for client in virtual_users {
+            if let Some(filtered) = client.filter_transaction(&transaction) {
+                if let Err(error) = client.receive_transaction(&filtered).await {
+                    warn!(
+                        user_id = ?client.user_id(),
+                        "Couldn't process a transaction for a virtual user: {error}"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the router of the webserver receiving the requests of the
+    /// homeserver, to serve it with other routes.
+    pub fn router(&self) -> axum::Router {
+        webserver::router(self.clone())
+    }
+
+    /// Run the webserver receiving the requests of the homeserver on the
+    /// given address.
+    ///
+    /// This only returns if the webserver fails.
+    pub async fn run(&self, addr: impl Into<SocketAddr>) -> Result<()> {
+        axum::Server::try_bind(&addr.into())
+            .map_err(|error| Error::Server(error.into()))?
+            .serve(self.router().into_make_service())
+            .await
+            .map_err(|error| Error::Server(error.into()))
+    }
+
+    fn user_id(&self, localpart: &str) -> Result<OwnedUserId> {
+        let user_id = UserId::parse_with_server_name(localpart, &self.inner.server_name)?;
+
+        if !self.is_user_in_namespace(&user_id) {
+            return Err(Error::UserNotInNamespace);
+        }
+
+        Ok(user_id)
+    }
+}
+
+impl fmt::Debug for AppService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppService")
+            .field("id", &self.inner.registration.id)
+            .field("server_name", &self.inner.server_name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for an [`AppService`].
+#[derive(Debug)]
+pub struct AppServiceBuilder {
+    homeserver_url: Url,
+    server_name: OwnedServerName,
+    registration: AppServiceRegistration,
+    client_builder: Option<ClientBuilder>,
+    device_id: Option<OwnedDeviceId>,
+}
+
+impl AppServiceBuilder {
+    /// Create a new builder.
+    ///
+    /// See [`AppService::builder()`] for the arguments.
+    pub fn new(
+        homeserver_url: Url,
+        server_name: OwnedServerName,
+        registration: AppServiceRegistration,
+    ) -> Self {
+        Self { homeserver_url, server_name, registration, client_builder: None, device_id: None }
+    }
+
+    /// Set the builder of the main client, to configure its stores or HTTP
+    /// settings.
+    ///
+    /// The URL of the homeserver is overridden.
+    pub fn client_builder(mut self, client_builder: ClientBuilder) -> Self {
+        self.client_builder = Some(client_builder);
+        self
+    }
+
+    /// Set the ID of the device of the main client.
+    ///
+    /// By default, a random device ID is generated the first time, and kept in
+    /// the store of the main client to be reused by the next builds.
+    pub fn device_id(mut self, device_id: &DeviceId) -> Self {
+        self.device_id = Some(device_id.to_owned());
+        self
+    }
+
+    /// Build the application service.
+    pub async fn build(self) -> Result<AppService> {
+        let Self { homeserver_url, server_name, registration, client_builder, device_id } = self;
+
+        let user_namespaces = registration
+            .namespaces
+            .users
+            .iter()
+            .map(|namespace| Regex::new(&format!("^{}$", namespace.regex)))
+            .collect::<Result<_, _>>()?;
+
+        let client = client_builder
+            .unwrap_or_else(Client::builder)
+            .homeserver_url(homeserver_url)
+            .build()
+            .await?;
+
+        let device_id = match device_id {
+            Some(device_id) => device_id,
+            None => stored_device_id(&client).await?,
+        };
+
+        let user_id =
+            UserId::parse_with_server_name(registration.sender_localpart.as_str(), &server_name)?;
+        client
+            .matrix_auth()
+            .restore_session(MatrixSession {
+                meta: SessionMeta { user_id, device_id },
+                tokens: MatrixSessionTokens {
+                    access_token: registration.as_token.clone(),
+                    refresh_token: None,
+                },
+            })
+            .await?;
+
+        Ok(AppService {
+            inner: Arc::new(AppServiceInner {
+                registration,
+                user_namespaces,
+                server_name,
+                client,
+                virtual_users: Default::default(),
+                transaction_ids: StdMutex::new(VecDeque::with_capacity(TRANSACTION_IDS_CAPACITY)),
+            }),
+        })
+    }
+}
+
+/// Get the ID of the device of the main client from its store, or generate a
+/// new one and store it.
+async fn stored_device_id(client: &Client) -> Result<OwnedDeviceId> {
+    let store = client.store();
+
+    if let Some(device_id) =
+        store.get_custom_value(DEVICE_ID_KEY).await.map_err(matrix_sdk::Error::from)?
+    {
+        match String::from_utf8(device_id) {
+            Ok(device_id) => return Ok(device_id.into()),
+            Err(error) => warn!("Ignoring an invalid stored device ID: {error}"),
+        }
+    }
+
+    let device_id = DeviceId::new();
+    store
+        .set_custom_value(DEVICE_ID_KEY, device_id.as_str().as_bytes().to_vec())
+        .await
+        .map_err(matrix_sdk::Error::from)?;
+
+    Ok(device_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use matrix_sdk::{
+        config::StoreConfig,
+        ruma::{
+            api::{
+                appservice::{event::push_events, Namespace, Namespaces},
+                MatrixVersion,
+            },
+            events::room::message::OriginalSyncRoomMessageEvent,
+            serde::Raw,
+            server_name, user_id, TransactionId,
+        },
+        Client, MemoryStore,
+    };
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path_regex},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{AppService, AppServiceBuilder, AppServiceRegistration, Error};
+
+    fn appservice_builder(server: &MockServer) -> AppServiceBuilder {
+        let mut namespaces = Namespaces::new();
+        namespaces.users.push(Namespace::new(true, "@_bridge_.*:example\\.org".to_owned()));
+        let registration = AppServiceRegistration::generate("bridge", None, "bridge", namespaces);
+
+        AppService::builder(
+            server.uri().parse().unwrap(),
+            server_name!("example.org").to_owned(),
+            registration,
+        )
+        .client_builder(Client::builder().server_versions([MatrixVersion::V1_0]))
+    }
+
+    pub(crate) async fn appservice(server: &MockServer) -> AppService {
+        appservice_builder(server).build().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn user_namespace() {
+        let server = MockServer::start().await;
+        let appservice = appservice(&server).await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/devices/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(&server)
+            .await;
+
+        assert!(appservice.is_user_in_namespace(user_id!("@bridge:example.org")));
+        assert!(appservice.is_user_in_namespace(user_id!("@_bridge_alice:example.org")));
+        assert!(!appservice.is_user_in_namespace(user_id!("@alice:example.org")));
+        assert!(!appservice.is_user_in_namespace(user_id!("@_bridge_alice:example.com")));
+
+        let client = appservice.virtual_user("_bridge_alice").await.unwrap();
+        assert_eq!(client.user_id(), Some(user_id!("@_bridge_alice:example.org")));
+
+        let error = appservice.virtual_user("alice").await.unwrap_err();
+        assert!(matches!(error, Error::UserNotInNamespace));
+    }
+
+    #[tokio::test]
+    async fn device_id_is_reused() {
+        let server = MockServer::start().await;
+        let store = Arc::new(MemoryStore::new());
+        let client_builder = || {
+            Client::builder()
+                .server_versions([MatrixVersion::V1_0])
+                .store_config(StoreConfig::new().state_store(store.clone()))
+        };
+
+        let appservice =
+            appservice_builder(&server).client_builder(client_builder()).build().await.unwrap();
+        let device_id = appservice.client().device_id().unwrap().to_owned();
+
+        let appservice =
+            appservice_builder(&server).client_builder(client_builder()).build().await.unwrap();
+        assert_eq!(appservice.client().device_id(), Some(&*device_id));
+    }
+
+    #[tokio::test]
+    async fn transaction_is_processed_once() {
+        let server = MockServer::start().await;
+        let appservice = appservice(&server).await;
+
+        let messages = Arc::new(AtomicUsize::new(0));
+        appservice.client().add_event_handler({
+            let messages = messages.clone();
+            move |_: OriginalSyncRoomMessageEvent| {
+                let messages = messages.clone();
+                async move {
+                    messages.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        let message = Raw::new(&json!({
+            "type": "m.room.message",
+            "sender": "@alice:example.org",
+            "room_id": "!room:example.org",
+            "event_id": "$message",
+            "origin_server_ts": 1,
+            "content": { "msgtype": "m.text", "body": "hello" },
+        }))
+        .unwrap()
+        .cast();
+        let transaction = push_events::v1::Request::new(TransactionId::new(), vec![message]);
+
+        // The same transaction received concurrently and then again.
+        let (first, second) = tokio::join!(
+            appservice.receive_transaction(transaction.clone()),
+            appservice.receive_transaction(transaction.clone()),
+        );
+        first.unwrap();
+        second.unwrap();
+        appservice.receive_transaction(transaction).await.unwrap();
+
+        assert_eq!(messages.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing and generation of the registration file of an application service.
+
+use std::{fs, ops::Deref, path::Path};
+
+use matrix_sdk::ruma::api::appservice::{Namespaces, Registration, RegistrationInit};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+use crate::Result;
+
+/// The length of the tokens generated for a registration.
+const TOKEN_LENGTH: usize = 64;
+
+/// The registration of an application service with a homeserver.
+///
+/// The homeserver must be configured with the same registration, usually as a
+/// YAML file.
+#[derive(Clone, Debug)]
+pub struct AppServiceRegistration {
+    inner: Registration,
+}
+
+impl AppServiceRegistration {
+    /// Generate a new registration, with random tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The unique ID of the application service.
+    ///
+    /// * `url` - The URL where the homeserver can reach the application
+    ///   service, or `None` if it doesn't need to receive transactions.
+    ///
+    /// * `sender_localpart` - The localpart of the main user of the application
+    ///   service.
+    ///
+    /// * `namespaces` - The users, aliases and rooms that the application
+    ///   service is interested in.
+    pub fn generate(
+        id: impl Into<String>,
+        url: Option<String>,
+        sender_localpart: impl Into<String>,
+        namespaces: Namespaces,
+    ) -> Self {
+        Registration::from(RegistrationInit {
+            id: id.into(),
+            url,
+            as_token: generate_token(),
+            hs_token: generate_token(),
+            sender_localpart: sender_localpart.into(),
+            namespaces,
+            rate_limited: Some(false),
+            protocols: None,
+        })
+        .into()
+    }
+
+    /// Parse a registration from a YAML string.
+    pub fn try_from_yaml_str(value: impl AsRef<str>) -> Result<Self> {
+        Ok(Self { inner: serde_yaml::from_str(value.as_ref())? })
+    }
+
+    /// Parse a registration from a YAML file.
+    pub fn try_from_yaml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        Ok(Self { inner: serde_yaml::from_reader(file)? })
+    }
+
+    /// Serialize this registration to a YAML string, to give it to the
+    /// homeserver.
+    pub fn to_yaml_string(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&self.inner)?)
+    }
+
+    /// Get the inner registration.
+    pub fn into_inner(self) -> Registration {
+        self.inner
+    }
+}
+
+impl From<Registration> for AppServiceRegistration {
+    fn from(value: Registration) -> Self {
+        Self { inner: value }
+    }
+}
+
+impl Deref for AppServiceRegistration {
+    type Target = Registration;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+fn generate_token() -> String {
+    thread_rng().sample_iter(Alphanumeric).take(TOKEN_LENGTH).map(char::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::api::appservice::{Namespace, Namespaces};
+
+    use super::AppServiceRegistration;
+
+    #[test]
+    fn yaml_roundtrip() {
+        let mut namespaces = Namespaces::new();
+        namespaces.users.push(Namespace::new(true, "@_bridge_.*:example\\.org".to_owned()));
+
+        let registration = AppServiceRegistration::generate(
+            "bridge",
+            Some("http://localhost:8080".to_owned()),
+            "bridge",
+            namespaces,
+        );
+        assert_eq!(registration.as_token.len(), 64);
+        assert_ne!(registration.as_token, registration.hs_token);
+
+        let yaml = registration.to_yaml_string().unwrap();
+        let parsed = AppServiceRegistration::try_from_yaml_str(yaml).unwrap();
+
+        assert_eq!(parsed.id, "bridge");
+        assert_eq!(parsed.url.as_deref(), Some("http://localhost:8080"));
+        assert_eq!(parsed.as_token, registration.as_token);
+        assert_eq!(parsed.hs_token, registration.hs_token);
+        assert_eq!(parsed.namespaces.users.len(), 1);
+        assert!(parsed.namespaces.users[0].exclusive);
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The webserver receiving the requests of the homeserver.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header::AUTHORIZATION, Method, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use matrix_sdk::ruma::api::{appservice::event::push_events, IncomingRequest};
+use serde_json::json;
+use tracing::warn;
+
+use crate::AppService;
+
+pub(crate) fn router(appservice: AppService) -> Router {
+    let routes = Router::new()
+        .route("/transactions/:txn_id", put(put_transaction))
+        .route("/users/:user_id", get(not_found))
+        .route("/rooms/:room_alias", get(not_found));
+
+    Router::new()
+        .nest("/_matrix/app/v1", routes.clone().route("/ping", post(ping)))
+        // The legacy paths, still used by some homeservers.
+        .merge(routes)
+        .layer(middleware::from_fn_with_state(appservice.clone(), authenticate))
+        .with_state(appservice)
+}
+
+/// Check that the request is sent with the token of the homeserver.
+async fn authenticate<B>(
+    State(appservice): State<AppService>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let header_token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query_token = request.uri().query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "access_token")
+            .map(|(_, value)| value.into_owned())
+    });
+
+    match header_token.map(ToOwned::to_owned).or(query_token) {
+        None => error_response(StatusCode::UNAUTHORIZED, "M_UNAUTHORIZED", "Missing token"),
+        Some(token) if !constant_time_eq(&token, &appservice.registration().hs_token) => {
+            error_response(StatusCode::FORBIDDEN, "M_FORBIDDEN", "Invalid token")
+        }
+        Some(_) => next.run(request).await,
+    }
+}
+
+/// Compare the tokens in a time that doesn't depend on their content, to not
+/// leak the expected token through timing.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+async fn put_transaction(
+    State(appservice): State<AppService>,
+    Path(txn_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    let mut request = Request::new(body);
+    *request.method_mut() = Method::PUT;
+
+    let transaction = match push_events::v1::Request::try_from_http_request(request, &[txn_id]) {
+        Ok(transaction) => transaction,
+        Err(error) => {
+            return error_response(StatusCode::BAD_REQUEST, "M_BAD_JSON", &error.to_string());
+        }
+    };
+
+    match appservice.receive_transaction(transaction).await {
+        Ok(()) => Json(json!({})).into_response(),
+        Err(error) => {
+            warn!("Couldn't process a transaction: {error}");
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "M_UNKNOWN", &error.to_string())
+        }
+    }
+}
+
+async fn ping() -> Response {
+    Json(json!({})).into_response()
+}
+
+/// The queries for users and room aliases aren't supported.
+async fn not_found() -> Response {
+    error_response(StatusCode::NOT_FOUND, "M_NOT_FOUND", "Not found")
+}
+
+fn error_response(status: StatusCode, errcode: &str, error: &str) -> Response {
+    (status, Json(json!({ "errcode": errcode, "error": error }))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+    use wiremock::MockServer;
+
+    use super::constant_time_eq;
+    use crate::tests::appservice;
+
+    #[test]
+    fn token_comparison() {
+        assert!(constant_time_eq("hs_token", "hs_token"));
+        assert!(!constant_time_eq("hs_token", "hs_tokeN"));
+        assert!(!constant_time_eq("hs_token", "hs_token2"));
+        assert!(!constant_time_eq("", "hs_token"));
+    }
+
+    fn transaction_request(uri: &str) -> Request<Body> {
+        let body = json!({ "events": [] });
+        Request::put(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn transaction_authentication() {
+        let server = MockServer::start().await;
+        let appservice = appservice(&server).await;
+        let hs_token = appservice.registration().hs_token.clone();
+
+        let response = appservice
+            .router()
+            .oneshot(transaction_request("/_matrix/app/v1/transactions/1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = appservice
+            .router()
+            .oneshot(transaction_request("/_matrix/app/v1/transactions/1?access_token=wrong"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let mut request = transaction_request("/_matrix/app/v1/transactions/1");
+        request
+            .headers_mut()
+            .insert("authorization", format!("Bearer {hs_token}").parse().unwrap());
+        let response = appservice.router().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = appservice
+            .router()
+            .oneshot(transaction_request(&format!("/transactions/2?access_token={hs_token}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -35,6 +35,7 @@ use ruma::events::{
 use ruma::{
     api::client::{self as api, push::get_notifications::v3::Notification},
     events::{
+        ignored_user_list::IgnoredUserListEventContent,
         push_rules::{PushRulesEvent, PushRulesEventContent},
         room::{
             member::{MembershipState, SyncRoomMemberEvent},
@@ -42,15 +43,15 @@ use ruma::{
         },
         AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
         AnySyncEphemeralRoomEvent, AnySyncMessageLikeEvent, AnySyncStateEvent,
-        AnySyncTimelineEvent, GlobalAccountDataEventType, StateEventType,
+        AnySyncTimelineEvent, GlobalAccountDataEvent, GlobalAccountDataEventType, StateEventType,
     },
     push::{Action, PushConditionRoomCtx, Ruleset},
     serde::Raw,
     MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UInt, UserId,
 };
-use tokio::sync::RwLock;
 #[cfg(feature = "e2e-encryption")]
 use tokio::sync::RwLockReadGuard;
+use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, info, instrument, trace, warn};
 
 #[cfg(all(feature = "e2e-encryption", feature = "experimental-sliding-sync"))]
@@ -62,6 +63,7 @@ use crate::{
     store::{
         ambiguity_map::AmbiguityCache, DynStateStore, MemoryStore, Result as StoreResult,
         StateChanges, StateStoreDataKey, StateStoreDataValue, StateStoreExt, Store, StoreConfig,
+        StoreError,
     },
     sync::{JoinedRoom, LeftRoom, Rooms, SyncResponse, Timeline},
     RoomStateFilter, SessionMeta,
@@ -90,6 +92,9 @@ pub struct BaseClient {
     olm_machine: Arc<RwLock<Option<OlmMachine>>>,
     /// Observable of when a user is ignored/unignored.
     pub(crate) ignore_user_list_changes: SharedObservable<()>,
+    /// A sender that is used to communicate the IDs of the rooms whose
+    /// `RoomInfo` changed after changes were applied.
+    pub(crate) room_info_updates: broadcast::Sender<BTreeSet<OwnedRoomId>>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -122,6 +127,7 @@ impl BaseClient {
             #[cfg(feature = "e2e-encryption")]
             olm_machine: Default::default(),
             ignore_user_list_changes: Default::default(),
+            room_info_updates: broadcast::Sender::new(100),
         }
     }
 
@@ -423,7 +429,7 @@ impl BaseClient {
     }
 
     #[instrument(skip_all, fields(room_id = ?room_info.room_id))]
-    pub(crate) fn handle_invited_state(
+    pub(crate) fn handle_stripped_state(
         &self,
         events: &[Raw<AnyStrippedStateEvent>],
         room_info: &mut RoomInfo,
@@ -510,10 +516,15 @@ impl BaseClient {
         &self,
         room_id: &RoomId,
         events: &[Raw<AnyRoomAccountDataEvent>],
+        room_info: &mut RoomInfo,
         changes: &mut StateChanges,
     ) {
         for raw_event in events {
             if let Ok(event) = raw_event.deserialize() {
+                if let AnyRoomAccountDataEvent::Tag(e) = &event {
+                    room_info.set_notable_tags(&e.content.tags);
+                }
+
                 changes.add_room_account_data(room_id, event, raw_event.clone());
             }
         }
@@ -637,11 +648,28 @@ impl BaseClient {
                 // We found an event we can decrypt
                 if let Ok(any_sync_event) = decrypted.event.deserialize() {
                     // We can deserialize it to find its type
-                    if let PossibleLatestEvent::YesRoomMessage(_) =
+                    if let PossibleLatestEvent::YesRoomMessage(_)
+                    | PossibleLatestEvent::YesPoll(_) =
                         is_suitable_for_latest_event(&any_sync_event)
                     {
-                        // The event is the right type for us to use as latest_event
-                        return Some((Box::new(LatestEvent::new(decrypted)), i));
+                        // The event is the right type for us to use as latest_event. Keep the
+                        // sender's profile with it, so that the preview can be displayed
+                        // without loading it again.
+                        let sender_profile = self
+                            .store
+                            .get_profile(room.room_id(), any_sync_event.sender())
+                            .await
+                            .ok()
+                            .flatten();
+
+                        return Some((
+                            Box::new(LatestEvent::new_with_sender_details(
+                                decrypted,
+                                sender_profile,
+                                None,
+                            )),
+                            i,
+                        ));
                     }
                 }
             }
@@ -670,6 +698,27 @@ impl BaseClient {
         Ok(room)
     }
 
+    /// User has knocked on a room.
+    ///
+    /// Update the internal and cached state accordingly. Return the final Room.
+    pub async fn room_knocked(&self, room_id: &RoomId) -> Result<Room> {
+        let room = self.store.get_or_create_room(room_id, RoomState::Knocked);
+        if room.state() != RoomState::Knocked {
+            let _sync_lock = self.sync_lock().read().await;
+
+            let mut room_info = room.clone_info();
+            room_info.mark_as_knocked();
+            room_info.mark_state_partially_synced();
+            room_info.mark_members_missing(); // the own member event changed
+            let mut changes = StateChanges::default();
+            changes.add_room(room_info.clone());
+            self.store.save_changes(&changes).await?; // Update the store
+            room.update_summary(room_info); // Update the cached room handle
+        }
+
+        Ok(room)
+    }
+
     /// User has left a room.
     ///
     /// Update the internal and cached state accordingly.
@@ -691,6 +740,47 @@ impl BaseClient {
         Ok(())
     }
 
+    /// Save the ignored users list of the current user, after it has been
+    /// changed locally, without waiting for the homeserver to send it back on
+    /// sync.
+    ///
+    /// The subscribers to the changes of the list are notified, and the
+    /// latest events of the rooms that were sent by a user who is now ignored
+    /// are forgotten, so the timelines and the room previews are updated
+    /// right away.
+    pub async fn receive_ignored_user_list(
+        &self,
+        content: IgnoredUserListEventContent,
+    ) -> Result<()> {
+        let mut changes = StateChanges::default();
+
+        #[cfg(feature = "experimental-sliding-sync")]
+        for room in self.store.get_rooms() {
+            let is_sender_ignored = room
+                .latest_event()
+                .and_then(|event| event.event().event.get_field::<OwnedUserId>("sender").ok()?)
+                .is_some_and(|sender| content.ignored_users.contains_key(&sender));
+
+            if is_sender_ignored {
+                let mut room_info = room.clone_info();
+                room_info.latest_event = None;
+                changes.add_room(room_info);
+            }
+        }
+
+        let event = GlobalAccountDataEvent { content };
+        changes.account_data.insert(
+            GlobalAccountDataEventType::IgnoredUserList,
+            Raw::new(&event).map_err(StoreError::Json)?.cast(),
+        );
+
+        let _sync_lock = self.sync_lock().write().await;
+        self.store.save_changes(&changes).await?;
+        self.apply_changes(&changes).await;
+
+        Ok(())
+    }
+
     /// Get access to the store's sync lock.
     pub fn sync_lock(&self) -> &RwLock<()> {
         self.store.sync_lock()
@@ -802,8 +892,13 @@ impl BaseClient {
                 )
                 .await?;
 
-            self.handle_room_account_data(&room_id, &new_info.account_data.events, &mut changes)
-                .await;
+            self.handle_room_account_data(
+                &room_id,
+                &new_info.account_data.events,
+                &mut room_info,
+                &mut changes,
+            )
+            .await;
 
             #[cfg(feature = "e2e-encryption")]
             if room_info.is_encrypted() {
@@ -873,8 +968,13 @@ impl BaseClient {
                 )
                 .await?;
 
-            self.handle_room_account_data(&room_id, &new_info.account_data.events, &mut changes)
-                .await;
+            self.handle_room_account_data(
+                &room_id,
+                &new_info.account_data.events,
+                &mut room_info,
+                &mut changes,
+            )
+            .await;
 
             changes.add_room(room_info);
             new_rooms.leave.insert(
@@ -889,13 +989,26 @@ impl BaseClient {
             room_info.mark_as_invited();
             room_info.mark_state_fully_synced();
 
-            self.handle_invited_state(&new_info.invite_state.events, &mut room_info, &mut changes);
+            self.handle_stripped_state(&new_info.invite_state.events, &mut room_info, &mut changes);
 
             changes.add_room(room_info);
 
             new_rooms.invite.insert(room_id, new_info);
         }
 
+        for (room_id, new_info) in response.rooms.knock {
+            let room = self.store.get_or_create_room(&room_id, RoomState::Knocked);
+            let mut room_info = room.clone_info();
+            room_info.mark_as_knocked();
+            room_info.mark_state_fully_synced();
+
+            self.handle_stripped_state(&new_info.knock_state.events, &mut room_info, &mut changes);
+
+            changes.add_room(room_info);
+
+            new_rooms.knock.insert(room_id, new_info);
+        }
+
         // TODO remove this, we're processing account data events here again
         // because we want to have the push rules in place before we process
         // rooms and their events, but we want to create the rooms before we
@@ -944,6 +1057,11 @@ impl BaseClient {
                 room.update_summary(room_info.clone())
             }
         }
+
+        if !changes.room_infos.is_empty() {
+            // It's fine if there are no receivers.
+            let _ = self.room_info_updates.send(changes.room_infos.keys().cloned().collect());
+        }
     }
 
     /// Receive a get member events response and convert it to a deserialized
@@ -1278,6 +1396,12 @@ impl BaseClient {
         self.ignore_user_list_changes.subscribe()
     }
 
+    /// Returns a receiver that gets the IDs of the rooms whose [`RoomInfo`]
+    /// changed, every time changes are applied (e.g. after a sync).
+    pub fn room_info_updates(&self) -> broadcast::Receiver<BTreeSet<OwnedRoomId>> {
+        self.room_info_updates.subscribe()
+    }
+
     pub(crate) fn deserialize_state_events(
         raw_events: &[Raw<AnySyncStateEvent>],
     ) -> Vec<(Raw<AnySyncStateEvent>, AnySyncStateEvent)> {
@@ -1303,17 +1427,22 @@ impl Default for BaseClient {
 #[cfg(test)]
 mod tests {
     use matrix_sdk_test::{
-        async_test, response_from_file, sync_timeline_event, InvitedRoomBuilder, JoinedRoomBuilder,
-        LeftRoomBuilder, StrippedStateTestEvent, SyncResponseBuilder,
+        async_test, response_from_file, sync_timeline_event, EventBuilder, InvitedRoomBuilder,
+        JoinedRoomBuilder, KnockedRoomBuilder, LeftRoomBuilder, StrippedStateTestEvent,
+        SyncLoadGenerator, SyncResponseBuilder,
     };
     use ruma::{
         api::{client as api, IncomingResponse},
+        events::receipt::{ReceiptThread, ReceiptType},
         room_id, user_id, RoomId, UserId,
     };
     use serde_json::json;
 
     use super::BaseClient;
-    use crate::{store::StateStoreExt, DisplayName, Room, RoomState, SessionMeta, StateChanges};
+    use crate::{
+        store::StateStoreExt, DisplayName, Room, RoomState, RoomStateFilter, SessionMeta,
+        StateChanges,
+    };
 
     #[async_test]
     async fn invite_after_leaving() {
@@ -1447,6 +1576,135 @@ mod tests {
         );
     }
 
+    #[async_test]
+    async fn knocked_room_from_sync() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!ithpyNKDtmhneaTQja:example.org");
+
+        let client = logged_in_client(user_id).await;
+
+        let response = api::sync::sync_events::v3::Response::try_from_http_response(
+            response_from_file(&json!({
+                "next_batch": "asdkl;fjasdkl;fj;asdkl;f",
+                "rooms": {
+                    "knock": {
+                        "!ithpyNKDtmhneaTQja:example.org": {
+                            "knock_state": {
+                                "events": [
+                                    {
+                                        "content": {
+                                            "join_rule": "knock"
+                                        },
+                                        "sender": "@test:example.org",
+                                        "state_key": "",
+                                        "type": "m.room.join_rules"
+                                    },
+                                    {
+                                        "content": {
+                                            "name": "Knock knock"
+                                        },
+                                        "sender": "@test:example.org",
+                                        "state_key": "",
+                                        "type": "m.room.name"
+                                    },
+                                    {
+                                        "content": {
+                                            "displayname": "alice",
+                                            "membership": "knock"
+                                        },
+                                        "sender": "@alice:example.org",
+                                        "state_key": "@alice:example.org",
+                                        "type": "m.room.member"
+                                    }
+                                ]
+                            }
+                        }
+                    }
+                }
+            })),
+        )
+        .expect("static json doesn't fail to parse");
+
+        let sync_response = client.receive_sync_response(response).await.unwrap();
+        assert!(sync_response.rooms.knock.contains_key(room_id));
+
+        let room = client.get_room(room_id).expect("Room not found");
+        assert_eq!(room.state(), RoomState::Knocked);
+        assert_eq!(room.name().as_deref(), Some("Knock knock"));
+        assert_eq!(client.get_rooms_filtered(RoomStateFilter::KNOCKED).len(), 1);
+        assert!(client.get_rooms_filtered(RoomStateFilter::INVITED).is_empty());
+    }
+
+    #[async_test]
+    async fn knocked_room_from_sync_builder() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!knock:example.org");
+
+        let client = logged_in_client(user_id).await;
+        let ev_builder = EventBuilder::new();
+
+        let response = SyncResponseBuilder::default()
+            .add_knocked_room(
+                KnockedRoomBuilder::new(room_id).add_state_bulk([
+                    StrippedStateTestEvent::Custom(json!({
+                        "content": { "name": "Knock knock" },
+                        "sender": "@test:example.org",
+                        "state_key": "",
+                        "type": "m.room.name",
+                    }))
+                    .into_raw_event(),
+                    ev_builder.make_stripped_knock(user_id),
+                ]),
+            )
+            .build_sync_response();
+        client.receive_sync_response(response).await.unwrap();
+
+        let room = client.get_room(room_id).expect("Room not found");
+        assert_eq!(room.state(), RoomState::Knocked);
+        assert_eq!(room.name().as_deref(), Some("Knock knock"));
+
+        // Joining the room replaces the knock.
+        let response = SyncResponseBuilder::default()
+            .add_knocked_room(KnockedRoomBuilder::new(room_id))
+            .add_joined_room(JoinedRoomBuilder::new(room_id))
+            .build_sync_response();
+        let sync_response = client.receive_sync_response(response).await.unwrap();
+        assert!(sync_response.rooms.knock.is_empty());
+        assert_eq!(room.state(), RoomState::Joined);
+    }
+
+    #[async_test]
+    async fn receive_generated_sync_load() {
+        let user_id = user_id!("@alice:example.org");
+        let client = logged_in_client(user_id).await;
+
+        let mut generator = SyncLoadGenerator::new(user_id)
+            .rooms(20)
+            .members_per_room(30)
+            .events_per_room(20)
+            .membership_churn(5)
+            .receipts_per_room(3);
+
+        for _ in 0..3 {
+            client.receive_sync_response(generator.build_sync_response()).await.unwrap();
+        }
+
+        assert_eq!(client.get_rooms_filtered(RoomStateFilter::JOINED).len(), 20);
+
+        let reader = &generator.member_ids()[0];
+        for room_id in generator.room_ids() {
+            let room = client.get_room(&room_id).expect("Room not found");
+
+            // The members that left joined again, besides the own user.
+            assert_eq!(room.joined_user_ids().await.unwrap().len(), 31);
+            assert!(room
+                .load_user_receipt(ReceiptType::Read, ReceiptThread::Unthreaded, reader)
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+
     #[cfg(all(feature = "e2e-encryption", feature = "experimental-sliding-sync"))]
     #[async_test]
     async fn when_there_are_no_latest_encrypted_events_decrypting_them_does_nothing() {
@@ -1,5 +1,7 @@
 //! Common types for [media content](https://matrix.org/docs/spec/client_server/r0.6.1#id66).
 
+use std::{path::PathBuf, time::Duration};
+
 use ruma::{
     api::client::media::get_content_thumbnail::v3::Method,
     events::{
@@ -12,8 +14,9 @@ use ruma::{
         },
         sticker::StickerEventContent,
     },
-    MxcUri, UInt,
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, UInt,
 };
+use serde::{Deserialize, Serialize};
 
 const UNIQUE_SEPARATOR: &str = "_";
 
@@ -99,6 +102,120 @@ impl UniqueKey for MediaRequest {
     }
 }
 
+/// The policy deciding which media content is kept in the cache of the state
+/// store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MediaRetentionPolicy {
+    /// The maximum size of the cache, in bytes.
+    ///
+    /// When the cache is bigger, the media content that was accessed the
+    /// longest time ago is removed first.
+    pub max_cache_size: Option<usize>,
+
+    /// The maximum size of a single media file that is cached, in bytes.
+    pub max_file_size: Option<usize>,
+
+    /// How long media content is kept after it was last accessed.
+    pub last_access_expiry: Option<Duration>,
+}
+
+impl MediaRetentionPolicy {
+    /// Create a policy without any limit.
+    pub fn empty() -> Self {
+        Self { max_cache_size: None, max_file_size: None, last_access_expiry: None }
+    }
+
+    /// Set the maximum size of the cache, in bytes.
+    pub fn with_max_cache_size(mut self, size: Option<usize>) -> Self {
+        self.max_cache_size = size;
+        self
+    }
+
+    /// Set the maximum size of a single cached file, in bytes.
+    pub fn with_max_file_size(mut self, size: Option<usize>) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Set how long media content is kept after it was last accessed.
+    pub fn with_last_access_expiry(mut self, duration: Option<Duration>) -> Self {
+        self.last_access_expiry = duration;
+        self
+    }
+
+    /// Whether a file of the given size is too big to be cached.
+    pub fn exceeds_max_file_size(&self, size: usize) -> bool {
+        self.max_file_size.is_some_and(|max_file_size| size > max_file_size)
+    }
+
+    /// Whether a cache of the given size is too big.
+    pub fn exceeds_max_cache_size(&self, size: usize) -> bool {
+        self.max_cache_size.is_some_and(|max_cache_size| size > max_cache_size)
+    }
+
+    /// Whether media content that was last accessed at `last_access` has
+    /// expired at `current_time`.
+    pub fn has_content_expired(
+        &self,
+        current_time: MilliSecondsSinceUnixEpoch,
+        last_access: MilliSecondsSinceUnixEpoch,
+    ) -> bool {
+        self.last_access_expiry.is_some_and(|expiry| {
+            let elapsed = u64::from(current_time.get()).saturating_sub(last_access.get().into());
+            u128::from(elapsed) > expiry.as_millis()
+        })
+    }
+}
+
+impl Default for MediaRetentionPolicy {
+    /// A cache of 400 MiB at most, with files of 20 MiB at most that are kept
+    /// for 60 days after they were last accessed.
+    fn default() -> Self {
+        Self {
+            max_cache_size: Some(400 * 1024 * 1024),
+            max_file_size: Some(20 * 1024 * 1024),
+            last_access_expiry: Some(Duration::from_secs(60 * 24 * 60 * 60)),
+        }
+    }
+}
+
+/// An upload of media that was started but that might not be complete, and that
+/// can be resumed.
+///
+/// The URI of the media is reserved on the homeserver before the content is
+/// uploaded, and the file the content is read from is remembered, so the upload
+/// can be started again after the application was restarted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingUpload {
+    /// The SHA-256 hash of the content, encoded as unpadded base64.
+    pub content_hash: String,
+
+    /// The size of the content, in bytes.
+    pub size: u64,
+
+    /// The MXC URI reserved for the content.
+    pub content_uri: OwnedMxcUri,
+
+    /// When the reservation of the URI expires if no content is uploaded to
+    /// it, if it does.
+    pub unused_expires_at: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// The content type of the media.
+    #[serde(default)]
+    pub content_type: Option<String>,
+
+    /// The path of the file the content is read from.
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+}
+
+impl PendingUpload {
+    /// Whether the reservation of the URI has expired at the given time.
+    pub fn has_expired(&self, current_time: MilliSecondsSinceUnixEpoch) -> bool {
+        self.unused_expires_at.is_some_and(|expires_at| expires_at <= current_time)
+    }
+}
+
 /// Trait for media event content.
 pub trait MediaEventContent {
     /// Get the source of the file for `Self`.
@@ -180,7 +297,7 @@ impl MediaEventContent for LocationMessageEventContent {
 
 #[cfg(test)]
 mod tests {
-    use ruma::mxc_uri;
+    use ruma::{mxc_uri, uint};
     use serde_json::json;
 
     use super::*;
@@ -220,4 +337,26 @@ mod tests {
 
         assert_eq!(file.uri(), mxc_uri);
     }
+
+    #[test]
+    fn test_media_retention_policy() {
+        let policy = MediaRetentionPolicy::empty()
+            .with_max_cache_size(Some(100))
+            .with_max_file_size(Some(10))
+            .with_last_access_expiry(Some(Duration::from_secs(60)));
+
+        assert!(!policy.exceeds_max_file_size(10));
+        assert!(policy.exceeds_max_file_size(11));
+        assert!(!policy.exceeds_max_cache_size(100));
+        assert!(policy.exceeds_max_cache_size(101));
+
+        let last_access = MilliSecondsSinceUnixEpoch(uint!(1_000));
+        assert!(!policy.has_content_expired(MilliSecondsSinceUnixEpoch(uint!(61_000)), last_access));
+        assert!(policy.has_content_expired(MilliSecondsSinceUnixEpoch(uint!(61_001)), last_access));
+
+        let empty = MediaRetentionPolicy::empty();
+        assert!(!empty.exceeds_max_file_size(usize::MAX));
+        assert!(!empty.exceeds_max_cache_size(usize::MAX));
+        assert!(!empty.has_content_expired(MilliSecondsSinceUnixEpoch(uint!(61_001)), last_access));
+    }
 }
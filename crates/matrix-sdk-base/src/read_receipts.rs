@@ -36,10 +36,17 @@
 //! `marks_as_unread` function shows the opiniated set of rules that will filter
 //! out uninterested events.
 //!
+//! Threaded read receipts are tracked separately: the number of unread
+//! messages and mentions of each thread are computed from the latest read
+//! receipt of the user in that thread, only looking at the events of that
+//! thread.
+//!
 //! The only public method in that module is [`compute_notifications`], which
 //! updates the `RoomInfo` in place according to the new counts.
 #![allow(dead_code)] // too many different build configurations, I give up
 
+use std::collections::{BTreeMap, BTreeSet};
+
 use eyeball_im::Vector;
 use matrix_sdk_common::deserialized_responses::SyncTimelineEvent;
 use ruma::{
@@ -75,6 +82,11 @@ pub(crate) struct RoomReadReceipts {
     /// compatibility with clients that have thread support) read receipt is
     /// attached to.
     latest_read_receipt_event_id: Option<OwnedEventId>,
+
+    /// The read receipts information of the threads of the room, by thread
+    /// root.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub threads: BTreeMap<OwnedEventId, ThreadReadReceipts>,
 }
 
 impl RoomReadReceipts {
@@ -143,6 +155,64 @@ impl RoomReadReceipts {
     }
 }
 
+/// Information about the threaded read receipts of a thread.
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+pub(crate) struct ThreadReadReceipts {
+    /// The number of unread messages in the thread.
+    pub num_unread: u64,
+
+    /// The number of unread messages causing highlights in the thread.
+    pub num_mentions: u64,
+
+    /// The id of the event the latest read receipt in this thread is attached
+    /// to.
+    latest_read_receipt_event_id: Option<OwnedEventId>,
+}
+
+impl ThreadReadReceipts {
+    fn update_for_event(&mut self, event: &SyncTimelineEvent, user_id: &UserId) -> bool {
+        let has_unread = marks_as_unread(&event.event, user_id);
+        if has_unread {
+            self.num_unread += 1;
+        }
+
+        let has_mention = event.push_actions.iter().any(|action| action.is_highlight());
+        if has_mention {
+            self.num_mentions += 1;
+        }
+
+        has_unread || has_mention
+    }
+
+    /// Same as [`RoomReadReceipts::find_and_count_events`], but only counting
+    /// the events of the thread with the given root.
+    fn find_and_count_events<'a>(
+        &mut self,
+        receipt_event_id: &EventId,
+        thread_root: &EventId,
+        user_id: &UserId,
+        events: impl IntoIterator<Item = &'a SyncTimelineEvent>,
+    ) -> bool {
+        let mut counting_receipts = false;
+
+        for event in events {
+            if counting_receipts {
+                if thread_root_of(&event.event).as_deref() == Some(thread_root) {
+                    self.update_for_event(event, user_id);
+                }
+            } else if let Ok(Some(event_id)) = event.event.get_field::<OwnedEventId>("event_id") {
+                if event_id == receipt_event_id {
+                    self.num_unread = 0;
+                    self.num_mentions = 0;
+                    counting_receipts = true;
+                }
+            }
+        }
+
+        counting_receipts
+    }
+}
+
 /// Provider for timeline events prior to the current sync.
 pub trait PreviousEventsProvider: Send + Sync {
     /// Returns the list of known timeline events, in sync order, for the given
@@ -175,6 +245,15 @@ pub(crate) fn compute_notifications<PEP: PreviousEventsProvider>(
     new_events: &[SyncTimelineEvent],
     read_receipts: &mut RoomReadReceipts,
 ) -> Result<bool> {
+    let threads_changed = compute_thread_notifications(
+        user_id,
+        room_id,
+        receipt_event,
+        previous_events_provider,
+        new_events,
+        read_receipts,
+    );
+
     let prev_latest_receipt_event_id = read_receipts.latest_read_receipt_event_id.clone();
 
     if let Some(receipt_event) = receipt_event {
@@ -254,7 +333,126 @@ pub(crate) fn compute_notifications<PEP: PreviousEventsProvider>(
         }
     }
 
-    Ok(new_receipt)
+    Ok(new_receipt || threads_changed)
+}
+
+/// Update the [`ThreadReadReceipts`] of the threads that have a new threaded
+/// read receipt or new events.
+///
+/// Returns whether the read receipts of a thread changed.
+fn compute_thread_notifications<PEP: PreviousEventsProvider>(
+    user_id: &UserId,
+    room_id: &RoomId,
+    receipt_event: Option<&ReceiptEventContent>,
+    previous_events_provider: &PEP,
+    new_events: &[SyncTimelineEvent],
+    read_receipts: &mut RoomReadReceipts,
+) -> bool {
+    let mut changed = false;
+    // The threads whose counts have been computed from a receipt, and don't need
+    // to account for the new events anymore.
+    let mut handled_threads = BTreeSet::new();
+
+    if let Some(receipt_event) = receipt_event {
+        for (thread_root, receipt_event_id) in user_thread_receipts(receipt_event, user_id) {
+            trace!("Got a new read receipt for thread {thread_root}");
+
+            let thread = read_receipts.threads.entry(thread_root.clone()).or_default();
+            thread.latest_read_receipt_event_id = Some(receipt_event_id.clone());
+            changed = true;
+
+            let found =
+                thread.find_and_count_events(&receipt_event_id, &thread_root, user_id, new_events)
+                    || thread.find_and_count_events(
+                        &receipt_event_id,
+                        &thread_root,
+                        user_id,
+                        previous_events_provider.for_room(room_id).iter().chain(new_events),
+                    );
+
+            if found {
+                handled_threads.insert(thread_root);
+            }
+        }
+    }
+
+    let new_thread_roots: BTreeSet<_> =
+        new_events.iter().filter_map(|event| thread_root_of(&event.event)).collect();
+
+    for thread_root in new_thread_roots {
+        if handled_threads.contains(&thread_root) {
+            continue;
+        }
+
+        let thread = read_receipts.threads.entry(thread_root.clone()).or_default();
+
+        // If the previous receipt refers to a new event, only count the events after
+        // it.
+        if let Some(receipt_event_id) = thread.latest_read_receipt_event_id.clone() {
+            if thread.find_and_count_events(&receipt_event_id, &thread_root, user_id, new_events) {
+                changed = true;
+                continue;
+            }
+        }
+
+        for event in new_events {
+            if thread_root_of(&event.event).as_deref() == Some(&*thread_root)
+                && thread.update_for_event(event, user_id)
+            {
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+/// Get the public and private threaded read receipts of the given user in the
+/// given receipt event, as `(thread_root, receipt_event_id)` pairs.
+fn user_thread_receipts(
+    receipt_event: &ReceiptEventContent,
+    user_id: &UserId,
+) -> BTreeMap<OwnedEventId, OwnedEventId> {
+    let mut receipts = BTreeMap::new();
+
+    for (event_id, event_receipts) in receipt_event.iter() {
+        for receipt_type in [ReceiptType::Read, ReceiptType::ReadPrivate] {
+            let Some(receipt) = event_receipts
+                .get(&receipt_type)
+                .and_then(|user_receipts| user_receipts.get(user_id))
+            else {
+                continue;
+            };
+
+            if let ReceiptThread::Thread(thread_root) = &receipt.thread {
+                receipts.insert(thread_root.clone(), event_id.clone());
+            }
+        }
+    }
+
+    receipts
+}
+
+/// Get the root of the thread the given event is in, if any.
+fn thread_root_of(event: &Raw<AnySyncTimelineEvent>) -> Option<OwnedEventId> {
+    #[derive(Deserialize)]
+    struct RelatesTo {
+        rel_type: Option<String>,
+        event_id: Option<OwnedEventId>,
+    }
+
+    #[derive(Deserialize)]
+    struct ContentParts {
+        #[serde(rename = "m.relates_to")]
+        relates_to: Option<RelatesTo>,
+    }
+
+    let relates_to = event.get_field::<ContentParts>("content").ok()??.relates_to?;
+    if relates_to.rel_type.as_deref() == Some("m.thread") {
+        relates_to.event_id
+    } else {
+        None
+    }
 }
 
 /// Is the event worth marking a room as unread?
@@ -354,9 +552,13 @@ mod tests {
 
     use matrix_sdk_common::deserialized_responses::SyncTimelineEvent;
     use matrix_sdk_test::sync_timeline_event;
-    use ruma::{event_id, push::Action, user_id, EventId, UserId};
+    use ruma::{
+        event_id, events::receipt::ReceiptEventContent, push::Action, room_id, user_id, EventId,
+        UserId,
+    };
+    use serde_json::{from_value as from_json_value, json};
 
-    use crate::read_receipts::{marks_as_unread, RoomReadReceipts};
+    use crate::read_receipts::{compute_notifications, marks_as_unread, RoomReadReceipts};
 
     #[test]
     fn test_room_message_marks_as_unread() {
@@ -588,6 +790,7 @@ mod tests {
             num_notifications: 13,
             num_mentions: 37,
             latest_read_receipt_event_id: None,
+            threads: Default::default(),
         };
         assert!(receipts
             .find_and_count_events(ev0, user_id, &[make_event(event_id!("$1"))],)
@@ -604,6 +807,7 @@ mod tests {
             num_notifications: 13,
             num_mentions: 37,
             latest_read_receipt_event_id: None,
+            threads: Default::default(),
         };
         assert!(receipts.find_and_count_events(ev0, user_id, &[make_event(ev0)]));
         assert_eq!(receipts.num_unread, 0);
@@ -617,6 +821,7 @@ mod tests {
             num_notifications: 13,
             num_mentions: 37,
             latest_read_receipt_event_id: None,
+            threads: Default::default(),
         };
         assert!(receipts
             .find_and_count_events(
@@ -640,6 +845,7 @@ mod tests {
             num_notifications: 13,
             num_mentions: 37,
             latest_read_receipt_event_id: None,
+            threads: Default::default(),
         };
         assert!(receipts.find_and_count_events(
             ev0,
@@ -655,4 +861,61 @@ mod tests {
         assert_eq!(receipts.num_notifications, 0);
         assert_eq!(receipts.num_mentions, 0);
     }
+
+    #[test]
+    fn test_compute_thread_notifications() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!room:example.org");
+        let root = event_id!("$root");
+
+        fn make_event(event_id: &str, thread_root: Option<&EventId>) -> SyncTimelineEvent {
+            let mut content = json!({ "body": "A", "msgtype": "m.text" });
+            if let Some(thread_root) = thread_root {
+                content["m.relates_to"] =
+                    json!({ "rel_type": "m.thread", "event_id": thread_root });
+            }
+
+            SyncTimelineEvent::new(sync_timeline_event!({
+                "sender": "@bob:example.org",
+                "type": "m.room.message",
+                "event_id": event_id,
+                "origin_server_ts": 12344446,
+                "content": content,
+            }))
+        }
+
+        let events = vec![
+            make_event("$root", None),
+            make_event("$t1", Some(root)),
+            make_event("$main", None),
+            make_event("$t2", Some(root)),
+            make_event("$t3", Some(root)),
+        ];
+
+        // Without receipts, all the events of the thread are unread.
+        let mut receipts = RoomReadReceipts::default();
+        assert!(compute_notifications(user_id, room_id, None, &(), &events, &mut receipts).unwrap());
+        assert_eq!(receipts.num_unread, 5);
+        assert_eq!(receipts.threads[root].num_unread, 3);
+
+        // A threaded receipt only resets the count of the thread.
+        let receipt_event: ReceiptEventContent = from_json_value(json!({
+            "$t2": {
+                "m.read.private": {
+                    "@alice:example.org": { "ts": 1436451550, "thread_id": "$root" },
+                },
+            },
+        }))
+        .unwrap();
+        let mut receipts = RoomReadReceipts::default();
+        compute_notifications(user_id, room_id, Some(&receipt_event), &(), &events, &mut receipts)
+            .unwrap();
+        assert_eq!(receipts.num_unread, 5);
+        assert_eq!(receipts.threads[root].num_unread, 1);
+
+        // New events in the thread are counted from the previous receipt.
+        let new_events = vec![make_event("$t4", Some(root)), make_event("$main2", None)];
+        compute_notifications(user_id, room_id, None, &(), &new_events, &mut receipts).unwrap();
+        assert_eq!(receipts.threads[root].num_unread, 2);
+    }
 }
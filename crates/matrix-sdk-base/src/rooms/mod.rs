@@ -11,7 +11,7 @@ use std::{
 
 use bitflags::bitflags;
 pub use members::RoomMember;
-pub use normal::{Room, RoomInfo, RoomState, RoomStateFilter};
+pub use normal::{Room, RoomInfo, RoomNotableTags, RoomState, RoomStateFilter};
 use ruma::{
     assign,
     events::{
@@ -27,6 +27,7 @@ use ruma::{
             join_rules::RoomJoinRulesEventContent,
             member::MembershipState,
             name::RoomNameEventContent,
+            pinned_events::RoomPinnedEventsEventContent,
             tombstone::RoomTombstoneEventContent,
             topic::RoomTopicEventContent,
         },
@@ -104,6 +105,9 @@ pub struct BaseRoomInfo {
     /// memberships.
     #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
     pub(crate) rtc_member: BTreeMap<OwnedUserId, MinimalStateEvent<CallMemberEventContent>>,
+    /// The events pinned in this room.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) pinned_events: Option<MinimalStateEvent<RoomPinnedEventsEventContent>>,
 }
 
 impl BaseRoomInfo {
@@ -175,6 +179,9 @@ impl BaseRoomInfo {
             AnySyncStateEvent::RoomPowerLevels(p) => {
                 self.max_power_level = p.power_levels().max().into();
             }
+            AnySyncStateEvent::RoomPinnedEvents(p) => {
+                self.pinned_events = Some(p.into());
+            }
             AnySyncStateEvent::CallMember(m) => {
                 let Some(o_ev) = m.as_original() else {
                     return false;
@@ -281,6 +288,8 @@ impl BaseRoomInfo {
             self.tombstone.as_mut().unwrap().redact(&room_version);
         } else if self.topic.has_event_id(redacts) {
             self.topic.as_mut().unwrap().redact(&room_version);
+        } else if self.pinned_events.has_event_id(redacts) {
+            self.pinned_events.as_mut().unwrap().redact(&room_version);
         } else {
             self.rtc_member.retain(|_, member_event| member_event.event_id() != Some(redacts));
         }
@@ -317,6 +326,7 @@ impl Default for BaseRoomInfo {
             tombstone: None,
             topic: None,
             rtc_member: BTreeMap::new(),
+            pinned_events: None,
         }
     }
 }
@@ -43,7 +43,7 @@ use ruma::{
             redaction::SyncRoomRedactionEvent,
             tombstone::RoomTombstoneEventContent,
         },
-        tag::Tags,
+        tag::{TagName, Tags},
         AnyRoomAccountDataEvent, AnyStrippedStateEvent, AnySyncStateEvent,
         RoomAccountDataEventType,
     },
@@ -105,7 +105,7 @@ pub struct RoomSummary {
 }
 
 /// Enum keeping track in which state the room is, e.g. if our own user is
-/// joined, invited, or has left the room.
+/// joined, invited, has knocked on, or has left the room.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum RoomState {
     /// The room is in a joined state.
@@ -114,17 +114,19 @@ pub enum RoomState {
     Left,
     /// The room is in a invited state.
     Invited,
+    /// The room is in a knocked state, i.e. our own user asked to join it.
+    Knocked,
 }
 
 impl From<&MembershipState> for RoomState {
     fn from(membership_state: &MembershipState) -> Self {
-        // We consider Ban, Knock and Leave to be Left, because they all mean we are not
-        // in the room.
+        // We consider Ban and Leave to be Left, because they both mean we are not in
+        // the room.
         match membership_state {
             MembershipState::Ban => Self::Left,
             MembershipState::Invite => Self::Invited,
             MembershipState::Join => Self::Joined,
-            MembershipState::Knock => Self::Left,
+            MembershipState::Knock => Self::Knocked,
             MembershipState::Leave => Self::Left,
             _ => panic!("Unexpected MembershipState: {}", membership_state),
         }
@@ -213,6 +215,42 @@ impl Room {
         self.inner.read().read_receipts.num_mentions
     }
 
+    /// Get the number of unread messages in the thread with the given root
+    /// (computed client-side), according to the threaded read receipts of the
+    /// user.
+    pub fn num_unread_messages_in_thread(&self, thread_root: &EventId) -> u64 {
+        self.inner
+            .read()
+            .read_receipts
+            .threads
+            .get(thread_root)
+            .map_or(0, |thread| thread.num_unread)
+    }
+
+    /// Get the number of unread mentions in the thread with the given root
+    /// (computed client-side), according to the threaded read receipts of the
+    /// user.
+    pub fn num_unread_mentions_in_thread(&self, thread_root: &EventId) -> u64 {
+        self.inner
+            .read()
+            .read_receipts
+            .threads
+            .get(thread_root)
+            .map_or(0, |thread| thread.num_mentions)
+    }
+
+    /// Get the roots of the threads of this room that have unread messages.
+    pub fn threads_with_unread_messages(&self) -> Vec<OwnedEventId> {
+        self.inner
+            .read()
+            .read_receipts
+            .threads
+            .iter()
+            .filter(|(_, thread)| thread.num_unread > 0)
+            .map(|(thread_root, _)| thread_root.clone())
+            .collect()
+    }
+
     /// Check if the room has its members fully synced.
     ///
     /// Members might be missing if lazy member loading was enabled for the
@@ -231,6 +269,19 @@ impl Room {
         })
     }
 
+    /// Check if the room was joined with a partial state, and the homeserver
+    /// doesn't know all of its state yet.
+    ///
+    /// This happens when the room was joined over federation with a faster
+    /// join ([MSC3706]). Until the room has its full state, the member list
+    /// and the values that depend on it, like the display name of the room,
+    /// are incomplete.
+    ///
+    /// [MSC3706]: https://github.com/matrix-org/matrix-spec-proposals/pull/3706
+    pub fn is_partial_state(&self) -> bool {
+        self.inner.read().partial_state
+    }
+
     /// Check if the room states have been synced
     ///
     /// States might be missing if we have only seen the room_id of this Room
@@ -298,7 +349,7 @@ impl Room {
     #[instrument(skip_all, fields(room_id = ?self.room_id))]
     pub async fn is_direct(&self) -> StoreResult<bool> {
         match self.state() {
-            RoomState::Joined | RoomState::Left => {
+            RoomState::Joined | RoomState::Left | RoomState::Knocked => {
                 Ok(!self.inner.read().base_info.dm_targets.is_empty())
             }
             RoomState::Invited => {
@@ -396,6 +447,12 @@ impl Room {
         self.inner.read().topic().map(ToOwned::to_owned)
     }
 
+    /// Get the IDs of the events pinned in this room, in the order of the
+    /// `m.room.pinned_events` state event.
+    pub fn pinned_event_ids(&self) -> Vec<OwnedEventId> {
+        self.inner.read().pinned_event_ids()
+    }
+
     /// Is there a non expired membership with application "m.call" and scope
     /// "m.room" in this room
     pub fn has_active_room_call(&self) -> bool {
@@ -414,6 +471,20 @@ impl Room {
         self.inner.read().active_room_call_participants()
     }
 
+    /// Returns the non expired memberships with application "m.call" and
+    /// scope "m.room" in this room, along with the ID of the user they belong
+    /// to.
+    ///
+    /// The vector is ordered by oldest membership user to newest.
+    pub fn active_room_call_memberships(&self) -> Vec<(OwnedUserId, Membership)> {
+        self.inner
+            .read()
+            .active_room_call_memberships()
+            .into_iter()
+            .map(|(user_id, membership)| (user_id, membership.clone()))
+            .collect()
+    }
+
     /// Return the cached display name of the room if it was provided via sync,
     /// or otherwise calculate it, taking into account its name, aliases and
     /// members.
@@ -460,6 +531,15 @@ impl Room {
         self.latest_encrypted_events.write().unwrap().drain(0..=index);
     }
 
+    /// Get the IDs of the members of this room that are known to the store,
+    /// with the given memberships.
+    pub async fn member_user_ids(
+        &self,
+        memberships: RoomMemberships,
+    ) -> StoreResult<Vec<OwnedUserId>> {
+        self.store.get_user_ids(self.room_id(), memberships).await
+    }
+
     /// Get the list of users ids that are considered to be joined members of
     /// this room.
     pub async fn joined_user_ids(&self) -> StoreResult<Vec<OwnedUserId>> {
@@ -470,7 +550,18 @@ impl Room {
     /// given memberships.
     pub async fn members(&self, memberships: RoomMemberships) -> StoreResult<Vec<RoomMember>> {
         let user_ids = self.store.get_user_ids(self.room_id(), memberships).await?;
+        self.members_with_user_ids(&user_ids).await
+    }
 
+    /// Get the `RoomMember`s of this room that are known to the store, among
+    /// the given users.
+    ///
+    /// This allows to load the members of big rooms in chunks, from the user
+    /// IDs returned by [`Room::member_user_ids()`].
+    pub async fn members_with_user_ids(
+        &self,
+        user_ids: &[OwnedUserId],
+    ) -> StoreResult<Vec<RoomMember>> {
         if user_ids.is_empty() {
             return Ok(Vec::new());
         }
@@ -479,18 +570,18 @@ impl Room {
             .store
             .get_state_events_for_keys_static::<RoomMemberEventContent, _, _>(
                 self.room_id(),
-                &user_ids,
+                user_ids,
             )
             .await?
             .into_iter()
             .map(|raw_event| raw_event.deserialize())
             .collect::<Result<Vec<_>, _>>()?;
 
-        let mut profiles = self.store.get_profiles(self.room_id(), &user_ids).await?;
+        let mut profiles = self.store.get_profiles(self.room_id(), user_ids).await?;
 
         let mut presences = self
             .store
-            .get_presence_events(&user_ids)
+            .get_presence_events(user_ids)
             .await?
             .into_iter()
             .filter_map(|e| {
@@ -547,7 +638,7 @@ impl Room {
     }
 
     async fn calculate_name(&self) -> StoreResult<DisplayName> {
-        let summary = {
+        let (summary, is_partial_state) = {
             let inner = self.inner.read();
 
             if let Some(name) = inner.name() {
@@ -558,13 +649,17 @@ impl Room {
                 let alias = alias.alias().trim();
                 return Ok(DisplayName::Aliased(alias.to_owned()));
             }
-            inner.summary.clone()
+            (inner.summary.clone(), inner.partial_state)
         };
 
         let is_own_member = |m: &RoomMember| m.user_id() == &*self.own_user_id;
         let is_own_user_id = |u: &str| u == self.own_user_id().as_str();
 
-        let members: Vec<RoomMember> = if summary.heroes.is_empty() {
+        let members: Vec<RoomMember> = if summary.heroes.is_empty() && is_partial_state {
+            // The members we know about are not representative of the room yet, so
+            // wait for the full state before using them.
+            Vec::new()
+        } else if summary.heroes.is_empty() {
             self.members(RoomMemberships::ACTIVE)
                 .await?
                 .into_iter()
@@ -592,6 +687,11 @@ impl Room {
                 // guessing
                 (members.len() as u64, 1u64)
             }
+            RoomState::Knocked => {
+                // we only have the stripped state of the room when we knocked, so we don't
+                // have a summary either
+                (members.len() as u64, 0u64)
+            }
             RoomState::Joined if summary.joined_member_count == 0 => {
                 // joined but the summary is not completed yet
                 (
@@ -696,6 +796,41 @@ impl Room {
         })
     }
 
+    /// Get the notable tags of this room, i.e. the tags that are relevant to
+    /// sort or filter rooms without having to load all the tags from the
+    /// store.
+    pub fn notable_tags(&self) -> RoomNotableTags {
+        self.inner.read().notable_tags
+    }
+
+    /// Whether this room has been tagged as a favourite.
+    pub fn is_favourite(&self) -> bool {
+        self.notable_tags().contains(RoomNotableTags::FAVOURITE)
+    }
+
+    /// Whether this room has been tagged as low priority.
+    pub fn is_low_priority(&self) -> bool {
+        self.notable_tags().contains(RoomNotableTags::LOW_PRIORITY)
+    }
+
+    /// The order of this room among the favourite rooms, if it has been
+    /// tagged as a favourite with an order.
+    ///
+    /// Rooms are sorted by ascending order, and rooms without an order come
+    /// after the others.
+    pub fn favourite_order(&self) -> Option<f64> {
+        self.inner.read().favourite_order
+    }
+
+    /// The order of this room among the low priority rooms, if it has been
+    /// tagged as low priority with an order.
+    ///
+    /// Rooms are sorted by ascending order, and rooms without an order come
+    /// after the others.
+    pub fn low_priority_order(&self) -> Option<f64> {
+        self.inner.read().low_priority_order
+    }
+
     /// Get the `Tags` for this room.
     pub async fn tags(&self) -> StoreResult<Option<Tags>> {
         if let Some(AnyRoomAccountDataEvent::Tag(event)) = self
@@ -759,6 +894,11 @@ pub struct RoomInfo {
     /// Flag remembering if the room members are synced.
     pub(crate) members_synced: bool,
 
+    /// Flag remembering if the room was joined with a partial state, and the
+    /// homeserver doesn't know its full state yet.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) partial_state: bool,
+
     /// The prev batch of this room we received during the last sync.
     pub(crate) last_prev_batch: Option<String>,
 
@@ -776,6 +916,19 @@ pub struct RoomInfo {
     #[serde(default)]
     pub(crate) read_receipts: RoomReadReceipts,
 
+    /// The notable tags of this room, extracted from its `m.tag` account
+    /// data.
+    #[serde(default)]
+    pub(crate) notable_tags: RoomNotableTags,
+
+    /// The order of the `m.favourite` tag of this room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) favourite_order: Option<f64>,
+
+    /// The order of the `m.lowpriority` tag of this room.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) low_priority_order: Option<f64>,
+
     /// Base room info which holds some basic event contents important for the
     /// room state.
     pub(crate) base_info: Box<BaseRoomInfo>,
@@ -807,16 +960,33 @@ impl RoomInfo {
             notification_counts: Default::default(),
             summary: Default::default(),
             members_synced: false,
+            partial_state: false,
             last_prev_batch: None,
             sync_info: SyncInfo::NoState,
             encryption_state_synced: false,
             #[cfg(feature = "experimental-sliding-sync")]
             latest_event: None,
             read_receipts: Default::default(),
+            notable_tags: Default::default(),
+            favourite_order: None,
+            low_priority_order: None,
             base_info: Box::new(BaseRoomInfo::new()),
         }
     }
 
+    /// Update the notable tags and their orders from the content of an
+    /// `m.tag` event.
+    pub(crate) fn set_notable_tags(&mut self, tags: &Tags) {
+        self.notable_tags = RoomNotableTags::from_tags(tags);
+        self.favourite_order = tags.get(&TagName::Favorite).and_then(|info| info.order);
+        self.low_priority_order = tags.get(&TagName::LowPriority).and_then(|info| info.order);
+    }
+
+    /// The notable tags of this room.
+    pub fn notable_tags(&self) -> RoomNotableTags {
+        self.notable_tags
+    }
+
     /// Mark this Room as joined.
     pub fn mark_as_joined(&mut self) {
         self.room_state = RoomState::Joined;
@@ -832,6 +1002,11 @@ impl RoomInfo {
         self.room_state = RoomState::Invited;
     }
 
+    /// Mark this Room as knocked.
+    pub fn mark_as_knocked(&mut self) {
+        self.room_state = RoomState::Knocked;
+    }
+
     /// Set the membership RoomState of this Room
     pub fn set_state(&mut self, room_state: RoomState) {
         self.room_state = room_state;
@@ -847,6 +1022,22 @@ impl RoomInfo {
         self.members_synced = false;
     }
 
+    /// Mark this Room as joined with a partial state.
+    pub fn mark_partial_state(&mut self) {
+        self.partial_state = true;
+    }
+
+    /// Mark this Room as having its full state known by the homeserver.
+    pub fn mark_full_state(&mut self) {
+        self.partial_state = false;
+    }
+
+    /// Whether this Room was joined with a partial state, and the homeserver
+    /// doesn't know its full state yet.
+    pub fn is_partial_state(&self) -> bool {
+        self.partial_state
+    }
+
     /// Mark this Room as still missing some state information.
     pub fn mark_state_partially_synced(&mut self) {
         self.sync_info = SyncInfo::PartiallySynced;
@@ -1080,6 +1271,16 @@ impl RoomInfo {
         Some(&self.base_info.topic.as_ref()?.as_original()?.content.topic)
     }
 
+    /// Get the IDs of the events pinned in this room.
+    pub fn pinned_event_ids(&self) -> Vec<OwnedEventId> {
+        self.base_info
+            .pinned_events
+            .as_ref()
+            .and_then(|ev| ev.as_original())
+            .map(|ev| ev.content.pinned.clone())
+            .unwrap_or_default()
+    }
+
     /// Get a list of all the valid (non expired) matrixRTC memberships and
     /// associated UserId's in this room.
     ///
@@ -1108,7 +1309,7 @@ impl RoomInfo {
     /// returns Memberships with application "m.call" and scope "m.room".
     ///
     /// The vector is ordered by oldest membership user to newest.
-    fn active_room_call_memberships(&self) -> Vec<(OwnedUserId, &Membership)> {
+    pub fn active_room_call_memberships(&self) -> Vec<(OwnedUserId, &Membership)> {
         self.active_matrix_rtc_memberships()
             .into_iter()
             .filter(|(_user_id, m)| m.is_room_call())
@@ -1182,6 +1383,49 @@ bitflags! {
         const INVITED  = 0b00000010;
         /// The room is in a left state.
         const LEFT     = 0b00000100;
+        /// The room is in a knocked state.
+        const KNOCKED  = 0b00001000;
+    }
+}
+
+bitflags! {
+    /// The tags of a room that are worth keeping in the [`RoomInfo`], as a
+    /// bitset.
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    pub struct RoomNotableTags: u8 {
+        /// The room has been tagged as a favourite (`m.favourite`).
+        const FAVOURITE    = 0b00000001;
+        /// The room has been tagged as low priority (`m.lowpriority`).
+        const LOW_PRIORITY = 0b00000010;
+    }
+}
+
+impl RoomNotableTags {
+    /// Compute the notable tags from the tags of a room.
+    pub fn from_tags(tags: &Tags) -> Self {
+        let mut notable_tags = Self::empty();
+
+        for tag in tags.keys() {
+            match tag {
+                TagName::Favorite => notable_tags.insert(Self::FAVOURITE),
+                TagName::LowPriority => notable_tags.insert(Self::LOW_PRIORITY),
+                _ => {}
+            }
+        }
+
+        notable_tags
+    }
+}
+
+impl Serialize for RoomNotableTags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RoomNotableTags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_bits_truncate(u8::deserialize(deserializer)?))
     }
 }
 
@@ -1196,6 +1440,7 @@ impl RoomStateFilter {
             RoomState::Joined => Self::JOINED,
             RoomState::Left => Self::LEFT,
             RoomState::Invited => Self::INVITED,
+            RoomState::Knocked => Self::KNOCKED,
         };
 
         self.contains(bit_state)
@@ -1214,6 +1459,9 @@ impl RoomStateFilter {
         if self.contains(Self::INVITED) {
             states.push(RoomState::Invited);
         }
+        if self.contains(Self::KNOCKED) {
+            states.push(RoomState::Knocked);
+        }
 
         states
     }
@@ -1247,9 +1495,10 @@ mod tests {
                 },
                 name::RoomNameEventContent,
             },
+            tag::{TagInfo, TagName, Tags},
             AnySyncStateEvent, StateEventType, StateUnsigned, SyncStateEvent,
         },
-        room_alias_id, room_id,
+        owned_event_id, room_alias_id, room_id,
         serde::Raw,
         user_id, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, UserId,
     };
@@ -1257,7 +1506,7 @@ mod tests {
 
     #[cfg(feature = "experimental-sliding-sync")]
     use super::SyncInfo;
-    use super::{Room, RoomInfo, RoomState};
+    use super::{Room, RoomInfo, RoomNotableTags, RoomState};
     #[cfg(any(feature = "experimental-sliding-sync", feature = "e2e-encryption"))]
     use crate::latest_event::LatestEvent;
     use crate::{
@@ -1287,6 +1536,7 @@ mod tests {
                 invited_member_count: 0,
             },
             members_synced: true,
+            partial_state: false,
             last_prev_batch: Some("pb".to_owned()),
             sync_info: SyncInfo::FullySynced,
             encryption_state_synced: true,
@@ -1295,6 +1545,9 @@ mod tests {
             ))),
             base_info: Box::new(BaseRoomInfo::new()),
             read_receipts: Default::default(),
+            notable_tags: RoomNotableTags::FAVOURITE,
+            favourite_order: None,
+            low_priority_order: None,
         };
 
         let info_json = json!({
@@ -1340,7 +1593,8 @@ mod tests {
                 "num_mentions": 0,
                 "num_notifications": 0,
                 "latest_read_receipt_event_id": null,
-            }
+            },
+            "notable_tags": 1,
         });
 
         assert_eq!(serde_json::to_value(info).unwrap(), info_json);
@@ -1412,6 +1666,61 @@ mod tests {
         assert!(info.base_info.name.is_none());
         assert!(info.base_info.tombstone.is_none());
         assert!(info.base_info.topic.is_none());
+        assert!(info.notable_tags.is_empty());
+    }
+
+    #[test]
+    fn test_notable_tags_from_tags() {
+        let mut tags = Tags::new();
+        assert!(RoomNotableTags::from_tags(&tags).is_empty());
+
+        tags.insert(TagName::User("u.work".parse().unwrap()), TagInfo::new());
+        assert!(RoomNotableTags::from_tags(&tags).is_empty());
+
+        tags.insert(TagName::Favorite, TagInfo::new());
+        assert_eq!(RoomNotableTags::from_tags(&tags), RoomNotableTags::FAVOURITE);
+
+        tags.insert(TagName::LowPriority, TagInfo::new());
+        assert_eq!(
+            RoomNotableTags::from_tags(&tags),
+            RoomNotableTags::FAVOURITE | RoomNotableTags::LOW_PRIORITY
+        );
+    }
+
+    #[test]
+    fn test_notable_tag_orders() {
+        let mut info = RoomInfo::new(room_id!("!r0:bar.org"), RoomState::Joined);
+
+        let mut tags = Tags::new();
+        tags.insert(TagName::Favorite, assign!(TagInfo::new(), { order: Some(0.5) }));
+        tags.insert(TagName::LowPriority, TagInfo::new());
+        info.set_notable_tags(&tags);
+
+        assert_eq!(info.favourite_order, Some(0.5));
+        assert_eq!(info.low_priority_order, None);
+
+        info.set_notable_tags(&Tags::new());
+        assert!(info.notable_tags.is_empty());
+        assert_eq!(info.favourite_order, None);
+    }
+
+    #[test]
+    fn test_pinned_event_ids() {
+        let (_, room) = make_room(RoomState::Joined);
+        assert!(room.pinned_event_ids().is_empty());
+
+        let event: AnySyncStateEvent = serde_json::from_value(json!({
+            "content": { "pinned": ["$a", "$b"] },
+            "event_id": "$pinned",
+            "origin_server_ts": 0,
+            "sender": "@alice:example.org",
+            "state_key": "",
+            "type": "m.room.pinned_events",
+        }))
+        .unwrap();
+        receive_state_events(&room, vec![&event]);
+
+        assert_eq!(room.pinned_event_ids(), vec![owned_event_id!("$a"), owned_event_id!("$b")]);
     }
 
     fn make_room(room_type: RoomState) -> (Arc<MemoryStore>, Room) {
@@ -27,7 +27,10 @@ use ruma::{
             v4,
         },
     },
-    events::{AnySyncStateEvent, AnySyncTimelineEvent},
+    events::{
+        room::member::MembershipState, AnyStrippedStateEvent, AnySyncStateEvent,
+        AnySyncTimelineEvent,
+    },
     serde::Raw,
     OwnedRoomId, RoomId,
 };
@@ -324,7 +327,7 @@ impl BaseClient {
         };
 
         let room_account_data = if let Some(events) = account_data.rooms.get(room_id) {
-            self.handle_room_account_data(room_id, events, changes).await;
+            self.handle_room_account_data(room_id, events, &mut room_info, changes).await;
             Some(events.to_vec())
         } else {
             None
@@ -406,6 +409,8 @@ impl BaseClient {
             )),
 
             RoomState::Invited => Ok((room_info, None, None, invited_room)),
+
+            RoomState::Knocked => Ok((room_info, None, None, None)),
         }
     }
 
@@ -430,7 +435,7 @@ impl BaseClient {
             // might not contain an m.room.member event, or they might set the
             // membership to something other than invite. This would be very
             // weird behaviour by the server, because invite_state is supposed
-            // to contain an m.room.member. We will call handle_invited_state, which will
+            // to contain an m.room.member. We will call handle_stripped_state, which will
             // reflect any information found in the real events inside
             // invite_state, but we default to considering this room invited
             // simply because invite_state exists. This is needed in the normal
@@ -439,7 +444,14 @@ impl BaseClient {
             // no content at all.
             room_info.mark_as_invited();
 
-            self.handle_invited_state(invite_state.as_slice(), &mut room_info, changes);
+            self.handle_stripped_state(invite_state.as_slice(), &mut room_info, changes);
+
+            // Sliding sync has no dedicated list for knocked rooms, so they are sent with
+            // an invite_state containing our own knock.
+            if self.is_own_knock(invite_state) {
+                room_info.mark_as_knocked();
+                return (room, room_info, None);
+            }
 
             (
                 room,
@@ -467,6 +479,23 @@ impl BaseClient {
         }
     }
 
+    /// Whether the given stripped state contains an `m.room.member` event
+    /// saying that the current user knocked on the room.
+    fn is_own_knock(&self, stripped_state: &[Raw<AnyStrippedStateEvent>]) -> bool {
+        let Some(meta) = self.session_meta() else {
+            return false;
+        };
+
+        stripped_state.iter().filter_map(|raw| raw.deserialize().ok()).any(|event| {
+            matches!(
+                event,
+                AnyStrippedStateEvent::RoomMember(member)
+                    if member.state_key == meta.user_id
+                        && member.content.membership == MembershipState::Knock
+            )
+        })
+    }
+
     /// Find any m.room.member events that refer to the current user, and update
     /// the state in room_info to reflect the "membership" property.
     pub(crate) fn handle_own_room_membership(
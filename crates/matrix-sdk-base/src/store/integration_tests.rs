@@ -1,6 +1,9 @@
 //! Trait and macro of integration tests for StateStore implementations.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
 
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
@@ -27,15 +30,18 @@ use ruma::{
     },
     mxc_uri, room_id,
     serde::Raw,
-    uint, user_id, EventId, OwnedEventId, OwnedUserId, RoomId, UserId,
+    uint, user_id, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId, UserId,
 };
 use serde_json::{json, value::Value as JsonValue};
 
 use super::DynStateStore;
 use crate::{
     deserialized_responses::MemberEvent,
-    media::{MediaFormat, MediaRequest, MediaThumbnailSize},
-    store::{Result, StateStoreExt},
+    media::{MediaFormat, MediaRequest, MediaRetentionPolicy, MediaThumbnailSize, PendingUpload},
+    store::{
+        ComposerDraft, ComposerDraftType, HomeserverDiscovery, Result, ScheduledMessage,
+        SentTransaction, StateStoreExt,
+    },
     RoomInfo, RoomMemberships, RoomState, StateChanges, StateStoreDataKey, StateStoreDataValue,
 };
 
@@ -50,6 +56,8 @@ pub trait StateStoreIntegrationTests {
     async fn populate(&self) -> Result<()>;
     /// Test media content storage.
     async fn test_media_content(&self);
+    /// Test the retention policy of the media content.
+    async fn test_media_cache_policy(&self);
     /// Test room topic redaction.
     async fn test_topic_redaction(&self) -> Result<()>;
     /// Test populating the store.
@@ -60,6 +68,16 @@ pub trait StateStoreIntegrationTests {
     async fn test_filter_saving(&self);
     /// Test sync token saving.
     async fn test_sync_token_saving(&self);
+    /// Test homeserver discovery saving.
+    async fn test_homeserver_discovery_saving(&self);
+    /// Test pending uploads saving.
+    async fn test_pending_uploads_saving(&self);
+    /// Test composer drafts saving.
+    async fn test_composer_draft_saving(&self);
+    /// Test scheduled messages saving.
+    async fn test_scheduled_messages_saving(&self);
+    /// Test sent transactions saving.
+    async fn test_sent_transactions_saving(&self);
     /// Test stripped room member saving.
     async fn test_stripped_member_saving(&self);
     /// Test room power levels saving.
@@ -302,6 +320,75 @@ impl StateStoreIntegrationTests for DynStateStore {
         );
     }
 
+    async fn test_media_cache_policy(&self) {
+        let request_file = MediaRequest {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/media").to_owned()),
+            format: MediaFormat::File,
+        };
+        let request_thumbnail = MediaRequest {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/media").to_owned()),
+            format: MediaFormat::Thumbnail(MediaThumbnailSize {
+                method: Method::Scale,
+                width: uint!(100),
+                height: uint!(100),
+            }),
+        };
+        let request_other_file = MediaRequest {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/media-other").to_owned()),
+            format: MediaFormat::File,
+        };
+
+        let all_media =
+            [(&request_file, "hello"), (&request_thumbnail, "world"), (&request_other_file, "foo")];
+
+        for (request, content) in all_media {
+            self.add_media_content(request, content.into()).await.unwrap();
+        }
+        assert_eq!(self.media_cache_size().await.unwrap(), 13);
+
+        let now = MilliSecondsSinceUnixEpoch::now();
+
+        // Nothing is removed without limits.
+        self.clean_up_media_cache(MediaRetentionPolicy::empty(), now).await.unwrap();
+        assert_eq!(self.media_cache_size().await.unwrap(), 13);
+
+        // The files that are too big are removed.
+        let policy = MediaRetentionPolicy::empty().with_max_file_size(Some(4));
+        self.clean_up_media_cache(policy, now).await.unwrap();
+        assert!(self.get_media_content(&request_file).await.unwrap().is_none());
+        assert!(self.get_media_content(&request_thumbnail).await.unwrap().is_none());
+        assert!(self.get_media_content(&request_other_file).await.unwrap().is_some());
+        assert_eq!(self.media_cache_size().await.unwrap(), 3);
+
+        // The files that weren't accessed for too long are removed.
+        let policy =
+            MediaRetentionPolicy::empty().with_last_access_expiry(Some(Duration::from_secs(60)));
+        self.clean_up_media_cache(policy, now).await.unwrap();
+        assert_eq!(self.media_cache_size().await.unwrap(), 3);
+
+        let later = MilliSecondsSinceUnixEpoch(now.0 + uint!(120_000));
+        self.clean_up_media_cache(policy, later).await.unwrap();
+        assert!(self.get_media_content(&request_other_file).await.unwrap().is_none());
+        assert_eq!(self.media_cache_size().await.unwrap(), 0);
+
+        // The least recently accessed files are removed when the cache is too big.
+        for (request, content) in all_media {
+            self.add_media_content(request, content.into()).await.unwrap();
+        }
+        let policy = MediaRetentionPolicy::empty().with_max_cache_size(Some(10));
+        self.clean_up_media_cache(policy, now).await.unwrap();
+        let cache_size = self.media_cache_size().await.unwrap();
+        assert!(cache_size > 0 && cache_size <= 10, "unexpected cache size {cache_size}");
+
+        // Everything is removed when the cache is cleared.
+        for (request, content) in all_media {
+            self.add_media_content(request, content.into()).await.unwrap();
+        }
+        self.clear_media_cache().await.unwrap();
+        assert!(self.get_media_content(&request_file).await.unwrap().is_none());
+        assert_eq!(self.media_cache_size().await.unwrap(), 0);
+    }
+
     async fn test_topic_redaction(&self) -> Result<()> {
         let room_id = room_id();
         self.populate().await?;
@@ -566,6 +653,164 @@ impl StateStoreIntegrationTests for DynStateStore {
         assert_matches!(self.get_kv_data(StateStoreDataKey::SyncToken).await, Ok(None));
     }
 
+    async fn test_homeserver_discovery_saving(&self) {
+        let discovery = HomeserverDiscovery {
+            server_url: "https://example.org".to_owned(),
+            homeserver_url: "https://matrix.example.org".to_owned(),
+            sliding_sync_proxy: Some("https://slidingsync.example.org".to_owned()),
+            authentication_issuer: None,
+            authentication_account: None,
+            validated_at: MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000)),
+        };
+
+        assert_matches!(self.get_kv_data(StateStoreDataKey::HomeserverDiscovery).await, Ok(None));
+
+        self.set_kv_data(
+            StateStoreDataKey::HomeserverDiscovery,
+            StateStoreDataValue::HomeserverDiscovery(discovery.clone()),
+        )
+        .await
+        .unwrap();
+        assert_let!(
+            Ok(Some(StateStoreDataValue::HomeserverDiscovery(stored_discovery))) =
+                self.get_kv_data(StateStoreDataKey::HomeserverDiscovery).await
+        );
+        assert_eq!(stored_discovery, discovery);
+
+        self.remove_kv_data(StateStoreDataKey::HomeserverDiscovery).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::HomeserverDiscovery).await, Ok(None));
+    }
+
+    async fn test_pending_uploads_saving(&self) {
+        let pending_uploads = vec![PendingUpload {
+            content_hash: "ZW5jcnlwdGVkIGhhc2g".to_owned(),
+            size: 500_000_000,
+            content_uri: mxc_uri!("mxc://localhost/pending").to_owned(),
+            unused_expires_at: Some(MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000))),
+            content_type: Some("video/mp4".to_owned()),
+            source: Some("/tmp/video.mp4".into()),
+        }];
+
+        assert_matches!(self.get_kv_data(StateStoreDataKey::PendingUploads).await, Ok(None));
+
+        self.set_kv_data(
+            StateStoreDataKey::PendingUploads,
+            StateStoreDataValue::PendingUploads(pending_uploads.clone()),
+        )
+        .await
+        .unwrap();
+        assert_let!(
+            Ok(Some(StateStoreDataValue::PendingUploads(stored_pending_uploads))) =
+                self.get_kv_data(StateStoreDataKey::PendingUploads).await
+        );
+        assert_eq!(stored_pending_uploads, pending_uploads);
+
+        self.remove_kv_data(StateStoreDataKey::PendingUploads).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::PendingUploads).await, Ok(None));
+    }
+
+    async fn test_composer_draft_saving(&self) {
+        let room_id = room_id!("!test_composer_draft_saving:localhost");
+        let other_room_id = room_id!("!other:localhost");
+        let draft = ComposerDraft {
+            plain_text: "Hello, *world*".to_owned(),
+            html_text: Some("Hello, <em>world</em>".to_owned()),
+            draft_type: ComposerDraftType::Reply { event_id: event_id!("$reply").to_owned() },
+        };
+
+        assert_matches!(
+            self.get_kv_data(StateStoreDataKey::ComposerDraft(room_id)).await,
+            Ok(None)
+        );
+
+        self.set_kv_data(
+            StateStoreDataKey::ComposerDraft(room_id),
+            StateStoreDataValue::ComposerDraft(draft.clone()),
+        )
+        .await
+        .unwrap();
+        assert_let!(
+            Ok(Some(StateStoreDataValue::ComposerDraft(stored_draft))) =
+                self.get_kv_data(StateStoreDataKey::ComposerDraft(room_id)).await
+        );
+        assert_eq!(stored_draft, draft);
+        // The drafts are stored per room.
+        assert_matches!(
+            self.get_kv_data(StateStoreDataKey::ComposerDraft(other_room_id)).await,
+            Ok(None)
+        );
+
+        self.remove_kv_data(StateStoreDataKey::ComposerDraft(room_id)).await.unwrap();
+        assert_matches!(
+            self.get_kv_data(StateStoreDataKey::ComposerDraft(room_id)).await,
+            Ok(None)
+        );
+    }
+
+    async fn test_scheduled_messages_saving(&self) {
+        let room_id = room_id!("!test_scheduled_messages_saving:localhost");
+        let scheduled_messages = vec![ScheduledMessage {
+            room_id: room_id.to_owned(),
+            transaction_id: "scheduled_txn".into(),
+            send_at: MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000)),
+            event_type: "m.room.message".to_owned(),
+            content: Raw::new(&json!({ "msgtype": "m.text", "body": "Later" })).unwrap().cast(),
+        }];
+
+        assert_matches!(self.get_kv_data(StateStoreDataKey::ScheduledMessages).await, Ok(None));
+
+        self.set_kv_data(
+            StateStoreDataKey::ScheduledMessages,
+            StateStoreDataValue::ScheduledMessages(scheduled_messages),
+        )
+        .await
+        .unwrap();
+        assert_let!(
+            Ok(Some(StateStoreDataValue::ScheduledMessages(stored_messages))) =
+                self.get_kv_data(StateStoreDataKey::ScheduledMessages).await
+        );
+        assert_eq!(stored_messages.len(), 1);
+        assert_eq!(stored_messages[0].room_id, room_id);
+        assert_eq!(stored_messages[0].transaction_id.as_str(), "scheduled_txn");
+        assert_eq!(
+            stored_messages[0].content.get_field::<String>("body").unwrap().unwrap(),
+            "Later"
+        );
+
+        self.remove_kv_data(StateStoreDataKey::ScheduledMessages).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::ScheduledMessages).await, Ok(None));
+    }
+
+    async fn test_sent_transactions_saving(&self) {
+        let room_id = room_id!("!test_sent_transactions_saving:localhost");
+        let sent_transactions = vec![SentTransaction {
+            room_id: room_id.to_owned(),
+            transaction_id: "sent_txn".into(),
+            event_id: event_id!("$sent").to_owned(),
+            sent_at: MilliSecondsSinceUnixEpoch(uint!(1_700_000_000_000)),
+        }];
+
+        assert_matches!(self.get_kv_data(StateStoreDataKey::SentTransactions).await, Ok(None));
+
+        self.set_kv_data(
+            StateStoreDataKey::SentTransactions,
+            StateStoreDataValue::SentTransactions(sent_transactions),
+        )
+        .await
+        .unwrap();
+        assert_let!(
+            Ok(Some(StateStoreDataValue::SentTransactions(stored_transactions))) =
+                self.get_kv_data(StateStoreDataKey::SentTransactions).await
+        );
+        assert_eq!(stored_transactions.len(), 1);
+        assert_eq!(stored_transactions[0].room_id, room_id);
+        assert_eq!(stored_transactions[0].transaction_id.as_str(), "sent_txn");
+        assert_eq!(stored_transactions[0].event_id, event_id!("$sent"));
+
+        self.remove_kv_data(StateStoreDataKey::SentTransactions).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::SentTransactions).await, Ok(None));
+    }
+
     async fn test_stripped_member_saving(&self) {
         let room_id = room_id!("!test_stripped_member_saving:localhost");
         let user_id = user_id();
@@ -1202,6 +1447,12 @@ macro_rules! statestore_integration_tests {
                 let store = get_store().await.unwrap().into_state_store();
                 store.test_media_content().await;
             }
+
+            #[async_test]
+            async fn test_media_cache_policy() {
+                let store = get_store().await.unwrap().into_state_store();
+                store.test_media_cache_policy().await;
+            }
         }
     };
     () => {
@@ -1246,6 +1497,36 @@ macro_rules! statestore_integration_tests {
             store.test_sync_token_saving().await
         }
 
+        #[async_test]
+        async fn test_homeserver_discovery_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_homeserver_discovery_saving().await
+        }
+
+        #[async_test]
+        async fn test_pending_uploads_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_pending_uploads_saving().await
+        }
+
+        #[async_test]
+        async fn test_composer_draft_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_composer_draft_saving().await
+        }
+
+        #[async_test]
+        async fn test_scheduled_messages_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_scheduled_messages_saving().await
+        }
+
+        #[async_test]
+        async fn test_sent_transactions_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_sent_transactions_saving().await
+        }
+
         #[async_test]
         async fn test_stripped_member_saving() {
             let store = get_store().await.unwrap().into_state_store();
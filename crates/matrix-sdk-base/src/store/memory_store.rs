@@ -29,18 +29,31 @@ use ruma::{
         AnySyncStateEvent, GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType,
     },
     serde::Raw,
-    CanonicalJsonObject, EventId, MxcUri, OwnedEventId, OwnedMxcUri, OwnedRoomId, OwnedUserId,
-    RoomId, RoomVersionId, UserId,
+    CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedMxcUri,
+    OwnedRoomId, OwnedUserId, RoomId, RoomVersionId, UserId,
 };
 use tracing::{debug, warn};
 
-use super::{Result, RoomInfo, StateChanges, StateStore, StoreError};
+use super::{
+    ComposerDraft, HomeserverDiscovery, Result, RoomInfo, ScheduledMessage, SentTransaction,
+    StateChanges, StateStore, StoreError,
+};
 use crate::{
     deserialized_responses::RawAnySyncOrStrippedState,
-    media::{MediaRequest, UniqueKey as _},
+    media::{MediaRequest, MediaRetentionPolicy, PendingUpload, UniqueKey as _},
     MinimalRoomMemberEvent, RoomMemberships, RoomState, StateStoreDataKey, StateStoreDataValue,
 };
 
+/// Media content in the [`MemoryStore`].
+#[derive(Debug)]
+struct MemoryMedia {
+    uri: OwnedMxcUri,
+    /// The unique key of the `MediaRequest`.
+    key: String,
+    data: Vec<u8>,
+    last_access: MilliSecondsSinceUnixEpoch,
+}
+
 /// In-Memory, non-persistent implementation of the `StateStore`
 ///
 /// Default if no other is configured at startup.
@@ -50,6 +63,11 @@ pub struct MemoryStore {
     user_avatar_url: StdRwLock<HashMap<String, String>>,
     sync_token: StdRwLock<Option<String>>,
     filters: StdRwLock<HashMap<String, String>>,
+    homeserver_discovery: StdRwLock<Option<HomeserverDiscovery>>,
+    pending_uploads: StdRwLock<Option<Vec<PendingUpload>>>,
+    composer_drafts: StdRwLock<HashMap<OwnedRoomId, ComposerDraft>>,
+    scheduled_messages: StdRwLock<Option<Vec<ScheduledMessage>>>,
+    sent_transactions: StdRwLock<Option<Vec<SentTransaction>>>,
     account_data: StdRwLock<HashMap<GlobalAccountDataEventType, Raw<AnyGlobalAccountDataEvent>>>,
     profiles: StdRwLock<HashMap<OwnedRoomId, HashMap<OwnedUserId, MinimalRoomMemberEvent>>>,
     display_names: StdRwLock<HashMap<OwnedRoomId, HashMap<String, BTreeSet<OwnedUserId>>>>,
@@ -78,7 +96,7 @@ pub struct MemoryStore {
             HashMap<(String, Option<String>), HashMap<OwnedEventId, HashMap<OwnedUserId, Receipt>>>,
         >,
     >,
-    media: StdRwLock<RingBuffer<(OwnedMxcUri, String /* unique key */, Vec<u8>)>>,
+    media: StdRwLock<RingBuffer<MemoryMedia>>,
     custom: StdRwLock<HashMap<Vec<u8>, Vec<u8>>>,
 }
 
@@ -149,6 +167,37 @@ impl StateStore for MemoryStore {
                 .get(user_id.as_str())
                 .cloned()
                 .map(StateStoreDataValue::UserAvatarUrl),
+            StateStoreDataKey::HomeserverDiscovery => self
+                .homeserver_discovery
+                .read()
+                .unwrap()
+                .clone()
+                .map(StateStoreDataValue::HomeserverDiscovery),
+            StateStoreDataKey::PendingUploads => self
+                .pending_uploads
+                .read()
+                .unwrap()
+                .clone()
+                .map(StateStoreDataValue::PendingUploads),
+            StateStoreDataKey::ComposerDraft(room_id) => self
+                .composer_drafts
+                .read()
+                .unwrap()
+                .get(room_id)
+                .cloned()
+                .map(StateStoreDataValue::ComposerDraft),
+            StateStoreDataKey::ScheduledMessages => self
+                .scheduled_messages
+                .read()
+                .unwrap()
+                .clone()
+                .map(StateStoreDataValue::ScheduledMessages),
+            StateStoreDataKey::SentTransactions => self
+                .sent_transactions
+                .read()
+                .unwrap()
+                .clone()
+                .map(StateStoreDataValue::SentTransactions),
         })
     }
 
@@ -174,6 +223,40 @@ impl StateStore for MemoryStore {
                     value.into_user_avatar_url().expect("Session data not a user avatar url"),
                 );
             }
+            StateStoreDataKey::HomeserverDiscovery => {
+                *self.homeserver_discovery.write().unwrap() = Some(
+                    value
+                        .into_homeserver_discovery()
+                        .expect("Session data not a homeserver discovery"),
+                );
+            }
+            StateStoreDataKey::PendingUploads => {
+                *self.pending_uploads.write().unwrap() = Some(
+                    value
+                        .into_pending_uploads()
+                        .expect("Session data not a list of pending uploads"),
+                );
+            }
+            StateStoreDataKey::ComposerDraft(room_id) => {
+                self.composer_drafts.write().unwrap().insert(
+                    room_id.to_owned(),
+                    value.into_composer_draft().expect("Session data not a composer draft"),
+                );
+            }
+            StateStoreDataKey::ScheduledMessages => {
+                *self.scheduled_messages.write().unwrap() = Some(
+                    value
+                        .into_scheduled_messages()
+                        .expect("Session data not a list of scheduled messages"),
+                );
+            }
+            StateStoreDataKey::SentTransactions => {
+                *self.sent_transactions.write().unwrap() = Some(
+                    value
+                        .into_sent_transactions()
+                        .expect("Session data not a list of sent transactions"),
+                );
+            }
         }
 
         Ok(())
@@ -188,6 +271,21 @@ impl StateStore for MemoryStore {
             StateStoreDataKey::UserAvatarUrl(user_id) => {
                 self.filters.write().unwrap().remove(user_id.as_str());
             }
+            StateStoreDataKey::HomeserverDiscovery => {
+                *self.homeserver_discovery.write().unwrap() = None;
+            }
+            StateStoreDataKey::PendingUploads => {
+                *self.pending_uploads.write().unwrap() = None;
+            }
+            StateStoreDataKey::ComposerDraft(room_id) => {
+                self.composer_drafts.write().unwrap().remove(room_id);
+            }
+            StateStoreDataKey::ScheduledMessages => {
+                *self.scheduled_messages.write().unwrap() = None;
+            }
+            StateStoreDataKey::SentTransactions => {
+                *self.sent_transactions.write().unwrap() = None;
+            }
         }
         Ok(())
     }
@@ -608,7 +706,7 @@ impl StateStore for MemoryStore {
             .read()
             .unwrap()
             .values()
-            .filter(|r| matches!(r.state(), RoomState::Invited))
+            .filter(|r| matches!(r.state(), RoomState::Invited | RoomState::Knocked))
             .cloned()
             .collect())
     }
@@ -706,27 +804,38 @@ impl StateStore for MemoryStore {
         // Avoid duplication. Let's try to remove it first.
         self.remove_media_content(request).await?;
         // Now, let's add it.
-        self.media.write().unwrap().push((request.uri().to_owned(), request.unique_key(), data));
+        self.media.write().unwrap().push(MemoryMedia {
+            uri: request.uri().to_owned(),
+            key: request.unique_key(),
+            data,
+            last_access: MilliSecondsSinceUnixEpoch::now(),
+        });
 
         Ok(())
     }
 
     async fn get_media_content(&self, request: &MediaRequest) -> Result<Option<Vec<u8>>> {
-        let media = self.media.read().unwrap();
+        let mut media = self.media.write().unwrap();
         let expected_key = request.unique_key();
 
-        Ok(media.iter().find_map(|(_media_uri, media_key, media_content)| {
-            (media_key == &expected_key).then(|| media_content.to_owned())
-        }))
+        let Some(index) = media.iter().position(|media| media.key == expected_key) else {
+            return Ok(None);
+        };
+
+        // Move the media to the end of the buffer, so the least recently accessed
+        // media is dropped first when it is full.
+        let mut content = media.remove(index).expect("the index should be valid");
+        content.last_access = MilliSecondsSinceUnixEpoch::now();
+        let data = content.data.clone();
+        media.push(content);
+
+        Ok(Some(data))
     }
 
     async fn remove_media_content(&self, request: &MediaRequest) -> Result<()> {
         let mut media = self.media.write().unwrap();
         let expected_key = request.unique_key();
-        let Some(index) = media
-            .iter()
-            .position(|(_media_uri, media_key, _media_content)| media_key == &expected_key)
-        else {
+        let Some(index) = media.iter().position(|media| media.key == expected_key) else {
             return Ok(());
         };
 
@@ -737,13 +846,10 @@ impl StateStore for MemoryStore {
 
     async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<()> {
         let mut media = self.media.write().unwrap();
-        let expected_key = uri.to_owned();
         let positions = media
             .iter()
             .enumerate()
-            .filter_map(|(position, (media_uri, _media_key, _media_content))| {
-                (media_uri == &expected_key).then_some(position)
-            })
+            .filter_map(|(position, media)| (&*media.uri == uri).then_some(position))
             .collect::<Vec<_>>();
 
         // Iterate in reverse-order so that positions stay valid after first removals.
@@ -754,6 +860,44 @@ impl StateStore for MemoryStore {
         Ok(())
     }
 
+    async fn media_cache_size(&self) -> Result<usize> {
+        Ok(self.media.read().unwrap().iter().map(|media| media.data.len()).sum())
+    }
+
+    async fn clean_up_media_cache(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<()> {
+        let mut media = self.media.write().unwrap();
+        let mut kept = media
+            .drain(..)
+            .filter(|media| {
+                !policy.exceeds_max_file_size(media.data.len())
+                    && !policy.has_content_expired(current_time, media.last_access)
+            })
+            .collect::<Vec<_>>();
+
+        // Keep the most recently accessed media that fits in the cache.
+        kept.sort_by_key(|media| std::cmp::Reverse(media.last_access));
+        let mut cache_size = 0;
+        kept.retain(|media| {
+            cache_size += media.data.len();
+            !policy.exceeds_max_cache_size(cache_size)
+        });
+
+        for content in kept.into_iter().rev() {
+            media.push(content);
+        }
+
+        Ok(())
+    }
+
+    async fn clear_media_cache(&self) -> Result<()> {
+        self.media.write().unwrap().clear();
+        Ok(())
+    }
+
     async fn remove_room(&self, room_id: &RoomId) -> Result<()> {
         self.profiles.write().unwrap().remove(room_id);
         self.display_names.write().unwrap().remove(room_id);
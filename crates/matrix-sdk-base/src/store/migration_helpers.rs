@@ -113,12 +113,16 @@ impl RoomInfoV1 {
             notification_counts,
             summary,
             members_synced,
+            partial_state: false,
             last_prev_batch,
             sync_info,
             encryption_state_synced,
             #[cfg(feature = "experimental-sliding-sync")]
             latest_event: latest_event.map(|ev| Box::new(LatestEvent::new(ev))),
             read_receipts: Default::default(),
+            notable_tags: Default::default(),
+            favourite_order: None,
+            low_priority_order: None,
             base_info: base_info.migrate(create),
         }
     }
@@ -204,6 +208,7 @@ impl BaseRoomInfoV1 {
             tombstone,
             topic,
             rtc_member: BTreeMap::new(),
+            pinned_events: None,
         })
     }
 }
@@ -70,7 +70,8 @@ pub use self::integration_tests::StateStoreIntegrationTests;
 pub use self::{
     memory_store::MemoryStore,
     traits::{
-        DynStateStore, IntoStateStore, StateStore, StateStoreDataKey, StateStoreDataValue,
+        ComposerDraft, ComposerDraftType, DynStateStore, HomeserverDiscovery, IntoStateStore,
+        ScheduledMessage, SentTransaction, StateStore, StateStoreDataKey, StateStoreDataValue,
         StateStoreExt,
     },
 };
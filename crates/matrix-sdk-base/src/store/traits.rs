@@ -26,19 +26,22 @@ use ruma::{
     events::{
         presence::PresenceEvent,
         receipt::{Receipt, ReceiptThread, ReceiptType},
-        AnyGlobalAccountDataEvent, AnyRoomAccountDataEvent, EmptyStateKey, GlobalAccountDataEvent,
-        GlobalAccountDataEventContent, GlobalAccountDataEventType, RedactContent,
-        RedactedStateEventContent, RoomAccountDataEvent, RoomAccountDataEventContent,
-        RoomAccountDataEventType, StateEventType, StaticEventContent, StaticStateEventContent,
+        AnyGlobalAccountDataEvent, AnyMessageLikeEventContent, AnyRoomAccountDataEvent,
+        EmptyStateKey, GlobalAccountDataEvent, GlobalAccountDataEventContent,
+        GlobalAccountDataEventType, RedactContent, RedactedStateEventContent, RoomAccountDataEvent,
+        RoomAccountDataEventContent, RoomAccountDataEventType, StateEventType, StaticEventContent,
+        StaticStateEventContent,
     },
     serde::Raw,
-    EventId, MxcUri, OwnedEventId, OwnedUserId, RoomId, UserId,
+    EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedRoomId, OwnedTransactionId,
+    OwnedUserId, RoomId, UserId,
 };
+use serde::{Deserialize, Serialize};
 
 use super::{StateChanges, StoreError};
 use crate::{
     deserialized_responses::{RawAnySyncOrStrippedState, RawMemberEvent, RawSyncOrStrippedState},
-    media::MediaRequest,
+    media::{MediaRequest, MediaRetentionPolicy, PendingUpload},
     MinimalRoomMemberEvent, RoomInfo, RoomMemberships,
 };
 
@@ -360,6 +363,29 @@ pub trait StateStore: AsyncTraitDeps {
     /// * `uri` - The `MxcUri` of the media files.
     async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<(), Self::Error>;
 
+    /// Get the total size of the media content in the media store, in bytes.
+    async fn media_cache_size(&self) -> Result<usize, Self::Error>;
+
+    /// Remove the media content that doesn't respect the given policy from the
+    /// media store.
+    ///
+    /// The content that was accessed the longest time ago is removed first
+    /// when the store is too big.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy to apply.
+    ///
+    /// * `current_time` - The time to compare the last access times to.
+    async fn clean_up_media_cache(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<(), Self::Error>;
+
+    /// Remove all the media content from the media store.
+    async fn clear_media_cache(&self) -> Result<(), Self::Error>;
+
     /// Removes a room and all elements associated from the state store.
     ///
     /// # Arguments
@@ -586,6 +612,22 @@ impl<T: StateStore> StateStore for EraseStateStoreError<T> {
         self.0.remove_media_content_for_uri(uri).await.map_err(Into::into)
     }
 
+    async fn media_cache_size(&self) -> Result<usize, Self::Error> {
+        self.0.media_cache_size().await.map_err(Into::into)
+    }
+
+    async fn clean_up_media_cache(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<(), Self::Error> {
+        self.0.clean_up_media_cache(policy, current_time).await.map_err(Into::into)
+    }
+
+    async fn clear_media_cache(&self) -> Result<(), Self::Error> {
+        self.0.clear_media_cache().await.map_err(Into::into)
+    }
+
     async fn remove_room(&self, room_id: &RoomId) -> Result<(), Self::Error> {
         self.0.remove_room(room_id).await.map_err(Into::into)
     }
@@ -783,6 +825,21 @@ pub enum StateStoreDataValue {
 
     /// The user avatar url
     UserAvatarUrl(String),
+
+    /// The result of the discovery of the homeserver.
+    HomeserverDiscovery(HomeserverDiscovery),
+
+    /// The uploads of media that might not be complete.
+    PendingUploads(Vec<PendingUpload>),
+
+    /// A composer draft for a room.
+    ComposerDraft(ComposerDraft),
+
+    /// The messages scheduled to be sent later.
+    ScheduledMessages(Vec<ScheduledMessage>),
+
+    /// The events recently sent with a transaction ID.
+    SentTransactions(Vec<SentTransaction>),
 }
 
 impl StateStoreDataValue {
@@ -800,6 +857,128 @@ impl StateStoreDataValue {
     pub fn into_user_avatar_url(self) -> Option<String> {
         as_variant!(self, Self::UserAvatarUrl)
     }
+
+    /// Get this value if it is the result of the discovery of the homeserver.
+    pub fn into_homeserver_discovery(self) -> Option<HomeserverDiscovery> {
+        as_variant!(self, Self::HomeserverDiscovery)
+    }
+
+    /// Get this value if it is a list of pending uploads.
+    pub fn into_pending_uploads(self) -> Option<Vec<PendingUpload>> {
+        as_variant!(self, Self::PendingUploads)
+    }
+
+    /// Get this value if it is a composer draft.
+    pub fn into_composer_draft(self) -> Option<ComposerDraft> {
+        as_variant!(self, Self::ComposerDraft)
+    }
+
+    /// Get this value if it is a list of scheduled messages.
+    pub fn into_scheduled_messages(self) -> Option<Vec<ScheduledMessage>> {
+        as_variant!(self, Self::ScheduledMessages)
+    }
+
+    /// Get this value if it is a list of sent transactions.
+    pub fn into_sent_transactions(self) -> Option<Vec<SentTransaction>> {
+        as_variant!(self, Self::SentTransactions)
+    }
+}
+
+/// The URLs resolved from the `/.well-known/matrix/client` file of a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HomeserverDiscovery {
+    /// The URL of the server the discovery was made for, e.g.
+    /// `https://example.org`.
+    pub server_url: String,
+
+    /// The base URL of the homeserver.
+    pub homeserver_url: String,
+
+    /// The URL of the sliding sync proxy, if any.
+    pub sliding_sync_proxy: Option<String>,
+
+    /// The issuer of the authentication server, if any.
+    pub authentication_issuer: Option<String>,
+
+    /// The URL of the account management of the authentication server, if
+    /// any.
+    pub authentication_account: Option<String>,
+
+    /// When the discovery was last made.
+    pub validated_at: MilliSecondsSinceUnixEpoch,
+}
+
+/// A message that was being written in a room, to restore it in the composer
+/// later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComposerDraft {
+    /// The draft content in plain text.
+    pub plain_text: String,
+
+    /// The draft content in HTML, if the composer supports rich text.
+    pub html_text: Option<String>,
+
+    /// The type of message being written.
+    pub draft_type: ComposerDraftType,
+}
+
+/// The type of message being written in a [`ComposerDraft`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ComposerDraftType {
+    /// A new message.
+    NewMessage,
+
+    /// A reply to the event with the given ID.
+    Reply {
+        /// The ID of the event being replied to.
+        event_id: OwnedEventId,
+    },
+
+    /// An edit of the event with the given ID.
+    Edit {
+        /// The ID of the event being edited.
+        event_id: OwnedEventId,
+    },
+}
+
+/// A message that should be sent to a room at a later time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledMessage {
+    /// The room to send the message to.
+    pub room_id: OwnedRoomId,
+
+    /// The transaction ID to use to send the message, which also identifies
+    /// it.
+    pub transaction_id: OwnedTransactionId,
+
+    /// When the message should be sent.
+    pub send_at: MilliSecondsSinceUnixEpoch,
+
+    /// The type of the event to send.
+    pub event_type: String,
+
+    /// The content of the event to send.
+    pub content: Raw<AnyMessageLikeEventContent>,
+}
+
+/// An event that was sent with a transaction ID.
+///
+/// This allows to know that an event was already sent when a send is retried
+/// with the same transaction ID, even in another process.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SentTransaction {
+    /// The room the event was sent to.
+    pub room_id: OwnedRoomId,
+
+    /// The transaction ID used to send the event.
+    pub transaction_id: OwnedTransactionId,
+
+    /// The ID of the event returned by the homeserver.
+    pub event_id: OwnedEventId,
+
+    /// When the event was sent.
+    pub sent_at: MilliSecondsSinceUnixEpoch,
 }
 
 /// A key for key-value data.
@@ -813,6 +992,21 @@ pub enum StateStoreDataKey<'a> {
 
     /// Avatar URL
     UserAvatarUrl(&'a UserId),
+
+    /// The result of the discovery of the homeserver.
+    HomeserverDiscovery,
+
+    /// The uploads of media that might not be complete.
+    PendingUploads,
+
+    /// The composer draft of the given room.
+    ComposerDraft(&'a RoomId),
+
+    /// The messages scheduled to be sent later.
+    ScheduledMessages,
+
+    /// The events recently sent with a transaction ID.
+    SentTransactions,
 }
 
 impl StateStoreDataKey<'_> {
@@ -823,4 +1017,18 @@ impl StateStoreDataKey<'_> {
     /// Key prefix to use for the [`UserAvatarUrl`][Self::UserAvatarUrl]
     /// variant.
     pub const USER_AVATAR_URL: &'static str = "user_avatar_url";
+    /// Key to use for the [`HomeserverDiscovery`][Self::HomeserverDiscovery]
+    /// variant.
+    pub const HOMESERVER_DISCOVERY: &'static str = "homeserver_discovery";
+    /// Key to use for the [`PendingUploads`][Self::PendingUploads] variant.
+    pub const PENDING_UPLOADS: &'static str = "pending_uploads";
+    /// Key prefix to use for the [`ComposerDraft`][Self::ComposerDraft]
+    /// variant.
+    pub const COMPOSER_DRAFT: &'static str = "composer_draft";
+    /// Key to use for the [`ScheduledMessages`][Self::ScheduledMessages]
+    /// variant.
+    pub const SCHEDULED_MESSAGES: &'static str = "scheduled_messages";
+    /// Key to use for the [`SentTransactions`][Self::SentTransactions]
+    /// variant.
+    pub const SENT_TRANSACTIONS: &'static str = "sent_transactions";
 }
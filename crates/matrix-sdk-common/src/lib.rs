@@ -26,6 +26,7 @@ pub mod executor;
 pub mod failures_cache;
 pub mod ring_buffer;
 pub mod store_locks;
+pub mod time;
 pub mod timeout;
 pub mod tracing_timer;
 
@@ -0,0 +1,109 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Abstraction over the passage of time, so it can be controlled in tests.
+//!
+//! The timers and the timestamps of the `Client` of `matrix-sdk`, and of the
+//! higher-level crates built on it, go through a [`Clock`], which is the
+//! [`SystemClock`] by default. Tests can use a clock whose time only moves
+//! forward when they ask for it, to be deterministic.
+//!
+//! The stores and the crypto crate don't have access to it and still read the
+//! time of the system.
+
+use std::{fmt, future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use instant::Instant;
+use ruma::MilliSecondsSinceUnixEpoch;
+
+use crate::AsyncTraitDeps;
+
+/// The future returned by [`Clock::sleep()`].
+#[cfg(not(target_arch = "wasm32"))]
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+/// The future returned by [`Clock::sleep()`].
+#[cfg(target_arch = "wasm32")]
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// A source of time.
+pub trait Clock: AsyncTraitDeps {
+    /// The current monotonic time, to measure durations.
+    fn now(&self) -> Instant;
+
+    /// The current time since the Unix epoch, to timestamp data.
+    fn timestamp(&self) -> MilliSecondsSinceUnixEpoch;
+
+    /// A future that resolves after the given duration has elapsed.
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+/// The [`Clock`] of the system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn timestamp(&self) -> MilliSecondsSinceUnixEpoch {
+        MilliSecondsSinceUnixEpoch::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        #[cfg(not(target_arch = "wasm32"))]
+        return Box::pin(tokio::time::sleep(duration));
+
+        #[cfg(target_arch = "wasm32")]
+        return Box::pin(gloo_timers::future::sleep(duration));
+    }
+}
+
+/// A shared [`Clock`].
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    /// Create a new `SharedClock` from the given clock.
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self(Arc::new(clock))
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl Clock for SharedClock {
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+
+    fn timestamp(&self) -> MilliSecondsSinceUnixEpoch {
+        self.0.timestamp()
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        self.0.sleep(duration)
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
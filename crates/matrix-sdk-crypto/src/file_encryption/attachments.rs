@@ -15,12 +15,15 @@
 use std::{
     collections::BTreeMap,
     io::{Error as IoError, ErrorKind, Read},
+    pin::Pin,
+    task::{ready, Context, Poll},
 };
 
 use aes::{
     cipher::{generic_array::GenericArray, KeyIvInit, StreamCipher},
     Aes256,
 };
+use futures_util::io::AsyncRead;
 use rand::{thread_rng, RngCore};
 use ruma::{
     events::room::{EncryptedFile, JsonWebKey, JsonWebKeyInit},
@@ -130,28 +133,10 @@ impl<'a, R: Read + 'a> AttachmentDecryptor<'a, R> {
         input: &'a mut R,
         info: MediaEncryptionInfo,
     ) -> Result<AttachmentDecryptor<'a, R>, DecryptorError> {
-        if info.version != VERSION {
-            return Err(DecryptorError::UnknownVersion);
-        }
-
-        let hash =
-            info.hashes.get("sha256").ok_or(DecryptorError::MissingHash)?.as_bytes().to_owned();
-        let mut key = info.key.k.into_inner();
-        let iv = info.iv.into_inner();
-
-        if key.len() != KEY_SIZE {
-            return Err(DecryptorError::KeyNonceLength);
-        }
-
-        let key_array = GenericArray::from_slice(&key);
-        let iv = GenericArray::from_exact_iter(iv).ok_or(DecryptorError::KeyNonceLength)?;
-
+        let (expected_hash, aes) = decryption_cipher(info)?;
         let sha = Sha256::default();
 
-        let aes = Aes256Ctr::new(key_array, &iv);
-        key.zeroize();
-
-        Ok(AttachmentDecryptor { inner: input, expected_hash: hash, sha, aes })
+        Ok(AttachmentDecryptor { inner: input, expected_hash, sha, aes })
     }
 }
 
@@ -225,31 +210,7 @@ impl<'a, R: Read + ?Sized + 'a> AttachmentEncryptor<'a, R> {
     /// let key = encryptor.finish();
     /// ```
     pub fn new(reader: &'a mut R) -> Self {
-        let mut key = [0u8; KEY_SIZE];
-        let mut iv = [0u8; IV_SIZE];
-
-        let mut rng = thread_rng();
-
-        rng.fill_bytes(&mut key);
-        // Only populate the first 8 bytes with randomness, the rest is 0
-        // initialized for the counter.
-        rng.fill_bytes(&mut iv[0..8]);
-
-        let web_key = JsonWebKey::from(JsonWebKeyInit {
-            kty: "oct".to_owned(),
-            key_ops: vec!["encrypt".to_owned(), "decrypt".to_owned()],
-            alg: "A256CTR".to_owned(),
-            #[allow(clippy::unnecessary_to_owned)]
-            k: Base64::new(key.to_vec()),
-            ext: true,
-        });
-        #[allow(clippy::unnecessary_to_owned)]
-        let encoded_iv = Base64::new(iv.to_vec());
-
-        let key_array = &key.into();
-
-        let aes = Aes256Ctr::new(key_array, &iv.into());
-        key.zeroize();
+        let (web_key, encoded_iv, aes) = encryption_cipher();
 
         AttachmentEncryptor {
             finished: false,
@@ -278,6 +239,216 @@ impl<'a, R: Read + ?Sized + 'a> AttachmentEncryptor<'a, R> {
     }
 }
 
+/// Generate a fresh key and IV, and the cipher to encrypt an attachment with
+/// them.
+///
+/// # Panics
+///
+/// Panics if we can't generate enough random data to create a fresh
+/// encryption key.
+fn encryption_cipher() -> (JsonWebKey, Base64, Aes256Ctr) {
+    let mut key = [0u8; KEY_SIZE];
+    let mut iv = [0u8; IV_SIZE];
+
+    let mut rng = thread_rng();
+
+    rng.fill_bytes(&mut key);
+    // Only populate the first 8 bytes with randomness, the rest is 0
+    // initialized for the counter.
+    rng.fill_bytes(&mut iv[0..8]);
+
+    let web_key = JsonWebKey::from(JsonWebKeyInit {
+        kty: "oct".to_owned(),
+        key_ops: vec!["encrypt".to_owned(), "decrypt".to_owned()],
+        alg: "A256CTR".to_owned(),
+        #[allow(clippy::unnecessary_to_owned)]
+        k: Base64::new(key.to_vec()),
+        ext: true,
+    });
+    #[allow(clippy::unnecessary_to_owned)]
+    let encoded_iv = Base64::new(iv.to_vec());
+
+    let key_array = &key.into();
+
+    let aes = Aes256Ctr::new(key_array, &iv.into());
+    key.zeroize();
+
+    (web_key, encoded_iv, aes)
+}
+
+/// Get the expected hash of an attachment and the cipher to decrypt it with
+/// the given encryption info.
+fn decryption_cipher(info: MediaEncryptionInfo) -> Result<(Vec<u8>, Aes256Ctr), DecryptorError> {
+    if info.version != VERSION {
+        return Err(DecryptorError::UnknownVersion);
+    }
+
+    let hash = info.hashes.get("sha256").ok_or(DecryptorError::MissingHash)?.as_bytes().to_owned();
+    let mut key = info.key.k.into_inner();
+    let iv = info.iv.into_inner();
+
+    if key.len() != KEY_SIZE {
+        return Err(DecryptorError::KeyNonceLength);
+    }
+
+    let key_array = GenericArray::from_slice(&key);
+    let iv = GenericArray::from_exact_iter(iv).ok_or(DecryptorError::KeyNonceLength)?;
+
+    let aes = Aes256Ctr::new(key_array, &iv);
+    key.zeroize();
+
+    Ok((hash, aes))
+}
+
+/// A wrapper that transparently decrypts anything that implements
+/// `AsyncRead`, as a Matrix attachment.
+///
+/// This is the asynchronous counterpart of [`AttachmentDecryptor`], which
+/// allows to decrypt big attachments without having them in memory.
+pub struct AsyncAttachmentDecryptor<R> {
+    inner: R,
+    expected_hash: Vec<u8>,
+    sha: Sha256,
+    aes: Aes256Ctr,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl<R: std::fmt::Debug> std::fmt::Debug for AsyncAttachmentDecryptor<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncAttachmentDecryptor")
+            .field("inner", &self.inner)
+            .field("expected_hash", &self.expected_hash)
+            .finish()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncAttachmentDecryptor<R> {
+    /// Wrap the given reader decrypting all the data we read from it.
+    ///
+    /// Reading fails at the end of the data if its hash doesn't match the one
+    /// of the encryption info.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The `AsyncRead` that should be wrapped and decrypted.
+    ///
+    /// * `info` - The encryption info that is necessary to decrypt data from
+    /// the reader.
+    pub fn new(reader: R, info: MediaEncryptionInfo) -> Result<Self, DecryptorError> {
+        let (expected_hash, aes) = decryption_cipher(info)?;
+        Ok(Self { inner: reader, expected_hash, sha: Sha256::default(), aes })
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncAttachmentDecryptor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let read_bytes = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        if read_bytes == 0 {
+            let hash = this.sha.finalize_reset();
+
+            if hash.as_slice() == this.expected_hash.as_slice() {
+                Poll::Ready(Ok(0))
+            } else {
+                Poll::Ready(Err(IoError::new(ErrorKind::Other, "Hash mismatch while decrypting")))
+            }
+        } else {
+            this.sha.update(&buf[0..read_bytes]);
+            this.aes.apply_keystream(&mut buf[0..read_bytes]);
+
+            Poll::Ready(Ok(read_bytes))
+        }
+    }
+}
+
+/// A wrapper that transparently encrypts anything that implements
+/// `AsyncRead`.
+///
+/// This is the asynchronous counterpart of [`AttachmentEncryptor`], which
+/// allows to encrypt big attachments without having them in memory.
+pub struct AsyncAttachmentEncryptor<R> {
+    inner: R,
+    web_key: JsonWebKey,
+    iv: Base64,
+    aes: Aes256Ctr,
+    sha: Sha256,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl<R: std::fmt::Debug> std::fmt::Debug for AsyncAttachmentEncryptor<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncAttachmentEncryptor").field("inner", &self.inner).finish()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncAttachmentEncryptor<R> {
+    /// Wrap the given reader encrypting all the data we read from it.
+    ///
+    /// After all the data was read, a call to [`finish()`](#method.finish) is
+    /// necessary to get the decryption key for the data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if we can't generate enough random data to create a fresh
+    /// encryption key.
+    ///
+    /// # Examples
+    /// ```
+    /// # use futures_executor::block_on;
+    /// # use futures_util::io::{AsyncReadExt, Cursor};
+    /// # use matrix_sdk_crypto::AsyncAttachmentEncryptor;
+    /// # block_on(async {
+    /// let cursor = Cursor::new(b"Hello world");
+    ///
+    /// let mut encryptor = AsyncAttachmentEncryptor::new(cursor);
+    ///
+    /// let mut encrypted = Vec::new();
+    /// encryptor.read_to_end(&mut encrypted).await.unwrap();
+    /// let key = encryptor.finish();
+    /// # });
+    /// ```
+    pub fn new(reader: R) -> Self {
+        let (web_key, iv, aes) = encryption_cipher();
+        Self { inner: reader, web_key, iv, aes, sha: Sha256::default() }
+    }
+
+    /// Consume the encryptor and get the encryption key.
+    pub fn finish(self) -> MediaEncryptionInfo {
+        let hash = self.sha.finalize();
+
+        MediaEncryptionInfo {
+            version: VERSION.to_owned(),
+            hashes: BTreeMap::from([(
+                "sha256".to_owned(),
+                Base64::new(hash.as_slice().to_owned()),
+            )]),
+            iv: self.iv,
+            key: self.web_key,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncAttachmentEncryptor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let read_bytes = ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        this.aes.apply_keystream(&mut buf[0..read_bytes]);
+        this.sha.update(&buf[0..read_bytes]);
+
+        Poll::Ready(Ok(read_bytes))
+    }
+}
+
 /// Struct holding all the information that is needed to decrypt an encrypted
 /// file.
 #[derive(Debug, Serialize, Deserialize)]
@@ -303,9 +474,14 @@ impl From<EncryptedFile> for MediaEncryptionInfo {
 mod tests {
     use std::io::{Cursor, Read};
 
+    use futures_util::io::AsyncReadExt;
+    use matrix_sdk_test::async_test;
     use serde_json::json;
 
-    use super::{AttachmentDecryptor, AttachmentEncryptor, MediaEncryptionInfo};
+    use super::{
+        AsyncAttachmentDecryptor, AsyncAttachmentEncryptor, AttachmentDecryptor,
+        AttachmentEncryptor, MediaEncryptionInfo,
+    };
 
     const EXAMPLE_DATA: &[u8] = &[
         179, 154, 118, 127, 186, 127, 110, 33, 203, 33, 33, 134, 67, 100, 173, 46, 235, 27, 215,
@@ -379,4 +555,46 @@ mod tests {
 
         decryptor.read_to_end(&mut decrypted_data).unwrap_err();
     }
+
+    #[async_test]
+    async fn async_encrypt_decrypt_cycle() {
+        let data = "Hello world".to_owned();
+
+        let mut encryptor =
+            AsyncAttachmentEncryptor::new(futures_util::io::Cursor::new(data.clone()));
+
+        let mut encrypted = Vec::new();
+        encryptor.read_to_end(&mut encrypted).await.unwrap();
+        let key = encryptor.finish();
+        assert_ne!(encrypted.as_slice(), data.as_bytes());
+
+        // The synchronous decryptor can decrypt it too.
+        let mut cursor = Cursor::new(encrypted.clone());
+        let mut decryptor = AttachmentDecryptor::new(&mut cursor, key).unwrap();
+        let mut decrypted_data = Vec::new();
+        decryptor.read_to_end(&mut decrypted_data).unwrap();
+        assert_eq!(data.as_bytes(), decrypted_data);
+
+        let mut decryptor = AsyncAttachmentDecryptor::new(
+            futures_util::io::Cursor::new(EXAMPLE_DATA.to_vec()),
+            example_key(),
+        )
+        .unwrap();
+        let mut decrypted_data = Vec::new();
+        decryptor.read_to_end(&mut decrypted_data).await.unwrap();
+
+        assert_eq!(decrypted_data, b"It's a secret to everybody");
+    }
+
+    #[async_test]
+    async fn async_decrypt_invalid_hash() {
+        let mut decryptor = AsyncAttachmentDecryptor::new(
+            futures_util::io::Cursor::new("fake message"),
+            example_key(),
+        )
+        .unwrap();
+        let mut decrypted_data = Vec::new();
+
+        decryptor.read_to_end(&mut decrypted_data).await.unwrap_err();
+    }
 }
@@ -1328,10 +1328,10 @@ pub(crate) mod tests {
     use std::ops::Deref;
 
     use futures_util::pin_mut;
-    use matrix_sdk_test::{async_test, response_from_file};
+    use matrix_sdk_test::{async_test, bulk_keys_query_response, response_from_file};
     use ruma::{
         api::{client::keys::get_keys::v3::Response as KeysQueryResponse, IncomingResponse},
-        device_id, user_id, TransactionId,
+        device_id, user_id, OwnedUserId, TransactionId,
     };
     use serde_json::json;
     use stream_assert::{assert_closed, assert_pending, assert_ready};
@@ -1418,6 +1418,35 @@ pub(crate) mod tests {
         identity.is_device_signed(&device).unwrap();
     }
 
+    #[async_test]
+    async fn test_manager_bulk_key_query_response() {
+        let manager = manager_test_helper(user_id(), device_id()).await;
+        let user_ids: Vec<OwnedUserId> =
+            (0..20).map(|idx| format!("@user_{idx}:localhost").try_into().unwrap()).collect();
+
+        let response = bulk_keys_query_response(user_ids.clone(), 5);
+        let response = KeysQueryResponse::try_from_http_response(response_from_file(&response))
+            .expect("Can't parse the `/keys/query` response");
+
+        manager.receive_keys_query_response(&TransactionId::new(), &response).await.unwrap();
+
+        for user_id in &user_ids {
+            let devices = manager.store.get_user_devices(user_id).await.unwrap();
+            assert_eq!(devices.devices().count(), 5);
+
+            let device = manager
+                .store
+                .get_readonly_device(user_id, device_id!("LOADDEVICE0"))
+                .await
+                .unwrap()
+                .unwrap();
+            let identity = manager.store.get_user_identity(user_id).await.unwrap().unwrap();
+            let identity = identity.other().unwrap();
+
+            identity.is_device_signed(&device).unwrap();
+        }
+    }
+
     #[async_test]
     async fn test_manager_own_key_query_response() {
         let manager = manager_test_helper(user_id(), device_id()).await;
@@ -72,8 +72,9 @@ impl RoomKeyImportResult {
 
 pub use error::{EventError, MegolmError, OlmError, SessionCreationError, SignatureError};
 pub use file_encryption::{
-    decrypt_room_key_export, encrypt_room_key_export, AttachmentDecryptor, AttachmentEncryptor,
-    DecryptorError, KeyExportError, MediaEncryptionInfo,
+    decrypt_room_key_export, encrypt_room_key_export, AsyncAttachmentDecryptor,
+    AsyncAttachmentEncryptor, AttachmentDecryptor, AttachmentEncryptor, DecryptorError,
+    KeyExportError, MediaEncryptionInfo,
 };
 pub use gossiping::{GossipRequest, GossippedSecret};
 pub use identities::{
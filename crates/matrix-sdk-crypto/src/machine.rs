@@ -69,9 +69,9 @@ use crate::{
     requests::{IncomingResponse, OutgoingRequest, UploadSigningKeysRequest},
     session_manager::{GroupSessionManager, SessionManager},
     store::{
-        Changes, CryptoStoreWrapper, DeviceChanges, IdentityChanges, IntoCryptoStore, MemoryStore,
-        PendingChanges, Result as StoreResult, RoomKeyInfo, SecretImportError, Store, StoreCache,
-        StoreTransaction,
+        CacheInvalidation, CacheInvalidationListener, Changes, CryptoStoreWrapper, DeviceChanges,
+        IdentityChanges, IntoCryptoStore, MemoryStore, PendingChanges, Result as StoreResult,
+        RoomKeyInfo, SecretImportError, Store, StoreCache, StoreTransaction,
     },
     types::{
         events::{
@@ -2044,6 +2044,45 @@ impl OlmMachine {
         Ok(true)
     }
 
+    /// Set the listener called with the in-memory caches that become outdated
+    /// for the other processes using the same store, every time this machine
+    /// writes to the store.
+    ///
+    /// The [`CacheInvalidation`]s should be sent to the other processes, which
+    /// can then call [`OlmMachine::invalidate_caches()`] with them.
+    pub fn set_cache_invalidation_listener(
+        &self,
+        listener: impl Fn(&CacheInvalidation) + Send + Sync + 'static,
+    ) {
+        self.inner
+            .store
+            .crypto_store()
+            .set_cache_invalidation_listener(CacheInvalidationListener(Arc::new(listener)));
+    }
+
+    /// Invalidate the in-memory caches that are outdated after another process
+    /// modified the store.
+    ///
+    /// This is a lighter alternative to recreating the whole `OlmMachine`: the
+    /// data that changed is loaded again from the store the next time it is
+    /// needed, the rest of the caches are kept.
+    pub async fn invalidate_caches(&self, invalidation: &CacheInvalidation) -> StoreResult<()> {
+        if invalidation.is_empty() {
+            return Ok(());
+        }
+
+        debug!(?invalidation, "Invalidating the caches after a change from another process");
+
+        self.inner.store.invalidate_caches(invalidation).await?;
+
+        let group_sessions = self.inner.group_session_manager.session_cache();
+        for room_id in &invalidation.outbound_group_sessions {
+            group_sessions.remove(room_id);
+        }
+
+        Ok(())
+    }
+
     /// Manage dehydrated devices.
     pub fn dehydrated_devices(&self) -> DehydratedDevices {
         DehydratedDevices { inner: self.to_owned() }
@@ -102,6 +102,17 @@ impl GroupSessionCache {
         self.sessions.read().unwrap().get(room_id).cloned()
     }
 
+    /// Forget the outbound group session of the given room, so it is loaded
+    /// again from the store the next time it is needed.
+    pub(crate) fn remove(&self, room_id: &RoomId) {
+        if self.sessions.write().unwrap().remove(room_id).is_some() {
+            self.sessions_being_shared
+                .write()
+                .unwrap()
+                .retain(|_, session| session.room_id() != room_id);
+        }
+    }
+
     /// Returns whether any session is withheld with the given device and code.
     fn has_session_withheld_to(&self, device: &ReadOnlyDevice, code: &WithheldCode) -> bool {
         self.sessions.read().unwrap().values().any(|s| s.is_withheld_to(device, code))
@@ -80,6 +80,11 @@ impl SessionStore {
     pub fn set_for_sender(&self, sender_key: &str, sessions: Vec<Session>) {
         self.entries.write().unwrap().insert(sender_key.to_owned(), Arc::new(Mutex::new(sessions)));
     }
+
+    /// Remove all the sessions that belong to the given sender key.
+    pub fn remove_for_sender(&self, sender_key: &str) {
+        self.entries.write().unwrap().remove(sender_key);
+    }
 }
 
 #[derive(Debug, Default)]
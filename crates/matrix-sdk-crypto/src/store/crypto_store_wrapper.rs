@@ -1,4 +1,7 @@
-use std::{ops::Deref, sync::Arc};
+use std::{
+    ops::Deref,
+    sync::{Arc, RwLock as StdRwLock},
+};
 
 use futures_core::Stream;
 use futures_util::StreamExt;
@@ -8,7 +11,10 @@ use tokio::sync::broadcast;
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 use tracing::warn;
 
-use super::{DeviceChanges, IdentityChanges, LockableCryptoStore};
+use super::{
+    CacheInvalidation, CacheInvalidationListener, DeviceChanges, IdentityChanges,
+    LockableCryptoStore, PendingChanges,
+};
 use crate::{
     store,
     store::{Changes, DynCryptoStore, IntoCryptoStore, RoomKeyInfo},
@@ -36,6 +42,10 @@ pub(crate) struct CryptoStoreWrapper {
     /// identities which got updated or newly created.
     identities_broadcaster:
         broadcast::Sender<(Option<ReadOnlyOwnUserIdentity>, IdentityChanges, DeviceChanges)>,
+
+    /// The listener called with the caches of the other processes using the
+    /// same store that are outdated after a write, if any.
+    cache_invalidation_listener: StdRwLock<Option<CacheInvalidationListener>>,
 }
 
 impl CryptoStoreWrapper {
@@ -52,6 +62,7 @@ impl CryptoStoreWrapper {
             room_keys_received_sender,
             secrets_broadcaster,
             identities_broadcaster,
+            cache_invalidation_listener: Default::default(),
         }
     }
 
@@ -70,9 +81,12 @@ impl CryptoStoreWrapper {
         let secrets = changes.secrets.to_owned();
         let devices = changes.devices.to_owned();
         let identities = changes.identities.to_owned();
+        let invalidation = CacheInvalidation::from_changes(&changes);
 
         self.store.save_changes(changes).await?;
 
+        self.notify_cache_invalidation(invalidation);
+
         if !room_key_updates.is_empty() {
             // Ignore the result. It can only fail if there are no listeners.
             let _ = self.room_keys_received_sender.send(room_key_updates);
@@ -95,6 +109,51 @@ impl CryptoStoreWrapper {
         Ok(())
     }
 
+    /// Save the set of pending changes to the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The set of changes that should be stored.
+    pub async fn save_pending_changes(&self, changes: PendingChanges) -> store::Result<()> {
+        let invalidation =
+            CacheInvalidation { account: changes.account.is_some(), ..Default::default() };
+
+        self.store.save_pending_changes(changes).await?;
+
+        self.notify_cache_invalidation(invalidation);
+
+        Ok(())
+    }
+
+    /// Save the tracked users and their dirty flags to the store.
+    pub async fn save_tracked_users(&self, users: &[(&UserId, bool)]) -> store::Result<()> {
+        self.store.save_tracked_users(users).await?;
+
+        self.notify_cache_invalidation(CacheInvalidation {
+            tracked_users: !users.is_empty(),
+            ..Default::default()
+        });
+
+        Ok(())
+    }
+
+    /// Set the listener called with the caches of the other processes using
+    /// the same store that are outdated after each write to the store.
+    pub(crate) fn set_cache_invalidation_listener(&self, listener: CacheInvalidationListener) {
+        *self.cache_invalidation_listener.write().unwrap() = Some(listener);
+    }
+
+    fn notify_cache_invalidation(&self, invalidation: CacheInvalidation) {
+        if invalidation.is_empty() {
+            return;
+        }
+
+        let listener = self.cache_invalidation_listener.read().unwrap().clone();
+        if let Some(listener) = listener {
+            (listener.0)(&invalidation);
+        }
+    }
+
     /// Receive notifications of room keys being received as a [`Stream`].
     ///
     /// Each time a room key is updated in any way, an update will be sent to
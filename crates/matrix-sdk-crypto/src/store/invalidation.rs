@@ -0,0 +1,143 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+use ruma::OwnedRoomId;
+use serde::{Deserialize, Serialize};
+
+use super::Changes;
+
+/// The in-memory caches of an [`OlmMachine`] that are outdated after a write
+/// to its store.
+///
+/// When several processes use the same store, the changes written by one of
+/// them must be sent to the others, which can then call
+/// [`OlmMachine::invalidate_caches()`] to only reload the data that changed,
+/// instead of recreating their whole `OlmMachine`.
+///
+/// [`OlmMachine`]: crate::OlmMachine
+/// [`OlmMachine::invalidate_caches()`]: crate::OlmMachine::invalidate_caches
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheInvalidation {
+    /// Whether the account changed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub account: bool,
+    /// Whether the private cross-signing identity changed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private_identity: bool,
+    /// Whether the list of users whose devices are tracked changed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tracked_users: bool,
+    /// The sender keys of the Olm sessions that changed, encoded as base64.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub olm_sessions: BTreeSet<String>,
+    /// The rooms whose outbound group session changed.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub outbound_group_sessions: BTreeSet<OwnedRoomId>,
+}
+
+impl CacheInvalidation {
+    /// Whether no cache needs to be invalidated.
+    pub fn is_empty(&self) -> bool {
+        !self.account
+            && !self.private_identity
+            && !self.tracked_users
+            && self.olm_sessions.is_empty()
+            && self.outbound_group_sessions.is_empty()
+    }
+
+    /// Add the caches invalidated by `other` to this one.
+    pub fn extend(&mut self, other: CacheInvalidation) {
+        self.account |= other.account;
+        self.private_identity |= other.private_identity;
+        self.tracked_users |= other.tracked_users;
+        self.olm_sessions.extend(other.olm_sessions);
+        self.outbound_group_sessions.extend(other.outbound_group_sessions);
+    }
+
+    /// The caches that are outdated after the given changes are saved.
+    pub(crate) fn from_changes(changes: &Changes) -> Self {
+        Self {
+            account: false,
+            private_identity: changes.private_identity.is_some(),
+            tracked_users: false,
+            olm_sessions: changes
+                .sessions
+                .iter()
+                .map(|session| session.sender_key.to_base64())
+                .collect(),
+            outbound_group_sessions: changes
+                .outbound_group_sessions
+                .iter()
+                .map(|session| session.room_id().to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// A listener called with the caches to invalidate after each write to the
+/// store.
+#[derive(Clone)]
+pub(crate) struct CacheInvalidationListener(
+    pub(crate) Arc<dyn Fn(&CacheInvalidation) + Send + Sync>,
+);
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for CacheInvalidationListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheInvalidationListener").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_id, room_id};
+    use serde_json::json;
+
+    use super::CacheInvalidation;
+
+    #[test]
+    fn test_extend_and_serialization() {
+        let mut invalidation = CacheInvalidation::default();
+        assert!(invalidation.is_empty());
+        assert_eq!(serde_json::to_value(&invalidation).unwrap(), json!({}));
+
+        invalidation.extend(CacheInvalidation {
+            account: true,
+            olm_sessions: ["key".to_owned()].into(),
+            ..Default::default()
+        });
+        invalidation.extend(CacheInvalidation {
+            outbound_group_sessions: [owned_room_id!("!room:localhost")].into(),
+            ..Default::default()
+        });
+        assert!(!invalidation.is_empty());
+
+        let value = serde_json::to_value(&invalidation).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "account": true,
+                "olm_sessions": ["key"],
+                "outbound_group_sessions": ["!room:localhost"],
+            })
+        );
+
+        let invalidation: CacheInvalidation = serde_json::from_value(value).unwrap();
+        assert!(invalidation.account);
+        assert!(!invalidation.tracked_users);
+        assert!(invalidation.outbound_group_sessions.contains(room_id!("!room:localhost")));
+    }
+}
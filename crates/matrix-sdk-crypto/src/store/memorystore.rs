@@ -220,6 +220,11 @@ impl CryptoStore for MemoryStore {
         Ok(self.sessions.get(sender_key))
     }
 
+    async fn clear_session_cache(&self, _sender_key: &str) -> Result<()> {
+        // The sessions are only stored in memory, so there is nothing to reload.
+        Ok(())
+    }
+
     async fn get_inbound_group_session(
         &self,
         room_id: &RoomId,
@@ -77,6 +77,7 @@ use crate::{
 pub mod caches;
 mod crypto_store_wrapper;
 mod error;
+mod invalidation;
 mod memorystore;
 mod traits;
 
@@ -88,6 +89,8 @@ pub mod integration_tests;
 use caches::{SequenceNumber, UsersForKeyQuery};
 pub(crate) use crypto_store_wrapper::CryptoStoreWrapper;
 pub use error::{CryptoStoreError, Result};
+pub use invalidation::CacheInvalidation;
+pub(crate) use invalidation::CacheInvalidationListener;
 use matrix_sdk_common::{store_locks::CrossProcessStoreLock, timeout::timeout};
 pub use memorystore::MemoryStore;
 pub use traits::{CryptoStore, DynCryptoStore, IntoCryptoStore};
@@ -966,6 +969,42 @@ impl Store {
         self.inner.store.save_changes(changes).await
     }
 
+    pub(crate) async fn save_pending_changes(&self, changes: PendingChanges) -> Result<()> {
+        self.inner.store.save_pending_changes(changes).await
+    }
+
+    /// Invalidate the in-memory caches of this store that are outdated after
+    /// the store was modified by another process.
+    ///
+    /// The data is loaded again from the store the next time it is needed.
+    pub(crate) async fn invalidate_caches(&self, invalidation: &CacheInvalidation) -> Result<()> {
+        if invalidation.account || invalidation.tracked_users {
+            // Wait for the transactions in progress, which hold the cache.
+            let cache = self.inner.cache.write().await;
+
+            if invalidation.account {
+                *cache.account.lock().await = None;
+            }
+
+            if invalidation.tracked_users {
+                *cache.loaded_tracked_users.write().await = false;
+                cache.tracked_users.write().unwrap().clear();
+            }
+        }
+
+        if invalidation.private_identity {
+            if let Some(identity) = self.inner.store.load_identity().await? {
+                *self.inner.identity.lock().await = identity;
+            }
+        }
+
+        for sender_key in &invalidation.olm_sessions {
+            self.inner.store.clear_session_cache(sender_key).await?;
+        }
+
+        Ok(())
+    }
+
     /// Compare the given `InboundGroupSession` with an existing session we have
     /// in the store.
     ///
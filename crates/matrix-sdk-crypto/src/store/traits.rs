@@ -75,6 +75,18 @@ pub trait CryptoStore: AsyncTraitDeps {
         sender_key: &str,
     ) -> Result<Option<Arc<Mutex<Vec<Session>>>>, Self::Error>;
 
+    /// Forget the sessions of the given sender key that are cached in memory,
+    /// if any, so they are loaded again from the store the next time they are
+    /// needed.
+    ///
+    /// This is used when the sessions were modified by another process using
+    /// the same store.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender_key` - The sender key that was used to establish the sessions.
+    async fn clear_session_cache(&self, sender_key: &str) -> Result<(), Self::Error>;
+
     /// Get the inbound group session from our store.
     ///
     /// # Arguments
@@ -316,6 +328,10 @@ impl<T: CryptoStore> CryptoStore for EraseCryptoStoreError<T> {
         self.0.get_sessions(sender_key).await.map_err(Into::into)
     }
 
+    async fn clear_session_cache(&self, sender_key: &str) -> Result<()> {
+        self.0.clear_session_cache(sender_key).await.map_err(Into::into)
+    }
+
     async fn get_inbound_group_session(
         &self,
         room_id: &RoomId,
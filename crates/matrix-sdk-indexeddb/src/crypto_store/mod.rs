@@ -752,6 +752,11 @@ impl_crypto_store! {
         Ok(self.session_cache.get(sender_key))
     }
 
+    async fn clear_session_cache(&self, sender_key: &str) -> Result<()> {
+        self.session_cache.remove_for_sender(sender_key);
+        Ok(())
+    }
+
     async fn get_inbound_group_session(
         &self,
         room_id: &RoomId,
@@ -46,7 +46,7 @@ use super::{
 };
 use crate::IndexeddbStateStoreError;
 
-const CURRENT_DB_VERSION: u32 = 8;
+const CURRENT_DB_VERSION: u32 = 9;
 const CURRENT_META_DB_VERSION: u32 = 2;
 
 /// Sometimes Migrations can't proceed without having to drop existing
@@ -225,6 +225,9 @@ pub async fn upgrade_inner_db(
             if old_version < 8 {
                 db = migrate_to_v8(db, store_cipher).await?;
             }
+            if old_version < 9 {
+                db = migrate_to_v9(db).await?;
+            }
         }
 
         db.close();
@@ -731,6 +734,18 @@ async fn migrate_to_v8(db: IdbDatabase, store_cipher: Option<&StoreCipher>) -> R
     Ok(IdbDatabase::open_u32(&name, 8)?.await?)
 }
 
+/// Add the media metadata store.
+///
+/// The cached media content is dropped since it doesn't have metadata.
+async fn migrate_to_v9(db: IdbDatabase) -> Result<IdbDatabase> {
+    let migration = OngoingMigration {
+        drop_stores: HashSet::from_iter([keys::MEDIA]),
+        create_stores: HashSet::from_iter([keys::MEDIA, keys::MEDIA_METADATA]),
+        ..Default::default()
+    };
+    apply_migration(db, 9, migration).await
+}
+
 #[cfg(all(test, target_arch = "wasm32"))]
 mod tests {
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
@@ -23,7 +23,7 @@ use gloo_utils::format::JsValueSerdeExt;
 use indexed_db_futures::prelude::*;
 use matrix_sdk_base::{
     deserialized_responses::RawAnySyncOrStrippedState,
-    media::{MediaRequest, UniqueKey},
+    media::{MediaRequest, MediaRetentionPolicy, UniqueKey},
     store::{StateChanges, StateStore, StoreError},
     MinimalRoomMemberEvent, RoomInfo, RoomMemberships, RoomState, StateStoreDataKey,
     StateStoreDataValue,
@@ -41,7 +41,8 @@ use ruma::{
         GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType, SyncStateEvent,
     },
     serde::Raw,
-    CanonicalJsonObject, EventId, MxcUri, OwnedEventId, OwnedUserId, RoomId, RoomVersionId, UserId,
+    CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedUserId,
+    RoomId, RoomVersionId, UserId,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{debug, warn};
@@ -89,6 +90,14 @@ impl From<IndexeddbStateStoreError> for StoreError {
     }
 }
 
+/// The metadata of media content, used to apply the retention policy.
+#[derive(Debug, Serialize, Deserialize)]
+struct MediaMetadata {
+    /// The size of the content, in bytes.
+    size: usize,
+    last_access: MilliSecondsSinceUnixEpoch,
+}
+
 mod keys {
     pub const INTERNAL_STATE: &str = "matrix-sdk-state";
     pub const BACKUPS_META: &str = "backups";
@@ -111,6 +120,7 @@ mod keys {
     pub const ROOM_EVENT_RECEIPTS: &str = "room_event_receipts";
 
     pub const MEDIA: &str = "media";
+    pub const MEDIA_METADATA: &str = "media_metadata";
 
     pub const CUSTOM: &str = "custom";
     pub const KV: &str = "kv";
@@ -130,6 +140,7 @@ mod keys {
         ROOM_USER_RECEIPTS,
         ROOM_EVENT_RECEIPTS,
         MEDIA,
+        MEDIA_METADATA,
         CUSTOM,
         KV,
     ];
@@ -385,6 +396,21 @@ impl IndexeddbStateStore {
             StateStoreDataKey::UserAvatarUrl(user_id) => {
                 self.encode_key(keys::KV, (StateStoreDataKey::USER_AVATAR_URL, user_id))
             }
+            StateStoreDataKey::HomeserverDiscovery => {
+                self.encode_key(keys::KV, StateStoreDataKey::HOMESERVER_DISCOVERY)
+            }
+            StateStoreDataKey::PendingUploads => {
+                self.encode_key(keys::KV, StateStoreDataKey::PENDING_UPLOADS)
+            }
+            StateStoreDataKey::ComposerDraft(room_id) => {
+                self.encode_key(keys::KV, (StateStoreDataKey::COMPOSER_DRAFT, room_id))
+            }
+            StateStoreDataKey::ScheduledMessages => {
+                self.encode_key(keys::KV, StateStoreDataKey::SCHEDULED_MESSAGES)
+            }
+            StateStoreDataKey::SentTransactions => {
+                self.encode_key(keys::KV, StateStoreDataKey::SENT_TRANSACTIONS)
+            }
         }
     }
 }
@@ -427,17 +453,40 @@ impl_state_store!({
             .transaction_on_one_with_mode(keys::KV, IdbTransactionMode::Readonly)?
             .object_store(keys::KV)?
             .get(&encoded_key)?
-            .await?
-            .map(|f| self.deserialize_event::<String>(&f))
-            .transpose()?;
+            .await?;
+
+        let Some(value) = value else {
+            return Ok(None);
+        };
 
         let value = match key {
-            StateStoreDataKey::SyncToken => value.map(StateStoreDataValue::SyncToken),
-            StateStoreDataKey::Filter(_) => value.map(StateStoreDataValue::Filter),
-            StateStoreDataKey::UserAvatarUrl(_) => value.map(StateStoreDataValue::UserAvatarUrl),
+            StateStoreDataKey::SyncToken => {
+                StateStoreDataValue::SyncToken(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::Filter(_) => {
+                StateStoreDataValue::Filter(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::UserAvatarUrl(_) => {
+                StateStoreDataValue::UserAvatarUrl(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::HomeserverDiscovery => {
+                StateStoreDataValue::HomeserverDiscovery(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::PendingUploads => {
+                StateStoreDataValue::PendingUploads(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::ComposerDraft(_) => {
+                StateStoreDataValue::ComposerDraft(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::ScheduledMessages => {
+                StateStoreDataValue::ScheduledMessages(self.deserialize_event(&value)?)
+            }
+            StateStoreDataKey::SentTransactions => {
+                StateStoreDataValue::SentTransactions(self.deserialize_event(&value)?)
+            }
         };
 
-        Ok(value)
+        Ok(Some(value))
     }
 
     async fn set_kv_data(
@@ -447,14 +496,37 @@ impl_state_store!({
     ) -> Result<()> {
         let encoded_key = self.encode_kv_data_key(key);
 
-        let value = match key {
-            StateStoreDataKey::SyncToken => {
-                value.into_sync_token().expect("Session data not a sync token")
-            }
-            StateStoreDataKey::Filter(_) => value.into_filter().expect("Session data not a filter"),
-            StateStoreDataKey::UserAvatarUrl(_) => {
-                value.into_user_avatar_url().expect("Session data not an user avatar url")
+        let serialized_value = match key {
+            StateStoreDataKey::SyncToken => self.serialize_event(
+                &value.into_sync_token().expect("Session data not a sync token"),
+            )?,
+            StateStoreDataKey::Filter(_) => {
+                self.serialize_event(&value.into_filter().expect("Session data not a filter"))?
             }
+            StateStoreDataKey::UserAvatarUrl(_) => self.serialize_event(
+                &value.into_user_avatar_url().expect("Session data not an user avatar url"),
+            )?,
+            StateStoreDataKey::HomeserverDiscovery => self.serialize_event(
+                &value
+                    .into_homeserver_discovery()
+                    .expect("Session data not a homeserver discovery"),
+            )?,
+            StateStoreDataKey::PendingUploads => self.serialize_event(
+                &value.into_pending_uploads().expect("Session data not a list of pending uploads"),
+            )?,
+            StateStoreDataKey::ComposerDraft(_) => self.serialize_event(
+                &value.into_composer_draft().expect("Session data not a composer draft"),
+            )?,
+            StateStoreDataKey::ScheduledMessages => self.serialize_event(
+                &value
+                    .into_scheduled_messages()
+                    .expect("Session data not a list of scheduled messages"),
+            )?,
+            StateStoreDataKey::SentTransactions => self.serialize_event(
+                &value
+                    .into_sent_transactions()
+                    .expect("Session data not a list of sent transactions"),
+            )?,
         };
 
         let tx =
@@ -462,7 +534,7 @@ impl_state_store!({
 
         let obj = tx.object_store(keys::KV)?;
 
-        obj.put_key_val(&encoded_key, &self.serialize_event(&value)?)?;
+        obj.put_key_val(&encoded_key, &serialized_value)?;
 
         tx.await.into_result()?;
 
@@ -1004,7 +1076,7 @@ impl_state_store!({
                 let value = cursor.value();
                 let info = self.deserialize_event::<RoomInfo>(&value)?;
 
-                if info.state() == RoomState::Invited {
+                if matches!(info.state(), RoomState::Invited | RoomState::Knocked) {
                     infos.push(info);
                 }
 
@@ -1141,10 +1213,16 @@ impl_state_store!({
     async fn add_media_content(&self, request: &MediaRequest, data: Vec<u8>) -> Result<()> {
         let key = self
             .encode_key(keys::MEDIA, (request.source.unique_key(), request.format.unique_key()));
-        let tx =
-            self.inner.transaction_on_one_with_mode(keys::MEDIA, IdbTransactionMode::Readwrite)?;
+        let metadata =
+            MediaMetadata { size: data.len(), last_access: MilliSecondsSinceUnixEpoch::now() };
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
 
         tx.object_store(keys::MEDIA)?.put_key_val(&key, &self.serialize_event(&data)?)?;
+        tx.object_store(keys::MEDIA_METADATA)?
+            .put_key_val(&key, &self.serialize_event(&metadata)?)?;
 
         tx.await.into_result().map_err(|e| e.into())
     }
@@ -1152,13 +1230,28 @@ impl_state_store!({
     async fn get_media_content(&self, request: &MediaRequest) -> Result<Option<Vec<u8>>> {
         let key = self
             .encode_key(keys::MEDIA, (request.source.unique_key(), request.format.unique_key()));
-        self.inner
-            .transaction_on_one_with_mode(keys::MEDIA, IdbTransactionMode::Readonly)?
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
+
+        let Some(data) = tx
             .object_store(keys::MEDIA)?
             .get(&key)?
             .await?
-            .map(|f| self.deserialize_event(&f))
-            .transpose()
+            .map(|f| self.deserialize_event::<Vec<u8>>(&f))
+            .transpose()?
+        else {
+            return Ok(None);
+        };
+
+        let metadata =
+            MediaMetadata { size: data.len(), last_access: MilliSecondsSinceUnixEpoch::now() };
+        tx.object_store(keys::MEDIA_METADATA)?
+            .put_key_val(&key, &self.serialize_event(&metadata)?)?;
+
+        tx.await.into_result()?;
+        Ok(Some(data))
     }
 
     async fn get_custom_value(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
@@ -1197,27 +1290,107 @@ impl_state_store!({
     async fn remove_media_content(&self, request: &MediaRequest) -> Result<()> {
         let key = self
             .encode_key(keys::MEDIA, (request.source.unique_key(), request.format.unique_key()));
-        let tx =
-            self.inner.transaction_on_one_with_mode(keys::MEDIA, IdbTransactionMode::Readwrite)?;
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
 
         tx.object_store(keys::MEDIA)?.delete(&key)?;
+        tx.object_store(keys::MEDIA_METADATA)?.delete(&key)?;
 
         tx.await.into_result().map_err(|e| e.into())
     }
 
     async fn remove_media_content_for_uri(&self, uri: &MxcUri) -> Result<()> {
         let range = self.encode_to_range(keys::MEDIA, uri)?;
-        let tx =
-            self.inner.transaction_on_one_with_mode(keys::MEDIA, IdbTransactionMode::Readwrite)?;
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
         let store = tx.object_store(keys::MEDIA)?;
+        let metadata_store = tx.object_store(keys::MEDIA_METADATA)?;
 
         for k in store.get_all_keys_with_key(&range)?.await?.iter() {
             store.delete(&k)?;
+            metadata_store.delete(&k)?;
         }
 
         tx.await.into_result().map_err(|e| e.into())
     }
 
+    async fn media_cache_size(&self) -> Result<usize> {
+        let tx = self
+            .inner
+            .transaction_on_one_with_mode(keys::MEDIA_METADATA, IdbTransactionMode::Readonly)?;
+
+        let size = tx
+            .object_store(keys::MEDIA_METADATA)?
+            .get_all()?
+            .await?
+            .iter()
+            .map(|value| self.deserialize_event::<MediaMetadata>(&value).map(|m| m.size))
+            .sum::<Result<usize>>()?;
+
+        Ok(size)
+    }
+
+    async fn clean_up_media_cache(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<()> {
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
+        let store = tx.object_store(keys::MEDIA)?;
+        let metadata_store = tx.object_store(keys::MEDIA_METADATA)?;
+
+        // Both requests return the entries in the order of the keys.
+        let keys = metadata_store.get_all_keys()?.await?;
+        let values = metadata_store.get_all()?.await?;
+
+        let mut kept = Vec::new();
+        for (key, value) in keys.iter().zip(values.iter()) {
+            let metadata: MediaMetadata = self.deserialize_event(&value)?;
+
+            if policy.exceeds_max_file_size(metadata.size)
+                || policy.has_content_expired(current_time, metadata.last_access)
+            {
+                store.delete(&key)?;
+                metadata_store.delete(&key)?;
+            } else {
+                kept.push((key, metadata));
+            }
+        }
+
+        // Remove the least recently accessed media until the cache is small enough.
+        kept.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.last_access));
+        let mut cache_size = 0;
+        for (key, metadata) in kept {
+            cache_size += metadata.size;
+
+            if policy.exceeds_max_cache_size(cache_size) {
+                store.delete(&key)?;
+                metadata_store.delete(&key)?;
+            }
+        }
+
+        tx.await.into_result().map_err(|e| e.into())
+    }
+
+    async fn clear_media_cache(&self) -> Result<()> {
+        let tx = self.inner.transaction_on_multi_with_mode(
+            &[keys::MEDIA, keys::MEDIA_METADATA],
+            IdbTransactionMode::Readwrite,
+        )?;
+
+        tx.object_store(keys::MEDIA)?.clear()?;
+        tx.object_store(keys::MEDIA_METADATA)?.clear()?;
+
+        tx.await.into_result().map_err(|e| e.into())
+    }
+
     async fn remove_room(&self, room_id: &RoomId) -> Result<()> {
         let direct_stores = [keys::ROOM_INFOS];
 
@@ -17,10 +17,12 @@
 #![warn(missing_debug_implementations, missing_docs)]
 
 mod error;
+mod login;
 mod types;
 mod utils;
 
 pub use error::{DecodingError, EncodingError};
+pub use login::{LoginQrCodeData, LoginQrCodeIntent};
 pub use qrcode;
 pub use types::{
     QrVerificationData, SelfVerificationData, SelfVerificationNoMasterKey, VerificationData,
@@ -28,7 +30,9 @@ pub use types::{
 
 #[cfg(test)]
 mod tests {
-    use crate::{DecodingError, QrVerificationData};
+    use vodozemac::{Curve25519PublicKey, Curve25519SecretKey};
+
+    use crate::{DecodingError, LoginQrCodeData, LoginQrCodeIntent, QrVerificationData};
 
     #[test]
     fn decode_invalid_header() {
@@ -82,4 +86,31 @@ mod tests {
         let result = QrVerificationData::from_bytes(data);
         assert!(matches!(result, Err(DecodingError::Keys(_))))
     }
+
+    #[test]
+    fn login_data_round_trip() {
+        let public_key = Curve25519PublicKey::from(&Curve25519SecretKey::new());
+
+        for intent in [
+            LoginQrCodeIntent::Login,
+            LoginQrCodeIntent::Reciprocate { server_name: "example.org".to_owned() },
+        ] {
+            let data = LoginQrCodeData {
+                public_key,
+                rendezvous_url: "https://rendezvous.example.org/abcdef".to_owned(),
+                intent,
+            };
+
+            let bytes = data.to_bytes().unwrap();
+            assert_eq!(LoginQrCodeData::from_bytes(bytes).unwrap(), data);
+            data.to_qr_code().unwrap();
+        }
+    }
+
+    #[test]
+    fn decode_login_data_with_verification_mode() {
+        let data = b"MATRIX\x02\x01";
+        let result = LoginQrCodeData::from_bytes(data);
+        assert!(matches!(result, Err(DecodingError::Mode(0x01))))
+    }
 }
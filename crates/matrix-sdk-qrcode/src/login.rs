@@ -0,0 +1,176 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use qrcode::{bits::Bits, EcLevel, QrCode, Version};
+use vodozemac::Curve25519PublicKey;
+
+use crate::{
+    error::{DecodingError, EncodingError},
+    utils::{HEADER, VERSION},
+};
+
+/// The role of the device displaying a [`LoginQrCodeData`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoginQrCodeIntent {
+    /// The QR code is displayed by a new device that wants to log in, and is
+    /// scanned by an existing device.
+    Login,
+
+    /// The QR code is displayed by an existing device to log in a new one,
+    /// which scans it.
+    Reciprocate {
+        /// The name of the homeserver of the existing device, which the new
+        /// device has to log in to.
+        server_name: String,
+    },
+}
+
+impl LoginQrCodeIntent {
+    const LOGIN_MODE: u8 = 0x03;
+    const RECIPROCATE_MODE: u8 = 0x04;
+
+    fn mode(&self) -> u8 {
+        match self {
+            Self::Login => Self::LOGIN_MODE,
+            Self::Reciprocate { .. } => Self::RECIPROCATE_MODE,
+        }
+    }
+}
+
+/// The data of a QR code used to log in a new device with an existing one, as
+/// defined in [MSC4108].
+///
+/// Both devices use the rendezvous session to exchange messages, encrypted
+/// with a secure channel established from the public key in the QR code.
+///
+/// Only the format of the QR code is implemented for now: the secure channel,
+/// the device authorization grant and the transfer of the secrets are not
+/// provided by this crate.
+///
+/// [MSC4108]: https://github.com/matrix-org/matrix-spec-proposals/pull/4108
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoginQrCodeData {
+    /// The ephemeral Curve25519 key of the device displaying the QR code.
+    pub public_key: Curve25519PublicKey,
+    /// The URL of the rendezvous session.
+    pub rendezvous_url: String,
+    /// The role of the device displaying the QR code.
+    pub intent: LoginQrCodeIntent,
+}
+
+impl LoginQrCodeData {
+    /// Parse the decoded payload of a QR code as a `LoginQrCodeData`.
+    ///
+    /// The byte slice consists of the following parts:
+    ///
+    /// * the ASCII string MATRIX
+    /// * one byte indicating the QR code version (must be 0x02)
+    /// * one byte indicating the intent, 0x03 to log in or 0x04 to reciprocate
+    /// * the public key, as 32 bytes
+    /// * the rendezvous URL, as two bytes in network byte order indicating its
+    ///   length, followed by the URL as a UTF-8 string
+    /// * only when reciprocating, the server name, encoded like the rendezvous
+    ///   URL
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The raw bytes of a decoded QR code.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, DecodingError> {
+        let mut decoded = Cursor::new(bytes);
+
+        let mut header = [0u8; 6];
+        let mut public_key = [0u8; 32];
+
+        decoded.read_exact(&mut header)?;
+        let version = decoded.read_u8()?;
+        let mode = decoded.read_u8()?;
+
+        if header != HEADER {
+            return Err(DecodingError::Header);
+        } else if version != VERSION {
+            return Err(DecodingError::Version(version));
+        } else if mode != LoginQrCodeIntent::LOGIN_MODE
+            && mode != LoginQrCodeIntent::RECIPROCATE_MODE
+        {
+            return Err(DecodingError::Mode(mode));
+        }
+
+        decoded.read_exact(&mut public_key)?;
+        let public_key = Curve25519PublicKey::from_slice(&public_key)?;
+        let rendezvous_url = read_string(&mut decoded)?;
+
+        let intent = if mode == LoginQrCodeIntent::RECIPROCATE_MODE {
+            LoginQrCodeIntent::Reciprocate { server_name: read_string(&mut decoded)? }
+        } else {
+            LoginQrCodeIntent::Login
+        };
+
+        Ok(Self { public_key, rendezvous_url, intent })
+    }
+
+    /// Encode the `LoginQrCodeData` into a vector of bytes that can be encoded
+    /// as a QR code.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodingError> {
+        let mut data =
+            [HEADER, &[VERSION, self.intent.mode()], self.public_key.as_bytes()].concat();
+        write_string(&mut data, &self.rendezvous_url)?;
+
+        if let LoginQrCodeIntent::Reciprocate { server_name } = &self.intent {
+            write_string(&mut data, server_name)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Encode the `LoginQrCodeData` into a `QrCode` that can be rendered and
+    /// presented to be scanned.
+    pub fn to_qr_code(&self) -> Result<QrCode, EncodingError> {
+        let data = self.to_bytes()?;
+
+        // Push the raw bytes without an ECI segment, like for the verification
+        // QR codes, the URL doesn't always fit in the smaller versions.
+        let mut bits = Bits::new(Version::Normal(10));
+        bits.push_byte_data(&data)?;
+        bits.push_terminator(EcLevel::L)?;
+
+        Ok(QrCode::with_bits(bits, EcLevel::L)?)
+    }
+}
+
+impl TryFrom<&[u8]> for LoginQrCodeData {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+fn read_string(decoded: &mut Cursor<impl AsRef<[u8]>>) -> Result<String, DecodingError> {
+    let len = decoded.read_u16::<BigEndian>()?;
+    let mut bytes = vec![0; len.into()];
+    decoded.read_exact(&mut bytes)?;
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) -> Result<(), EncodingError> {
+    let len: u16 = string.len().try_into()?;
+    data.extend_from_slice(&len.to_be_bytes());
+    data.extend_from_slice(string.as_bytes());
+
+    Ok(())
+}
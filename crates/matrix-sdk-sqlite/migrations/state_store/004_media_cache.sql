@@ -0,0 +1,8 @@
+-- The size of the content, which is not the size of the data if it is encrypted.
+ALTER TABLE "media" ADD COLUMN "size" INTEGER NOT NULL DEFAULT 0;
+-- The last access time, in milliseconds since the Unix epoch.
+ALTER TABLE "media" ADD COLUMN "last_access" INTEGER NOT NULL DEFAULT 0;
+
+UPDATE "media" SET "size" = length("data");
+
+CREATE INDEX "media_last_access_idx" ON "media" ("last_access");
@@ -903,6 +903,11 @@ impl CryptoStore for SqliteCryptoStore {
         Ok(self.session_cache.get(sender_key))
     }
 
+    async fn clear_session_cache(&self, sender_key: &str) -> Result<()> {
+        self.session_cache.remove_for_sender(sender_key);
+        Ok(())
+    }
+
     #[instrument(skip(self))]
     async fn get_inbound_group_session(
         &self,
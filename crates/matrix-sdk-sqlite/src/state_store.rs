@@ -10,7 +10,7 @@ use async_trait::async_trait;
 use deadpool_sqlite::{Object as SqliteConn, Pool as SqlitePool, Runtime};
 use matrix_sdk_base::{
     deserialized_responses::{RawAnySyncOrStrippedState, SyncOrStrippedState},
-    media::{MediaRequest, UniqueKey},
+    media::{MediaRequest, MediaRetentionPolicy, UniqueKey},
     store::migration_helpers::RoomInfoV1,
     MinimalRoomMemberEvent, RoomInfo, RoomMemberships, RoomState, StateChanges, StateStore,
     StateStoreDataKey, StateStoreDataValue,
@@ -29,7 +29,8 @@ use ruma::{
         GlobalAccountDataEventType, RoomAccountDataEventType, StateEventType,
     },
     serde::Raw,
-    CanonicalJsonObject, EventId, OwnedEventId, OwnedUserId, RoomId, RoomVersionId, UserId,
+    CanonicalJsonObject, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId,
+    RoomVersionId, UserId,
 };
 use rusqlite::{OptionalExtension, Transaction};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
@@ -57,7 +58,7 @@ mod keys {
     pub const MEDIA: &str = "media";
 }
 
-const DATABASE_VERSION: u8 = 3;
+const DATABASE_VERSION: u8 = 4;
 
 /// A sqlite based cryptostore.
 #[derive(Clone)]
@@ -213,6 +214,20 @@ impl SqliteStateStore {
             .await?;
         }
 
+        // Migration to v4: the media cache has a retention policy.
+        if from < 4 && to >= 4 {
+            let now = i64::from(MilliSecondsSinceUnixEpoch::now().get());
+            conn.with_transaction(move |txn| {
+                txn.execute_batch(include_str!("../migrations/state_store/004_media_cache.sql"))?;
+                // Consider that the existing media was accessed now, to not remove
+                // all of it on the first clean up.
+                txn.execute("UPDATE media SET last_access = ?", (now,))?;
+
+                Result::<_, Error>::Ok(())
+            })
+            .await?;
+        }
+
         conn.set_kv("version", vec![to]).await?;
 
         Ok(())
@@ -275,6 +290,19 @@ impl SqliteStateStore {
             StateStoreDataKey::UserAvatarUrl(u) => {
                 Cow::Owned(format!("{}:{u}", StateStoreDataKey::USER_AVATAR_URL))
             }
+            StateStoreDataKey::HomeserverDiscovery => {
+                Cow::Borrowed(StateStoreDataKey::HOMESERVER_DISCOVERY)
+            }
+            StateStoreDataKey::PendingUploads => Cow::Borrowed(StateStoreDataKey::PENDING_UPLOADS),
+            StateStoreDataKey::ComposerDraft(room_id) => {
+                Cow::Owned(format!("{}:{room_id}", StateStoreDataKey::COMPOSER_DRAFT))
+            }
+            StateStoreDataKey::ScheduledMessages => {
+                Cow::Borrowed(StateStoreDataKey::SCHEDULED_MESSAGES)
+            }
+            StateStoreDataKey::SentTransactions => {
+                Cow::Borrowed(StateStoreDataKey::SENT_TRANSACTIONS)
+            }
         };
 
         self.encode_key(keys::KV_BLOB, &*key_s)
@@ -825,24 +853,38 @@ trait SqliteObjectStateStoreExt: SqliteObjectExt {
             .await?)
     }
 
-    async fn set_media(&self, uri: Key, format: Key, data: Vec<u8>) -> Result<()> {
+    async fn set_media(&self, uri: Key, format: Key, data: Vec<u8>, size: usize) -> Result<()> {
+        let last_access = i64::from(MilliSecondsSinceUnixEpoch::now().get());
         self.execute(
-            "INSERT OR REPLACE INTO media (uri, format, data) VALUES (?, ?, ?)",
-            (uri, format, data),
+            "INSERT OR REPLACE INTO media (uri, format, data, size, last_access)
+             VALUES (?, ?, ?, ?, ?)",
+            (uri, format, data, size, last_access),
         )
         .await?;
         Ok(())
     }
 
     async fn get_media(&self, uri: Key, format: Key) -> Result<Option<Vec<u8>>> {
-        Ok(self
-            .query_row(
-                "SELECT data FROM media WHERE uri = ? AND format = ?",
-                (uri, format),
-                |row| row.get(0),
-            )
-            .await
-            .optional()?)
+        let last_access = i64::from(MilliSecondsSinceUnixEpoch::now().get());
+        self.with_transaction(move |txn| {
+            let data: Option<Vec<u8>> = txn
+                .query_row(
+                    "SELECT data FROM media WHERE uri = ? AND format = ?",
+                    (&uri, &format),
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if data.is_some() {
+                txn.execute(
+                    "UPDATE media SET last_access = ? WHERE uri = ? AND format = ?",
+                    (last_access, &uri, &format),
+                )?;
+            }
+
+            Result::<_, Error>::Ok(data)
+        })
+        .await
     }
 
     async fn remove_media(&self, uri: Key, format: Key) -> Result<()> {
@@ -854,6 +896,53 @@ trait SqliteObjectStateStoreExt: SqliteObjectExt {
         self.execute("DELETE FROM media WHERE uri = ?", (uri,)).await?;
         Ok(())
     }
+
+    async fn get_media_size(&self) -> Result<usize> {
+        Ok(self.query_row("SELECT COALESCE(SUM(size), 0) FROM media", (), |row| row.get(0)).await?)
+    }
+
+    async fn clean_up_media(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<()> {
+        self.with_transaction(move |txn| {
+            if let Some(max_file_size) = policy.max_file_size {
+                txn.execute("DELETE FROM media WHERE size > ?", (max_file_size,))?;
+            }
+
+            if let Some(expiry) = policy.last_access_expiry {
+                let expiry = i64::try_from(expiry.as_millis()).unwrap_or(i64::MAX);
+                let oldest_access = i64::from(current_time.get()).saturating_sub(expiry);
+                txn.execute("DELETE FROM media WHERE last_access < ?", (oldest_access,))?;
+            }
+
+            if policy.max_cache_size.is_some() {
+                // Keep the most recently accessed media that fits in the cache.
+                let entries = txn
+                    .prepare("SELECT rowid, size FROM media ORDER BY last_access DESC")?
+                    .query_map((), |row| Ok((row.get::<_, i64>(0)?, row.get::<_, usize>(1)?)))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+                let mut cache_size = 0;
+                for (rowid, size) in entries {
+                    cache_size += size;
+
+                    if policy.exceeds_max_cache_size(cache_size) {
+                        txn.execute("DELETE FROM media WHERE rowid = ?", (rowid,))?;
+                    }
+                }
+            }
+
+            Result::<_, Error>::Ok(())
+        })
+        .await
+    }
+
+    async fn remove_all_medias(&self) -> Result<()> {
+        self.execute("DELETE FROM media", ()).await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -873,12 +962,30 @@ impl StateStore for SqliteStateStore {
             .get_kv_blob(self.encode_state_store_data_key(key))
             .await?
             .map(|data| {
-                let string = self.deserialize_value(&data)?;
                 Ok(match key {
-                    StateStoreDataKey::SyncToken => StateStoreDataValue::SyncToken(string),
-                    StateStoreDataKey::Filter(_) => StateStoreDataValue::Filter(string),
+                    StateStoreDataKey::SyncToken => {
+                        StateStoreDataValue::SyncToken(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::Filter(_) => {
+                        StateStoreDataValue::Filter(self.deserialize_value(&data)?)
+                    }
                     StateStoreDataKey::UserAvatarUrl(_) => {
-                        StateStoreDataValue::UserAvatarUrl(string)
+                        StateStoreDataValue::UserAvatarUrl(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::HomeserverDiscovery => {
+                        StateStoreDataValue::HomeserverDiscovery(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::PendingUploads => {
+                        StateStoreDataValue::PendingUploads(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::ComposerDraft(_) => {
+                        StateStoreDataValue::ComposerDraft(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::ScheduledMessages => {
+                        StateStoreDataValue::ScheduledMessages(self.deserialize_value(&data)?)
+                    }
+                    StateStoreDataKey::SentTransactions => {
+                        StateStoreDataValue::SentTransactions(self.deserialize_value(&data)?)
                     }
                 })
             })
@@ -890,19 +997,42 @@ impl StateStore for SqliteStateStore {
         key: StateStoreDataKey<'_>,
         value: StateStoreDataValue,
     ) -> Result<()> {
-        let value = match key {
-            StateStoreDataKey::SyncToken => {
-                value.into_sync_token().expect("Session data not a sync token")
-            }
-            StateStoreDataKey::Filter(_) => value.into_filter().expect("Session data not a filter"),
-            StateStoreDataKey::UserAvatarUrl(_) => {
-                value.into_user_avatar_url().expect("Session data not an user avatar url")
+        let serialized_value = match key {
+            StateStoreDataKey::SyncToken => self.serialize_value(
+                &value.into_sync_token().expect("Session data not a sync token"),
+            )?,
+            StateStoreDataKey::Filter(_) => {
+                self.serialize_value(&value.into_filter().expect("Session data not a filter"))?
             }
+            StateStoreDataKey::UserAvatarUrl(_) => self.serialize_value(
+                &value.into_user_avatar_url().expect("Session data not an user avatar url"),
+            )?,
+            StateStoreDataKey::HomeserverDiscovery => self.serialize_value(
+                &value
+                    .into_homeserver_discovery()
+                    .expect("Session data not a homeserver discovery"),
+            )?,
+            StateStoreDataKey::PendingUploads => self.serialize_value(
+                &value.into_pending_uploads().expect("Session data not a list of pending uploads"),
+            )?,
+            StateStoreDataKey::ComposerDraft(_) => self.serialize_value(
+                &value.into_composer_draft().expect("Session data not a composer draft"),
+            )?,
+            StateStoreDataKey::ScheduledMessages => self.serialize_value(
+                &value
+                    .into_scheduled_messages()
+                    .expect("Session data not a list of scheduled messages"),
+            )?,
+            StateStoreDataKey::SentTransactions => self.serialize_value(
+                &value
+                    .into_sent_transactions()
+                    .expect("Session data not a list of sent transactions"),
+            )?,
         };
 
         self.acquire()
             .await?
-            .set_kv_blob(self.encode_state_store_data_key(key), self.serialize_value(&value)?)
+            .set_kv_blob(self.encode_state_store_data_key(key), serialized_value)
             .await
     }
 
@@ -960,7 +1090,8 @@ impl StateStore for SqliteStateStore {
                 }
 
                 for (room_id, room_info) in room_infos {
-                    let stripped = room_info.state() == RoomState::Invited;
+                    let stripped =
+                        matches!(room_info.state(), RoomState::Invited | RoomState::Knocked);
                     // Remove non-stripped data for stripped rooms and vice-versa.
                     this.remove_maybe_stripped_room_data(txn, &room_id, !stripped)?;
 
@@ -1373,8 +1504,10 @@ impl StateStore for SqliteStateStore {
     }
 
     async fn get_stripped_room_infos(&self) -> Result<Vec<RoomInfo>> {
-        let states =
-            vec![self.encode_key(keys::ROOM_INFO, serde_json::to_string(&RoomState::Invited)?)];
+        let states = vec![
+            self.encode_key(keys::ROOM_INFO, serde_json::to_string(&RoomState::Invited)?),
+            self.encode_key(keys::ROOM_INFO, serde_json::to_string(&RoomState::Knocked)?),
+        ];
         self.acquire()
             .await?
             .get_room_infos(states)
@@ -1538,8 +1671,9 @@ impl StateStore for SqliteStateStore {
     async fn add_media_content(&self, request: &MediaRequest, content: Vec<u8>) -> Result<()> {
         let uri = self.encode_key(keys::MEDIA, request.source.unique_key());
         let format = self.encode_key(keys::MEDIA, request.format.unique_key());
+        let size = content.len();
         let data = self.encode_value(content)?;
-        self.acquire().await?.set_media(uri, format, data).await
+        self.acquire().await?.set_media(uri, format, data, size).await
     }
 
     async fn get_media_content(&self, request: &MediaRequest) -> Result<Option<Vec<u8>>> {
@@ -1560,6 +1694,22 @@ impl StateStore for SqliteStateStore {
         self.acquire().await?.remove_uri_medias(uri).await
     }
 
+    async fn media_cache_size(&self) -> Result<usize> {
+        self.acquire().await?.get_media_size().await
+    }
+
+    async fn clean_up_media_cache(
+        &self,
+        policy: MediaRetentionPolicy,
+        current_time: MilliSecondsSinceUnixEpoch,
+    ) -> Result<()> {
+        self.acquire().await?.clean_up_media(policy, current_time).await
+    }
+
+    async fn clear_media_cache(&self) -> Result<()> {
+        self.acquire().await?.remove_all_medias().await
+    }
+
     async fn remove_room(&self, room_id: &RoomId) -> Result<()> {
         let this = self.clone();
         let room_id = room_id.to_owned();
@@ -1659,12 +1809,20 @@ mod migration_tests {
         },
     };
 
-    use matrix_sdk_base::{sync::UnreadNotificationsCount, RoomState, StateStore};
+    use matrix_sdk_base::{
+        media::{MediaFormat, MediaRequest, MediaRetentionPolicy, UniqueKey},
+        sync::UnreadNotificationsCount,
+        RoomState, StateStore,
+    };
     use matrix_sdk_test::async_test;
     use once_cell::sync::Lazy;
     use ruma::{
-        events::{room::create::RoomCreateEventContent, StateEventType},
-        room_id, server_name, user_id, EventId, MilliSecondsSinceUnixEpoch, RoomId, UserId,
+        events::{
+            room::{create::RoomCreateEventContent, MediaSource},
+            StateEventType,
+        },
+        mxc_uri, room_id, server_name, user_id, EventId, MilliSecondsSinceUnixEpoch, RoomId,
+        UserId,
     };
     use rusqlite::Transaction;
     use serde_json::json;
@@ -1915,4 +2073,43 @@ mod migration_tests {
         assert_eq!(room_c.name(), None);
         assert_eq!(room_c.creator(), Some(room_c_create_sender));
     }
+
+    #[async_test]
+    pub async fn test_migrating_v3_to_v4() {
+        let path = new_path();
+        let request = MediaRequest {
+            source: MediaSource::Plain(mxc_uri!("mxc://localhost/media").to_owned()),
+            format: MediaFormat::File,
+        };
+        let content = b"hello".to_vec();
+
+        // Create and populate db.
+        {
+            let db = create_fake_db(&path, 3).await.unwrap();
+            let conn = db.pool.get().await.unwrap();
+
+            let uri = db.encode_key(keys::MEDIA, request.source.unique_key());
+            let format = db.encode_key(keys::MEDIA, request.format.unique_key());
+            let data = db.encode_value(content.clone()).unwrap();
+            conn.execute(
+                "INSERT INTO media (uri, format, data) VALUES (?, ?, ?)",
+                (uri, format, data),
+            )
+            .await
+            .unwrap();
+        }
+
+        // This transparently migrates to the latest version.
+        let store = SqliteStateStore::open(path, Some(SECRET)).await.unwrap();
+
+        assert_eq!(store.get_media_content(&request).await.unwrap(), Some(content));
+        // The size of the encrypted data is used for the existing media.
+        assert!(store.media_cache_size().await.unwrap() > 0);
+
+        // The existing media is considered as accessed during the migration.
+        let policy = MediaRetentionPolicy::empty()
+            .with_last_access_expiry(Some(std::time::Duration::from_secs(60)));
+        store.clean_up_media_cache(policy, MilliSecondsSinceUnixEpoch::now()).await.unwrap();
+        assert!(store.get_media_content(&request).await.unwrap().is_some());
+    }
 }
@@ -31,7 +31,7 @@ use std::time::Duration;
 use async_stream::stream;
 use futures_core::stream::Stream;
 use futures_util::{pin_mut, StreamExt};
-use matrix_sdk::{Client, SlidingSync, LEASE_DURATION_MS};
+use matrix_sdk::{time::Clock, Client, SlidingSync, LEASE_DURATION_MS};
 use ruma::{api::client::sync::sync_events::v4, assign};
 use tokio::sync::OwnedMutexGuard;
 use tracing::{debug, trace};
@@ -170,7 +170,7 @@ impl EncryptionSyncService {
                     LEASE_DURATION_MS
                 );
 
-                tokio::time::sleep(Duration::from_millis(LEASE_DURATION_MS.into())).await;
+                self.client.clock().sleep(Duration::from_millis(LEASE_DURATION_MS.into())).await;
 
                 lock_guard = self
                     .client
@@ -13,31 +13,46 @@
 // limitations under the License.
 
 use std::{
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
     time::Duration,
 };
 
 use futures_util::{pin_mut, StreamExt as _};
-use matrix_sdk::{room::Room, Client, ClientBuildError, SlidingSyncList, SlidingSyncMode};
+use matrix_sdk::{
+    media::{MediaFormat, MediaRequest, MediaThumbnailSize},
+    notification_settings::{InviteNotificationPolicy, PushRuleEvaluator},
+    room::{IncomingCall, Room},
+    time::Clock,
+    Client, ClientBuildError, SlidingSyncList, SlidingSyncMode,
+};
 use matrix_sdk_base::{
     crypto::{vodozemac, MegolmError},
     deserialized_responses::TimelineEvent,
+    timeout::timeout,
     RoomState, StoreError,
 };
 use ruma::{
-    api::client::sync::sync_events::v4::{
-        AccountDataConfig, RoomSubscription, SyncRequestListFilters,
+    api::client::{
+        media::get_content_thumbnail::v3::Method,
+        sync::sync_events::v4::{AccountDataConfig, RoomSubscription, SyncRequestListFilters},
     },
     assign,
     events::{
-        room::{member::StrippedRoomMemberEvent, message::SyncRoomMessageEvent},
+        poll::unstable_start::SyncUnstablePollStartEvent,
+        room::{
+            member::{MembershipState, StrippedRoomMemberEvent},
+            message::{MessageType, SyncRoomMessageEvent},
+            ImageInfo, MediaSource, ThumbnailInfo,
+        },
+        sticker::SyncStickerEvent,
         AnyFullStateEventContent, AnyStateEvent, AnySyncMessageLikeEvent, AnySyncTimelineEvent,
         FullStateEventContent, StateEventType, TimelineEventType,
     },
     html::RemoveReplyFallback,
     push::Action,
     serde::Raw,
-    uint, EventId, OwnedEventId, RoomId, UserId,
+    uint, EventId, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UInt, UserId,
 };
 use thiserror::Error;
 use tokio::sync::Mutex as AsyncMutex;
@@ -49,6 +64,14 @@ use crate::{
     DEFAULT_SANITIZER_MODE,
 };
 
+/// The default maximum size of the thumbnails fetched for notifications, in
+/// bytes.
+const DEFAULT_MAX_THUMBNAIL_SIZE: u64 = 1024 * 1024;
+
+/// The default maximum duration of the download of the thumbnails fetched for
+/// notifications.
+const DEFAULT_THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// What kind of process setup do we have for this notification client?
 #[derive(Clone)]
 pub enum NotificationProcessSetup {
@@ -91,6 +114,12 @@ pub struct NotificationClient {
     /// rules?
     filter_by_push_rules: bool,
 
+    /// The maximum size of the thumbnails to fetch, in bytes.
+    max_thumbnail_size: u64,
+
+    /// The maximum duration of the download of a thumbnail.
+    thumbnail_timeout: Duration,
+
     /// A mutex to serialize requests to the notifications sliding sync.
     ///
     /// If several notifications come in at the same time (e.g. network was
@@ -144,6 +173,60 @@ impl NotificationClient {
         }
     }
 
+    /// Fetches the content of several notifications at once.
+    ///
+    /// This works like [`Self::get_notification`], but a single sliding sync
+    /// subscribes to all the rooms, and the push rules are only loaded once
+    /// for the whole batch. The events which couldn't be found this way are
+    /// then fetched with `/context` queries.
+    ///
+    /// The result has an entry for each requested event, with the same meaning
+    /// as the result of [`Self::get_notification`]. An error is only returned
+    /// for the whole batch when the shared steps fail, i.e. setting up the
+    /// sliding sync or loading the push rules.
+    #[instrument(skip_all)]
+    pub async fn get_notifications(
+        &self,
+        room_to_event_ids: &HashMap<OwnedRoomId, Vec<OwnedEventId>>,
+    ) -> Result<HashMap<OwnedEventId, Result<Option<NotificationItem>, Error>>, Error> {
+        let mut raw_events = self.try_sliding_sync(room_to_event_ids).await?;
+
+        let sync_evaluator = PushRuleEvaluator::from_account(&self.client).await?;
+        let mut context_evaluator = None;
+
+        let mut results = HashMap::new();
+
+        for (room_id, event_ids) in room_to_event_ids {
+            for event_id in event_ids {
+                let status = match raw_events.remove(event_id) {
+                    Some(raw_event) => {
+                        self.resolve_sliding_sync_event(room_id, raw_event, &sync_evaluator).await
+                    }
+                    None => Ok(NotificationStatus::EventNotFound),
+                };
+
+                let result = match status {
+                    Ok(NotificationStatus::Event(item)) => Ok(Some(item)),
+                    Ok(NotificationStatus::EventFilteredOut) => Ok(None),
+                    Ok(NotificationStatus::EventNotFound) => {
+                        let evaluator = match context_evaluator.take() {
+                            Some(evaluator) => evaluator,
+                            None => PushRuleEvaluator::from_account(&self.parent_client).await?,
+                        };
+                        let result = self.resolve_with_context(room_id, event_id, &evaluator).await;
+                        context_evaluator = Some(evaluator);
+                        result
+                    }
+                    Err(error) => Err(error),
+                };
+
+                results.insert(event_id.clone(), result);
+            }
+        }
+
+        Ok(results)
+    }
+
     /// Run an encryption sync loop, in case an event is still encrypted.
     ///
     /// Will return true if and only:
@@ -209,7 +292,7 @@ impl NotificationClient {
                     for _ in 0..3 {
                         trace!("waiting for decryption…");
 
-                        tokio::time::sleep(Duration::from_millis(wait)).await;
+                        self.client.clock().sleep(Duration::from_millis(wait)).await;
 
                         match room.decrypt_event(raw_event.cast_ref()).await {
                             Ok(new_event) => {
@@ -275,40 +358,46 @@ impl NotificationClient {
         }
     }
 
-    /// Try to run a sliding sync (without encryption) to retrieve the event
-    /// from the notification.
+    /// Try to run a sliding sync (without encryption) to retrieve the events
+    /// from the notifications.
     ///
     /// This works by requesting explicit state that'll be useful for building
-    /// the `NotificationItem`, and subscribing to the room which the
-    /// notification relates to.
+    /// the `NotificationItem`s, and subscribing to the rooms which the
+    /// notifications relate to.
+    ///
+    /// Returns the events which could be found.
     #[instrument(skip_all)]
     async fn try_sliding_sync(
         &self,
-        room_id: &RoomId,
-        event_id: &EventId,
-    ) -> Result<Option<RawNotificationEvent>, Error> {
+        room_to_event_ids: &HashMap<OwnedRoomId, Vec<OwnedEventId>>,
+    ) -> Result<HashMap<OwnedEventId, RawNotificationEvent>, Error> {
         // Serialize all the calls to this method by taking a lock at the beginning,
         // that will be dropped later.
         let _guard = self.notification_sync_mutex.lock().await;
 
-        // Set up a sliding sync that only subscribes to the room that had the
-        // notification, so we can figure out the full event and associated
+        // Set up a sliding sync that only subscribes to the rooms that had the
+        // notifications, so we can figure out the full events and associated
         // information.
 
-        let notification = Arc::new(Mutex::new(None));
+        let notifications = Arc::new(Mutex::new(HashMap::new()));
+
+        let target_event_ids: Arc<HashSet<OwnedEventId>> =
+            Arc::new(room_to_event_ids.values().flatten().cloned().collect());
 
-        let cloned_notif = notification.clone();
-        let target_event_id = event_id.to_owned();
+        let cloned_notifs = notifications.clone();
+        let cloned_targets = target_event_ids.clone();
 
         let timeline_event_handler =
             self.client.add_event_handler(move |raw: Raw<AnySyncTimelineEvent>| async move {
                 match raw.get_field::<OwnedEventId>("event_id") {
                     Ok(Some(event_id)) => {
-                        if event_id == target_event_id {
-                            // found it! There shouldn't be a previous event before, but if there
+                        if cloned_targets.contains(&event_id) {
+                            // found one! There shouldn't be a previous event before, but if there
                             // is, that should be ok to just replace it.
-                            *cloned_notif.lock().unwrap() =
-                                Some(RawNotificationEvent::Timeline(raw));
+                            cloned_notifs
+                                .lock()
+                                .unwrap()
+                                .insert(event_id, RawNotificationEvent::Timeline(raw));
                         }
                     }
                     Ok(None) => {
@@ -320,16 +409,19 @@ impl NotificationClient {
                 }
             });
 
-        let cloned_notif = notification.clone();
-        let target_event_id = event_id.to_owned();
+        let cloned_notifs = notifications.clone();
+        let cloned_targets = target_event_ids.clone();
         let stripped_member_handler =
             self.client.add_event_handler(move |raw: Raw<StrippedRoomMemberEvent>| async move {
                 match raw.get_field::<OwnedEventId>("event_id") {
                     Ok(Some(event_id)) => {
-                        if event_id == target_event_id {
-                            // found it! There shouldn't be a previous event before, but if there
+                        if cloned_targets.contains(&event_id) {
+                            // found one! There shouldn't be a previous event before, but if there
                             // is, that should be ok to just replace it.
-                            *cloned_notif.lock().unwrap() = Some(RawNotificationEvent::Invite(raw));
+                            cloned_notifs
+                                .lock()
+                                .unwrap()
+                                .insert(event_id, RawNotificationEvent::Invite(raw));
                         }
                     }
                     Ok(None) => {
@@ -375,13 +467,15 @@ impl NotificationClient {
             .build()
             .await?;
 
-        sync.subscribe_to_room(
-            room_id.to_owned(),
-            Some(assign!(RoomSubscription::default(), {
-                required_state,
-                timeline_limit: Some(uint!(16))
-            })),
-        );
+        for room_id in room_to_event_ids.keys() {
+            sync.subscribe_to_room(
+                room_id.to_owned(),
+                Some(assign!(RoomSubscription::default(), {
+                    required_state: required_state.clone(),
+                    timeline_limit: Some(uint!(16))
+                })),
+            );
+        }
 
         let mut remaining_attempts = 3;
 
@@ -394,8 +488,8 @@ impl NotificationClient {
                 break;
             }
 
-            if notification.lock().unwrap().is_some() {
-                // We got the event.
+            if notifications.lock().unwrap().len() == target_event_ids.len() {
+                // We got all the events.
                 break;
             }
 
@@ -409,8 +503,8 @@ impl NotificationClient {
         self.client.remove_event_handler(stripped_member_handler);
         self.client.remove_event_handler(timeline_event_handler);
 
-        let maybe_event = notification.lock().unwrap().take();
-        Ok(maybe_event)
+        let events = std::mem::take(&mut *notifications.lock().unwrap());
+        Ok(events)
     }
 
     /// Get a full notification, given a room id and event id.
@@ -422,10 +516,24 @@ impl NotificationClient {
         room_id: &RoomId,
         event_id: &EventId,
     ) -> Result<NotificationStatus, Error> {
-        let Some(mut raw_event) = self.try_sliding_sync(room_id, event_id).await? else {
+        let room_to_event_ids = HashMap::from([(room_id.to_owned(), vec![event_id.to_owned()])]);
+
+        let Some(raw_event) = self.try_sliding_sync(&room_to_event_ids).await?.remove(event_id)
+        else {
             return Ok(NotificationStatus::EventNotFound);
         };
 
+        let evaluator = PushRuleEvaluator::from_account(&self.client).await?;
+        self.resolve_sliding_sync_event(room_id, raw_event, &evaluator).await
+    }
+
+    /// Build the notification for an event retrieved with the sliding sync.
+    async fn resolve_sliding_sync_event(
+        &self,
+        room_id: &RoomId,
+        mut raw_event: RawNotificationEvent,
+        evaluator: &PushRuleEvaluator,
+    ) -> Result<NotificationStatus, Error> {
         // At this point it should have been added by the sync, if it's not, give up.
         let Some(room) = self.client.get_room(room_id) else { return Err(Error::UnknownRoom) };
 
@@ -434,25 +542,39 @@ impl NotificationClient {
                 // Timeline events may be encrypted, so make sure they get decrypted first.
                 if let Some(timeline_event) = self.retry_decryption(&room, timeline_event).await? {
                     raw_event = RawNotificationEvent::Timeline(timeline_event.event.cast());
-                    timeline_event.push_actions
+                    match timeline_event.push_actions {
+                        Some(push_actions) => push_actions,
+                        None => evaluator.room_actions(&room, &timeline_event.event).await?,
+                    }
                 } else {
-                    room.event_push_actions(timeline_event).await?
+                    evaluator.room_actions(&room, timeline_event).await?
                 }
             }
             RawNotificationEvent::Invite(invite_event) => {
                 // Invite events can't be encrypted, so they should be in clear text.
-                room.event_push_actions(invite_event).await?
+                evaluator.room_actions(&room, invite_event).await?
             }
         };
 
-        if let Some(push_actions) = &push_actions {
-            if self.filter_by_push_rules && !push_actions.iter().any(|a| a.should_notify()) {
+        if self.filter_by_push_rules && !push_actions.iter().any(|a| a.should_notify()) {
+            return Ok(NotificationStatus::EventFilteredOut);
+        }
+
+        if let RawNotificationEvent::Invite(invite_event) = &raw_event {
+            if self.filter_by_push_rules && !self.is_invite_notified(invite_event).await? {
                 return Ok(NotificationStatus::EventFilteredOut);
             }
         }
 
         Ok(NotificationStatus::Event(
-            NotificationItem::new(&room, &raw_event, push_actions.as_deref(), Vec::new()).await?,
+            NotificationItem::new(
+                &room,
+                &raw_event,
+                Some(&push_actions),
+                Vec::new(),
+                self.thumbnail_options(),
+            )
+            .await?,
         ))
     }
 
@@ -472,6 +594,50 @@ impl NotificationClient {
         &self,
         room_id: &RoomId,
         event_id: &EventId,
+    ) -> Result<Option<NotificationItem>, Error> {
+        let evaluator = PushRuleEvaluator::from_account(&self.parent_client).await?;
+        self.resolve_with_context(room_id, event_id, &evaluator).await
+    }
+
+    /// Whether the invite should notify according to the invite notification
+    /// policy of the user.
+    ///
+    /// The push rules already filter out all the invites when they shouldn't
+    /// notify, but they can't restrict the notifications to the invites sent
+    /// by known users, i.e. users sharing a joined room with the current user.
+    async fn is_invite_notified(
+        &self,
+        invite_event: &Raw<StrippedRoomMemberEvent>,
+    ) -> Result<bool, Error> {
+        let policy =
+            self.parent_client.notification_settings().await.invite_notification_policy().await;
+        if policy != InviteNotificationPolicy::KnownUsersOnly {
+            return Ok(true);
+        }
+
+        let sender = invite_event
+            .get_field::<OwnedUserId>("sender")
+            .ok()
+            .flatten()
+            .ok_or(Error::InvalidRumaEvent)?;
+
+        for room in self.parent_client.joined_rooms() {
+            if let Some(member) = room.get_member_no_sync(&sender).await? {
+                if *member.membership() == MembershipState::Join {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Build the notification for an event retrieved with a `/context` query.
+    async fn resolve_with_context(
+        &self,
+        room_id: &RoomId,
+        event_id: &EventId,
+        evaluator: &PushRuleEvaluator,
     ) -> Result<Option<NotificationItem>, Error> {
         info!("fetching notification event with a /context query");
 
@@ -489,12 +655,12 @@ impl NotificationClient {
             timeline_event = decrypted_event;
         }
 
-        if self.filter_by_push_rules
-            && !timeline_event
-                .push_actions
-                .as_ref()
-                .is_some_and(|actions| actions.iter().any(|a| a.should_notify()))
-        {
+        let push_actions = match timeline_event.push_actions {
+            Some(push_actions) => push_actions,
+            None => evaluator.room_actions(&room, &timeline_event.event).await?,
+        };
+
+        if self.filter_by_push_rules && !push_actions.iter().any(|a| a.should_notify()) {
             return Ok(None);
         }
 
@@ -502,12 +668,21 @@ impl NotificationClient {
             NotificationItem::new(
                 &room,
                 &RawNotificationEvent::Timeline(timeline_event.event.cast()),
-                timeline_event.push_actions.as_deref(),
+                Some(&push_actions),
                 state_events,
+                self.thumbnail_options(),
             )
             .await?,
         ))
     }
+
+    fn thumbnail_options(&self) -> ThumbnailOptions<'_> {
+        ThumbnailOptions {
+            client: &self.parent_client,
+            max_size: self.max_thumbnail_size,
+            timeout: self.thumbnail_timeout,
+        }
+    }
 }
 
 fn is_event_encrypted(event_type: TimelineEventType) -> bool {
@@ -538,6 +713,8 @@ pub struct NotificationClientBuilder {
     /// SDK client that uses the same state store as the caller's context.
     parent_client: Client,
     filter_by_push_rules: bool,
+    max_thumbnail_size: u64,
+    thumbnail_timeout: Duration,
 
     /// Is the notification client running on its own process or not?
     process_setup: NotificationProcessSetup,
@@ -550,22 +727,58 @@ impl NotificationClientBuilder {
     ) -> Result<Self, Error> {
         let client = parent_client.notification_client().await?;
 
-        Ok(Self { client, parent_client, filter_by_push_rules: false, process_setup })
+        Ok(Self {
+            client,
+            parent_client,
+            filter_by_push_rules: false,
+            max_thumbnail_size: DEFAULT_MAX_THUMBNAIL_SIZE,
+            thumbnail_timeout: DEFAULT_THUMBNAIL_TIMEOUT,
+            process_setup,
+        })
     }
 
     /// Filter out the notification event according to the push rules present in
     /// the event.
+    ///
+    /// Invites are also filtered out according to the invite notification
+    /// policy of the user, when it only allows invites from known users.
     pub fn filter_by_push_rules(mut self) -> Self {
         self.filter_by_push_rules = true;
         self
     }
 
+    /// Set the maximum size of the thumbnails fetched for the notifications, in
+    /// bytes.
+    ///
+    /// Thumbnails which are known or turn out to be bigger are left out of the
+    /// notifications. Set it to 0 to never fetch thumbnails.
+    ///
+    /// Defaults to 1 MiB.
+    pub fn max_thumbnail_size(mut self, max_size: u64) -> Self {
+        self.max_thumbnail_size = max_size;
+        self
+    }
+
+    /// Set the maximum duration of the download of the thumbnails fetched for
+    /// the notifications.
+    ///
+    /// Thumbnails that take longer to download are left out of the
+    /// notifications.
+    ///
+    /// Defaults to 5 seconds.
+    pub fn thumbnail_timeout(mut self, timeout: Duration) -> Self {
+        self.thumbnail_timeout = timeout;
+        self
+    }
+
     /// Finishes configuring the `NotificationClient`.
     pub fn build(self) -> NotificationClient {
         NotificationClient {
             client: self.client,
             parent_client: self.parent_client,
             filter_by_push_rules: self.filter_by_push_rules,
+            max_thumbnail_size: self.max_thumbnail_size,
+            thumbnail_timeout: self.thumbnail_timeout,
             notification_sync_mutex: AsyncMutex::new(()),
             encryption_sync_mutex: AsyncMutex::new(()),
             process_setup: self.process_setup,
@@ -623,6 +836,40 @@ pub struct NotificationItem {
     /// It is set if and only if the push actions could be determined.
     pub is_noisy: Option<bool>,
     pub has_mention: Option<bool>,
+
+    /// A thumbnail of the image, video or sticker of the event, already
+    /// decrypted if necessary.
+    ///
+    /// It is only set if it could be fetched within the maximum thumbnail size
+    /// of the client.
+    pub thumbnail: Option<NotificationThumbnail>,
+    /// The name and size of the file of the event, for file messages.
+    pub file_info: Option<NotificationFileInfo>,
+    /// The question of the poll started by the event.
+    pub poll_question: Option<String>,
+    /// The call the event rings for, if it is a call invite or a ring
+    /// notification addressed to the current user.
+    ///
+    /// The caller is the sender of the event.
+    pub incoming_call: Option<IncomingCall>,
+}
+
+/// The thumbnail of the media of a notification.
+#[derive(Clone, Debug)]
+pub struct NotificationThumbnail {
+    /// The data of the thumbnail.
+    pub data: Vec<u8>,
+    /// The MIME type of the thumbnail, if known.
+    pub mimetype: Option<String>,
+}
+
+/// Information about the file of a notification.
+#[derive(Clone, Debug)]
+pub struct NotificationFileInfo {
+    /// The name of the file.
+    pub filename: String,
+    /// The size of the file in bytes, if known.
+    pub size: Option<u64>,
 }
 
 impl NotificationItem {
@@ -631,6 +878,7 @@ impl NotificationItem {
         raw_event: &RawNotificationEvent,
         push_actions: Option<&[Action]>,
         state_events: Vec<Raw<AnyStateEvent>>,
+        thumbnail_options: ThumbnailOptions<'_>,
     ) -> Result<Self, Error> {
         let event = match raw_event {
             RawNotificationEvent::Timeline(raw_event) => {
@@ -688,6 +936,26 @@ impl NotificationItem {
         let is_noisy = push_actions.map(|actions| actions.iter().any(|a| a.sound().is_some()));
         let has_mention = push_actions.map(|actions| actions.iter().any(|a| a.is_highlight()));
 
+        let (thumbnail, file_info, poll_question) = match &event {
+            NotificationEvent::Timeline(event) => {
+                let thumbnail = match ThumbnailSource::from_event(event) {
+                    Some(source) if thumbnail_options.max_size > 0 => {
+                        source.fetch(thumbnail_options).await
+                    }
+                    _ => None,
+                };
+                (thumbnail, file_info(event), poll_question(event))
+            }
+            NotificationEvent::Invite(_) => (None, None, None),
+        };
+
+        let incoming_call = match raw_event {
+            RawNotificationEvent::Timeline(raw_event) => {
+                IncomingCall::from_raw_event(room.room_id(), raw_event, room.own_user_id())
+            }
+            RawNotificationEvent::Invite(_) => None,
+        };
+
         let item = NotificationItem {
             event,
             sender_display_name,
@@ -700,12 +968,174 @@ impl NotificationItem {
             joined_members_count: room.joined_members_count(),
             is_noisy,
             has_mention,
+            thumbnail,
+            file_info,
+            poll_question,
+            incoming_call,
         };
 
         Ok(item)
     }
 }
 
+/// How the thumbnails of the notifications are fetched.
+#[derive(Clone, Copy)]
+struct ThumbnailOptions<'a> {
+    /// The client used to download the thumbnails.
+    ///
+    /// This is the parent client, so the thumbnails end up in the media cache
+    /// of the main app, which doesn't need to download them again.
+    client: &'a Client,
+    /// The maximum size of the thumbnails, in bytes.
+    max_size: u64,
+    /// The maximum duration of the download of a thumbnail.
+    timeout: Duration,
+}
+
+/// A media that can be fetched as the thumbnail of a notification.
+struct ThumbnailSource {
+    source: MediaSource,
+    size: Option<UInt>,
+    mimetype: Option<String>,
+}
+
+impl ThumbnailSource {
+    /// Get the source of the thumbnail of the media of the event, if any.
+    fn from_event(event: &AnySyncTimelineEvent) -> Option<Self> {
+        let AnySyncTimelineEvent::MessageLike(event) = event else {
+            return None;
+        };
+
+        match event {
+            AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(ev)) => {
+                match &ev.content.msgtype {
+                    MessageType::Image(content) => {
+                        Some(Self::from_image(content.source.clone(), content.info.as_deref()))
+                    }
+                    // Videos can't be previewed without their thumbnail.
+                    MessageType::Video(content) => {
+                        let info = content.info.as_deref()?;
+                        Self::from_thumbnail(
+                            info.thumbnail_source.as_ref(),
+                            info.thumbnail_info.as_deref(),
+                        )
+                    }
+                    _ => None,
+                }
+            }
+            AnySyncMessageLikeEvent::Sticker(SyncStickerEvent::Original(ev)) => {
+                Some(Self::from_image(
+                    MediaSource::Plain(ev.content.url.clone()),
+                    Some(&ev.content.info),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Use the thumbnail of the image if there is one, or the image itself.
+    fn from_image(source: MediaSource, info: Option<&ImageInfo>) -> Self {
+        info.and_then(|info| {
+            Self::from_thumbnail(info.thumbnail_source.as_ref(), info.thumbnail_info.as_deref())
+        })
+        .unwrap_or_else(|| Self {
+            source,
+            size: info.and_then(|info| info.size),
+            mimetype: info.and_then(|info| info.mimetype.clone()),
+        })
+    }
+
+    fn from_thumbnail(source: Option<&MediaSource>, info: Option<&ThumbnailInfo>) -> Option<Self> {
+        Some(Self {
+            source: source?.clone(),
+            size: info.and_then(|info| info.size),
+            mimetype: info.and_then(|info| info.mimetype.clone()),
+        })
+    }
+
+    /// Download the thumbnail, if it fits in the maximum size and the download
+    /// doesn't time out.
+    ///
+    /// Errors are only logged, since a notification without its thumbnail is
+    /// still better than no notification.
+    async fn fetch(self, options: ThumbnailOptions<'_>) -> Option<NotificationThumbnail> {
+        let ThumbnailOptions { client, max_size, timeout: max_duration } = options;
+
+        let (format, mimetype) = match &self.source {
+            // Let the server scale down the media. The MIME type of the result
+            // may differ from the one of the original media.
+            MediaSource::Plain(_) => {
+                let size = MediaThumbnailSize {
+                    method: Method::Scale,
+                    width: uint!(800),
+                    height: uint!(600),
+                };
+                (MediaFormat::Thumbnail(size), None)
+            }
+            // The server can't scale down encrypted media, so the whole file is
+            // downloaded, which is only done if it is known to be small enough.
+            MediaSource::Encrypted(_) => {
+                if !self.size.is_some_and(|size| u64::from(size) <= max_size) {
+                    debug!("Skipping encrypted thumbnail of unknown or too large size");
+                    return None;
+                }
+                (MediaFormat::File, self.mimetype)
+            }
+        };
+
+        let request = MediaRequest { source: self.source, format };
+        let media = client.media();
+        let data = match timeout(media.get_media_content(&request, true), max_duration).await {
+            Ok(Ok(data)) => data,
+            Ok(Err(error)) => {
+                warn!("Couldn't fetch the thumbnail of a notification: {error}");
+                return None;
+            }
+            Err(_) => {
+                warn!("Fetching the thumbnail of a notification timed out");
+                return None;
+            }
+        };
+
+        if data.len() as u64 > max_size {
+            debug!("Skipping thumbnail of {} bytes", data.len());
+            return None;
+        }
+
+        Some(NotificationThumbnail { data, mimetype })
+    }
+}
+
+/// Get the name and size of the file of the event, for file messages.
+fn file_info(event: &AnySyncTimelineEvent) -> Option<NotificationFileInfo> {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+        SyncRoomMessageEvent::Original(ev),
+    )) = event
+    else {
+        return None;
+    };
+    let MessageType::File(content) = &ev.content.msgtype else {
+        return None;
+    };
+
+    Some(NotificationFileInfo {
+        filename: content.filename.clone().unwrap_or_else(|| content.body.clone()),
+        size: content.info.as_ref().and_then(|info| info.size).map(u64::from),
+    })
+}
+
+/// Get the question of the poll started by the event.
+fn poll_question(event: &AnySyncTimelineEvent) -> Option<String> {
+    let AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::UnstablePollStart(
+        SyncUnstablePollStartEvent::Original(ev),
+    )) = event
+    else {
+        return None;
+    };
+
+    Some(ev.content.poll_start().question.text.clone())
+}
+
 /// An error for the [`NotificationClient`].
 #[derive(Debug, Error)]
 pub enum Error {
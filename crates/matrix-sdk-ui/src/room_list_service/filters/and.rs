@@ -0,0 +1,31 @@
+use matrix_sdk::RoomListEntry;
+
+use super::BoxedFilterFn;
+
+/// Create a new filter that will accept the entries accepted by all the given
+/// filters.
+///
+/// With no filters, all entries are accepted.
+pub fn new_filter(filters: Vec<BoxedFilterFn>) -> impl Fn(&RoomListEntry) -> bool {
+    move |room_list_entry| -> bool { filters.iter().all(|filter| filter(room_list_entry)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::new_filter;
+
+    #[test]
+    fn test_and() {
+        let room_list_entry = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+
+        assert!(new_filter(vec![])(&room_list_entry));
+        assert!(new_filter(vec![Box::new(|_| true), Box::new(|_| true)])(&room_list_entry));
+        assert!(new_filter(vec![Box::new(|_| true), Box::new(|_| false)])(&room_list_entry).not());
+        assert!(new_filter(vec![Box::new(|_| false), Box::new(|_| false)])(&room_list_entry).not());
+    }
+}
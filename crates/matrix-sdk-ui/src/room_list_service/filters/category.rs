@@ -0,0 +1,89 @@
+use matrix_sdk::{Client, RoomListEntry};
+
+/// An enum to represent whether a room is about “people” (strictly 2 users) or
+/// “group” (1 or more than 2 users).
+///
+/// The category is determined by whether the room has been marked as a direct
+/// message room in the `m.direct` account data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomCategory {
+    /// The room is a group room, i.e. not a direct message room.
+    Group,
+    /// The room is a direct message room.
+    People,
+}
+
+struct CategoryRoomMatcher<F: Fn(&RoomListEntry) -> Option<RoomCategory>> {
+    category: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<RoomCategory>> CategoryRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry, expected_category: RoomCategory) -> bool {
+        if !matches!(room, RoomListEntry::Filled(_) | RoomListEntry::Invalidated(_)) {
+            return false;
+        }
+
+        (self.category)(room) == Some(expected_category)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms that are not of the expected category.
+pub fn new_filter(
+    client: &Client,
+    expected_category: RoomCategory,
+) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    let matcher = CategoryRoomMatcher {
+        category: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+
+            Some(if room.direct_targets().is_empty() {
+                RoomCategory::Group
+            } else {
+                RoomCategory::People
+            })
+        },
+    };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry, expected_category) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::{CategoryRoomMatcher, RoomCategory};
+
+    #[test]
+    fn test_kind_of_room_list_entry() {
+        let matcher = CategoryRoomMatcher { category: |_| Some(RoomCategory::Group) };
+
+        assert!(!matcher.matches(&RoomListEntry::Empty, RoomCategory::Group));
+        assert!(matcher.matches(
+            &RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned()),
+            RoomCategory::Group
+        ));
+        assert!(matcher.matches(
+            &RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned()),
+            RoomCategory::Group
+        ));
+    }
+
+    #[test]
+    fn test_people_or_group() {
+        let matcher = CategoryRoomMatcher { category: |_| Some(RoomCategory::People) };
+        let room_list_entry = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+
+        assert!(matcher.matches(&room_list_entry, RoomCategory::People));
+        assert!(!matcher.matches(&room_list_entry, RoomCategory::Group));
+
+        let matcher = CategoryRoomMatcher { category: |_| None };
+
+        assert!(!matcher.matches(&room_list_entry, RoomCategory::People));
+        assert!(!matcher.matches(&room_list_entry, RoomCategory::Group));
+    }
+}
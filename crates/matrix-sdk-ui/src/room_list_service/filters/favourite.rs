@@ -0,0 +1,59 @@
+use matrix_sdk::{Client, RoomListEntry};
+
+struct FavouriteRoomMatcher<F: Fn(&RoomListEntry) -> Option<bool>> {
+    is_favourite: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<bool>> FavouriteRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry) -> bool {
+        if !matches!(room, RoomListEntry::Filled(_) | RoomListEntry::Invalidated(_)) {
+            return false;
+        }
+
+        (self.is_favourite)(room).unwrap_or(false)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms that are not tagged as favourite.
+pub fn new_filter(client: &Client) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    let matcher = FavouriteRoomMatcher {
+        is_favourite: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+            Some(room.is_favourite())
+        },
+    };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::FavouriteRoomMatcher;
+
+    #[test]
+    fn test_is_favourite() {
+        let matcher = FavouriteRoomMatcher { is_favourite: |_| Some(true) };
+
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        assert!(matcher.matches(&RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned())));
+    }
+
+    #[test]
+    fn test_is_not_favourite() {
+        let matcher = FavouriteRoomMatcher { is_favourite: |_| Some(false) };
+
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+
+        let matcher = FavouriteRoomMatcher { is_favourite: |_| None };
+
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+    }
+}
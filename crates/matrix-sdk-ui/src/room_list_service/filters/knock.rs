@@ -0,0 +1,63 @@
+use matrix_sdk::{Client, RoomListEntry};
+use matrix_sdk_base::RoomState;
+
+struct KnockRoomMatcher<F: Fn(&RoomListEntry) -> Option<RoomState>> {
+    get_state: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<RoomState>> KnockRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry) -> bool {
+        if !matches!(room, RoomListEntry::Filled(_) | RoomListEntry::Invalidated(_)) {
+            return false;
+        }
+
+        (self.get_state)(room) == Some(RoomState::Knocked)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms the user hasn't knocked on.
+pub fn new_filter(client: &Client) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    let matcher = KnockRoomMatcher {
+        get_state: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+            Some(room.state())
+        },
+    };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use matrix_sdk_base::RoomState;
+    use ruma::room_id;
+
+    use super::KnockRoomMatcher;
+
+    #[test]
+    fn test_all_knock_kind_of_room_list_entry() {
+        // When we can't figure out the room state, nothing matches.
+        let matcher = KnockRoomMatcher { get_state: |_| None };
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+
+        // When a room has been joined, left or the user was invited, it doesn't match.
+        let matcher = KnockRoomMatcher { get_state: |_| Some(RoomState::Joined) };
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        let matcher = KnockRoomMatcher { get_state: |_| Some(RoomState::Left) };
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        let matcher = KnockRoomMatcher { get_state: |_| Some(RoomState::Invited) };
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+
+        // When the user has knocked, it does match (unless it's empty).
+        let matcher = KnockRoomMatcher { get_state: |_| Some(RoomState::Knocked) };
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        assert!(matcher.matches(&RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned())));
+    }
+}
@@ -0,0 +1,59 @@
+use matrix_sdk::{Client, RoomListEntry};
+
+struct LowPriorityRoomMatcher<F: Fn(&RoomListEntry) -> Option<bool>> {
+    is_low_priority: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<bool>> LowPriorityRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry) -> bool {
+        if !matches!(room, RoomListEntry::Filled(_) | RoomListEntry::Invalidated(_)) {
+            return false;
+        }
+
+        (self.is_low_priority)(room).unwrap_or(false)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms that are not tagged as low priority.
+pub fn new_filter(client: &Client) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    let matcher = LowPriorityRoomMatcher {
+        is_low_priority: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+            Some(room.is_low_priority())
+        },
+    };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::LowPriorityRoomMatcher;
+
+    #[test]
+    fn test_is_low_priority() {
+        let matcher = LowPriorityRoomMatcher { is_low_priority: |_| Some(true) };
+
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        assert!(matcher.matches(&RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned())));
+    }
+
+    #[test]
+    fn test_is_not_low_priority() {
+        let matcher = LowPriorityRoomMatcher { is_low_priority: |_| Some(false) };
+
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+
+        let matcher = LowPriorityRoomMatcher { is_low_priority: |_| None };
+
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+    }
+}
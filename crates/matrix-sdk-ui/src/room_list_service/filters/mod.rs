@@ -1,20 +1,50 @@
 mod all;
 mod all_non_left;
+mod and;
+mod category;
+mod favourite;
 mod fuzzy_match_room_name;
+mod invite;
+mod knock;
+mod low_priority;
 mod none;
 mod normalized_match_room_name;
+mod not;
+mod or;
+mod room_info;
+mod space;
+mod unread;
 
 pub use all::new_filter as new_filter_all;
 pub use all_non_left::new_filter as new_filter_all_non_left;
+pub use and::new_filter as new_filter_and;
+pub use category::{new_filter as new_filter_category, RoomCategory};
+pub use favourite::new_filter as new_filter_favourite;
 pub use fuzzy_match_room_name::new_filter as new_filter_fuzzy_match_room_name;
+pub use invite::new_filter as new_filter_invite;
+pub use knock::new_filter as new_filter_knock;
+pub use low_priority::new_filter as new_filter_low_priority;
+use matrix_sdk::RoomListEntry;
 pub use none::new_filter as new_filter_none;
 pub use normalized_match_room_name::new_filter as new_filter_normalized_match_room_name;
+pub use not::new_filter as new_filter_not;
+pub use or::new_filter as new_filter_or;
+pub use room_info::new_filter as new_filter_room_info;
+pub use space::new_filter as new_filter_space;
 use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+pub use unread::new_filter as new_filter_unread;
+
+/// A type-erased filter.
+///
+/// Filters can be combined with [`new_filter_and`], [`new_filter_or`] and
+/// [`new_filter_not`], e.g. to get the unread rooms that are not direct
+/// messages.
+pub type BoxedFilterFn = Box<dyn Fn(&RoomListEntry) -> bool + Send + Sync>;
 
 /// Normalize a string, i.e. decompose it into NFD (Normalization Form D, i.e. a
 /// canonical decomposition, see http://www.unicode.org/reports/tr15/) and
 /// filter out the combining marks.
-fn normalize_string(str: &str) -> String {
+pub(super) fn normalize_string(str: &str) -> String {
     str.nfd().filter(|c| !is_combining_mark(*c)).collect::<String>()
 }
 
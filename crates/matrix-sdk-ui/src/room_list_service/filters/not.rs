@@ -0,0 +1,34 @@
+use std::ops::Not;
+
+use matrix_sdk::RoomListEntry;
+
+use super::BoxedFilterFn;
+
+/// Create a new filter that will negate the given filter.
+pub fn new_filter(filter: BoxedFilterFn) -> impl Fn(&RoomListEntry) -> bool {
+    move |room_list_entry| -> bool { filter(room_list_entry).not() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::new_filter;
+
+    #[test]
+    fn test_true() {
+        let not = new_filter(Box::new(|_| true));
+
+        assert!(not(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())).not());
+    }
+
+    #[test]
+    fn test_false() {
+        let not = new_filter(Box::new(|_| false));
+
+        assert!(not(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+    }
+}
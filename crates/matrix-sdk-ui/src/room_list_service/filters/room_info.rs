@@ -0,0 +1,20 @@
+use matrix_sdk::{Client, RoomInfo, RoomListEntry};
+
+/// Create a new filter that will accept all filled or invalidated entries for
+/// which `predicate` returns `true`, given the [`RoomInfo`] of the room.
+///
+/// Entries whose room is unknown to the `Client` are filtered out. The
+/// predicate is evaluated again every time the `RoomInfo` of a room changes.
+pub fn new_filter(
+    client: &Client,
+    predicate: impl Fn(&RoomInfo) -> bool,
+) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    move |room_list_entry| -> bool {
+        let Some(room_id) = room_list_entry.as_room_id() else { return false };
+        let Some(room) = client.get_room(room_id) else { return false };
+
+        predicate(&room.clone_info())
+    }
+}
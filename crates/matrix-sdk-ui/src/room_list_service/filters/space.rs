@@ -0,0 +1,52 @@
+use matrix_sdk::RoomListEntry;
+use ruma::RoomId;
+
+use super::super::Space;
+
+struct SpaceRoomMatcher<F: Fn(&RoomId) -> bool> {
+    is_child: F,
+}
+
+impl<F: Fn(&RoomId) -> bool> SpaceRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry) -> bool {
+        let Some(room_id) = room.as_room_id() else { return false };
+
+        (self.is_child)(room_id)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms that are not children of the given space.
+///
+/// The filter follows the changes of the children of the space, but the
+/// room list must be told to evaluate it again with
+/// [`RoomListDynamicEntriesController::refresh_filter`] when
+/// [`Space::children_stream`] yields a new value.
+///
+/// [`RoomListDynamicEntriesController::refresh_filter`]: super::super::RoomListDynamicEntriesController::refresh_filter
+pub fn new_filter(space: &Space) -> impl Fn(&RoomListEntry) -> bool {
+    let relations = space.relations();
+
+    let matcher =
+        SpaceRoomMatcher { is_child: move |room_id| relations.read().unwrap().contains(room_id) };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::SpaceRoomMatcher;
+
+    #[test]
+    fn test_space_children() {
+        let matcher = SpaceRoomMatcher { is_child: |room_id| room_id == room_id!("!r0:bar.org") };
+
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        assert!(matcher.matches(&RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned())));
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r1:bar.org").to_owned())));
+    }
+}
@@ -0,0 +1,64 @@
+use matrix_sdk::{Client, RoomListEntry};
+
+struct UnreadRoomMatcher<F: Fn(&RoomListEntry) -> Option<u64>> {
+    num_unread_notifications: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<u64>> UnreadRoomMatcher<F> {
+    fn matches(&self, room: &RoomListEntry) -> bool {
+        if !matches!(room, RoomListEntry::Filled(_) | RoomListEntry::Invalidated(_)) {
+            return false;
+        }
+
+        (self.num_unread_notifications)(room).is_some_and(|count| count > 0)
+    }
+}
+
+/// Create a new filter that will accept all filled or invalidated entries, but
+/// filters out rooms that have no unread notifications.
+///
+/// For encrypted rooms, the counts computed client-side are used.
+pub fn new_filter(client: &Client) -> impl Fn(&RoomListEntry) -> bool {
+    let client = client.clone();
+
+    let matcher = UnreadRoomMatcher {
+        num_unread_notifications: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+
+            Some(if room.is_encrypted() {
+                room.num_unread_notifications()
+            } else {
+                room.unread_notification_counts().notification_count
+            })
+        },
+    };
+
+    move |room_list_entry| -> bool { matcher.matches(room_list_entry) }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::UnreadRoomMatcher;
+
+    #[test]
+    fn test_has_unread_notifications() {
+        let matcher = UnreadRoomMatcher { num_unread_notifications: |_| Some(42) };
+
+        assert!(!matcher.matches(&RoomListEntry::Empty));
+        assert!(matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+        assert!(matcher.matches(&RoomListEntry::Invalidated(room_id!("!r0:bar.org").to_owned())));
+    }
+
+    #[test]
+    fn test_has_no_unread_notifications() {
+        let matcher = UnreadRoomMatcher { num_unread_notifications: |_| Some(0) };
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+
+        let matcher = UnreadRoomMatcher { num_unread_notifications: |_| None };
+        assert!(!matcher.matches(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())));
+    }
+}
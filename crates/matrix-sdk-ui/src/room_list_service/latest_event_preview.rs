@@ -0,0 +1,357 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for that specific language governing permissions and
+// limitations under the License.
+
+//! The `LatestEventPreview` type.
+
+use ruma::{
+    events::room::message::MessageType, push::Action, MilliSecondsSinceUnixEpoch, OwnedUserId,
+};
+
+use crate::timeline::{EventTimelineItem, TimelineDetails, TimelineItemContent};
+
+/// The maximum number of characters of the body of a preview.
+const MAX_BODY_LENGTH: usize = 256;
+
+/// A ready-to-render preview of the latest event of a room.
+#[derive(Clone, Debug)]
+pub struct LatestEventPreview {
+    /// The ID of the sender of the event.
+    pub sender_id: OwnedUserId,
+
+    /// The display name of the sender of the event, if known.
+    pub sender_display_name: Option<String>,
+
+    /// A short and plain text version of the content of the event, on a single
+    /// line.
+    pub body: String,
+
+    /// The time the event was sent at.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+
+    /// Whether the event was sent by the current user.
+    pub is_own: bool,
+
+    /// Whether the event mentions the current user, i.e. whether the push
+    /// rules highlight it.
+    pub is_mention: bool,
+}
+
+impl LatestEventPreview {
+    /// Create the preview of the given latest event, or `None` if the event
+    /// can't be previewed.
+    pub(super) async fn new(room: &matrix_sdk::Room, item: &EventTimelineItem) -> Option<Self> {
+        let sender_display_name = match item.sender_profile() {
+            TimelineDetails::Ready(profile) => profile.display_name.clone(),
+            _ => None,
+        };
+
+        let body = match item.content() {
+            TimelineItemContent::Message(message) => match message.msgtype() {
+                MessageType::Emote(emote) => {
+                    let sender_name =
+                        sender_display_name.as_deref().unwrap_or(item.sender().localpart());
+                    format!("* {sender_name} {}", emote.body)
+                }
+                msgtype => msgtype.body().to_owned(),
+            },
+            TimelineItemContent::Sticker(sticker) => sticker.content().body.clone(),
+            TimelineItemContent::Poll(poll) => poll.results().question,
+            TimelineItemContent::Voice(voice) => voice.message().body().to_owned(),
+            _ => return None,
+        };
+
+        let is_mention = if item.is_own() {
+            false
+        } else if let Some(event) = item.original_json() {
+            room.event_push_actions(event)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|actions| actions.iter().any(Action::is_highlight))
+        } else {
+            false
+        };
+
+        Some(Self {
+            sender_id: item.sender().to_owned(),
+            sender_display_name,
+            body: sanitize_body(&body),
+            timestamp: item.timestamp(),
+            is_own: item.is_own(),
+            is_mention,
+        })
+    }
+}
+
+/// Make the body fit on a single line: whitespaces (including new lines) are
+/// collapsed, control characters are removed, and the result is truncated to
+/// [`MAX_BODY_LENGTH`] characters.
+fn sanitize_body(body: &str) -> String {
+    let mut sanitized = String::with_capacity(body.len().min(MAX_BODY_LENGTH));
+    let mut length = 0;
+
+    for word in body.split_whitespace() {
+        let word: String = word.chars().filter(|c| !c.is_control()).collect();
+
+        if word.is_empty() {
+            continue;
+        }
+
+        if length > 0 {
+            if length == MAX_BODY_LENGTH {
+                sanitized.push('…');
+                break;
+            }
+
+            sanitized.push(' ');
+            length += 1;
+        }
+
+        for c in word.chars() {
+            if length == MAX_BODY_LENGTH {
+                sanitized.push('…');
+                return sanitized;
+            }
+
+            sanitized.push(c);
+            length += 1;
+        }
+    }
+
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::{config::RequestConfig, Client};
+    use matrix_sdk_base::{
+        deserialized_responses::SyncTimelineEvent, latest_event::LatestEvent, BaseClient,
+        SessionMeta,
+    };
+    use matrix_sdk_test::{async_test, sync_timeline_event};
+    use ruma::{
+        api::{client::sync::sync_events::v4, MatrixVersion},
+        device_id,
+        events::{
+            room::{
+                encrypted::{
+                    EncryptedEventScheme, MegolmV1AesSha2ContentInit, RoomEncryptedEventContent,
+                },
+                member::{MembershipState, RoomMemberEventContent},
+            },
+            FullStateEventContent,
+        },
+        owned_user_id, user_id, MilliSecondsSinceUnixEpoch, RoomId, UInt, UserId,
+    };
+
+    use super::{sanitize_body, LatestEventPreview, MAX_BODY_LENGTH};
+    use crate::timeline::{EventTimelineItem, TimelineItemContent};
+
+    const ROOM_ID: &str = "!r:e.uk";
+
+    /// Copied from matrix_sdk_base::sliding_sync::test
+    async fn logged_in_client() -> Client {
+        let base_client = BaseClient::new();
+        base_client
+            .set_session_meta(SessionMeta {
+                user_id: user_id!("@u:e.uk").to_owned(),
+                device_id: device_id!("XYZ").to_owned(),
+            })
+            .await
+            .expect("Failed to set session meta");
+
+        Client::builder()
+            .homeserver_url("http://localhost:1234")
+            .server_versions([MatrixVersion::V1_0])
+            .request_config(RequestConfig::new().disable_retry())
+            .base_client(base_client)
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Get the room, and the timeline item of the given latest event.
+    async fn room_and_item(
+        client: &Client,
+        event: SyncTimelineEvent,
+    ) -> (matrix_sdk::Room, EventTimelineItem) {
+        let room_id = <&RoomId>::try_from(ROOM_ID).unwrap();
+
+        let mut response = v4::Response::new("6".to_owned());
+        response.rooms.insert(room_id.to_owned(), v4::SlidingSyncRoom::new());
+        client.process_sliding_sync_test_helper(&response).await.unwrap();
+
+        let item =
+            EventTimelineItem::from_latest_event(client.clone(), room_id, LatestEvent::new(event))
+                .await
+                .unwrap();
+
+        (client.get_room(room_id).unwrap(), item)
+    }
+
+    fn message_event(sender: &UserId, msgtype: &str, body: &str) -> SyncTimelineEvent {
+        sync_timeline_event!({
+            "event_id": "$message",
+            "sender": sender,
+            "origin_server_ts": 1234,
+            "type": "m.room.message",
+            "room_id": ROOM_ID,
+            "content": {
+                "body": body,
+                "msgtype": msgtype,
+            },
+        })
+        .into()
+    }
+
+    #[async_test]
+    async fn test_preview_message() {
+        let client = logged_in_client().await;
+        let sender = user_id!("@t:o.uk");
+        let (room, item) =
+            room_and_item(&client, message_event(sender, "m.text", "Hello\n\nworld")).await;
+
+        let preview = LatestEventPreview::new(&room, &item).await.unwrap();
+
+        assert_eq!(preview.sender_id, sender);
+        assert_eq!(preview.sender_display_name, None);
+        assert_eq!(preview.body, "Hello world");
+        assert_eq!(preview.timestamp, MilliSecondsSinceUnixEpoch(UInt::new(1234).unwrap()));
+        assert!(!preview.is_own);
+        assert!(!preview.is_mention);
+    }
+
+    #[async_test]
+    async fn test_preview_emote() {
+        let client = logged_in_client().await;
+        let (room, item) =
+            room_and_item(&client, message_event(user_id!("@t:o.uk"), "m.emote", "waves")).await;
+
+        let preview = LatestEventPreview::new(&room, &item).await.unwrap();
+
+        // Without a display name, the emote uses the localpart of the sender.
+        assert_eq!(preview.body, "* t waves");
+    }
+
+    #[async_test]
+    async fn test_preview_own_event() {
+        let client = logged_in_client().await;
+        let sender = owned_user_id!("@u:e.uk");
+        let (room, item) =
+            room_and_item(&client, message_event(&sender, "m.text", "Hello, it's me")).await;
+
+        let preview = LatestEventPreview::new(&room, &item).await.unwrap();
+
+        assert_eq!(preview.sender_id, sender);
+        assert_eq!(preview.body, "Hello, it's me");
+        assert!(preview.is_own);
+        // Own events never mention the current user.
+        assert!(!preview.is_mention);
+    }
+
+    #[async_test]
+    async fn test_no_preview_for_redacted_event() {
+        let client = logged_in_client().await;
+        let event = sync_timeline_event!({
+            "event_id": "$message",
+            "sender": "@t:o.uk",
+            "origin_server_ts": 1234,
+            "type": "m.room.message",
+            "room_id": ROOM_ID,
+            "content": {},
+            "unsigned": {
+                "redacted_because": {
+                    "event_id": "$redaction",
+                    "sender": "@t:o.uk",
+                    "origin_server_ts": 1235,
+                    "type": "m.room.redaction",
+                    "redacts": "$message",
+                    "content": {},
+                },
+            },
+        })
+        .into();
+        let (room, item) = room_and_item(&client, event).await;
+
+        assert!(item.content().is_redacted());
+        assert!(LatestEventPreview::new(&room, &item).await.is_none());
+    }
+
+    #[async_test]
+    async fn test_no_preview_for_encrypted_event() {
+        let client = logged_in_client().await;
+        let (room, item) =
+            room_and_item(&client, message_event(user_id!("@t:o.uk"), "m.text", "Secret")).await;
+        let item = item.with_test_content(TimelineItemContent::unable_to_decrypt(
+            RoomEncryptedEventContent::new(
+                EncryptedEventScheme::MegolmV1AesSha2(
+                    MegolmV1AesSha2ContentInit {
+                        ciphertext: "ciphertext".to_owned(),
+                        sender_key: "sender_key".to_owned(),
+                        device_id: "DEVICEID".into(),
+                        session_id: "session_id".to_owned(),
+                    }
+                    .into(),
+                ),
+                None,
+            ),
+        ));
+
+        assert!(LatestEventPreview::new(&room, &item).await.is_none());
+    }
+
+    #[async_test]
+    async fn test_no_preview_for_membership_change() {
+        let client = logged_in_client().await;
+        let sender = owned_user_id!("@t:o.uk");
+        let (room, item) = room_and_item(&client, message_event(&sender, "m.text", "Hi")).await;
+        let item = item.with_test_content(TimelineItemContent::room_member(
+            sender.clone(),
+            FullStateEventContent::Original {
+                content: RoomMemberEventContent::new(MembershipState::Join),
+                prev_content: None,
+            },
+            sender,
+        ));
+
+        assert!(LatestEventPreview::new(&room, &item).await.is_none());
+    }
+
+    #[test]
+    fn test_sanitize_body_whitespaces() {
+        assert_eq!(sanitize_body("  Hello\n\n  world\t!  "), "Hello world !");
+        assert_eq!(sanitize_body(""), "");
+        assert_eq!(sanitize_body(" \n "), "");
+    }
+
+    #[test]
+    fn test_sanitize_body_control_characters() {
+        assert_eq!(sanitize_body("Hel\u{0}lo\u{7}"), "Hello");
+        assert_eq!(sanitize_body("Hello \u{7} world"), "Hello world");
+    }
+
+    #[test]
+    fn test_sanitize_body_truncate() {
+        let body = "a".repeat(MAX_BODY_LENGTH);
+        assert_eq!(sanitize_body(&body), body);
+
+        let body = "é".repeat(MAX_BODY_LENGTH + 1);
+        let sanitized = sanitize_body(&body);
+        assert_eq!(sanitized.chars().count(), MAX_BODY_LENGTH + 1);
+        assert!(sanitized.ends_with("é…"));
+
+        let body = format!("{} b", "a".repeat(MAX_BODY_LENGTH));
+        assert_eq!(sanitize_body(&body), format!("{}…", "a".repeat(MAX_BODY_LENGTH)));
+    }
+}
@@ -62,19 +62,25 @@
 //! machine's state, which can be pretty helpful for the client app.
 
 pub mod filters;
+mod latest_event_preview;
 mod room;
 mod room_list;
+mod search;
+pub mod sorters;
+mod space;
 mod state;
 
 use std::{future::ready, sync::Arc, time::Duration};
 
 use async_stream::stream;
 use eyeball::{SharedObservable, Subscriber};
+use eyeball_im::VectorDiff;
 use futures_util::{pin_mut, Stream, StreamExt};
+pub use latest_event_preview::LatestEventPreview;
 pub use matrix_sdk::RoomListEntry;
 use matrix_sdk::{
-    sliding_sync::Ranges, Client, Error as SlidingSyncError, SlidingSync, SlidingSyncList,
-    SlidingSyncListBuilder, SlidingSyncMode,
+    sliding_sync::Ranges, Client, Error as SlidingSyncError, HttpError, SlidingSync,
+    SlidingSyncList, SlidingSyncListBuilder, SlidingSyncMode,
 };
 use matrix_sdk_base::ring_buffer::RingBuffer;
 pub use room::*;
@@ -88,6 +94,7 @@ use ruma::{
     events::{StateEventType, TimelineEventType},
     OwnedRoomId, RoomId,
 };
+pub use space::Space;
 pub use state::*;
 use thiserror::Error;
 use tokio::{
@@ -392,7 +399,7 @@ impl RoomListService {
     }
 
     async fn list_for(&self, sliding_sync_list_name: &str) -> Result<RoomList, Error> {
-        RoomList::new(&self.sliding_sync, sliding_sync_list_name, self.state()).await
+        RoomList::new(&self.client, &self.sliding_sync, sliding_sync_list_name, self.state()).await
     }
 
     /// Get a [`RoomList`] for all rooms.
@@ -406,6 +413,28 @@ impl RoomListService {
         self.list_for(INVITES_LIST_NAME).await
     }
 
+    /// Search the query in all the joined rooms, not only the ones that have
+    /// been loaded by the room lists.
+    ///
+    /// The query is fuzzy matched on the room name, the room canonical alias
+    /// and, for direct message rooms, the display name of the other users. The
+    /// matching ignores the case and the diacritics. The best matches come
+    /// first.
+    ///
+    /// The first item of the stream is a `VectorDiff::Reset` with the initial
+    /// results, and the results are updated when the rooms change.
+    pub fn search(&self, query: &str) -> impl Stream<Item = Vec<VectorDiff<RoomListEntry>>> {
+        search::search(self.client.clone(), query)
+    }
+
+    /// Get a [`Space`], with its children resolved.
+    ///
+    /// The hierarchy of the space is loaded from the server, so that the
+    /// children the user hasn't joined are known too.
+    pub async fn space(&self, space_id: &RoomId) -> Result<Space, Error> {
+        Space::new(&self.client, space_id).await
+    }
+
     /// Pass an [`Input`] onto the state machine.
     pub async fn apply_input(&self, input: Input) -> Result<InputResult, Error> {
         use Input::*;
@@ -504,6 +533,10 @@ pub enum Error {
     /// The requested room doesn't exist.
     #[error("Room `{0}` not found")]
     RoomNotFound(OwnedRoomId),
+
+    /// The hierarchy of a space couldn't be loaded.
+    #[error("Failed to load the space hierarchy: {0}")]
+    SpaceHierarchy(HttpError),
 }
 
 /// An input for the [`RoomList`]' state machine.
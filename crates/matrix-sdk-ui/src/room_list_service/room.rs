@@ -17,13 +17,13 @@
 use std::sync::Arc;
 
 use async_once_cell::OnceCell as AsyncOnceCell;
-use matrix_sdk::{SlidingSync, SlidingSyncRoom};
+use matrix_sdk::{room::RoomMember, SlidingSync, SlidingSyncRoom};
 use ruma::{
     api::client::sync::sync_events::{v4::RoomSubscription, UnreadNotificationsCount},
-    OwnedMxcUri, RoomId,
+    OwnedMxcUri, OwnedRoomAliasId, OwnedUserId, RoomId,
 };
 
-use super::Error;
+use super::{Error, LatestEventPreview};
 use crate::{
     timeline::{EventTimelineItem, SlidingSyncRoomExt},
     Timeline,
@@ -161,6 +161,14 @@ impl Room {
         self.inner.sliding_sync_room.latest_timeline_item().await
     }
 
+    /// Get a ready-to-render preview of the latest event, see
+    /// [`Self::latest_event`].
+    pub async fn latest_event_preview(&self) -> Option<LatestEventPreview> {
+        let latest_event = self.latest_event().await?;
+
+        LatestEventPreview::new(&self.inner.room, &latest_event).await
+    }
+
     /// Is there any unread notifications?
     pub fn has_unread_notifications(&self) -> bool {
         self.inner.sliding_sync_room.has_unread_notifications()
@@ -170,4 +178,59 @@ impl Room {
     pub fn unread_notifications(&self) -> UnreadNotificationsCount {
         self.inner.sliding_sync_room.unread_notifications()
     }
+
+    /// Get what's needed to present the invite to this room, before the user
+    /// accepts or declines it.
+    ///
+    /// Only invited rooms have an invite preview.
+    pub async fn invite_preview(&self) -> matrix_sdk::Result<InvitePreview> {
+        let room = &self.inner.room;
+        let invite = room.invite_details().await?;
+
+        Ok(InvitePreview {
+            inviter_id: invite.invitee.event().sender().to_owned(),
+            inviter: invite.inviter,
+            room_name: self.name().await,
+            topic: room.topic(),
+            avatar_url: self.avatar_url(),
+            canonical_alias: room.canonical_alias(),
+            joined_members_count: room.joined_members_count(),
+            is_direct: room.is_direct().await.unwrap_or(false),
+        })
+    }
+
+    /// Decline the invite to this room, and ignore the user who sent it.
+    ///
+    /// See [`matrix_sdk::Room::decline_and_block`].
+    pub async fn decline_and_block(&self, report_reason: Option<String>) -> matrix_sdk::Result<()> {
+        self.inner.room.decline_and_block(report_reason).await
+    }
+}
+
+/// What's needed to present an invite to the user.
+#[derive(Clone, Debug)]
+pub struct InvitePreview {
+    /// The ID of the user who sent the invite.
+    pub inviter_id: OwnedUserId,
+
+    /// The user who sent the invite, if their membership in the room is known.
+    pub inviter: Option<RoomMember>,
+
+    /// The name of the room.
+    pub room_name: Option<String>,
+
+    /// The topic of the room.
+    pub topic: Option<String>,
+
+    /// The avatar of the room.
+    pub avatar_url: Option<OwnedMxcUri>,
+
+    /// The canonical alias of the room.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+
+    /// The number of members who joined the room.
+    pub joined_members_count: u64,
+
+    /// Whether the invite is for a direct message room.
+    pub is_direct: bool,
 }
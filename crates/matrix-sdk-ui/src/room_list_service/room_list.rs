@@ -12,7 +12,12 @@
 // See the License for that specific language governing permissions and
 // limitations under the License.
 
-use std::{future::ready, sync::Arc};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    future::ready,
+    sync::Arc,
+};
 
 use async_cell::sync::AsyncCell;
 use async_rx::StreamExt as _;
@@ -23,15 +28,22 @@ use eyeball_im_util::vector::VectorObserverExt;
 use futures_util::{pin_mut, stream, Stream, StreamExt as _};
 use matrix_sdk::{
     executor::{spawn, JoinHandle},
-    RoomListEntry, SlidingSync, SlidingSyncList,
+    Client, RoomListEntry, SlidingSync, SlidingSyncList,
 };
+use ruma::OwnedRoomId;
+use tokio::sync::broadcast::{self, error::RecvError};
 
-use super::{Error, State};
+use super::{
+    filters::BoxedFilterFn,
+    sorters::{apply_diff, sort_by_dynamic_sorter, BoxedSorterFn},
+    Error, State,
+};
 
 /// A `RoomList` represents a list of rooms, from a
 /// [`RoomListService`](super::RoomListService).
 #[derive(Debug)]
 pub struct RoomList {
+    client: Client,
     sliding_sync_list: SlidingSyncList,
     loading_state: SharedObservable<RoomListLoadingState>,
     loading_state_task: JoinHandle<()>,
@@ -45,6 +57,7 @@ impl Drop for RoomList {
 
 impl RoomList {
     pub(super) async fn new(
+        client: &Client,
         sliding_sync: &SlidingSync,
         sliding_sync_list_name: &str,
         room_list_service_state: Subscriber<State>,
@@ -63,6 +76,7 @@ impl RoomList {
             });
 
         Ok(Self {
+            client: client.clone(),
             sliding_sync_list: sliding_sync_list.clone(),
             loading_state: loading_state.clone(),
             loading_state_task: spawn(async move {
@@ -120,20 +134,36 @@ impl RoomList {
     /// call to [`RoomListDynamicEntriesController::set_filter`], the stream
     /// will yield a [`VectorDiff::Clear`] followed by any updates of the
     /// room list under that filter (until the next reset).
+    ///
+    /// The filter is evaluated again for a room every time its
+    /// [`RoomInfo`](matrix_sdk::RoomInfo) changes, e.g. when it's tagged as a
+    /// favourite or when it receives new notifications.
+    ///
+    /// Entries are kept in the order of the server, i.e. by recency, until a
+    /// sorter is set through [`RoomListDynamicEntriesController::set_sorter`].
+    /// Changing the sorter doesn't reset the stream, the entries are moved to
+    /// their new position instead.
     pub fn entries_with_dynamic_adapters(
         &self,
         page_size: usize,
     ) -> (impl Stream<Item = Vec<VectorDiff<RoomListEntry>>>, RoomListDynamicEntriesController)
     {
         let list = self.sliding_sync_list.clone();
+        let client = self.client.clone();
 
         let filter_fn_cell = AsyncCell::shared();
 
+        let filter_refresh = SharedObservable::new(());
+
+        let sorter = SharedObservable::<Option<Arc<BoxedSorterFn>>>::new(None);
+
         let limit = SharedObservable::<usize>::new(page_size);
         let limit_stream = limit.subscribe();
 
         let dynamic_entries_controller = RoomListDynamicEntriesController::new(
             filter_fn_cell.clone(),
+            filter_refresh.clone(),
+            sorter.clone(),
             page_size,
             limit,
             list.maximum_number_of_rooms_stream(),
@@ -141,10 +171,19 @@ impl RoomList {
 
         let stream = stream! {
             loop {
-                let filter_fn = filter_fn_cell.take().await;
-                let (values, stream) = list
-                    .room_list_stream()
-                    .filter(filter_fn)
+                let filter_fn: Arc<BoxedFilterFn> = Arc::new(filter_fn_cell.take().await);
+                let (values, stream) = list.room_list_stream();
+                let (values, stream) = refresh_on_room_info_updates(
+                    values,
+                    stream,
+                    client.subscribe_to_room_info_updates(),
+                    filter_refresh.subscribe(),
+                    filter_fn.clone(),
+                );
+                let (values, stream) =
+                    (values, stream).filter(move |entry: &RoomListEntry| filter_fn(entry));
+                let (values, stream) = sort_by_dynamic_sorter(values, stream, sorter.subscribe());
+                let (values, stream) = (values, stream)
                     .dynamic_limit_with_initial_value(page_size, limit_stream.clone());
 
                 // Clearing the stream before chaining with the real stream.
@@ -199,7 +238,134 @@ pub enum RoomListLoadingState {
     },
 }
 
-type BoxedFilterFn = Box<dyn Fn(&RoomListEntry) -> bool + Send + Sync>;
+enum RefreshUpdate {
+    Entries(Vec<VectorDiff<RoomListEntry>>),
+    /// The rooms whose info has changed, or `None` if we don't know which ones
+    /// have changed.
+    Rooms(Option<BTreeSet<OwnedRoomId>>),
+}
+
+/// Emit a [`VectorDiff::Set`] for the entries whose room info has changed in a
+/// way that changes the result of the filter, so that the filter adapter
+/// further down the stream evaluates them again.
+///
+/// All the entries are checked when `filter_refreshes` yields.
+fn refresh_on_room_info_updates<S>(
+    values: Vector<RoomListEntry>,
+    entries_stream: S,
+    room_info_updates: broadcast::Receiver<BTreeSet<OwnedRoomId>>,
+    filter_refreshes: impl Stream<Item = ()>,
+    filter_fn: Arc<BoxedFilterFn>,
+) -> (Vector<RoomListEntry>, impl Stream<Item = Vec<VectorDiff<RoomListEntry>>>)
+where
+    S: Stream<Item = Vec<VectorDiff<RoomListEntry>>>,
+{
+    let room_info_updates = stream::unfold(room_info_updates, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(room_ids) => Some((Some(room_ids), receiver)),
+            // Some updates have been missed, refresh everything.
+            Err(RecvError::Lagged(_)) => Some((None, receiver)),
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    // The last result of the filter for each room.
+    let mut matches = HashMap::new();
+    let remember_match =
+        move |matches: &mut HashMap<OwnedRoomId, bool>, entry: &RoomListEntry| -> Option<bool> {
+            let room_id = entry.as_room_id()?;
+            let is_matching = filter_fn(entry);
+
+            matches
+                .insert(room_id.to_owned(), is_matching)
+                .map(|was_matching| was_matching != is_matching)
+        };
+
+    for entry in &values {
+        remember_match(&mut matches, entry);
+    }
+
+    let mut entries = values.clone();
+    let updates = stream::select(
+        entries_stream.map(RefreshUpdate::Entries),
+        stream::select(
+            room_info_updates.map(RefreshUpdate::Rooms),
+            filter_refreshes.map(|()| RefreshUpdate::Rooms(None)),
+        ),
+    );
+
+    let stream = updates.filter_map(move |update| {
+        let diffs = match update {
+            RefreshUpdate::Entries(diffs) => {
+                let mut has_removed_entries = false;
+
+                for diff in diffs.iter().cloned() {
+                    match &diff {
+                        VectorDiff::Append { values } => {
+                            values.iter().for_each(|entry| {
+                                remember_match(&mut matches, entry);
+                            });
+                        }
+                        VectorDiff::Reset { values } => {
+                            matches.clear();
+                            values.iter().for_each(|entry| {
+                                remember_match(&mut matches, entry);
+                            });
+                        }
+                        VectorDiff::PushFront { value }
+                        | VectorDiff::PushBack { value }
+                        | VectorDiff::Insert { value, .. } => {
+                            remember_match(&mut matches, value);
+                        }
+                        VectorDiff::Set { value, .. } => {
+                            has_removed_entries = true;
+                            remember_match(&mut matches, value);
+                        }
+                        VectorDiff::Clear => {
+                            matches.clear();
+                        }
+                        VectorDiff::PopFront
+                        | VectorDiff::PopBack
+                        | VectorDiff::Remove { .. }
+                        | VectorDiff::Truncate { .. } => {
+                            has_removed_entries = true;
+                        }
+                    }
+
+                    apply_diff(&mut entries, diff);
+                }
+
+                // Forget the rooms that have left the list.
+                if has_removed_entries {
+                    let room_ids = entries
+                        .iter()
+                        .filter_map(|entry| entry.as_room_id())
+                        .collect::<BTreeSet<_>>();
+                    matches.retain(|room_id, _| room_ids.contains(&**room_id));
+                }
+
+                diffs
+            }
+
+            RefreshUpdate::Rooms(room_ids) => entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| match (&room_ids, entry.as_room_id()) {
+                    (_, None) => false,
+                    (None, Some(_)) => true,
+                    (Some(room_ids), Some(room_id)) => room_ids.contains(room_id),
+                })
+                // Only refresh the entries for which the result of the filter has changed.
+                .filter(|(_, entry)| remember_match(&mut matches, entry).unwrap_or(false))
+                .map(|(index, entry)| VectorDiff::Set { index, value: entry.clone() })
+                .collect(),
+        };
+
+        ready((!diffs.is_empty()).then_some(diffs))
+    });
+
+    (values, stream)
+}
 
 /// Controller for the [`RoomList`] dynamic entries.
 ///
@@ -207,6 +373,8 @@ type BoxedFilterFn = Box<dyn Fn(&RoomListEntry) -> bool + Send + Sync>;
 /// [`RoomList::entries_with_dynamic_adapters`]
 pub struct RoomListDynamicEntriesController {
     filter: Arc<AsyncCell<BoxedFilterFn>>,
+    filter_refresh: SharedObservable<()>,
+    sorter: SharedObservable<Option<Arc<BoxedSorterFn>>>,
     page_size: usize,
     limit: SharedObservable<usize>,
     maximum_number_of_rooms: Subscriber<Option<u32>>,
@@ -215,11 +383,20 @@ pub struct RoomListDynamicEntriesController {
 impl RoomListDynamicEntriesController {
     fn new(
         filter: Arc<AsyncCell<BoxedFilterFn>>,
+        filter_refresh: SharedObservable<()>,
+        sorter: SharedObservable<Option<Arc<BoxedSorterFn>>>,
         page_size: usize,
         limit_stream: SharedObservable<usize>,
         maximum_number_of_rooms: Subscriber<Option<u32>>,
     ) -> Self {
-        Self { filter, page_size, limit: limit_stream, maximum_number_of_rooms }
+        Self {
+            filter,
+            filter_refresh,
+            sorter,
+            page_size,
+            limit: limit_stream,
+            maximum_number_of_rooms,
+        }
     }
 
     /// Set the filter.
@@ -241,6 +418,33 @@ impl RoomListDynamicEntriesController {
         }
     }
 
+    /// Evaluate the filter again for all the entries, without resetting the
+    /// stream.
+    ///
+    /// The filter is already evaluated again when the info of a room changes.
+    /// This is useful for filters depending on something else, like
+    /// [`new_filter_space`](super::filters::new_filter_space).
+    pub fn refresh_filter(&self) {
+        self.filter_refresh.set(());
+    }
+
+    /// Set the sorter.
+    ///
+    /// The entries are re-sorted immediately, without recreating the list:
+    /// the associated stream yields the moves needed to reach the new order.
+    /// See the [`sorters`](super::sorters) module for the available sorters.
+    pub fn set_sorter(
+        &self,
+        sorter: impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering + Send + Sync + 'static,
+    ) {
+        self.sorter.set(Some(Arc::new(Box::new(sorter))));
+    }
+
+    /// Remove the sorter, i.e. go back to the order of the server.
+    pub fn reset_sorter(&self) {
+        self.sorter.set(None);
+    }
+
     /// Add one page, i.e. view `page_size` more entries in the room list if
     /// any.
     pub fn add_one_page(&self) {
@@ -0,0 +1,181 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for that specific language governing permissions and
+// limitations under the License.
+
+//! Search in all the joined rooms, independently of the Sliding Sync lists.
+
+use async_stream::stream;
+use eyeball_im::{Vector, VectorDiff};
+use futures_util::Stream;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher as _};
+use matrix_sdk::{Client, Room, RoomListEntry};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{filters::normalize_string, sorters::compute_diffs};
+
+struct RoomSearcher {
+    matcher: SkimMatcherV2,
+    pattern: String,
+}
+
+impl RoomSearcher {
+    fn new(query: &str) -> Self {
+        Self {
+            matcher: SkimMatcherV2::default().ignore_case().use_cache(true),
+            pattern: normalize_string(query.trim()),
+        }
+    }
+
+    /// Get the best score of the pattern among the given subjects, or `None`
+    /// if none of them matches.
+    ///
+    /// An empty pattern matches everything, with the same score.
+    fn score<'a>(&self, subjects: impl IntoIterator<Item = &'a str>) -> Option<i64> {
+        if self.pattern.is_empty() {
+            return Some(0);
+        }
+
+        subjects
+            .into_iter()
+            .filter_map(|subject| {
+                self.matcher.fuzzy_match(&normalize_string(subject), &self.pattern)
+            })
+            .max()
+    }
+}
+
+/// Collect the strings a room can be found with: its name, its canonical
+/// alias, and the display names of the other users if it's a direct message
+/// room.
+async fn search_subjects(room: &Room) -> Vec<String> {
+    let mut subjects = Vec::new();
+
+    if let Some(name) = room.name() {
+        subjects.push(name);
+    }
+
+    if let Some(alias) = room.canonical_alias() {
+        subjects.push(alias.to_string());
+    }
+
+    for user_id in room.direct_targets() {
+        match room.get_member_no_sync(&user_id).await {
+            Ok(Some(member)) => subjects.push(member.name().to_owned()),
+            _ => subjects.push(user_id.localpart().to_owned()),
+        }
+    }
+
+    subjects
+}
+
+/// Get the entries for all the joined rooms matching the query, the best
+/// matches first.
+async fn search_entries(client: &Client, searcher: &RoomSearcher) -> Vector<RoomListEntry> {
+    let mut matches = Vec::new();
+
+    for room in client.joined_rooms() {
+        let subjects = search_subjects(&room).await;
+
+        if let Some(score) = searcher.score(subjects.iter().map(String::as_str)) {
+            matches.push((score, room.room_id().to_owned()));
+        }
+    }
+
+    // Sort by best score, and then by room ID to keep the order stable.
+    matches.sort_by(|(left_score, left_id), (right_score, right_id)| {
+        right_score.cmp(left_score).then_with(|| left_id.cmp(right_id))
+    });
+
+    matches.into_iter().map(|(_, room_id)| RoomListEntry::Filled(room_id)).collect()
+}
+
+/// Search the query in all the joined rooms.
+///
+/// The first item of the stream is a `VectorDiff::Reset` with the current
+/// results. They are computed again every time a room info changes, and only
+/// the differences are sent afterwards.
+pub(super) fn search(
+    client: Client,
+    query: &str,
+) -> impl Stream<Item = Vec<VectorDiff<RoomListEntry>>> {
+    let searcher = RoomSearcher::new(query);
+
+    stream! {
+        let mut room_info_updates = client.subscribe_to_room_info_updates();
+        let mut current = search_entries(&client, &searcher).await;
+
+        yield vec![VectorDiff::Reset { values: current.clone() }];
+
+        loop {
+            match room_info_updates.recv().await {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+
+            let entries = search_entries(&client, &searcher).await;
+            let diffs = compute_diffs(&current, &entries);
+            current = entries;
+
+            if !diffs.is_empty() {
+                yield diffs;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RoomSearcher;
+
+    #[test]
+    fn test_empty_query() {
+        let searcher = RoomSearcher::new("  ");
+
+        assert_eq!(searcher.score(["hello"]), Some(0));
+        assert_eq!(searcher.score(["hello", "world"]), Some(0));
+    }
+
+    #[test]
+    fn test_no_match() {
+        let searcher = RoomSearcher::new("foo");
+
+        assert!(searcher.score(["hello", "#world:matrix.org"]).is_none());
+    }
+
+    #[test]
+    fn test_match_any_subject() {
+        let searcher = RoomSearcher::new("wrld");
+
+        assert!(searcher.score(["hello", "#world:matrix.org"]).is_some());
+    }
+
+    #[test]
+    fn test_normalized_match() {
+        let searcher = RoomSearcher::new("Ștefan");
+
+        assert!(searcher.score(["stefan"]).is_some());
+        assert!(searcher.score(["STEFAN"]).is_some());
+        assert!(searcher.score(["Été", "Stéfan Ionescu"]).is_some());
+    }
+
+    #[test]
+    fn test_best_score() {
+        let searcher = RoomSearcher::new("hello");
+
+        let exact = searcher.score(["hello"]).unwrap();
+        let fuzzy = searcher.score(["h e l l o"]).unwrap();
+
+        assert!(exact > fuzzy);
+        assert_eq!(searcher.score(["h e l l o", "hello"]), Some(exact));
+    }
+}
@@ -0,0 +1,135 @@
+use std::cmp::Ordering;
+
+use matrix_sdk::{Client, RoomListEntry};
+use matrix_sdk_base::RoomNotableTags;
+
+struct FavouritesFirstSorter<F, O>
+where
+    F: Fn(&RoomListEntry) -> Option<RoomNotableTags>,
+    O: Fn(&RoomListEntry) -> Option<f64>,
+{
+    get_notable_tags: F,
+    get_tag_order: O,
+}
+
+impl<F, O> FavouritesFirstSorter<F, O>
+where
+    F: Fn(&RoomListEntry) -> Option<RoomNotableTags>,
+    O: Fn(&RoomListEntry) -> Option<f64>,
+{
+    fn rank(&self, room: &RoomListEntry) -> u8 {
+        match (self.get_notable_tags)(room) {
+            Some(tags) if tags.contains(RoomNotableTags::FAVOURITE) => 0,
+            Some(tags) if tags.contains(RoomNotableTags::LOW_PRIORITY) => 2,
+            _ => 1,
+        }
+    }
+
+    fn cmp(&self, left: &RoomListEntry, right: &RoomListEntry) -> Ordering {
+        self.rank(left).cmp(&self.rank(right)).then_with(|| {
+            // Rooms with an order come first, by ascending order.
+            match ((self.get_tag_order)(left), (self.get_tag_order)(right)) {
+                (Some(left), Some(right)) => left.total_cmp(&right),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        })
+    }
+}
+
+/// Create a new sorter that will put the favourite rooms first, and the low
+/// priority rooms last.
+///
+/// The favourite and low priority rooms are sorted by the order of their tag.
+pub fn new_sorter(client: &Client) -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    let tags_client = client.clone();
+    let order_client = client.clone();
+
+    let sorter = FavouritesFirstSorter {
+        get_notable_tags: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = tags_client.get_room(room_id)?;
+            Some(room.notable_tags())
+        },
+        get_tag_order: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = order_client.get_room(room_id)?;
+
+            if room.is_favourite() {
+                room.favourite_order()
+            } else if room.is_low_priority() {
+                room.low_priority_order()
+            } else {
+                None
+            }
+        },
+    };
+
+    move |left, right| -> Ordering { sorter.cmp(left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use matrix_sdk_base::RoomNotableTags;
+    use ruma::room_id;
+
+    use super::FavouritesFirstSorter;
+
+    #[test]
+    fn test_favourites_first() {
+        let sorter = FavouritesFirstSorter {
+            get_notable_tags: |room| match room.as_room_id()?.as_str() {
+                "!fav:bar.org" => Some(RoomNotableTags::FAVOURITE),
+                "!low:bar.org" => Some(RoomNotableTags::LOW_PRIORITY),
+                _ => Some(RoomNotableTags::empty()),
+            },
+            get_tag_order: |_| None,
+        };
+
+        let favourite = RoomListEntry::Filled(room_id!("!fav:bar.org").to_owned());
+        let low_priority = RoomListEntry::Filled(room_id!("!low:bar.org").to_owned());
+        let other = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+
+        assert_eq!(sorter.cmp(&favourite, &other), Ordering::Less);
+        assert_eq!(sorter.cmp(&other, &low_priority), Ordering::Less);
+        assert_eq!(sorter.cmp(&favourite, &low_priority), Ordering::Less);
+        assert_eq!(sorter.cmp(&low_priority, &favourite), Ordering::Greater);
+        assert_eq!(sorter.cmp(&other, &other), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_favourite_and_low_priority() {
+        // A room tagged as both favourite and low priority is a favourite.
+        let sorter = FavouritesFirstSorter {
+            get_notable_tags: |_| Some(RoomNotableTags::FAVOURITE | RoomNotableTags::LOW_PRIORITY),
+            get_tag_order: |_| None,
+        };
+
+        assert_eq!(sorter.rank(&RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned())), 0);
+    }
+
+    #[test]
+    fn test_tag_order() {
+        let sorter = FavouritesFirstSorter {
+            get_notable_tags: |_| Some(RoomNotableTags::FAVOURITE),
+            get_tag_order: |room| match room.as_room_id()?.as_str() {
+                "!first:bar.org" => Some(0.1),
+                "!second:bar.org" => Some(0.5),
+                _ => None,
+            },
+        };
+
+        let first = RoomListEntry::Filled(room_id!("!first:bar.org").to_owned());
+        let second = RoomListEntry::Filled(room_id!("!second:bar.org").to_owned());
+        let unordered = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+
+        assert_eq!(sorter.cmp(&first, &second), Ordering::Less);
+        assert_eq!(sorter.cmp(&second, &unordered), Ordering::Less);
+        assert_eq!(sorter.cmp(&unordered, &first), Ordering::Greater);
+        assert_eq!(sorter.cmp(&unordered, &unordered), Ordering::Equal);
+    }
+}
@@ -0,0 +1,52 @@
+use std::cmp::Ordering;
+
+use matrix_sdk::RoomListEntry;
+
+use super::BoxedSorterFn;
+
+/// Create a new sorter that will run multiple sorters, one after the other.
+///
+/// The first sorter is used, and if it considers two entries to be equal, the
+/// next one is used, and so on. For example, combining
+/// [`new_sorter_favourites_first`](super::new_sorter_favourites_first) and
+/// [`new_sorter_name`](super::new_sorter_name) puts the favourite rooms on top,
+/// each group being sorted alphabetically.
+pub fn new_sorter(
+    sorters: Vec<BoxedSorterFn>,
+) -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    move |left, right| -> Ordering {
+        sorters
+            .iter()
+            .map(|sorter| sorter(left, right))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::new_sorter;
+
+    #[test]
+    fn test_first_non_equal_ordering_wins() {
+        let r0 = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+        let r1 = RoomListEntry::Filled(room_id!("!r1:bar.org").to_owned());
+
+        let sorter = new_sorter(vec![]);
+        assert_eq!(sorter(&r0, &r1), Ordering::Equal);
+
+        let sorter = new_sorter(vec![
+            Box::new(|_, _| Ordering::Equal),
+            Box::new(|left, right| left.as_room_id().cmp(&right.as_room_id())),
+            Box::new(|_, _| Ordering::Greater),
+        ]);
+        assert_eq!(sorter(&r0, &r1), Ordering::Less);
+        assert_eq!(sorter(&r1, &r0), Ordering::Greater);
+        assert_eq!(sorter(&r0, &r0), Ordering::Greater);
+    }
+}
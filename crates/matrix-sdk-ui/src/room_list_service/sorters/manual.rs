@@ -0,0 +1,82 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use matrix_sdk::{Client, RoomListEntry};
+use ruma::{events::GlobalAccountDataEventType, OwnedRoomId};
+use serde::Deserialize;
+
+/// The content of an account data event holding a manual ordering of rooms.
+#[derive(Debug, Deserialize)]
+struct ManualRoomOrderContent {
+    rooms: Vec<OwnedRoomId>,
+}
+
+/// Load a manual ordering of rooms from the global account data event of the
+/// given type.
+///
+/// The content of the event is expected to be of the form `{"rooms": […]}`,
+/// where `rooms` is the ordered list of room IDs. If the event doesn't exist,
+/// an empty ordering is returned.
+pub async fn load_manual_order(
+    client: &Client,
+    event_type: &str,
+) -> Result<Vec<OwnedRoomId>, matrix_sdk::Error> {
+    let Some(raw) =
+        client.account().account_data_raw(GlobalAccountDataEventType::from(event_type)).await?
+    else {
+        return Ok(Vec::new());
+    };
+
+    Ok(raw.deserialize_as::<ManualRoomOrderContent>()?.rooms)
+}
+
+/// Create a new sorter that will sort the rooms according to the given
+/// ordering.
+///
+/// Rooms that are part of `order` come first, in the same order. Other rooms
+/// come after, and keep their relative order. See [`load_manual_order`] to
+/// load the ordering from the account data.
+pub fn new_sorter(order: Vec<OwnedRoomId>) -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    let positions: HashMap<OwnedRoomId, usize> =
+        order.into_iter().enumerate().map(|(position, room_id)| (room_id, position)).collect();
+
+    move |left, right| -> Ordering {
+        let position = |room: &RoomListEntry| {
+            room.as_room_id().and_then(|room_id| positions.get(room_id)).copied()
+        };
+
+        match (position(left), position(right)) {
+            (Some(left), Some(right)) => left.cmp(&right),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::new_sorter;
+
+    #[test]
+    fn test_manual_order() {
+        let manual = new_sorter(vec![
+            room_id!("!r2:bar.org").to_owned(),
+            room_id!("!r0:bar.org").to_owned(),
+        ]);
+
+        let r0 = RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned());
+        let r1 = RoomListEntry::Filled(room_id!("!r1:bar.org").to_owned());
+        let r2 = RoomListEntry::Invalidated(room_id!("!r2:bar.org").to_owned());
+        let r3 = RoomListEntry::Filled(room_id!("!r3:bar.org").to_owned());
+
+        assert_eq!(manual(&r2, &r0), Ordering::Less);
+        assert_eq!(manual(&r0, &r1), Ordering::Less);
+        assert_eq!(manual(&r3, &r2), Ordering::Greater);
+        assert_eq!(manual(&r1, &r3), Ordering::Equal);
+    }
+}
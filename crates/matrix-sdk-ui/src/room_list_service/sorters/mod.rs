@@ -0,0 +1,545 @@
+//! Sorters for the entries of a [`RoomList`](super::RoomList).
+//!
+//! A sorter is a function comparing two [`RoomListEntry`]s. It can be set, and
+//! changed over time, with
+//! [`RoomListDynamicEntriesController::set_sorter`](super::RoomListDynamicEntriesController::set_sorter).
+//! Entries without a room ID (i.e. [`RoomListEntry::Empty`]) are always put at
+//! the end, so sorters only have to care about actual rooms.
+
+mod favourites_first;
+mod lexicographic;
+mod manual;
+mod name;
+mod recency;
+mod unread_first;
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet, VecDeque},
+    future::ready,
+    sync::Arc,
+};
+
+use eyeball::Subscriber;
+use eyeball_im::{Vector, VectorDiff};
+pub use favourites_first::new_sorter as new_sorter_favourites_first;
+use futures_util::{stream, Stream, StreamExt as _};
+pub use lexicographic::new_sorter as new_sorter_lexicographic;
+pub use manual::{load_manual_order, new_sorter as new_sorter_manual};
+use matrix_sdk::RoomListEntry;
+pub use name::new_sorter as new_sorter_name;
+pub use recency::new_sorter as new_sorter_recency;
+use ruma::RoomId;
+pub use unread_first::new_sorter as new_sorter_unread_first;
+
+/// A type-erased sorter.
+pub type BoxedSorterFn = Box<dyn Fn(&RoomListEntry, &RoomListEntry) -> Ordering + Send + Sync>;
+
+/// Compare two entries with the given sorter, putting entries without a room
+/// ID at the end.
+fn compare(sorter: &BoxedSorterFn, left: &RoomListEntry, right: &RoomListEntry) -> Ordering {
+    match (left.as_room_id(), right.as_room_id()) {
+        (Some(_), Some(_)) => sorter(left, right),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// A key identifying an entry, to look entries up without scanning the
+/// vectors.
+///
+/// `RoomListEntry` only implements `PartialEq` for tests.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum EntryKey<'a> {
+    Empty,
+    Invalidated(&'a RoomId),
+    Filled(&'a RoomId),
+}
+
+impl<'a> EntryKey<'a> {
+    fn new(entry: &'a RoomListEntry) -> Self {
+        match entry {
+            RoomListEntry::Empty => Self::Empty,
+            RoomListEntry::Invalidated(room_id) => Self::Invalidated(room_id),
+            RoomListEntry::Filled(room_id) => Self::Filled(room_id),
+        }
+    }
+}
+
+/// Apply a diff to a vector of entries.
+pub(super) fn apply_diff(entries: &mut Vector<RoomListEntry>, diff: VectorDiff<RoomListEntry>) {
+    match diff {
+        VectorDiff::Append { values } => entries.append(values),
+        VectorDiff::Clear => entries.clear(),
+        VectorDiff::PushFront { value } => entries.push_front(value),
+        VectorDiff::PushBack { value } => entries.push_back(value),
+        VectorDiff::PopFront => {
+            entries.pop_front();
+        }
+        VectorDiff::PopBack => {
+            entries.pop_back();
+        }
+        VectorDiff::Insert { index, value } => entries.insert(index, value),
+        VectorDiff::Set { index, value } => {
+            entries.set(index, value);
+        }
+        VectorDiff::Remove { index } => {
+            entries.remove(index);
+        }
+        VectorDiff::Truncate { length } => entries.truncate(length),
+        VectorDiff::Reset { values } => *entries = values,
+    }
+}
+
+/// Compute the diffs to go from `old` to `new`.
+///
+/// Entries that are present in both vectors are moved (with a `Remove` and an
+/// `Insert`) rather than replaced, so that the diffs stay meaningful for the
+/// observers (e.g. to animate the moves). If it's cheaper to send all the
+/// entries again, a single `Reset` is returned.
+pub(super) fn compute_diffs(
+    old: &Vector<RoomListEntry>,
+    new: &Vector<RoomListEntry>,
+) -> Vec<VectorDiff<RoomListEntry>> {
+    let old_entries = old.iter().collect::<Vec<_>>();
+    let mut remaining = RemainingEntries::new(&old_entries);
+    let mut diffs = Vec::new();
+
+    // The number of entries with a given key in `new[index..]`.
+    let mut in_new = HashMap::<_, usize>::new();
+
+    for entry in new {
+        *in_new.entry(EntryKey::new(entry)).or_default() += 1;
+    }
+
+    // `remaining` always holds the entries from `index` to the end of the
+    // vector being transformed, `new[..index]` being already built.
+    for (index, entry) in new.iter().enumerate() {
+        let key = EntryKey::new(entry);
+
+        if let Some(count) = in_new.get_mut(&key) {
+            *count -= 1;
+        }
+
+        if let Some(first) = remaining.first() {
+            if remaining.key(first) == key {
+                remaining.remove(first);
+                continue;
+            }
+        }
+
+        // Whether the entry at `index` is not needed anymore to build
+        // `new[index..]`.
+        let is_surplus = |first: &usize| {
+            let first_key = remaining.key(*first);
+            remaining.count(first_key) > in_new.get(&first_key).copied().unwrap_or_default()
+        };
+
+        if let Some(position) = remaining.first_with_key(key) {
+            let from = index + remaining.rank(position);
+            remaining.remove(position);
+            diffs.push(VectorDiff::Remove { index: from });
+            diffs.push(VectorDiff::Insert { index, value: old_entries[position].clone() });
+        } else if let Some(first) = remaining.first().filter(is_surplus) {
+            remaining.remove(first);
+            diffs.push(VectorDiff::Set { index, value: entry.clone() });
+        } else {
+            diffs.push(VectorDiff::Insert { index, value: entry.clone() });
+        }
+    }
+
+    if !remaining.is_empty() {
+        diffs.push(VectorDiff::Truncate { length: new.len() });
+    }
+
+    if diffs.len() > new.len() {
+        vec![VectorDiff::Reset { values: new.clone() }]
+    } else {
+        diffs
+    }
+}
+
+/// The entries of the old vector that haven't been consumed by
+/// [`compute_diffs`] yet.
+///
+/// They are always at the end of the vector being transformed, in their
+/// original order, so the position of one of them is the number of entries
+/// already built plus its rank among the remaining entries. The ranks are
+/// counted with a Fenwick tree, so that every operation is `O(log n)`.
+struct RemainingEntries<'a> {
+    /// The keys of the old entries, by position in the old vector.
+    keys: Vec<EntryKey<'a>>,
+    /// The positions of the remaining entries, by key, in ascending order.
+    by_key: HashMap<EntryKey<'a>, VecDeque<usize>>,
+    /// A Fenwick tree counting the remaining entries.
+    tree: Vec<usize>,
+    /// Whether the entry at a given position has been consumed.
+    consumed: Vec<bool>,
+    /// The position of the first remaining entry.
+    first: usize,
+    /// The number of remaining entries.
+    len: usize,
+}
+
+impl<'a> RemainingEntries<'a> {
+    fn new(entries: &[&'a RoomListEntry]) -> Self {
+        let keys = entries.iter().copied().map(EntryKey::new).collect::<Vec<_>>();
+        let mut by_key = HashMap::<_, VecDeque<_>>::new();
+
+        for (position, key) in keys.iter().enumerate() {
+            by_key.entry(*key).or_default().push_back(position);
+        }
+
+        // Every entry counts for one, so each node of the tree covers as many
+        // entries as its lowest set bit.
+        let tree = (1..=keys.len()).map(|node| node & node.wrapping_neg()).collect();
+        let len = keys.len();
+
+        Self { keys, by_key, tree, consumed: vec![false; len], first: 0, len }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The position of the first remaining entry.
+    fn first(&self) -> Option<usize> {
+        (self.first < self.keys.len()).then_some(self.first)
+    }
+
+    fn key(&self, position: usize) -> EntryKey<'a> {
+        self.keys[position]
+    }
+
+    /// The number of remaining entries with the given key.
+    fn count(&self, key: EntryKey<'a>) -> usize {
+        self.by_key.get(&key).map_or(0, VecDeque::len)
+    }
+
+    /// The position of the first remaining entry with the given key.
+    fn first_with_key(&self, key: EntryKey<'a>) -> Option<usize> {
+        self.by_key.get(&key)?.front().copied()
+    }
+
+    /// The number of remaining entries before the given position.
+    fn rank(&self, position: usize) -> usize {
+        let mut rank = 0;
+        let mut node = position;
+
+        while node > 0 {
+            rank += self.tree[node - 1];
+            node &= node - 1;
+        }
+
+        rank
+    }
+
+    /// Consume the entry at the given position.
+    ///
+    /// It must be the first remaining entry with its key.
+    fn remove(&mut self, position: usize) {
+        if let Some(positions) = self.by_key.get_mut(&self.keys[position]) {
+            positions.pop_front();
+        }
+
+        self.consumed[position] = true;
+        self.len -= 1;
+
+        let mut node = position + 1;
+
+        while node <= self.tree.len() {
+            self.tree[node - 1] -= 1;
+            node += node & node.wrapping_neg();
+        }
+
+        while self.consumed.get(self.first).is_some_and(|consumed| *consumed) {
+            self.first += 1;
+        }
+    }
+}
+
+/// The entries of a room list, sorted by a sorter that can change over time.
+///
+/// Without a sorter, the entries are kept in the order of the server, and the
+/// diffs are forwarded as is.
+struct SortedEntries {
+    unsorted: Vector<RoomListEntry>,
+    sorted: Vector<RoomListEntry>,
+    sorter: Option<Arc<BoxedSorterFn>>,
+}
+
+impl SortedEntries {
+    fn new(unsorted: Vector<RoomListEntry>, sorter: Option<Arc<BoxedSorterFn>>) -> Self {
+        let sorted = Self::sort(&unsorted, sorter.as_deref());
+
+        Self { unsorted, sorted, sorter }
+    }
+
+    fn sort(
+        entries: &Vector<RoomListEntry>,
+        sorter: Option<&BoxedSorterFn>,
+    ) -> Vector<RoomListEntry> {
+        let Some(sorter) = sorter else { return entries.clone() };
+
+        let mut entries = entries.iter().cloned().collect::<Vec<_>>();
+
+        // `sort_by` is stable: entries that compare equal keep the order in which
+        // the server sent them.
+        entries.sort_by(|left, right| compare(sorter, left, right));
+
+        entries.into_iter().collect()
+    }
+
+    fn apply_diffs(
+        &mut self,
+        diffs: Vec<VectorDiff<RoomListEntry>>,
+    ) -> Vec<VectorDiff<RoomListEntry>> {
+        if self.sorter.is_none() {
+            for diff in diffs.iter().cloned() {
+                apply_diff(&mut self.unsorted, diff);
+            }
+
+            self.sorted = self.unsorted.clone();
+
+            return diffs;
+        }
+
+        // Entries that have been updated in place must still be reported as such,
+        // even if they don't move, so that observers know they have changed.
+        let mut updated_entries = Vec::new();
+
+        for diff in diffs {
+            if let VectorDiff::Set { value, .. } = &diff {
+                updated_entries.push(value.clone());
+            }
+
+            apply_diff(&mut self.unsorted, diff);
+        }
+
+        let mut diffs = self.resort();
+
+        if updated_entries.is_empty() || matches!(diffs.as_slice(), [VectorDiff::Reset { .. }]) {
+            return diffs;
+        }
+
+        let mut reported = diffs
+            .iter()
+            .filter_map(|diff| match diff {
+                VectorDiff::Insert { value, .. } | VectorDiff::Set { value, .. } => {
+                    Some(EntryKey::new(value))
+                }
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let mut positions = HashMap::new();
+
+        for (index, entry) in self.sorted.iter().enumerate() {
+            positions.entry(EntryKey::new(entry)).or_insert(index);
+        }
+
+        let mut updates = Vec::new();
+
+        for entry in &updated_entries {
+            let key = EntryKey::new(entry);
+
+            if !reported.insert(key) {
+                continue;
+            }
+
+            if let Some(&index) = positions.get(&key) {
+                updates.push(VectorDiff::Set { index, value: entry.clone() });
+            }
+        }
+
+        diffs.extend(updates);
+
+        diffs
+    }
+
+    fn set_sorter(&mut self, sorter: Option<Arc<BoxedSorterFn>>) -> Vec<VectorDiff<RoomListEntry>> {
+        self.sorter = sorter;
+
+        self.resort()
+    }
+
+    fn resort(&mut self) -> Vec<VectorDiff<RoomListEntry>> {
+        let sorted = Self::sort(&self.unsorted, self.sorter.as_deref());
+        let diffs = compute_diffs(&self.sorted, &sorted);
+        self.sorted = sorted;
+
+        diffs
+    }
+}
+
+enum SortUpdate {
+    Entries(Vec<VectorDiff<RoomListEntry>>),
+    Sorter(Option<Arc<BoxedSorterFn>>),
+}
+
+/// Sort the entries of a room list, re-sorting them whenever the entries or
+/// the sorter change.
+///
+/// Changing the sorter doesn't reset the stream: the entries are moved to
+/// their new positions instead.
+pub(super) fn sort_by_dynamic_sorter<S>(
+    values: Vector<RoomListEntry>,
+    entries_stream: S,
+    sorter: Subscriber<Option<Arc<BoxedSorterFn>>>,
+) -> (Vector<RoomListEntry>, impl Stream<Item = Vec<VectorDiff<RoomListEntry>>>)
+where
+    S: Stream<Item = Vec<VectorDiff<RoomListEntry>>>,
+{
+    let mut sorted_entries = SortedEntries::new(values, sorter.get());
+    let values = sorted_entries.sorted.clone();
+
+    let updates =
+        stream::select(entries_stream.map(SortUpdate::Entries), sorter.map(SortUpdate::Sorter));
+
+    let stream = updates.filter_map(move |update| {
+        let diffs = match update {
+            SortUpdate::Entries(diffs) => sorted_entries.apply_diffs(diffs),
+            SortUpdate::Sorter(sorter) => sorted_entries.set_sorter(sorter),
+        };
+
+        ready((!diffs.is_empty()).then_some(diffs))
+    });
+
+    (values, stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cmp::Ordering, sync::Arc};
+
+    use assert_matches2::assert_let;
+    use eyeball_im::{Vector, VectorDiff};
+    use matrix_sdk::RoomListEntry;
+    use ruma::{room_id, RoomId};
+
+    use super::{apply_diff, compute_diffs, BoxedSorterFn, SortedEntries};
+
+    fn filled(room_id: &RoomId) -> RoomListEntry {
+        RoomListEntry::Filled(room_id.to_owned())
+    }
+
+    fn room_ids(entries: &Vector<RoomListEntry>) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| entry.as_room_id().map(|id| id.to_string()).unwrap_or_default())
+            .collect()
+    }
+
+    fn assert_diffs(old: Vector<RoomListEntry>, new: Vector<RoomListEntry>) {
+        let mut applied = old.clone();
+
+        for diff in compute_diffs(&old, &new) {
+            apply_diff(&mut applied, diff);
+        }
+
+        assert_eq!(room_ids(&applied), room_ids(&new));
+    }
+
+    #[test]
+    fn test_compute_diffs() {
+        let r0 = filled(room_id!("!r0:bar.org"));
+        let r1 = filled(room_id!("!r1:bar.org"));
+        let r2 = filled(room_id!("!r2:bar.org"));
+        let r3 = filled(room_id!("!r3:bar.org"));
+
+        // Nothing changes.
+        let entries = Vector::from(vec![r0.clone(), r1.clone(), r2.clone()]);
+        assert!(compute_diffs(&entries, &entries).is_empty());
+
+        // A room moves to the top.
+        let old = Vector::from(vec![r0.clone(), r1.clone(), r2.clone()]);
+        let new = Vector::from(vec![r2.clone(), r0.clone(), r1.clone()]);
+        assert_eq!(compute_diffs(&old, &new).len(), 2);
+        assert_diffs(old, new);
+
+        // Insertions, removals and empty entries.
+        let old = Vector::from(vec![r0.clone(), RoomListEntry::Empty, r1.clone(), r2.clone()]);
+        let new = Vector::from(vec![r3.clone(), r1.clone(), r0.clone(), RoomListEntry::Empty]);
+        assert_diffs(old, new);
+
+        // Everything is removed.
+        let old = Vector::from(vec![r0, r1, r2, r3]);
+        assert_diffs(old, Vector::new());
+
+        // A room moves to the top of a long list.
+        let old = (0..10_000)
+            .map(|i| filled(&RoomId::parse(format!("!r{i}:bar.org")).unwrap()))
+            .collect::<Vector<_>>();
+        let mut new = old.clone();
+        let last = new.pop_back().unwrap();
+        new.push_front(last);
+        assert_eq!(compute_diffs(&old, &new).len(), 2);
+        assert_diffs(old, new);
+    }
+
+    #[test]
+    fn test_sorted_entries() {
+        let by_room_id: BoxedSorterFn =
+            Box::new(|left, right| left.as_room_id().cmp(&right.as_room_id()));
+        let reversed: BoxedSorterFn =
+            Box::new(|left, right| right.as_room_id().cmp(&left.as_room_id()));
+
+        let mut sorted_entries = SortedEntries::new(
+            Vector::from(vec![
+                RoomListEntry::Empty,
+                filled(room_id!("!r1:bar.org")),
+                filled(room_id!("!r0:bar.org")),
+            ]),
+            Some(Arc::new(by_room_id)),
+        );
+
+        // Empty entries are put at the end.
+        assert_eq!(room_ids(&sorted_entries.sorted), ["!r0:bar.org", "!r1:bar.org", ""]);
+
+        // A new entry is inserted at the right position.
+        let diffs = sorted_entries
+            .apply_diffs(vec![VectorDiff::PushFront { value: filled(room_id!("!r2:bar.org")) }]);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(
+            room_ids(&sorted_entries.sorted),
+            ["!r0:bar.org", "!r1:bar.org", "!r2:bar.org", ""]
+        );
+
+        // An entry updated in place is reported, even if it doesn't move.
+        let diffs = sorted_entries.apply_diffs(vec![VectorDiff::Set {
+            index: 0,
+            value: filled(room_id!("!r2:bar.org")),
+        }]);
+        assert_eq!(diffs.len(), 1);
+        assert_let!(VectorDiff::Set { index: 2, .. } = &diffs[0]);
+
+        // Changing the sorter moves the entries.
+        let diffs = sorted_entries.set_sorter(Some(Arc::new(reversed)));
+        assert!(diffs.iter().all(|diff| !matches!(diff, VectorDiff::Reset { .. })));
+        assert_eq!(
+            room_ids(&sorted_entries.sorted),
+            ["!r2:bar.org", "!r1:bar.org", "!r0:bar.org", ""]
+        );
+
+        // Sorters returning `Equal` keep the order of the server.
+        let recency: BoxedSorterFn = Box::new(|_, _| Ordering::Equal);
+        sorted_entries.set_sorter(Some(Arc::new(recency)));
+        assert_eq!(
+            room_ids(&sorted_entries.sorted),
+            ["!r2:bar.org", "!r1:bar.org", "!r0:bar.org", ""]
+        );
+
+        // Without a sorter, the order of the server is restored…
+        sorted_entries.set_sorter(None);
+        assert_eq!(
+            room_ids(&sorted_entries.sorted),
+            ["!r2:bar.org", "", "!r1:bar.org", "!r0:bar.org"]
+        );
+
+        // … and the diffs are forwarded as is.
+        let diffs = sorted_entries.apply_diffs(vec![VectorDiff::PopBack]);
+        assert_let!([VectorDiff::PopBack] = diffs.as_slice());
+        assert_eq!(room_ids(&sorted_entries.sorted), ["!r2:bar.org", "", "!r1:bar.org"]);
+    }
+}
@@ -0,0 +1,77 @@
+use std::cmp::Ordering;
+
+use matrix_sdk::{Client, RoomListEntry};
+
+use super::super::filters::normalize_string;
+
+struct NameSorter<F: Fn(&RoomListEntry) -> Option<String>> {
+    get_name: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<String>> NameSorter<F> {
+    fn sort_key(&self, room: &RoomListEntry) -> Option<String> {
+        (self.get_name)(room).map(|name| normalize_string(&name).to_lowercase())
+    }
+
+    fn cmp(&self, left: &RoomListEntry, right: &RoomListEntry) -> Ordering {
+        // Rooms without a name are put at the end.
+        match (self.sort_key(left), self.sort_key(right)) {
+            (Some(left), Some(right)) => left.cmp(&right),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Create a new sorter that will sort the rooms alphabetically.
+///
+/// The room name is used if there is one, otherwise it falls back to the
+/// canonical alias. Names are normalized with `normalize_string` and compared
+/// case-insensitively.
+pub fn new_sorter(client: &Client) -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    let client = client.clone();
+
+    let sorter = NameSorter {
+        get_name: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+
+            room.name().or_else(|| room.canonical_alias().map(|alias| alias.alias().to_owned()))
+        },
+    };
+
+    move |left, right| -> Ordering { sorter.cmp(left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::NameSorter;
+
+    #[test]
+    fn test_alphabetical() {
+        let sorter = NameSorter {
+            get_name: |room| match room.as_room_id()?.as_str() {
+                "!a:bar.org" => Some("Été".to_owned()),
+                "!b:bar.org" => Some("ecole".to_owned()),
+                "!c:bar.org" => Some("Zoo".to_owned()),
+                _ => None,
+            },
+        };
+
+        let a = RoomListEntry::Filled(room_id!("!a:bar.org").to_owned());
+        let b = RoomListEntry::Filled(room_id!("!b:bar.org").to_owned());
+        let c = RoomListEntry::Filled(room_id!("!c:bar.org").to_owned());
+        let unnamed = RoomListEntry::Filled(room_id!("!d:bar.org").to_owned());
+
+        assert_eq!(sorter.cmp(&b, &a), Ordering::Less);
+        assert_eq!(sorter.cmp(&a, &c), Ordering::Less);
+        assert_eq!(sorter.cmp(&c, &unnamed), Ordering::Less);
+        assert_eq!(sorter.cmp(&unnamed, &unnamed), Ordering::Equal);
+    }
+}
@@ -0,0 +1,34 @@
+use std::cmp::Ordering;
+
+use matrix_sdk::RoomListEntry;
+
+/// Create a new sorter that keeps the entries in the order they are received
+/// from the server.
+///
+/// The server sorts the rooms by recency, so this is the default sorter.
+pub fn new_sorter() -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    |_left, _right| -> Ordering { Ordering::Equal }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::new_sorter;
+
+    #[test]
+    fn test_keep_server_order() {
+        let recency = new_sorter();
+
+        assert_eq!(
+            recency(
+                &RoomListEntry::Filled(room_id!("!r0:bar.org").to_owned()),
+                &RoomListEntry::Filled(room_id!("!r1:bar.org").to_owned())
+            ),
+            Ordering::Equal
+        );
+    }
+}
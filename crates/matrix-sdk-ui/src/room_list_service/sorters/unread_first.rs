@@ -0,0 +1,94 @@
+use std::cmp::Ordering;
+
+use matrix_sdk::{Client, RoomListEntry};
+
+/// The unread state of a room, as used by the sorter. Variants are declared in
+/// the order they must be sorted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum UnreadState {
+    Mentions,
+    Notifications,
+    Messages,
+    Read,
+}
+
+struct UnreadFirstSorter<F: Fn(&RoomListEntry) -> Option<UnreadState>> {
+    get_unread_state: F,
+}
+
+impl<F: Fn(&RoomListEntry) -> Option<UnreadState>> UnreadFirstSorter<F> {
+    fn cmp(&self, left: &RoomListEntry, right: &RoomListEntry) -> Ordering {
+        let left = (self.get_unread_state)(left).unwrap_or(UnreadState::Read);
+        let right = (self.get_unread_state)(right).unwrap_or(UnreadState::Read);
+
+        left.cmp(&right)
+    }
+}
+
+/// Create a new sorter that will put the rooms with unread mentions first,
+/// then the rooms with unread notifications, then the rooms with unread
+/// messages, and finally the rooms that have been read.
+///
+/// For encrypted rooms, the counts computed client-side are used.
+pub fn new_sorter(client: &Client) -> impl Fn(&RoomListEntry, &RoomListEntry) -> Ordering {
+    let client = client.clone();
+
+    let sorter = UnreadFirstSorter {
+        get_unread_state: move |room| {
+            let room_id = room.as_room_id()?;
+            let room = client.get_room(room_id)?;
+
+            let (mentions, notifications) = if room.is_encrypted() {
+                (room.num_unread_mentions(), room.num_unread_notifications())
+            } else {
+                let counts = room.unread_notification_counts();
+                (counts.highlight_count, counts.notification_count)
+            };
+
+            Some(if mentions > 0 {
+                UnreadState::Mentions
+            } else if notifications > 0 {
+                UnreadState::Notifications
+            } else if room.num_unread_messages() > 0 {
+                UnreadState::Messages
+            } else {
+                UnreadState::Read
+            })
+        },
+    };
+
+    move |left, right| -> Ordering { sorter.cmp(left, right) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use matrix_sdk::RoomListEntry;
+    use ruma::room_id;
+
+    use super::{UnreadFirstSorter, UnreadState};
+
+    #[test]
+    fn test_unread_first() {
+        let sorter = UnreadFirstSorter {
+            get_unread_state: |room| match room.as_room_id()?.as_str() {
+                "!mentions:bar.org" => Some(UnreadState::Mentions),
+                "!notifications:bar.org" => Some(UnreadState::Notifications),
+                "!messages:bar.org" => Some(UnreadState::Messages),
+                _ => None,
+            },
+        };
+
+        let mentions = RoomListEntry::Filled(room_id!("!mentions:bar.org").to_owned());
+        let notifications = RoomListEntry::Filled(room_id!("!notifications:bar.org").to_owned());
+        let messages = RoomListEntry::Invalidated(room_id!("!messages:bar.org").to_owned());
+        let unknown = RoomListEntry::Filled(room_id!("!unknown:bar.org").to_owned());
+
+        assert_eq!(sorter.cmp(&mentions, &notifications), Ordering::Less);
+        assert_eq!(sorter.cmp(&notifications, &messages), Ordering::Less);
+        assert_eq!(sorter.cmp(&messages, &unknown), Ordering::Less);
+        assert_eq!(sorter.cmp(&unknown, &mentions), Ordering::Greater);
+        assert_eq!(sorter.cmp(&unknown, &unknown), Ordering::Equal);
+    }
+}
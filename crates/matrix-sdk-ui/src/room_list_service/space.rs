@@ -0,0 +1,469 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for that specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::ready,
+    sync::{Arc, RwLock},
+};
+
+use eyeball::{SharedObservable, Subscriber};
+use futures_util::StreamExt as _;
+use matrix_sdk::{
+    deserialized_responses::SyncOrStrippedState,
+    executor::{spawn, JoinHandle},
+    room::ParentSpace,
+    Client, RoomState,
+};
+use ruma::{
+    api::client::space::get_hierarchy,
+    assign,
+    events::{space::child::SpaceChildEventContent, SyncStateEvent},
+    OwnedRoomId, RoomId,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use super::Error;
+
+/// The relationships between a space and its children.
+#[derive(Debug, Default)]
+pub(super) struct SpaceRelations {
+    /// The rooms the space declares as its children, with `m.space.child`
+    /// state events.
+    declared: BTreeSet<OwnedRoomId>,
+
+    /// The joined rooms that declare the space as their parent, with
+    /// `m.space.parent` state events.
+    claimed: BTreeSet<OwnedRoomId>,
+
+    /// The rooms found in the hierarchy of the space. It includes the rooms
+    /// the user hasn't joined, and the rooms of the sub-spaces.
+    hierarchy: BTreeSet<OwnedRoomId>,
+
+    /// The children of every room found in the hierarchy of the space,
+    /// including the space itself.
+    hierarchy_tree: BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+}
+
+impl SpaceRelations {
+    /// Whether the room is a child of the space.
+    pub(super) fn contains(&self, room_id: &RoomId) -> bool {
+        self.declared.contains(room_id)
+            || self.claimed.contains(room_id)
+            || self.hierarchy.contains(room_id)
+    }
+
+    fn children(&self) -> BTreeSet<OwnedRoomId> {
+        self.declared.iter().chain(&self.claimed).chain(&self.hierarchy).cloned().collect()
+    }
+
+    /// Replace the hierarchy of the space with the one loaded from the
+    /// server.
+    fn set_hierarchy(
+        &mut self,
+        space_id: &RoomId,
+        hierarchy_tree: BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+    ) {
+        self.hierarchy_tree = hierarchy_tree;
+        self.hierarchy = rooms_in_hierarchy(space_id, &self.hierarchy_tree);
+    }
+
+    /// Replace the children declared by the space.
+    ///
+    /// The children the space doesn't declare anymore are removed from the
+    /// hierarchy too, with the rooms that were only reachable through them.
+    fn set_declared(&mut self, space_id: &RoomId, declared: BTreeSet<OwnedRoomId>) {
+        if let Some(children) = self.hierarchy_tree.get_mut(space_id) {
+            for room_id in self.declared.difference(&declared) {
+                children.remove(room_id);
+            }
+        }
+
+        self.declared = declared;
+        self.hierarchy = rooms_in_hierarchy(space_id, &self.hierarchy_tree);
+    }
+}
+
+/// Get the rooms of the hierarchy that can be reached from the space.
+fn rooms_in_hierarchy(
+    space_id: &RoomId,
+    hierarchy_tree: &BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>,
+) -> BTreeSet<OwnedRoomId> {
+    let mut rooms = BTreeSet::new();
+    let mut spaces = vec![space_id];
+
+    while let Some(parent_id) = spaces.pop() {
+        let Some(children) = hierarchy_tree.get(parent_id) else { continue };
+
+        for room_id in children {
+            // Only the rooms returned by the server are part of the hierarchy,
+            // and every sub-space is only visited once.
+            if **room_id != *space_id
+                && hierarchy_tree.contains_key(room_id)
+                && rooms.insert(room_id.clone())
+            {
+                spaces.push(room_id);
+            }
+        }
+    }
+
+    rooms
+}
+
+/// A space, and the rooms it contains.
+///
+/// The children of the space are resolved from the `m.space.child` state
+/// events of the space, from the `m.space.parent` state events of the joined
+/// rooms, and from the space hierarchy returned by the server, which also
+/// contains the rooms the user hasn't joined.
+///
+/// The local state events are watched, so the children are updated as soon as
+/// a sync changes them. The hierarchy is only loaded again with
+/// [`Space::refresh`], but the rooms of the hierarchy that the space doesn't
+/// declare as its children anymore are dropped as soon as its state changes.
+///
+/// To get a room list restricted to a space, use
+/// [`new_filter_space`](super::filters::new_filter_space).
+#[derive(Debug)]
+pub struct Space {
+    client: Client,
+    space_id: OwnedRoomId,
+    relations: Arc<RwLock<SpaceRelations>>,
+    children: SharedObservable<BTreeSet<OwnedRoomId>>,
+    update_task: JoinHandle<()>,
+}
+
+impl Drop for Space {
+    fn drop(&mut self) {
+        self.update_task.abort();
+    }
+}
+
+impl Space {
+    pub(super) async fn new(client: &Client, space_id: &RoomId) -> Result<Self, Error> {
+        let mut claimed = BTreeSet::new();
+
+        for room in client.joined_rooms() {
+            if claims_space(&room, space_id).await {
+                claimed.insert(room.room_id().to_owned());
+            }
+        }
+
+        let mut relations = SpaceRelations {
+            declared: declared_children(client, space_id).await,
+            claimed,
+            ..Default::default()
+        };
+        relations.set_hierarchy(space_id, load_hierarchy(client, space_id).await?);
+
+        let children = SharedObservable::new(relations.children());
+        let relations = Arc::new(RwLock::new(relations));
+
+        let update_task = spawn({
+            let client = client.clone();
+            let space_id = space_id.to_owned();
+            let relations = relations.clone();
+            let children = children.clone();
+            let mut room_info_updates = client.subscribe_to_room_info_updates();
+
+            async move {
+                loop {
+                    let room_ids = match room_info_updates.recv().await {
+                        Ok(room_ids) => Some(room_ids),
+                        // Some updates have been missed, check all the rooms.
+                        Err(RecvError::Lagged(_)) => None,
+                        Err(RecvError::Closed) => break,
+                    };
+
+                    update_relations(&client, &space_id, &relations, room_ids).await;
+
+                    let new_children = relations.read().unwrap().children();
+                    children.set_if_not_eq(new_children);
+                }
+            }
+        });
+
+        Ok(Self {
+            client: client.clone(),
+            space_id: space_id.to_owned(),
+            relations,
+            children,
+            update_task,
+        })
+    }
+
+    /// Get the ID of the space.
+    pub fn space_id(&self) -> &RoomId {
+        &self.space_id
+    }
+
+    /// Get the IDs of the children of the space.
+    pub fn children(&self) -> BTreeSet<OwnedRoomId> {
+        self.children.get()
+    }
+
+    /// Get a stream of the IDs of the children of the space, updated every
+    /// time they change.
+    pub fn children_stream(&self) -> Subscriber<BTreeSet<OwnedRoomId>> {
+        self.children.subscribe()
+    }
+
+    /// Whether the room is a child of the space.
+    pub fn contains(&self, room_id: &RoomId) -> bool {
+        self.relations.read().unwrap().contains(room_id)
+    }
+
+    /// Load the hierarchy of the space again from the server.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        let hierarchy_tree = load_hierarchy(&self.client, &self.space_id).await?;
+
+        let new_children = {
+            let mut relations = self.relations.write().unwrap();
+            relations.set_hierarchy(&self.space_id, hierarchy_tree);
+            relations.children()
+        };
+
+        self.children.set_if_not_eq(new_children);
+
+        Ok(())
+    }
+
+    pub(super) fn relations(&self) -> Arc<RwLock<SpaceRelations>> {
+        self.relations.clone()
+    }
+}
+
+/// Update the relations after the info of the given rooms has changed, or of
+/// all the rooms if `None`.
+async fn update_relations(
+    client: &Client,
+    space_id: &RoomId,
+    relations: &RwLock<SpaceRelations>,
+    room_ids: Option<BTreeSet<OwnedRoomId>>,
+) {
+    let Some(room_ids) = room_ids else {
+        let declared = declared_children(client, space_id).await;
+        let mut claimed = BTreeSet::new();
+
+        for room in client.joined_rooms() {
+            if claims_space(&room, space_id).await {
+                claimed.insert(room.room_id().to_owned());
+            }
+        }
+
+        let mut relations = relations.write().unwrap();
+        relations.set_declared(space_id, declared);
+        relations.claimed = claimed;
+
+        return;
+    };
+
+    let declared = if room_ids.contains(space_id) {
+        Some(declared_children(client, space_id).await)
+    } else {
+        None
+    };
+
+    let mut claims = Vec::new();
+
+    for room_id in room_ids.into_iter().filter(|room_id| **room_id != *space_id) {
+        let claim = match client.get_room(&room_id) {
+            Some(room) => room.state() == RoomState::Joined && claims_space(&room, space_id).await,
+            None => false,
+        };
+
+        claims.push((room_id, claim));
+    }
+
+    let mut relations = relations.write().unwrap();
+
+    if let Some(declared) = declared {
+        relations.set_declared(space_id, declared);
+    }
+
+    for (room_id, claim) in claims {
+        if claim {
+            relations.claimed.insert(room_id);
+        } else {
+            relations.claimed.remove(&room_id);
+        }
+    }
+}
+
+/// Get the children declared by the space with valid `m.space.child` state
+/// events, i.e. with a non-empty `via` field.
+async fn declared_children(client: &Client, space_id: &RoomId) -> BTreeSet<OwnedRoomId> {
+    let Some(space) = client.get_room(space_id) else { return BTreeSet::new() };
+
+    let events = match space.get_state_events_static::<SpaceChildEventContent>().await {
+        Ok(events) => events,
+        Err(error) => {
+            warn!(?space_id, "Couldn't load the `m.space.child` events: {error}");
+            return BTreeSet::new();
+        }
+    };
+
+    events
+        .into_iter()
+        .filter_map(|event| match event.deserialize() {
+            Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event))) => {
+                (!event.content.via.is_empty()).then_some(event.state_key)
+            }
+            Ok(SyncOrStrippedState::Stripped(event)) => {
+                (!event.content.via.is_empty()).then_some(event.state_key)
+            }
+            Ok(SyncOrStrippedState::Sync(SyncStateEvent::Redacted(_))) | Err(_) => None,
+        })
+        .collect()
+}
+
+/// Whether the room declares the space as its parent, and the relationship is
+/// legitimate.
+async fn claims_space(room: &matrix_sdk::Room, space_id: &RoomId) -> bool {
+    let parents = match room.parent_spaces().await {
+        Ok(parents) => parents,
+        Err(error) => {
+            warn!(room_id = ?room.room_id(), "Couldn't load the parent spaces: {error}");
+            return false;
+        }
+    };
+
+    parents
+        .any(|parent| {
+            ready(matches!(
+                parent,
+                Ok(ParentSpace::Reciprocal(room) | ParentSpace::WithPowerlevel(room))
+                    if room.room_id() == space_id
+            ))
+        })
+        .await
+}
+
+/// The maximum number of pages of the hierarchy of a space that are loaded.
+const MAX_HIERARCHY_PAGES: usize = 20;
+
+/// Get the rooms in the hierarchy of the space with their children, by
+/// paginating the hierarchy endpoint.
+///
+/// At most [`MAX_HIERARCHY_PAGES`] pages are loaded.
+async fn load_hierarchy(
+    client: &Client,
+    space_id: &RoomId,
+) -> Result<BTreeMap<OwnedRoomId, BTreeSet<OwnedRoomId>>, Error> {
+    let mut hierarchy_tree = BTreeMap::new();
+    let mut from = None;
+
+    for _ in 0..MAX_HIERARCHY_PAGES {
+        let request = assign!(get_hierarchy::v1::Request::new(space_id.to_owned()), { from });
+        let response = client.send(request, None).await.map_err(Error::SpaceHierarchy)?;
+
+        hierarchy_tree.extend(response.rooms.into_iter().map(|room| {
+            let children = room
+                .children_state
+                .iter()
+                .filter_map(|event| event.deserialize().ok())
+                // Children without servers to join them through are invalid.
+                .filter(|event| !event.content.via.is_empty())
+                .map(|event| event.state_key)
+                .collect();
+
+            (room.room_id, children)
+        }));
+
+        match response.next_batch {
+            Some(next_batch) => from = Some(next_batch),
+            None => return Ok(hierarchy_tree),
+        }
+    }
+
+    warn!(
+        ?space_id,
+        "The hierarchy of the space is too large, only the first {MAX_HIERARCHY_PAGES} pages \
+         were loaded"
+    );
+
+    Ok(hierarchy_tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use ruma::{owned_room_id, room_id, OwnedRoomId};
+
+    use super::SpaceRelations;
+
+    #[test]
+    fn test_relations_children() {
+        let relations = SpaceRelations {
+            declared: [owned_room_id!("!a:b.c"), owned_room_id!("!d:e.f")].into(),
+            claimed: [owned_room_id!("!d:e.f"), owned_room_id!("!g:h.i")].into(),
+            hierarchy: [owned_room_id!("!a:b.c"), owned_room_id!("!j:k.l")].into(),
+            ..Default::default()
+        };
+
+        assert_eq!(relations.children().len(), 4);
+        assert!(relations.contains(room_id!("!a:b.c")));
+        assert!(relations.contains(room_id!("!g:h.i")));
+        assert!(relations.contains(room_id!("!j:k.l")));
+        assert!(!relations.contains(room_id!("!m:n.o")));
+    }
+
+    #[test]
+    fn test_undeclared_children_are_dropped_from_hierarchy() {
+        let space_id = room_id!("!space:b.c");
+        let mut relations = SpaceRelations {
+            declared: [owned_room_id!("!a:b.c"), owned_room_id!("!sub:b.c")].into(),
+            ..Default::default()
+        };
+
+        let children = |room_ids: &[&OwnedRoomId]| -> BTreeSet<OwnedRoomId> {
+            room_ids.iter().map(|room_id| (*room_id).clone()).collect()
+        };
+        let (a, sub, d, e) = (
+            owned_room_id!("!a:b.c"),
+            owned_room_id!("!sub:b.c"),
+            owned_room_id!("!d:e.f"),
+            owned_room_id!("!e:f.g"),
+        );
+
+        relations.set_hierarchy(
+            space_id,
+            [
+                (space_id.to_owned(), children(&[&a, &sub])),
+                (a.clone(), children(&[])),
+                (sub.clone(), children(&[&d])),
+                (d.clone(), children(&[])),
+                // Not reachable from the space.
+                (e.clone(), children(&[])),
+            ]
+            .into(),
+        );
+
+        assert!(relations.contains(&a));
+        assert!(relations.contains(&sub));
+        assert!(relations.contains(&d));
+        assert!(!relations.contains(&e));
+
+        // The space doesn't declare the sub-space anymore, so its rooms are not
+        // children anymore.
+        relations.set_declared(space_id, [a.clone()].into());
+
+        assert!(relations.contains(&a));
+        assert!(!relations.contains(&sub));
+        assert!(!relations.contains(&d));
+        assert_eq!(relations.children().len(), 1);
+    }
+}
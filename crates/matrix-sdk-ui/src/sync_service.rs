@@ -29,6 +29,7 @@ use eyeball::{SharedObservable, Subscriber};
 use futures_core::Future;
 use futures_util::{pin_mut, StreamExt as _};
 use matrix_sdk::Client;
+use ruma::api::client::error::ErrorKind;
 use thiserror::Error;
 use tokio::{
     sync::{
@@ -65,6 +66,12 @@ pub enum State {
     Terminated,
     /// Any of the underlying syncs has ran into an error.
     Error,
+    /// The session was soft logged out by the server.
+    ///
+    /// The underlying syncs were stopped without discarding their state, and
+    /// can be restarted with [`SyncService::start`] once the client logged
+    /// back in with [`Client::relogin`](matrix_sdk::Client::relogin).
+    Paused,
 }
 
 pub struct SyncService {
@@ -189,7 +196,9 @@ impl SyncService {
                 }
             }
 
-            if report.is_error {
+            if report.is_soft_logout {
+                state.set(State::Paused);
+            } else if report.is_error {
                 if report.has_expired {
                     if stop_room_list {
                         room_list_service.expire_sync_session().await;
@@ -219,7 +228,7 @@ impl SyncService {
             let encryption_sync_stream = encryption_sync.sync(sync_permit_guard);
             pin_mut!(encryption_sync_stream);
 
-            let (is_error, has_expired) = loop {
+            let (is_error, has_expired, is_soft_logout) = loop {
                 let res = encryption_sync_stream.next().await;
                 match res {
                     Some(Ok(())) => {
@@ -235,12 +244,18 @@ impl SyncService {
                             } else {
                                 false
                             };
+                        let is_soft_logout =
+                            if let encryption_sync_service::Error::SlidingSync(err) = &err {
+                                is_soft_logout(err.client_api_error_kind())
+                            } else {
+                                false
+                            };
                         error!("Error while processing encryption in sync service: {err:#}");
-                        break (true, has_expired);
+                        break (true, has_expired, is_soft_logout);
                     }
                     None => {
                         // The stream has ended.
-                        break (false, false);
+                        break (false, false, false);
                     }
                 }
             };
@@ -249,6 +264,7 @@ impl SyncService {
                 .send(TerminationReport {
                     is_error,
                     has_expired,
+                    is_soft_logout,
                     origin: TerminationOrigin::EncryptionSync,
                 })
                 .await
@@ -265,7 +281,7 @@ impl SyncService {
             let room_list_stream = room_list_service.sync();
             pin_mut!(room_list_stream);
 
-            let (is_error, has_expired) = loop {
+            let (is_error, has_expired, is_soft_logout) = loop {
                 let res = room_list_stream.next().await;
                 match res {
                     Some(Ok(())) => {
@@ -280,12 +296,18 @@ impl SyncService {
                         } else {
                             false
                         };
+                        let is_soft_logout =
+                            if let room_list_service::Error::SlidingSync(err) = &err {
+                                is_soft_logout(err.client_api_error_kind())
+                            } else {
+                                false
+                            };
                         error!("Error while processing room list in sync service: {err:#}");
-                        break (true, has_expired);
+                        break (true, has_expired, is_soft_logout);
                     }
                     None => {
                         // The stream has ended.
-                        break (false, false);
+                        break (false, false, false);
                     }
                 }
             };
@@ -294,6 +316,7 @@ impl SyncService {
                 .send(TerminationReport {
                     is_error,
                     has_expired,
+                    is_soft_logout,
                     origin: TerminationOrigin::RoomList,
                 })
                 .await
@@ -351,7 +374,7 @@ impl SyncService {
         let _guard = self.modifying_state.lock().await;
 
         match self.state.get() {
-            State::Idle | State::Terminated | State::Error => {
+            State::Idle | State::Terminated | State::Error | State::Paused => {
                 // No need to stop if we were not running.
                 return Ok(());
             }
@@ -373,6 +396,7 @@ impl SyncService {
             .send(TerminationReport {
                 is_error: false,
                 has_expired: false,
+                is_soft_logout: false,
                 origin: TerminationOrigin::Scheduler,
             })
             .await
@@ -415,9 +439,16 @@ enum TerminationOrigin {
 struct TerminationReport {
     is_error: bool,
     has_expired: bool,
+    is_soft_logout: bool,
     origin: TerminationOrigin,
 }
 
+/// Whether a sync failed because the session was soft logged out, in which
+/// case it must not be expired, to resume it after logging back in.
+fn is_soft_logout(error_kind: Option<&ErrorKind>) -> bool {
+    matches!(error_kind, Some(ErrorKind::UnknownToken { soft_logout: true }))
+}
+
 // Testing helpers, mostly.
 #[doc(hidden)]
 impl SyncService {
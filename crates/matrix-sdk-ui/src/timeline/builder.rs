@@ -21,15 +21,20 @@ use matrix_sdk::{
     deserialized_responses::SyncTimelineEvent, executor::spawn, sync::RoomUpdate, Room,
 };
 use ruma::{
-    events::{receipt::ReceiptType, AnySyncTimelineEvent},
+    events::{
+        receipt::ReceiptType, AnySyncTimelineEvent, MessageLikeEventContent, StateEventContent,
+        StaticEventContent,
+    },
     RoomVersionId,
 };
+use serde::de::DeserializeOwned;
 use tokio::sync::{broadcast, mpsc, Notify};
 use tracing::{info, info_span, trace, warn, Instrument, Span};
 
 #[cfg(feature = "e2e-encryption")]
 use super::to_device::{handle_forwarded_room_key_event, handle_room_key_event};
 use super::{
+    event_cache::EventCacheSource,
     inner::{TimelineInner, TimelineInnerSettings},
     queue::send_queued_messages,
     BackPaginationStatus, Timeline, TimelineDropHandle,
@@ -44,6 +49,7 @@ pub struct TimelineBuilder {
     prev_token: Option<String>,
     events: Vector<SyncTimelineEvent>,
     settings: TimelineInnerSettings,
+    use_event_cache: bool,
 }
 
 impl TimelineBuilder {
@@ -53,6 +59,7 @@ impl TimelineBuilder {
             prev_token: None,
             events: Vector::new(),
             settings: TimelineInnerSettings::default(),
+            use_event_cache: false,
         }
     }
 
@@ -67,6 +74,21 @@ impl TimelineBuilder {
         self
     }
 
+    /// Use the [event cache] of the room as the source of the events of the
+    /// timeline.
+    ///
+    /// The timeline is then a view over the cache: it starts with the events
+    /// of the cache, that are restored from the store, the events received
+    /// from sync and from backwards paginations are never duplicated, and the
+    /// gaps left by limited syncs are filled by
+    /// [`Timeline::paginate_backwards()`] instead of resetting the timeline.
+    ///
+    /// [event cache]: matrix_sdk::event_cache
+    pub fn use_event_cache(mut self) -> Self {
+        self.use_event_cache = true;
+        self
+    }
+
     /// Enable tracking of the fully-read marker and the read receipts on the
     /// timeline.
     pub(crate) fn track_read_marker_and_receipts(mut self) -> Self {
@@ -115,6 +137,45 @@ impl TimelineBuilder {
         self
     }
 
+    /// Register a custom message-like event type, so that its events are
+    /// deserialized into `C` and added to the timeline as
+    /// [`TimelineItemContent::Custom`].
+    ///
+    /// The registered events are added to the timeline regardless of the
+    /// [event filter](Self::event_filter). Events of this type that fail to
+    /// deserialize are handled like other events that fail to deserialize,
+    /// and redacted events are added as redacted messages.
+    ///
+    /// Custom events can be sent with [`Room::send()`].
+    ///
+    /// [`TimelineItemContent::Custom`]: super::TimelineItemContent::Custom
+    /// [`Room::send()`]: matrix_sdk::Room::send
+    pub fn register_message_like_event<C>(mut self) -> Self
+    where
+        C: StaticEventContent + MessageLikeEventContent + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.settings.custom_events.register_message_like::<C>(C::TYPE);
+        self
+    }
+
+    /// Register a custom state event type, so that its events are
+    /// deserialized into `C` and added to the timeline as
+    /// [`TimelineItemContent::Custom`].
+    ///
+    /// This works like [`register_message_like_event()`], and the custom
+    /// state events can be sent with [`Room::send_state_event_for_key()`].
+    ///
+    /// [`TimelineItemContent::Custom`]: super::TimelineItemContent::Custom
+    /// [`register_message_like_event()`]: Self::register_message_like_event
+    /// [`Room::send_state_event_for_key()`]: matrix_sdk::Room::send_state_event_for_key
+    pub fn register_state_event<C>(mut self) -> Self
+    where
+        C: StaticEventContent + StateEventContent + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.settings.custom_events.register_state::<C>(C::TYPE);
+        self
+    }
+
     /// Create a [`Timeline`] with the options set on this builder.
     #[tracing::instrument(
         skip(self),
@@ -123,10 +184,20 @@ impl TimelineBuilder {
             events_length = self.events.len(),
             track_read_receipts = self.settings.track_read_receipts,
             prev_token = self.prev_token,
+            use_event_cache = self.use_event_cache,
         )
     )]
     pub async fn build(self) -> Timeline {
-        let Self { room, prev_token, events, settings } = self;
+        let Self { room, prev_token, events, settings, use_event_cache } = self;
+
+        let (event_cache, events, prev_token) = if use_event_cache {
+            let (event_cache, events) = EventCacheSource::new(room.event_cache().await).await;
+            // The gaps are tracked by the event cache.
+            (Some(Arc::new(event_cache)), events.into(), None)
+        } else {
+            (None, events, prev_token)
+        };
+
         let has_events = !events.is_empty();
         let track_read_marker_and_receipts = settings.track_read_receipts;
 
@@ -152,6 +223,7 @@ impl TimelineBuilder {
         let room_update_join_handle = spawn({
             let sync_response_notify = sync_response_notify.clone();
             let inner = inner.clone();
+            let uses_event_cache = event_cache.is_some();
 
             let span =
                 info_span!(parent: Span::none(), "room_update_handler", room_id = ?room.room_id());
@@ -163,8 +235,12 @@ impl TimelineBuilder {
                         Ok(up) => up,
                         Err(broadcast::error::RecvError::Closed) => break,
                         Err(broadcast::error::RecvError::Lagged(_)) => {
-                            warn!("Lagged behind sync responses, resetting timeline");
-                            inner.clear().await;
+                            // The events don't come from the room updates when
+                            // the timeline uses the event cache.
+                            if !uses_event_cache {
+                                warn!("Lagged behind sync responses, resetting timeline");
+                                inner.clear().await;
+                            }
                             continue;
                         }
                     };
@@ -173,14 +249,23 @@ impl TimelineBuilder {
 
                     match update {
                         RoomUpdate::Left { updates, .. } => {
-                            inner.handle_sync_timeline(updates.timeline).await;
+                            if !uses_event_cache {
+                                inner.handle_sync_timeline(updates.timeline).await;
+                            }
                         }
-                        RoomUpdate::Joined { updates, .. } => {
+                        RoomUpdate::Joined { mut updates, .. } => {
+                            if uses_event_cache {
+                                // The events are added through the event cache.
+                                updates.timeline = Default::default();
+                            }
                             inner.handle_joined_room_update(updates).await;
                         }
                         RoomUpdate::Invited { .. } => {
                             warn!("Room is in invited state, can't build or update its timeline");
                         }
+                        RoomUpdate::Knocked { .. } => {
+                            warn!("Room is in knocked state, can't build or update its timeline");
+                        }
                     }
 
                     sync_response_notify.notify_waiters();
@@ -192,13 +277,17 @@ impl TimelineBuilder {
         let mut ignore_user_list_stream = client.subscribe_to_ignore_user_list_changes();
         let ignore_user_list_update_join_handle = spawn({
             let inner = inner.clone();
+            let event_cache = event_cache.clone();
 
             let span = info_span!(parent: Span::none(), "ignore_user_list_update_handler", room_id = ?room.room_id());
             span.follows_from(Span::current());
 
             async move {
                 while ignore_user_list_stream.next().await.is_some() {
-                    inner.clear().await;
+                    match &event_cache {
+                        Some(event_cache) => event_cache.reset(&inner).await,
+                        None => inner.clear().await,
+                    }
                 }
             }
             .instrument(span)
@@ -252,6 +341,28 @@ impl TimelineBuilder {
             })
         };
 
+        let event_cache_join_handle = event_cache.clone().map(|event_cache| {
+            let inner = inner.clone();
+
+            let span = info_span!(parent: Span::none(), "event_cache_update_handler", room_id = ?room.room_id());
+            span.follows_from(Span::current());
+
+            spawn(
+                async move {
+                    let mut updates = event_cache.subscribe_to_updates().await;
+
+                    loop {
+                        event_cache.catch_up(&inner).await;
+
+                        if let Err(broadcast::error::RecvError::Closed) = updates.recv().await {
+                            break;
+                        }
+                    }
+                }
+                .instrument(span),
+            )
+        });
+
         let (msg_sender, msg_receiver) = mpsc::channel(1);
         info!("Starting message-sending loop");
         spawn(send_queued_messages(inner.clone(), room.clone(), msg_receiver));
@@ -261,6 +372,7 @@ impl TimelineBuilder {
             back_pagination_mtx: Default::default(),
             back_pagination_status: SharedObservable::new(BackPaginationStatus::Idle),
             sync_response_notify,
+            event_cache,
             msg_sender,
             drop_handle: Arc::new(TimelineDropHandle {
                 client,
@@ -268,6 +380,7 @@ impl TimelineBuilder {
                 room_update_join_handle,
                 ignore_user_list_update_join_handle,
                 room_key_from_backups_join_handle,
+                event_cache_join_handle,
             }),
         };
 
@@ -0,0 +1,73 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::deserialized_responses::TimelineEvent;
+use ruma::{
+    events::{
+        room::message::{MessageType, Relation, RoomMessageEvent},
+        AnyMessageLikeEvent, AnyTimelineEvent,
+    },
+    html::RemoveReplyFallback,
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, UserId,
+};
+
+use crate::DEFAULT_SANITIZER_MODE;
+
+/// A previous version of an edited message.
+#[derive(Clone, Debug)]
+pub struct EditHistoryItem {
+    /// The ID of the edit event.
+    pub event_id: OwnedEventId,
+    /// The sender of the edit.
+    pub sender: OwnedUserId,
+    /// The time at which the edit was made.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+    /// The new content of the message set by the edit.
+    pub content: MessageType,
+}
+
+impl EditHistoryItem {
+    /// Construct an `EditHistoryItem` from an `m.replace` relation of the
+    /// given event.
+    ///
+    /// Returns `None` if the event is not a valid edit of this event, for
+    /// example if it is not a message or if it couldn't be decrypted.
+    pub(super) fn from_event(event: &TimelineEvent, original_sender: &UserId) -> Option<Self> {
+        let AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::RoomMessage(
+            RoomMessageEvent::Original(event),
+        )) = event.event.deserialize().ok()?
+        else {
+            return None;
+        };
+
+        // Only the sender of a message can edit it.
+        if event.sender != original_sender {
+            return None;
+        }
+
+        let Some(Relation::Replacement(replacement)) = event.content.relates_to else {
+            return None;
+        };
+
+        let mut content = replacement.new_content.msgtype;
+        content.sanitize(DEFAULT_SANITIZER_MODE, RemoveReplyFallback::No);
+
+        Some(Self {
+            event_id: event.event_id,
+            sender: event.sender,
+            timestamp: event.origin_server_ts,
+            content,
+        })
+    }
+}
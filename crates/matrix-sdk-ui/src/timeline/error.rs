@@ -55,6 +55,18 @@ pub enum Error {
     /// Could not get user
     #[error("User ID is not available")]
     UserIdNotAvailable,
+
+    /// The location is not a valid `geo:` URI.
+    #[error("Invalid geo URI")]
+    InvalidGeoUri,
+
+    /// The media of a forwarded event could not be re-uploaded.
+    #[error("Failed forwarding media")]
+    FailedForwardingMedia,
+
+    /// The edits of an event could not be fetched.
+    #[error("Failed fetching edit history")]
+    FailedFetchingEditHistory,
 }
 
 #[derive(Error)]
@@ -0,0 +1,195 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use eyeball_im::VectorDiff;
+use matrix_sdk::{
+    deserialized_responses::SyncTimelineEvent,
+    event_cache::{RoomEventCache, RoomEventCacheUpdate},
+};
+use ruma::OwnedEventId;
+use tokio::sync::{
+    broadcast::{self, error::TryRecvError},
+    Mutex,
+};
+use tracing::{trace, warn};
+
+use super::inner::TimelineInner;
+
+/// A change of the events of the event cache, to apply to the timeline.
+#[derive(Debug)]
+pub(super) enum CachedEventsUpdate {
+    /// The event was added at the end of the cache.
+    PushBack(SyncTimelineEvent),
+    /// The event was inserted after the event with the given ID, or at the
+    /// start of the cache if it is `None`.
+    InsertAfter { prev_event_id: Option<OwnedEventId>, event: SyncTimelineEvent },
+    /// The event with the given ID was removed from the cache.
+    Remove(OwnedEventId),
+    /// All the events were removed from the cache.
+    Clear,
+}
+
+/// The event cache of a room, used as the source of the events of a
+/// [`Timeline`](super::Timeline).
+///
+/// The timeline is a view over the events of the cache: the events received
+/// from sync and from backwards paginations are only added to the timeline
+/// through the updates of the cache.
+#[derive(Debug)]
+pub(super) struct EventCacheSource {
+    cache: RoomEventCache,
+    subscription: Mutex<CacheSubscription>,
+}
+
+#[derive(Debug)]
+struct CacheSubscription {
+    /// The updates of the cache that were not applied to the timeline yet.
+    updates: broadcast::Receiver<RoomEventCacheUpdate>,
+    /// The IDs of the events of the cache, as of the last applied update.
+    ///
+    /// Used to find the events that the positions of the updates refer to.
+    event_ids: Vec<Option<OwnedEventId>>,
+}
+
+impl CacheSubscription {
+    async fn new(cache: &RoomEventCache) -> (Self, Vec<SyncTimelineEvent>) {
+        let (events, updates) = cache.subscribe().await;
+        let event_ids = events.iter().map(|event| event.event_id()).collect();
+        (Self { updates, event_ids }, events)
+    }
+
+    /// Convert a change of the cache to a change of the timeline.
+    ///
+    /// Returns `Ok(None)` if the change doesn't affect the timeline, and
+    /// `Err(())` if it can't be applied to the timeline.
+    fn map_diff(
+        &mut self,
+        diff: VectorDiff<SyncTimelineEvent>,
+    ) -> Result<Option<CachedEventsUpdate>, ()> {
+        let update = match diff {
+            VectorDiff::PushBack { value } => {
+                self.event_ids.push(value.event_id());
+                CachedEventsUpdate::PushBack(value)
+            }
+            VectorDiff::Insert { index, value } if index <= self.event_ids.len() => {
+                // Events without an ID are never in the timeline, so they can't
+                // be used as a reference.
+                let prev_event_id = self.event_ids[..index].iter().flatten().next_back().cloned();
+                self.event_ids.insert(index, value.event_id());
+                CachedEventsUpdate::InsertAfter { prev_event_id, event: value }
+            }
+            VectorDiff::Remove { index } if index < self.event_ids.len() => {
+                match self.event_ids.remove(index) {
+                    Some(event_id) => CachedEventsUpdate::Remove(event_id),
+                    None => return Ok(None),
+                }
+            }
+            VectorDiff::Clear => {
+                self.event_ids.clear();
+                CachedEventsUpdate::Clear
+            }
+            _ => return Err(()),
+        };
+
+        Ok(Some(update))
+    }
+}
+
+impl EventCacheSource {
+    /// Subscribe to the updates of the given cache.
+    ///
+    /// Returns the source and the events that are currently in the cache.
+    pub(super) async fn new(cache: RoomEventCache) -> (Self, Vec<SyncTimelineEvent>) {
+        let (subscription, events) = CacheSubscription::new(&cache).await;
+        (Self { cache, subscription: Mutex::new(subscription) }, events)
+    }
+
+    /// The event cache of the room.
+    pub(super) fn cache(&self) -> &RoomEventCache {
+        &self.cache
+    }
+
+    /// Get a receiver that is notified of the updates of the cache, to know
+    /// when to call [`EventCacheSource::catch_up()`].
+    pub(super) async fn subscribe_to_updates(&self) -> broadcast::Receiver<RoomEventCacheUpdate> {
+        self.subscription.lock().await.updates.resubscribe()
+    }
+
+    /// Apply the updates of the cache that were received since the last call
+    /// to the timeline.
+    pub(super) async fn catch_up(&self, inner: &TimelineInner) {
+        let mut subscription = self.subscription.lock().await;
+
+        loop {
+            match subscription.updates.try_recv() {
+                Ok(RoomEventCacheUpdate::UpdateEvents { diffs }) => {
+                    self.handle_diffs(inner, diffs, &mut subscription).await;
+                }
+                Err(TryRecvError::Lagged(num_skipped)) => {
+                    warn!(num_skipped, "Lagged behind the event cache, resetting timeline");
+                    self.reset_with_subscription(inner, &mut subscription).await;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Replace the events of the timeline with the events of the cache.
+    pub(super) async fn reset(&self, inner: &TimelineInner) {
+        let mut subscription = self.subscription.lock().await;
+        self.reset_with_subscription(inner, &mut subscription).await;
+    }
+
+    async fn reset_with_subscription(
+        &self,
+        inner: &TimelineInner,
+        subscription: &mut CacheSubscription,
+    ) {
+        // The pending updates are already included in the events, so they are
+        // dropped with the previous receiver.
+        let (new_subscription, events) = CacheSubscription::new(&self.cache).await;
+        *subscription = new_subscription;
+
+        inner.clear().await;
+        inner.add_initial_events(events.into(), None).await;
+    }
+
+    async fn handle_diffs(
+        &self,
+        inner: &TimelineInner,
+        diffs: Vec<VectorDiff<SyncTimelineEvent>>,
+        subscription: &mut CacheSubscription,
+    ) {
+        // The positions of the diffs are translated to the IDs of the events
+        // around them, because the timeline doesn't have an item for every
+        // event of the cache.
+        let mut updates = Vec::with_capacity(diffs.len());
+        for diff in diffs {
+            match subscription.map_diff(diff) {
+                Ok(Some(update)) => updates.push(update),
+                Ok(None) => {}
+                Err(()) => {
+                    trace!("Unexpected change of the event cache, resetting timeline");
+                    self.reset_with_subscription(inner, subscription).await;
+                    return;
+                }
+            }
+        }
+
+        if !inner.handle_cached_events_updates(updates).await {
+            warn!("Failed to apply the changes of the event cache, resetting timeline");
+            self.reset_with_subscription(inner, subscription).await;
+        }
+    }
+}
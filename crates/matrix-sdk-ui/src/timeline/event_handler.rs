@@ -50,9 +50,9 @@ use tracing::{debug, error, field::debug, info, instrument, trace, warn};
 
 use super::{
     event_item::{
-        AnyOtherFullStateEventContent, BundledReactions, EventItemIdentifier, EventSendState,
-        EventTimelineItemKind, LocalEventTimelineItem, Profile, RemoteEventOrigin,
-        RemoteEventTimelineItem,
+        AnyOtherFullStateEventContent, BundledReactions, CustomEventContent, CustomEventError,
+        EventItemIdentifier, EventSendState, EventTimelineItemKind, LocalEventTimelineItem,
+        Profile, RemoteEventOrigin, RemoteEventTimelineItem,
     },
     inner::{TimelineInnerMetadata, TimelineInnerStateTransaction},
     item::timeline_item,
@@ -124,6 +124,7 @@ pub(super) enum TimelineEventKind {
         state_key: String,
         error: Arc<serde_json::Error>,
     },
+    Custom(CustomEventContent),
 }
 
 impl TimelineEventKind {
@@ -170,6 +171,20 @@ impl TimelineEventKind {
         }
     }
 
+    /// Creates a new `TimelineEventKind` for an event whose type was
+    /// registered.
+    pub(super) fn custom(result: Result<CustomEventContent, CustomEventError>) -> Self {
+        match result {
+            Ok(content) => Self::Custom(content),
+            Err(CustomEventError::MessageLike { event_type, error }) => {
+                Self::FailedToParseMessageLike { event_type, error }
+            }
+            Err(CustomEventError::State { event_type, state_key, error }) => {
+                Self::FailedToParseState { event_type, state_key, error }
+            }
+        }
+    }
+
     pub(super) fn failed_to_parse(
         event: SyncTimelineEventWithoutContent,
         error: serde_json::Error,
@@ -203,6 +218,9 @@ pub(super) enum TimelineItemPosition {
         /// Whether this event is coming from a local cache.
         from_cache: bool,
     },
+    /// The event is inserted at the given index of the list of all the
+    /// events, by the event cache.
+    Insert(usize),
     #[cfg(feature = "e2e-encryption")]
     Update(usize),
 }
@@ -354,6 +372,10 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                     TimelineItemContent::FailedToParseState { event_type, state_key, error },
                 );
             }
+
+            TimelineEventKind::Custom(content) => {
+                self.add(should_add, TimelineItemContent::Custom(content));
+            }
         }
 
         if !self.result.item_added {
@@ -559,6 +581,7 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                 Some(event_item.with_content(
                     TimelineItemContent::Poll(poll_state.add_response(
                         &self.ctx.sender,
+                        self.ctx.is_own_event,
                         self.ctx.timestamp,
                         &c,
                     )),
@@ -569,6 +592,7 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                 self.meta.poll_pending_events.add_response(
                     &c.relates_to.event_id,
                     &self.ctx.sender,
+                    self.ctx.is_own_event,
                     self.ctx.timestamp,
                     &c,
                 );
@@ -686,7 +710,7 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
 
         self.items.for_each(|mut entry| {
             let Some(event_item) = entry.as_event() else { return };
-            let Some(message) = event_item.content.as_message() else { return };
+            let Some(message) = event_item.content.room_message() else { return };
             let Some(in_reply_to) = message.in_reply_to() else { return };
             let TimelineDetails::Ready(replied_to_event) = &in_reply_to.event else { return };
             if redacts == in_reply_to.event_id {
@@ -695,7 +719,8 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                     event_id: in_reply_to.event_id.clone(),
                     event: TimelineDetails::Ready(Box::new(replied_to_event)),
                 };
-                let content = TimelineItemContent::Message(message.with_in_reply_to(in_reply_to));
+                let content =
+                    TimelineItemContent::from_message(message.with_in_reply_to(in_reply_to));
                 let new_item = entry.with_kind(event_item.with_content(content, None));
 
                 ObservableVectorTransactionEntry::set(&mut entry, new_item);
@@ -776,7 +801,9 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                 }
 
                 let origin = match position {
-                    TimelineItemPosition::Start => RemoteEventOrigin::Pagination,
+                    TimelineItemPosition::Start | TimelineItemPosition::Insert(_) => {
+                        RemoteEventOrigin::Pagination
+                    }
                     // We only paginate backwards for now, so End only happens for syncs
                     TimelineItemPosition::End { from_cache: true } => RemoteEventOrigin::Cache,
                     TimelineItemPosition::End { from_cache: false } => RemoteEventOrigin::Sync,
@@ -846,26 +873,63 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
                 }
 
                 trace!("Adding new remote timeline item at the start");
+                self.push_front_remote_item(item);
+            }
 
-                // Check if the earliest day divider has the same date as this event.
-                if let Some(VirtualTimelineItem::DayDivider(divider_ts)) =
-                    self.items.front().and_then(|item| item.as_virtual())
-                {
-                    let divider_ts = *divider_ts;
-                    if let Some(day_divider_item) =
-                        self.meta.maybe_create_day_divider_from_timestamps(divider_ts, timestamp)
-                    {
-                        self.items.push_front(day_divider_item);
+            Flow::Remote {
+                position: TimelineItemPosition::Insert(event_index), event_id, ..
+            } => {
+                if rfind_event_by_id(self.items, event_id).is_some() {
+                    trace!("Skipping inserted event that has already been seen");
+                    return;
+                }
+
+                // The item goes after the item of the closest visible event
+                // that precedes it.
+                let prev_item = self
+                    .meta
+                    .all_events
+                    .range(..*event_index)
+                    .rev()
+                    .filter(|meta| meta.visible)
+                    .find_map(|meta| rfind_event_by_id(self.items, &meta.event_id))
+                    .map(|(idx, prev_item)| (idx, prev_item.timestamp()));
+
+                if let Some((prev_idx, prev_ts)) = prev_item {
+                    trace!(prev_idx, "Inserting new remote timeline item");
+
+                    // The read marker stays right after the fully-read event.
+                    let mut insert_idx = prev_idx + 1;
+                    if self.items.get(insert_idx).is_some_and(|item| item.is_read_marker()) {
+                        insert_idx += 1;
                     }
+
+                    if timestamp_to_date(prev_ts) != timestamp_to_date(timestamp) {
+                        // Reuse the day divider of the next item if it is on the
+                        // same day.
+                        let next_day_divider_matches = matches!(
+                            self.items.get(insert_idx).and_then(|item| item.as_virtual()),
+                            Some(VirtualTimelineItem::DayDivider(divider_ts))
+                                if timestamp_to_date(*divider_ts) == timestamp_to_date(timestamp)
+                        );
+
+                        if !next_day_divider_matches {
+                            trace!("Adding day divider (inserted)");
+                            let day_divider = self
+                                .meta
+                                .new_timeline_item(VirtualTimelineItem::DayDivider(timestamp));
+                            self.items.insert(insert_idx, day_divider);
+                        }
+
+                        insert_idx += 1;
+                    }
+
+                    let item = self.meta.new_timeline_item(item);
+                    self.items.insert(insert_idx, item);
                 } else {
-                    // The list must always start with a day divider.
-                    let day_divider =
-                        self.meta.new_timeline_item(VirtualTimelineItem::DayDivider(timestamp));
-                    self.items.push_front(day_divider);
+                    trace!("Adding new remote timeline item at the start");
+                    self.push_front_remote_item(item);
                 }
-
-                let item = self.meta.new_timeline_item(item);
-                self.items.insert(1, item);
             }
 
             Flow::Remote {
@@ -1038,6 +1102,32 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
         }
     }
 
+    /// Add a remote item at the start of the timeline, after the day divider
+    /// of its date.
+    fn push_front_remote_item(&mut self, item: EventTimelineItem) {
+        let timestamp = item.timestamp();
+
+        // Check if the earliest day divider has the same date as this event.
+        if let Some(VirtualTimelineItem::DayDivider(divider_ts)) =
+            self.items.front().and_then(|item| item.as_virtual())
+        {
+            let divider_ts = *divider_ts;
+            if let Some(day_divider_item) =
+                self.meta.maybe_create_day_divider_from_timestamps(divider_ts, timestamp)
+            {
+                self.items.push_front(day_divider_item);
+            }
+        } else {
+            // The list must always start with a day divider.
+            let day_divider =
+                self.meta.new_timeline_item(VirtualTimelineItem::DayDivider(timestamp));
+            self.items.push_front(day_divider);
+        }
+
+        let item = self.meta.new_timeline_item(item);
+        self.items.insert(1, item);
+    }
+
     fn pending_reactions(&mut self) -> Option<BundledReactions> {
         match &self.ctx.flow {
             Flow::Local { .. } => None,
@@ -1074,8 +1164,12 @@ impl<'a, 'o> TimelineEventHandler<'a, 'o> {
 /// `old_item` *should* always be a local echo usually, but with the sliding
 /// sync proxy, we often re-receive remote events that aren't remote echoes.
 fn transfer_details(item: &mut EventTimelineItem, old_item: &EventTimelineItem) {
-    let TimelineItemContent::Message(msg) = &mut item.content else { return };
-    let TimelineItemContent::Message(old_msg) = &old_item.content else { return };
+    let msg = match &mut item.content {
+        TimelineItemContent::Message(msg) => msg,
+        TimelineItemContent::Voice(voice) => &mut voice.message,
+        _ => return,
+    };
+    let Some(old_msg) = old_item.content.room_message() else { return };
 
     let Some(in_reply_to) = &mut msg.in_reply_to else { return };
     let Some(old_in_reply_to) = &old_msg.in_reply_to else { return };
@@ -0,0 +1,252 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{any::Any, collections::BTreeMap, fmt, sync::Arc};
+
+use ruma::{
+    events::{AnySyncTimelineEvent, MessageLikeEventType, StateEventType},
+    serde::Raw,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+/// The content of a custom event whose type was registered with
+/// [`TimelineBuilder::register_message_like_event()`] or
+/// [`TimelineBuilder::register_state_event()`].
+///
+/// [`TimelineBuilder::register_message_like_event()`]: crate::timeline::TimelineBuilder::register_message_like_event
+/// [`TimelineBuilder::register_state_event()`]: crate::timeline::TimelineBuilder::register_state_event
+#[derive(Clone)]
+pub struct CustomEventContent {
+    event_type: String,
+    state_key: Option<String>,
+    content: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomEventContent {
+    /// The type of the event.
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    /// The state key of the event, if it is a state event.
+    pub fn state_key(&self) -> Option<&str> {
+        self.state_key.as_deref()
+    }
+
+    /// Get the content of the event, if it has the given type.
+    ///
+    /// The type is the one that was registered for the type of the event.
+    pub fn downcast_ref<C: 'static>(&self) -> Option<&C> {
+        self.content.downcast_ref()
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for CustomEventContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomEventContent")
+            .field("event_type", &self.event_type)
+            .field("state_key", &self.state_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The kind of a registered custom event.
+#[derive(Clone, Copy, Debug)]
+enum CustomEventKind {
+    MessageLike,
+    State,
+}
+
+type DeserializeContentFn =
+    fn(&Raw<AnySyncTimelineEvent>) -> serde_json::Result<Arc<dyn Any + Send + Sync>>;
+
+/// The custom events that are deserialized into their registered type by the
+/// timeline.
+#[derive(Clone, Default)]
+pub(in crate::timeline) struct CustomEventRegistry {
+    events: BTreeMap<String, (CustomEventKind, DeserializeContentFn)>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for CustomEventRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.events.keys()).finish()
+    }
+}
+
+impl CustomEventRegistry {
+    pub(in crate::timeline) fn register_message_like<C>(&mut self, event_type: &str)
+    where
+        C: DeserializeOwned + Send + Sync + 'static,
+    {
+        let deserialize_content = deserialize_content::<C> as DeserializeContentFn;
+        self.events
+            .insert(event_type.to_owned(), (CustomEventKind::MessageLike, deserialize_content));
+    }
+
+    pub(in crate::timeline) fn register_state<C>(&mut self, event_type: &str)
+    where
+        C: DeserializeOwned + Send + Sync + 'static,
+    {
+        let deserialize_content = deserialize_content::<C> as DeserializeContentFn;
+        self.events.insert(event_type.to_owned(), (CustomEventKind::State, deserialize_content));
+    }
+
+    /// Deserialize the given event if its type was registered.
+    ///
+    /// Returns `None` if the type of the event was not registered, if it
+    /// doesn't have the registered kind or if it is redacted, in which case it
+    /// should be handled like any other event.
+    pub(in crate::timeline) fn deserialize(
+        &self,
+        raw: &Raw<AnySyncTimelineEvent>,
+    ) -> Option<Result<CustomEventContent, CustomEventError>> {
+        let parts = raw.deserialize_as::<CustomEventParts>().ok()?;
+        let (kind, deserialize_content) = self.events.get(&parts.event_type)?;
+
+        if parts.unsigned.redacted_because.is_some() {
+            return None;
+        }
+
+        let state_key = match (kind, parts.state_key) {
+            (CustomEventKind::MessageLike, None) => None,
+            (CustomEventKind::State, Some(state_key)) => Some(state_key),
+            _ => return None,
+        };
+
+        Some(match deserialize_content(raw) {
+            Ok(content) => {
+                Ok(CustomEventContent { event_type: parts.event_type, state_key, content })
+            }
+            Err(error) => Err(match state_key {
+                None => CustomEventError::MessageLike {
+                    event_type: parts.event_type.into(),
+                    error: Arc::new(error),
+                },
+                Some(state_key) => CustomEventError::State {
+                    event_type: parts.event_type.into(),
+                    state_key,
+                    error: Arc::new(error),
+                },
+            }),
+        })
+    }
+}
+
+/// A registered custom event that failed to deserialize.
+pub(in crate::timeline) enum CustomEventError {
+    MessageLike { event_type: MessageLikeEventType, error: Arc<serde_json::Error> },
+    State { event_type: StateEventType, state_key: String, error: Arc<serde_json::Error> },
+}
+
+fn deserialize_content<C>(
+    raw: &Raw<AnySyncTimelineEvent>,
+) -> serde_json::Result<Arc<dyn Any + Send + Sync>>
+where
+    C: DeserializeOwned + Send + Sync + 'static,
+{
+    #[derive(Deserialize)]
+    struct ContentParts<C> {
+        content: C,
+    }
+
+    let parts: ContentParts<C> = raw.deserialize_as()?;
+    Ok(Arc::new(parts.content))
+}
+
+/// The parts of an event that are needed to know if it is a registered custom
+/// event.
+#[derive(Deserialize)]
+struct CustomEventParts {
+    #[serde(rename = "type")]
+    event_type: String,
+    state_key: Option<String>,
+    #[serde(default)]
+    unsigned: UnsignedParts,
+}
+
+#[derive(Default, Deserialize)]
+struct UnsignedParts {
+    redacted_because: Option<serde::de::IgnoredAny>,
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::serde::Raw;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::CustomEventRegistry;
+
+    #[derive(Deserialize)]
+    struct PingEventContent {
+        count: u32,
+    }
+
+    #[test]
+    fn test_deserialize_registered_event() {
+        let mut registry = CustomEventRegistry::default();
+        registry.register_message_like::<PingEventContent>("org.example.ping");
+
+        let raw = Raw::new(&json!({
+            "type": "org.example.ping",
+            "event_id": "$ping",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 1,
+            "content": { "count": 3 },
+        }))
+        .unwrap()
+        .cast();
+        let content = registry.deserialize(&raw).unwrap().ok().unwrap();
+        assert_eq!(content.event_type(), "org.example.ping");
+        assert_eq!(content.state_key(), None);
+        assert_eq!(content.downcast_ref::<PingEventContent>().unwrap().count, 3);
+
+        let invalid = Raw::new(&json!({
+            "type": "org.example.ping",
+            "event_id": "$ping",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 1,
+            "content": { "count": "three" },
+        }))
+        .unwrap()
+        .cast();
+        assert!(registry.deserialize(&invalid).unwrap().is_err());
+
+        let redacted = Raw::new(&json!({
+            "type": "org.example.ping",
+            "event_id": "$ping",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 1,
+            "content": {},
+            "unsigned": { "redacted_because": {} },
+        }))
+        .unwrap()
+        .cast();
+        assert!(registry.deserialize(&redacted).is_none());
+
+        let state = Raw::new(&json!({
+            "type": "org.example.ping",
+            "event_id": "$ping",
+            "sender": "@alice:localhost",
+            "origin_server_ts": 1,
+            "state_key": "",
+            "content": { "count": 3 },
+        }))
+        .unwrap()
+        .cast();
+        assert!(registry.deserialize(&state).is_none());
+    }
+}
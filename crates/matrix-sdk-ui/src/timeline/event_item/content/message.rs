@@ -14,8 +14,9 @@
 
 //! Timeline item content bits for `m.room.message` events.
 
-use std::{fmt, sync::Arc};
+use std::{fmt, sync::Arc, time::Duration};
 
+use as_variant::as_variant;
 use imbl::{vector, Vector};
 use matrix_sdk::deserialized_responses::TimelineEvent;
 use ruma::{
@@ -25,8 +26,8 @@ use ruma::{
         room::{
             message,
             message::{
-                MessageType, Relation, RoomMessageEventContent,
-                RoomMessageEventContentWithoutRelation, SyncRoomMessageEvent,
+                AudioMessageEventContent, MessageType, Relation, RoomMessageEventContent,
+                RoomMessageEventContentWithoutRelation, SyncRoomMessageEvent, UnstableAmplitude,
             },
         },
         AnyMessageLikeEventContent, AnySyncMessageLikeEvent, AnyTimelineEvent,
@@ -42,7 +43,7 @@ use crate::{
     timeline::{
         event_item::{EventTimelineItem, Profile, TimelineDetails},
         traits::RoomDataProvider,
-        Error as TimelineError, TimelineItem,
+        Error as TimelineError, Location, TimelineItem,
     },
     DEFAULT_SANITIZER_MODE,
 };
@@ -131,6 +132,12 @@ impl Message {
         self.msgtype.body()
     }
 
+    /// Get the location shared by this message, if it is an `m.location`
+    /// message with a valid `geo:` URI.
+    pub fn location(&self) -> Option<Location> {
+        as_variant!(&self.msgtype, MessageType::Location).and_then(Location::from_content)
+    }
+
     /// Get the event this message is replying to, if any.
     pub fn in_reply_to(&self) -> Option<&InReplyToDetails> {
         self.in_reply_to.as_ref()
@@ -160,6 +167,11 @@ impl Message {
     pub(in crate::timeline) fn with_in_reply_to(&self, in_reply_to: InReplyToDetails) -> Self {
         Self { in_reply_to: Some(in_reply_to), ..self.clone() }
     }
+
+    /// Whether this message is a voice message.
+    pub(in crate::timeline) fn is_voice(&self) -> bool {
+        matches!(&self.msgtype, MessageType::Audio(content) if content.voice.is_some())
+    }
 }
 
 impl From<Message> for RoomMessageEventContent {
@@ -170,6 +182,45 @@ impl From<Message> for RoomMessageEventContent {
     }
 }
 
+/// An `m.room.message` event that is a voice message, as defined in
+/// [MSC3245].
+///
+/// [MSC3245]: https://github.com/matrix-org/matrix-spec-proposals/pull/3245
+#[derive(Clone, Debug)]
+pub struct VoiceMessage {
+    pub(in crate::timeline) message: Message,
+}
+
+impl VoiceMessage {
+    /// Get the message of this voice message, with its relations.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Get the audio content of this voice message.
+    pub fn content(&self) -> &AudioMessageEventContent {
+        as_variant!(&self.message.msgtype, MessageType::Audio)
+            .expect("voice messages always have an audio msgtype")
+    }
+
+    /// Get the duration of the voice message, if it is known.
+    pub fn duration(&self) -> Option<Duration> {
+        let content = self.content();
+        content
+            .audio
+            .as_ref()
+            .map(|audio| audio.duration)
+            .or_else(|| content.info.as_ref()?.duration)
+    }
+
+    /// Get the waveform of the voice message.
+    ///
+    /// It is empty if the sender didn't provide one.
+    pub fn waveform(&self) -> &[UnstableAmplitude] {
+        self.content().audio.as_ref().map(|audio| audio.waveform.as_slice()).unwrap_or_default()
+    }
+}
+
 /// Turn a pair of thread root ID and in-reply-to ID as stored in [`Message`]
 /// back into a [`Relation`].
 ///
@@ -291,8 +342,11 @@ impl RepliedToEvent {
             return Err(TimelineError::UnsupportedEvent);
         };
 
-        let content =
-            TimelineItemContent::Message(Message::from_event(c, event.relations(), &vector![]));
+        let content = TimelineItemContent::from_message(Message::from_event(
+            c,
+            event.relations(),
+            &vector![],
+        ));
         let sender = event.sender().to_owned();
         let sender_profile = TimelineDetails::from_initial_value(
             room_data_provider.profile_from_user_id(&sender).await,
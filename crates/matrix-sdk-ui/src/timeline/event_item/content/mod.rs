@@ -55,9 +55,14 @@ use tracing::warn;
 
 use crate::timeline::{polls::PollState, TimelineItem};
 
+mod custom;
 mod message;
 
-pub use self::message::{InReplyToDetails, Message, RepliedToEvent};
+pub(in crate::timeline) use self::custom::{CustomEventError, CustomEventRegistry};
+pub use self::{
+    custom::CustomEventContent,
+    message::{InReplyToDetails, Message, RepliedToEvent, VoiceMessage},
+};
 
 /// The content of an [`EventTimelineItem`][super::EventTimelineItem].
 #[derive(Clone, Debug)]
@@ -106,6 +111,17 @@ pub enum TimelineItemContent {
 
     /// An `m.poll.start` event.
     Poll(PollState),
+
+    /// An `m.room.message` event that is a voice message.
+    Voice(VoiceMessage),
+
+    /// An event whose type was registered with
+    /// [`TimelineBuilder::register_message_like_event()`] or
+    /// [`TimelineBuilder::register_state_event()`].
+    ///
+    /// [`TimelineBuilder::register_message_like_event()`]: crate::timeline::TimelineBuilder::register_message_like_event
+    /// [`TimelineBuilder::register_state_event()`]: crate::timeline::TimelineBuilder::register_state_event
+    Custom(CustomEventContent),
 }
 
 impl TimelineItemContent {
@@ -164,7 +180,7 @@ impl TimelineItemContent {
                 // `Message::from_event` marks the original event as `Unavailable` if it can't
                 // be found inside the timeline_items.
                 let timeline_items = Vector::new();
-                TimelineItemContent::Message(Message::from_event(
+                TimelineItemContent::from_message(Message::from_event(
                     event_content,
                     relations,
                     &timeline_items,
@@ -195,6 +211,12 @@ impl TimelineItemContent {
         as_variant!(self, Self::Message)
     }
 
+    /// If `self` is of the [`Voice`][Self::Voice] variant, return the inner
+    /// [`VoiceMessage`].
+    pub fn as_voice(&self) -> Option<&VoiceMessage> {
+        as_variant!(self, Self::Voice)
+    }
+
     /// If `self` is of the [`UnableToDecrypt`][Self::UnableToDecrypt] variant,
     /// return the inner [`EncryptedMessage`].
     pub fn as_unable_to_decrypt(&self) -> Option<&EncryptedMessage> {
@@ -212,7 +234,27 @@ impl TimelineItemContent {
         relations: BundledMessageLikeRelations<AnySyncMessageLikeEvent>,
         timeline_items: &Vector<Arc<TimelineItem>>,
     ) -> Self {
-        Self::Message(Message::from_event(c, relations, timeline_items))
+        Self::from_message(Message::from_event(c, relations, timeline_items))
+    }
+
+    /// Wrap the given message in the [`Voice`][Self::Voice] variant if it is a
+    /// voice message, or in the [`Message`][Self::Message] variant otherwise.
+    pub(in crate::timeline) fn from_message(message: Message) -> Self {
+        if message.is_voice() {
+            Self::Voice(VoiceMessage { message })
+        } else {
+            Self::Message(message)
+        }
+    }
+
+    /// Get the message of the [`Message`][Self::Message] or
+    /// [`Voice`][Self::Voice] variants.
+    pub(in crate::timeline) fn room_message(&self) -> Option<&Message> {
+        match self {
+            Self::Message(message) => Some(message),
+            Self::Voice(voice) => Some(&voice.message),
+            _ => None,
+        }
     }
 
     #[cfg(not(tarpaulin_include))] // debug-logging functionality
@@ -228,6 +270,8 @@ impl TimelineItemContent {
             TimelineItemContent::FailedToParseMessageLike { .. }
             | TimelineItemContent::FailedToParseState { .. } => "an event that couldn't be parsed",
             TimelineItemContent::Poll(_) => "a poll",
+            TimelineItemContent::Voice(_) => "a voice message",
+            TimelineItemContent::Custom(_) => "a custom event",
         }
     }
 
@@ -306,7 +350,11 @@ impl TimelineItemContent {
             | Self::RedactedMessage
             | Self::Sticker(_)
             | Self::Poll(_)
+            | Self::Voice(_)
             | Self::UnableToDecrypt(_) => Self::RedactedMessage,
+            // The registered type of the content is unknown here, so it can't be
+            // redacted in place.
+            Self::Custom(_) => Self::RedactedMessage,
             Self::MembershipChange(ev) => Self::MembershipChange(ev.redact(room_version)),
             Self::ProfileChange(ev) => Self::ProfileChange(ev.redact()),
             Self::OtherState(ev) => Self::OtherState(ev.redact(room_version)),
@@ -33,14 +33,15 @@ mod remote;
 
 pub use self::{
     content::{
-        AnyOtherFullStateEventContent, EncryptedMessage, InReplyToDetails, MemberProfileChange,
-        MembershipChange, Message, OtherState, RepliedToEvent, RoomMembershipChange, Sticker,
-        TimelineItemContent,
+        AnyOtherFullStateEventContent, CustomEventContent, EncryptedMessage, InReplyToDetails,
+        MemberProfileChange, MembershipChange, Message, OtherState, RepliedToEvent,
+        RoomMembershipChange, Sticker, TimelineItemContent, VoiceMessage,
     },
     local::EventSendState,
     reactions::{BundledReactions, ReactionGroup},
 };
 pub(super) use self::{
+    content::{CustomEventError, CustomEventRegistry},
     local::LocalEventTimelineItem,
     remote::{RemoteEventOrigin, RemoteEventTimelineItem},
 };
@@ -324,7 +325,7 @@ impl EventTimelineItem {
         // This must be in sync with the early returns of `Timeline::send_reply`
         if self.event_id().is_none() {
             false
-        } else if let TimelineItemContent::Message(_) = self.content() {
+        } else if self.content().room_message().is_some() {
             true
         } else {
             self.latest_json().is_some()
@@ -407,6 +408,13 @@ impl EventTimelineItem {
         new
     }
 
+    /// Clone the current event item, and update its content, for the tests
+    /// outside of the timeline.
+    #[cfg(test)]
+    pub(crate) fn with_test_content(&self, new_content: TimelineItemContent) -> Self {
+        self.with_content(new_content, None)
+    }
+
     /// Clone the current event item, and update its `sender_profile`.
     pub(super) fn with_sender_profile(&self, sender_profile: TimelineDetails<Profile>) -> Self {
         Self { sender_profile, ..self.clone() }
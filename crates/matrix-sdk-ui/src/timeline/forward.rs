@@ -0,0 +1,169 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use matrix_sdk::{
+    media::{MediaFormat, MediaRequest},
+    room::Room,
+};
+use mime::Mime;
+use ruma::{
+    events::{
+        room::{
+            message::{MessageType, RoomMessageEventContent},
+            MediaSource, ThumbnailInfo,
+        },
+        sticker::StickerEventContent,
+        AnyMessageLikeEventContent,
+    },
+    html::RemoveReplyFallback,
+};
+
+use super::{Error, TimelineItemContent};
+use crate::DEFAULT_SANITIZER_MODE;
+
+/// Build the content of an event forwarding the given content to the given
+/// room.
+///
+/// The relations of the original event are not kept. Encrypted media is
+/// re-uploaded unencrypted if the target room is not encrypted, so its key is
+/// never sent in clear text.
+pub(super) async fn forwarded_content(
+    content: &TimelineItemContent,
+    target_room: &Room,
+) -> Result<AnyMessageLikeEventContent, Error> {
+    let message = match content {
+        TimelineItemContent::Message(message) => message,
+        TimelineItemContent::Voice(voice) => voice.message(),
+        TimelineItemContent::Sticker(sticker) => {
+            let content = sticker.content();
+            return Ok(StickerEventContent::new(
+                content.body.clone(),
+                content.info.clone(),
+                content.url.clone(),
+            )
+            .into());
+        }
+        _ => return Err(Error::UnsupportedEvent),
+    };
+
+    let mut msgtype = message.msgtype().clone();
+
+    // The relation to the replied-to event is not kept, so its fallback would
+    // only look like a quote in the forwarded message.
+    if message.in_reply_to().is_some() {
+        msgtype.sanitize(DEFAULT_SANITIZER_MODE, RemoveReplyFallback::Yes);
+    }
+
+    if has_encrypted_media(&msgtype)
+        && !target_room.is_encrypted().await.map_err(|_| Error::FailedForwardingMedia)?
+    {
+        reupload_encrypted_media(target_room, &mut msgtype).await?;
+    }
+
+    Ok(RoomMessageEventContent::new(msgtype).into())
+}
+
+fn has_encrypted_media(msgtype: &MessageType) -> bool {
+    let is_encrypted =
+        |source: Option<&MediaSource>| matches!(source, Some(MediaSource::Encrypted(_)));
+
+    match msgtype {
+        MessageType::Audio(content) => is_encrypted(Some(&content.source)),
+        MessageType::File(content) => {
+            is_encrypted(Some(&content.source))
+                || is_encrypted(content.info.as_ref().and_then(|i| i.thumbnail_source.as_ref()))
+        }
+        MessageType::Image(content) => {
+            is_encrypted(Some(&content.source))
+                || is_encrypted(content.info.as_ref().and_then(|i| i.thumbnail_source.as_ref()))
+        }
+        MessageType::Video(content) => {
+            is_encrypted(Some(&content.source))
+                || is_encrypted(content.info.as_ref().and_then(|i| i.thumbnail_source.as_ref()))
+        }
+        _ => false,
+    }
+}
+
+async fn reupload_encrypted_media(room: &Room, msgtype: &mut MessageType) -> Result<(), Error> {
+    match msgtype {
+        MessageType::Audio(content) => {
+            let mimetype = content.info.as_ref().and_then(|info| info.mimetype.clone());
+            reupload_if_encrypted(room, &mut content.source, mimetype.as_deref()).await?;
+        }
+        MessageType::File(content) => {
+            let mimetype = content.info.as_ref().and_then(|info| info.mimetype.clone());
+            reupload_if_encrypted(room, &mut content.source, mimetype.as_deref()).await?;
+            if let Some(info) = &mut content.info {
+                reupload_thumbnail(room, &mut info.thumbnail_source, &info.thumbnail_info).await?;
+            }
+        }
+        MessageType::Image(content) => {
+            let mimetype = content.info.as_ref().and_then(|info| info.mimetype.clone());
+            reupload_if_encrypted(room, &mut content.source, mimetype.as_deref()).await?;
+            if let Some(info) = &mut content.info {
+                reupload_thumbnail(room, &mut info.thumbnail_source, &info.thumbnail_info).await?;
+            }
+        }
+        MessageType::Video(content) => {
+            let mimetype = content.info.as_ref().and_then(|info| info.mimetype.clone());
+            reupload_if_encrypted(room, &mut content.source, mimetype.as_deref()).await?;
+            if let Some(info) = &mut content.info {
+                reupload_thumbnail(room, &mut info.thumbnail_source, &info.thumbnail_info).await?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn reupload_thumbnail(
+    room: &Room,
+    source: &mut Option<MediaSource>,
+    info: &Option<Box<ThumbnailInfo>>,
+) -> Result<(), Error> {
+    if let Some(source) = source {
+        let mimetype = info.as_ref().and_then(|info| info.mimetype.as_deref());
+        reupload_if_encrypted(room, source, mimetype).await?;
+    }
+
+    Ok(())
+}
+
+/// Download and decrypt the given media if it is encrypted, and upload it
+/// again unencrypted.
+async fn reupload_if_encrypted(
+    room: &Room,
+    source: &mut MediaSource,
+    mimetype: Option<&str>,
+) -> Result<(), Error> {
+    let MediaSource::Encrypted(_) = source else {
+        return Ok(());
+    };
+
+    let media = room.client().media();
+    let request = MediaRequest { source: source.clone(), format: MediaFormat::File };
+    let data =
+        media.get_media_content(&request, true).await.map_err(|_| Error::FailedForwardingMedia)?;
+
+    let mime_type = mimetype
+        .and_then(|mimetype| mimetype.parse::<Mime>().ok())
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+    let response =
+        media.upload(&mime_type, data).await.map_err(|_| Error::FailedForwardingMedia)?;
+
+    *source = MediaSource::Plain(response.content_uri);
+    Ok(())
+}
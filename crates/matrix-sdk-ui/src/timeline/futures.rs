@@ -4,6 +4,8 @@ use eyeball::{SharedObservable, Subscriber};
 use matrix_sdk::{attachment::AttachmentConfig, TransmissionProgress};
 use matrix_sdk_base::boxed_into_future;
 use mime::Mime;
+use tokio::fs::File;
+use tokio_util::compat::TokioAsyncReadCompatExt;
 use tracing::{Instrument as _, Span};
 
 use super::{Error, Timeline};
@@ -54,11 +56,24 @@ impl<'a> IntoFuture for SendAttachment<'a> {
                 .ok_or(Error::InvalidAttachmentFileName)?
                 .to_str()
                 .expect("path was created from UTF-8 string, hence filename part is UTF-8 too");
-            let data = fs::read(&url).map_err(|_| Error::InvalidAttachmentData)?;
 
-            timeline
-                .room()
-                .send_attachment(body, &mime_type, data, config)
+            let request = if mime_type.type_() == mime::IMAGE {
+                // Images are loaded in memory to generate their thumbnail.
+                let data = fs::read(&url).map_err(|_| Error::InvalidAttachmentData)?;
+                timeline.room().send_attachment(body, &mime_type, data, config)
+            } else {
+                let file = File::open(&url).await.map_err(|_| Error::InvalidAttachmentData)?;
+                let size = file.metadata().await.map_err(|_| Error::InvalidAttachmentData)?.len();
+                timeline.room().send_attachment_stream(
+                    body,
+                    &mime_type,
+                    file.compat(),
+                    size,
+                    config,
+                )
+            };
+
+            request
                 .with_send_progress_observable(send_progress)
                 .await
                 .map_err(|_| Error::FailedSendingAttachment)?;
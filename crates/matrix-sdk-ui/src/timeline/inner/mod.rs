@@ -27,6 +27,7 @@ use matrix_sdk::crypto::OlmMachine;
 use matrix_sdk::{
     deserialized_responses::{SyncTimelineEvent, TimelineEvent},
     sync::JoinedRoom,
+    time::Clock,
     Error, Result, Room,
 };
 use matrix_sdk_base::sync::Timeline;
@@ -59,7 +60,8 @@ use tracing::{field, info_span, Instrument as _};
 #[cfg(feature = "e2e-encryption")]
 use super::traits::Decryptor;
 use super::{
-    event_item::EventItemIdentifier,
+    event_cache::CachedEventsUpdate,
+    event_item::{CustomEventRegistry, EventItemIdentifier},
     item::timeline_item,
     pagination::PaginationTokens,
     reactions::ReactionToggleResult,
@@ -110,6 +112,8 @@ pub(super) struct TimelineInnerSettings {
     pub(super) event_filter: Arc<TimelineEventFilterFn>,
     /// Are unparsable events added as timeline items of their own kind?
     pub(super) add_failed_to_parse: bool,
+    /// The custom events that are deserialized into their registered type.
+    pub(super) custom_events: CustomEventRegistry,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -118,6 +122,7 @@ impl fmt::Debug for TimelineInnerSettings {
         f.debug_struct("TimelineInnerSettings")
             .field("track_read_receipts", &self.track_read_receipts)
             .field("add_failed_to_parse", &self.add_failed_to_parse)
+            .field("custom_events", &self.custom_events)
             .finish_non_exhaustive()
     }
 }
@@ -128,6 +133,7 @@ impl Default for TimelineInnerSettings {
             track_read_receipts: false,
             event_filter: Arc::new(default_event_filter),
             add_failed_to_parse: true,
+            custom_events: CustomEventRegistry::default(),
         }
     }
 }
@@ -315,6 +321,7 @@ impl<P: RoomDataProvider> TimelineInner<P> {
                 state.handle_local_event(
                     sender,
                     sender_profile,
+                    self.room_data_provider.clock().timestamp(),
                     txn_id.clone(),
                     event_content.clone(),
                 );
@@ -334,6 +341,7 @@ impl<P: RoomDataProvider> TimelineInner<P> {
                 state.handle_local_redaction(
                     sender,
                     sender_profile,
+                    self.room_data_provider.clock().timestamp(),
                     TransactionId::new(),
                     to_redact,
                     no_reason.clone(),
@@ -407,7 +415,7 @@ impl<P: RoomDataProvider> TimelineInner<P> {
     }
 
     pub(super) async fn add_initial_events(
-        &mut self,
+        &self,
         events: Vector<SyncTimelineEvent>,
         back_pagination_token: Option<String>,
     ) {
@@ -456,8 +464,10 @@ impl<P: RoomDataProvider> TimelineInner<P> {
         let sender = self.room_data_provider.own_user_id().to_owned();
         let profile = self.room_data_provider.profile_from_user_id(&sender).await;
 
+        let timestamp = self.room_data_provider.clock().timestamp();
+
         let mut state = self.state.write().await;
-        state.handle_local_event(sender, profile, txn_id, content);
+        state.handle_local_event(sender, profile, timestamp, txn_id, content);
     }
 
     /// Handle the creation of a new local event.
@@ -471,8 +481,10 @@ impl<P: RoomDataProvider> TimelineInner<P> {
         let sender = self.room_data_provider.own_user_id().to_owned();
         let profile = self.room_data_provider.profile_from_user_id(&sender).await;
 
+        let timestamp = self.room_data_provider.clock().timestamp();
+
         let mut state = self.state.write().await;
-        state.handle_local_redaction(sender, profile, txn_id, to_redact, content);
+        state.handle_local_redaction(sender, profile, timestamp, txn_id, to_redact, content);
     }
 
     /// Update the send state of a local event represented by a transaction ID.
@@ -616,7 +628,8 @@ impl<P: RoomDataProvider> TimelineInner<P> {
                 // We're done, so also update the timeline
                 state.in_flight_reaction.remove(&annotation_key);
                 state.reaction_state.remove(&annotation_key);
-                state.update_timeline_reaction(user_id, annotation, result)?;
+                let timestamp = self.room_data_provider.clock().timestamp();
+                state.update_timeline_reaction(user_id, timestamp, annotation, result)?;
 
                 ReactionAction::None
             }
@@ -709,7 +722,7 @@ impl<P: RoomDataProvider> TimelineInner<P> {
 
         state
             .handle_back_paginated_events(
-                events,
+                events.into_iter().map(Into::into).collect(),
                 pagination_tokens.to,
                 &self.room_data_provider,
                 &self.settings,
@@ -718,6 +731,18 @@ impl<P: RoomDataProvider> TimelineInner<P> {
             .ok_or(HandleBackPaginatedEventsError::ResultOverflow)
     }
 
+    /// Apply the changes of the events of the event cache.
+    ///
+    /// Returns `false` if a change couldn't be applied, in which case the
+    /// timeline must be reset.
+    pub(super) async fn handle_cached_events_updates(
+        &self,
+        updates: Vec<CachedEventsUpdate>,
+    ) -> bool {
+        let mut state = self.state.write().await;
+        state.handle_cached_events_updates(updates, &self.room_data_provider, &self.settings).await
+    }
+
     pub(super) async fn set_fully_read_event(&self, fully_read_event_id: OwnedEventId) {
         self.state.write().await.set_fully_read_event(fully_read_event_id);
     }
@@ -976,7 +1001,7 @@ impl TimelineInner {
             .ok_or(super::Error::RemoteEventNotInTimeline)?;
         let remote_item = item.as_remote().ok_or(super::Error::RemoteEventNotInTimeline)?.clone();
 
-        let TimelineItemContent::Message(message) = item.content().clone() else {
+        let Some(message) = item.content().room_message().cloned() else {
             info!("Event is not a message");
             return Ok(());
         };
@@ -1012,7 +1037,7 @@ impl TimelineInner {
 
         // Check the state of the event again, it might have been redacted while
         // the request was in-flight.
-        let TimelineItemContent::Message(message) = item.content().clone() else {
+        let Some(message) = item.content().room_message().cloned() else {
             info!("Event is no longer a message (redacted?)");
             return Ok(());
         };
@@ -1024,7 +1049,7 @@ impl TimelineInner {
         trace!("Updating in-reply-to details");
         let internal_id = item.internal_id;
         let mut item = item.clone();
-        item.set_content(TimelineItemContent::Message(
+        item.set_content(TimelineItemContent::from_message(
             message.with_in_reply_to(InReplyToDetails {
                 event_id: in_reply_to.event_id.clone(),
                 event,
@@ -1143,7 +1168,7 @@ async fn fetch_replied_to_event(
         event_id: in_reply_to.to_owned(),
         event: TimelineDetails::Pending,
     });
-    let event_item = item.with_content(TimelineItemContent::Message(reply), None);
+    let event_item = item.with_content(TimelineItemContent::from_message(reply), None);
 
     let new_timeline_item = state.new_timeline_item(event_item);
     state.items.set(index, new_timeline_item);
@@ -20,9 +20,10 @@ use std::{
     sync::Arc,
 };
 
+use as_variant::as_variant;
 use eyeball_im::{ObservableVector, ObservableVectorTransaction, ObservableVectorTransactionEntry};
 use imbl::Vector;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use matrix_sdk::{deserialized_responses::SyncTimelineEvent, sync::Timeline};
 use matrix_sdk_base::{deserialized_responses::TimelineEvent, sync::JoinedRoom};
 #[cfg(test)]
@@ -42,6 +43,7 @@ use super::{HandleManyEventsResult, ReactionState, TimelineInnerSettings};
 use crate::{
     events::SyncTimelineEventWithoutContent,
     timeline::{
+        event_cache::CachedEventsUpdate,
         event_handler::{
             Flow, HandleEventResult, TimelineEventContext, TimelineEventHandler, TimelineEventKind,
             TimelineItemPosition,
@@ -173,7 +175,7 @@ impl TimelineInnerState {
     #[instrument(skip_all)]
     pub(super) async fn handle_back_paginated_events<P: RoomDataProvider>(
         &mut self,
-        events: Vec<TimelineEvent>,
+        events: Vec<SyncTimelineEvent>,
         back_pagination_token: Option<String>,
         room_data_provider: &P,
         settings: &TimelineInnerSettings,
@@ -185,7 +187,7 @@ impl TimelineInnerState {
         for event in events {
             let (event_id, res) = txn
                 .handle_remote_event(
-                    event.into(),
+                    event,
                     TimelineItemPosition::Start,
                     room_data_provider,
                     settings,
@@ -209,6 +211,63 @@ impl TimelineInnerState {
         Some(total)
     }
 
+    /// Apply the changes of the events of the event cache.
+    ///
+    /// Returns `false` if a change refers to an event that is unknown, in which
+    /// case the timeline must be reset.
+    #[instrument(skip_all)]
+    pub(super) async fn handle_cached_events_updates<P: RoomDataProvider>(
+        &mut self,
+        updates: Vec<CachedEventsUpdate>,
+        room_data_provider: &P,
+        settings: &TimelineInnerSettings,
+    ) -> bool {
+        let mut txn = self.transaction();
+
+        for update in updates {
+            match update {
+                CachedEventsUpdate::PushBack(event) => {
+                    txn.handle_live_event(event, room_data_provider, settings).await;
+                }
+                CachedEventsUpdate::InsertAfter { prev_event_id: None, event } => {
+                    txn.handle_remote_event(
+                        event,
+                        TimelineItemPosition::Start,
+                        room_data_provider,
+                        settings,
+                    )
+                    .await;
+                }
+                CachedEventsUpdate::InsertAfter { prev_event_id: Some(prev_event_id), event } => {
+                    let Some(prev_index) =
+                        txn.all_events.iter().position(|meta| meta.event_id == prev_event_id)
+                    else {
+                        warn!(%prev_event_id, "Previous event of inserted event not found");
+                        return false;
+                    };
+
+                    txn.handle_remote_event(
+                        event,
+                        TimelineItemPosition::Insert(prev_index + 1),
+                        room_data_provider,
+                        settings,
+                    )
+                    .await;
+                }
+                CachedEventsUpdate::Remove(event_id) => {
+                    if !txn.remove_remote_event(&event_id) {
+                        warn!(%event_id, "Removed event not found");
+                        return false;
+                    }
+                }
+                CachedEventsUpdate::Clear => txn.clear(),
+            }
+        }
+
+        txn.commit();
+        true
+    }
+
     #[cfg(test)]
     pub(super) async fn handle_live_event<P: RoomDataProvider>(
         &mut self,
@@ -226,13 +285,14 @@ impl TimelineInnerState {
         &mut self,
         own_user_id: OwnedUserId,
         own_profile: Option<Profile>,
+        timestamp: MilliSecondsSinceUnixEpoch,
         txn_id: OwnedTransactionId,
         content: AnyMessageLikeEventContent,
     ) {
         let ctx = TimelineEventContext {
             sender: own_user_id,
             sender_profile: own_profile,
-            timestamp: MilliSecondsSinceUnixEpoch::now(),
+            timestamp,
             is_own_event: true,
             // FIXME: Should we supply something here for encrypted rooms?
             encryption_info: None,
@@ -254,6 +314,7 @@ impl TimelineInnerState {
         &mut self,
         own_user_id: OwnedUserId,
         own_profile: Option<Profile>,
+        timestamp: MilliSecondsSinceUnixEpoch,
         txn_id: OwnedTransactionId,
         to_redact: EventItemIdentifier,
         content: RoomRedactionEventContent,
@@ -261,7 +322,7 @@ impl TimelineInnerState {
         let ctx = TimelineEventContext {
             sender: own_user_id,
             sender_profile: own_profile,
-            timestamp: MilliSecondsSinceUnixEpoch::now(),
+            timestamp,
             is_own_event: true,
             // FIXME: Should we supply something here for encrypted rooms?
             encryption_info: None,
@@ -339,6 +400,7 @@ impl TimelineInnerState {
     pub(super) fn update_timeline_reaction(
         &mut self,
         own_user_id: &UserId,
+        timestamp: MilliSecondsSinceUnixEpoch,
         annotation: &Annotation,
         result: &ReactionToggleResult,
     ) -> Result<(), TimelineError> {
@@ -370,10 +432,8 @@ impl TimelineInnerState {
         };
         // Note: remote event is not synced yet, so we're adding an item
         // with the local timestamp.
-        let reaction_sender_data = ReactionSenderData {
-            sender_id: own_user_id.to_owned(),
-            timestamp: MilliSecondsSinceUnixEpoch::now(),
-        };
+        let reaction_sender_data =
+            ReactionSenderData { sender_id: own_user_id.to_owned(), timestamp };
 
         let new_reactions = {
             let mut reactions = remote_related.reactions.clone();
@@ -543,13 +603,22 @@ impl TimelineInnerStateTransaction<'_> {
         {
             Ok(event) => {
                 let room_version = room_data_provider.room_version();
-                let should_add = (settings.event_filter)(&event, &room_version);
+                let custom = settings.custom_events.deserialize(&raw);
+                let should_add = match &custom {
+                    // Registered events are always rendered.
+                    Some(Ok(_)) => true,
+                    Some(Err(_)) => settings.add_failed_to_parse,
+                    None => (settings.event_filter)(&event, &room_version),
+                };
                 (
                     event.event_id().to_owned(),
                     event.sender().to_owned(),
                     event.origin_server_ts(),
                     event.transaction_id().map(ToOwned::to_owned),
-                    TimelineEventKind::from_event(event, &room_version),
+                    match custom {
+                        Some(custom) => TimelineEventKind::custom(custom),
+                        None => TimelineEventKind::from_event(event, &room_version),
+                    },
                     should_add,
                 )
             }
@@ -612,6 +681,16 @@ impl TimelineInnerStateTransaction<'_> {
 
         let is_own_event = sender == room_data_provider.own_user_id();
 
+        // The transaction ID is only in the unsigned data of the event received by
+        // the device that sent it, and it might be missing if the event was sent
+        // before a restart, so look for it in the store to match the local echo.
+        let txn_id = match txn_id {
+            None if is_own_event && matches!(position, TimelineItemPosition::End { .. }) => {
+                room_data_provider.transaction_id_for_event(&event_id).await
+            }
+            txn_id => txn_id,
+        };
+
         let event_meta = FullEventMeta {
             event_id: &event_id,
             sender: Some(&sender),
@@ -651,6 +730,51 @@ impl TimelineInnerStateTransaction<'_> {
         (Some(event_id), result)
     }
 
+    /// Remove the remote event with the given ID, and its item if it has one.
+    ///
+    /// Returns `false` if the event is unknown.
+    fn remove_remote_event(&mut self, event_id: &EventId) -> bool {
+        let Some(pos) = self.meta.all_events.iter().position(|meta| meta.event_id == event_id)
+        else {
+            return false;
+        };
+        self.meta.all_events.remove(pos);
+
+        let Some((idx, item)) = rfind_event_by_id(&self.items, event_id) else {
+            return true;
+        };
+
+        // The event is usually added again at another position, so its
+        // reactions are kept for its new item.
+        if let Some(remote_item) = item.as_remote() {
+            let reaction_ids: IndexSet<_> = remote_item
+                .reactions
+                .values()
+                .flat_map(|group| group.0.keys())
+                .filter_map(|id| as_variant!(id, EventItemIdentifier::EventId))
+                .cloned()
+                .collect();
+
+            if !reaction_ids.is_empty() {
+                self.meta.reactions.pending.insert(event_id.to_owned(), reaction_ids);
+            }
+        }
+
+        trace!(idx, "Removing timeline item");
+        self.items.remove(idx);
+
+        // Remove the day divider if it doesn't precede any item anymore.
+        if idx > 0
+            && self.items[idx - 1].is_day_divider()
+            && self.items.get(idx).map_or(true, |item| item.is_day_divider())
+        {
+            trace!("Removing day divider");
+            self.items.remove(idx - 1);
+        }
+
+        true
+    }
+
     fn clear(&mut self) {
         // By first checking if there are any local echoes first, we do a bit
         // more work in case some are found, but it should be worth it because
@@ -735,6 +859,20 @@ impl TimelineInnerStateTransaction<'_> {
 
                 self.all_events.push_back(event_meta.base_meta());
             }
+            TimelineItemPosition::Insert(mut index) => {
+                // Handle duplicated event.
+                if let Some(pos) =
+                    self.all_events.iter().position(|ev| ev.event_id == event_meta.event_id)
+                {
+                    self.all_events.remove(pos);
+
+                    if pos < index {
+                        index -= 1;
+                    }
+                }
+
+                self.all_events.insert(index, event_meta.base_meta());
+            }
             #[cfg(feature = "e2e-encryption")]
             TimelineItemPosition::Update(_) => {
                 if let Some(event) =
@@ -754,7 +892,12 @@ impl TimelineInnerStateTransaction<'_> {
         }
 
         if settings.track_read_receipts
-            && matches!(position, TimelineItemPosition::Start | TimelineItemPosition::End { .. })
+            && matches!(
+                position,
+                TimelineItemPosition::Start
+                    | TimelineItemPosition::End { .. }
+                    | TimelineItemPosition::Insert(_)
+            )
         {
             self.load_read_receipts_for_event(event_meta.event_id, room_data_provider).await;
 
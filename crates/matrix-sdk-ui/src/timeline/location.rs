@@ -0,0 +1,233 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use ruma::events::{
+    location::{AssetType, LocationContent, ZoomLevel},
+    room::message::LocationMessageEventContent,
+};
+
+/// A `geo:` URI, as defined in [RFC 5870], with coordinates in the WGS-84
+/// reference system.
+///
+/// [RFC 5870]: https://www.rfc-editor.org/rfc/rfc5870
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeoUri {
+    /// The latitude, in decimal degrees between -90 and 90.
+    pub latitude: f64,
+    /// The longitude, in decimal degrees between -180 and 180.
+    pub longitude: f64,
+    /// The altitude, in meters, if any.
+    pub altitude: Option<f64>,
+    /// The uncertainty of the location, in meters, if any.
+    pub uncertainty: Option<f64>,
+}
+
+impl GeoUri {
+    /// Parse a `geo:` URI.
+    ///
+    /// Returns `None` if the string is not a valid `geo:` URI, if its
+    /// coordinates are out of bounds or if it uses another reference system
+    /// than WGS-84.
+    pub fn parse(uri: &str) -> Option<Self> {
+        let scheme = uri.get(..4)?;
+        if !scheme.eq_ignore_ascii_case("geo:") {
+            return None;
+        }
+
+        let mut parts = uri[4..].split(';');
+        let mut coordinates = parts.next()?.split(',').map(parse_number);
+
+        let latitude = coordinates.next()??;
+        let longitude = coordinates.next()??;
+        let altitude = match coordinates.next() {
+            Some(altitude) => Some(altitude?),
+            None => None,
+        };
+        if coordinates.next().is_some() {
+            return None;
+        }
+
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return None;
+        }
+
+        let mut uncertainty = None;
+        for parameter in parts {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+
+            if name.eq_ignore_ascii_case("crs") {
+                if !value.eq_ignore_ascii_case("wgs84") {
+                    return None;
+                }
+            } else if name.eq_ignore_ascii_case("u") {
+                uncertainty = Some(parse_number(value).filter(|u| *u >= 0.0)?);
+            }
+        }
+
+        Some(Self { latitude, longitude, altitude, uncertainty })
+    }
+}
+
+impl fmt::Display for GeoUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "geo:{},{}", self.latitude, self.longitude)?;
+        if let Some(altitude) = self.altitude {
+            write!(f, ",{altitude}")?;
+        }
+        if let Some(uncertainty) = self.uncertainty {
+            write!(f, ";u={uncertainty}")?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    // `f64::from_str` accepts values like `inf` or `1e5` that are not allowed
+    // in a `geo:` URI.
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit() || b == b'.' || b == b'-') {
+        return None;
+    }
+
+    s.parse().ok().filter(|n: &f64| n.is_finite())
+}
+
+/// A static location shared in a room.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    /// The coordinates of the location.
+    pub geo_uri: GeoUri,
+    /// The description of the location, if any.
+    pub description: Option<String>,
+    /// The zoom level to display the location at, from 0 to 20, if any.
+    pub zoom_level: Option<u8>,
+    /// What the location represents.
+    pub asset_type: AssetType,
+}
+
+impl Location {
+    /// Get the location shared by the given content.
+    ///
+    /// The location in the [MSC3488] content block is used if it is present,
+    /// otherwise the `geo_uri` of the message is used. Returns `None` if the
+    /// location is not a valid `geo:` URI.
+    ///
+    /// [MSC3488]: https://github.com/matrix-org/matrix-spec-proposals/pull/3488
+    pub fn from_content(content: &LocationMessageEventContent) -> Option<Self> {
+        let (geo_uri, description, zoom_level) = match &content.location {
+            Some(location) => (
+                location.uri.as_str(),
+                location.description.clone(),
+                location.zoom_level.as_ref().and_then(|zoom| zoom.get().try_into().ok()),
+            ),
+            None => (content.geo_uri.as_str(), None, None),
+        };
+
+        Some(Self {
+            geo_uri: GeoUri::parse(geo_uri)?,
+            description,
+            zoom_level,
+            asset_type: content.asset.as_ref().map_or(AssetType::Self_, |a| a.type_.clone()),
+        })
+    }
+
+    /// Build the content of an `m.room.message` event sharing this location.
+    pub(super) fn into_content(self) -> LocationMessageEventContent {
+        let geo_uri = self.geo_uri.to_string();
+        let body = match &self.description {
+            Some(description) => format!("{description} ({geo_uri})"),
+            None => format!("Location: {geo_uri}"),
+        };
+
+        let mut location = LocationContent::new(geo_uri.clone());
+        location.description = self.description;
+        location.zoom_level = self.zoom_level.and_then(ZoomLevel::new);
+
+        let mut content =
+            LocationMessageEventContent::new(body, geo_uri).with_asset_type(self.asset_type);
+        content.location = Some(location);
+        content
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::events::{location::AssetType, room::message::LocationMessageEventContent};
+
+    use super::{GeoUri, Location};
+
+    #[test]
+    fn test_parse_geo_uri() {
+        assert_eq!(
+            GeoUri::parse("geo:51.5008,0.1247"),
+            Some(GeoUri {
+                latitude: 51.5008,
+                longitude: 0.1247,
+                altitude: None,
+                uncertainty: None
+            })
+        );
+        assert_eq!(
+            GeoUri::parse("GEO:-33.8,151.2,40;crs=WGS84;u=35;foo=bar"),
+            Some(GeoUri {
+                latitude: -33.8,
+                longitude: 151.2,
+                altitude: Some(40.0),
+                uncertainty: Some(35.0),
+            })
+        );
+
+        assert_eq!(GeoUri::parse("geo:91,0"), None);
+        assert_eq!(GeoUri::parse("geo:0,181"), None);
+        assert_eq!(GeoUri::parse("geo:0"), None);
+        assert_eq!(GeoUri::parse("geo:0,0,0,0"), None);
+        assert_eq!(GeoUri::parse("geo:inf,0"), None);
+        assert_eq!(GeoUri::parse("geo:0,0;crs=Moon-2011"), None);
+        assert_eq!(GeoUri::parse("geo:0,0;u=-1"), None);
+        assert_eq!(GeoUri::parse("https://example.org"), None);
+    }
+
+    #[test]
+    fn test_geo_uri_roundtrip() {
+        let uri = "geo:-33.8,151.2,40;u=35";
+        assert_eq!(GeoUri::parse(uri).unwrap().to_string(), uri);
+    }
+
+    #[test]
+    fn test_location_content_roundtrip() {
+        let location = Location {
+            geo_uri: GeoUri::parse("geo:51.5008,0.1247").unwrap(),
+            description: Some("Big Ben".to_owned()),
+            zoom_level: Some(15),
+            asset_type: AssetType::Pin,
+        };
+
+        let content = location.clone().into_content();
+        assert_eq!(content.body, "Big Ben (geo:51.5008,0.1247)");
+        assert_eq!(content.geo_uri, "geo:51.5008,0.1247");
+        assert_eq!(Location::from_content(&content), Some(location));
+    }
+
+    #[test]
+    fn test_location_from_legacy_content() {
+        let content = LocationMessageEventContent::new("Here".to_owned(), "geo:1,2".to_owned());
+        let location = Location::from_content(&content).unwrap();
+
+        assert_eq!(location.geo_uri.latitude, 1.0);
+        assert_eq!(location.geo_uri.longitude, 2.0);
+        assert_eq!(location.description, None);
+        assert_eq!(location.asset_type, AssetType::Self_);
+    }
+}
@@ -25,48 +25,57 @@ use imbl::Vector;
 use matrix_sdk::{
     attachment::AttachmentConfig,
     event_handler::EventHandlerHandle,
-    executor::JoinHandle,
-    room::{Receipts, Room},
+    executor::{spawn, JoinHandle},
+    media::{MediaEventContent, MediaFormat, MediaRequest, MediaThumbnailSize},
+    room::{Receipts, RelationsOptions, Room},
     Client, Result,
 };
 use matrix_sdk_base::RoomState;
 use mime::Mime;
 use pin_project_lite::pin_project;
 use ruma::{
-    api::client::receipt::create_receipt::v3::ReceiptType,
+    api::{client::receipt::create_receipt::v3::ReceiptType, Direction},
     events::{
+        location::AssetType,
         poll::unstable_start::{
             ReplacementUnstablePollStartEventContent, UnstablePollStartContentBlock,
             UnstablePollStartEventContent,
         },
         reaction::ReactionEventContent,
         receipt::{Receipt, ReceiptThread},
-        relation::Annotation,
+        relation::{Annotation, RelationType},
         room::{
             message::{
-                AddMentions, ForwardThread, OriginalRoomMessageEvent, ReplacementMetadata,
-                RoomMessageEventContent, RoomMessageEventContentWithoutRelation,
+                AddMentions, ForwardThread, MessageType, OriginalRoomMessageEvent,
+                ReplacementMetadata, RoomMessageEventContent,
+                RoomMessageEventContentWithoutRelation,
             },
             redaction::RoomRedactionEventContent,
+            ImageInfo, MediaSource,
         },
+        sticker::StickerEventContent,
         AnyMessageLikeEventContent,
     },
-    uint, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedTransactionId, TransactionId,
-    UserId,
+    uint, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedMxcUri, OwnedTransactionId,
+    TransactionId, UserId,
 };
 use thiserror::Error;
 use tokio::sync::{mpsc::Sender, Mutex, Notify};
 use tracing::{debug, error, info, instrument, warn};
 
-use self::futures::SendAttachment;
+use self::{event_cache::EventCacheSource, futures::SendAttachment};
 
 mod builder;
+mod edit_history;
 mod error;
+mod event_cache;
 mod event_handler;
 mod event_item;
+mod forward;
 pub mod futures;
 mod inner;
 mod item;
+mod location;
 mod pagination;
 mod polls;
 mod queue;
@@ -83,15 +92,17 @@ mod virtual_item;
 
 pub use self::{
     builder::TimelineBuilder,
+    edit_history::EditHistoryItem,
     error::{Error, UnsupportedEditItem, UnsupportedReplyItem},
     event_item::{
-        AnyOtherFullStateEventContent, BundledReactions, EncryptedMessage, EventItemOrigin,
-        EventSendState, EventTimelineItem, InReplyToDetails, MemberProfileChange, MembershipChange,
-        Message, OtherState, Profile, ReactionGroup, RepliedToEvent, RoomMembershipChange, Sticker,
-        TimelineDetails, TimelineItemContent,
+        AnyOtherFullStateEventContent, BundledReactions, CustomEventContent, EncryptedMessage,
+        EventItemOrigin, EventSendState, EventTimelineItem, InReplyToDetails, MemberProfileChange,
+        MembershipChange, Message, OtherState, Profile, ReactionGroup, RepliedToEvent,
+        RoomMembershipChange, Sticker, TimelineDetails, TimelineItemContent, VoiceMessage,
     },
     inner::default_event_filter,
     item::{TimelineItem, TimelineItemKind},
+    location::{GeoUri, Location},
     pagination::{BackPaginationStatus, PaginationOptions, PaginationOutcome},
     polls::PollResult,
     reactions::ReactionSenderData,
@@ -123,6 +134,9 @@ pub struct Timeline {
     /// Notifier for handled sync responses.
     sync_response_notify: Arc<Notify>,
 
+    /// The event cache of the room, if it is the source of the events.
+    event_cache: Option<Arc<EventCacheSource>>,
+
     msg_sender: Sender<LocalMessage>,
     drop_handle: Arc<TimelineDropHandle>,
 }
@@ -300,6 +314,79 @@ impl Timeline {
         }
     }
 
+    /// Share a static location.
+    ///
+    /// The event contains both the `geo_uri` of `m.location` messages and the
+    /// [MSC3488] location and asset content blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `geo_uri` - The `geo:` URI of the location.
+    ///
+    /// * `description` - A description of the location, if any.
+    ///
+    /// * `zoom_level` - The zoom level to display the location at, from 0 to
+    ///   20, if any.
+    ///
+    /// * `asset_type` - What the location represents, defaults to the location
+    ///   of the sender.
+    ///
+    /// [MSC3488]: https://github.com/matrix-org/matrix-spec-proposals/pull/3488
+    #[instrument(skip(self, description))]
+    pub async fn send_location(
+        &self,
+        geo_uri: &str,
+        description: Option<String>,
+        zoom_level: Option<u8>,
+        asset_type: Option<AssetType>,
+    ) -> Result<(), Error> {
+        let location = Location {
+            geo_uri: GeoUri::parse(geo_uri).ok_or(Error::InvalidGeoUri)?,
+            description,
+            zoom_level,
+            asset_type: asset_type.unwrap_or(AssetType::Self_),
+        };
+
+        let content = RoomMessageEventContent::new(MessageType::Location(location.into_content()));
+        self.send(content.into()).await;
+
+        Ok(())
+    }
+
+    /// Send a sticker.
+    ///
+    /// Stickers from the image pack of the user can be sent with
+    /// [`PackImage::to_sticker_content()`] and [`Timeline::send()`] instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - A textual representation of the sticker.
+    ///
+    /// * `info` - Metadata about the image, which should include its size and a
+    ///   thumbnail if it is large.
+    ///
+    /// * `url` - The URL of the image.
+    ///
+    /// [`PackImage::to_sticker_content()`]: matrix_sdk::stickers::PackImage::to_sticker_content
+    #[instrument(skip(self, body, info))]
+    pub async fn send_sticker(&self, body: String, info: ImageInfo, url: OwnedMxcUri) {
+        self.send(StickerEventContent::new(body, info, url).into()).await;
+    }
+
+    /// Forward the given event to the room of the `target` timeline.
+    ///
+    /// The content of the event is sent as a new event, without its relations,
+    /// and a local echo is added to the `target` timeline. Encrypted media is
+    /// re-uploaded if the target room is not encrypted.
+    ///
+    /// Only messages and stickers can be forwarded.
+    #[instrument(skip(self, item, target))]
+    pub async fn forward(&self, item: &EventTimelineItem, target: &Timeline) -> Result<(), Error> {
+        let content = forward::forwarded_content(item.content(), target.room()).await?;
+        target.send(content).await;
+        Ok(())
+    }
+
     /// Send a reply to the given event.
     ///
     /// Currently only supports events events with an event ID and JSON being
@@ -336,8 +423,8 @@ impl Timeline {
         let add_mentions =
             if content.mentions.is_some() { AddMentions::Yes } else { AddMentions::No };
 
-        let content = match reply_item.content() {
-            TimelineItemContent::Message(msg) => {
+        let content = match reply_item.content().room_message() {
+            Some(msg) => {
                 let event = OriginalRoomMessageEvent {
                     event_id: event_id.to_owned(),
                     sender: reply_item.sender().to_owned(),
@@ -348,7 +435,7 @@ impl Timeline {
                 };
                 content.make_reply_to(&event, forward_thread, add_mentions)
             }
-            _ => {
+            None => {
                 let Some(raw_event) = reply_item.latest_json() else {
                     return Err(UnsupportedReplyItem::MISSING_JSON);
                 };
@@ -394,8 +481,8 @@ impl Timeline {
 
         let replied_to_message =
             original_content.in_reply_to().and_then(|details| match &details.event {
-                TimelineDetails::Ready(event) => match event.content() {
-                    TimelineItemContent::Message(msg) => Some(OriginalRoomMessageEvent {
+                TimelineDetails::Ready(event) => {
+                    event.content().room_message().map(|msg| OriginalRoomMessageEvent {
                         content: msg.to_content(),
                         event_id: event_id.to_owned(),
                         sender: event.sender.clone(),
@@ -403,9 +490,8 @@ impl Timeline {
                         origin_server_ts: MilliSecondsSinceUnixEpoch(uint!(0)),
                         room_id: self.room().room_id().to_owned(),
                         unsigned: Default::default(),
-                    }),
-                    _ => None,
-                },
+                    })
+                }
                 _ => {
                     warn!("original event is a reply, but we don't have the replied-to event");
                     None
@@ -534,7 +620,9 @@ impl Timeline {
     ///
     /// * `config` - An attachment configuration object containing details about
     ///   the attachment
-    /// like a thumbnail, its size, duration etc.
+    /// like a thumbnail, its size, duration etc. With the `image-proc` feature,
+    /// the thumbnail and the metadata of images can be generated with
+    /// `AttachmentConfig::generate_thumbnail()`.
     #[instrument(skip_all)]
     pub fn send_attachment(
         &self,
@@ -565,6 +653,9 @@ impl Timeline {
             TimelineItemContent::Message(msg) => {
                 AnyMessageLikeEventContent::RoomMessage(msg.into())
             }
+            TimelineItemContent::Voice(voice) => {
+                AnyMessageLikeEventContent::RoomMessage(voice.message.into())
+            }
             TimelineItemContent::RedactedMessage => {
                 error_return!("Invalid state: attempting to retry a redacted message");
             }
@@ -586,6 +677,9 @@ impl Timeline {
             TimelineItemContent::Poll(poll_state) => AnyMessageLikeEventContent::UnstablePollStart(
                 UnstablePollStartEventContent::New(poll_state.into()),
             ),
+            TimelineItemContent::Custom(_) => {
+                error_return!("Invalid state: attempting to retry a custom event");
+            }
         };
 
         debug!("Retrying failed local echo");
@@ -637,6 +731,52 @@ impl Timeline {
         self.inner.fetch_in_reply_to_details(event_id).await
     }
 
+    /// Fetch the edit history of the given message, oldest edit first.
+    ///
+    /// The original content of the message is not part of the history, it is
+    /// available in the original JSON of the item.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedEvent`] if the item is a local echo, and
+    /// [`Error::FailedFetchingEditHistory`] if the request to the homeserver
+    /// failed.
+    #[instrument(skip_all, fields(room_id = ?self.room().room_id()))]
+    pub async fn fetch_edit_history(
+        &self,
+        item: &EventTimelineItem,
+    ) -> Result<Vec<EditHistoryItem>, Error> {
+        let event_id = item.event_id().ok_or(Error::UnsupportedEvent)?;
+
+        let mut history = Vec::new();
+        let mut options = RelationsOptions::with_rel_type(RelationType::Replacement);
+        options.dir = Direction::Forward;
+
+        loop {
+            let relations = self
+                .room()
+                .relations(event_id, options.clone())
+                .await
+                .map_err(|_| Error::FailedFetchingEditHistory)?;
+
+            history.extend(
+                relations
+                    .chunk
+                    .iter()
+                    .filter_map(|event| EditHistoryItem::from_event(event, item.sender())),
+            );
+
+            let Some(next_batch) = relations.next_batch else { break };
+            options = options.from(next_batch.as_str());
+        }
+
+        // The homeserver returns the events in topological order, but the order
+        // in which the edits were made is what matters to users.
+        history.sort_by_key(|edit| edit.timestamp);
+
+        Ok(history)
+    }
+
     /// Fetch all member events for the room this timeline is displaying.
     ///
     /// If the full member list is not known, sender profiles are currently
@@ -657,6 +797,51 @@ impl Timeline {
         }
     }
 
+    /// Download the thumbnails of the media of the given items in the
+    /// background, so they are in the media cache when the items are
+    /// displayed.
+    ///
+    /// This should be called with the items that are visible or about to be.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The timeline items to fetch the thumbnails of.
+    ///
+    /// * `thumbnail_size` - The size of the thumbnails to request from the
+    ///   homeserver, for the media that don't have a thumbnail in their event.
+    ///   If this is `None`, the whole file is fetched instead.
+    pub fn prefetch_media_thumbnails(
+        &self,
+        items: &[Arc<TimelineItem>],
+        thumbnail_size: Option<MediaThumbnailSize>,
+    ) -> JoinHandle<()> {
+        let requests = items
+            .iter()
+            .filter_map(|item| {
+                let source = match item.as_event()?.content() {
+                    TimelineItemContent::Message(message) => match message.msgtype() {
+                        MessageType::Image(content) => content.thumbnail_source(),
+                        MessageType::Video(content) => content.thumbnail_source(),
+                        MessageType::File(content) => content.thumbnail_source(),
+                        _ => None,
+                    },
+                    TimelineItemContent::Sticker(sticker) => sticker.content().source(),
+                    _ => None,
+                }?;
+
+                let format = match (&source, &thumbnail_size) {
+                    (MediaSource::Plain(_), Some(size)) => MediaFormat::Thumbnail(size.clone()),
+                    _ => MediaFormat::File,
+                };
+
+                Some(MediaRequest { source, format })
+            })
+            .collect::<Vec<_>>();
+
+        let media = self.room().client().media();
+        spawn(async move { media.prefetch_media_content(requests).await })
+    }
+
     /// Get the latest read receipt for the given user.
     ///
     /// Contrary to [`Room::load_user_receipt()`] that only keeps track of read
@@ -690,13 +875,21 @@ impl Timeline {
     /// This uses [`Room::send_single_receipt`] internally, but checks
     /// first if the receipt points to an event in this timeline that is more
     /// recent than the current ones, to avoid unnecessary requests.
+    ///
+    /// If [`Client::private_read_receipts()`] is enabled, public read receipts
+    /// are sent as private read receipts.
     #[instrument(skip(self))]
     pub async fn send_single_receipt(
         &self,
-        receipt_type: ReceiptType,
+        mut receipt_type: ReceiptType,
         thread: ReceiptThread,
         event_id: OwnedEventId,
     ) -> Result<()> {
+        if matches!(receipt_type, ReceiptType::Read) && self.room().client().private_read_receipts()
+        {
+            receipt_type = ReceiptType::ReadPrivate;
+        }
+
         if !self.inner.should_send_receipt(&receipt_type, &thread, &event_id).await {
             return Ok(());
         }
@@ -710,8 +903,17 @@ impl Timeline {
     /// checks first if the receipts point to events in this timeline that
     /// are more recent than the current ones, to avoid unnecessary
     /// requests.
+    ///
+    /// If [`Client::private_read_receipts()`] is enabled, the public read
+    /// receipt is sent as a private read receipt.
     #[instrument(skip(self))]
     pub async fn send_multiple_receipts(&self, mut receipts: Receipts) -> Result<()> {
+        if self.room().client().private_read_receipts() {
+            if let Some(public_read_receipt) = receipts.public_read_receipt.take() {
+                receipts.private_read_receipt.get_or_insert(public_read_receipt);
+            }
+        }
+
         if let Some(fully_read) = &receipts.fully_read {
             if !self
                 .inner
@@ -779,6 +981,7 @@ struct TimelineDropHandle {
     room_update_join_handle: JoinHandle<()>,
     ignore_user_list_update_join_handle: JoinHandle<()>,
     room_key_from_backups_join_handle: JoinHandle<()>,
+    event_cache_join_handle: Option<JoinHandle<()>>,
 }
 
 impl Drop for TimelineDropHandle {
@@ -789,6 +992,9 @@ impl Drop for TimelineDropHandle {
         self.room_update_join_handle.abort();
         self.ignore_user_list_update_join_handle.abort();
         self.room_key_from_backups_join_handle.abort();
+        if let Some(handle) = &self.event_cache_join_handle {
+            handle.abort();
+        }
     }
 }
 
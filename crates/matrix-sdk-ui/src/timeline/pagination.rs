@@ -14,12 +14,12 @@
 
 use std::{fmt, ops::ControlFlow, pin::pin, sync::Arc, time::Duration};
 
-use matrix_sdk::{room::MessagesOptions, Result};
+use matrix_sdk::{event_cache::BackPaginationOutcome, room::MessagesOptions, Result};
 use matrix_sdk_base::timeout::timeout;
 use ruma::assign;
 use tracing::{error, info, instrument, trace, warn};
 
-use super::{inner::HandleBackPaginatedEventsError, Timeline};
+use super::{event_cache::EventCacheSource, inner::HandleBackPaginatedEventsError, Timeline};
 
 impl Timeline {
     /// Run back-pagination.
@@ -44,6 +44,10 @@ impl Timeline {
         // `wait_for_token` option is set
         const WAIT_FOR_TOKEN_TIMEOUT: Duration = Duration::from_secs(3);
 
+        if let Some(event_cache) = &self.event_cache {
+            return self.paginate_backwards_with_event_cache(event_cache, options).await;
+        }
+
         let mut from = match self.inner.back_pagination_token().await {
             None if options.wait_for_token => {
                 trace!("Waiting for back-pagination token from sync...");
@@ -102,6 +106,61 @@ impl Timeline {
         Ok(ControlFlow::Break(BackPaginationStatus::Idle))
     }
 
+    /// Run back-pagination through the event cache of the room.
+    ///
+    /// The gaps are tracked by the event cache, so the pagination can't be
+    /// reset by a sync response.
+    async fn paginate_backwards_with_event_cache(
+        &self,
+        event_cache: &EventCacheSource,
+        mut options: PaginationOptions<'_>,
+    ) -> Result<ControlFlow<BackPaginationStatus>> {
+        self.back_pagination_status.set_if_not_eq(BackPaginationStatus::Paginating);
+
+        let mut outcome = PaginationOutcome::new();
+        while let Some(limit) = options.next_event_limit(outcome) {
+            let num_items = self.inner.items().await.len();
+
+            let BackPaginationOutcome { num_events, reached_start } =
+                event_cache.cache().paginate_backwards(limit).await?;
+
+            // Apply the new events of the cache right away, to know how many
+            // items they added.
+            event_cache.catch_up(&self.inner).await;
+            let items_added = self.inner.items().await.len().saturating_sub(num_items);
+
+            // FIXME: Change to try block once stable
+            let mut update_outcome = || {
+                outcome.events_received = num_events.try_into().ok()?;
+                outcome.total_events_received =
+                    outcome.total_events_received.checked_add(outcome.events_received)?;
+
+                outcome.items_added = items_added.try_into().ok()?;
+                outcome.total_items_added =
+                    outcome.total_items_added.checked_add(outcome.items_added)?;
+
+                Some(())
+            };
+
+            if update_outcome().is_none() {
+                error!("Received an excessive number of events, ending pagination");
+                break;
+            }
+
+            if reached_start {
+                trace!("Start of timeline was reached");
+                return Ok(ControlFlow::Break(BackPaginationStatus::TimelineStartReached));
+            }
+
+            if num_events == 0 {
+                // Paginating again right away might not make any progress.
+                break;
+            }
+        }
+
+        Ok(ControlFlow::Break(BackPaginationStatus::Idle))
+    }
+
     /// Do back-pagination requests until the back-pagination token is updated.
     #[instrument(skip(self, outcome))]
     async fn paginate_backwards_until_new_token(
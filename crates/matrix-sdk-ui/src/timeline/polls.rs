@@ -32,6 +32,7 @@ pub struct PollState {
 #[derive(Clone, Debug)]
 pub(super) struct ResponseData {
     pub(super) sender: OwnedUserId,
+    pub(super) is_own: bool,
     pub(super) timestamp: MilliSecondsSinceUnixEpoch,
     pub(super) answers: Vec<String>,
 }
@@ -64,12 +65,14 @@ impl PollState {
     pub(super) fn add_response(
         &self,
         sender: &UserId,
+        is_own: bool,
         timestamp: MilliSecondsSinceUnixEpoch,
         content: &UnstablePollResponseEventContent,
     ) -> Self {
         let mut clone = self.clone();
         clone.response_data.push(ResponseData {
             sender: sender.to_owned(),
+            is_own,
             timestamp,
             answers: content.poll_response.answers.clone(),
         });
@@ -104,6 +107,17 @@ impl PollState {
             self.end_event_timestamp,
         );
 
+        let own_user_id = self
+            .response_data
+            .iter()
+            .find(|response_data| response_data.is_own)
+            .map(|response_data| &*response_data.sender);
+        let own_votes = results
+            .iter()
+            .filter(|(_, voters)| own_user_id.is_some_and(|user_id| voters.contains(&user_id)))
+            .map(|(answer_id, _)| (*answer_id).to_owned())
+            .collect();
+
         PollResult {
             question: self.start_event_content.poll_start.question.text.clone(),
             kind: self.start_event_content.poll_start.kind.clone(),
@@ -113,12 +127,17 @@ impl PollState {
                 .poll_start
                 .answers
                 .iter()
-                .map(|i| PollResultAnswer { id: i.id.clone(), text: i.text.clone() })
+                .map(|i| PollResultAnswer {
+                    id: i.id.clone(),
+                    text: i.text.clone(),
+                    vote_count: results.get(i.id.as_str()).map_or(0, |voters| voters.len() as u64),
+                })
                 .collect(),
             votes: results
                 .iter()
                 .map(|i| ((*i.0).to_owned(), i.1.iter().map(|i| i.to_string()).collect()))
                 .collect(),
+            own_votes,
             end_time: self.end_event_timestamp.map(|millis| millis.0.into()),
             has_been_edited: self.has_been_edited,
         }
@@ -152,11 +171,13 @@ impl PollPendingEvents {
         &mut self,
         start_id: &EventId,
         sender: &UserId,
+        is_own: bool,
         timestamp: MilliSecondsSinceUnixEpoch,
         content: &UnstablePollResponseEventContent,
     ) {
         self.pending_poll_responses.entry(start_id.to_owned()).or_default().push(ResponseData {
             sender: sender.to_owned(),
+            is_own,
             timestamp,
             answers: content.poll_response.answers.clone(),
         });
@@ -185,6 +206,8 @@ pub struct PollResult {
     pub max_selections: u64,
     pub answers: Vec<PollResultAnswer>,
     pub votes: HashMap<String, Vec<String>>,
+    /// The IDs of the answers the current user voted for.
+    pub own_votes: Vec<String>,
     pub end_time: Option<u64>,
     pub has_been_edited: bool,
 }
@@ -193,4 +216,6 @@ pub struct PollResult {
 pub struct PollResultAnswer {
     pub id: String,
     pub text: String,
+    /// The number of valid votes for this answer.
+    pub vote_count: u64,
 }
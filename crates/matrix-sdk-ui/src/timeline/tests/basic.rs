@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
@@ -30,6 +32,7 @@ use ruma::{
         },
         FullStateEventContent,
     },
+    uint,
 };
 use stream_assert::assert_next_matches;
 
@@ -99,6 +102,43 @@ async fn sticker() {
     assert_matches!(item.content(), TimelineItemContent::Sticker(_));
 }
 
+#[async_test]
+async fn voice_message() {
+    let timeline = TestTimeline::new();
+    let mut stream = timeline.subscribe_events().await;
+
+    timeline
+        .handle_live_custom_event(sync_timeline_event!({
+            "content": {
+                "body": "Voice message",
+                "msgtype": "m.audio",
+                "info": {
+                    "duration": 5300,
+                    "mimetype": "audio/ogg",
+                },
+                "url": "mxc://server.name/voice",
+                "org.matrix.msc1767.audio": {
+                    "duration": 5300,
+                    "waveform": [0, 512, 1024, 256],
+                },
+                "org.matrix.msc3245.voice": {},
+            },
+            "event_id": "$143273582443PhrSn",
+            "origin_server_ts": 143273582,
+            "sender": "@alice:server.name",
+            "type": "m.room.message",
+        }))
+        .await;
+
+    let item = assert_next_matches!(stream, VectorDiff::PushBack { value } => value);
+    assert!(item.can_be_replied_to());
+    assert_let!(TimelineItemContent::Voice(voice) = item.content());
+    assert_eq!(voice.message().body(), "Voice message");
+    assert_eq!(voice.duration(), Some(Duration::from_millis(5300)));
+    let waveform: Vec<_> = voice.waveform().iter().map(|amplitude| amplitude.get()).collect();
+    assert_eq!(waveform, [uint!(0), uint!(512), uint!(1024), uint!(256)]);
+}
+
 #[async_test]
 async fn room_member() {
     let timeline = TestTimeline::new();
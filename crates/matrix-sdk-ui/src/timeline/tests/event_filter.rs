@@ -33,6 +33,7 @@ use ruma::{
         AnySyncTimelineEvent,
     },
 };
+use serde::Deserialize;
 use stream_assert::assert_next_matches;
 
 use super::TestTimeline;
@@ -200,3 +201,43 @@ async fn hide_failed_to_parse() {
 
     assert_eq!(timeline.inner.items().await.len(), 0);
 }
+
+#[async_test]
+async fn registered_custom_event() {
+    #[derive(Deserialize)]
+    struct PingEventContent {
+        count: u32,
+    }
+
+    let mut settings = TimelineInnerSettings::default();
+    settings.custom_events.register_message_like::<PingEventContent>("org.example.ping");
+    let timeline = TestTimeline::new().with_settings(settings);
+
+    timeline
+        .handle_live_custom_event(sync_timeline_event!({
+            "content": { "count": 3 },
+            "event_id": "$ping",
+            "origin_server_ts": 10,
+            "sender": "@alice:example.org",
+            "type": "org.example.ping",
+        }))
+        .await;
+
+    // Unregistered custom events are still ignored by the default filter.
+    timeline
+        .handle_live_custom_event(sync_timeline_event!({
+            "content": { "count": 3 },
+            "event_id": "$pong",
+            "origin_server_ts": 11,
+            "sender": "@alice:example.org",
+            "type": "org.example.pong",
+        }))
+        .await;
+
+    let items = timeline.inner.items().await;
+    assert_eq!(items.len(), 2);
+    let _day_divider = items[0].as_virtual().unwrap();
+    assert_let!(TimelineItemContent::Custom(content) = items[1].as_event().unwrap().content());
+    assert_eq!(content.event_type(), "org.example.ping");
+    assert_eq!(content.downcast_ref::<PingEventContent>().unwrap().count, 3);
+}
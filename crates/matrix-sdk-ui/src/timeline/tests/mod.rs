@@ -25,7 +25,10 @@ use eyeball_im::VectorDiff;
 use futures_core::Stream;
 use futures_util::{FutureExt, StreamExt};
 use indexmap::IndexMap;
-use matrix_sdk::deserialized_responses::{SyncTimelineEvent, TimelineEvent};
+use matrix_sdk::{
+    deserialized_responses::{SyncTimelineEvent, TimelineEvent},
+    time::SharedClock,
+};
 use matrix_sdk_base::latest_event::LatestEvent;
 use matrix_sdk_test::{EventBuilder, ALICE, BOB};
 use ruma::{
@@ -287,6 +290,10 @@ impl RoomDataProvider for TestRoomDataProvider {
         RoomVersionId::V10
     }
 
+    fn clock(&self) -> SharedClock {
+        SharedClock::default()
+    }
+
     async fn profile_from_user_id(&self, _user_id: &UserId) -> Option<Profile> {
         None
     }
@@ -330,6 +337,10 @@ impl RoomDataProvider for TestRoomDataProvider {
 
         Some((push_rules, push_context))
     }
+
+    async fn transaction_id_for_event(&self, _event_id: &EventId) -> Option<OwnedTransactionId> {
+        None
+    }
 }
 
 pub(super) async fn assert_event_is_updated(
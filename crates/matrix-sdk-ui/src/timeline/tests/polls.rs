@@ -159,6 +159,31 @@ async fn a_somewhat_complex_voting_session_yields_the_expected_outcome() {
     assert_eq!(results.votes["id_down"], vec![ALICE.to_string()]);
 }
 
+#[async_test]
+async fn results_contain_the_vote_counts_and_own_votes() {
+    let timeline = TestTimeline::new();
+    timeline.send_poll_start(&ALICE, fakes::poll_a()).await;
+    let poll_id = timeline.poll_event().await.event_id().unwrap().to_owned();
+
+    // Nobody voted yet
+    let results = timeline.poll_state().await.results();
+    assert!(results.answers.iter().all(|answer| answer.vote_count == 0));
+    assert!(results.own_votes.is_empty());
+
+    // Bob votes, Alice is the own user
+    timeline.send_poll_response(&BOB, vec!["id_up"], &poll_id).await;
+    let results = timeline.poll_state().await.results();
+    assert_eq!(results.answers[0].vote_count, 1);
+    assert!(results.own_votes.is_empty());
+
+    // Alice votes for the other answer
+    timeline.send_poll_response(&ALICE, vec!["id_down"], &poll_id).await;
+    let results = timeline.poll_state().await.results();
+    assert_eq!(results.answers[0].vote_count, 1);
+    assert_eq!(results.answers[1].vote_count, 1);
+    assert_eq!(results.own_votes, vec!["id_down".to_owned()]);
+}
+
 #[async_test]
 async fn events_received_before_start_are_not_lost() {
     let timeline = TestTimeline::new();
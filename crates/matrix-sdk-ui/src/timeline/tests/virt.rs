@@ -16,6 +16,7 @@ use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use chrono::{Datelike, Local, TimeZone};
 use eyeball_im::VectorDiff;
+use matrix_sdk::deserialized_responses::SyncTimelineEvent;
 use matrix_sdk_test::{async_test, ALICE, BOB};
 use ruma::{
     event_id,
@@ -24,7 +25,7 @@ use ruma::{
 use stream_assert::assert_next_matches;
 
 use super::TestTimeline;
-use crate::timeline::{TimelineItemKind, VirtualTimelineItem};
+use crate::timeline::{event_cache::CachedEventsUpdate, TimelineItemKind, VirtualTimelineItem};
 
 #[async_test]
 async fn day_divider() {
@@ -150,3 +151,65 @@ async fn update_read_marker() {
     let marker = assert_next_matches!(stream, VectorDiff::Insert { index: 4, value } => value);
     assert_matches!(marker.kind, TimelineItemKind::Virtual(VirtualTimelineItem::ReadMarker));
 }
+
+#[async_test]
+async fn day_divider_of_inserted_event() {
+    let timeline = TestTimeline::new();
+    let mut stream = timeline.subscribe().await;
+
+    let day = 24 * 60 * 60 * 1000;
+    let event_a = event_id!("$a");
+    let event_b = event_id!("$b");
+    let event_c = event_id!("$c");
+
+    timeline
+        .handle_live_message_event_with_id(
+            &ALICE,
+            event_a,
+            RoomMessageEventContent::text_plain("A"),
+        )
+        .await;
+    timeline.event_builder.set_next_ts(2 * day);
+    timeline
+        .handle_live_message_event_with_id(
+            &ALICE,
+            event_c,
+            RoomMessageEventContent::text_plain("C"),
+        )
+        .await;
+
+    // Day divider, A, day divider, C.
+    for _ in 0..4 {
+        assert_next_matches!(stream, VectorDiff::PushBack { .. });
+    }
+
+    // An event of the day between the two is inserted with its own day divider.
+    timeline.event_builder.set_next_ts(day);
+    let event = timeline.event_builder.make_sync_message_event_with_id(
+        &BOB,
+        event_b,
+        RoomMessageEventContent::text_plain("B"),
+    );
+    let updates = vec![CachedEventsUpdate::InsertAfter {
+        prev_event_id: Some(event_a.to_owned()),
+        event: SyncTimelineEvent::new(event),
+    }];
+    assert!(timeline.inner.handle_cached_events_updates(updates).await);
+
+    let day_divider = assert_next_matches!(stream, VectorDiff::Insert { index: 2, value } => value);
+    assert_matches!(day_divider.as_virtual().unwrap(), VirtualTimelineItem::DayDivider { .. });
+    let item = assert_next_matches!(stream, VectorDiff::Insert { index: 3, value } => value);
+    assert_eq!(item.as_event().unwrap().event_id(), Some(event_b));
+
+    // Its day divider is removed with it.
+    let updates = vec![CachedEventsUpdate::Remove(event_b.to_owned())];
+    assert!(timeline.inner.handle_cached_events_updates(updates).await);
+
+    assert_next_matches!(stream, VectorDiff::Remove { index: 3 });
+    assert_next_matches!(stream, VectorDiff::Remove { index: 2 });
+    assert_eq!(timeline.len().await, 4);
+
+    // An unknown event can't be removed.
+    let updates = vec![CachedEventsUpdate::Remove(event_b.to_owned())];
+    assert!(!timeline.inner.handle_cached_events_updates(updates).await);
+}
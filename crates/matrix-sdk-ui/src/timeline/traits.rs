@@ -14,9 +14,9 @@
 
 use async_trait::async_trait;
 use indexmap::IndexMap;
-use matrix_sdk::Room;
 #[cfg(feature = "e2e-encryption")]
 use matrix_sdk::{deserialized_responses::TimelineEvent, Result};
+use matrix_sdk::{time::SharedClock, Room};
 use matrix_sdk_base::latest_event::LatestEvent;
 #[cfg(feature = "e2e-encryption")]
 use ruma::{events::AnySyncTimelineEvent, serde::Raw};
@@ -26,7 +26,7 @@ use ruma::{
         AnySyncMessageLikeEvent,
     },
     push::{PushConditionRoomCtx, Ruleset},
-    EventId, OwnedEventId, OwnedUserId, RoomVersionId, UserId,
+    EventId, OwnedEventId, OwnedTransactionId, OwnedUserId, RoomVersionId, UserId,
 };
 use tracing::{debug, error, warn};
 
@@ -89,6 +89,9 @@ impl RoomExt for Room {
 pub(super) trait RoomDataProvider: Clone + Send + Sync + 'static {
     fn own_user_id(&self) -> &UserId;
     fn room_version(&self) -> RoomVersionId;
+
+    /// The clock of the client, to timestamp the local echoes.
+    fn clock(&self) -> SharedClock;
     async fn profile_from_user_id(&self, user_id: &UserId) -> Option<Profile>;
     async fn profile_from_latest_event(&self, latest_event: &LatestEvent) -> Option<Profile>;
 
@@ -104,6 +107,10 @@ pub(super) trait RoomDataProvider: Clone + Send + Sync + 'static {
     async fn load_event_receipts(&self, event_id: &EventId) -> IndexMap<OwnedUserId, Receipt>;
 
     async fn push_rules_and_context(&self) -> Option<(Ruleset, PushConditionRoomCtx)>;
+
+    /// Get the transaction ID that was used by this client to send the given
+    /// event, if it is known.
+    async fn transaction_id_for_event(&self, event_id: &EventId) -> Option<OwnedTransactionId>;
 }
 
 #[async_trait]
@@ -119,6 +126,10 @@ impl RoomDataProvider for Room {
         })
     }
 
+    fn clock(&self) -> SharedClock {
+        self.client().clock().clone()
+    }
+
     async fn profile_from_user_id(&self, user_id: &UserId) -> Option<Profile> {
         match self.get_member_no_sync(user_id).await {
             Ok(Some(member)) => Some(Profile {
@@ -213,6 +224,16 @@ impl RoomDataProvider for Room {
             }
         }
     }
+
+    async fn transaction_id_for_event(&self, event_id: &EventId) -> Option<OwnedTransactionId> {
+        match self.transaction_id_for_event(event_id).await {
+            Ok(txn_id) => txn_id,
+            Err(e) => {
+                error!(?event_id, "Failed to get the transaction ID of the event: {e}");
+                None
+            }
+        }
+    }
 }
 
 // Internal helper to make most of retry_event_decryption independent of a room
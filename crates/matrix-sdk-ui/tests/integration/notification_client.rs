@@ -1,10 +1,14 @@
 use std::{
+    collections::HashMap,
     sync::{Arc, Mutex},
     time::Duration,
 };
 
 use assert_matches::assert_matches;
-use matrix_sdk::config::SyncSettings;
+use matrix_sdk::{
+    config::SyncSettings,
+    media::{MediaFormat, MediaRequest, MediaThumbnailSize},
+};
 use matrix_sdk_test::{async_test, sync_timeline_event, JoinedRoomBuilder, SyncResponseBuilder};
 use matrix_sdk_ui::{
     notification_client::{
@@ -12,10 +16,15 @@ use matrix_sdk_ui::{
     },
     sync_service::SyncService,
 };
-use ruma::{event_id, events::TimelineEventType, room_id, user_id};
+use ruma::{
+    api::client::media::get_content_thumbnail::v3::Method,
+    event_id,
+    events::{room::MediaSource, TimelineEventType},
+    mxc_uri, room_id, uint, user_id,
+};
 use serde_json::json;
 use wiremock::{
-    matchers::{header, method, path},
+    matchers::{header, method, path, path_regex},
     Mock, Request, ResponseTemplate,
 };
 
@@ -110,6 +119,71 @@ async fn test_notification_client_with_context() {
     assert_eq!(item.sender_avatar_url.as_deref(), Some("https://example.org/avatar.jpeg"));
 }
 
+#[async_test]
+async fn test_notification_client_with_context_evaluates_push_rules_with_incomplete_state() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let event_id = event_id!("$example_event_id");
+    let sender = user_id!("@user:example.org");
+    // The user isn't mentioned by ID, only by the localpart used as its display
+    // name, since its member event is unknown.
+    let event_json = json!({
+        "content": {
+            "body": "Hello example!",
+            "msgtype": "m.text",
+        },
+        "room_id": room_id,
+        "event_id": event_id,
+        "origin_server_ts": 152049794,
+        "sender": sender,
+        "type": "m.room.message",
+    });
+
+    // The room is only known by a message, without its member and power levels
+    // events.
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id).add_timeline_event(sync_timeline_event!(event_json)),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let dummy_sync_service = Arc::new(SyncService::builder(client.clone()).build().await.unwrap());
+    let process_setup =
+        NotificationProcessSetup::SingleProcess { sync_service: dummy_sync_service };
+    let notification_client = NotificationClient::builder(client, process_setup)
+        .await
+        .unwrap()
+        .filter_by_push_rules()
+        .build();
+
+    // The event is retrieved without push actions, nor state.
+    Mock::given(method("GET"))
+        .and(path(format!("/_matrix/client/r0/rooms/{room_id}/context/{event_id}")))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "event": event_json,
+            "state": [],
+        })))
+        .mount(&server)
+        .await;
+    mock_encryption_state(&server, false).await;
+
+    let item = notification_client.get_notification_with_context(room_id, event_id).await.unwrap();
+
+    server.reset().await;
+
+    // The push rules are evaluated locally with the fallback values, so the event
+    // isn't filtered out and mentions the user.
+    let item = item.expect("the notification should be found");
+    assert_eq!(item.has_mention, Some(true));
+    assert_eq!(item.is_noisy, Some(true));
+}
+
 #[async_test]
 async fn test_notification_client_sliding_sync() {
     let room_id = room_id!("!a98sd12bjh:example.org");
@@ -309,3 +383,214 @@ async fn test_notification_client_sliding_sync() {
     assert_eq!(item.room_display_name, room_name);
     assert_eq!(item.is_noisy, Some(false));
 }
+
+#[async_test]
+async fn test_notification_client_batch() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+
+    let sender = user_id!("@user:example.org");
+    let first_event_id = event_id!("$first_event_id");
+    let second_event_id = event_id!("$second_event_id");
+
+    let message = |event_id, body| {
+        json!({
+            "content": {
+                "body": body,
+                "msgtype": "m.text",
+            },
+            "room_id": room_id,
+            "event_id": event_id,
+            "origin_server_ts": 152049794,
+            "sender": sender,
+            "type": "m.room.message",
+        })
+    };
+
+    let room_name = "The Maltese Falcon";
+    let sender_display_name = "John Mastodon";
+
+    let first_event = message(first_event_id, "Hello world!");
+    let second_event = message(second_event_id, "Hello again!");
+
+    // Both events are retrieved with a single sliding sync request.
+    Mock::given(SlidingSyncMatcher)
+        .respond_with(move |request: &Request| {
+            let partial_request: PartialSlidingSyncRequest = request.body_json().unwrap();
+            ResponseTemplate::new(200).set_body_json(json!({
+                "txn_id": partial_request.txn_id,
+                "pos": "0",
+                "rooms": {
+                    "!a98sd12bjh:example.org": {
+                        "name": room_name,
+                        "initial": true,
+
+                        "required_state": [
+                            {
+                                "content": {
+                                    "displayname": sender_display_name,
+                                    "membership": "join"
+                                },
+                                "room_id": room_id,
+                                "event_id": "$151800140517rfvjc:example.org",
+                                "origin_server_ts": 151800140,
+                                "sender": sender,
+                                "state_key": sender,
+                                "type": "m.room.member",
+                            },
+                        ],
+
+                        "timeline": [
+                            first_event.clone(),
+                            second_event.clone(),
+                        ]
+                    }
+                },
+
+                "extensions": {
+                    "account_data": {}
+                }
+            }))
+        })
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dummy_sync_service = Arc::new(SyncService::builder(client.clone()).build().await.unwrap());
+    let process_setup =
+        NotificationProcessSetup::SingleProcess { sync_service: dummy_sync_service };
+    let notification_client =
+        NotificationClient::builder(client, process_setup).await.unwrap().build();
+
+    let room_to_event_ids = HashMap::from([(
+        room_id.to_owned(),
+        vec![first_event_id.to_owned(), second_event_id.to_owned()],
+    )]);
+    let mut results = notification_client.get_notifications(&room_to_event_ids).await.unwrap();
+
+    assert_eq!(results.len(), 2);
+
+    for event_id in [first_event_id, second_event_id] {
+        let item = results.remove(event_id).unwrap().unwrap().expect("notification not found");
+
+        assert_matches!(item.event, NotificationEvent::Timeline(event) => {
+            assert_eq!(event.event_id(), event_id);
+        });
+        assert_eq!(item.sender_display_name.as_deref(), Some(sender_display_name));
+        assert_eq!(item.room_display_name, room_name);
+    }
+}
+
+#[async_test]
+async fn test_notification_client_media() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+
+    let sender = user_id!("@user:example.org");
+    let image_event_id = event_id!("$image_event_id");
+    let file_event_id = event_id!("$file_event_id");
+
+    let event = |event_id, content| {
+        json!({
+            "content": content,
+            "room_id": room_id,
+            "event_id": event_id,
+            "origin_server_ts": 152049794,
+            "sender": sender,
+            "type": "m.room.message",
+        })
+    };
+
+    let image_event = event(
+        image_event_id,
+        json!({
+            "body": "cat.png",
+            "msgtype": "m.image",
+            "url": "mxc://example.org/image",
+            "info": {
+                "mimetype": "image/png",
+                "size": 4_000_000,
+            },
+        }),
+    );
+    let file_event = event(
+        file_event_id,
+        json!({
+            "body": "Here is the report",
+            "filename": "report.pdf",
+            "msgtype": "m.file",
+            "url": "mxc://example.org/file",
+            "info": {
+                "mimetype": "application/pdf",
+                "size": 12_345,
+            },
+        }),
+    );
+
+    Mock::given(SlidingSyncMatcher)
+        .respond_with(move |request: &Request| {
+            let partial_request: PartialSlidingSyncRequest = request.body_json().unwrap();
+            ResponseTemplate::new(200).set_body_json(json!({
+                "txn_id": partial_request.txn_id,
+                "pos": "0",
+                "rooms": {
+                    "!a98sd12bjh:example.org": {
+                        "initial": true,
+                        "timeline": [
+                            image_event.clone(),
+                            file_event.clone(),
+                        ]
+                    }
+                },
+                "extensions": {
+                    "account_data": {}
+                }
+            }))
+        })
+        .mount(&server)
+        .await;
+
+    // The server scales the image down, so the size of the original image
+    // doesn't matter.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/media/.*/thumbnail/example.org/image"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"thumbnail".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let dummy_sync_service = Arc::new(SyncService::builder(client.clone()).build().await.unwrap());
+    let process_setup =
+        NotificationProcessSetup::SingleProcess { sync_service: dummy_sync_service };
+    let notification_client =
+        NotificationClient::builder(client.clone(), process_setup).await.unwrap().build();
+
+    let room_to_event_ids = HashMap::from([(
+        room_id.to_owned(),
+        vec![image_event_id.to_owned(), file_event_id.to_owned()],
+    )]);
+    let mut results = notification_client.get_notifications(&room_to_event_ids).await.unwrap();
+
+    let image_item = results.remove(image_event_id).unwrap().unwrap().unwrap();
+    let thumbnail = image_item.thumbnail.expect("the thumbnail should have been fetched");
+    assert_eq!(thumbnail.data, b"thumbnail");
+    assert!(image_item.file_info.is_none());
+
+    let file_item = results.remove(file_event_id).unwrap().unwrap().unwrap();
+    assert!(file_item.thumbnail.is_none());
+    let file_info = file_item.file_info.unwrap();
+    assert_eq!(file_info.filename, "report.pdf");
+    assert_eq!(file_info.size, Some(12_345));
+
+    // The thumbnail was saved in the media cache of the client, so it isn't
+    // downloaded again.
+    let request = MediaRequest {
+        source: MediaSource::Plain(mxc_uri!("mxc://example.org/image").to_owned()),
+        format: MediaFormat::Thumbnail(MediaThumbnailSize {
+            method: Method::Scale,
+            width: uint!(800),
+            height: uint!(600),
+        }),
+    };
+    assert_eq!(client.media().get_media_content(&request, true).await.unwrap(), b"thumbnail");
+}
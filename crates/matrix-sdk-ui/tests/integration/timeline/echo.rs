@@ -288,3 +288,56 @@ async fn cancel_failed() {
     // Observable local echo being removed
     assert_matches!(timeline_stream.next().await, Some(VectorDiff::Remove { index: 0 }));
 }
+
+#[async_test]
+async fn forward_message() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let target_room_id = room_id!("!b98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        sync_timeline_event!({
+            "content": {
+                "body": "> <@bob:localhost> Hi\n\nHello, World!",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "<mx-reply><blockquote>Hi</blockquote></mx-reply><b>Hello</b>, World!",
+                "msgtype": "m.text",
+                "m.relates_to": {
+                    "m.in_reply_to": { "event_id": "$reply_target" },
+                },
+            },
+            "event_id": "$7at8sd:localhost",
+            "origin_server_ts": 152038280,
+            "sender": "@bob:localhost",
+            "type": "m.room.message",
+        }),
+    ));
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(target_room_id));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    mock_encryption_state(&server, false).await;
+
+    let timeline = client.get_room(room_id).unwrap().timeline().await;
+    let (items, _) = timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+    let item = items.last().unwrap();
+
+    let target_timeline = client.get_room(target_room_id).unwrap().timeline().await;
+    let (_, mut target_stream) =
+        target_timeline.subscribe_filter_map(|item| item.as_event().cloned()).await;
+
+    timeline.forward(item, &target_timeline).await.unwrap();
+
+    // The local echo is added to the target timeline, without the reply.
+    assert_let!(Some(VectorDiff::PushBack { value: local_echo }) = target_stream.next().await);
+    assert_matches!(local_echo.send_state(), Some(EventSendState::NotSentYet));
+    assert_let!(TimelineItemContent::Message(msg) = local_echo.content());
+    assert!(msg.in_reply_to().is_none());
+    assert_eq!(msg.body(), "Hello, World!");
+    assert_let!(MessageType::Text(text) = msg.msgtype());
+    assert_eq!(text.formatted.as_ref().unwrap().body, "<b>Hello</b>, World!");
+}
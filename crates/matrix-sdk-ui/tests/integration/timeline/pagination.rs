@@ -17,7 +17,10 @@ use std::{sync::Arc, time::Duration};
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use eyeball_im::VectorDiff;
-use futures_util::future::{join, join3};
+use futures_util::{
+    future::{join, join3},
+    StreamExt,
+};
 use matrix_sdk::config::SyncSettings;
 use matrix_sdk_test::{
     async_test, EventBuilder, JoinedRoomBuilder, StateTestEvent, SyncResponseBuilder, ALICE, BOB,
@@ -33,6 +36,7 @@ use ruma::{
         FullStateEventContent,
     },
     room_id,
+    serde::Raw,
 };
 use serde_json::{json, Value as JsonValue};
 use stream_assert::{assert_next_eq, assert_next_matches};
@@ -445,6 +449,76 @@ async fn timeline_reset_while_paginating() {
     server.verify().await;
 }
 
+#[async_test]
+async fn back_pagination_with_event_cache() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(room_id).unwrap();
+    let timeline = room.timeline_builder().use_event_cache().build().await;
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    let message = |event_id: &str, body: &str| {
+        json!({
+            "content": { "body": body, "msgtype": "m.text" },
+            "event_id": event_id,
+            "origin_server_ts": 152037280,
+            "room_id": room_id,
+            "sender": "@alice:example.org",
+            "type": "m.room.message",
+        })
+    };
+
+    // A limited sync leaves a gap in the event cache.
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_timeline_event(Raw::new(&message("$c", "c")).unwrap().cast())
+            .set_timeline_limited()
+            .set_timeline_prev_batch("gap".to_owned()),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    // The event is added to the timeline through the event cache.
+    timeout(Duration::from_secs(1), timeline_stream.next()).await.unwrap();
+
+    // The pagination fills the gap, without duplicating the event that is
+    // already known.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("from", "gap"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "gap",
+            "chunk": [message("$c", "c"), message("$b", "b")],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    timeline.paginate_backwards(PaginationOptions::simple_request(10)).await.unwrap();
+    assert_eq!(timeline.back_pagination_status().get(), BackPaginationStatus::TimelineStartReached);
+
+    let bodies = timeline
+        .items()
+        .await
+        .iter()
+        .filter_map(|item| match item.as_event()?.content() {
+            TimelineItemContent::Message(message) => Some(message.body().to_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(bodies, ["b", "c"]);
+}
+
 pub static ROOM_MESSAGES_BATCH_1: Lazy<JsonValue> = Lazy::new(|| {
     json!({
         "chunk": [
@@ -1047,6 +1047,67 @@ async fn send_multiple_receipts() {
     timeline.send_multiple_receipts(second_receipts.clone()).await.unwrap();
 }
 
+#[async_test]
+async fn send_private_receipts_when_enabled() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(room_id).unwrap();
+    let timeline = room.timeline().await;
+
+    client.set_private_read_receipts(true);
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/receipt/m\.read/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .named("Public read receipt")
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/receipt/m\.read\.private/"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("Private read receipt")
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/read_markers$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({
+            "m.read.private": "$second_receipts_event_id",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .named("Read markers")
+        .mount(&server)
+        .await;
+
+    timeline
+        .send_single_receipt(
+            ReceiptType::Read,
+            ReceiptThread::Unthreaded,
+            event_id!("$first_receipts_event_id").to_owned(),
+        )
+        .await
+        .unwrap();
+    timeline
+        .send_multiple_receipts(
+            Receipts::new().public_read_receipt(event_id!("$second_receipts_event_id").to_owned()),
+        )
+        .await
+        .unwrap();
+}
+
 #[async_test]
 async fn latest_user_read_receipt() {
     let room_id = room_id!("!a98sd12bjh:example.org");
@@ -49,7 +49,12 @@ use ruma::{
 use serde::Deserialize;
 use tracing::error;
 
-use crate::{config::RequestConfig, Client, Error, HttpError, Result};
+use crate::{
+    config::RequestConfig,
+    stickers::ImagePackEventContent,
+    uiaa::{send_with_uiaa, UiaaHandler},
+    Client, Error, HttpError, Result,
+};
 
 /// A high-level API to manage the client owner's account.
 ///
@@ -328,6 +333,33 @@ impl Account {
         Ok(self.client.send(request, None).await?)
     }
 
+    /// Change the password of the account, going through the user-interactive
+    /// authentication with the given handler.
+    ///
+    /// This is the same as [`Account::change_password()`], except that the
+    /// authentication stages required by the server are completed by calling
+    /// the handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_password` - The new password to set.
+    ///
+    /// * `uiaa_handler` - The handler providing the authentication data.
+    pub async fn change_password_with_uiaa(
+        &self,
+        new_password: &str,
+        uiaa_handler: &dyn UiaaHandler,
+    ) -> Result<()> {
+        send_with_uiaa(uiaa_handler, |auth| {
+            let request =
+                assign!(change_password::v3::Request::new(new_password.to_owned()), { auth });
+            self.client.send(request, None)
+        })
+        .await?;
+
+        Ok(())
+    }
+
     /// Deactivate this account definitively.
     ///
     /// # Arguments
@@ -377,6 +409,38 @@ impl Account {
         Ok(self.client.send(request, None).await?)
     }
 
+    /// Deactivate this account definitively, going through the
+    /// user-interactive authentication with the given handler.
+    ///
+    /// # Arguments
+    ///
+    /// * `id_server` - The identity server from which to unbind the user’s
+    /// [Third Party Identifiers][3pid].
+    ///
+    /// * `erase` - Whether the homeserver should also forget all the messages
+    /// sent by the user, so they are not shown to users joining the rooms
+    /// later.
+    ///
+    /// * `uiaa_handler` - The handler providing the authentication data.
+    ///
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn deactivate_with_uiaa(
+        &self,
+        id_server: Option<&str>,
+        erase: bool,
+        uiaa_handler: &dyn UiaaHandler,
+    ) -> Result<deactivate::v3::Response> {
+        Ok(send_with_uiaa(uiaa_handler, |auth| {
+            let request = assign!(deactivate::v3::Request::new(), {
+                id_server: id_server.map(ToOwned::to_owned),
+                erase,
+                auth,
+            });
+            self.client.send(request, None)
+        })
+        .await?)
+    }
+
     /// Get the registered [Third Party Identifiers][3pid] on the homeserver of
     /// the account.
     ///
@@ -599,6 +663,72 @@ impl Account {
         Ok(self.client.send(request, None).await?)
     }
 
+    /// Add a [Third Party Identifier][3pid] on the homeserver for this
+    /// account, going through the user-interactive authentication with the
+    /// given handler.
+    ///
+    /// This is the same as [`Account::add_3pid()`], except that the
+    /// authentication stages required by the server are completed by calling
+    /// the handler.
+    ///
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn add_3pid_with_uiaa(
+        &self,
+        client_secret: &ClientSecret,
+        sid: &SessionId,
+        uiaa_handler: &dyn UiaaHandler,
+    ) -> Result<()> {
+        send_with_uiaa(uiaa_handler, |auth| {
+            let request =
+                assign!(add_3pid::v3::Request::new(client_secret.to_owned(), sid.to_owned()), {
+                    auth
+                });
+            self.client.send(request, None)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// Submit the validation token of a [Third Party Identifier][3pid] that the
+    /// user received.
+    ///
+    /// This must be called between [`Account::request_3pid_email_token()`] or
+    /// [`Account::request_3pid_msisdn_token()`] and [`Account::add_3pid()`]
+    /// when their response contains a `submit_url`. Otherwise, the token is
+    /// submitted by the user directly, for example by following a link.
+    ///
+    /// # Arguments
+    ///
+    /// * `submit_url` - The `submit_url` of the response to the token request.
+    ///
+    /// * `client_secret` - The same client secret used to request the token.
+    ///
+    /// * `sid` - The session ID returned with the `submit_url`.
+    ///
+    /// * `token` - The token received by the user.
+    ///
+    /// # Returns
+    ///
+    /// Whether the token was valid.
+    ///
+    /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
+    pub async fn submit_3pid_token(
+        &self,
+        submit_url: &str,
+        client_secret: &ClientSecret,
+        sid: &SessionId,
+        token: &str,
+    ) -> Result<bool> {
+        let request = submit_3pid_token::Request {
+            submit_url: submit_url.to_owned(),
+            sid: sid.to_owned(),
+            client_secret: client_secret.to_owned(),
+            token: token.to_owned(),
+        };
+        Ok(self.client.send(request, None).await?.success)
+    }
+
     /// Delete a [Third Party Identifier][3pid] from the homeserver for this
     /// account.
     ///
@@ -861,9 +991,10 @@ impl Account {
         ignored_user_list.ignored_users.insert(user_id.to_owned(), IgnoredUser::new());
 
         // Updating the account data
-        self.set_account_data(ignored_user_list).await?;
-        // TODO: I think I should reset all the storage and perform a new local sync
-        // here but I don't know how
+        self.set_account_data(ignored_user_list.clone()).await?;
+        // Update the local state right away, so the timelines and the rooms are
+        // refreshed without waiting for the next sync.
+        self.client.base_client().receive_ignored_user_list(ignored_user_list).await?;
         Ok(())
     }
 
@@ -873,12 +1004,22 @@ impl Account {
         ignored_user_list.ignored_users.remove(user_id);
 
         // Updating the account data
-        self.set_account_data(ignored_user_list).await?;
-        // TODO: I think I should reset all the storage and perform a new local sync
-        // here but I don't know how
+        self.set_account_data(ignored_user_list.clone()).await?;
+        // Update the local state right away, so the timelines and the rooms are
+        // refreshed without waiting for the next sync.
+        self.client.base_client().receive_ignored_user_list(ignored_user_list).await?;
         Ok(())
     }
 
+    /// Get the users in the account's ignore list, as they are known locally.
+    ///
+    /// Use [`Client::subscribe_to_ignore_user_list_changes()`] to be notified
+    /// when this list changes.
+    pub async fn ignored_users(&self) -> Result<Vec<OwnedUserId>> {
+        let ignored_user_list = self.get_ignored_user_list_event_content().await?;
+        Ok(ignored_user_list.ignored_users.into_keys().collect())
+    }
+
     async fn get_ignored_user_list_event_content(&self) -> Result<IgnoredUserListEventContent> {
         let ignored_user_list = self
             .account_data::<IgnoredUserListEventContent>()
@@ -912,6 +1053,25 @@ impl Account {
                 )
             }))
     }
+
+    /// Get the personal sticker and emoji pack of the user, as it is known
+    /// locally.
+    ///
+    /// Returns an empty pack if the user doesn't have one yet.
+    pub async fn image_pack(&self) -> Result<ImagePackEventContent> {
+        Ok(self
+            .account_data::<ImagePackEventContent>()
+            .await?
+            .map(|c| c.deserialize())
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    /// Replace the personal sticker and emoji pack of the user.
+    pub async fn set_image_pack(&self, pack: ImagePackEventContent) -> Result<()> {
+        self.set_account_data(pack).await?;
+        Ok(())
+    }
 }
 
 fn get_raw_content<Ev, C>(raw: Option<Raw<Ev>>) -> Result<Option<Raw<C>>> {
@@ -926,3 +1086,74 @@ fn get_raw_content<Ev, C>(raw: Option<Raw<Ev>>) -> Result<Option<Raw<C>>> {
         .transpose()?
         .map(|get_raw| get_raw.content))
 }
+
+/// The submission of a 3PID validation token, to the URL given by the server
+/// when it requested the token.
+///
+/// The URL is not necessarily on the homeserver, so the request is not
+/// authenticated.
+mod submit_3pid_token {
+    use bytes::BufMut;
+    use http::header::CONTENT_TYPE;
+    use ruma::{
+        api::{
+            error::IntoHttpError, response, MatrixVersion, Metadata, OutgoingRequest,
+            SendAccessToken,
+        },
+        metadata, OwnedClientSecret, OwnedSessionId,
+    };
+    use serde_json::json;
+
+    // The path is not used, the request is sent to `submit_url`.
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: false,
+        authentication: None,
+        history: {
+            1.0 => "/_matrix/client/v3/submit_token",
+        }
+    };
+
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        pub submit_url: String,
+        pub sid: OwnedSessionId,
+        pub client_secret: OwnedClientSecret,
+        pub token: String,
+    }
+
+    impl OutgoingRequest for Request {
+        type EndpointError = ruma::api::client::Error;
+        type IncomingResponse = Response;
+
+        const METADATA: Metadata = METADATA;
+
+        fn try_into_http_request<T: Default + BufMut>(
+            self,
+            _base_url: &str,
+            _access_token: SendAccessToken<'_>,
+            _considering_versions: &[MatrixVersion],
+        ) -> Result<http::Request<T>, IntoHttpError> {
+            let body = json!({
+                "sid": self.sid,
+                "client_secret": self.client_secret,
+                "token": self.token,
+            });
+
+            let mut buf = T::default();
+            buf.put_slice(&serde_json::to_vec(&body)?);
+
+            Ok(http::Request::builder()
+                .method(http::Method::POST)
+                .uri(self.submit_url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(buf)?)
+        }
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        /// Whether the token was valid.
+        pub success: bool,
+    }
+}
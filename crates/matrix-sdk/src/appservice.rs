@@ -0,0 +1,552 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Acting as the users of an [application service].
+//!
+//! A [`Client`] logged in with the token of an application service can create
+//! clients for the users in its namespace with [`Client::for_user()`], and
+//! process the transactions pushed by the homeserver with
+//! [`Client::receive_transaction()`].
+//!
+//! [application service]: https://spec.matrix.org/latest/application-service-api/
+
+use std::collections::BTreeMap;
+
+use matrix_sdk_base::{
+    store::{DynStateStore, StoreConfig},
+    BaseClient, SessionMeta,
+};
+use ruma::{
+    api::{
+        appservice::event::push_events,
+        client::{
+            device::update_device,
+            session::login::{
+                self,
+                v3::{ApplicationService, LoginInfo},
+            },
+            sync::sync_events::v3::{self as sync_events, JoinedRoom},
+            uiaa::UserIdentifier,
+        },
+    },
+    assign,
+    events::{AnyTimelineEvent, StateEventType},
+    serde::Raw,
+    DeviceId, OwnedDeviceId, OwnedRoomId, OwnedUserId, UserId,
+};
+use tracing::warn;
+
+use crate::{
+    http_client::{AssertedIdentity, HttpClient},
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    Client, Error, Result,
+};
+
+impl Client {
+    /// Create a builder for a client acting as the given user, with the token
+    /// of the application service this client is logged in with.
+    ///
+    /// By default, the client asserts the identity of the user in every
+    /// request, with an in-memory store. The first time, a device is created
+    /// for the user on the homeserver, and its ID is kept in the store to be
+    /// reused by the next clients with the same store.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of a user in the namespace of the application
+    ///   service.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use ruma::user_id;
+    /// # async {
+    /// # let appservice_client: Client = unimplemented!();
+    /// let client = appservice_client
+    ///     .for_user(user_id!("@_bridge_alice:example.org"))
+    ///     .build()
+    ///     .await?;
+    /// client.account().set_display_name(Some("Alice (Bridged)")).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn for_user(&self, user_id: &UserId) -> VirtualUserBuilder {
+        VirtualUserBuilder {
+            client: self.clone(),
+            user_id: user_id.to_owned(),
+            device_id: None,
+            store_config: StoreConfig::default(),
+            login: false,
+        }
+    }
+
+    /// Process a transaction pushed by the homeserver to the application
+    /// service, like the response of a sync.
+    ///
+    /// The event handlers of the client are called for the events of the
+    /// transaction.
+    ///
+    /// When the homeserver supports [MSC2409] and [MSC3202], the to-device
+    /// events, device list changes and one-time key counts of the device of
+    /// this client are processed too, which allows the users of the
+    /// application service to use end-to-end encryption.
+    ///
+    /// [MSC2409]: https://github.com/matrix-org/matrix-spec-proposals/pull/2409
+    /// [MSC3202]: https://github.com/matrix-org/matrix-spec-proposals/pull/3202
+    pub async fn receive_transaction(&self, transaction: &push_events::v1::Request) -> Result<()> {
+        let user_id = self.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        let device_id = self.device_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+
+        let mut response = sync_events::Response::new(transaction.txn_id.to_string());
+
+        for event in &transaction.events {
+            let Some(room_id) = room_id_of(event) else {
+                warn!("Received an event without a room ID in a transaction");
+                continue;
+            };
+
+            response
+                .rooms
+                .join
+                .entry(room_id)
+                .or_insert_with(JoinedRoom::new)
+                .timeline
+                .events
+                .push(event.clone().cast());
+        }
+
+        for event in &transaction.ephemeral {
+            let Ok(Some(room_id)) = event.get_field::<OwnedRoomId>("room_id") else {
+                warn!("Received an ephemeral event without a room ID in a transaction");
+                continue;
+            };
+
+            response
+                .rooms
+                .join
+                .entry(room_id)
+                .or_insert_with(JoinedRoom::new)
+                .ephemeral
+                .events
+                .push(event.clone().cast());
+        }
+
+        response.to_device.events = transaction
+            .to_device
+            .iter()
+            .filter(|event| is_for_device(event, &user_id, &device_id))
+            .cloned()
+            .collect();
+        response.device_lists = transaction.device_lists.clone();
+        response.device_one_time_keys_count = transaction
+            .device_one_time_keys_count
+            .get(&user_id)
+            .and_then(|devices| devices.get(&device_id))
+            .cloned()
+            .unwrap_or_default();
+        response.device_unused_fallback_key_types = transaction
+            .device_unused_fallback_key_types
+            .get(&user_id)
+            .and_then(|devices| devices.get(&device_id))
+            .cloned();
+
+        self.process_sync(response).await?;
+
+        #[cfg(feature = "e2e-encryption")]
+        if let Err(error) = self.send_outgoing_requests().await {
+            warn!("Error while sending outgoing E2EE requests: {error}");
+        }
+
+        Ok(())
+    }
+
+    /// Keep only the parts of the transaction that concern the user of this
+    /// client: the events of the rooms it knows about or of its own
+    /// membership, and the end-to-end encryption data of its device.
+    ///
+    /// This is used to dispatch a transaction received by the application
+    /// service to the clients of its users.
+    pub fn filter_transaction(
+        &self,
+        transaction: &push_events::v1::Request,
+    ) -> Option<push_events::v1::Request> {
+        let user_id = self.user_id()?;
+
+        let events: Vec<_> = transaction
+            .events
+            .iter()
+            .filter(|event| {
+                room_id_of(event).is_some_and(|room_id| self.get_room(&room_id).is_some())
+                    || is_membership_of(event, user_id)
+            })
+            .cloned()
+            .collect();
+        let ephemeral: Vec<_> = transaction
+            .ephemeral
+            .iter()
+            .filter(|event| {
+                event
+                    .get_field::<OwnedRoomId>("room_id")
+                    .ok()
+                    .flatten()
+                    .is_some_and(|room_id| self.get_room(&room_id).is_some())
+            })
+            .cloned()
+            .collect();
+        let to_device: Vec<_> = transaction
+            .to_device
+            .iter()
+            .filter(|event| {
+                event.get_field::<OwnedUserId>("to_user_id").ok().flatten().as_deref()
+                    == Some(user_id)
+            })
+            .cloned()
+            .collect();
+
+        let device_one_time_keys_count: BTreeMap<_, _> = transaction
+            .device_one_time_keys_count
+            .get_key_value(user_id)
+            .map(|(user_id, counts)| (user_id.clone(), counts.clone()))
+            .into_iter()
+            .collect();
+        let device_unused_fallback_key_types: BTreeMap<_, _> = transaction
+            .device_unused_fallback_key_types
+            .get_key_value(user_id)
+            .map(|(user_id, types)| (user_id.clone(), types.clone()))
+            .into_iter()
+            .collect();
+
+        let has_device_lists = !transaction.device_lists.changed.is_empty()
+            || !transaction.device_lists.left.is_empty();
+
+        if events.is_empty()
+            && ephemeral.is_empty()
+            && to_device.is_empty()
+            && device_one_time_keys_count.is_empty()
+            && !has_device_lists
+        {
+            return None;
+        }
+
+        Some(assign!(push_events::v1::Request::new(transaction.txn_id.clone(), events), {
+            ephemeral,
+            to_device,
+            device_lists: transaction.device_lists.clone(),
+            device_one_time_keys_count,
+            device_unused_fallback_key_types,
+        }))
+    }
+}
+
+fn room_id_of(event: &Raw<AnyTimelineEvent>) -> Option<OwnedRoomId> {
+    event.get_field("room_id").ok().flatten()
+}
+
+fn is_membership_of(event: &Raw<AnyTimelineEvent>, user_id: &UserId) -> bool {
+    event.get_field::<StateEventType>("type").ok().flatten() == Some(StateEventType::RoomMember)
+        && event.get_field::<OwnedUserId>("state_key").ok().flatten().as_deref() == Some(user_id)
+}
+
+fn is_for_device<T>(event: &Raw<T>, user_id: &UserId, device_id: &DeviceId) -> bool {
+    event.get_field::<OwnedUserId>("to_user_id").ok().flatten().as_deref() == Some(user_id)
+        && event.get_field::<OwnedDeviceId>("to_device_id").ok().flatten().as_deref()
+            == Some(device_id)
+}
+
+/// Builder for a [`Client`] acting as a user of an application service.
+///
+/// It is created with [`Client::for_user()`].
+#[derive(Debug)]
+pub struct VirtualUserBuilder {
+    client: Client,
+    user_id: OwnedUserId,
+    device_id: Option<OwnedDeviceId>,
+    store_config: StoreConfig,
+    login: bool,
+}
+
+impl VirtualUserBuilder {
+    /// Set the ID of the device of the user.
+    ///
+    /// When the client asserts the identity of the user, the device must
+    /// already exist and the homeserver must support [MSC3202] to use it.
+    /// When it is not set, a device is created the first time the client is
+    /// built.
+    ///
+    /// [MSC3202]: https://github.com/matrix-org/matrix-spec-proposals/pull/3202
+    pub fn device_id(mut self, device_id: &DeviceId) -> Self {
+        self.device_id = Some(device_id.to_owned());
+        self
+    }
+
+    /// Set the stores of the client.
+    ///
+    /// The stores should be persistent to use end-to-end encryption, since the
+    /// keys of the device are in the crypto store.
+    pub fn store_config(mut self, store_config: StoreConfig) -> Self {
+        self.store_config = store_config;
+        self
+    }
+
+    /// Log the user in with the `m.login.application_service` login type,
+    /// instead of asserting its identity.
+    ///
+    /// This creates a new device, or reuses the one set with
+    /// [`device_id()`](Self::device_id), and the client uses the access
+    /// token of that device.
+    pub fn login(mut self) -> Self {
+        self.login = true;
+        self
+    }
+
+    /// Build the client.
+    pub async fn build(self) -> Result<Client> {
+        let Self { client, user_id, device_id, store_config, login } = self;
+
+        let appservice_token = client.access_token().ok_or(Error::AuthenticationRequired)?;
+        let mut http_client = client.inner.http_client.clone();
+        let base_client = BaseClient::with_store_config(store_config);
+
+        let (device_id, access_token) = if login {
+            let request = assign!(
+                login::v3::Request::new(LoginInfo::ApplicationService(ApplicationService::new(
+                    UserIdentifier::UserIdOrLocalpart(user_id.to_string()),
+                ))),
+                { device_id }
+            );
+            // The login endpoint doesn't require authentication, but the token of the
+            // application service must be sent with this login type.
+            let response = client.send(request, Some(client.request_config().force_auth())).await?;
+
+            (response.device_id, response.access_token)
+        } else {
+            let device_id = match device_id {
+                Some(device_id) => device_id,
+                None => {
+                    virtual_device_id(
+                        &client,
+                        &http_client,
+                        &user_id,
+                        &appservice_token,
+                        base_client.store(),
+                    )
+                    .await?
+                }
+            };
+
+            http_client.asserted_identity = Some(AssertedIdentity {
+                user_id: user_id.clone(),
+                device_id: Some(device_id.clone()),
+            });
+
+            (device_id, appservice_token)
+        };
+
+        let user_client = client.new_for_user(http_client, base_client);
+
+        user_client
+            .matrix_auth()
+            .restore_session(MatrixSession {
+                meta: SessionMeta { user_id, device_id },
+                tokens: MatrixSessionTokens { access_token, refresh_token: None },
+            })
+            .await?;
+
+        Ok(user_client)
+    }
+}
+
+/// The key of the custom value of the store holding the ID of the device of a
+/// virtual user.
+const VIRTUAL_DEVICE_ID_KEY: &[u8] = b"appservice_virtual_device_id";
+
+/// Get the ID of the device of the virtual user from its store, or create a
+/// new device on the homeserver and remember its ID.
+async fn virtual_device_id(
+    client: &Client,
+    http_client: &HttpClient,
+    user_id: &UserId,
+    appservice_token: &str,
+    store: &DynStateStore,
+) -> Result<OwnedDeviceId> {
+    if let Some(device_id) = store.get_custom_value(VIRTUAL_DEVICE_ID_KEY).await? {
+        match String::from_utf8(device_id) {
+            Ok(device_id) => return Ok(device_id.into()),
+            Err(error) => warn!("Ignoring an invalid stored device ID: {error}"),
+        }
+    }
+
+    let device_id = DeviceId::new();
+
+    // Application services can create the devices of their users by updating
+    // them, as proposed in MSC4190. The device can't be asserted yet.
+    let mut http_client = http_client.clone();
+    http_client.asserted_identity =
+        Some(AssertedIdentity { user_id: user_id.to_owned(), device_id: None });
+    http_client
+        .send(
+            update_device::v3::Request::new(device_id.clone()),
+            None,
+            client.homeserver().to_string(),
+            Some(appservice_token),
+            client.server_versions().await?,
+            Default::default(),
+        )
+        .await?;
+
+    store.set_custom_value(VIRTUAL_DEVICE_ID_KEY, device_id.as_str().as_bytes().to_vec()).await?;
+
+    Ok(device_id)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::Arc;
+
+    use matrix_sdk_base::store::{MemoryStore, StoreConfig};
+    use matrix_sdk_test::async_test;
+    use ruma::{
+        api::appservice::event::push_events, device_id, room_id, serde::Raw, user_id, TransactionId,
+    };
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path, path_regex, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        matrix_auth::{MatrixSession, MatrixSessionTokens},
+        test_utils::test_client_builder,
+        Client,
+    };
+
+    async fn appservice_client(server: &MockServer) -> Client {
+        let client = test_client_builder(Some(server.uri())).build().await.unwrap();
+        client
+            .matrix_auth()
+            .restore_session(MatrixSession {
+                meta: matrix_sdk_base::SessionMeta {
+                    user_id: user_id!("@bridge:localhost").to_owned(),
+                    device_id: device_id!("BRIDGE").to_owned(),
+                },
+                tokens: MatrixSessionTokens {
+                    access_token: "as_token".to_owned(),
+                    refresh_token: None,
+                },
+            })
+            .await
+            .unwrap();
+        client
+    }
+
+    async fn mock_create_device(server: &MockServer) {
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/devices/"))
+            .and(query_param("user_id", "@_bridge_alice:localhost"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(server)
+            .await;
+    }
+
+    #[async_test]
+    async fn test_virtual_user_asserts_identity() {
+        let server = MockServer::start().await;
+        let client = appservice_client(&server).await;
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/devices/"))
+            .and(query_param("user_id", "@_bridge_alice:localhost"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .named("create_device")
+            .mount(&server)
+            .await;
+
+        let store = Arc::new(MemoryStore::new());
+        let alice = client
+            .for_user(user_id!("@_bridge_alice:localhost"))
+            .store_config(StoreConfig::new().state_store(store.clone()))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(alice.user_id(), Some(user_id!("@_bridge_alice:localhost")));
+        assert_eq!(alice.access_token().as_deref(), Some("as_token"));
+        let device_id = alice.device_id().unwrap().to_owned();
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/account/whoami"))
+            .and(query_param("user_id", "@_bridge_alice:localhost"))
+            .and(query_param("org.matrix.msc3202.device_id", device_id.as_str()))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "user_id": "@_bridge_alice:localhost" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        alice.whoami().await.unwrap();
+
+        // The device is reused by a client with the same store, without creating
+        // it again.
+        let alice = client
+            .for_user(user_id!("@_bridge_alice:localhost"))
+            .store_config(StoreConfig::new().state_store(store))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(alice.device_id(), Some(&*device_id));
+    }
+
+    #[async_test]
+    async fn test_filter_transaction() {
+        let server = MockServer::start().await;
+        let client = appservice_client(&server).await;
+        mock_create_device(&server).await;
+        let alice = client.for_user(user_id!("@_bridge_alice:localhost")).build().await.unwrap();
+
+        let membership = Raw::new(&json!({
+            "type": "m.room.member",
+            "state_key": "@_bridge_alice:localhost",
+            "sender": "@_bridge_alice:localhost",
+            "room_id": "!room:localhost",
+            "event_id": "$member",
+            "origin_server_ts": 1,
+            "content": { "membership": "join" },
+        }))
+        .unwrap()
+        .cast();
+        let message = Raw::new(&json!({
+            "type": "m.room.message",
+            "sender": "@bob:localhost",
+            "room_id": "!other:localhost",
+            "event_id": "$message",
+            "origin_server_ts": 2,
+            "content": { "msgtype": "m.text", "body": "hello" },
+        }))
+        .unwrap()
+        .cast();
+
+        let transaction =
+            push_events::v1::Request::new(TransactionId::new(), vec![membership, message]);
+
+        // Only the membership of alice concerns her.
+        let filtered = alice.filter_transaction(&transaction).unwrap();
+        assert_eq!(filtered.events.len(), 1);
+
+        alice.receive_transaction(&filtered).await.unwrap();
+        assert!(alice.get_room(room_id!("!room:localhost")).is_some());
+        assert!(alice.get_room(room_id!("!other:localhost")).is_none());
+    }
+}
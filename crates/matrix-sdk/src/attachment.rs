@@ -19,7 +19,7 @@ use std::io::{BufRead, Cursor, Seek};
 use std::time::Duration;
 
 #[cfg(feature = "image-proc")]
-use image::GenericImageView;
+use image::{DynamicImage, GenericImageView};
 use ruma::{
     assign,
     events::room::{
@@ -184,6 +184,19 @@ pub struct Thumbnail {
     pub info: Option<BaseThumbnailInfo>,
 }
 
+/// The data of an attachment to upload.
+pub(crate) enum AttachmentData {
+    /// The data is in memory.
+    Bytes(Vec<u8>),
+    /// The data is read from a reader as it is uploaded.
+    #[cfg(not(target_arch = "wasm32"))]
+    Stream {
+        reader: Box<dyn futures_util::io::AsyncRead + Send + Sync + Unpin>,
+        /// The size of the data, in bytes.
+        size: u64,
+    },
+}
+
 /// Configuration for sending an attachment.
 #[derive(Debug)]
 pub struct AttachmentConfig {
@@ -214,7 +227,9 @@ impl AttachmentConfig {
 
     /// Generate the thumbnail to send for this media.
     ///
-    /// Uses [`generate_image_thumbnail()`].
+    /// Uses [`generate_image_thumbnail()`]. The metadata of the image that is
+    /// not set with [`AttachmentConfig::info()`] is also filled in, like with
+    /// [`generate_image_info()`].
     ///
     /// Thumbnails can only be generated for supported image attachments. For
     /// more information, see the [image](https://github.com/image-rs/image)
@@ -346,14 +361,89 @@ pub fn generate_image_thumbnail<R: BufRead + Seek>(
     reader: R,
     size: Option<(u32, u32)>,
 ) -> Result<(Vec<u8>, BaseThumbnailInfo), ImageError> {
-    let image_format = image::ImageFormat::from_mime_type(content_type);
-    if image_format.is_none() {
-        return Err(ImageError::FormatNotSupported);
+    let image_format = image_format(content_type)?;
+    let image = image::load(reader, image_format)?;
+
+    thumbnail_from_image(&image, image_format, size)
+}
+
+/// Compute the metadata of an image.
+///
+/// The dimensions and the size of the image are filled in, as well as its
+/// [BlurHash](https://blurha.sh/).
+///
+/// This is a convenience method that uses the
+/// [image](https://github.com/image-rs/image) crate.
+///
+/// # Arguments
+/// * `content_type` - The type of the image.
+///
+/// * `data` - The raw bytes of the image.
+#[cfg(feature = "image-proc")]
+pub fn generate_image_info(
+    content_type: &mime::Mime,
+    data: &[u8],
+) -> Result<BaseImageInfo, ImageError> {
+    let image = image::load_from_memory_with_format(data, image_format(content_type)?)?;
+    Ok(image_info(&image, data.len()))
+}
+
+/// Compute the metadata of an image and generate its thumbnail, decoding it
+/// only once.
+///
+/// The thumbnail is `None` if it would be bigger than the original image.
+#[cfg(feature = "image-proc")]
+pub(crate) fn process_image(
+    content_type: &mime::Mime,
+    data: &[u8],
+    thumbnail_size: Option<(u32, u32)>,
+) -> Result<(BaseImageInfo, Option<(Vec<u8>, BaseThumbnailInfo)>), ImageError> {
+    let image_format = image_format(content_type)?;
+    let image = image::load_from_memory_with_format(data, image_format)?;
+    let info = image_info(&image, data.len());
+
+    let thumbnail = match thumbnail_from_image(&image, image_format, thumbnail_size) {
+        Ok(thumbnail) => Some(thumbnail),
+        Err(ImageError::ThumbnailBiggerThanOriginal) => None,
+        Err(error) => return Err(error),
+    };
+
+    Ok((info, thumbnail))
+}
+
+#[cfg(feature = "image-proc")]
+fn image_format(content_type: &mime::Mime) -> Result<image::ImageFormat, ImageError> {
+    image::ImageFormat::from_mime_type(content_type).ok_or(ImageError::FormatNotSupported)
+}
+
+#[cfg(feature = "image-proc")]
+fn image_info(image: &DynamicImage, size: usize) -> BaseImageInfo {
+    let (width, height) = image.dimensions();
+
+    BaseImageInfo {
+        height: Some(height.into()),
+        width: Some(width.into()),
+        size: UInt::new(size as u64),
+        blurhash: compute_blurhash(image),
     }
+}
 
-    let image_format = image_format.unwrap();
+/// Compute the BlurHash of an image.
+///
+/// The hash only encodes a few components, so it is computed from a small
+/// version of the image to be fast.
+#[cfg(feature = "image-proc")]
+fn compute_blurhash(image: &DynamicImage) -> Option<String> {
+    let small = image.thumbnail(32, 32).to_rgba8();
+    blurhash::encode(4, 3, small.width(), small.height(), small.as_raw()).ok()
+}
 
-    let image = image::load(reader, image_format)?;
+#[cfg(feature = "image-proc")]
+fn thumbnail_from_image(
+    image: &DynamicImage,
+    image_format: image::ImageFormat,
+    size: Option<(u32, u32)>,
+) -> Result<(Vec<u8>, BaseThumbnailInfo), ImageError> {
     let (original_width, original_height) = image.dimensions();
 
     let (width, height) = size.unwrap_or((800, 600));
@@ -380,3 +470,48 @@ pub fn generate_image_thumbnail<R: BufRead + Seek>(
         },
     ))
 }
+
+#[cfg(all(test, feature = "image-proc"))]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+    use ruma::uint;
+
+    use super::{generate_image_info, process_image};
+
+    fn png_image(width: u32, height: u32) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([200, 0, 0])));
+        let mut data = Vec::new();
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_generate_image_info() {
+        let data = png_image(120, 80);
+        let info = generate_image_info(&mime::IMAGE_PNG, &data).unwrap();
+
+        assert_eq!(info.width, Some(uint!(120)));
+        assert_eq!(info.height, Some(uint!(80)));
+        assert_eq!(info.size, Some((data.len() as u32).into()));
+        assert!(info.blurhash.is_some());
+    }
+
+    #[test]
+    fn test_process_image() {
+        let data = png_image(120, 80);
+
+        let (info, thumbnail) = process_image(&mime::IMAGE_PNG, &data, Some((60, 60))).unwrap();
+        assert_eq!(info.width, Some(uint!(120)));
+        let (_, thumbnail_info) = thumbnail.unwrap();
+        assert_eq!(thumbnail_info.width, Some(uint!(60)));
+        assert_eq!(thumbnail_info.height, Some(uint!(40)));
+
+        // The image is too small to have a thumbnail, but its info is still
+        // computed.
+        let (info, thumbnail) = process_image(&mime::IMAGE_PNG, &data, None).unwrap();
+        assert_eq!(info.height, Some(uint!(80)));
+        assert!(thumbnail.is_none());
+    }
+}
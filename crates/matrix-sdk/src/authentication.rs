@@ -12,18 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::pin::Pin;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
 
 use as_variant::as_variant;
+use async_trait::async_trait;
+use eyeball::SharedObservable;
 use futures_core::Future;
 use matrix_sdk_base::SessionMeta;
+use matrix_sdk_common::{
+    instant::Instant,
+    time::{Clock, SharedClock},
+    AsyncTraitDeps,
+};
 use tokio::sync::{broadcast, Mutex, OnceCell};
+use tracing::error;
 
 #[cfg(feature = "experimental-oidc")]
 use crate::oidc::{self, Oidc, OidcAuthData, OidcCtx};
 use crate::{
     matrix_auth::{self, MatrixAuth, MatrixAuthData},
-    Client, RefreshTokenError, SessionChange,
+    Client, RefreshTokenError, SessionChange, SessionState,
 };
 
 /// Session tokens, for any kind of authentication.
@@ -43,6 +56,84 @@ pub(crate) type SaveSessionCallback = dyn Fn(Client) -> Pin<Box<dyn Send + Sync
 pub(crate) type ReloadSessionCallback =
     dyn Fn(Client) -> Result<SessionTokens, SessionCallbackError> + Send + Sync;
 
+/// Why the session of a [`Client`] is saved with its
+/// [`SessionStoreDelegate`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionSaveReason {
+    /// The client logged into a new session, with a new device ID.
+    Login,
+    /// The access token was refreshed.
+    TokensRefreshed,
+    /// The client logged back into the same device after a soft logout, with
+    /// new tokens.
+    SoftLogoutRecovered,
+}
+
+/// A delegate persisting the session of a [`Client`] in the storage of the
+/// application.
+///
+/// It can be set with [`Client::set_session_store_delegate()`], and is the
+/// single place where the session needs to be persisted: it is called every
+/// time the tokens, the device ID or the OpenID Connect session data change.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait SessionStoreDelegate: AsyncTraitDeps {
+    /// Load the current session tokens from the storage of the application.
+    ///
+    /// This is used as the source of truth when the tokens might have been
+    /// refreshed by another process, so it is only required in setups with
+    /// multiple processes. The default implementation returns an error.
+    fn reload_session(
+        &self,
+        client: &Client,
+    ) -> Result<SessionTokens, Box<dyn std::error::Error + Send + Sync>> {
+        let _ = client;
+        Err("reloading the session is not supported by this delegate".into())
+    }
+
+    /// Save the current session of the client in the storage of the
+    /// application.
+    ///
+    /// It can be obtained with [`Client::session()`]. The session was already
+    /// updated in the client when this is called.
+    async fn save_session(
+        &self,
+        client: &Client,
+        reason: SessionSaveReason,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// A [`SessionStoreDelegate`] wrapping the callbacks set with
+/// [`Client::set_session_callbacks()`].
+pub(crate) struct SessionCallbacks {
+    pub(crate) reload_session_callback: Box<ReloadSessionCallback>,
+    pub(crate) save_session_callback: Box<SaveSessionCallback>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SessionCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCallbacks").finish_non_exhaustive()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SessionStoreDelegate for SessionCallbacks {
+    fn reload_session(&self, client: &Client) -> Result<SessionTokens, SessionCallbackError> {
+        (self.reload_session_callback)(client.clone())
+    }
+
+    async fn save_session(
+        &self,
+        client: &Client,
+        _reason: SessionSaveReason,
+    ) -> Result<(), SessionCallbackError> {
+        (self.save_session_callback)(client.clone()).await
+    }
+}
+
 /// All the data relative to authentication, and that must be shared between a
 /// client and all its children.
 pub(crate) struct AuthCtx {
@@ -56,28 +147,70 @@ pub(crate) struct AuthCtx {
     /// Lock making sure we're only doing one token refresh at a time.
     pub(crate) refresh_token_lock: Mutex<Result<(), RefreshTokenError>>,
 
+    /// When the access token should be refreshed because it is about to
+    /// expire, if its lifetime is known.
+    pub(crate) access_token_refresh_at: StdMutex<Option<Instant>>,
+
     /// Session change publisher. Allows the subscriber to handle changes to the
     /// session such as logging out when the access token is invalid or
     /// persisting updates to the access/refresh tokens.
     pub(crate) session_change_sender: broadcast::Sender<SessionChange>,
 
+    /// The current state of the session.
+    pub(crate) session_state: SharedObservable<SessionState>,
+
     /// Authentication data to keep in memory.
     pub(crate) auth_data: OnceCell<AuthData>,
 
-    /// A callback called whenever we need an absolute source of truth for the
-    /// current session tokens.
-    ///
-    /// This is required only in multiple processes setups.
-    pub(crate) reload_session_callback: OnceCell<Box<ReloadSessionCallback>>,
+    /// The delegate persisting the session into the app's secure storage, and
+    /// used as an absolute source of truth for the current session tokens in
+    /// multiple processes setups.
+    pub(crate) session_store_delegate: OnceCell<Arc<dyn SessionStoreDelegate>>,
+
+    /// The source of time of the client, to know when the access token
+    /// expires.
+    pub(crate) clock: SharedClock,
+}
 
-    /// A callback to save a session back into the app's secure storage.
+impl AuthCtx {
+    /// The longest time before the expiration of the access token at which it
+    /// is refreshed.
+    const MAX_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+    /// Remember the lifetime of the new access token, or forget the one of the
+    /// previous token if it is unknown.
+    pub(crate) fn set_access_token_lifetime(&self, expires_in: Option<Duration>) {
+        *self.access_token_refresh_at.lock().unwrap() = expires_in.map(|expires_in| {
+            let margin = (expires_in / 2).min(Self::MAX_REFRESH_MARGIN);
+            self.clock.now() + (expires_in - margin)
+        });
+    }
+
+    /// Whether the access token is about to expire, and should be refreshed
+    /// before sending new requests.
+    pub(crate) fn access_token_needs_refresh(&self) -> bool {
+        self.access_token_refresh_at
+            .lock()
+            .unwrap()
+            .is_some_and(|refresh_at| refresh_at <= self.clock.now())
+    }
+}
+
+impl Client {
+    /// Save the session with the [`SessionStoreDelegate`], if there is one.
     ///
     /// This is always called, independently of the presence of a cross-process
     /// lock.
     ///
-    /// Internal invariant: this must be called only after `set_session_tokens`
-    /// has been called, not before.
-    pub(crate) save_session_callback: OnceCell<Box<SaveSessionCallback>>,
+    /// Internal invariant: this must be called only after the session was
+    /// updated in the client, not before.
+    pub(crate) async fn save_session(&self, reason: SessionSaveReason) {
+        if let Some(delegate) = self.inner.auth_ctx.session_store_delegate.get() {
+            if let Err(err) = delegate.save_session(self, reason).await {
+                error!(?reason, "Failed to save the session: {err}");
+            }
+        }
+    }
 }
 
 /// An enum over all the possible authentication APIs.
@@ -13,27 +13,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{fmt, sync::Arc};
+use std::{any::type_name, collections::HashMap, fmt, sync::Arc, time::Duration};
 
-use matrix_sdk_base::{store::StoreConfig, BaseClient};
+use eyeball::SharedObservable;
+use matrix_sdk_base::{store::StoreConfig, BaseClient, StateStoreDataKey, StateStoreDataValue};
+use matrix_sdk_common::time::{Clock, SharedClock};
+#[cfg(feature = "experimental-oidc")]
+use ruma::api::client::discovery::discover_homeserver::AuthenticationServerInfo;
 use ruma::{
-    api::{client::discovery::discover_homeserver, error::FromHttpResponseError, MatrixVersion},
+    api::{error::FromHttpResponseError, MatrixVersion, OutgoingRequest},
     OwnedServerName, ServerName,
 };
 use thiserror::Error;
 use tokio::sync::{broadcast, Mutex, OnceCell};
-use tracing::{debug, field::debug, instrument, Span};
+use tracing::{debug, field::debug, instrument, warn, Span};
 use url::Url;
 
-use super::{Client, ClientInner};
+use super::{
+    discovery::{discover_homeserver, DiscoveryCtx},
+    Client, ClientInner, SessionState,
+};
 #[cfg(feature = "e2e-encryption")]
 use crate::encryption::EncryptionSettings;
+#[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+use crate::http_client::CertificatePinFailure;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::http_client::HttpSettings;
 #[cfg(feature = "experimental-oidc")]
 use crate::oidc::OidcCtx;
 use crate::{
-    authentication::AuthCtx, config::RequestConfig, error::RumaApiError, http_client::HttpClient,
+    authentication::AuthCtx,
+    config::{RequestCategory, RequestConfig},
+    error::RumaApiError,
+    http_client::{HttpClient, HttpTransport, RequestLimits},
+    media::MediaRetentionPolicy,
     HttpError,
 };
 
@@ -82,11 +95,18 @@ pub struct ClientBuilder {
     #[cfg(feature = "experimental-sliding-sync")]
     sliding_sync_proxy: Option<String>,
     http_cfg: Option<HttpConfig>,
+    http_transport: Option<Arc<dyn HttpTransport>>,
     store_config: BuilderStoreConfig,
     request_config: RequestConfig,
+    max_concurrent_requests: Option<usize>,
+    max_concurrent_requests_per_category: HashMap<RequestCategory, usize>,
+    endpoint_timeouts: HashMap<&'static str, Duration>,
+    media_retention_policy: MediaRetentionPolicy,
     respect_login_well_known: bool,
     server_versions: Option<Box<[MatrixVersion]>>,
     handle_refresh_tokens: bool,
+    read_only: bool,
+    clock: SharedClock,
     base_client: Option<BaseClient>,
     #[cfg(feature = "e2e-encryption")]
     encryption_settings: EncryptionSettings,
@@ -99,11 +119,18 @@ impl ClientBuilder {
             #[cfg(feature = "experimental-sliding-sync")]
             sliding_sync_proxy: None,
             http_cfg: None,
+            http_transport: None,
             store_config: BuilderStoreConfig::Custom(StoreConfig::default()),
             request_config: Default::default(),
+            max_concurrent_requests: None,
+            max_concurrent_requests_per_category: HashMap::new(),
+            endpoint_timeouts: HashMap::new(),
+            media_retention_policy: Default::default(),
             respect_login_well_known: true,
             server_versions: None,
             handle_refresh_tokens: false,
+            read_only: false,
+            clock: Default::default(),
             base_client: None,
             #[cfg(feature = "e2e-encryption")]
             encryption_settings: Default::default(),
@@ -137,6 +164,10 @@ impl ClientBuilder {
     /// We assume we can connect in HTTPS to that server. If that's not the
     /// case, prefer using [`Self::insecure_server_name_no_tls`].
     ///
+    /// The result of the discovery is cached in the state store and reused
+    /// when the client is built again, and it is revalidated periodically by
+    /// the client.
+    ///
     /// This method is mutually exclusive with
     /// [`homeserver_url()`][Self::homeserver_url], if you set both whatever was
     /// set last will be used.
@@ -222,6 +253,16 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the policy deciding which media content is kept in the cache of the
+    /// state store.
+    ///
+    /// By default, the cache is limited to 400 MiB, files over 20 MiB aren't
+    /// cached and content is removed 60 days after it was last accessed.
+    pub fn media_retention_policy(mut self, policy: MediaRetentionPolicy) -> Self {
+        self.media_retention_policy = policy;
+        self
+    }
+
     /// Update the client's homeserver URL with the discovery information
     /// present in the login response, if any.
     pub fn respect_login_well_known(mut self, value: bool) -> Self {
@@ -235,13 +276,70 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the maximum number of requests that can be in flight at the same
+    /// time.
+    ///
+    /// The other requests wait until one of them is done. By default, there
+    /// is no limit.
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = Some(max);
+        self
+    }
+
+    /// Set the maximum number of requests of the given category that can be
+    /// in flight at the same time.
+    ///
+    /// This applies on top of [`max_concurrent_requests()`], and is useful to
+    /// prevent large media downloads from delaying the calls to the API, or
+    /// the other way around. By default, there is no limit.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{config::RequestCategory, Client};
+    ///
+    /// // Keep at least 2 slots free for API calls.
+    /// let client_builder = Client::builder()
+    ///     .max_concurrent_requests(6)
+    ///     .max_concurrent_requests_for(RequestCategory::Media, 4);
+    /// ```
+    ///
+    /// [`max_concurrent_requests()`]: Self::max_concurrent_requests
+    pub fn max_concurrent_requests_for(mut self, category: RequestCategory, max: usize) -> Self {
+        self.max_concurrent_requests_per_category.insert(category, max);
+        self
+    }
+
+    /// Set the timeout of all the requests to the endpoint of `R`.
+    ///
+    /// This takes precedence over the timeout of the [`RequestConfig`] used to
+    /// send the request, whether it is the default one or not.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use matrix_sdk::{ruma::api::client::media::get_content, Client};
+    ///
+    /// let client_builder = Client::builder()
+    ///     .endpoint_timeout::<get_content::v3::Request>(Duration::from_secs(10));
+    /// ```
+    pub fn endpoint_timeout<R: OutgoingRequest>(mut self, timeout: Duration) -> Self {
+        self.endpoint_timeouts.insert(type_name::<R>(), timeout);
+        self
+    }
+
     /// Set the proxy through which all the HTTP requests should go.
     ///
-    /// Note, only HTTP proxies are supported.
+    /// Note, SOCKS proxies are only supported with the `socks` feature. With
+    /// the `socks5h` scheme, the host names are resolved by the proxy, which
+    /// is necessary to reach onion services through Tor.
     ///
     /// # Arguments
     ///
-    /// * `proxy` - The HTTP URL of the proxy.
+    /// * `proxy` - The URL of the proxy, e.g. `http://localhost:8080`,
+    ///   `socks5://localhost:1080` or `socks5h://localhost:9050`.
     ///
     /// # Examples
     ///
@@ -256,6 +354,97 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the proxy through which the HTTP requests to `http` URLs should go.
+    ///
+    /// It takes precedence over the proxy set with [`proxy()`][Self::proxy].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn http_proxy(mut self, proxy: impl AsRef<str>) -> Self {
+        self.http_settings().http_proxy = Some(proxy.as_ref().to_owned());
+        self
+    }
+
+    /// Set the proxy through which the HTTP requests to `https` URLs should
+    /// go.
+    ///
+    /// It takes precedence over the proxy set with [`proxy()`][Self::proxy].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn https_proxy(mut self, proxy: impl AsRef<str>) -> Self {
+        self.http_settings().https_proxy = Some(proxy.as_ref().to_owned());
+        self
+    }
+
+    /// Set the hosts that should be reached directly, without going through
+    /// the proxies.
+    ///
+    /// The hosts use the same format as the `NO_PROXY` environment variable:
+    /// a domain also matches its subdomains, and IP addresses can be given
+    /// with a CIDR range, e.g. `192.168.1.0/24`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn no_proxy(mut self, hosts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.http_settings().no_proxy.extend(hosts.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the credentials to authenticate with the proxies, either with HTTP
+    /// basic authentication or with the username and password authentication
+    /// of SOCKS5.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn proxy_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.http_settings().proxy_credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Trust the given certificates, in addition to the default root
+    /// certificates, for the HTTP requests.
+    ///
+    /// This is useful for homeservers using certificates signed by a private
+    /// certificate authority.
+    ///
+    /// # Arguments
+    ///
+    /// * `certificates` - The DER-encoded certificates.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn add_root_certificates(mut self, certificates: Vec<Vec<u8>>) -> Self {
+        self.http_settings().additional_root_certificates.extend(certificates);
+        self
+    }
+
+    /// Only accept the servers whose certificate chain contains one of the
+    /// given public keys.
+    ///
+    /// The certificate chain must still be valid, unless
+    /// [`disable_ssl_verification()`][Self::disable_ssl_verification] is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `pins` - The SHA-256 hashes of the DER-encoded `SubjectPublicKeyInfo`
+    ///   of the accepted certificates, as used by HTTP Public Key Pinning.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+    pub fn pin_certificates(mut self, pins: Vec<[u8; 32]>) -> Self {
+        self.http_settings().certificate_pins.extend(pins);
+        self
+    }
+
+    /// Set a function to call when the certificate chain of a server is
+    /// rejected because it doesn't contain any of the public keys given to
+    /// [`pin_certificates()`][Self::pin_certificates].
+    ///
+    /// The request fails with a TLS error in that case, this allows to tell it
+    /// apart from the other connection errors, to show a proper security
+    /// error.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+    pub fn on_certificate_pin_failure(
+        mut self,
+        handler: impl Fn(CertificatePinFailure) + Send + Sync + 'static,
+    ) -> Self {
+        self.http_settings().certificate_pin_failure_handler = Some(Arc::new(handler));
+        self
+    }
+
     /// Disable SSL verification for the HTTP requests.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn disable_ssl_verification(mut self) -> Self {
@@ -273,7 +462,8 @@ impl ClientBuilder {
     /// Specify a [`reqwest::Client`] instance to handle sending requests and
     /// receiving responses.
     ///
-    /// This method is mutually exclusive with [`proxy()`][Self::proxy],
+    /// This method is mutually exclusive with the other methods configuring the
+    /// HTTP client, like [`proxy()`][Self::proxy],
     /// [`disable_ssl_verification`][Self::disable_ssl_verification] and
     /// [`user_agent()`][Self::user_agent].
     pub fn http_client(mut self, client: reqwest::Client) -> Self {
@@ -281,6 +471,21 @@ impl ClientBuilder {
         self
     }
 
+    /// Specify a custom [`HttpTransport`] to send the requests with, instead
+    /// of the built-in `reqwest` client.
+    ///
+    /// This allows to use another HTTP stack, like the native networking of
+    /// the platform. The SDK still handles the authentication, the
+    /// serialization and the retries of the requests.
+    ///
+    /// The transport takes precedence over the other methods configuring the
+    /// HTTP client, like [`http_client()`][Self::http_client] or
+    /// [`proxy()`][Self::proxy], which are then ignored.
+    pub fn http_transport(mut self, transport: impl HttpTransport + 'static) -> Self {
+        self.http_transport = Some(Arc::new(transport));
+        self
+    }
+
     /// Specify the Matrix versions supported by the homeserver manually, rather
     /// than `build()` doing it using a `get_supported_versions` request.
     ///
@@ -321,6 +526,36 @@ impl ClientBuilder {
         self
     }
 
+    /// Put the client in read-only mode.
+    ///
+    /// In this mode, the requests that would modify the state of the
+    /// homeserver, like sending events, receipts or typing notices, or setting
+    /// account data, fail with [`HttpError::ReadOnly`] without being sent.
+    ///
+    /// Syncing and decrypting events still work, so this is useful for clients
+    /// that only observe the account, or for tests that need to make sure
+    /// that nothing is sent to the homeserver by accident.
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Set the clock used for the timers and the timestamps of the client.
+    ///
+    /// This includes the retry delays of the requests, of the sync loop and of
+    /// the background tasks, the refresh of typing notices, the expiration of
+    /// the access token and the sending of scheduled messages. The clock is
+    /// available with [`Client::clock()`] for the higher-level crates.
+    ///
+    /// Defaults to the [`SystemClock`]. Tests can use a clock whose time can
+    /// be advanced manually, to be deterministic.
+    ///
+    /// [`SystemClock`]: matrix_sdk_common::time::SystemClock
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+
     /// Public for test only
     #[doc(hidden)]
     pub fn base_client(mut self, base_client: BaseClient) -> Self {
@@ -383,7 +618,19 @@ impl ClientBuilder {
             BaseClient::with_store_config(store_config)
         };
 
-        let http_client = HttpClient::new(inner_http_client.clone(), self.request_config);
+        let request_limits = RequestLimits::new(
+            self.max_concurrent_requests,
+            &self.max_concurrent_requests_per_category,
+            self.endpoint_timeouts,
+        );
+        let http_client = HttpClient::new(
+            inner_http_client.clone(),
+            self.http_transport,
+            self.request_config,
+            request_limits,
+            self.read_only,
+            self.clock.clone(),
+        );
 
         #[cfg(feature = "experimental-oidc")]
         let mut authentication_server_info = None;
@@ -393,6 +640,8 @@ impl ClientBuilder {
         #[cfg(feature = "experimental-sliding-sync")]
         let mut sliding_sync_proxy: Option<Url> = None;
 
+        let mut discovery_ctx = None;
+
         let homeserver = match homeserver_cfg {
             HomeserverConfig::Url(url) => {
                 #[cfg(feature = "experimental-sliding-sync")]
@@ -409,45 +658,81 @@ impl ClientBuilder {
                 url
             }
             HomeserverConfig::ServerName { server: server_name, protocol } => {
-                debug!("Trying to discover the homeserver");
-
-                let homeserver = match protocol {
+                let server_url = match protocol {
                     UrlScheme::Http => format!("http://{server_name}"),
                     UrlScheme::Https => format!("https://{server_name}"),
                 };
 
-                let well_known = http_client
-                    .send(
-                        discover_homeserver::Request::new(),
-                        Some(RequestConfig::short_retry()),
-                        homeserver,
-                        None,
-                        &[MatrixVersion::V1_0],
-                        Default::default(),
-                    )
+                let cached_discovery = match base_client
+                    .store()
+                    .get_kv_data(StateStoreDataKey::HomeserverDiscovery)
                     .await
-                    .map_err(|e| match e {
-                        HttpError::Api(err) => ClientBuildError::AutoDiscovery(err),
-                        err => ClientBuildError::Http(err),
-                    })?;
+                {
+                    Ok(value) => value
+                        .and_then(|value| value.into_homeserver_discovery())
+                        .filter(|discovery| discovery.server_url == server_url),
+                    Err(error) => {
+                        warn!("Couldn't load the cached discovery of the homeserver: {error}");
+                        None
+                    }
+                };
+
+                let discovery = match cached_discovery {
+                    Some(discovery) => {
+                        debug!(
+                            homeserver_url = discovery.homeserver_url,
+                            "Using the cached discovery of the homeserver"
+                        );
+                        discovery
+                    }
+                    None => {
+                        debug!("Trying to discover the homeserver");
+
+                        let discovery = discover_homeserver(&http_client, &self.clock, server_url)
+                            .await
+                            .map_err(|e| match e {
+                                HttpError::Api(err) => ClientBuildError::AutoDiscovery(err),
+                                err => ClientBuildError::Http(err),
+                            })?;
+
+                        if let Err(error) = base_client
+                            .store()
+                            .set_kv_data(
+                                StateStoreDataKey::HomeserverDiscovery,
+                                StateStoreDataValue::HomeserverDiscovery(discovery.clone()),
+                            )
+                            .await
+                        {
+                            warn!("Couldn't save the discovery of the homeserver: {error}");
+                        }
+
+                        debug!(
+                            homeserver_url = discovery.homeserver_url,
+                            "Discovered the homeserver"
+                        );
+                        discovery
+                    }
+                };
 
                 #[cfg(feature = "experimental-oidc")]
                 {
-                    authentication_server_info = well_known.authentication;
+                    authentication_server_info =
+                        discovery.authentication_issuer.clone().map(|issuer| {
+                            AuthenticationServerInfo::new(
+                                issuer,
+                                discovery.authentication_account.clone(),
+                            )
+                        });
                     allow_insecure_oidc = matches!(protocol, UrlScheme::Http);
                 }
 
                 #[cfg(feature = "experimental-sliding-sync")]
-                if let Some(proxy) = well_known.sliding_sync_proxy.map(|p| p.url) {
-                    sliding_sync_proxy = Url::parse(&proxy).ok();
+                if let Some(proxy) = &discovery.sliding_sync_proxy {
+                    sliding_sync_proxy = Url::parse(proxy).ok();
                 }
 
-                debug!(
-                    homeserver_url = well_known.homeserver.base_url,
-                    "Discovered the homeserver"
-                );
-
-                well_known.homeserver.base_url
+                discovery_ctx = Some(Arc::new(DiscoveryCtx::new(&discovery)));
+                discovery.homeserver_url
             }
         };
 
@@ -456,10 +741,12 @@ impl ClientBuilder {
         let auth_ctx = Arc::new(AuthCtx {
             handle_refresh_tokens: self.handle_refresh_tokens,
             refresh_token_lock: Mutex::new(Ok(())),
+            access_token_refresh_at: Default::default(),
             session_change_sender: broadcast::Sender::new(1),
+            session_state: SharedObservable::new(SessionState::LoggedOut),
             auth_data: OnceCell::default(),
-            reload_session_callback: OnceCell::default(),
-            save_session_callback: OnceCell::default(),
+            session_store_delegate: OnceCell::default(),
+            clock: self.clock.clone(),
             #[cfg(feature = "experimental-oidc")]
             oidc: OidcCtx::new(authentication_server_info, allow_insecure_oidc),
         });
@@ -467,12 +754,15 @@ impl ClientBuilder {
         let inner = ClientInner::new(
             auth_ctx,
             homeserver,
+            discovery_ctx,
             #[cfg(feature = "experimental-sliding-sync")]
             sliding_sync_proxy,
             http_client,
             base_client,
             self.server_versions,
             self.respect_login_well_known,
+            self.media_retention_policy,
+            self.clock,
             #[cfg(feature = "e2e-encryption")]
             self.encryption_settings,
         );
@@ -0,0 +1,122 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Requests to endpoints that are not supported by the SDK, like the ones of
+//! experimental MSCs.
+
+use bytes::{BufMut, Bytes};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use ruma::{
+    api::{
+        error::{FromHttpResponseError, IntoHttpError},
+        EndpointError, IncomingResponse, MatrixVersion, Metadata, OutgoingRequest, SendAccessToken,
+    },
+    metadata,
+};
+use serde::de::DeserializeOwned;
+use url::form_urlencoded;
+
+/// A request to an arbitrary endpoint of the homeserver.
+#[derive(Clone, Debug)]
+pub(crate) struct CustomRequest {
+    pub(crate) method: Method,
+    pub(crate) path: String,
+    pub(crate) query: Vec<(String, String)>,
+    pub(crate) body: Option<serde_json::Value>,
+}
+
+impl OutgoingRequest for CustomRequest {
+    type EndpointError = ruma::api::client::Error;
+    type IncomingResponse = CustomResponse;
+
+    // Only the authentication scheme is used by the SDK, the method and the
+    // path of the request are the ones given at runtime.
+    const METADATA: Metadata = metadata! {
+        method: GET,
+        rate_limited: false,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/client/unstable/custom",
+        }
+    };
+
+    fn try_into_http_request<T: Default + BufMut>(
+        self,
+        base_url: &str,
+        access_token: SendAccessToken<'_>,
+        _considering_versions: &[MatrixVersion],
+    ) -> Result<http::Request<T>, IntoHttpError> {
+        let mut url = format!("{}{}", base_url.trim_end_matches('/'), self.path);
+
+        if !self.query.is_empty() {
+            url.push('?');
+            url.push_str(
+                &form_urlencoded::Serializer::new(String::new()).extend_pairs(self.query).finish(),
+            );
+        }
+
+        let mut builder = http::Request::builder().method(self.method).uri(url);
+
+        if let Some(access_token) = access_token.get_required_for_endpoint() {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {access_token}"));
+        }
+
+        let mut body = T::default();
+        if let Some(json) = self.body {
+            builder = builder.header(CONTENT_TYPE, "application/json");
+            body.put_slice(&serde_json::to_vec(&json)?);
+        }
+
+        Ok(builder.body(body)?)
+    }
+}
+
+/// The response to a request sent with
+/// [`Client::send_custom()`](crate::Client::send_custom).
+#[derive(Clone, Debug)]
+pub struct CustomResponse {
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The raw body of the response.
+    pub body: Bytes,
+}
+
+impl CustomResponse {
+    /// Deserialize the JSON body of the response.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+impl IncomingResponse for CustomResponse {
+    type EndpointError = ruma::api::client::Error;
+
+    fn try_from_http_response<T: AsRef<[u8]>>(
+        response: http::Response<T>,
+    ) -> Result<Self, FromHttpResponseError<Self::EndpointError>> {
+        if response.status().is_client_error() || response.status().is_server_error() {
+            return Err(FromHttpResponseError::Server(
+                ruma::api::client::Error::from_http_response(response),
+            ));
+        }
+
+        Ok(Self {
+            status: response.status(),
+            body: Bytes::copy_from_slice(response.body().as_ref()),
+        })
+    }
+}
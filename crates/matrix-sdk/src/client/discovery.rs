@@ -0,0 +1,226 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Discovery of the homeserver of a server name, and revalidation of its
+//! cached result.
+
+use std::{sync::Mutex as StdMutex, time::Duration};
+
+use matrix_sdk_base::{store::HomeserverDiscovery, StateStoreDataKey, StateStoreDataValue};
+use matrix_sdk_common::{
+    instant::Instant,
+    time::{Clock, SharedClock},
+};
+use ruma::{
+    api::{client::discovery::discover_homeserver, MatrixVersion},
+    MilliSecondsSinceUnixEpoch,
+};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{config::RequestConfig, http_client::HttpClient, Client, HttpResult};
+
+/// How long the result of a discovery is used before it is revalidated.
+const REVALIDATION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The minimum delay between two revalidations that weren't requested
+/// explicitly.
+const MIN_REVALIDATION_DELAY: Duration = Duration::from_secs(60);
+
+/// A change of the URL of the homeserver used by a [`Client`].
+///
+/// It can be received with [`Client::subscribe_to_homeserver_changes()`].
+#[derive(Clone, Debug)]
+pub struct HomeserverChange {
+    /// The URL that was used before the change.
+    pub previous: Url,
+    /// The URL that is used from now on.
+    pub new: Url,
+}
+
+/// The state of the discovery of the homeserver, for a client that was built
+/// with a server name.
+#[derive(Debug)]
+pub(crate) struct DiscoveryCtx {
+    /// The URL of the server to discover the homeserver of.
+    server_url: String,
+    /// When the result of the discovery was last validated.
+    validated_at: StdMutex<MilliSecondsSinceUnixEpoch>,
+    /// When the last automatic revalidation was started.
+    last_attempt: StdMutex<Option<Instant>>,
+}
+
+impl DiscoveryCtx {
+    pub(crate) fn new(discovery: &HomeserverDiscovery) -> Self {
+        Self {
+            server_url: discovery.server_url.clone(),
+            validated_at: StdMutex::new(discovery.validated_at),
+            last_attempt: Default::default(),
+        }
+    }
+
+    fn is_stale(&self, clock: &SharedClock) -> bool {
+        let validated_at = u64::from(self.validated_at.lock().unwrap().get());
+        let now = u64::from(clock.timestamp().get());
+        let interval_ms = REVALIDATION_INTERVAL.as_millis().try_into().unwrap_or(u64::MAX);
+
+        now.saturating_sub(validated_at) >= interval_ms
+    }
+
+    /// Whether an automatic revalidation can start now, in which case the
+    /// attempt is recorded.
+    fn start_attempt(&self, clock: &SharedClock) -> bool {
+        let mut last_attempt = self.last_attempt.lock().unwrap();
+        let now = clock.now();
+
+        if last_attempt.is_some_and(|instant| now - instant < MIN_REVALIDATION_DELAY) {
+            return false;
+        }
+
+        *last_attempt = Some(now);
+        true
+    }
+}
+
+/// Fetch the `/.well-known/matrix/client` file of the given server.
+pub(crate) async fn discover_homeserver(
+    http_client: &HttpClient,
+    clock: &SharedClock,
+    server_url: String,
+) -> HttpResult<HomeserverDiscovery> {
+    let well_known = http_client
+        .send(
+            discover_homeserver::Request::new(),
+            Some(RequestConfig::short_retry()),
+            server_url.clone(),
+            None,
+            &[MatrixVersion::V1_0],
+            Default::default(),
+        )
+        .await?;
+
+    #[cfg(feature = "experimental-sliding-sync")]
+    let sliding_sync_proxy = well_known.sliding_sync_proxy.map(|p| p.url);
+    #[cfg(not(feature = "experimental-sliding-sync"))]
+    let sliding_sync_proxy = None;
+
+    let (authentication_issuer, authentication_account) = match well_known.authentication {
+        Some(info) => (Some(info.issuer), info.account),
+        None => (None, None),
+    };
+
+    Ok(HomeserverDiscovery {
+        server_url,
+        homeserver_url: well_known.homeserver.base_url,
+        sliding_sync_proxy,
+        authentication_issuer,
+        authentication_account,
+        validated_at: clock.timestamp(),
+    })
+}
+
+impl Client {
+    /// Get a receiver of the changes of the URL of the homeserver.
+    ///
+    /// The URL changes when the discovery of the homeserver is revalidated
+    /// and the server now advertises another homeserver, or when the login
+    /// response contains a different homeserver.
+    pub fn subscribe_to_homeserver_changes(&self) -> broadcast::Receiver<HomeserverChange> {
+        self.inner.homeserver_change_sender.subscribe()
+    }
+
+    /// Discover the homeserver again, and update the URLs used by the client
+    /// if they changed.
+    ///
+    /// This only does something if the client was built with
+    /// [`ClientBuilder::server_name()`] or
+    /// [`ClientBuilder::insecure_server_name_no_tls()`]. It is done
+    /// automatically when the cached discovery is older than a day, or when
+    /// the homeserver can't be reached.
+    ///
+    /// Returns whether the URL of the homeserver changed.
+    ///
+    /// [`ClientBuilder::server_name()`]: crate::ClientBuilder::server_name
+    /// [`ClientBuilder::insecure_server_name_no_tls()`]: crate::ClientBuilder::insecure_server_name_no_tls
+    pub async fn revalidate_homeserver(&self) -> HttpResult<bool> {
+        let Some(discovery_ctx) = &self.inner.discovery else {
+            return Ok(false);
+        };
+
+        let discovery = discover_homeserver(
+            &self.inner.http_client,
+            &self.inner.clock,
+            discovery_ctx.server_url.clone(),
+        )
+        .await?;
+        *discovery_ctx.validated_at.lock().unwrap() = discovery.validated_at;
+
+        if let Err(error) = self
+            .store()
+            .set_kv_data(
+                StateStoreDataKey::HomeserverDiscovery,
+                StateStoreDataValue::HomeserverDiscovery(discovery.clone()),
+            )
+            .await
+        {
+            warn!("Couldn't save the discovery of the homeserver: {error}");
+        }
+
+        #[cfg(feature = "experimental-sliding-sync")]
+        self.set_sliding_sync_proxy(
+            discovery.sliding_sync_proxy.as_deref().and_then(|url| Url::parse(url).ok()),
+        );
+
+        let homeserver = match Url::parse(&discovery.homeserver_url) {
+            Ok(url) => url,
+            Err(error) => {
+                warn!(
+                    homeserver_url = discovery.homeserver_url,
+                    "Discovered an invalid homeserver URL: {error}"
+                );
+                return Ok(false);
+            }
+        };
+
+        Ok(self.set_homeserver(homeserver))
+    }
+
+    /// Revalidate the discovery of the homeserver in the background, if it is
+    /// stale or if `force` is set.
+    ///
+    /// Automatic revalidations are spaced by at least a minute.
+    pub(crate) fn maybe_revalidate_homeserver(&self, force: bool) {
+        let Some(discovery_ctx) = &self.inner.discovery else {
+            return;
+        };
+
+        if !force && !discovery_ctx.is_stale(&self.inner.clock) {
+            return;
+        }
+
+        if !discovery_ctx.start_attempt(&self.inner.clock) {
+            return;
+        }
+
+        let client = self.clone();
+        matrix_sdk_common::executor::spawn(async move {
+            match client.revalidate_homeserver().await {
+                Ok(true) => debug!(homeserver = %client.homeserver(), "The homeserver changed"),
+                Ok(false) => debug!("The discovery of the homeserver is up to date"),
+                Err(error) => warn!("Couldn't revalidate the discovery of the homeserver: {error}"),
+            }
+        });
+    }
+}
@@ -71,6 +71,9 @@ impl<R> SendRequest<R> {
 
     /// Get a subscriber to observe the progress of sending the request
     /// body.
+    ///
+    /// If the request doesn't have a body, like media downloads, the progress
+    /// of receiving the response body is observed instead.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn subscribe_to_send_progress(&self) -> Subscriber<TransmissionProgress> {
         self.send_progress.subscribe()
@@ -90,6 +93,25 @@ where
         let Self { client, request, config, send_progress, sliding_sync_proxy_url } = self;
 
         Box::pin(async move {
+            // Refresh the access token before it expires, rather than waiting for a request
+            // to fail. If it doesn't work, the request fails and the error is handled
+            // below.
+            if client.inner.auth_ctx.handle_refresh_tokens
+                && client.inner.auth_ctx.access_token_needs_refresh()
+            {
+                trace!("Token refresh: The access token is about to expire.");
+
+                if let Err(error) = client.refresh_access_token().await {
+                    trace!("Token refresh: Proactive refresh failed: {error}");
+                }
+            }
+
+            let is_homeserver_request = sliding_sync_proxy_url.is_none();
+
+            if is_homeserver_request {
+                client.maybe_revalidate_homeserver(false);
+            }
+
             let res = Box::pin(client.send_inner(
                 request.clone(),
                 config,
@@ -98,6 +120,14 @@ where
             ))
             .await;
 
+            // The homeserver might have moved, check if the discovery changed.
+            #[cfg(not(target_arch = "wasm32"))]
+            if is_homeserver_request
+                && matches!(&res, Err(HttpError::Reqwest(error)) if error.is_connect())
+            {
+                client.maybe_revalidate_homeserver(true);
+            }
+
             // An `M_UNKNOWN_TOKEN` error can potentially be fixed with a token refresh.
             if let Err(Some(ErrorKind::UnknownToken { soft_logout })) =
                 res.as_ref().map_err(HttpError::client_api_error_kind)
@@ -15,11 +15,14 @@
 // limitations under the License.
 
 use std::{
-    collections::{btree_map, BTreeMap},
+    collections::{btree_map, BTreeMap, BTreeSet},
     fmt::{self, Debug},
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex, RwLock as StdRwLock,
+    },
 };
 
 use eyeball::{SharedObservable, Subscriber};
@@ -30,7 +33,7 @@ use matrix_sdk_base::{
     store::DynStateStore, BaseClient, RoomState, RoomStateFilter, SendOutsideWasm, SessionMeta,
     SyncOutsideWasm,
 };
-use matrix_sdk_common::instant::Instant;
+use matrix_sdk_common::{executor::JoinHandle, instant::Instant, time::SharedClock};
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{room::encryption::RoomEncryptionEventContent, InitialStateEvent};
 use ruma::{
@@ -38,14 +41,14 @@ use ruma::{
         client::{
             account::whoami,
             alias::get_alias,
-            device::{delete_devices, get_devices, update_device},
+            device::{delete_devices, update_device},
             directory::{get_public_rooms, get_public_rooms_filtered},
             discovery::{
                 get_capabilities::{self, Capabilities},
                 get_supported_versions,
             },
             filter::{create_filter::v3::Request as FilterUploadRequest, FilterDefinition},
-            membership::{join_room_by_id, join_room_by_id_or_alias},
+            knock::knock_room,
             profile::get_profile,
             push::{get_notifications::v3::Notification, set_pusher, Pusher},
             room::create_room,
@@ -58,12 +61,14 @@ use ruma::{
         MatrixVersion, OutgoingRequest,
     },
     assign,
+    events::{direct::DirectEventContent, AnySyncTimelineEvent, GlobalAccountDataEventType},
     push::Ruleset,
-    DeviceId, OwnedDeviceId, OwnedRoomId, OwnedServerName, RoomAliasId, RoomId, RoomOrAliasId,
-    ServerName, UInt, UserId,
+    serde::Raw,
+    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedRoomId, OwnedServerName, RoomAliasId,
+    RoomId, RoomOrAliasId, ServerName, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
-use tokio::sync::{broadcast, Mutex, OnceCell, RwLock, RwLockReadGuard};
+use tokio::sync::{broadcast, mpsc, Mutex, OnceCell, RwLock, RwLockReadGuard};
 use tracing::{debug, error, instrument, trace, Instrument, Span};
 use url::Url;
 
@@ -71,19 +76,26 @@ use self::futures::SendRequest;
 #[cfg(feature = "experimental-oidc")]
 use crate::oidc::Oidc;
 use crate::{
-    authentication::{AuthCtx, AuthData, ReloadSessionCallback, SaveSessionCallback},
+    authentication::{
+        AuthCtx, AuthData, ReloadSessionCallback, SaveSessionCallback, SessionCallbacks,
+        SessionStoreDelegate,
+    },
     config::RequestConfig,
     deduplicating_handler::DeduplicatingHandler,
     error::{HttpError, HttpResult},
+    event_cache::EventCache,
     event_handler::{
         EventHandler, EventHandlerDropGuard, EventHandlerHandle, EventHandlerStore, SyncEvent,
     },
     http_client::HttpClient,
     matrix_auth::MatrixAuth,
+    media::{MediaCacheState, MediaRetentionPolicy, UrlPreview},
     notification_settings::NotificationSettings,
+    room::{faster_joins, IncomingCall},
+    room_creation::RoomCreationBuilder,
     sync::{RoomUpdate, SyncResponse},
-    Account, AuthApi, AuthSession, Error, Media, RefreshTokenError, Result, Room,
-    TransmissionProgress,
+    Account, AuthApi, AuthSession, Error, Media, Pushers, RateLimited, RefreshTokenError, Result,
+    Room, TransmissionProgress, UnreadCounts,
 };
 #[cfg(feature = "e2e-encryption")]
 use crate::{
@@ -95,13 +107,21 @@ use crate::{
 };
 
 mod builder;
+mod custom_request;
+mod discovery;
 pub(crate) mod futures;
+mod report;
 #[cfg(feature = "e2e-encryption")]
 mod tasks;
 
-pub use self::builder::{ClientBuildError, ClientBuilder};
 #[cfg(feature = "e2e-encryption")]
 use self::tasks::{BackupDownloadTask, BackupUploadingTask, ClientTasks};
+pub use self::{
+    builder::{ClientBuildError, ClientBuilder},
+    custom_request::CustomResponse,
+    discovery::HomeserverChange,
+};
+use self::{custom_request::CustomRequest, discovery::DiscoveryCtx};
 
 #[cfg(not(target_arch = "wasm32"))]
 type NotificationHandlerFut = Pin<Box<dyn Future<Output = ()> + Send>>;
@@ -140,6 +160,44 @@ pub enum SessionChange {
     TokensRefreshed,
 }
 
+/// The state of the session of a `Client`.
+///
+/// This can be observed with [`Client::subscribe_to_session_state()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    /// The client isn't logged in, or its session was logged out by the
+    /// server.
+    LoggedOut,
+    /// The client is logged in.
+    LoggedIn,
+    /// The server invalidated the access token with a soft logout.
+    ///
+    /// The session is kept, with its device ID and its encryption keys, and
+    /// can be resumed with [`Client::relogin()`].
+    SoftLoggedOut,
+}
+
+/// The credentials used to log back into a session after a soft logout, with
+/// [`Client::relogin()`].
+#[derive(Clone)]
+pub enum ReloginCredentials {
+    /// The password of the user.
+    Password(String),
+    /// A login token, obtained for example with SSO.
+    Token(String),
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for ReloginCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variant = match self {
+            Self::Password(_) => "Password",
+            Self::Token(_) => "Token",
+        };
+        f.debug_struct("ReloginCredentials").field("variant", &variant).finish_non_exhaustive()
+    }
+}
+
 /// An async/await enabled Matrix client.
 ///
 /// All of the state is held in an `Arc` so the `Client` can be cloned freely.
@@ -154,6 +212,18 @@ pub(crate) struct ClientLocks {
     /// Look at the [`Account::mark_as_dm()`] method for a more detailed
     /// explanation.
     pub(crate) mark_as_dm_lock: Mutex<()>,
+    /// Lock ensuring that [`Client::dm_with()`] doesn't create several DMs
+    /// with the same user when it is called concurrently.
+    pub(crate) dm_with_lock: Mutex<()>,
+    /// Lock ensuring that the list of pending uploads in the store is only
+    /// modified by a single task at once.
+    pub(crate) pending_uploads_lock: Mutex<()>,
+    /// Lock ensuring that the list of scheduled messages in the store is only
+    /// modified by a single task at once.
+    pub(crate) scheduled_messages_lock: Mutex<()>,
+    /// Lock ensuring that the list of sent transactions in the store is only
+    /// modified by a single task at once.
+    pub(crate) sent_transactions_lock: Mutex<()>,
     /// Lock ensuring that only a single secret store is getting opened at the
     /// same time.
     ///
@@ -212,6 +282,14 @@ pub(crate) struct ClientLocks {
     /// outside the `OlmMachine`.
     #[cfg(feature = "e2e-encryption")]
     pub(crate) crypto_store_generation: Arc<Mutex<Option<u64>>>,
+    /// The channel used to invalidate the caches of the `OlmMachine` precisely
+    /// when another process modified the crypto store, if it was enabled with
+    /// [`Encryption::enable_cross_process_cache_invalidation()`].
+    ///
+    /// [`Encryption::enable_cross_process_cache_invalidation()`]: crate::encryption::Encryption::enable_cross_process_cache_invalidation
+    #[cfg(all(feature = "e2e-encryption", not(target_arch = "wasm32")))]
+    pub(crate) crypto_store_invalidation:
+        OnceCell<Arc<crate::encryption::CacheInvalidationChannel>>,
 }
 
 pub(crate) struct ClientInner {
@@ -220,6 +298,11 @@ pub(crate) struct ClientInner {
 
     /// The URL of the homeserver to connect to.
     homeserver: StdRwLock<Url>,
+    /// Sender of the changes of `homeserver`.
+    homeserver_change_sender: broadcast::Sender<HomeserverChange>,
+    /// The state of the discovery of the homeserver, if the client was built
+    /// with a server name.
+    discovery: Option<Arc<DiscoveryCtx>>,
     /// The sliding sync proxy that is trusted by the homeserver.
     #[cfg(feature = "experimental-sliding-sync")]
     sliding_sync_proxy: StdRwLock<Option<Url>>,
@@ -229,6 +312,9 @@ pub(crate) struct ClientInner {
     base_client: BaseClient,
     /// The Matrix versions the server supports (well-known ones only)
     server_versions: OnceCell<Box<[MatrixVersion]>>,
+    /// The unstable features the server advertises, with whether they are
+    /// enabled.
+    unstable_features: OnceCell<BTreeMap<String, bool>>,
     /// Collection of locks individual client methods might want to use, either
     /// to ensure that only a single call to a method happens at once or to
     /// deduplicate multiple calls to a method.
@@ -236,6 +322,8 @@ pub(crate) struct ClientInner {
     #[cfg(feature = "e2e-encryption")]
     pub(crate) tasks: StdMutex<ClientTasks>,
     pub(crate) typing_notice_times: StdRwLock<BTreeMap<OwnedRoomId, Instant>>,
+    /// The source of time of the client.
+    pub(crate) clock: SharedClock,
     /// Event handlers. See `add_event_handler`.
     pub(crate) event_handlers: EventHandlerStore,
     /// Notification handlers. See `register_notification_handler`.
@@ -250,6 +338,21 @@ pub(crate) struct ClientInner {
     /// wait for the sync to get the data to fetch a room object from the state
     /// store.
     pub(crate) sync_beat: event_listener::Event,
+    /// The unread counts aggregated over all the joined rooms, updated after
+    /// every sync.
+    pub(crate) unread_counts: SharedObservable<UnreadCounts>,
+    /// Whether read receipts should be sent privately, see
+    /// [`Client::set_private_read_receipts()`].
+    private_read_receipts: AtomicBool,
+    /// The state of the media cache.
+    pub(crate) media_cache: MediaCacheState,
+    /// The task waiting to send the next scheduled message.
+    pub(crate) scheduled_messages_task: StdMutex<Option<JoinHandle<()>>>,
+    /// Sender of the IDs of the rooms joined with a partial state whose full
+    /// state is now known.
+    pub(crate) room_full_state_sender: broadcast::Sender<OwnedRoomId>,
+    /// The cache of the timeline events of the rooms.
+    event_cache: EventCache,
     /// End-to-end encryption settings.
     #[cfg(feature = "e2e-encryption")]
     pub(crate) encryption_settings: EncryptionSettings,
@@ -269,15 +372,20 @@ impl ClientInner {
     fn new(
         auth_ctx: Arc<AuthCtx>,
         homeserver: Url,
+        discovery: Option<Arc<DiscoveryCtx>>,
         #[cfg(feature = "experimental-sliding-sync")] sliding_sync_proxy: Option<Url>,
         http_client: HttpClient,
         base_client: BaseClient,
         server_versions: Option<Box<[MatrixVersion]>>,
         respect_login_well_known: bool,
+        media_retention_policy: MediaRetentionPolicy,
+        clock: SharedClock,
         #[cfg(feature = "e2e-encryption")] encryption_settings: EncryptionSettings,
     ) -> Arc<Self> {
         let client = Self {
             homeserver: StdRwLock::new(homeserver),
+            homeserver_change_sender: broadcast::Sender::new(16),
+            discovery,
             auth_ctx,
             #[cfg(feature = "experimental-sliding-sync")]
             sliding_sync_proxy: StdRwLock::new(sliding_sync_proxy),
@@ -287,12 +395,20 @@ impl ClientInner {
             tasks: StdMutex::new(Default::default()),
             locks: Default::default(),
             server_versions: OnceCell::new_with(server_versions),
+            unstable_features: OnceCell::new(),
             typing_notice_times: Default::default(),
+            clock,
             event_handlers: Default::default(),
             notification_handlers: Default::default(),
             room_update_channels: Default::default(),
             respect_login_well_known,
             sync_beat: event_listener::Event::new(),
+            unread_counts: Default::default(),
+            private_read_receipts: AtomicBool::new(false),
+            media_cache: MediaCacheState::new(media_retention_policy),
+            scheduled_messages_task: Default::default(),
+            room_full_state_sender: broadcast::Sender::new(16),
+            event_cache: Default::default(),
             #[cfg(feature = "e2e-encryption")]
             encryption_settings,
             #[cfg(feature = "e2e-encryption")]
@@ -350,6 +466,36 @@ impl Client {
         self.inner.base_client.subscribe_to_ignore_user_list_changes()
     }
 
+    /// Returns a receiver that gets the IDs of the rooms whose [`RoomInfo`]
+    /// changed, every time a sync response has been processed.
+    ///
+    /// [`RoomInfo`]: crate::RoomInfo
+    pub fn subscribe_to_room_info_updates(&self) -> broadcast::Receiver<BTreeSet<OwnedRoomId>> {
+        self.inner.base_client.room_info_updates()
+    }
+
+    /// Get the cache of the timeline events of the rooms.
+    pub fn event_cache(&self) -> &EventCache {
+        &self.inner.event_cache
+    }
+
+    /// Get the unread counts aggregated over all the joined rooms.
+    ///
+    /// This is updated after every sync response has been processed, see
+    /// [`UnreadCounts`] for details on how they are computed.
+    pub fn unread_counts(&self) -> UnreadCounts {
+        self.inner.unread_counts.get()
+    }
+
+    /// Returns a subscriber that publishes the aggregated unread counts every
+    /// time they change.
+    ///
+    /// This is meant to be used to update the application badge without
+    /// having to iterate over all the rooms after every sync.
+    pub fn unread_counts_stream(&self) -> Subscriber<UnreadCounts> {
+        self.inner.unread_counts.subscribe()
+    }
+
     /// Create a new [`ClientBuilder`].
     pub fn builder() -> ClientBuilder {
         ClientBuilder::new()
@@ -365,11 +511,24 @@ impl Client {
 
     /// Change the homeserver URL used by this client.
     ///
+    /// Returns whether the URL changed, in which case the change is broadcast.
+    ///
     /// # Arguments
     ///
     /// * `homeserver_url` - The new URL to use.
-    fn set_homeserver(&self, homeserver_url: Url) {
-        *self.inner.homeserver.write().unwrap() = homeserver_url;
+    fn set_homeserver(&self, homeserver_url: Url) -> bool {
+        let previous =
+            std::mem::replace(&mut *self.inner.homeserver.write().unwrap(), homeserver_url.clone());
+
+        if previous == homeserver_url {
+            return false;
+        }
+
+        _ = self
+            .inner
+            .homeserver_change_sender
+            .send(HomeserverChange { previous, new: homeserver_url });
+        true
     }
 
     /// Get the capabilities of the homeserver.
@@ -411,6 +570,39 @@ impl Client {
         self.inner.http_client.request_config
     }
 
+    /// Whether this client is in read-only mode, and blocks the requests that
+    /// would modify the state of the homeserver.
+    ///
+    /// See [`ClientBuilder::read_only()`].
+    pub fn is_read_only(&self) -> bool {
+        self.inner.http_client.read_only
+    }
+
+    /// The clock used for the timers and the timestamps of this client.
+    ///
+    /// See [`ClientBuilder::clock()`].
+    pub fn clock(&self) -> &SharedClock {
+        &self.inner.clock
+    }
+
+    /// Whether read receipts should be sent as private `m.read.private`
+    /// receipts rather than public `m.read` receipts.
+    ///
+    /// Private read receipts still reset the unread counts of the room, but
+    /// are not shown to the other members of the room. Defaults to `false`.
+    pub fn private_read_receipts(&self) -> bool {
+        self.inner.private_read_receipts.load(Ordering::Relaxed)
+    }
+
+    /// Set whether read receipts should be sent as private `m.read.private`
+    /// receipts rather than public `m.read` receipts.
+    ///
+    /// This is a privacy setting of the user, that is used by the higher-level
+    /// APIs that send read receipts, like the timeline.
+    pub fn set_private_read_receipts(&self, enabled: bool) {
+        self.inner.private_read_receipts.store(enabled, Ordering::Relaxed);
+    }
+
     /// Is the client logged in.
     pub fn logged_in(&self) -> bool {
         self.inner.base_client.logged_in()
@@ -467,6 +659,26 @@ impl Client {
         self.send(request, None).await
     }
 
+    /// Get a preview of the given URL, generated by the homeserver.
+    ///
+    /// The previews are cached in memory, so they are only requested once per
+    /// URL and timestamp. Use [`Room::url_previews_enabled`] to know whether
+    /// previews should be shown for the URLs in a room.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to preview.
+    ///
+    /// * `ts` - The preferred point in time of the preview, usually the
+    ///   timestamp of the event containing the URL.
+    pub async fn get_url_preview(
+        &self,
+        url: &str,
+        ts: MilliSecondsSinceUnixEpoch,
+    ) -> Result<UrlPreview> {
+        self.media().get_url_preview(url, ts).await
+    }
+
     /// Get the user id of the current owner of the client.
     pub fn user_id(&self) -> Option<&UserId> {
         self.session_meta().map(|s| s.user_id.as_ref())
@@ -536,6 +748,11 @@ impl Client {
         Media::new(self.clone())
     }
 
+    /// Get the pushers manager of the client.
+    pub fn pushers(&self) -> Pushers {
+        Pushers::new(self.clone())
+    }
+
     /// Access the OpenID Connect API of the client.
     #[cfg(feature = "experimental-oidc")]
     pub fn oidc(&self) -> Oidc {
@@ -765,6 +982,38 @@ impl Client {
         EventHandlerDropGuard::new(handle, self.clone())
     }
 
+    /// Get a stream of the calls that should make the current device ring, as
+    /// received by the sync.
+    ///
+    /// Both invites to 1:1 calls and ring notifications for MatrixRTC calls
+    /// are detected, see [`IncomingCall::from_raw_event()`]. Calls that
+    /// already expired when they are received are skipped.
+    ///
+    /// The calls are only detected while the stream is alive.
+    pub fn incoming_calls_stream(&self) -> impl Stream<Item = IncomingCall> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        let handle = self.add_event_handler(move |event: Raw<AnySyncTimelineEvent>, room: Room| {
+            if let Some(call) =
+                IncomingCall::from_raw_event(room.room_id(), &event, room.own_user_id())
+            {
+                if !call.is_expired(&room.client.inner.clock) {
+                    let _ = sender.send(call);
+                }
+            }
+            async {}
+        });
+        let drop_guard = self.event_handler_drop_guard(handle);
+
+        async_stream::stream! {
+            let _drop_guard = drop_guard;
+
+            while let Some(call) = receiver.recv().await {
+                yield call;
+            }
+        }
+    }
+
     /// Add an arbitrary value for use as event handler context.
     ///
     /// The value can be obtained in an event handler by adding an argument of
@@ -888,6 +1137,15 @@ impl Client {
             .collect()
     }
 
+    /// Returns the rooms this client knows about and has knocked on.
+    pub fn knocked_rooms(&self) -> Vec<Room> {
+        self.base_client()
+            .get_rooms_filtered(RoomStateFilter::KNOCKED)
+            .into_iter()
+            .map(|room| Room::new(self.clone(), room))
+            .collect()
+    }
+
     /// Returns the left rooms this client knows about.
     pub fn left_rooms(&self) -> Vec<Room> {
         self.base_client()
@@ -957,9 +1215,44 @@ impl Client {
 
     pub(crate) async fn set_session_meta(&self, session_meta: SessionMeta) -> Result<()> {
         self.base_client().set_session_meta(session_meta).await?;
+        self.inner.auth_ctx.session_state.set(SessionState::LoggedIn);
         Ok(())
     }
 
+    /// Log back into the current session after it was soft logged out by the
+    /// server.
+    ///
+    /// The new access token is requested for the same user and device ID, so
+    /// the crypto identity and the local state of the session are kept, and
+    /// the sync can be resumed after this returns successfully.
+    ///
+    /// This is only supported for sessions using the native Matrix
+    /// authentication API.
+    ///
+    /// # Arguments
+    ///
+    /// * `credentials` - The password of the user, or a login token.
+    pub async fn relogin(&self, credentials: ReloginCredentials) -> Result<()> {
+        let Some(AuthApi::Matrix(auth)) = self.auth_api() else {
+            return Err(Error::AuthenticationRequired);
+        };
+
+        auth.relogin(credentials).await
+    }
+
+    /// The current state of the session.
+    pub fn session_state(&self) -> SessionState {
+        self.inner.auth_ctx.session_state.get()
+    }
+
+    /// Get a subscriber to observe the state of the session.
+    ///
+    /// This can be used to pause the sync and ask the user to log back in with
+    /// [`Client::relogin()`] when the session is soft logged out.
+    pub fn subscribe_to_session_state(&self) -> Subscriber<SessionState> {
+        self.inner.auth_ctx.session_state.subscribe()
+    }
+
     /// Refresh the access token using the authentication API used to log into
     /// this session.
     ///
@@ -1060,10 +1353,16 @@ impl Client {
     ///
     /// * `room_id` - The `RoomId` of the room to be joined.
     pub async fn join_room_by_id(&self, room_id: &RoomId) -> Result<Room> {
-        let request = join_room_by_id::v3::Request::new(room_id.to_owned());
+        let request = faster_joins::join_room_by_id::Request { room_id: room_id.to_owned() };
         let response = self.send(request, None).await?;
         let base_room = self.base_client().room_joined(&response.room_id).await?;
-        Ok(Room::new(self.clone(), base_room))
+        let room = Room::new(self.clone(), base_room);
+
+        if response.partial_state {
+            room.mark_partial_state().await?;
+        }
+
+        Ok(room)
     }
 
     /// Join a room by `RoomId`.
@@ -1080,14 +1379,90 @@ impl Client {
         alias: &RoomOrAliasId,
         server_names: &[OwnedServerName],
     ) -> Result<Room> {
-        let request = assign!(join_room_by_id_or_alias::v3::Request::new(alias.to_owned()), {
+        let request = faster_joins::join_room_by_id_or_alias::Request {
+            room_id_or_alias: alias.to_owned(),
             server_name: server_names.to_owned(),
-        });
+        };
         let response = self.send(request, None).await?;
         let base_room = self.base_client().room_joined(&response.room_id).await?;
+        let room = Room::new(self.clone(), base_room);
+
+        if response.partial_state {
+            room.mark_partial_state().await?;
+        }
+
+        Ok(room)
+    }
+
+    /// Knock on a room, i.e. ask its members to be invited to it.
+    ///
+    /// The room must have a `knock` or `knock_restricted` join rule. Returns
+    /// the room, in the [`RoomState::Knocked`] state.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The `RoomId` or `RoomAliasId` of the room to
+    ///   knock on.
+    ///
+    /// * `reason` - The reason for knocking, which is shown to the members of
+    ///   the room.
+    ///
+    /// * `server_names` - The servers to attempt to knock on the room through.
+    ///   One of them must be participating in the room.
+    pub async fn knock(
+        &self,
+        room_id_or_alias: &RoomOrAliasId,
+        reason: Option<String>,
+        server_names: Vec<OwnedServerName>,
+    ) -> Result<Room> {
+        let request = assign!(knock_room::v3::Request::new(room_id_or_alias.to_owned()), {
+            reason,
+            server_name: server_names,
+        });
+        let response = self.send(request, None).await?;
+        let base_room = self.base_client().room_knocked(&response.room_id).await?;
         Ok(Room::new(self.clone(), base_room))
     }
 
+    /// Report a room to the administrators of the homeserver.
+    ///
+    /// The room doesn't need to be known by the client, so it can be used to
+    /// report rooms from the room directory or from invites.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room to report.
+    ///
+    /// * `reason` - The reason for reporting the room, which can be empty.
+    pub async fn report_room(&self, room_id: &RoomId, reason: String) -> Result<()> {
+        let request = report::report_room::Request { room_id: room_id.to_owned(), reason };
+        self.send(request, None).await?;
+        Ok(())
+    }
+
+    /// Report a user to the administrators of the homeserver.
+    ///
+    /// This is only sent if the homeserver advertises support for [MSC4260]
+    /// in its unstable features, otherwise [`Error::UnsupportedFeature`] is
+    /// returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to report.
+    ///
+    /// * `reason` - The reason for reporting the user, which can be empty.
+    ///
+    /// [MSC4260]: https://github.com/matrix-org/matrix-spec-proposals/pull/4260
+    pub async fn report_user(&self, user_id: &UserId, reason: String) -> Result<()> {
+        if !self.supports_unstable_feature("org.matrix.msc4260").await? {
+            return Err(Error::UnsupportedFeature("reporting users"));
+        }
+
+        let request = report::report_user::Request { user_id: user_id.to_owned(), reason };
+        self.send(request, None).await?;
+        Ok(())
+    }
+
     /// Search the homeserver's directory of public rooms.
     ///
     /// Sends a request to "_matrix/client/r0/publicRooms", returns
@@ -1140,7 +1515,9 @@ impl Client {
     ///
     /// If you want to create a direct message with one specific user, you can
     /// use [`create_dm`][Self::create_dm], which is more convenient than
-    /// assembling the [`create_room::v3::Request`] yourself.
+    /// assembling the [`create_room::v3::Request`] yourself. For other rooms,
+    /// [`build_room`][Self::build_room] validates the parameters and handles
+    /// the settings that need more than the `createRoom` request.
     ///
     /// If the `is_direct` field of the request is set to `true` and at least
     /// one user is invited, the room will be automatically added to the direct
@@ -1185,6 +1562,13 @@ impl Client {
         Ok(joined_room)
     }
 
+    /// Start building a new room.
+    ///
+    /// See [`RoomCreationBuilder`] for the available settings.
+    pub fn build_room(&self) -> RoomCreationBuilder {
+        RoomCreationBuilder::new(self.clone())
+    }
+
     /// Create a DM room.
     ///
     /// Convenience shorthand for [`create_room`][Self::create_room] with the
@@ -1216,6 +1600,112 @@ impl Client {
         self.create_room(request).await
     }
 
+    /// Get the DM room with the given user, or create it if there is none.
+    ///
+    /// A joined room is considered as the DM with the user if it is in the
+    /// `m.direct` account data for them, and if it has at most two members.
+    /// When several rooms match, the one with the most recent activity is
+    /// used.
+    ///
+    /// Before creating a room, the `m.direct` account data is fetched from
+    /// the server in case the DM was created by another device. If that room
+    /// wasn't received by this device yet, it is joined. If another device
+    /// created a DM at the same time, all devices settle on the new room with
+    /// the smallest ID, and the other new rooms are left.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The ID of the user to get the DM with.
+    pub async fn dm_with(&self, user_id: &UserId) -> Result<Room> {
+        let _guard = self.locks().dm_with_lock.lock().await;
+
+        if let Some(room) = self.find_dm_room(|room| room.direct_targets().contains(user_id)) {
+            return Ok(room);
+        }
+
+        // The DM might have been created by another device, and not received by
+        // this one yet.
+        let previous_room_ids = self.fetch_dm_room_ids(user_id).await?;
+        if let Some(room) = self.find_dm_room(|room| previous_room_ids.contains(room.room_id())) {
+            return Ok(room);
+        }
+
+        if let Some(room) = self.join_unsynced_dm_room(&previous_room_ids).await {
+            return Ok(room);
+        }
+
+        let room = self.create_dm(user_id).await?;
+
+        // `create_dm` doesn't fail when the room couldn't be marked as a DM, so
+        // our room might be missing here.
+        let canonical_room_id = self
+            .fetch_dm_room_ids(user_id)
+            .await?
+            .into_iter()
+            .filter(|room_id| !previous_room_ids.contains(room_id))
+            .chain([room.room_id().to_owned()])
+            .min()
+            .expect("the list of new DM rooms contains at least our room");
+
+        if canonical_room_id == room.room_id() {
+            return Ok(room);
+        }
+
+        debug!(
+            room_id = ?room.room_id(), ?canonical_room_id,
+            "Another device created a DM concurrently, using its room instead"
+        );
+        let canonical_room = self.join_room_by_id(&canonical_room_id).await?;
+        room.leave().await?;
+
+        Ok(canonical_room)
+    }
+
+    /// Find the DM room among the joined rooms that match the given filter.
+    fn find_dm_room(&self, filter: impl Fn(&Room) -> bool) -> Option<Room> {
+        self.joined_rooms()
+            .into_iter()
+            .filter(|room| filter(room) && room.active_members_count() <= 2)
+            .max_by_key(dm_recency)
+    }
+
+    /// Join one of the given DM rooms that this device doesn't know yet, or
+    /// was only invited to.
+    ///
+    /// The rooms are tried from the smallest ID, like when settling on a room
+    /// after a concurrent creation. The rooms that were left, or that are
+    /// joined but were not picked by `find_dm_room`, are ignored.
+    async fn join_unsynced_dm_room(&self, room_ids: &[OwnedRoomId]) -> Option<Room> {
+        let mut candidates: Vec<_> = room_ids
+            .iter()
+            .filter(|room_id| {
+                self.get_room(room_id).map_or(true, |room| room.state() == RoomState::Invited)
+            })
+            .collect();
+        candidates.sort();
+
+        for room_id in candidates {
+            match self.join_room_by_id(room_id).await {
+                Ok(room) => return Some(room),
+                Err(error) => debug!(?room_id, "Could not join a previous DM room: {error}"),
+            }
+        }
+
+        None
+    }
+
+    /// Fetch the IDs of the DM rooms with the given user from the `m.direct`
+    /// account data on the server.
+    async fn fetch_dm_room_ids(&self, user_id: &UserId) -> Result<Vec<OwnedRoomId>> {
+        let raw_content =
+            self.account().fetch_account_data(GlobalAccountDataEventType::Direct).await?;
+        let mut content = raw_content
+            .and_then(|content| content.deserialize_as::<DirectEventContent>().ok())
+            .unwrap_or_default();
+
+        Ok(content.remove(user_id).unwrap_or_default())
+    }
+
     /// Search the homeserver's directory for public rooms with a filter.
     ///
     /// # Arguments
@@ -1310,6 +1800,65 @@ impl Client {
         }
     }
 
+    /// Send a request to an endpoint of the homeserver that isn't supported by
+    /// the SDK or by Ruma, like the endpoints of experimental MSCs.
+    ///
+    /// The request is authenticated with the access token of the client, if
+    /// any, and is retried or refreshes the access token like the requests
+    /// sent with [`send()`](Self::send).
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method of the request.
+    ///
+    /// * `path` - The path of the endpoint, relative to the homeserver, e.g.
+    ///   `/_matrix/client/unstable/org.example.msc1234/things`.
+    ///
+    /// * `query` - The query parameters of the request.
+    ///
+    /// * `body` - The JSON body of the request, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// use serde_json::json;
+    ///
+    /// let response = client
+    ///     .send_custom(
+    ///         http::Method::POST,
+    ///         "/_matrix/client/unstable/org.example.msc1234/things",
+    ///         &[("limit", "10")],
+    ///         Some(json!({ "name": "thing" })),
+    ///     )
+    ///     .await?;
+    /// let content: serde_json::Value = response.deserialize()?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn send_custom(
+        &self,
+        method: http::Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<serde_json::Value>,
+    ) -> HttpResult<CustomResponse> {
+        let request = CustomRequest {
+            method,
+            path: path.to_owned(),
+            query: query
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+                .collect(),
+            body,
+        };
+
+        self.send(request, None).await
+    }
+
     #[cfg(feature = "experimental-sliding-sync")]
     // FIXME: remove this as soon as Sliding-Sync isn't needing an external server
     // anymore
@@ -1364,6 +1913,12 @@ impl Client {
     }
 
     fn broadcast_unknown_token(&self, soft_logout: &bool) {
+        self.inner.auth_ctx.session_state.set(if *soft_logout {
+            SessionState::SoftLoggedOut
+        } else {
+            SessionState::LoggedOut
+        });
+
         _ = self
             .inner
             .auth_ctx
@@ -1371,8 +1926,8 @@ impl Client {
             .send(SessionChange::UnknownToken { soft_logout: *soft_logout });
     }
 
-    async fn request_server_versions(&self) -> HttpResult<Box<[MatrixVersion]>> {
-        let server_versions: Box<[MatrixVersion]> = self
+    async fn request_supported_versions(&self) -> HttpResult<get_supported_versions::Response> {
+        let response = self
             .inner
             .http_client
             .send(
@@ -1383,15 +1938,18 @@ impl Client {
                 &[MatrixVersion::V1_0],
                 Default::default(),
             )
-            .await?
-            .known_versions()
-            .collect();
+            .await?;
 
-        if server_versions.is_empty() {
-            Ok(vec![MatrixVersion::V1_0].into())
-        } else {
-            Ok(server_versions)
-        }
+        // Both are cached from the same response, so the endpoint is only
+        // called once if both are needed.
+        _ = self.inner.unstable_features.set(response.unstable_features.clone());
+        _ = self.inner.server_versions.set(known_versions(&response));
+
+        Ok(response)
+    }
+
+    async fn request_server_versions(&self) -> HttpResult<Box<[MatrixVersion]>> {
+        Ok(known_versions(&self.request_supported_versions().await?))
     }
 
     pub(crate) async fn server_versions(&self) -> HttpResult<&[MatrixVersion]> {
@@ -1404,35 +1962,30 @@ impl Client {
         Ok(server_versions)
     }
 
-    /// Get information of all our own devices.
-    ///
-    /// # Examples
+    /// The unstable features advertised by the homeserver, with whether they
+    /// are enabled.
     ///
-    /// ```no_run
-    /// # use matrix_sdk::{Client, config::SyncSettings};
-    /// # use url::Url;
-    /// # async {
-    /// # let homeserver = Url::parse("http://localhost:8080")?;
-    /// # let mut client = Client::new(homeserver).await?;
-    /// let response = client.devices().await?;
-    ///
-    /// for device in response.devices {
-    ///     println!(
-    ///         "Device: {} {}",
-    ///         device.device_id,
-    ///         device.display_name.as_deref().unwrap_or("")
-    ///     );
-    /// }
-    /// # anyhow::Ok(()) };
-    /// ```
-    pub async fn devices(&self) -> HttpResult<get_devices::v3::Response> {
-        let request = get_devices::v3::Request::new();
+    /// They are fetched the first time this is called and then cached.
+    pub(crate) async fn unstable_features(&self) -> HttpResult<&BTreeMap<String, bool>> {
+        self.inner
+            .unstable_features
+            .get_or_try_init(|| async {
+                HttpResult::Ok(self.request_supported_versions().await?.unstable_features)
+            })
+            .await
+    }
 
-        self.send(request, None).await
+    /// Whether the homeserver advertises the given unstable feature as
+    /// enabled.
+    pub(crate) async fn supports_unstable_feature(&self, feature: &str) -> HttpResult<bool> {
+        Ok(self.unstable_features().await?.get(feature).copied().unwrap_or(false))
     }
 
     /// Delete the given devices from the server.
     ///
+    /// See [`Client::delete_devices_with_uiaa()`] to go through the
+    /// user-interactive authentication with a handler.
+    ///
     /// # Arguments
     ///
     /// * `devices` - The list of devices that should be deleted from the
@@ -1611,8 +2164,20 @@ impl Client {
             error!(error = ?e, "Error while sending outgoing E2EE requests");
         }
 
+        let filter = match (sync_settings.filter, sync_settings.filter_builder) {
+            (Some(filter), _) => Some(*filter),
+            // Guests are not allowed to upload filters.
+            (None, Some(builder)) if self.matrix_auth().is_guest() => {
+                Some(sync_events::v3::Filter::FilterDefinition(builder.build()))
+            }
+            (None, Some(builder)) => Some(sync_events::v3::Filter::FilterId(
+                self.get_or_upload_filter(&builder.filter_name(), builder.build()).await?,
+            )),
+            (None, None) => None,
+        };
+
         let request = assign!(sync_events::v3::Request::new(), {
-            filter: sync_settings.filter.map(|f| *f),
+            filter,
             since: sync_settings.token,
             full_state: sync_settings.full_state,
             set_presence: sync_settings.set_presence,
@@ -1861,7 +2426,7 @@ impl Client {
             }
             trace!("Done running callback");
 
-            Client::delay_sync(&mut last_sync_time).await
+            self.delay_sync(&mut last_sync_time).await
         }
 
         Ok(())
@@ -1926,7 +2491,7 @@ impl Client {
             loop {
                 yield self.sync_loop_helper(&mut sync_settings).instrument(parent_span.clone()).await;
 
-                Client::delay_sync(&mut last_sync_time).await
+                self.delay_sync(&mut last_sync_time).await
             }
         }
     }
@@ -1949,28 +2514,52 @@ impl Client {
         broadcast.subscribe()
     }
 
+    /// Subscribes a new receiver to the notifications about the requests that
+    /// were rate-limited by the homeserver.
+    ///
+    /// The rate-limited requests are retried automatically after the delay
+    /// requested by the homeserver, within the limits of their
+    /// [`RequestConfig`]. This is not supported on WebAssembly, where the
+    /// requests are never retried.
+    pub fn subscribe_to_rate_limits(&self) -> broadcast::Receiver<RateLimited> {
+        self.inner.http_client.rate_limits.subscribe()
+    }
+
     /// Sets the save/restore session callbacks.
     ///
-    /// This is another mechanism to get synchronous updates to session tokens,
-    /// while [`Self::subscribe_to_session_changes`] provides an async update.
+    /// This is a shortcut for [`Self::set_session_store_delegate()`] with
+    /// closures.
     pub fn set_session_callbacks(
         &self,
         reload_session_callback: Box<ReloadSessionCallback>,
         save_session_callback: Box<SaveSessionCallback>,
     ) -> Result<()> {
-        self.inner
-            .auth_ctx
-            .reload_session_callback
-            .set(reload_session_callback)
-            .map_err(|_| Error::MultipleSessionCallbacks)?;
+        self.set_session_store_delegate(SessionCallbacks {
+            reload_session_callback,
+            save_session_callback,
+        })
+    }
 
+    /// Sets the delegate persisting the session into the storage of the
+    /// application.
+    ///
+    /// The delegate is the single place where the session is saved: it is
+    /// called after a login, after the access token was refreshed and after
+    /// the recovery from a soft logout, with the matching
+    /// [`SessionSaveReason`](crate::SessionSaveReason). It is also used to
+    /// reload the tokens when they might have been refreshed by another
+    /// process.
+    ///
+    /// It can only be set once.
+    pub fn set_session_store_delegate(
+        &self,
+        delegate: impl SessionStoreDelegate + 'static,
+    ) -> Result<()> {
         self.inner
             .auth_ctx
-            .save_session_callback
-            .set(save_session_callback)
-            .map_err(|_| Error::MultipleSessionCallbacks)?;
-
-        Ok(())
+            .session_store_delegate
+            .set(Arc::new(delegate))
+            .map_err(|_| Error::MultipleSessionCallbacks)
     }
 
     /// Sets a given pusher
@@ -1990,8 +2579,18 @@ impl Client {
     }
 
     /// Get the notification settings of the current owner of the client.
+    ///
+    /// The push rules are loaded from the store, so they are available before
+    /// the first sync, and then updated on sync. If the store doesn't contain
+    /// them yet, the server-default push rules are used.
     pub async fn notification_settings(&self) -> NotificationSettings {
-        let ruleset = self.account().push_rules().await.unwrap_or_else(|_| Ruleset::new());
+        let ruleset = match self.account().push_rules().await {
+            Ok(ruleset) => ruleset,
+            Err(error) => {
+                error!("Unable to load the push rules from the store: {error}");
+                self.user_id().map(Ruleset::server_default).unwrap_or_else(Ruleset::new)
+            }
+        };
         NotificationSettings::new(self.clone(), ruleset)
     }
 
@@ -2001,12 +2600,15 @@ impl Client {
             inner: ClientInner::new(
                 self.inner.auth_ctx.clone(),
                 self.homeserver(),
+                self.inner.discovery.clone(),
                 #[cfg(feature = "experimental-sliding-sync")]
                 self.inner.sliding_sync_proxy.read().unwrap().clone(),
                 self.inner.http_client.clone(),
                 self.inner.base_client.clone_with_in_memory_state_store(),
                 self.inner.server_versions.get().cloned(),
                 self.inner.respect_login_well_known,
+                self.media().media_retention_policy(),
+                self.inner.clock.clone(),
                 #[cfg(feature = "e2e-encryption")]
                 self.inner.encryption_settings,
             ),
@@ -2025,13 +2627,79 @@ impl Client {
 
         Ok(client)
     }
+
+    /// Create a new `Client` for another user of the same homeserver, with its
+    /// own session and stores.
+    #[cfg(feature = "appservice")]
+    pub(crate) fn new_for_user(&self, http_client: HttpClient, base_client: BaseClient) -> Client {
+        let auth_ctx = Arc::new(AuthCtx {
+            handle_refresh_tokens: false,
+            refresh_token_lock: Mutex::new(Ok(())),
+            access_token_refresh_at: Default::default(),
+            session_change_sender: broadcast::Sender::new(1),
+            session_state: SharedObservable::new(SessionState::LoggedOut),
+            auth_data: OnceCell::default(),
+            session_store_delegate: OnceCell::default(),
+            clock: self.inner.clock.clone(),
+            #[cfg(feature = "experimental-oidc")]
+            oidc: crate::oidc::OidcCtx::new(None, false),
+        });
+
+        Client {
+            inner: ClientInner::new(
+                auth_ctx,
+                self.homeserver(),
+                None,
+                #[cfg(feature = "experimental-sliding-sync")]
+                self.sliding_sync_proxy(),
+                http_client,
+                base_client,
+                self.inner.server_versions.get().cloned(),
+                false,
+                self.media().media_retention_policy(),
+                self.inner.clock.clone(),
+                #[cfg(feature = "e2e-encryption")]
+                self.inner.encryption_settings,
+            ),
+        }
+    }
+}
+
+/// The time of the most recent activity in the given room, if it is known.
+#[cfg(feature = "experimental-sliding-sync")]
+fn dm_recency(room: &Room) -> Option<MilliSecondsSinceUnixEpoch> {
+    room.latest_event()?.event().event.get_field("origin_server_ts").ok().flatten()
+}
+
+/// The time of the most recent activity in the given room, if it is known.
+#[cfg(not(feature = "experimental-sliding-sync"))]
+fn dm_recency(_room: &Room) -> Option<MilliSecondsSinceUnixEpoch> {
+    None
+}
+
+/// The Matrix versions of the given `/versions` response that are known to
+/// Ruma, or Matrix 1.0 if there are none.
+fn known_versions(response: &get_supported_versions::Response) -> Box<[MatrixVersion]> {
+    let server_versions: Box<[MatrixVersion]> = response.known_versions().collect();
+
+    if server_versions.is_empty() {
+        vec![MatrixVersion::V1_0].into()
+    } else {
+        server_versions
+    }
 }
 
 // The http mocking library is not supported for wasm32
 #[cfg(all(test, not(target_arch = "wasm32")))]
 pub(crate) mod tests {
-    use std::time::Duration;
+    use std::{
+        sync::{Arc, Mutex as StdMutex},
+        time::Duration,
+    };
 
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use eyeball::SharedObservable;
     use matrix_sdk_base::RoomState;
     use matrix_sdk_test::{
         async_test, test_json, JoinedRoomBuilder, StateTestEvent, SyncResponseBuilder,
@@ -2041,9 +2709,10 @@ pub(crate) mod tests {
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
     use ruma::{events::ignored_user_list::IgnoredUserListEventContent, UserId};
+    use serde_json::json;
     use url::Url;
     use wiremock::{
-        matchers::{body_json, header, method, path},
+        matchers::{body_json, header, method, path, query_param},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -2051,6 +2720,7 @@ pub(crate) mod tests {
     use crate::{
         config::{RequestConfig, SyncSettings},
         test_utils::{logged_in_client, no_retry_test_client, test_client_builder},
+        HttpTransport, HttpTransportError, TransmissionProgress,
     };
 
     #[async_test]
@@ -2175,6 +2845,99 @@ pub(crate) mod tests {
         client.matrix_auth().login_username("example", "wordpass").send().await.unwrap_err();
     }
 
+    #[async_test]
+    async fn test_rate_limited_http_requests_are_retried() {
+        let server = MockServer::start().await;
+        let client = test_client_builder(Some(server.uri())).build().await.unwrap();
+        let mut rate_limits = client.subscribe_to_rate_limits();
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/capabilities"))
+            .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+                "errcode": "M_LIMIT_EXCEEDED",
+                "error": "Too many requests",
+                "retry_after_ms": 100,
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/capabilities"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "capabilities": {} })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        client.get_capabilities().await.unwrap();
+
+        let rate_limited = rate_limits.try_recv().unwrap();
+        assert_eq!(rate_limited.path, "/_matrix/client/r0/capabilities");
+        assert_eq!(rate_limited.retry_after, Some(Duration::from_millis(100)));
+        assert!(rate_limited.will_retry);
+    }
+
+    #[async_test]
+    async fn test_custom_http_transport() {
+        #[derive(Debug, Default)]
+        struct TestTransport {
+            paths: StdMutex<Vec<String>>,
+        }
+
+        #[async_trait]
+        impl HttpTransport for Arc<TestTransport> {
+            async fn send(
+                &self,
+                request: http::Request<Bytes>,
+                _timeout: Duration,
+                _send_progress: SharedObservable<TransmissionProgress>,
+            ) -> Result<http::Response<Bytes>, HttpTransportError> {
+                self.paths.lock().unwrap().push(request.uri().path().to_owned());
+                let body = json!({ "capabilities": {} }).to_string();
+                Ok(http::Response::builder().status(200).body(body.into())?)
+            }
+        }
+
+        let transport = Arc::new(TestTransport::default());
+        let client =
+            test_client_builder(None).http_transport(transport.clone()).build().await.unwrap();
+
+        client.get_capabilities().await.unwrap();
+
+        assert_eq!(*transport.paths.lock().unwrap(), ["/_matrix/client/r0/capabilities"]);
+    }
+
+    #[async_test]
+    async fn test_send_custom_request() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/unstable/org.example.custom/things"))
+            .and(query_param("kind", "test"))
+            .and(header("authorization", "Bearer 1234"))
+            .and(body_json(json!({ "name": "thing" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": "abc" })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let response = client
+            .send_custom(
+                http::Method::PUT,
+                "/_matrix/client/unstable/org.example.custom/things",
+                &[("kind", "test")],
+                Some(json!({ "name": "thing" })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status, http::StatusCode::OK);
+        let content: serde_json::Value = response.deserialize().unwrap();
+        assert_eq!(content, json!({ "id": "abc" }));
+    }
+
     #[async_test]
     async fn test_retry_timeout_http_requests() {
         // Keep this timeout small so that the test doesn't take long
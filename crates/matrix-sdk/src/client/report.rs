@@ -0,0 +1,81 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Endpoints to report rooms and users to the homeserver administrators,
+//! which are not supported by Ruma yet.
+//!
+//! Ruma doesn't know the versions of the spec that added them, so their
+//! stable paths are declared as if they were available since Matrix 1.0. The
+//! support of the homeserver is checked with its unstable features instead,
+//! where it advertises them.
+
+/// Report a room, as defined in [MSC4151].
+///
+/// [MSC4151]: https://github.com/matrix-org/matrix-spec-proposals/pull/4151
+pub(super) mod report_room {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata, OwnedRoomId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/v3/rooms/:room_id/report",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id: OwnedRoomId,
+        pub reason: String,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    #[derive(Default)]
+    pub struct Response {}
+}
+
+/// Report a user, as defined in [MSC4260].
+///
+/// [MSC4260]: https://github.com/matrix-org/matrix-spec-proposals/pull/4260
+pub(super) mod report_user {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata, OwnedUserId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/v3/users/:user_id/report",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub user_id: OwnedUserId,
+        pub reason: String,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    #[derive(Default)]
+    pub struct Response {}
+}
@@ -15,7 +15,7 @@
 use std::{collections::BTreeMap, sync::Weak, time::Duration};
 
 use futures_util::future::join_all;
-use matrix_sdk_common::failures_cache::FailuresCache;
+use matrix_sdk_common::{failures_cache::FailuresCache, time::Clock};
 use ruma::OwnedRoomId;
 use tokio::sync::mpsc::{self, UnboundedReceiver};
 use tracing::{trace, warn};
@@ -131,7 +131,7 @@ impl BackupDownloadTask {
         failures_cache: FailuresCache<RoomKeyInfo>,
     ) {
         // Wait a bit, perhaps the room key will arrive in the meantime.
-        tokio::time::sleep(Duration::from_millis(Self::DOWNLOAD_DELAY_MILLIS)).await;
+        client.inner.clock.sleep(Duration::from_millis(Self::DOWNLOAD_DELAY_MILLIS)).await;
 
         if let Some(machine) = client.olm_machine().await.as_ref() {
             let (room_id, session_id) = &room_key_info;
@@ -0,0 +1,405 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management of several accounts logged in the same process.
+
+#[cfg(feature = "sqlite")]
+use std::path::PathBuf;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock as StdRwLock},
+};
+
+use eyeball::{ObservableWriteGuard, SharedObservable, Subscriber};
+use futures_util::StreamExt;
+use matrix_sdk_common::executor::{spawn, JoinHandle};
+use ruma::{OwnedUserId, UserId};
+use thiserror::Error;
+
+use crate::{Client, ClientBuilder, UnreadCounts};
+
+/// Errors that can happen when managing the accounts of a [`ClientRegistry`].
+#[derive(Debug, Error)]
+pub enum ClientRegistryError {
+    /// The client isn't logged in, so it can't be associated to an account.
+    #[error("the client is not logged in")]
+    NotLoggedIn,
+
+    /// A client is already registered for this account.
+    #[error("a client is already registered for {0}")]
+    AlreadyRegistered(OwnedUserId),
+
+    /// No client is registered for this account.
+    #[error("no client is registered for {0}")]
+    UnknownAccount(OwnedUserId),
+}
+
+/// A set of [`Client`]s logged into different accounts, in the same process.
+///
+/// The clients created with [`ClientRegistry::client_builder()`] share the
+/// same HTTP connection pool, and each of them uses its own stores, in a
+/// directory derived from the user ID of the account. Once logged in, they are
+/// added to the registry with [`ClientRegistry::add_client()`].
+///
+/// The registry keeps track of the account currently displayed by the
+/// application, and aggregates the unread counts of all the accounts.
+#[derive(Clone, Debug)]
+pub struct ClientRegistry {
+    inner: Arc<ClientRegistryInner>,
+}
+
+#[derive(Debug)]
+struct ClientRegistryInner {
+    /// The HTTP client shared by all the clients.
+    http_client: reqwest::Client,
+
+    /// The directory containing the stores of all the accounts.
+    #[cfg(feature = "sqlite")]
+    store_path: Option<PathBuf>,
+
+    /// The registered accounts.
+    accounts: StdRwLock<BTreeMap<OwnedUserId, RegisteredAccount>>,
+
+    /// The account currently used by the application.
+    active_account: SharedObservable<Option<OwnedUserId>>,
+
+    /// The unread counts of all the accounts added together.
+    unread_counts: SharedObservable<UnreadCounts>,
+}
+
+impl ClientRegistryInner {
+    fn update_unread_counts(&self) {
+        let counts = self.accounts.read().unwrap().values().fold(
+            UnreadCounts::default(),
+            |mut counts, account| {
+                counts.add_counts(&account.client.unread_counts());
+                counts
+            },
+        );
+
+        self.unread_counts.set_if_not_eq(counts);
+    }
+}
+
+#[derive(Debug)]
+struct RegisteredAccount {
+    client: Client,
+    /// The task updating the aggregated unread counts when the ones of this
+    /// account change.
+    unread_counts_task: JoinHandle<()>,
+}
+
+impl Drop for RegisteredAccount {
+    fn drop(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.unread_counts_task.abort();
+    }
+}
+
+impl ClientRegistry {
+    /// Create a new empty `ClientRegistry`.
+    ///
+    /// # Arguments
+    ///
+    /// * `http_client` - The HTTP client used by all the accounts, so they
+    ///   share its connection pool.
+    pub fn new(http_client: reqwest::Client) -> Self {
+        Self::new_inner(
+            http_client,
+            #[cfg(feature = "sqlite")]
+            None,
+        )
+    }
+
+    /// Create a new empty `ClientRegistry`, whose clients use SQLite stores.
+    ///
+    /// # Arguments
+    ///
+    /// * `http_client` - The HTTP client used by all the accounts, so they
+    ///   share its connection pool.
+    ///
+    /// * `store_path` - The directory in which the stores of every account are
+    ///   created, each in its own subdirectory.
+    #[cfg(feature = "sqlite")]
+    pub fn with_sqlite_stores(
+        http_client: reqwest::Client,
+        store_path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::new_inner(http_client, Some(store_path.into()))
+    }
+
+    fn new_inner(
+        http_client: reqwest::Client,
+        #[cfg(feature = "sqlite")] store_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(ClientRegistryInner {
+                http_client,
+                #[cfg(feature = "sqlite")]
+                store_path,
+                accounts: Default::default(),
+                active_account: SharedObservable::new(None),
+                unread_counts: Default::default(),
+            }),
+        }
+    }
+
+    /// The directory of the stores of the given account, if the clients use
+    /// SQLite stores.
+    ///
+    /// It can be removed once the account is logged out.
+    #[cfg(feature = "sqlite")]
+    pub fn store_path(&self, user_id: &UserId) -> Option<PathBuf> {
+        // Percent-encode the user ID to get a valid directory name on every
+        // platform, that can't collide with the one of another account.
+        let dir_name: String = url::form_urlencoded::byte_serialize(user_id.as_bytes()).collect();
+        Some(self.inner.store_path.as_ref()?.join(dir_name))
+    }
+
+    /// Create a builder for the client of the given account, with the shared
+    /// HTTP client and the stores of the account.
+    ///
+    /// The homeserver and the other settings of the client must still be
+    /// configured on the builder.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user ID of the account.
+    ///
+    /// * `passphrase` - The passphrase used to encrypt the stores of the
+    ///   account, if they are SQLite stores.
+    #[cfg_attr(not(feature = "sqlite"), allow(unused_variables))]
+    pub fn client_builder(&self, user_id: &UserId, passphrase: Option<&str>) -> ClientBuilder {
+        let builder = Client::builder().http_client(self.inner.http_client.clone());
+
+        #[cfg(feature = "sqlite")]
+        if let Some(store_path) = self.store_path(user_id) {
+            return builder.sqlite_store(store_path, passphrase);
+        }
+
+        builder
+    }
+
+    /// Add a logged-in client to the registry.
+    ///
+    /// If there was no active account, the account of this client becomes the
+    /// active one.
+    pub fn add_client(&self, client: Client) -> Result<(), ClientRegistryError> {
+        let user_id = client.user_id().ok_or(ClientRegistryError::NotLoggedIn)?.to_owned();
+
+        {
+            let mut accounts = self.inner.accounts.write().unwrap();
+
+            if accounts.contains_key(&user_id) {
+                return Err(ClientRegistryError::AlreadyRegistered(user_id));
+            }
+
+            let registry = Arc::downgrade(&self.inner);
+            let mut unread_counts_stream = client.unread_counts_stream();
+            let unread_counts_task = spawn(async move {
+                while unread_counts_stream.next().await.is_some() {
+                    let Some(registry) = registry.upgrade() else {
+                        break;
+                    };
+                    registry.update_unread_counts();
+                }
+            });
+
+            accounts.insert(user_id.clone(), RegisteredAccount { client, unread_counts_task });
+        }
+
+        self.inner.update_unread_counts();
+
+        let mut active_account = self.inner.active_account.write();
+        if active_account.is_none() {
+            ObservableWriteGuard::set(&mut active_account, Some(user_id));
+        }
+
+        Ok(())
+    }
+
+    /// Remove the client of the given account from the registry, and return
+    /// it.
+    ///
+    /// If it was the active account, another registered account becomes the
+    /// active one, if any.
+    pub fn remove_client(&self, user_id: &UserId) -> Result<Client, ClientRegistryError> {
+        let account = self
+            .inner
+            .accounts
+            .write()
+            .unwrap()
+            .remove(user_id)
+            .ok_or_else(|| ClientRegistryError::UnknownAccount(user_id.to_owned()))?;
+
+        self.inner.update_unread_counts();
+
+        let mut active_account = self.inner.active_account.write();
+        if active_account.as_deref() == Some(user_id) {
+            let next_account = self.inner.accounts.read().unwrap().keys().next().cloned();
+            ObservableWriteGuard::set(&mut active_account, next_account);
+        }
+
+        Ok(account.client.clone())
+    }
+
+    /// Get the client of the given account.
+    pub fn client(&self, user_id: &UserId) -> Option<Client> {
+        Some(self.inner.accounts.read().unwrap().get(user_id)?.client.clone())
+    }
+
+    /// Get the user IDs of all the registered accounts.
+    pub fn user_ids(&self) -> Vec<OwnedUserId> {
+        self.inner.accounts.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Get the user ID of the account currently used by the application.
+    pub fn active_account(&self) -> Option<OwnedUserId> {
+        self.inner.active_account.get()
+    }
+
+    /// Get the client of the account currently used by the application.
+    pub fn active_client(&self) -> Option<Client> {
+        self.client(self.active_account()?.as_ref())
+    }
+
+    /// Switch to another registered account.
+    pub fn set_active_account(&self, user_id: &UserId) -> Result<(), ClientRegistryError> {
+        if !self.inner.accounts.read().unwrap().contains_key(user_id) {
+            return Err(ClientRegistryError::UnknownAccount(user_id.to_owned()));
+        }
+
+        self.inner.active_account.set_if_not_eq(Some(user_id.to_owned()));
+        Ok(())
+    }
+
+    /// Get a subscriber to observe the switches of account.
+    ///
+    /// The application can use it to stop the sync of the previous account,
+    /// and to update its UI.
+    pub fn subscribe_to_active_account(&self) -> Subscriber<Option<OwnedUserId>> {
+        self.inner.active_account.subscribe()
+    }
+
+    /// Get the unread counts of all the accounts, added together.
+    pub fn unread_counts(&self) -> UnreadCounts {
+        self.inner.unread_counts.get()
+    }
+
+    /// Get a subscriber to observe the unread counts of all the accounts,
+    /// added together.
+    pub fn unread_counts_stream(&self) -> Subscriber<UnreadCounts> {
+        self.inner.unread_counts.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use matrix_sdk_base::SessionMeta;
+    use matrix_sdk_test::async_test;
+    use ruma::{device_id, user_id, UserId};
+
+    use super::{ClientRegistry, ClientRegistryError};
+    use crate::{
+        matrix_auth::{MatrixSession, MatrixSessionTokens},
+        test_utils::no_retry_test_client,
+        Client, UnreadCounts,
+    };
+
+    async fn client_for(user_id: &UserId) -> Client {
+        let client = no_retry_test_client(None).await;
+        let session = MatrixSession {
+            meta: SessionMeta {
+                user_id: user_id.to_owned(),
+                device_id: device_id!("DEVICEID").to_owned(),
+            },
+            tokens: MatrixSessionTokens { access_token: "1234".to_owned(), refresh_token: None },
+        };
+        client.matrix_auth().restore_session(session).await.unwrap();
+        client
+    }
+
+    #[async_test]
+    async fn test_accounts() {
+        let alice = user_id!("@alice:localhost");
+        let bob = user_id!("@bob:localhost");
+        let registry = ClientRegistry::new(reqwest::Client::new());
+        let active_account = registry.subscribe_to_active_account();
+
+        assert_matches!(
+            registry.add_client(no_retry_test_client(None).await),
+            Err(ClientRegistryError::NotLoggedIn)
+        );
+
+        registry.add_client(client_for(alice).await).unwrap();
+        registry.add_client(client_for(bob).await).unwrap();
+        assert_matches!(
+            registry.add_client(client_for(bob).await),
+            Err(ClientRegistryError::AlreadyRegistered(_))
+        );
+
+        assert_eq!(registry.user_ids(), [alice.to_owned(), bob.to_owned()]);
+        // The first account is the active one.
+        assert_eq!(active_account.get().as_deref(), Some(alice));
+        assert_eq!(registry.active_client().unwrap().user_id(), Some(alice));
+
+        registry.set_active_account(bob).unwrap();
+        assert_eq!(active_account.get().as_deref(), Some(bob));
+
+        let client = registry.remove_client(bob).unwrap();
+        assert_eq!(client.user_id(), Some(bob));
+        assert_eq!(active_account.get().as_deref(), Some(alice));
+        assert_matches!(
+            registry.set_active_account(bob),
+            Err(ClientRegistryError::UnknownAccount(_))
+        );
+    }
+
+    #[async_test]
+    async fn test_aggregated_unread_counts() {
+        let registry = ClientRegistry::new(reqwest::Client::new());
+        let alice = client_for(user_id!("@alice:localhost")).await;
+        let bob = client_for(user_id!("@bob:localhost")).await;
+
+        alice.inner.unread_counts.set(UnreadCounts {
+            num_rooms_with_notifications: 1,
+            num_notifications: 2,
+            num_mentions: 1,
+            num_unread_messages: 3,
+        });
+        bob.inner.unread_counts.set(UnreadCounts {
+            num_rooms_with_notifications: 2,
+            num_notifications: 4,
+            num_mentions: 0,
+            num_unread_messages: 5,
+        });
+
+        registry.add_client(alice).unwrap();
+        registry.add_client(bob).unwrap();
+
+        assert_eq!(
+            registry.unread_counts(),
+            UnreadCounts {
+                num_rooms_with_notifications: 3,
+                num_notifications: 6,
+                num_mentions: 1,
+                num_unread_messages: 8,
+            }
+        );
+
+        registry.remove_client(user_id!("@bob:localhost")).unwrap();
+        assert_eq!(registry.unread_counts().num_notifications, 2);
+    }
+}
@@ -129,11 +129,40 @@ impl RequestConfig {
     }
 }
 
+/// The category of a request, used to limit how many requests of the same
+/// kind can be in flight at the same time.
+///
+/// See [`ClientBuilder::max_concurrent_requests_for()`].
+///
+/// [`ClientBuilder::max_concurrent_requests_for()`]: crate::ClientBuilder::max_concurrent_requests_for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RequestCategory {
+    /// Requests to the media repository, like uploads and downloads of files
+    /// and thumbnails.
+    Media,
+    /// All the other requests to the homeserver.
+    Api,
+}
+
+impl RequestCategory {
+    /// Get the category of a request from the path of its URI.
+    pub(crate) fn from_path(path: &str) -> Self {
+        if path.starts_with("/_matrix/media/")
+            || path.starts_with("/_matrix/client/v1/media/")
+            || path.starts_with("/_matrix/client/unstable/org.matrix.msc3916/media/")
+        {
+            Self::Media
+        } else {
+            Self::Api
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
-    use super::RequestConfig;
+    use super::{RequestCategory, RequestConfig};
 
     #[test]
     fn smoketest() {
@@ -161,4 +190,26 @@ mod tests {
         let cfg = RequestConfig::short_retry();
         assert_eq!(cfg.retry_limit, Some(3));
     }
+
+    #[test]
+    fn request_category_from_path() {
+        assert_eq!(
+            RequestCategory::from_path("/_matrix/media/v3/download/localhost/abcd"),
+            RequestCategory::Media
+        );
+        assert_eq!(
+            RequestCategory::from_path("/_matrix/client/v1/media/thumbnail/localhost/abcd"),
+            RequestCategory::Media
+        );
+        assert_eq!(
+            RequestCategory::from_path(
+                "/_matrix/client/unstable/org.matrix.msc3916/media/download/localhost/abcd"
+            ),
+            RequestCategory::Media
+        );
+        assert_eq!(
+            RequestCategory::from_path("/_matrix/client/v3/account/whoami"),
+            RequestCategory::Api
+        );
+    }
 }
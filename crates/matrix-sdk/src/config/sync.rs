@@ -15,7 +15,14 @@
 use std::{fmt, time::Duration};
 
 use matrix_sdk_common::debug::DebugStructExt;
-use ruma::{api::client::sync::sync_events, presence::PresenceState};
+use ruma::{
+    api::client::{
+        filter::{FilterDefinition, LazyLoadOptions},
+        sync::sync_events,
+    },
+    presence::PresenceState,
+    OwnedRoomId,
+};
 
 const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -24,6 +31,7 @@ const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
 pub struct SyncSettings {
     // Filter is pretty big at 1000 bytes, box it to reduce stack size
     pub(crate) filter: Option<Box<sync_events::v3::Filter>>,
+    pub(crate) filter_builder: Option<SyncFilterBuilder>,
     pub(crate) timeout: Option<Duration>,
     pub(crate) token: Option<String>,
     pub(crate) full_state: bool,
@@ -39,9 +47,10 @@ impl Default for SyncSettings {
 #[cfg(not(tarpaulin_include))]
 impl fmt::Debug for SyncSettings {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let Self { filter, timeout, token: _, full_state, set_presence } = self;
+        let Self { filter, filter_builder, timeout, token: _, full_state, set_presence } = self;
         f.debug_struct("SyncSettings")
             .maybe_field("filter", filter)
+            .maybe_field("filter_builder", filter_builder)
             .maybe_field("timeout", timeout)
             .field("full_state", full_state)
             .field("set_presence", set_presence)
@@ -55,6 +64,7 @@ impl SyncSettings {
     pub fn new() -> Self {
         Self {
             filter: None,
+            filter_builder: None,
             timeout: Some(DEFAULT_SYNC_TIMEOUT),
             token: None,
             full_state: false,
@@ -98,6 +108,25 @@ impl SyncSettings {
         self
     }
 
+    /// Set the sync filter with a [`SyncFilterBuilder`].
+    ///
+    /// The filter is uploaded to the server the first time it is used, and
+    /// its ID is cached in the store so the following syncs, even after a
+    /// restart, only send the filter ID. Guests can't upload filters, so the
+    /// whole filter is sent with every sync when logged into a guest account.
+    ///
+    /// This is ignored if a filter was set with [`SyncSettings::filter()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - The builder of the filter that should be used for the sync
+    ///   call.
+    #[must_use]
+    pub fn filter_builder(mut self, builder: SyncFilterBuilder) -> Self {
+        self.filter_builder = Some(builder);
+        self
+    }
+
     /// Should the server return the full state from the start of the timeline.
     ///
     /// This does nothing if no sync token is set.
@@ -129,3 +158,119 @@ impl SyncSettings {
         self
     }
 }
+
+/// Builder for the filter of a sync call.
+///
+/// It allows to configure the most common options of a [`FilterDefinition`],
+/// and is used with [`SyncSettings::filter_builder()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use matrix_sdk::config::{SyncFilterBuilder, SyncSettings};
+/// let builder = SyncFilterBuilder::new()
+///     .lazy_load_members(true)
+///     .timeline_limit(20)
+///     .not_event_types(["m.call.invite"]);
+///
+/// let sync_settings = SyncSettings::new().filter_builder(builder);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SyncFilterBuilder {
+    lazy_load_members: bool,
+    timeline_limit: Option<u32>,
+    event_types: Option<Vec<String>>,
+    not_event_types: Vec<String>,
+    rooms: Option<Vec<OwnedRoomId>>,
+    not_rooms: Vec<OwnedRoomId>,
+}
+
+impl SyncFilterBuilder {
+    /// Create a new builder for a filter that doesn't filter anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the membership events of the room members should be lazily
+    /// loaded.
+    ///
+    /// If this is enabled, the state of a room only contains the membership
+    /// events of the senders of the events in the timeline.
+    #[must_use]
+    pub fn lazy_load_members(mut self, enabled: bool) -> Self {
+        self.lazy_load_members = enabled;
+        self
+    }
+
+    /// Set the maximum number of events to return in the timeline of each
+    /// room.
+    #[must_use]
+    pub fn timeline_limit(mut self, limit: u32) -> Self {
+        self.timeline_limit = Some(limit);
+        self
+    }
+
+    /// Only include the events of the given types in the timeline of the
+    /// rooms.
+    ///
+    /// A `*` can be used as a wildcard to match any sequence of characters.
+    #[must_use]
+    pub fn event_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.event_types = Some(types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Exclude the events of the given types from the timeline of the rooms.
+    ///
+    /// A `*` can be used as a wildcard to match any sequence of characters.
+    /// This takes precedence over [`SyncFilterBuilder::event_types()`].
+    #[must_use]
+    pub fn not_event_types(mut self, types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.not_event_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only include the given rooms.
+    #[must_use]
+    pub fn rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.rooms = Some(rooms.into_iter().collect());
+        self
+    }
+
+    /// Exclude the given rooms.
+    ///
+    /// This takes precedence over [`SyncFilterBuilder::rooms()`].
+    #[must_use]
+    pub fn not_rooms(mut self, rooms: impl IntoIterator<Item = OwnedRoomId>) -> Self {
+        self.not_rooms = rooms.into_iter().collect();
+        self
+    }
+
+    /// Build the definition of the filter.
+    pub fn build(&self) -> FilterDefinition {
+        let mut definition = FilterDefinition::default();
+
+        if self.lazy_load_members {
+            definition.room.state.lazy_load_options =
+                LazyLoadOptions::Enabled { include_redundant_members: false };
+        }
+
+        definition.room.timeline.limit = self.timeline_limit.map(Into::into);
+        definition.room.timeline.types = self.event_types.clone();
+        definition.room.timeline.not_types = self.not_event_types.clone();
+        definition.room.rooms = self.rooms.clone();
+        definition.room.not_rooms = self.not_rooms.clone();
+
+        definition
+    }
+
+    /// The name under which the ID of the filter is cached in the store.
+    ///
+    /// It depends on the definition of the filter, so a new filter is
+    /// uploaded when the options change.
+    pub(crate) fn filter_name(&self) -> String {
+        let definition =
+            serde_json::to_string(&self.build()).expect("filter definition serialization failed");
+        format!("sync_filter_builder:{definition}")
+    }
+}
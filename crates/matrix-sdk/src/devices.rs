@@ -0,0 +1,146 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management of the devices, or sessions, of the current user.
+
+use ruma::{
+    api::client::device::{delete_devices, get_devices},
+    DeviceId, MilliSecondsSinceUnixEpoch, OwnedDeviceId,
+};
+#[cfg(feature = "e2e-encryption")]
+use tracing::warn;
+
+use crate::{
+    uiaa::{send_with_uiaa, UiaaHandler},
+    Client, HttpResult,
+};
+
+/// A device of the current user, as listed by the homeserver.
+#[derive(Clone, Debug)]
+pub struct OwnDevice {
+    /// The ID of the device.
+    pub device_id: OwnedDeviceId,
+    /// The display name of the device, if any.
+    pub display_name: Option<String>,
+    /// The IP address from which the device was last seen, if known.
+    pub last_seen_ip: Option<String>,
+    /// When the device was last seen, if known.
+    pub last_seen_ts: Option<MilliSecondsSinceUnixEpoch>,
+    /// Whether this is the device of the client.
+    pub is_current: bool,
+    /// Whether the device is verified.
+    pub verification_state: DeviceVerificationState,
+}
+
+/// The verification state of an [`OwnDevice`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeviceVerificationState {
+    /// The device was verified, either with cross-signing or locally.
+    Verified,
+    /// The device isn't verified.
+    Unverified,
+    /// The state is unknown, because the device doesn't support encryption,
+    /// its keys weren't downloaded yet, or encryption isn't enabled.
+    Unknown,
+}
+
+impl Client {
+    /// Get all the devices of the current user.
+    ///
+    /// When encryption is enabled, the verification state of the devices is
+    /// looked up in the crypto store.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let mut client = Client::new(homeserver).await?;
+    /// for device in client.devices().await? {
+    ///     println!(
+    ///         "Device: {} {} {:?}",
+    ///         device.device_id,
+    ///         device.display_name.as_deref().unwrap_or(""),
+    ///         device.verification_state,
+    ///     );
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn devices(&self) -> HttpResult<Vec<OwnDevice>> {
+        let request = get_devices::v3::Request::new();
+        let response = self.send(request, None).await?;
+        let own_device_id = self.device_id();
+
+        let mut devices = Vec::with_capacity(response.devices.len());
+
+        for device in response.devices {
+            let verification_state = self.device_verification_state(&device.device_id).await;
+
+            devices.push(OwnDevice {
+                is_current: own_device_id == Some(&*device.device_id),
+                device_id: device.device_id,
+                display_name: device.display_name,
+                last_seen_ip: device.last_seen_ip,
+                last_seen_ts: device.last_seen_ts,
+                verification_state,
+            });
+        }
+
+        Ok(devices)
+    }
+
+    #[cfg(feature = "e2e-encryption")]
+    async fn device_verification_state(&self, device_id: &DeviceId) -> DeviceVerificationState {
+        let Some(user_id) = self.user_id() else {
+            return DeviceVerificationState::Unknown;
+        };
+
+        match self.encryption().get_device(user_id, device_id).await {
+            Ok(Some(device)) if device.is_verified() => DeviceVerificationState::Verified,
+            Ok(Some(_)) => DeviceVerificationState::Unverified,
+            Ok(None) => DeviceVerificationState::Unknown,
+            Err(error) => {
+                warn!(?device_id, "Couldn't load the device from the crypto store: {error}");
+                DeviceVerificationState::Unknown
+            }
+        }
+    }
+
+    #[cfg(not(feature = "e2e-encryption"))]
+    async fn device_verification_state(&self, _device_id: &DeviceId) -> DeviceVerificationState {
+        DeviceVerificationState::Unknown
+    }
+
+    /// Delete the given devices from the server, going through the
+    /// user-interactive authentication with the given handler.
+    ///
+    /// This is the same as [`Client::delete_devices()`], except that the
+    /// authentication stages required by the server are completed by calling
+    /// the handler, until the devices are deleted or the handler cancels.
+    ///
+    /// # Arguments
+    ///
+    /// * `devices` - The devices that should be deleted from the server.
+    ///
+    /// * `uiaa_handler` - The handler providing the authentication data.
+    pub async fn delete_devices_with_uiaa(
+        &self,
+        devices: &[OwnedDeviceId],
+        uiaa_handler: &dyn UiaaHandler,
+    ) -> HttpResult<delete_devices::v3::Response> {
+        send_with_uiaa(uiaa_handler, |auth_data| self.delete_devices(devices, auth_data)).await
+    }
+}
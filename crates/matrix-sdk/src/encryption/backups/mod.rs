@@ -28,6 +28,8 @@ use matrix_sdk_base::crypto::{
     backups::MegolmV1BackupKey, store::BackupDecryptionKey, types::RoomKeyBackupInfo,
     KeysBackupRequest, OlmMachine, RoomKeyImportResult,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use matrix_sdk_common::time::Clock;
 use ruma::{
     api::client::{
         backup::{
@@ -573,7 +575,7 @@ impl Backups {
                 {
                     let delay =
                         self.client.inner.backup_state.upload_delay.read().unwrap().to_owned();
-                    tokio::time::sleep(delay).await;
+                    self.client.inner.clock.sleep(delay).await;
                 }
 
                 Ok(())
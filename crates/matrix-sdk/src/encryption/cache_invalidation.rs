@@ -0,0 +1,324 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A channel between the processes using the same crypto store, to invalidate
+//! the in-memory caches of their `OlmMachine` precisely.
+//!
+//! Every time a process writes to the crypto store, it appends the caches
+//! that became outdated to a log in a file shared by all the processes, under
+//! a new generation number, and calls its notifier so the other processes can
+//! be woken up. The log is only rewritten while holding an exclusive lock on a
+//! file next to it, so the entries of concurrent processes are never lost.
+//!
+//! When a process acquires the cross-process lock of the crypto store, or when
+//! it is notified, it reads the entries of the other processes since the last
+//! generation it knows about and only invalidates the corresponding caches.
+//! If the log doesn't go back far enough, or was reset, the whole `OlmMachine`
+//! is recreated instead.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex as StdMutex,
+};
+
+use fs4::FileExt;
+use matrix_sdk_base::crypto::store::CacheInvalidation;
+use ruma::TransactionId;
+use serde::{Deserialize, Serialize};
+use tracing::{trace, warn};
+
+/// The maximum number of entries kept in the log.
+const MAX_ENTRIES: usize = 100;
+
+/// The content of the file shared by the processes.
+#[derive(Debug, Serialize, Deserialize)]
+struct InvalidationLog {
+    /// A random identifier of the log, which changes when it is reset.
+    id: String,
+    /// The generation of the last entry.
+    generation: u64,
+    /// The most recent entries, in ascending order of generation.
+    entries: VecDeque<InvalidationLogEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InvalidationLogEntry {
+    generation: u64,
+    /// The holder of the cross-process lock of the process that wrote the
+    /// changes.
+    holder: String,
+    invalidation: CacheInvalidation,
+}
+
+impl InvalidationLog {
+    fn new() -> Self {
+        Self { id: TransactionId::new().to_string(), generation: 0, entries: VecDeque::new() }
+    }
+}
+
+pub(crate) struct CacheInvalidationChannel {
+    path: PathBuf,
+    holder: String,
+    notifier: Box<dyn Fn() + Send + Sync>,
+    /// The ID of the log and its last generation whose entries were applied
+    /// to the caches of this process.
+    applied_generation: StdMutex<(String, u64)>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for CacheInvalidationChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheInvalidationChannel")
+            .field("path", &self.path)
+            .field("holder", &self.holder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CacheInvalidationChannel {
+    /// Open the channel using the file at the given path, which is created if
+    /// it doesn't exist.
+    ///
+    /// The caches are considered up to date with the current content of the
+    /// log.
+    pub(crate) fn new(
+        path: PathBuf,
+        holder: String,
+        notifier: Box<dyn Fn() + Send + Sync>,
+    ) -> io::Result<Self> {
+        let _lock = lock_log(&path)?;
+
+        let log = match read_log(&path)? {
+            Some(log) => log,
+            None => {
+                let log = InvalidationLog::new();
+                write_log(&path, &log)?;
+                log
+            }
+        };
+
+        Ok(Self {
+            path,
+            holder,
+            notifier,
+            applied_generation: StdMutex::new((log.id, log.generation)),
+        })
+    }
+
+    /// Append the caches outdated by a write of this process to the log, and
+    /// notify the other processes.
+    pub(crate) fn record(&self, invalidation: &CacheInvalidation) {
+        let _lock = match lock_log(&self.path) {
+            Ok(lock) => lock,
+            Err(error) => {
+                warn!("Failed to lock the crypto store invalidation log: {error}");
+                return;
+            }
+        };
+
+        let mut log = match read_log(&self.path) {
+            Ok(Some(log)) => log,
+            Ok(None) => InvalidationLog::new(),
+            Err(error) => {
+                warn!("Failed to read the crypto store invalidation log, resetting it: {error}");
+                InvalidationLog::new()
+            }
+        };
+
+        log.generation += 1;
+        log.entries.push_back(InvalidationLogEntry {
+            generation: log.generation,
+            holder: self.holder.clone(),
+            invalidation: invalidation.clone(),
+        });
+
+        while log.entries.len() > MAX_ENTRIES {
+            log.entries.pop_front();
+        }
+
+        if let Err(error) = write_log(&self.path, &log) {
+            warn!("Failed to write the crypto store invalidation log: {error}");
+            return;
+        }
+
+        trace!(generation = log.generation, "Recorded crypto store changes");
+
+        (self.notifier)();
+    }
+
+    /// Take the caches outdated by the other processes since the last call.
+    ///
+    /// Returns `None` if the changes are unknown, because the log was reset or
+    /// doesn't go back far enough, in which case all the caches must be
+    /// invalidated.
+    pub(crate) fn take_foreign_changes(&self) -> Option<CacheInvalidation> {
+        let log = match read_log(&self.path) {
+            Ok(Some(log)) => log,
+            Ok(None) => {
+                warn!("The crypto store invalidation log was removed");
+                return None;
+            }
+            Err(error) => {
+                warn!("Failed to read the crypto store invalidation log: {error}");
+                return None;
+            }
+        };
+
+        let mut applied_generation = self.applied_generation.lock().unwrap();
+        let (applied_id, previous_generation) = &mut *applied_generation;
+
+        if *applied_id == log.id && *previous_generation == log.generation {
+            return Some(CacheInvalidation::default());
+        }
+
+        let is_same_log = *applied_id == log.id;
+        let previous_generation = std::mem::replace(previous_generation, log.generation);
+        *applied_id = log.id;
+
+        let covers_missing_generations = is_same_log
+            && log.generation > previous_generation
+            && log.entries.front().is_some_and(|entry| entry.generation <= previous_generation + 1);
+
+        if !covers_missing_generations {
+            return None;
+        }
+
+        let mut invalidation = CacheInvalidation::default();
+        for entry in log.entries {
+            if entry.generation > previous_generation && entry.holder != self.holder {
+                invalidation.extend(entry.invalidation);
+            }
+        }
+
+        Some(invalidation)
+    }
+}
+
+/// Take the exclusive lock of the log, for all the processes and the tasks of
+/// this process.
+///
+/// The lock is released when the returned file is closed. It is taken on a
+/// separate file because the log is replaced on every write.
+fn lock_log(path: &Path) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path.with_extension("lock"))?;
+    file.lock_exclusive()?;
+    Ok(file)
+}
+
+fn read_log(path: &Path) -> io::Result<Option<InvalidationLog>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Write the log to a temporary file first, so the other processes never read
+/// a partially written log.
+fn write_log(path: &Path, log: &InvalidationLog) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_vec(log)?)?;
+    fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_base::crypto::store::CacheInvalidation;
+    use ruma::owned_room_id;
+
+    use super::{read_log, CacheInvalidationChannel};
+
+    fn channel(path: &std::path::Path, holder: &str) -> CacheInvalidationChannel {
+        CacheInvalidationChannel::new(path.to_owned(), holder.to_owned(), Box::new(|| {})).unwrap()
+    }
+
+    #[test]
+    fn test_foreign_changes_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invalidation.json");
+
+        let app = channel(&path, "app");
+        let nse = channel(&path, "nse");
+
+        nse.record(&CacheInvalidation { account: true, ..Default::default() });
+        app.record(&CacheInvalidation { tracked_users: true, ..Default::default() });
+        nse.record(&CacheInvalidation {
+            outbound_group_sessions: [owned_room_id!("!room:localhost")].into(),
+            ..Default::default()
+        });
+
+        // The app only sees the changes of the extension.
+        let invalidation = app.take_foreign_changes().unwrap();
+        assert!(invalidation.account);
+        assert!(!invalidation.tracked_users);
+        assert_eq!(invalidation.outbound_group_sessions.len(), 1);
+
+        // The changes are only taken once.
+        assert!(app.take_foreign_changes().unwrap().is_empty());
+
+        // And vice versa.
+        let invalidation = nse.take_foreign_changes().unwrap();
+        assert!(!invalidation.account);
+        assert!(invalidation.tracked_users);
+    }
+
+    #[test]
+    fn test_unknown_changes_after_reset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invalidation.json");
+
+        let nse = channel(&path, "nse");
+        nse.record(&CacheInvalidation { account: true, ..Default::default() });
+
+        let app = channel(&path, "app");
+
+        // The log was removed, so the changes are unknown.
+        std::fs::remove_file(&path).unwrap();
+        nse.record(&CacheInvalidation { tracked_users: true, ..Default::default() });
+        nse.record(&CacheInvalidation { tracked_users: true, ..Default::default() });
+
+        assert!(app.take_foreign_changes().is_none());
+        assert!(app.take_foreign_changes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_records_are_not_lost() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("invalidation.json");
+
+        let channels: Vec<_> = (0..4).map(|i| channel(&path, &format!("process{i}"))).collect();
+
+        std::thread::scope(|scope| {
+            for channel in &channels {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        channel.record(&CacheInvalidation { account: true, ..Default::default() });
+                    }
+                });
+            }
+        });
+
+        let log = read_log(&path).unwrap().unwrap();
+        assert_eq!(log.generation, 40);
+        assert_eq!(log.entries.len(), 40);
+    }
+}
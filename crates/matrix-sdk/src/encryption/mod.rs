@@ -21,6 +21,7 @@ use std::{
     io::{Cursor, Read, Write},
     iter,
     path::PathBuf,
+    sync::Arc,
 };
 
 use eyeball::SharedObservable;
@@ -29,6 +30,8 @@ use futures_util::{
     future::try_join,
     stream::{self, StreamExt},
 };
+#[cfg(not(target_arch = "wasm32"))]
+use matrix_sdk_base::crypto::AsyncAttachmentEncryptor;
 use matrix_sdk_base::crypto::{
     CrossSigningBootstrapRequests, OlmMachine, OutgoingRequest, RoomMessageRequest, ToDeviceRequest,
 };
@@ -66,7 +69,7 @@ use self::{
     secret_storage::SecretStorage,
 };
 use crate::{
-    attachment::{AttachmentInfo, Thumbnail},
+    attachment::{AttachmentData, AttachmentInfo, Thumbnail},
     encryption::{
         identities::{Device, UserDevices},
         verification::{SasVerification, Verification, VerificationRequest},
@@ -77,6 +80,8 @@ use crate::{
 };
 
 pub mod backups;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache_invalidation;
 pub mod futures;
 pub mod identities;
 pub mod recovery;
@@ -93,6 +98,8 @@ pub use matrix_sdk_base::crypto::{
     SessionCreationError, SignatureError, VERSION,
 };
 
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use self::cache_invalidation::CacheInvalidationChannel;
 pub use crate::error::RoomKeyImportError;
 
 /// Settings for end-to-end encryption features.
@@ -223,13 +230,53 @@ impl Client {
         PrepareEncryptedFile::new(self, content_type, reader)
     }
 
+    /// Encrypt and upload the file to be read from `reader` as it is sent.
+    ///
+    /// This is the same as [`Client::prepare_encrypted_file()`], except that
+    /// the file is never entirely loaded in memory, which allows to upload big
+    /// files. The upload is not retried if it fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the file.
+    ///
+    /// * `reader` - The reader of the raw bytes of the file.
+    ///
+    /// * `size` - The size of the file, in bytes.
+    ///
+    /// * `send_progress` - The observable to report the progress of the upload
+    ///   to.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn prepare_encrypted_file_stream(
+        &self,
+        content_type: &mime::Mime,
+        reader: impl futures_util::io::AsyncRead + Send + Sync + Unpin + 'static,
+        size: u64,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<ruma::events::room::EncryptedFile> {
+        // The encryption doesn't change the size of the data.
+        let encryptor = AsyncAttachmentEncryptor::new(reader);
+        let (response, encryptor) =
+            self.media().upload_reader(content_type, encryptor, size, send_progress).await?;
+        let keys = encryptor.finish();
+
+        Ok(ruma::events::room::EncryptedFileInit {
+            url: response.content_uri,
+            key: keys.key,
+            iv: keys.iv,
+            hashes: keys.hashes,
+            v: keys.version,
+        }
+        .into())
+    }
+
     /// Encrypt and upload the file to be read from `reader` and construct an
     /// attachment message with `body`, `content_type`, `info` and `thumbnail`.
     pub(crate) async fn prepare_encrypted_attachment_message(
         &self,
         body: &str,
         content_type: &mime::Mime,
-        data: Vec<u8>,
+        data: AttachmentData,
         info: Option<AttachmentInfo>,
         thumbnail: Option<Thumbnail>,
         send_progress: SharedObservable<TransmissionProgress>,
@@ -238,10 +285,19 @@ impl Client {
             self.upload_encrypted_thumbnail(thumbnail, content_type, send_progress.clone());
 
         let upload_attachment = async {
-            let mut cursor = Cursor::new(data);
-            self.prepare_encrypted_file(content_type, &mut cursor)
-                .with_send_progress_observable(send_progress)
-                .await
+            match data {
+                AttachmentData::Bytes(data) => {
+                    let mut cursor = Cursor::new(data);
+                    self.prepare_encrypted_file(content_type, &mut cursor)
+                        .with_send_progress_observable(send_progress)
+                        .await
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                AttachmentData::Stream { reader, size } => {
+                    self.prepare_encrypted_file_stream(content_type, reader, size, send_progress)
+                        .await
+                }
+            }
         };
 
         let ((thumbnail_source, thumbnail_info), file) =
@@ -1166,9 +1222,114 @@ impl Encryption {
         Ok(())
     }
 
+    /// Enables the invalidation of the caches of the `OlmMachine` shared
+    /// between the processes using the same crypto store.
+    ///
+    /// By default, when a process acquires the cross-process lock after
+    /// another process wrote to the crypto store, the whole `OlmMachine` is
+    /// recreated. With this channel, every process records the caches that
+    /// its writes made outdated in the file at `path`, and only those caches
+    /// are reloaded by the other processes.
+    ///
+    /// The cross-process lock must have been enabled first with
+    /// [`Self::enable_cross_process_store_lock`], and all the processes using
+    /// the crypto store must enable the channel with the same file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file shared by the processes, which is
+    ///   created if it doesn't exist.
+    ///
+    /// * `notifier` - A function called after each write of this process, to
+    ///   wake up the other processes, which should then call
+    ///   [`Self::apply_cross_process_cache_invalidations`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn enable_cross_process_cache_invalidation(
+        &self,
+        path: impl Into<PathBuf>,
+        notifier: impl Fn() + Send + Sync + 'static,
+    ) -> Result<(), Error> {
+        let Some(lock) = self.client.locks().cross_process_crypto_store_lock.get() else {
+            error!("The cross-process lock must be enabled before the cache invalidation");
+            return Err(Error::BadCryptoStoreState);
+        };
+
+        let channel = CacheInvalidationChannel::new(
+            path.into(),
+            lock.lock_holder().to_owned(),
+            Box::new(notifier),
+        )?;
+
+        self.client
+            .locks()
+            .crypto_store_invalidation
+            .set(Arc::new(channel))
+            .map_err(|_| Error::BadCryptoStoreState)?;
+
+        let olm_machine = self.client.olm_machine().await;
+        let olm_machine = olm_machine.as_ref().ok_or(Error::NoOlmMachine)?;
+        self.install_cache_invalidation_listener(olm_machine);
+
+        Ok(())
+    }
+
+    /// Invalidate the caches of the `OlmMachine` that were made outdated by the
+    /// other processes, if the cross-process cache invalidation was enabled
+    /// with [`Self::enable_cross_process_cache_invalidation`].
+    ///
+    /// This is called automatically when the cross-process lock is acquired,
+    /// but can be called when the other processes notify this one, to keep the
+    /// caches fresh without waiting for the lock.
+    ///
+    /// If the changes of the other processes are unknown, the whole
+    /// `OlmMachine` is recreated.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn apply_cross_process_cache_invalidations(&self) -> Result<(), Error> {
+        let Some(channel) = self.client.locks().crypto_store_invalidation.get() else {
+            return Ok(());
+        };
+
+        let olm_machine_guard = self.client.olm_machine().await;
+        let Some(olm_machine) = olm_machine_guard.as_ref() else {
+            return Ok(());
+        };
+
+        if let Some(invalidation) = channel.take_foreign_changes() {
+            olm_machine.invalidate_caches(&invalidation).await?;
+        } else {
+            // (get rid of the reference to the current crypto store first)
+            drop(olm_machine_guard);
+            self.client.base_client().regenerate_olm().await?;
+
+            // The new `OlmMachine` has a new store wrapper, without the listener.
+            let olm_machine = self.client.olm_machine().await;
+            if let Some(olm_machine) = olm_machine.as_ref() {
+                self.install_cache_invalidation_listener(olm_machine);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the writes of the given `OlmMachine` in the cross-process cache
+    /// invalidation channel, if it was enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn install_cache_invalidation_listener(&self, olm_machine: &OlmMachine) {
+        if let Some(channel) = self.client.locks().crypto_store_invalidation.get() {
+            let channel = channel.clone();
+            olm_machine
+                .set_cache_invalidation_listener(move |invalidation| channel.record(invalidation));
+        }
+    }
+
     /// Maybe reload the `OlmMachine` after acquiring the lock for the first
     /// time.
     async fn on_lock_newly_acquired(&self) -> Result<(), Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.client.locks().crypto_store_invalidation.get().is_some() {
+            return self.apply_cross_process_cache_invalidations().await;
+        }
+
         let olm_machine_guard = self.client.olm_machine().await;
         if let Some(olm_machine) = olm_machine_guard.as_ref() {
             // If the crypto store generation has changed,
@@ -1586,4 +1747,86 @@ mod tests {
         let after_taking_lock_second_time = client.olm_machine().await.as_ref().unwrap().clone();
         assert!(after_taking_lock_first_time.same_as(&after_taking_lock_second_time));
     }
+
+    #[cfg(feature = "sqlite")]
+    #[async_test]
+    async fn test_cross_process_cache_invalidation_keeps_olm_machine() {
+        // Create two clients using the same sqlite database and invalidation log.
+        let sqlite_path = std::env::temp_dir().join("cache_invalidation_sqlite.db");
+        let log_path = std::env::temp_dir().join("cache_invalidation_log.json");
+        _ = std::fs::remove_file(&log_path);
+
+        let session = MatrixSession {
+            meta: SessionMeta {
+                user_id: user_id!("@example:localhost").to_owned(),
+                device_id: device_id!("DEVICEID").to_owned(),
+            },
+            tokens: MatrixSessionTokens { access_token: "1234".to_owned(), refresh_token: None },
+        };
+
+        let client1 = Client::builder()
+            .homeserver_url("http://localhost:1234")
+            .request_config(RequestConfig::new().disable_retry())
+            .sqlite_store(&sqlite_path, None)
+            .build()
+            .await
+            .unwrap();
+        client1.matrix_auth().restore_session(session.clone()).await.unwrap();
+
+        let client2 = Client::builder()
+            .homeserver_url("http://localhost:1234")
+            .request_config(RequestConfig::new().disable_retry())
+            .sqlite_store(sqlite_path, None)
+            .build()
+            .await
+            .unwrap();
+        client2.matrix_auth().restore_session(session).await.unwrap();
+
+        // The lock must be enabled first.
+        assert!(client1
+            .encryption()
+            .enable_cross_process_cache_invalidation(&log_path, || {})
+            .await
+            .is_err());
+
+        for (client, holder) in [(&client1, "client1"), (&client2, "client2")] {
+            client.encryption().enable_cross_process_store_lock(holder.to_owned()).await.unwrap();
+            client
+                .encryption()
+                .enable_cross_process_cache_invalidation(&log_path, || {})
+                .await
+                .unwrap();
+        }
+
+        // The first client loads the tracked users in its cache.
+        let acquired1 = client1.encryption().try_lock_store_once().await.unwrap();
+        assert!(acquired1.is_some());
+
+        let initial_olm_machine =
+            client1.olm_machine().await.clone().expect("must have an olm machine");
+        assert!(initial_olm_machine.tracked_users().await.unwrap().is_empty());
+
+        drop(acquired1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // The second client tracks a new user.
+        let acquired2 = client2.encryption().try_lock_store_once().await.unwrap();
+        assert!(acquired2.is_some());
+
+        let bob = user_id!("@bob:localhost");
+        let olm_machine2 = client2.olm_machine().await.clone().expect("must have an olm machine");
+        olm_machine2.update_tracked_users([bob]).await.unwrap();
+
+        drop(acquired2);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // When the first client acquires the lock again, its olm machine is kept but
+        // the outdated cache was invalidated.
+        let acquired1 = client1.encryption().try_lock_store_once().await.unwrap();
+        assert!(acquired1.is_some());
+
+        let olm_machine = client1.olm_machine().await.clone().expect("must have an olm machine");
+        assert!(initial_olm_machine.same_as(&olm_machine));
+        assert!(olm_machine.tracked_users().await.unwrap().contains(bob));
+    }
 }
@@ -33,15 +33,17 @@ use ruma::{
         },
         error::{FromHttpResponseError, IntoHttpError},
     },
-    events::tag::InvalidUserTagName,
+    events::{tag::InvalidUserTagName, StateEventType},
     push::{InsertPushRuleError, RemovePushRuleError},
-    IdParseError,
+    IdParseError, OwnedRoomId, RoomVersionId,
 };
 use serde_json::Error as JsonError;
 use thiserror::Error;
 use url::ParseError as UrlParseError;
 
-use crate::store_locks::LockStoreError;
+use crate::{
+    http_client::HttpTransportError, room::CallSignalingState, store_locks::LockStoreError,
+};
 
 /// Result type of the matrix-sdk.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -88,6 +90,10 @@ pub enum HttpError {
     #[error(transparent)]
     Reqwest(#[from] ReqwestError),
 
+    /// An error from the custom HTTP transport.
+    #[error(transparent)]
+    Transport(HttpTransportError),
+
     /// Queried endpoint requires authentication but was called on an anonymous
     /// client.
     #[error("the queried endpoint requires authentication but was called before logging in")]
@@ -97,6 +103,15 @@ pub enum HttpError {
     #[error("the queried endpoint is not meant for clients")]
     NotClientRequest,
 
+    /// The request would modify the state of the homeserver, but the client is
+    /// in read-only mode.
+    ///
+    /// See [`ClientBuilder::read_only()`](crate::ClientBuilder::read_only).
+    #[error(
+        "the client is in read-only mode and can't send requests that modify the server state"
+    )]
+    ReadOnly,
+
     /// An error converting between ruma_*_api types and Hyper types.
     #[error(transparent)]
     Api(FromHttpResponseError<RumaApiError>),
@@ -252,6 +267,24 @@ pub enum Error {
     #[error("wrong room state: {0}")]
     WrongRoomState(WrongRoomState),
 
+    /// Attempted to perform an action on a 1:1 call that is not allowed in
+    /// its current state.
+    #[error("wrong call state: {0}")]
+    WrongCallState(WrongCallState),
+
+    /// The current user doesn't have the power level needed to send a state
+    /// event of the given type in the room.
+    #[error("not allowed to send `{0}` state events in this room")]
+    InsufficientPowerLevel(StateEventType),
+
+    /// The settings of a room to create are invalid.
+    #[error(transparent)]
+    RoomCreation(#[from] RoomCreationError),
+
+    /// The new settings of a room are invalid.
+    #[error(transparent)]
+    RoomSettings(#[from] RoomSettingsError),
+
     /// The client is in inconsistent state. This happens when we set a room to
     /// a specific type, but then cannot get it in this type.
     #[error("The internal client state is inconsistent.")]
@@ -270,6 +303,11 @@ pub enum Error {
     #[error("a concurrent request failed; see logs for details")]
     ConcurrentRequestFailed,
 
+    /// The homeserver doesn't advertise support for the feature needed by the
+    /// request.
+    #[error("the homeserver doesn't support {0}")]
+    UnsupportedFeature(&'static str),
+
     /// An other error was raised
     /// this might happen because encryption was enabled on the base-crate
     /// but not here and that raised.
@@ -457,6 +495,53 @@ pub enum NotificationSettingsError {
     /// Unable to save the push rules
     #[error("Unable to save push rules")]
     UnableToSavePushRules,
+    /// Unable to save the temporary mutes
+    #[error("Unable to save temporary mutes")]
+    UnableToSaveTemporaryMutes,
+    /// Unable to save the invite notification policy
+    #[error("Unable to save invite notification policy")]
+    UnableToSaveInviteNotificationPolicy,
+}
+
+/// Errors that can occur when validating the settings of a
+/// [`RoomCreationBuilder`](crate::room_creation::RoomCreationBuilder).
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum RoomCreationError {
+    /// The localpart of the alias of the room is empty or contains invalid
+    /// characters.
+    #[error("invalid room alias localpart `{0}`")]
+    InvalidAliasLocalpart(String),
+    /// A direct room must have at least one invited user.
+    #[error("a direct room needs at least one invited user")]
+    DirectRoomWithoutInvitee,
+    /// The current user can't invite themselves to the room they create.
+    #[error("the current user can't invite themselves")]
+    InviteSelf,
+    /// Both a power levels preset and a power levels override were set.
+    #[error("a power levels preset can't be combined with a power levels override")]
+    ConflictingPowerLevels,
+    /// The parent space is not a space the current user is joined to.
+    #[error("`{0}` is not a joined space")]
+    UnknownSpace(OwnedRoomId),
+    /// The current user is not allowed to add rooms to the parent space.
+    #[error("not allowed to add rooms to the space `{0}`")]
+    CannotAddToSpace(OwnedRoomId),
+}
+
+/// Errors that can occur when validating the new settings of a room, like its
+/// join rule.
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum RoomSettingsError {
+    /// A restricted join rule must allow the members of at least one room.
+    #[error("a restricted join rule needs at least one allowed room")]
+    RestrictedWithoutAllowedRooms,
+    /// The join rule is not supported by the version of the room.
+    #[error("the join rule is not supported by room version {0}")]
+    UnsupportedJoinRule(RoomVersionId),
+    /// The order of a child of a space is too long or contains invalid
+    /// characters.
+    #[error("invalid space child order `{0}`")]
+    InvalidSpaceChildOrder(String),
 }
 
 impl From<InsertPushRuleError> for NotificationSettingsError {
@@ -483,3 +568,16 @@ impl WrongRoomState {
         Self { expected, got }
     }
 }
+
+#[derive(Debug, Error)]
+#[error("expected: {expected}, got: {got:?}")]
+pub struct WrongCallState {
+    expected: &'static str,
+    got: Option<CallSignalingState>,
+}
+
+impl WrongCallState {
+    pub(crate) fn new(expected: &'static str, got: Option<CallSignalingState>) -> Self {
+        Self { expected, got }
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A cache of the timeline events of the rooms.
+//!
+//! The events received from sync and from backwards paginations are merged in
+//! a single list per room, where:
+//!
+//! - the events are never duplicated, even if they are received both from sync
+//!   and from a pagination,
+//! - the gaps left by limited syncs are tracked with their pagination token,
+//!   and filled by [`RoomEventCache::paginate_backwards()`],
+//! - the changes are published as [`VectorDiff`]s, so views like timelines can
+//!   be kept in sync with [`RoomEventCache::subscribe()`].
+//!
+//! The most recent events and the gaps between them are persisted in the state
+//! store, so the cache of a room is restored the first time its
+//! [`RoomEventCache`] is requested after the client was restarted. A room
+//! starts being cached the first time its [`RoomEventCache`] is requested.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex as StdMutex, Weak},
+};
+
+use eyeball_im::VectorDiff;
+use matrix_sdk_base::{deserialized_responses::SyncTimelineEvent, sync::Timeline};
+use matrix_sdk_common::executor::{spawn, JoinHandle};
+use ruma::{OwnedRoomId, RoomId};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    Mutex, RwLock,
+};
+use tracing::{trace, warn};
+
+use self::store::{PaginationToken, RoomEvents};
+use crate::{
+    client::ClientInner,
+    room::{Messages, MessagesOptions},
+    sync::RoomUpdate,
+    Client, Result, Room,
+};
+
+mod store;
+
+/// The prefix of the keys of the events of the rooms in the state store.
+const STORE_KEY_PREFIX: &str = "event_cache.";
+
+/// The maximum number of events of a room that are persisted in the state
+/// store.
+///
+/// The older events are loaded again with backwards paginations.
+const MAX_PERSISTED_EVENTS: usize = 200;
+
+/// The cache of the timeline events of all the rooms.
+///
+/// It can be obtained with [`Client::event_cache()`].
+#[derive(Clone, Default)]
+pub struct EventCache {
+    rooms: Arc<StdMutex<BTreeMap<OwnedRoomId, RoomEventCache>>>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for EventCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventCache").finish_non_exhaustive()
+    }
+}
+
+impl EventCache {
+    /// Get the cache of the given room, and start caching its events if it
+    /// wasn't already.
+    ///
+    /// The events that were cached before the client was restarted are loaded
+    /// from the store.
+    pub async fn for_room(&self, room: &Room) -> RoomEventCache {
+        let room_event_cache = self.rooms.lock().unwrap().get(room.room_id()).cloned();
+        if let Some(room_event_cache) = room_event_cache {
+            return room_event_cache;
+        }
+
+        let room_event_cache = RoomEventCache::new(room).await;
+
+        // Another task might have started caching the room in the meantime.
+        self.rooms
+            .lock()
+            .unwrap()
+            .entry(room.room_id().to_owned())
+            .or_insert(room_event_cache)
+            .clone()
+    }
+
+    /// Forget the cached events of all the rooms.
+    pub async fn clear_all_rooms(&self) {
+        let rooms = self.rooms.lock().unwrap().values().cloned().collect::<Vec<_>>();
+
+        for room in rooms {
+            room.clear().await;
+        }
+    }
+}
+
+/// An update of the events of a room in the [`RoomEventCache`].
+#[derive(Debug, Clone)]
+pub enum RoomEventCacheUpdate {
+    /// The list of events of the room changed.
+    UpdateEvents {
+        /// The changes to apply to the list of events, in order.
+        diffs: Vec<VectorDiff<SyncTimelineEvent>>,
+    },
+}
+
+/// The result of [`RoomEventCache::paginate_backwards()`].
+#[derive(Debug, Clone, Copy)]
+pub struct BackPaginationOutcome {
+    /// The number of events that were added to the cache.
+    ///
+    /// It can be lower than the number of events that were requested, if some
+    /// of them were already known.
+    pub num_events: usize,
+    /// Whether all the events of the room are now in the cache, without any
+    /// gap.
+    pub reached_start: bool,
+}
+
+/// The cache of the timeline events of a single room.
+#[derive(Clone)]
+pub struct RoomEventCache {
+    inner: Arc<RoomEventCacheInner>,
+}
+
+struct RoomEventCacheInner {
+    room_id: OwnedRoomId,
+    client: Weak<ClientInner>,
+    events: RwLock<RoomEvents>,
+    sender: broadcast::Sender<RoomEventCacheUpdate>,
+    /// Lock ensuring that a single backwards pagination runs at a time.
+    pagination_lock: Mutex<()>,
+    /// The task listening to the sync updates of the room.
+    listen_task: StdMutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for RoomEventCacheInner {
+    fn drop(&mut self) {
+        if let Some(_task) = self.listen_task.lock().unwrap().take() {
+            #[cfg(not(target_arch = "wasm32"))]
+            _task.abort();
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for RoomEventCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomEventCache").field("room_id", &self.inner.room_id).finish()
+    }
+}
+
+impl RoomEventCache {
+    async fn new(room: &Room) -> Self {
+        // Subscribe before loading the events, so the updates received in the
+        // meantime are not missed.
+        let updates = room.client.subscribe_to_room_updates(room.room_id());
+        let events = load_events(&room.client, room.room_id()).await;
+
+        let inner = Arc::new(RoomEventCacheInner {
+            room_id: room.room_id().to_owned(),
+            client: Arc::downgrade(&room.client.inner),
+            events: RwLock::new(events),
+            sender: broadcast::Sender::new(32),
+            pagination_lock: Default::default(),
+            listen_task: Default::default(),
+        });
+
+        let task = spawn(Self::listen_to_updates(Arc::downgrade(&inner), updates));
+        *inner.listen_task.lock().unwrap() = Some(task);
+
+        Self { inner }
+    }
+
+    async fn listen_to_updates(
+        inner: Weak<RoomEventCacheInner>,
+        mut updates: broadcast::Receiver<RoomUpdate>,
+    ) {
+        loop {
+            let update = updates.recv().await;
+
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            let cache = RoomEventCache { inner };
+
+            match update {
+                Ok(RoomUpdate::Joined { updates, .. }) => {
+                    cache.handle_sync_timeline(updates.timeline).await;
+                }
+                Ok(RoomUpdate::Left { updates, .. }) => {
+                    cache.handle_sync_timeline(updates.timeline).await;
+                }
+                Ok(RoomUpdate::Invited { .. } | RoomUpdate::Knocked { .. }) => {}
+                Err(RecvError::Lagged(num_skipped)) => {
+                    // Some events were missed, so the cache can't guarantee that it
+                    // doesn't have holes anymore.
+                    warn!(
+                        room_id = ?cache.inner.room_id,
+                        num_skipped,
+                        "Missed some room updates, clearing the event cache"
+                    );
+                    cache.clear().await;
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn handle_sync_timeline(&self, timeline: Timeline) {
+        let Timeline { limited, prev_batch, events } = timeline;
+
+        if events.is_empty() && !limited {
+            return;
+        }
+
+        let mut room_events = self.inner.events.write().await;
+        let diffs = room_events.push_sync_timeline(events, limited, prev_batch);
+        self.save(&room_events).await;
+        self.send_diffs(diffs);
+    }
+
+    /// The ID of the room.
+    pub fn room_id(&self) -> &RoomId {
+        &self.inner.room_id
+    }
+
+    /// Get the events that are currently in the cache, in topological order.
+    pub async fn events(&self) -> Vec<SyncTimelineEvent> {
+        self.inner.events.read().await.events().cloned().collect()
+    }
+
+    /// Get the events that are currently in the cache, in topological order,
+    /// and a receiver for the next changes of this list.
+    ///
+    /// No change can happen between the returned events and the first update
+    /// received.
+    pub async fn subscribe(
+        &self,
+    ) -> (Vec<SyncTimelineEvent>, broadcast::Receiver<RoomEventCacheUpdate>) {
+        let room_events = self.inner.events.read().await;
+        let receiver = self.inner.sender.subscribe();
+        (room_events.events().cloned().collect(), receiver)
+    }
+
+    /// The number of gaps in the cache, including the one before the oldest
+    /// event if the start of the room wasn't reached yet.
+    pub async fn num_gaps(&self) -> usize {
+        self.inner.events.read().await.num_gaps()
+    }
+
+    /// Fill the most recent gap of the cache with a backwards pagination.
+    ///
+    /// If there is no gap between the cached events, this loads the events
+    /// before the oldest one. The events that were already in the cache are
+    /// not duplicated, and if the pagination reaches events that were already
+    /// known, the gap is closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_events` - The maximum number of events to request.
+    pub async fn paginate_backwards(&self, num_events: u16) -> Result<BackPaginationOutcome> {
+        let _guard = self.inner.pagination_lock.lock().await;
+
+        let token = self.inner.events.read().await.pagination_token();
+        let from = match &token {
+            PaginationToken::Live => None,
+            PaginationToken::Gap(token) => Some(token.as_str()),
+            PaginationToken::ReachedStart => {
+                return Ok(BackPaginationOutcome { num_events: 0, reached_start: true });
+            }
+        };
+
+        let Some(room) = self.client().and_then(|client| client.get_room(&self.inner.room_id))
+        else {
+            // The client is shutting down.
+            return Ok(BackPaginationOutcome { num_events: 0, reached_start: false });
+        };
+
+        trace!(room_id = ?self.inner.room_id, ?from, "Paginating backwards");
+
+        let mut options = MessagesOptions::backward().from(from);
+        options.limit = num_events.into();
+        let Messages { end, chunk, .. } = room.messages(options).await?;
+
+        let mut room_events = self.inner.events.write().await;
+        let insertion = room_events.insert_backwards_pagination(
+            &token,
+            chunk.into_iter().map(Into::into).collect(),
+            end,
+        );
+        let reached_start = room_events.pagination_token() == PaginationToken::ReachedStart;
+        self.save(&room_events).await;
+        self.send_diffs(insertion.diffs);
+
+        Ok(BackPaginationOutcome { num_events: insertion.num_events, reached_start })
+    }
+
+    /// Forget all the cached events of this room.
+    pub async fn clear(&self) {
+        let mut room_events = self.inner.events.write().await;
+        room_events.clear();
+        self.save(&room_events).await;
+        self.send_diffs(vec![VectorDiff::Clear]);
+    }
+
+    fn client(&self) -> Option<Client> {
+        self.inner.client.upgrade().map(|inner| Client { inner })
+    }
+
+    /// Persist the events of the room in the store.
+    ///
+    /// The cache still works if this fails, but it will start from scratch the
+    /// next time the client is restarted.
+    async fn save(&self, room_events: &RoomEvents) {
+        let Some(client) = self.client() else {
+            return;
+        };
+
+        let value = match room_events.to_json(MAX_PERSISTED_EVENTS) {
+            Ok(value) => value,
+            Err(error) => {
+                warn!(room_id = ?self.inner.room_id, "Failed to serialize the event cache: {error}");
+                return;
+            }
+        };
+
+        if let Err(error) =
+            client.store().set_custom_value(&store_key(&self.inner.room_id), value).await
+        {
+            warn!(room_id = ?self.inner.room_id, "Failed to persist the event cache: {error}");
+        }
+    }
+
+    fn send_diffs(&self, diffs: Vec<VectorDiff<SyncTimelineEvent>>) {
+        if !diffs.is_empty() {
+            // Nobody might be listening, that's fine.
+            _ = self.inner.sender.send(RoomEventCacheUpdate::UpdateEvents { diffs });
+        }
+    }
+}
+
+/// The key of the events of the room in the custom values of the state store.
+fn store_key(room_id: &RoomId) -> Vec<u8> {
+    format!("{STORE_KEY_PREFIX}{room_id}").into_bytes()
+}
+
+/// Load the events of the room that were persisted in the store.
+///
+/// If they can't be loaded, the cache of the room starts empty.
+async fn load_events(client: &Client, room_id: &RoomId) -> RoomEvents {
+    let value = match client.store().get_custom_value(&store_key(room_id)).await {
+        Ok(value) => value,
+        Err(error) => {
+            warn!(?room_id, "Failed to load the event cache: {error}");
+            None
+        }
+    };
+
+    let Some(value) = value else {
+        return RoomEvents::default();
+    };
+
+    RoomEvents::from_json(&value).unwrap_or_else(|error| {
+        warn!(?room_id, "Failed to deserialize the event cache: {error}");
+        RoomEvents::default()
+    })
+}
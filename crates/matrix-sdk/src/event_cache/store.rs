@@ -0,0 +1,597 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The storage of the events of a room, split in chunks separated by gaps.
+
+use std::{collections::HashSet, mem};
+
+use eyeball_im::VectorDiff;
+use matrix_sdk_base::deserialized_responses::SyncTimelineEvent;
+use ruma::OwnedEventId;
+use serde::{Deserialize, Serialize};
+
+/// A list of contiguous events.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Chunk {
+    /// The token to paginate backwards from the start of this chunk, if there
+    /// may be events missing between this chunk and the previous one, or
+    /// before this chunk if it is the first one.
+    prev_token: Option<String>,
+    /// The events of this chunk, in topological order.
+    events: Vec<SyncTimelineEvent>,
+    /// Tokens to paginate backwards from inside this chunk, with the index of
+    /// the event they precede, in ascending order.
+    ///
+    /// They don't separate the events, but they allow to persist only the
+    /// most recent events of the chunk.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inner_tokens: Vec<(usize, String)>,
+}
+
+impl Chunk {
+    /// Insert an event at the start of this chunk.
+    fn push_front(&mut self, event: SyncTimelineEvent) {
+        self.events.insert(0, event);
+
+        for (index, _) in &mut self.inner_tokens {
+            *index += 1;
+        }
+    }
+
+    /// Remove the event at the given index.
+    fn remove(&mut self, position: usize) {
+        self.events.remove(position);
+
+        for (index, _) in &mut self.inner_tokens {
+            if *index > position {
+                *index -= 1;
+            }
+        }
+    }
+
+    /// Add the events of the given chunk, that is contiguous with this one,
+    /// at the end of this chunk.
+    fn append(&mut self, other: Chunk) {
+        let offset = self.events.len();
+        self.events.extend(other.events);
+        self.inner_tokens
+            .extend(other.inner_tokens.into_iter().map(|(index, token)| (index + offset, token)));
+    }
+}
+
+/// A borrowed part of a [`Chunk`], to serialize it.
+#[derive(Serialize)]
+struct ChunkPart<'a> {
+    prev_token: Option<&'a str>,
+    events: &'a [SyncTimelineEvent],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    inner_tokens: Vec<(usize, &'a str)>,
+}
+
+/// Where the next backwards pagination should start from.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum PaginationToken {
+    /// There are no events yet, the pagination must start from the end of the
+    /// timeline.
+    Live,
+    /// The pagination must fill the gap with the given token.
+    Gap(String),
+    /// All the events of the room are known.
+    ReachedStart,
+}
+
+/// The result of inserting the events of a backwards pagination.
+#[derive(Debug, Default)]
+pub(super) struct PaginationInsertion {
+    /// The changes of the list of events.
+    pub(super) diffs: Vec<VectorDiff<SyncTimelineEvent>>,
+    /// The number of new events.
+    pub(super) num_events: usize,
+}
+
+/// The events of a room, grouped in chunks of contiguous events.
+///
+/// A chunk with a `prev_token` is separated from the previous chunk by a gap,
+/// which is filled with a backwards pagination. The first chunk is the start
+/// of the room if it doesn't have a `prev_token`.
+///
+/// An event is never present twice: when an event that is already known is
+/// received again, the old occurrence is removed.
+#[derive(Debug, Default)]
+pub(super) struct RoomEvents {
+    chunks: Vec<Chunk>,
+    event_ids: HashSet<OwnedEventId>,
+    /// Whether the events were restored from the store, and no sync timeline
+    /// was received since.
+    restored: bool,
+}
+
+impl RoomEvents {
+    /// Restore the events persisted with [`RoomEvents::to_json()`].
+    ///
+    /// The events received while they were not cached are unknown, so the
+    /// next sync timeline is handled as if it was limited.
+    pub(super) fn from_json(json: &[u8]) -> serde_json::Result<Self> {
+        let chunks: Vec<Chunk> = serde_json::from_slice(json)?;
+        let event_ids = chunks
+            .iter()
+            .flat_map(|chunk| &chunk.events)
+            .filter_map(|event| event.event_id())
+            .collect();
+
+        Ok(Self { chunks, event_ids, restored: true })
+    }
+
+    /// Serialize the most recent events and the gaps, to persist them.
+    ///
+    /// If there are more than `max_events`, the oldest events are left out at
+    /// a position that can be paginated from, so they can be loaded again.
+    /// More events are kept if there is no such position.
+    pub(super) fn to_json(&self, max_events: usize) -> serde_json::Result<Vec<u8>> {
+        let (start_chunk, start_event) = self.persisted_start(max_events);
+
+        let chunks: Vec<_> = self.chunks[start_chunk..]
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let skipped = if i == 0 { start_event } else { 0 };
+                let prev_token = if skipped == 0 {
+                    chunk.prev_token.as_deref()
+                } else {
+                    chunk
+                        .inner_tokens
+                        .iter()
+                        .find(|(index, _)| *index == skipped)
+                        .map(|(_, token)| token.as_str())
+                };
+
+                ChunkPart {
+                    prev_token,
+                    events: &chunk.events[skipped..],
+                    inner_tokens: chunk
+                        .inner_tokens
+                        .iter()
+                        .filter(|(index, _)| *index > skipped)
+                        .map(|(index, token)| (index - skipped, token.as_str()))
+                        .collect(),
+                }
+            })
+            .collect();
+
+        serde_json::to_vec(&chunks)
+    }
+
+    /// The position of the oldest event to persist, as the index of its chunk
+    /// and its index in that chunk.
+    ///
+    /// It is the oldest position with a token such that at most `max_events`
+    /// are persisted, or the most recent position with a token if there is
+    /// none.
+    fn persisted_start(&self, max_events: usize) -> (usize, usize) {
+        if self.events().count() <= max_events {
+            return (0, 0);
+        }
+
+        let mut start = None;
+        let mut num_following_events = 0;
+
+        for (chunk_index, chunk) in self.chunks.iter().enumerate().rev() {
+            let positions = chunk
+                .inner_tokens
+                .iter()
+                .rev()
+                .map(|(index, _)| *index)
+                .chain(chunk.prev_token.is_some().then_some(0));
+
+            for event_index in positions {
+                let num_events = num_following_events + chunk.events.len() - event_index;
+
+                if let Some(start) = start.filter(|_| num_events > max_events) {
+                    return start;
+                }
+
+                start = Some((chunk_index, event_index));
+            }
+
+            num_following_events += chunk.events.len();
+        }
+
+        start.unwrap_or((0, 0))
+    }
+
+    /// Iterate over all the events, in topological order.
+    pub(super) fn events(&self) -> impl Iterator<Item = &SyncTimelineEvent> {
+        self.chunks.iter().flat_map(|chunk| &chunk.events)
+    }
+
+    /// The number of gaps, including the one before the first chunk.
+    pub(super) fn num_gaps(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.prev_token.is_some()).count()
+    }
+
+    /// Remove all the events and gaps.
+    pub(super) fn clear(&mut self) {
+        self.chunks.clear();
+        self.event_ids.clear();
+        self.restored = false;
+    }
+
+    /// Add the events of the timeline of a sync response.
+    ///
+    /// If the timeline is `limited`, there might be events missing between the
+    /// last known event and the new ones, so they are added in a new chunk
+    /// after a gap.
+    pub(super) fn push_sync_timeline(
+        &mut self,
+        events: Vec<SyncTimelineEvent>,
+        limited: bool,
+        prev_batch: Option<String>,
+    ) -> Vec<VectorDiff<SyncTimelineEvent>> {
+        let mut diffs = Vec::new();
+        let limited = limited || mem::take(&mut self.restored);
+
+        if self.chunks.is_empty() {
+            self.chunks.push(Chunk { prev_token: prev_batch, ..Default::default() });
+        } else if limited && prev_batch.is_some() {
+            match self.chunks.last_mut() {
+                // Don't create empty chunks, the previous gap can be replaced.
+                Some(last) if last.events.is_empty() => last.prev_token = prev_batch,
+                _ => self.chunks.push(Chunk { prev_token: prev_batch, ..Default::default() }),
+            }
+        } else if let Some(prev_batch) = prev_batch.filter(|_| !events.is_empty()) {
+            // The events are contiguous with the previous ones, but the token
+            // can still be used to paginate from the first new event.
+            let last = self.chunks.last_mut().expect("there is always a chunk at this point");
+            if !last.events.is_empty() {
+                last.inner_tokens.push((last.events.len(), prev_batch));
+            }
+        }
+
+        for event in events {
+            if let Some(event_id) = event.event_id() {
+                self.remove_event(&event_id, &mut diffs);
+                self.event_ids.insert(event_id);
+            }
+
+            let chunk = self.chunks.last_mut().expect("there is always a chunk at this point");
+            chunk.events.push(event.clone());
+            diffs.push(VectorDiff::PushBack { value: event });
+        }
+
+        diffs
+    }
+
+    /// Where the next backwards pagination should start from.
+    ///
+    /// The most recent gap is filled first, so the events are always
+    /// contiguous from the end of the timeline.
+    pub(super) fn pagination_token(&self) -> PaginationToken {
+        if self.chunks.is_empty() {
+            return PaginationToken::Live;
+        }
+
+        self.chunks
+            .iter()
+            .rev()
+            .find_map(|chunk| chunk.prev_token.clone())
+            .map_or(PaginationToken::ReachedStart, PaginationToken::Gap)
+    }
+
+    /// Insert the events of a backwards pagination.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token the pagination started from.
+    ///
+    /// * `events` - The events that were received, in reverse topological
+    ///   order.
+    ///
+    /// * `end` - The token to continue the pagination, or `None` if the start
+    ///   of the room was reached.
+    pub(super) fn insert_backwards_pagination(
+        &mut self,
+        token: &PaginationToken,
+        events: Vec<SyncTimelineEvent>,
+        end: Option<String>,
+    ) -> PaginationInsertion {
+        let mut insertion = PaginationInsertion::default();
+
+        let chunk_index = match token {
+            PaginationToken::Live => {
+                if !self.chunks.is_empty() {
+                    // Some events were received in the meantime, the result can't be
+                    // placed reliably.
+                    return insertion;
+                }
+
+                self.chunks.push(Chunk::default());
+                0
+            }
+            PaginationToken::Gap(token) => {
+                match self.chunks.iter().position(|chunk| chunk.prev_token.as_ref() == Some(token))
+                {
+                    Some(index) => index,
+                    // The gap was already filled, or the cache was cleared.
+                    None => return insertion,
+                }
+            }
+            PaginationToken::ReachedStart => return insertion,
+        };
+
+        // The token of the gap can still be used to paginate from the events
+        // that were already in the chunk.
+        if let PaginationToken::Gap(token) = token {
+            let chunk = &mut self.chunks[chunk_index];
+            if !chunk.events.is_empty() {
+                chunk.inner_tokens.insert(0, (0, token.clone()));
+            }
+        }
+
+        let mut reached_previous_chunk = false;
+
+        for event in events {
+            if let Some(event_id) = event.event_id() {
+                if self.event_ids.contains(&event_id) {
+                    if chunk_index > 0
+                        && self.chunks[chunk_index - 1]
+                            .events
+                            .iter()
+                            .any(|known| known.event_id().as_ref() == Some(&event_id))
+                    {
+                        // The pagination caught up with the previous chunk, so the gap is
+                        // filled.
+                        reached_previous_chunk = true;
+                        break;
+                    }
+
+                    // The event was already received elsewhere, keep the position
+                    // given by the pagination.
+                    self.remove_event(&event_id, &mut insertion.diffs);
+                }
+
+                self.event_ids.insert(event_id);
+            }
+
+            let index = self.num_events_before(chunk_index);
+            self.chunks[chunk_index].push_front(event.clone());
+            insertion.diffs.push(VectorDiff::Insert { index, value: event });
+            insertion.num_events += 1;
+        }
+
+        // If the homeserver says that there are no more events, the previous chunk
+        // must be contiguous with this one too.
+        if reached_previous_chunk || (end.is_none() && chunk_index > 0) {
+            let chunk = self.chunks.remove(chunk_index);
+            self.chunks[chunk_index - 1].append(chunk);
+        } else {
+            let chunk = &mut self.chunks[chunk_index];
+            chunk.prev_token = end;
+            // A token at the start of the chunk is redundant with the gap.
+            chunk.inner_tokens.retain(|(index, _)| *index > 0);
+        }
+
+        insertion
+    }
+
+    /// The number of events in the chunks before the one at the given index.
+    fn num_events_before(&self, chunk_index: usize) -> usize {
+        self.chunks[..chunk_index].iter().map(|chunk| chunk.events.len()).sum()
+    }
+
+    /// Remove the event with the given ID, if it is known.
+    fn remove_event(
+        &mut self,
+        event_id: &OwnedEventId,
+        diffs: &mut Vec<VectorDiff<SyncTimelineEvent>>,
+    ) {
+        if !self.event_ids.remove(event_id) {
+            return;
+        }
+
+        let mut index = 0;
+        for chunk in &mut self.chunks {
+            if let Some(position) =
+                chunk.events.iter().position(|event| event.event_id().as_ref() == Some(event_id))
+            {
+                chunk.remove(position);
+                diffs.push(VectorDiff::Remove { index: index + position });
+                return;
+            }
+
+            index += chunk.events.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches2::assert_matches;
+    use eyeball_im::VectorDiff;
+    use matrix_sdk_base::deserialized_responses::SyncTimelineEvent;
+    use ruma::serde::Raw;
+    use serde_json::json;
+
+    use super::{PaginationToken, RoomEvents};
+
+    fn event(id: &str) -> SyncTimelineEvent {
+        SyncTimelineEvent::new(
+            Raw::new(&json!({
+                "type": "m.room.message",
+                "event_id": id,
+                "sender": "@alice:localhost",
+                "origin_server_ts": 0,
+                "content": { "msgtype": "m.text", "body": id },
+            }))
+            .unwrap()
+            .cast(),
+        )
+    }
+
+    fn event_ids(room_events: &RoomEvents) -> Vec<String> {
+        room_events.events().map(|event| event.event_id().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_sync_events_are_deduplicated() {
+        let mut room_events = RoomEvents::default();
+
+        let diffs = room_events.push_sync_timeline(
+            vec![event("$a"), event("$b")],
+            false,
+            Some("prev".to_owned()),
+        );
+        assert_eq!(diffs.len(), 2);
+        assert_eq!(room_events.pagination_token(), PaginationToken::Gap("prev".to_owned()));
+
+        let diffs = room_events.push_sync_timeline(vec![event("$a"), event("$c")], false, None);
+        assert_matches!(&diffs[0], VectorDiff::Remove { index: 0 });
+        assert_matches!(&diffs[1], VectorDiff::PushBack { .. });
+        assert_matches!(&diffs[2], VectorDiff::PushBack { .. });
+        assert_eq!(event_ids(&room_events), ["$b", "$a", "$c"]);
+    }
+
+    #[test]
+    fn test_limited_sync_creates_gap_filled_by_pagination() {
+        let mut room_events = RoomEvents::default();
+
+        room_events.push_sync_timeline(vec![event("$a"), event("$b")], false, None);
+        assert_eq!(room_events.pagination_token(), PaginationToken::ReachedStart);
+
+        room_events.push_sync_timeline(
+            vec![event("$e"), event("$f")],
+            true,
+            Some("gap".to_owned()),
+        );
+        assert_eq!(room_events.num_gaps(), 1);
+
+        // The first page doesn't reach the known events.
+        let token = room_events.pagination_token();
+        assert_eq!(token, PaginationToken::Gap("gap".to_owned()));
+        let insertion = room_events.insert_backwards_pagination(
+            &token,
+            vec![event("$d")],
+            Some("gap2".to_owned()),
+        );
+        assert_eq!(insertion.num_events, 1);
+        assert_matches!(&insertion.diffs[0], VectorDiff::Insert { index: 2, .. });
+        assert_eq!(event_ids(&room_events), ["$a", "$b", "$d", "$e", "$f"]);
+
+        // The second page overlaps with the first chunk, which closes the gap.
+        let token = room_events.pagination_token();
+        assert_eq!(token, PaginationToken::Gap("gap2".to_owned()));
+        let insertion = room_events.insert_backwards_pagination(
+            &token,
+            vec![event("$c"), event("$b"), event("$a")],
+            Some("gap3".to_owned()),
+        );
+        assert_eq!(insertion.num_events, 1);
+        assert_eq!(event_ids(&room_events), ["$a", "$b", "$c", "$d", "$e", "$f"]);
+        assert_eq!(room_events.num_gaps(), 0);
+        assert_eq!(room_events.pagination_token(), PaginationToken::ReachedStart);
+    }
+
+    #[test]
+    fn test_restored_events_keep_their_gaps() {
+        let mut room_events = RoomEvents::default();
+        room_events.push_sync_timeline(vec![event("$a")], false, Some("prev".to_owned()));
+        room_events.push_sync_timeline(vec![event("$c")], true, Some("gap".to_owned()));
+
+        let json = room_events.to_json(10).unwrap();
+        let mut room_events = RoomEvents::from_json(&json).unwrap();
+        assert_eq!(event_ids(&room_events), ["$a", "$c"]);
+        assert_eq!(room_events.num_gaps(), 2);
+        assert_eq!(room_events.pagination_token(), PaginationToken::Gap("gap".to_owned()));
+
+        // The events are still deduplicated, and the events that were missed
+        // while they were not cached are behind a new gap.
+        room_events.push_sync_timeline(
+            vec![event("$c"), event("$e")],
+            false,
+            Some("restored".to_owned()),
+        );
+        assert_eq!(event_ids(&room_events), ["$a", "$c", "$e"]);
+        assert_eq!(room_events.num_gaps(), 3);
+        assert_eq!(room_events.pagination_token(), PaginationToken::Gap("restored".to_owned()));
+    }
+
+    #[test]
+    fn test_only_recent_events_are_persisted() {
+        let mut room_events = RoomEvents::default();
+        room_events.push_sync_timeline(
+            vec![event("$a"), event("$b")],
+            false,
+            Some("t1".to_owned()),
+        );
+        room_events.push_sync_timeline(
+            vec![event("$c"), event("$d")],
+            false,
+            Some("t2".to_owned()),
+        );
+        room_events.push_sync_timeline(vec![event("$e")], false, Some("t3".to_owned()));
+
+        let json = room_events.to_json(10).unwrap();
+        let restored = RoomEvents::from_json(&json).unwrap();
+        assert_eq!(event_ids(&restored), ["$a", "$b", "$c", "$d", "$e"]);
+        assert_eq!(restored.pagination_token(), PaginationToken::Gap("t1".to_owned()));
+
+        // The events are left out at the start of a sync timeline, so they can
+        // be loaded again.
+        let json = room_events.to_json(3).unwrap();
+        let restored = RoomEvents::from_json(&json).unwrap();
+        assert_eq!(event_ids(&restored), ["$c", "$d", "$e"]);
+        assert_eq!(restored.pagination_token(), PaginationToken::Gap("t2".to_owned()));
+
+        // The most recent position is used if there are too many events after
+        // every position.
+        let json = room_events.to_json(0).unwrap();
+        let restored = RoomEvents::from_json(&json).unwrap();
+        assert_eq!(event_ids(&restored), ["$e"]);
+        assert_eq!(restored.pagination_token(), PaginationToken::Gap("t3".to_owned()));
+    }
+
+    #[test]
+    fn test_pagination_keeps_gap_token_to_persist_recent_events() {
+        let mut room_events = RoomEvents::default();
+        room_events.push_sync_timeline(vec![event("$c")], false, Some("prev".to_owned()));
+
+        let token = room_events.pagination_token();
+        room_events.insert_backwards_pagination(
+            &token,
+            vec![event("$b"), event("$a")],
+            Some("prev2".to_owned()),
+        );
+        assert_eq!(room_events.pagination_token(), PaginationToken::Gap("prev2".to_owned()));
+
+        let json = room_events.to_json(1).unwrap();
+        let restored = RoomEvents::from_json(&json).unwrap();
+        assert_eq!(event_ids(&restored), ["$c"]);
+        assert_eq!(restored.pagination_token(), PaginationToken::Gap("prev".to_owned()));
+    }
+
+    #[test]
+    fn test_outdated_pagination_is_ignored() {
+        let mut room_events = RoomEvents::default();
+        room_events.push_sync_timeline(vec![event("$b")], false, Some("prev".to_owned()));
+
+        let token = room_events.pagination_token();
+        let insertion = room_events.insert_backwards_pagination(&token, vec![event("$a")], None);
+        assert_eq!(insertion.num_events, 1);
+        assert_eq!(room_events.pagination_token(), PaginationToken::ReachedStart);
+
+        // The same gap can't be filled twice.
+        let insertion = room_events.insert_backwards_pagination(&token, vec![event("$z")], None);
+        assert_eq!(insertion.num_events, 0);
+        assert_eq!(event_ids(&room_events), ["$a", "$b"]);
+    }
+}
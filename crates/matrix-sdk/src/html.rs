@@ -0,0 +1,470 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conversions between the formats of the body of a message: HTML, Markdown
+//! and plain text.
+//!
+//! The HTML of received messages must be sanitized before it is rendered,
+//! with [`sanitize_html`], which is the sanitizer of Ruma also used by the
+//! other parts of the SDK.
+
+use std::fmt::Write;
+
+pub use ruma::html::{sanitize_html, HtmlSanitizerMode, RemoveReplyFallback};
+
+/// The elements that are removed with their content, because their content
+/// is not meant to be displayed as text.
+const CONTENT_DROPPED_ELEMENTS: &[&str] = &[
+    "head", "iframe", "noscript", "object", "script", "select", "style", "template", "textarea",
+    "title",
+];
+
+/// The elements that are separated from their siblings by a blank line when
+/// converted to plain text.
+const PARAGRAPH_ELEMENTS: &[&str] =
+    &["blockquote", "details", "h1", "h2", "h3", "h4", "h5", "h6", "ol", "p", "pre", "table", "ul"];
+
+/// The elements that are separated from their siblings by a line break when
+/// converted to plain text.
+const LINE_ELEMENTS: &[&str] = &["caption", "div", "hr", "li", "summary", "tr"];
+
+/// Convert the given Markdown to sanitized HTML.
+///
+/// Like [`RoomMessageEventContent::text_markdown()`], returns `None` if the
+/// Markdown doesn't contain any formatting, in which case the plain text
+/// can be sent without an HTML body.
+///
+/// The HTML is sanitized with [`HtmlSanitizerMode::Compat`].
+///
+/// [`RoomMessageEventContent::text_markdown()`]: ruma::events::room::message::RoomMessageEventContent::text_markdown
+#[cfg(feature = "markdown")]
+pub fn markdown_to_html(markdown: &str) -> Option<String> {
+    let formatted = ruma::events::room::message::FormattedBody::markdown(markdown)?;
+    Some(sanitize_html(&formatted.body, HtmlSanitizerMode::Compat, RemoveReplyFallback::No))
+}
+
+/// Convert the given plain text to HTML.
+///
+/// The special characters are escaped and the line breaks are replaced by
+/// `<br>` elements.
+pub fn plain_text_to_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\n' => html.push_str("<br>"),
+            c => push_escaped(&mut html, c),
+        }
+    }
+
+    html
+}
+
+/// Convert the given HTML to plain text, e.g. for the plain text fallback of
+/// a message or for a notification.
+///
+/// The block elements are separated by line breaks, the items of lists are
+/// prefixed with a bullet or their number, and the rich reply fallback is
+/// removed.
+pub fn html_to_plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    // The next number of each ordered list, and `None` for unordered lists.
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut pre_depth = 0usize;
+    let mut tokens = Tokenizer::new(html);
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(content) => {
+                // Ignore the line breaks between the tags, they are not
+                // rendered outside of `pre` elements.
+                if pre_depth == 0 && content.contains('\n') && content.trim().is_empty() {
+                    continue;
+                }
+                text.push_str(&unescape(content));
+            }
+            Token::StartTag { name, attributes, self_closing } => {
+                let name = name.as_str();
+
+                if CONTENT_DROPPED_ELEMENTS.contains(&name) || name == "mx-reply" {
+                    if !self_closing {
+                        tokens.skip_element(name);
+                    }
+                    continue;
+                }
+
+                if PARAGRAPH_ELEMENTS.contains(&name) {
+                    end_line(&mut text, 2);
+                } else if LINE_ELEMENTS.contains(&name) {
+                    end_line(&mut text, 1);
+                }
+
+                match name {
+                    "br" => text.push('\n'),
+                    "pre" => pre_depth += 1,
+                    "ul" => lists.push(None),
+                    "ol" => {
+                        let start = attributes
+                            .iter()
+                            .find(|(attribute, _)| attribute == "start")
+                            .and_then(|(_, value)| value.trim().parse().ok());
+                        lists.push(Some(start.unwrap_or(1)));
+                    }
+                    "li" => match lists.last_mut() {
+                        Some(Some(number)) => {
+                            let _ = write!(text, "{number}. ");
+                            *number += 1;
+                        }
+                        _ => text.push_str("- "),
+                    },
+                    _ => {}
+                }
+            }
+            Token::EndTag { name } => {
+                let name = name.as_str();
+
+                match name {
+                    "pre" => pre_depth = pre_depth.saturating_sub(1),
+                    "ul" | "ol" => {
+                        lists.pop();
+                    }
+                    _ => {}
+                }
+
+                if PARAGRAPH_ELEMENTS.contains(&name) {
+                    end_line(&mut text, 2);
+                } else if LINE_ELEMENTS.contains(&name) {
+                    end_line(&mut text, 1);
+                }
+            }
+        }
+    }
+
+    text.trim_end_matches('\n').to_owned()
+}
+
+/// Make sure that the given text ends with at least the given number of line
+/// breaks, unless it is empty.
+fn end_line(text: &mut String, line_breaks: usize) {
+    if text.is_empty() {
+        return;
+    }
+
+    let current = text.len() - text.trim_end_matches('\n').len();
+    for _ in current..line_breaks {
+        text.push('\n');
+    }
+}
+
+fn push_escaped(html: &mut String, c: char) {
+    match c {
+        '&' => html.push_str("&amp;"),
+        '<' => html.push_str("&lt;"),
+        '>' => html.push_str("&gt;"),
+        '"' => html.push_str("&quot;"),
+        '\'' => html.push_str("&#39;"),
+        c => html.push(c),
+    }
+}
+
+/// Escape the given value to use it in a quoted attribute.
+pub(crate) fn escape_attribute(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        push_escaped(&mut escaped, c);
+    }
+    escaped
+}
+
+/// Replace the character references in the given HTML text by the
+/// characters they represent.
+///
+/// Only numeric references and the most common named references are
+/// supported, the others are kept as-is.
+pub(crate) fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let reference = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((character_reference(&rest[1..=end])?, end + 2)));
+
+        match reference {
+            Some((c, len)) => {
+                unescaped.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Get the character represented by the given reference, without its `&`
+/// and `;`.
+fn character_reference(reference: &str) -> Option<char> {
+    let code = match reference {
+        "amp" => return Some('&'),
+        "lt" => return Some('<'),
+        "gt" => return Some('>'),
+        "quot" => return Some('"'),
+        "apos" => return Some('\''),
+        "nbsp" => return Some('\u{a0}'),
+        _ => reference.strip_prefix('#')?,
+    };
+
+    let code = match code.strip_prefix(['x', 'X']) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => code.parse().ok()?,
+    };
+
+    char::from_u32(code).filter(|&c| c != '\0')
+}
+
+/// A token of HTML.
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    /// Text, which can contain character references.
+    Text(&'a str),
+    /// The start of an element.
+    StartTag {
+        /// The lowercase name of the element.
+        name: String,
+        /// The lowercase names and the unescaped values of the attributes.
+        attributes: Vec<(String, String)>,
+        /// Whether the tag ends with `/>`.
+        self_closing: bool,
+    },
+    /// The end of an element.
+    EndTag {
+        /// The lowercase name of the element.
+        name: String,
+    },
+}
+
+/// A lenient HTML tokenizer, used to convert HTML to plain text.
+///
+/// Comments, doctypes and processing instructions are skipped, and a tag
+/// that is not terminated is dropped with the rest of the HTML, like
+/// browsers do.
+struct Tokenizer<'a> {
+    html: &'a str,
+    pos: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(html: &'a str) -> Self {
+        Self { html, pos: 0 }
+    }
+
+    /// Skip the content of the element with the given name, up to and
+    /// including its end tag.
+    fn skip_element(&mut self, name: &str) {
+        let rest = self.html[self.pos..].to_ascii_lowercase();
+        let end = rest
+            .find(&format!("</{name}"))
+            .and_then(|start| rest[start..].find('>').map(|end| start + end + 1));
+        self.pos = end.map_or(self.html.len(), |end| self.pos + end);
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let rest = &self.html[self.pos..];
+            if rest.is_empty() {
+                return None;
+            }
+
+            if !rest.starts_with('<') {
+                let len = rest.find('<').unwrap_or(rest.len());
+                self.pos += len;
+                return Some(Token::Text(&rest[..len]));
+            }
+
+            if let Some(comment) = rest.strip_prefix("<!--") {
+                self.pos += "<!--".len() + comment.find("-->").map_or(comment.len(), |end| end + 3);
+                continue;
+            }
+
+            let bytes = rest.as_bytes();
+            match bytes.get(1).copied() {
+                Some(b'!' | b'?') => {
+                    self.pos += rest.find('>').map_or(rest.len(), |end| end + 1);
+                }
+                Some(b'/') if bytes.get(2).is_some_and(u8::is_ascii_alphabetic) => {
+                    let Some(end) = rest.find('>') else {
+                        self.pos = self.html.len();
+                        return None;
+                    };
+                    self.pos += end + 1;
+
+                    let name = rest[2..end]
+                        .split(|c: char| c.is_ascii_whitespace() || c == '/')
+                        .next()
+                        .unwrap_or_default();
+                    return Some(Token::EndTag { name: name.to_ascii_lowercase() });
+                }
+                Some(b) if b.is_ascii_alphabetic() => {
+                    let Some((token, len)) = parse_start_tag(rest) else {
+                        self.pos = self.html.len();
+                        return None;
+                    };
+                    self.pos += len;
+                    return Some(token);
+                }
+                _ => {
+                    // This is not a tag, so this is text.
+                    self.pos += 1;
+                    return Some(Token::Text(&rest[..1]));
+                }
+            }
+        }
+    }
+}
+
+/// Parse the start tag at the beginning of the given HTML.
+///
+/// Returns the token and the length of the tag, or `None` if the tag is not
+/// terminated.
+fn parse_start_tag(html: &str) -> Option<(Token<'_>, usize)> {
+    let bytes = html.as_bytes();
+    let is_name_end = |b: u8| b.is_ascii_whitespace() || b == b'/' || b == b'>';
+
+    let mut pos = 1;
+    while pos < bytes.len() && !is_name_end(bytes[pos]) {
+        pos += 1;
+    }
+    let name = html[1..pos].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+
+    loop {
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'/') {
+            self_closing = bytes[pos] == b'/';
+            pos += 1;
+        }
+
+        if *bytes.get(pos)? == b'>' {
+            return Some((Token::StartTag { name, attributes, self_closing }, pos + 1));
+        }
+        self_closing = false;
+
+        let name_start = pos;
+        while pos < bytes.len() && !is_name_end(bytes[pos]) && bytes[pos] != b'=' {
+            pos += 1;
+        }
+        if pos == name_start {
+            // This is a `=` without an attribute name.
+            pos += 1;
+            continue;
+        }
+        let attribute = html[name_start..pos].to_ascii_lowercase();
+
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        let mut value = String::new();
+        if bytes.get(pos) == Some(&b'=') {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+
+            let quote = *bytes.get(pos)?;
+            if quote == b'"' || quote == b'\'' {
+                let len = html[pos + 1..].find(char::from(quote))?;
+                value = unescape(&html[pos + 1..pos + 1 + len]);
+                pos += len + 2;
+            } else {
+                let value_start = pos;
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                    pos += 1;
+                }
+                value = unescape(&html[value_start..pos]);
+            }
+        }
+
+        attributes.push((attribute, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        html_to_plain_text, plain_text_to_html, sanitize_html, unescape, HtmlSanitizerMode,
+        RemoveReplyFallback,
+    };
+
+    #[test]
+    fn test_sanitize_html() {
+        let html = "<mx-reply><blockquote>In reply to</blockquote></mx-reply>\
+                    <p onclick=\"alert(1)\">Answer</p>";
+        assert_eq!(
+            sanitize_html(html, HtmlSanitizerMode::Compat, RemoveReplyFallback::Yes),
+            "<p>Answer</p>"
+        );
+    }
+
+    #[test]
+    fn test_html_to_plain_text() {
+        let html = "<mx-reply><blockquote>Quote</blockquote></mx-reply>\
+                    <h1>Title</h1>\n<p>Some <b>bold</b> text&nbsp;&amp; a<br>line break</p>\n\
+                    <ol start=\"3\">\n<li>three</li>\n<li>four</li>\n</ol>\n\
+                    <ul><li>item</li></ul><pre>a\n  b</pre>";
+        assert_eq!(
+            html_to_plain_text(html),
+            "Title\n\nSome bold text\u{a0}& a\nline break\n\n3. three\n4. four\n\n- item\n\na\n  b"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_to_html() {
+        assert_eq!(
+            plain_text_to_html("<b>\"Tom\" & 'Jerry'</b>\nnext"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;<br>next"
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("&lt;&#65;&#x42;&gt; &unknown; &amp;amp; &"), "<AB> &unknown; &amp; &");
+    }
+
+    #[cfg(feature = "markdown")]
+    #[test]
+    fn test_markdown_to_html() {
+        use super::markdown_to_html;
+
+        assert_eq!(markdown_to_html("plain text"), None);
+
+        let html = markdown_to_html("**bold** <script>alert(1)</script>").unwrap();
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(!html.contains("script"));
+    }
+}
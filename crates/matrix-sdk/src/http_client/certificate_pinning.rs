@@ -0,0 +1,234 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pinning of the certificates of the servers, by the hash of their public key.
+
+use std::{sync::Arc, time::SystemTime};
+
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, CertificateError, ClientConfig, Error, OwnedTrustAnchor, RootCertStore,
+    ServerName,
+};
+use sha2::{Digest, Sha256};
+use x509_cert::der::{Decode, Encode};
+
+/// A certificate chain that was rejected because it doesn't contain any of the
+/// pinned public keys.
+#[derive(Clone, Debug)]
+pub struct CertificatePinFailure {
+    /// The name of the server that presented the certificate chain.
+    pub server_name: String,
+    /// The SHA-256 hashes of the DER-encoded `SubjectPublicKeyInfo` of the
+    /// certificates of the chain, starting with the one of the server.
+    pub hashes: Vec<[u8; 32]>,
+}
+
+/// A function called when a certificate chain is rejected because of the
+/// pinning.
+pub(crate) type CertificatePinFailureHandler = Arc<dyn Fn(CertificatePinFailure) + Send + Sync>;
+
+/// Build a TLS configuration which only accepts the certificate chains
+/// containing one of the pinned public keys.
+///
+/// * `pins` - The SHA-256 hashes of the DER-encoded `SubjectPublicKeyInfo` of
+///   the accepted certificates.
+///
+/// * `additional_root_certificates` - DER-encoded certificates to trust, in
+///   addition to the default ones.
+///
+/// * `verify_chain` - Whether the certificate chain must also be valid.
+///
+/// * `failure_handler` - The function to call when a certificate chain is
+///   rejected because none of its public keys are pinned.
+pub(super) fn tls_config(
+    pins: Vec<[u8; 32]>,
+    additional_root_certificates: &[Vec<u8>],
+    verify_chain: bool,
+    failure_handler: Option<CertificatePinFailureHandler>,
+) -> ClientConfig {
+    let chain_verifier = verify_chain.then(|| {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        roots.add_parsable_certificates(additional_root_certificates);
+
+        WebPkiVerifier::new(roots, None)
+    });
+
+    ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinningVerifier {
+            pins,
+            chain_verifier,
+            failure_handler,
+        }))
+        .with_no_client_auth()
+}
+
+struct PinningVerifier {
+    pins: Vec<[u8; 32]>,
+    chain_verifier: Option<WebPkiVerifier>,
+    failure_handler: Option<CertificatePinFailureHandler>,
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, Error> {
+        if let Some(chain_verifier) = &self.chain_verifier {
+            chain_verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        let hashes: Vec<_> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|certificate| spki_sha256(&certificate.0))
+            .collect();
+
+        if hashes.iter().any(|hash| self.pins.contains(hash)) {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        if let Some(failure_handler) = &self.failure_handler {
+            let server_name = match server_name {
+                ServerName::DnsName(name) => name.as_ref().to_owned(),
+                ServerName::IpAddress(address) => address.to_string(),
+                _ => String::new(),
+            };
+            failure_handler(CertificatePinFailure { server_name, hashes });
+        }
+
+        Err(Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure))
+    }
+}
+
+/// Compute the SHA-256 hash of the `SubjectPublicKeyInfo` of a DER-encoded
+/// certificate.
+fn spki_sha256(certificate: &[u8]) -> Option<[u8; 32]> {
+    let certificate = x509_cert::Certificate::from_der(certificate).ok()?;
+    let spki = certificate.tbs_certificate.subject_public_key_info.to_der().ok()?;
+    Some(Sha256::digest(spki).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    };
+
+    use ruma::serde::Base64;
+    use rustls::{client::ServerCertVerifier, Certificate, ServerName};
+
+    use super::{spki_sha256, CertificatePinFailure, PinningVerifier};
+
+    /// A self-signed CA certificate.
+    const CA_CERTIFICATE: &str = "MIIBezCCASGgAwIBAgIUFuFXQbhcd3FCvEGtt8KPa6K2XSwwCgYIKoZIzj0EAwIwEjEQMA4GA1UEAwwHVGVzdCBDQTAgFw0yNjEwMTYxOTQyMzZaGA8yMTI2MDkyMjE5NDIzNlowEjEQMA4GA1UEAwwHVGVzdCBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABF9dOxjXYOs6UEoGS7qYY7vZ+PPCoLWzx3w7dYhRBzpJh2xLFnFKqN7OqfMf7QOzVG8BMm42JSyn2LgXwUZFrzejUzBRMB0GA1UdDgQWBBSM7ksTTn6v4TtQGgHP6Bbp+F2VmTAfBgNVHSMEGDAWgBSM7ksTTn6v4TtQGgHP6Bbp+F2VmTAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIQCh1Nf46H465dIHvlX+fq1NEdPxWeMNyqzXfWjNbobD0gIgUxym5Uroej5j92p1qqKncduHFjf814aOEYxjtV+/6rE=";
+    /// The SHA-256 hash of the `SubjectPublicKeyInfo` of the CA certificate.
+    const CA_PIN: &str = "krxnZCTYjxvza1Pj8OYNB6OhAc3xe2qra0uewABj5Vo=";
+
+    /// A certificate for `matrix.localhost`, signed by the CA.
+    const SERVER_CERTIFICATE: &str = "MIIBczCCARmgAwIBAgIUPejtyE6hbm/LE+OA/R+xyXSB15YwCgYIKoZIzj0EAwIwEjEQMA4GA1UEAwwHVGVzdCBDQTAgFw0yNjEwMTYxOTQyMzZaGA8yMTI2MDkyMjE5NDIzNlowGzEZMBcGA1UEAwwQbWF0cml4LmxvY2FsaG9zdDBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABFdiffMpLgZGeKCA2M+IMXYD6h2FwIZ+HK0EiPzWz3FtfPbPjdfhfrOHt1/XM7VhP9gnN6Y9r95xlfqRWrT6CH6jQjBAMB0GA1UdDgQWBBQ+jj3RJlpN/wjabMpnf06MAxWCdTAfBgNVHSMEGDAWgBSM7ksTTn6v4TtQGgHP6Bbp+F2VmTAKBggqhkjOPQQDAgNIADBFAiEA/Zev1G6EX9+vaFSKBW6lY8PAiDMxpNQgJLt4eRhrQxsCIDMY8E9DCzEGliwKTnh9e0ArhOmnJe68X/yDiRFXiW/7";
+    /// The SHA-256 hash of the `SubjectPublicKeyInfo` of the server certificate.
+    const SERVER_PIN: &str = "wM9S6/Jhj/zGKA+yhQAhEYCIeOcGOIfjZowMhlMvlBk=";
+
+    /// A pin that doesn't match any of the certificates.
+    const OTHER_PIN: [u8; 32] = [0; 32];
+
+    fn decode(base64: &str) -> Vec<u8> {
+        let base64: Base64 = Base64::parse(base64).unwrap();
+        base64.into_inner()
+    }
+
+    fn pin(base64: &str) -> [u8; 32] {
+        decode(base64).try_into().unwrap()
+    }
+
+    /// Verify the chain made of the server certificate and the CA certificate,
+    /// without checking its validity.
+    fn verify(
+        pins: Vec<[u8; 32]>,
+        failures: Option<Arc<Mutex<Vec<CertificatePinFailure>>>>,
+    ) -> Result<(), rustls::Error> {
+        let verifier = PinningVerifier {
+            pins,
+            chain_verifier: None,
+            failure_handler: failures.map(|failures| {
+                Arc::new(move |failure: CertificatePinFailure| {
+                    failures.lock().unwrap().push(failure)
+                }) as _
+            }),
+        };
+
+        verifier
+            .verify_server_cert(
+                &Certificate(decode(SERVER_CERTIFICATE)),
+                &[Certificate(decode(CA_CERTIFICATE))],
+                &ServerName::try_from("matrix.localhost").unwrap(),
+                &mut std::iter::empty(),
+                &[],
+                SystemTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    #[test]
+    fn test_spki_sha256() {
+        assert_eq!(spki_sha256(&decode(SERVER_CERTIFICATE)), Some(pin(SERVER_PIN)));
+        assert_eq!(spki_sha256(&decode(CA_CERTIFICATE)), Some(pin(CA_PIN)));
+        assert_eq!(spki_sha256(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn test_matching_pin_is_accepted() {
+        verify(vec![pin(SERVER_PIN)], None).unwrap();
+        // The pin of an intermediate certificate of the chain is accepted too.
+        verify(vec![pin(CA_PIN)], None).unwrap();
+    }
+
+    #[test]
+    fn test_backup_pin_is_accepted() {
+        verify(vec![OTHER_PIN, pin(SERVER_PIN)], None).unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_pin_is_rejected() {
+        let failures = Arc::new(Mutex::new(Vec::new()));
+
+        verify(vec![OTHER_PIN], Some(failures.clone())).unwrap_err();
+
+        let failures = failures.lock().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].server_name, "matrix.localhost");
+        assert_eq!(failures[0].hashes, [pin(SERVER_PIN), pin(CA_PIN)]);
+    }
+}
@@ -14,6 +14,8 @@
 
 use std::{
     any::type_name,
+    collections::HashMap,
+    error::Error as StdError,
     fmt::Debug,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -22,25 +24,37 @@ use std::{
     time::Duration,
 };
 
+use async_trait::async_trait;
 use bytes::{Bytes, BytesMut};
 use bytesize::ByteSize;
 use eyeball::SharedObservable;
 use http::Method;
+use matrix_sdk_common::{time::SharedClock, AsyncTraitDeps};
 use ruma::api::{
     error::{FromHttpResponseError, IntoHttpError},
     AuthScheme, MatrixVersion, OutgoingRequest, SendAccessToken,
 };
+use tokio::sync::{broadcast, OwnedSemaphorePermit, Semaphore};
 use tracing::{debug, field::debug, instrument, trace};
 
-use crate::{config::RequestConfig, error::HttpError};
+use crate::{
+    config::{RequestCategory, RequestConfig},
+    error::HttpError,
+};
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+mod certificate_pinning;
 #[cfg(not(target_arch = "wasm32"))]
 mod native;
 #[cfg(target_arch = "wasm32")]
 mod wasm;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+pub use certificate_pinning::CertificatePinFailure;
+#[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+pub(crate) use certificate_pinning::CertificatePinFailureHandler;
 #[cfg(not(target_arch = "wasm32"))]
-pub(crate) use native::HttpSettings;
+pub(crate) use native::{BodyStream, HttpSettings, ReaderStream};
 
 pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -48,12 +62,45 @@ pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 pub(crate) struct HttpClient {
     pub(crate) inner: reqwest::Client,
     pub(crate) request_config: RequestConfig,
+    /// The transport to send the requests with instead of `inner`, if any.
+    pub(crate) transport: Option<Arc<dyn HttpTransport>>,
     next_request_id: Arc<AtomicU64>,
+    /// Sender of the notifications about the rate-limited requests.
+    pub(crate) rate_limits: broadcast::Sender<RateLimited>,
+    /// The limits on the requests, shared between all the clones.
+    limits: Arc<RequestLimits>,
+    /// Whether the requests modifying the state of the homeserver are
+    /// blocked.
+    pub(crate) read_only: bool,
+    /// The clock used for the delays between the retries of a request.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    clock: SharedClock,
+    /// The identity asserted by an application service in every request.
+    #[cfg(feature = "appservice")]
+    pub(crate) asserted_identity: Option<AssertedIdentity>,
 }
 
 impl HttpClient {
-    pub(crate) fn new(inner: reqwest::Client, request_config: RequestConfig) -> Self {
-        HttpClient { inner, request_config, next_request_id: AtomicU64::new(0).into() }
+    pub(crate) fn new(
+        inner: reqwest::Client,
+        transport: Option<Arc<dyn HttpTransport>>,
+        request_config: RequestConfig,
+        limits: RequestLimits,
+        read_only: bool,
+        clock: SharedClock,
+    ) -> Self {
+        HttpClient {
+            inner,
+            transport,
+            request_config,
+            next_request_id: AtomicU64::new(0).into(),
+            rate_limits: broadcast::channel(16).0,
+            limits: limits.into(),
+            read_only,
+            clock,
+            #[cfg(feature = "appservice")]
+            asserted_identity: None,
+        }
     }
 
     fn get_request_id(&self) -> String {
@@ -92,6 +139,21 @@ impl HttpClient {
         Ok(request)
     }
 
+    /// Make sure that the given request can be sent, if the client is in
+    /// read-only mode.
+    fn check_read_only(&self, request: &http::Request<Bytes>) -> Result<(), HttpError> {
+        if self.read_only && !is_allowed_in_read_only_mode(request.method(), request.uri().path()) {
+            debug!(
+                method = %request.method(),
+                path = request.uri().path(),
+                "Blocking request in read-only mode"
+            );
+            return Err(HttpError::ReadOnly);
+        }
+
+        Ok(())
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[instrument(
         skip(self, request, config, homeserver, access_token, send_progress),
@@ -120,11 +182,15 @@ impl HttpClient {
         R: OutgoingRequest + Debug,
         HttpError: From<FromHttpResponseError<R::EndpointError>>,
     {
-        let config = match config {
+        let mut config = match config {
             Some(config) => config,
             None => self.request_config,
         };
 
+        if let Some(timeout) = self.limits.endpoint_timeouts.get(type_name::<R>()) {
+            config.timeout = *timeout;
+        }
+
         // Keep some local variables in a separate scope so the compiler doesn't include
         // them in the future type. https://github.com/rust-lang/rust/issues/57478
         let request = {
@@ -140,8 +206,15 @@ impl HttpClient {
                 return Err(HttpError::NotClientRequest);
             }
 
-            let request =
+            #[allow(unused_mut)]
+            let mut request =
                 self.serialize_request(request, config, homeserver, access_token, server_versions)?;
+            self.check_read_only(&request)?;
+
+            #[cfg(feature = "appservice")]
+            if let Some(asserted_identity) = &self.asserted_identity {
+                asserted_identity.add_to_request(&mut request);
+            }
 
             let method = request.method();
 
@@ -190,6 +263,181 @@ impl HttpClient {
     }
 }
 
+/// Whether a request with the given method and path can be sent by a client
+/// in read-only mode.
+///
+/// Only the requests that don't modify the state of the homeserver are allowed,
+/// except the ones that are necessary to log in, to sync and to decrypt the
+/// received events.
+fn is_allowed_in_read_only_mode(method: &Method, path: &str) -> bool {
+    if [Method::GET, Method::HEAD, Method::OPTIONS].contains(method) {
+        return true;
+    }
+
+    if *method != Method::POST {
+        return false;
+    }
+
+    // Remove the `/_matrix/client/{version}/` prefix.
+    let Some((_, endpoint)) =
+        path.strip_prefix("/_matrix/client/").and_then(|path| path.split_once('/'))
+    else {
+        return false;
+    };
+
+    match endpoint {
+        // Session management.
+        "login" | "logout" | "refresh" => true,
+        // Requests that only read data, despite using POST.
+        "search" | "publicRooms" | "user_directory/search" | "keys/query" => true,
+        // Sliding sync.
+        "org.matrix.msc3575/sync" => true,
+        // The keys of the device must be uploaded to receive room keys.
+        "keys/upload" => true,
+        // The filter used by sync.
+        _ => endpoint.starts_with("user/") && endpoint.ends_with("/filter"),
+    }
+}
+
+/// The user, and optionally the device, that an application service acts as.
+///
+/// They are added to the query string of the requests, as defined in the
+/// specification for the user and in [MSC3202] for the device.
+///
+/// [MSC3202]: https://github.com/matrix-org/matrix-spec-proposals/pull/3202
+#[cfg(feature = "appservice")]
+#[derive(Clone, Debug)]
+pub(crate) struct AssertedIdentity {
+    pub(crate) user_id: ruma::OwnedUserId,
+    pub(crate) device_id: Option<ruma::OwnedDeviceId>,
+}
+
+#[cfg(feature = "appservice")]
+impl AssertedIdentity {
+    fn add_to_request(&self, request: &mut http::Request<Bytes>) {
+        let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+        serializer.append_pair("user_id", self.user_id.as_str());
+        if let Some(device_id) = &self.device_id {
+            serializer.append_pair("org.matrix.msc3202.device_id", device_id.as_str());
+        }
+        let identity_query = serializer.finish();
+
+        let uri = request.uri();
+        let path_and_query = match uri.query() {
+            Some(query) => format!("{}?{query}&{identity_query}", uri.path()),
+            None => format!("{}?{identity_query}", uri.path()),
+        };
+
+        let mut uri_parts = uri.clone().into_parts();
+        uri_parts.path_and_query =
+            Some(path_and_query.try_into().expect("path and query are still valid"));
+        *request.uri_mut() = http::Uri::from_parts(uri_parts).expect("URI is still valid");
+    }
+}
+
+/// Limits on the number of requests in flight and on the time they can take.
+#[derive(Debug, Default)]
+pub(crate) struct RequestLimits {
+    /// Limits the number of requests in flight, whatever their category.
+    all: Option<Arc<Semaphore>>,
+    /// Limits the number of requests in flight, by category.
+    categories: HashMap<RequestCategory, Arc<Semaphore>>,
+    /// Timeouts replacing the one of the [`RequestConfig`], by type name of
+    /// the request.
+    endpoint_timeouts: HashMap<&'static str, Duration>,
+}
+
+impl RequestLimits {
+    pub(crate) fn new(
+        max_concurrent_requests: Option<usize>,
+        max_concurrent_requests_per_category: &HashMap<RequestCategory, usize>,
+        endpoint_timeouts: HashMap<&'static str, Duration>,
+    ) -> Self {
+        Self {
+            all: max_concurrent_requests.map(|max| Arc::new(Semaphore::new(max))),
+            categories: max_concurrent_requests_per_category
+                .iter()
+                .map(|(category, max)| (*category, Arc::new(Semaphore::new(*max))))
+                .collect(),
+            endpoint_timeouts,
+        }
+    }
+
+    /// Wait until a request of the given category can be sent.
+    ///
+    /// The request must be sent, and its response received, while the returned
+    /// permits are held.
+    async fn acquire(
+        &self,
+        category: RequestCategory,
+    ) -> (Option<OwnedSemaphorePermit>, Option<OwnedSemaphorePermit>) {
+        // Wait for the category first, so a request doesn't hold a slot of the
+        // global limit while it waits behind requests of the same category.
+        let category_permit = match self.categories.get(&category) {
+            Some(semaphore) => Some(
+                semaphore.clone().acquire_owned().await.expect("the semaphore is never closed"),
+            ),
+            None => None,
+        };
+        let permit = match &self.all {
+            Some(semaphore) => Some(
+                semaphore.clone().acquire_owned().await.expect("the semaphore is never closed"),
+            ),
+            None => None,
+        };
+
+        (category_permit, permit)
+    }
+}
+
+/// The error returned by an [`HttpTransport`].
+pub type HttpTransportError = Box<dyn StdError + Send + Sync>;
+
+/// The HTTP stack used to send the requests of a [`Client`](crate::Client),
+/// instead of the built-in `reqwest` client.
+///
+/// The transport only has to send a request and return the response, whatever
+/// its status code. The SDK still takes care of the authentication, the
+/// serialization of the requests and responses and the retries.
+///
+/// It can be set with
+/// [`ClientBuilder::http_transport()`](crate::ClientBuilder::http_transport).
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait HttpTransport: AsyncTraitDeps {
+    /// Send a request and receive its response.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request to send, with its body already serialized.
+    ///
+    /// * `timeout` - How long to wait for the response before giving up.
+    ///
+    /// * `send_progress` - The progress of the upload of the body of the
+    ///   request, the transport can update it if it supports it.
+    async fn send(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<http::Response<Bytes>, HttpTransportError>;
+}
+
+/// A request that was rate-limited by the homeserver.
+#[derive(Clone, Debug)]
+pub struct RateLimited {
+    /// The method of the request.
+    pub method: Method,
+    /// The path of the request.
+    pub path: String,
+    /// How long the homeserver asked to wait before retrying the request, if
+    /// it did.
+    pub retry_after: Option<Duration>,
+    /// Whether the request will be retried, which depends on the
+    /// [`RequestConfig`] used to send it.
+    pub will_retry: bool,
+}
+
 /// Progress of sending or receiving a payload.
 #[derive(Clone, Copy, Debug, Default)]
 pub struct TransmissionProgress {
@@ -199,10 +447,16 @@ pub struct TransmissionProgress {
     pub total: usize,
 }
 
+/// Convert a `reqwest` response to an `http` one.
+///
+/// If `receive_progress` is set, the body is read chunk by chunk and the
+/// progress is updated after each chunk.
 async fn response_to_http_response(
     mut response: reqwest::Response,
+    receive_progress: Option<&SharedObservable<TransmissionProgress>>,
 ) -> Result<http::Response<Bytes>, reqwest::Error> {
     let status = response.status();
+    let content_length = response.content_length();
 
     let mut http_builder = http::Response::builder().status(status);
     let headers = http_builder.headers_mut().expect("Can't get the response builder headers");
@@ -213,7 +467,23 @@ async fn response_to_http_response(
         }
     }
 
-    let body = response.bytes().await?;
+    let body = match receive_progress {
+        Some(receive_progress) => {
+            if let Some(content_length) = content_length {
+                let content_length = content_length.try_into().unwrap_or(usize::MAX);
+                receive_progress.update(|p| p.total += content_length);
+            }
+
+            let mut body = BytesMut::new();
+            while let Some(chunk) = response.chunk().await? {
+                receive_progress.update(|p| p.current += chunk.len());
+                body.extend_from_slice(&chunk);
+            }
+
+            body.freeze()
+        }
+        None => response.bytes().await?,
+    };
 
     Ok(http_builder.body(body).expect("Can't construct a response using the given body"))
 }
@@ -232,13 +502,61 @@ impl tower::Service<http::Request<Bytes>> for HttpClient {
     }
 
     fn call(&mut self, req: http::Request<Bytes>) -> Self::Future {
-        let inner = self.inner.clone();
+        let client = self.clone();
 
         let fut = async move {
-            native::send_request(&inner, &req, DEFAULT_REQUEST_TIMEOUT, Default::default())
+            client
+                .send_http_request(&req, DEFAULT_REQUEST_TIMEOUT, Default::default())
                 .await
                 .map_err(Into::into)
         };
         Box::pin(fut)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use futures_util::FutureExt;
+
+    use super::RequestLimits;
+    use crate::config::RequestCategory;
+
+    #[test]
+    fn test_requests_beyond_the_limit_wait() {
+        let limits = RequestLimits::new(Some(2), &HashMap::new(), HashMap::new());
+
+        let first = limits.acquire(RequestCategory::Api).now_or_never().unwrap();
+        let second = limits.acquire(RequestCategory::Media).now_or_never().unwrap();
+
+        // The third request waits until one of the first ones is done.
+        let mut third = Box::pin(limits.acquire(RequestCategory::Api));
+        assert!((&mut third).now_or_never().is_none());
+
+        drop(first);
+        assert!(third.now_or_never().is_some());
+        drop(second);
+    }
+
+    #[test]
+    fn test_requests_beyond_the_limit_of_their_category_wait() {
+        let limits = RequestLimits::new(
+            Some(2),
+            &HashMap::from([(RequestCategory::Media, 1)]),
+            HashMap::new(),
+        );
+
+        let media = limits.acquire(RequestCategory::Media).now_or_never().unwrap();
+
+        // Another media request waits, but doesn't take a slot of the global
+        // limit while doing so.
+        let mut other_media = Box::pin(limits.acquire(RequestCategory::Media));
+        assert!((&mut other_media).now_or_never().is_none());
+        let api = limits.acquire(RequestCategory::Api).now_or_never().unwrap();
+
+        drop(media);
+        assert!(other_media.now_or_never().is_some());
+        drop(api);
+    }
+}
@@ -13,26 +13,54 @@
 // limitations under the License.
 
 use std::{
-    fmt::Debug,
-    mem,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug},
+    hash::{BuildHasher, Hasher},
+    io, mem,
+    pin::Pin,
     sync::atomic::{AtomicU64, Ordering},
+    task::{ready, Context, Poll},
     time::Duration,
 };
 
-use backoff::{future::retry, Error as RetryError, ExponentialBackoff};
-use bytes::Bytes;
+use backoff::{
+    exponential::ExponentialBackoff,
+    future::{Retry, Sleeper},
+    Backoff, Error as RetryError,
+};
+use bytes::{Bytes, BytesMut};
 use bytesize::ByteSize;
 use eyeball::SharedObservable;
-use http::header::CONTENT_LENGTH;
+use futures_core::Stream;
+use futures_util::{io::AsyncRead, stream, StreamExt, TryStreamExt};
+use http::{
+    header::{CONTENT_LENGTH, RETRY_AFTER},
+    HeaderMap, StatusCode,
+};
+use matrix_sdk_common::{
+    instant::Instant,
+    time::{Clock, SharedClock, SleepFuture},
+};
 use ruma::api::{
     client::error::{ErrorBody as ClientApiErrorBody, ErrorKind as ClientApiErrorKind},
     error::FromHttpResponseError,
-    IncomingResponse, OutgoingRequest,
+    IncomingResponse, MatrixVersion, OutgoingRequest,
+};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+use super::{
+    response_to_http_response, HttpClient, RateLimited, TransmissionProgress,
+    DEFAULT_REQUEST_TIMEOUT,
+};
+use crate::{
+    config::{RequestCategory, RequestConfig},
+    error::HttpError,
+    RumaApiError,
 };
-use tracing::{info, warn};
 
-use super::{response_to_http_response, HttpClient, TransmissionProgress, DEFAULT_REQUEST_TIMEOUT};
-use crate::{config::RequestConfig, error::HttpError, RumaApiError};
+/// A stream of the chunks of the body of a response.
+pub(crate) type BodyStream = Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>;
 
 impl HttpClient {
     pub(super) async fn send_request<R>(
@@ -45,9 +73,11 @@ impl HttpClient {
         R: OutgoingRequest + Debug,
         HttpError: From<FromHttpResponseError<R::EndpointError>>,
     {
-        let backoff =
-            ExponentialBackoff { max_elapsed_time: config.retry_timeout, ..Default::default() };
+        let backoff = self.retry_backoff(config.retry_timeout);
         let retry_count = AtomicU64::new(1);
+        let method = request.method().clone();
+        let path = request.uri().path().to_owned();
+        let category = RequestCategory::from_path(&path);
 
         let send_request = || {
             let send_progress = send_progress.clone();
@@ -58,43 +88,66 @@ impl HttpClient {
                     false
                 };
 
+                // Notify about the rate-limited request, and retry it after the requested delay
+                // unless the retry limit is reached.
+                let rate_limited = |err: HttpError, retry_after: Option<Duration>| {
+                    _ = self.rate_limits.send(RateLimited {
+                        method: method.clone(),
+                        path: path.clone(),
+                        retry_after,
+                        will_retry: !stop,
+                    });
+
+                    if stop {
+                        RetryError::Permanent(err)
+                    } else {
+                        RetryError::Transient { err, retry_after: retry_after.map(with_jitter) }
+                    }
+                };
+
                 // Turn errors into permanent errors when the retry limit is reached
-                let error_type = if stop {
-                    RetryError::Permanent
-                } else {
-                    |err: HttpError| {
-                        if let Some(api_error) = err.as_ruma_api_error() {
-                            let status_code = match api_error {
-                                RumaApiError::ClientApi(e) => match e.body {
-                                    ClientApiErrorBody::Standard {
-                                        kind: ClientApiErrorKind::LimitExceeded { retry_after_ms },
-                                        ..
-                                    } => {
-                                        return RetryError::Transient {
-                                            err,
-                                            retry_after: retry_after_ms,
-                                        };
-                                    }
-                                    _ => Some(e.status_code),
-                                },
-                                RumaApiError::Uiaa(_) => None,
-                                RumaApiError::Other(e) => Some(e.status_code),
-                            };
-
-                            if let Some(status_code) = status_code {
-                                if status_code.is_server_error() {
-                                    return RetryError::Transient { err, retry_after: None };
+                let error_type = |err: HttpError, retry_after_header: Option<Duration>| {
+                    if let Some(api_error) = err.as_ruma_api_error() {
+                        let status_code = match api_error {
+                            RumaApiError::ClientApi(e) => match e.body {
+                                ClientApiErrorBody::Standard {
+                                    kind: ClientApiErrorKind::LimitExceeded { retry_after_ms },
+                                    ..
+                                } => {
+                                    return rate_limited(
+                                        err,
+                                        retry_after_ms.or(retry_after_header),
+                                    );
                                 }
+                                _ => Some(e.status_code),
+                            },
+                            RumaApiError::Uiaa(_) => None,
+                            RumaApiError::Other(e) => Some(e.status_code),
+                        };
+
+                        if let Some(status_code) = status_code {
+                            // Some proxies rate-limit the requests without a Matrix error.
+                            if status_code == StatusCode::TOO_MANY_REQUESTS {
+                                return rate_limited(err, retry_after_header);
                             }
-                        }
 
-                        RetryError::Permanent(err)
+                            if status_code.is_server_error() && !stop {
+                                return RetryError::Transient { err, retry_after: None };
+                            }
+                        }
                     }
+
+                    RetryError::Permanent(err)
                 };
 
-                let response = send_request(&self.inner, &request, config.timeout, send_progress)
+                // The permits are only held during an attempt, so a request waiting to be
+                // retried doesn't block the other requests.
+                let permits = self.limits.acquire(category).await;
+                let response = self
+                    .send_http_request(&request, config.timeout, send_progress)
                     .await
-                    .map_err(error_type)?;
+                    .map_err(|err| error_type(err, None))?;
+                drop(permits);
 
                 let status_code = response.status();
                 let response_size = ByteSize(response.body().len().try_into().unwrap_or(u64::MAX));
@@ -115,22 +168,342 @@ impl HttpClient {
                     }
                 }
 
+                let retry_after_header = parse_retry_after(response.headers());
                 R::IncomingResponse::try_from_http_response(response)
-                    .map_err(|e| error_type(HttpError::from(e)))
+                    .map_err(|e| error_type(HttpError::from(e), retry_after_header))
             }
         };
 
-        retry::<_, HttpError, _, _, _>(backoff, send_request).await
+        let sleeper = RetryClock(self.clock.clone());
+        Retry::new(sleeper, backoff, |_: HttpError, _: Duration| {}, send_request).await
+    }
+
+    /// The exponential backoff between the retries of a request, measuring
+    /// the time with the clock of the client.
+    fn retry_backoff(&self, max_elapsed_time: Option<Duration>) -> ExponentialBackoff<RetryClock> {
+        let defaults = backoff::ExponentialBackoff::default();
+        let mut backoff = ExponentialBackoff {
+            current_interval: defaults.current_interval,
+            initial_interval: defaults.initial_interval,
+            randomization_factor: defaults.randomization_factor,
+            multiplier: defaults.multiplier,
+            max_interval: defaults.max_interval,
+            start_time: defaults.start_time,
+            max_elapsed_time,
+            clock: RetryClock(self.clock.clone()),
+        };
+
+        // Start measuring the elapsed time with our clock.
+        backoff.reset();
+
+        backoff
+    }
+
+    /// Send a single HTTP request with the custom transport if there is one,
+    /// or with the `reqwest` client otherwise.
+    pub(super) async fn send_http_request(
+        &self,
+        request: &http::Request<Bytes>,
+        timeout: Duration,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        match &self.transport {
+            Some(transport) => transport
+                .send(clone_request(request), timeout, send_progress)
+                .await
+                .map_err(HttpError::Transport),
+            None => send_request(&self.inner, request, timeout, send_progress).await,
+        }
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
+impl HttpClient {
+    /// Send a request with the given stream as its body, instead of the body
+    /// of the serialized request.
+    ///
+    /// The stream can only be read once, so the request is never retried.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn send_with_body_stream<R>(
+        &self,
+        request: R,
+        body: impl Stream<Item = io::Result<Bytes>> + Send + Sync + 'static,
+        content_length: u64,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        server_versions: &[MatrixVersion],
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<R::IncomingResponse, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+        let request = self.serialize_streaming_request(
+            request,
+            config,
+            homeserver,
+            access_token,
+            server_versions,
+        )?;
+
+        let category = RequestCategory::from_path(request.uri().path());
+        let _permits = self.limits.acquire(category).await;
+
+        debug!(uri = %request.uri(), content_length, "Sending request with a streamed body");
+
+        let response = match &self.transport {
+            Some(transport) => {
+                // Custom transports only deal with complete bodies.
+                let body = Box::pin(body)
+                    .try_fold(BytesMut::new(), |mut buf, chunk| async move {
+                        buf.extend_from_slice(&chunk);
+                        Ok(buf)
+                    })
+                    .await
+                    .map_err(|error| HttpError::Transport(Box::new(error)))?;
+
+                transport
+                    .send(request.map(|_| body.freeze()), config.timeout, send_progress)
+                    .await
+                    .map_err(HttpError::Transport)?
+            }
+            None => {
+                let content_length_usize = content_length.try_into().unwrap_or(usize::MAX);
+                send_progress.update(|p| p.total += content_length_usize);
+
+                let body = body.inspect_ok(move |chunk| {
+                    send_progress.update(|p| p.current += chunk.len());
+                });
+                let mut request =
+                    reqwest::Request::try_from(request.map(|_| reqwest::Body::wrap_stream(body)))?;
+
+                // reqwest doesn't know the size of a streamed body.
+                request.headers_mut().insert(CONTENT_LENGTH, content_length.into());
+                *request.timeout_mut() = Some(config.timeout);
+
+                let response = self.inner.execute(request).await?;
+                response_to_http_response(response, None).await?
+            }
+        };
+
+        Ok(R::IncomingResponse::try_from_http_response(response)?)
+    }
+
+    /// Send a request and get its response body as a stream, instead of
+    /// deserializing the response.
+    ///
+    /// The body of a successful response is not checked, the request should
+    /// be one whose response is the raw body.
+    pub(crate) async fn send_with_response_stream<R>(
+        &self,
+        request: R,
+        config: Option<RequestConfig>,
+        homeserver: String,
+        access_token: Option<&str>,
+        server_versions: &[MatrixVersion],
+    ) -> Result<BodyStream, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+        HttpError: From<FromHttpResponseError<R::EndpointError>>,
+    {
+        let config = config.unwrap_or(self.request_config);
+        let request = self.serialize_streaming_request(
+            request,
+            config,
+            homeserver,
+            access_token,
+            server_versions,
+        )?;
+
+        let category = RequestCategory::from_path(request.uri().path());
+        let permits = self.limits.acquire(category).await;
+
+        debug!(uri = %request.uri(), "Sending request with a streamed response");
+
+        let response = match &self.transport {
+            Some(transport) => transport
+                .send(request, config.timeout, Default::default())
+                .await
+                .map_err(HttpError::Transport)?,
+            None => {
+                let mut request = reqwest::Request::try_from(request)?;
+                *request.timeout_mut() = Some(config.timeout);
+
+                let response = self.inner.execute(request).await?;
+
+                if !response.status().is_client_error() && !response.status().is_server_error() {
+                    // The permits are held until the body is dropped, so the
+                    // download counts against the limits while it is read.
+                    let body = response.bytes_stream().map(move |chunk| {
+                        let _permits = &permits;
+                        chunk.map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+                    });
+                    return Ok(Box::pin(body));
+                }
+
+                response_to_http_response(response, None).await?
+            }
+        };
+
+        // Let Ruma deserialize the errors.
+        let body = response.body().clone();
+        R::IncomingResponse::try_from_http_response(response)?;
+
+        Ok(Box::pin(stream::once(async move { Ok(body) })))
+    }
+
+    fn serialize_streaming_request<R>(
+        &self,
+        request: R,
+        config: RequestConfig,
+        homeserver: String,
+        access_token: Option<&str>,
+        server_versions: &[MatrixVersion],
+    ) -> Result<http::Request<Bytes>, HttpError>
+    where
+        R: OutgoingRequest + Debug,
+    {
+        #[allow(unused_mut)]
+        let mut request =
+            self.serialize_request(request, config, homeserver, access_token, server_versions)?;
+        self.check_read_only(&request)?;
+
+        #[cfg(feature = "appservice")]
+        if let Some(asserted_identity) = &self.asserted_identity {
+            asserted_identity.add_to_request(&mut request);
+        }
+
+        Ok(request)
+    }
+}
+
+/// A stream of the data read from an `AsyncRead`, in chunks.
+///
+/// The reader is sent back through a channel once all its data was read.
+pub(crate) struct ReaderStream<R> {
+    reader: Option<R>,
+    buf: Box<[u8]>,
+    end_sender: Option<oneshot::Sender<R>>,
+}
+
+impl<R: AsyncRead + Unpin> ReaderStream<R> {
+    pub(crate) fn new(reader: R) -> (Self, oneshot::Receiver<R>) {
+        let (end_sender, end_receiver) = oneshot::channel();
+        let stream = Self {
+            reader: Some(reader),
+            buf: vec![0; 8192].into_boxed_slice(),
+            end_sender: Some(end_sender),
+        };
+
+        (stream, end_receiver)
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for ReaderStream<R> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(reader) = &mut this.reader else {
+            return Poll::Ready(None);
+        };
+
+        match ready!(Pin::new(reader).poll_read(cx, &mut this.buf[..])) {
+            Ok(0) => {
+                if let (Some(reader), Some(end_sender)) =
+                    (this.reader.take(), this.end_sender.take())
+                {
+                    _ = end_sender.send(reader);
+                }
+                Poll::Ready(None)
+            }
+            Ok(read_bytes) => {
+                Poll::Ready(Some(Ok(Bytes::copy_from_slice(&this.buf[..read_bytes]))))
+            }
+            Err(error) => Poll::Ready(Some(Err(error))),
+        }
+    }
+}
+
+/// A [`Clock`] adapter measuring the elapsed time and the delays between the
+/// retries of a request.
 #[derive(Clone, Debug)]
+struct RetryClock(SharedClock);
+
+impl backoff::Clock for RetryClock {
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+impl Sleeper for RetryClock {
+    type Sleep = SleepFuture;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        self.0.sleep(duration)
+    }
+}
+
+/// Parse the `Retry-After` header of a response, when it contains a number of
+/// seconds.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Add up to 10% of random jitter to a delay, so the requests that were
+/// rate-limited at the same time are not retried all at once.
+fn with_jitter(delay: Duration) -> Duration {
+    // The keys of `RandomState` are random, which is good enough for jitter.
+    let random = RandomState::new().build_hasher().finish();
+    delay + delay.mul_f64((random % 1000) as f64 / 10_000.0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone)]
 pub(crate) struct HttpSettings {
     pub(crate) disable_ssl_verification: bool,
     pub(crate) proxy: Option<String>,
+    /// The proxy for the `http` URLs only, which takes precedence over
+    /// `proxy`.
+    pub(crate) http_proxy: Option<String>,
+    /// The proxy for the `https` URLs only, which takes precedence over
+    /// `proxy`.
+    pub(crate) https_proxy: Option<String>,
+    pub(crate) proxy_credentials: Option<(String, String)>,
+    /// The hosts that are reached directly, without going through the
+    /// proxies.
+    pub(crate) no_proxy: Vec<String>,
     pub(crate) user_agent: Option<String>,
     pub(crate) timeout: Duration,
+    /// DER-encoded certificates to trust in addition to the default ones.
+    pub(crate) additional_root_certificates: Vec<Vec<u8>>,
+    /// SHA-256 hashes of the public keys of the accepted certificates, if
+    /// they are pinned.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) certificate_pins: Vec<[u8; 32]>,
+    /// The function to call when a certificate chain is rejected because of
+    /// the pinning.
+    #[cfg(feature = "rustls-tls")]
+    pub(crate) certificate_pin_failure_handler: Option<super::CertificatePinFailureHandler>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for HttpSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpSettings")
+            .field("disable_ssl_verification", &self.disable_ssl_verification)
+            .field("proxy", &self.proxy)
+            .field("http_proxy", &self.http_proxy)
+            .field("https_proxy", &self.https_proxy)
+            .field("proxy_username", &self.proxy_credentials.as_ref().map(|(username, _)| username))
+            .field("no_proxy", &self.no_proxy)
+            .field("user_agent", &self.user_agent)
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -139,8 +512,17 @@ impl Default for HttpSettings {
         Self {
             disable_ssl_verification: false,
             proxy: None,
+            http_proxy: None,
+            https_proxy: None,
+            proxy_credentials: None,
+            no_proxy: Vec::new(),
             user_agent: None,
             timeout: DEFAULT_REQUEST_TIMEOUT,
+            additional_root_certificates: Vec::new(),
+            #[cfg(feature = "rustls-tls")]
+            certificate_pins: Vec::new(),
+            #[cfg(feature = "rustls-tls")]
+            certificate_pin_failure_handler: None,
         }
     }
 }
@@ -158,9 +540,54 @@ impl HttpSettings {
             http_client = http_client.danger_accept_invalid_certs(true)
         }
 
+        // The proxies are matched in the order they are added, so the ones for a
+        // single scheme must come first.
+        let mut proxies = Vec::new();
+        if let Some(p) = &self.http_proxy {
+            info!(proxy_url = p, "Setting the HTTP proxy for the HTTP client");
+            proxies.push(reqwest::Proxy::http(p.as_str())?);
+        }
+        if let Some(p) = &self.https_proxy {
+            info!(proxy_url = p, "Setting the HTTPS proxy for the HTTP client");
+            proxies.push(reqwest::Proxy::https(p.as_str())?);
+        }
         if let Some(p) = &self.proxy {
             info!(proxy_url = p, "Setting the proxy for the HTTP client");
-            http_client = http_client.proxy(reqwest::Proxy::all(p.as_str())?);
+            proxies.push(reqwest::Proxy::all(p.as_str())?);
+        }
+
+        let no_proxy = (!self.no_proxy.is_empty()).then(|| self.no_proxy.join(","));
+
+        for mut proxy in proxies {
+            // This is also used for the authentication of SOCKS5 proxies.
+            if let Some((username, password)) = &self.proxy_credentials {
+                proxy = proxy.basic_auth(username, password);
+            }
+
+            if let Some(no_proxy) = &no_proxy {
+                proxy = proxy.no_proxy(reqwest::NoProxy::from_string(no_proxy));
+            }
+
+            http_client = http_client.proxy(proxy);
+        }
+
+        for certificate in &self.additional_root_certificates {
+            http_client =
+                http_client.add_root_certificate(reqwest::Certificate::from_der(certificate)?);
+        }
+
+        // The pinning TLS configuration replaces the one built by reqwest, so it
+        // takes care of the root certificates and of the SSL verification too.
+        #[cfg(feature = "rustls-tls")]
+        if !self.certificate_pins.is_empty() {
+            info!("Pinning the certificates of the HTTP client");
+            http_client =
+                http_client.use_preconfigured_tls(super::certificate_pinning::tls_config(
+                    self.certificate_pins.clone(),
+                    &self.additional_root_certificates,
+                    !self.disable_ssl_verification,
+                    self.certificate_pin_failure_handler.clone(),
+                ));
         }
 
         Ok(http_client.build()?)
@@ -175,11 +602,14 @@ pub(super) async fn send_request(
 ) -> Result<http::Response<Bytes>, HttpError> {
     use std::convert::Infallible;
 
-    use futures_util::stream;
+    // Requests without a body, like media downloads, report the progress of
+    // receiving the response instead.
+    let receive_progress = (request.body().is_empty() && send_progress.subscriber_count() != 0)
+        .then(|| send_progress.clone());
 
     let request = clone_request(request);
     let request = {
-        let mut request = if send_progress.subscriber_count() != 0 {
+        let mut request = if receive_progress.is_none() && send_progress.subscriber_count() != 0 {
             let content_length = request.body().len();
             send_progress.update(|p| p.total += content_length);
 
@@ -213,7 +643,7 @@ pub(super) async fn send_request(
     };
 
     let response = client.execute(request).await?;
-    Ok(response_to_http_response(response).await?)
+    Ok(response_to_http_response(response, receive_progress.as_ref()).await?)
 }
 
 // Clones all request parts except the extensions which can't be cloned.
@@ -20,21 +20,35 @@ use eyeball::SharedObservable;
 use ruma::api::{error::FromHttpResponseError, IncomingResponse, OutgoingRequest};
 
 use super::{response_to_http_response, HttpClient, TransmissionProgress};
-use crate::{config::RequestConfig, error::HttpError};
+use crate::{
+    config::{RequestCategory, RequestConfig},
+    error::HttpError,
+};
 
 impl HttpClient {
     pub(super) async fn send_request<R>(
         &self,
         request: http::Request<Bytes>,
-        _config: RequestConfig,
-        _send_progress: SharedObservable<TransmissionProgress>,
+        config: RequestConfig,
+        send_progress: SharedObservable<TransmissionProgress>,
     ) -> Result<R::IncomingResponse, HttpError>
     where
         R: OutgoingRequest + Debug,
         HttpError: From<FromHttpResponseError<R::EndpointError>>,
     {
-        let request = reqwest::Request::try_from(request)?;
-        let response = response_to_http_response(self.inner.execute(request).await?).await?;
+        let category = RequestCategory::from_path(request.uri().path());
+        let _permits = self.limits.acquire(category).await;
+
+        let response = match &self.transport {
+            Some(transport) => transport
+                .send(request, config.timeout, send_progress)
+                .await
+                .map_err(HttpError::Transport)?,
+            None => {
+                let request = reqwest::Request::try_from(request)?;
+                response_to_http_response(self.inner.execute(request).await?, None).await?
+            }
+        };
 
         let status_code = response.status();
         let response_size = ByteSize(response.body().len().try_into().unwrap_or(u64::MAX));
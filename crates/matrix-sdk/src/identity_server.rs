@@ -0,0 +1,317 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level identity server API.
+//!
+//! An identity server maps third-party identifiers, like email addresses, to
+//! Matrix user IDs. It is needed to invite users to a room by email.
+
+use std::sync::Arc;
+
+use ruma::{
+    api::{
+        client::{account::request_openid_token, discovery::discover_homeserver},
+        MatrixVersion,
+    },
+    events::GlobalAccountDataEventType,
+};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::{config::RequestConfig, Client, Error, Result};
+
+/// The default language used to pick the translation of the policies of an
+/// identity server.
+const DEFAULT_POLICY_LANGUAGE: &str = "en";
+
+/// A policy that the user must accept before using an identity server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentityServerPolicy {
+    /// The ID of the policy, e.g. `privacy_policy`.
+    pub id: String,
+    /// The version of the policy.
+    pub version: String,
+    /// The name of the policy, in the requested language if available.
+    pub name: String,
+    /// The URL of the policy, which is used to accept it.
+    pub url: String,
+}
+
+/// An identity server used by the current user.
+///
+/// The user must be registered with the identity server and accept its
+/// policies before using it. The registration is done automatically when
+/// needed, with an OpenID token of the homeserver.
+#[derive(Clone, Debug)]
+pub struct IdentityServer {
+    client: Client,
+    base_url: Url,
+    access_token: Arc<Mutex<Option<String>>>,
+}
+
+impl IdentityServer {
+    /// Create a new `IdentityServer` with the given base URL.
+    ///
+    /// To use the identity server chosen by the user or advertised by their
+    /// homeserver, use [`Client::identity_server()`] instead.
+    pub fn new(client: Client, base_url: Url) -> Self {
+        Self { client, base_url, access_token: Default::default() }
+    }
+
+    /// The base URL of the identity server.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// The name of the identity server, as expected by the homeserver when
+    /// inviting users by third-party identifier.
+    pub fn server_name(&self) -> String {
+        let host = self.base_url.host_str().unwrap_or_default();
+
+        match self.base_url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_owned(),
+        }
+    }
+
+    /// Get the policies of the identity server.
+    ///
+    /// # Arguments
+    ///
+    /// * `language` - The language of the names and URLs of the policies.
+    ///   Defaults to English, or to any available language if there is no
+    ///   English version.
+    pub async fn policies(&self, language: Option<&str>) -> Result<Vec<IdentityServerPolicy>> {
+        let language = language.unwrap_or(DEFAULT_POLICY_LANGUAGE);
+        let response = self.send(get_terms::Request {}, None).await?;
+
+        Ok(response
+            .policies
+            .into_iter()
+            .filter_map(|(id, mut policy)| {
+                let translation = policy
+                    .translations
+                    .remove(language)
+                    .or_else(|| policy.translations.into_values().next())?;
+
+                Some(IdentityServerPolicy {
+                    id,
+                    version: policy.version,
+                    name: translation.name,
+                    url: translation.url,
+                })
+            })
+            .collect())
+    }
+
+    /// Accept the policies with the given URLs on behalf of the user.
+    pub async fn accept_policies(&self, urls: Vec<String>) -> Result<()> {
+        let access_token = self.access_token().await?;
+        self.send(accept_terms::Request { user_accepts: urls }, Some(&access_token)).await?;
+        Ok(())
+    }
+
+    /// Get the access token of the user for the identity server.
+    ///
+    /// The user is registered with the identity server the first time it is
+    /// called.
+    pub async fn access_token(&self) -> Result<String> {
+        let mut access_token = self.access_token.lock().await;
+
+        if let Some(token) = &*access_token {
+            return Ok(token.clone());
+        }
+
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let openid_token = self
+            .client
+            .send(request_openid_token::v3::Request::new(user_id.to_owned()), None)
+            .await?;
+
+        let request = register::Request {
+            access_token: openid_token.access_token,
+            token_type: openid_token.token_type.to_string(),
+            matrix_server_name: openid_token.matrix_server_name.to_string(),
+            expires_in: openid_token.expires_in.as_secs(),
+        };
+        let token = self.send(request, None).await?.token;
+
+        *access_token = Some(token.clone());
+        Ok(token)
+    }
+
+    async fn send<R>(&self, request: R, access_token: Option<&str>) -> Result<R::IncomingResponse>
+    where
+        R: ruma::api::OutgoingRequest<EndpointError = ruma::api::client::Error> + std::fmt::Debug,
+    {
+        Ok(self
+            .client
+            .inner
+            .http_client
+            .send(
+                request,
+                None,
+                self.base_url.to_string(),
+                access_token,
+                &[MatrixVersion::V1_0],
+                Default::default(),
+            )
+            .await?)
+    }
+}
+
+impl Client {
+    /// Get the identity server of the current user.
+    ///
+    /// It is the one set by the user in their account data, or else the one
+    /// advertised in the `/.well-known/matrix/client` file of their
+    /// homeserver. Returns `None` if the user disabled the identity server or
+    /// if none was found.
+    pub async fn identity_server(&self) -> Result<Option<IdentityServer>> {
+        let event_type = GlobalAccountDataEventType::from("m.identity_server");
+        if let Some(raw) = self.account().account_data_raw(event_type).await? {
+            let content = raw.deserialize_as::<IdentityServerContent>()?;
+            let base_url = content.base_url.and_then(|url| Url::parse(&url).ok());
+            return Ok(base_url.map(|url| IdentityServer::new(self.clone(), url)));
+        }
+
+        let user_id = self.user_id().ok_or(Error::AuthenticationRequired)?;
+        let server_url = format!("https://{}", user_id.server_name());
+
+        let Ok(well_known) = self
+            .inner
+            .http_client
+            .send(
+                discover_homeserver::Request::new(),
+                Some(RequestConfig::short_retry()),
+                server_url,
+                None,
+                &[MatrixVersion::V1_0],
+                Default::default(),
+            )
+            .await
+        else {
+            return Ok(None);
+        };
+
+        Ok(well_known
+            .identity_server
+            .and_then(|info| Url::parse(&info.base_url).ok())
+            .map(|url| IdentityServer::new(self.clone(), url)))
+    }
+}
+
+/// The content of the `m.identity_server` account data event.
+#[derive(Deserialize)]
+struct IdentityServerContent {
+    base_url: Option<String>,
+}
+
+/// Register with the identity server using an OpenID token of the homeserver.
+mod register {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: false,
+        authentication: None,
+        history: {
+            1.0 => "/_matrix/identity/v2/account/register",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        pub access_token: String,
+        pub token_type: String,
+        pub matrix_server_name: String,
+        pub expires_in: u64,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub token: String,
+    }
+}
+
+/// Get the policies of the identity server.
+mod get_terms {
+    use std::collections::BTreeMap;
+
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata,
+    };
+    use serde::{Deserialize, Serialize};
+
+    const METADATA: Metadata = metadata! {
+        method: GET,
+        rate_limited: false,
+        authentication: None,
+        history: {
+            1.0 => "/_matrix/identity/v2/terms",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {}
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub policies: BTreeMap<String, Policy>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct Policy {
+        pub version: String,
+        #[serde(flatten)]
+        pub translations: BTreeMap<String, LocalizedPolicy>,
+    }
+
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct LocalizedPolicy {
+        pub name: String,
+        pub url: String,
+    }
+}
+
+/// Accept policies of the identity server.
+mod accept_terms {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: false,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/identity/v2/terms",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        pub user_accepts: Vec<String>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    #[derive(Default)]
+    pub struct Response {}
+}
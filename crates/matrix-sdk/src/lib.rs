@@ -22,7 +22,10 @@ pub use bytes;
 pub use matrix_sdk_base::crypto;
 pub use matrix_sdk_base::{
     deserialized_responses,
-    store::{DynStateStore, MemoryStore, StateStoreExt},
+    store::{
+        ComposerDraft, ComposerDraftType, DynStateStore, MemoryStore, ScheduledMessage,
+        SentTransaction, StateStoreExt,
+    },
     DisplayName, Room as BaseRoom, RoomCreateWithCreatorEventContent, RoomInfo,
     RoomMember as BaseRoomMember, RoomMemberships, RoomState, SessionMeta, StateChanges,
     StateStore, StoreError,
@@ -31,22 +34,39 @@ pub use matrix_sdk_common::*;
 pub use reqwest;
 
 mod account;
+#[cfg(feature = "appservice")]
+pub mod appservice;
 pub mod attachment;
 mod authentication;
 mod client;
+pub mod client_registry;
 pub mod config;
 mod deduplicating_handler;
+pub mod devices;
 #[cfg(feature = "e2e-encryption")]
 pub mod encryption;
 mod error;
+pub mod event_cache;
 pub mod event_handler;
+pub mod html;
 mod http_client;
+pub mod identity_server;
 pub mod matrix_auth;
 pub mod media;
+pub mod mentions;
+pub mod message_search;
 pub mod notification_settings;
 #[cfg(feature = "experimental-oidc")]
 pub mod oidc;
+pub mod policy_list;
+mod pushers;
 pub mod room;
+pub mod room_creation;
+pub mod room_directory_search;
+pub mod room_preview;
+mod scheduled_messages;
+mod sent_transactions;
+pub mod stickers;
 pub mod utils;
 pub mod futures {
     //! Named futures returned from methods on types in [the crate root][crate].
@@ -56,22 +76,33 @@ pub mod futures {
 #[cfg(feature = "experimental-sliding-sync")]
 pub mod sliding_sync;
 pub mod sync;
+pub mod uiaa;
+mod unread_counts;
+pub mod uri;
 #[cfg(feature = "experimental-widgets")]
 pub mod widget;
 
 pub use account::Account;
-pub use authentication::{AuthApi, AuthSession, SessionTokens};
-pub use client::{Client, ClientBuildError, ClientBuilder, LoopCtrl, SessionChange};
+pub use authentication::{
+    AuthApi, AuthSession, SessionSaveReason, SessionStoreDelegate, SessionTokens,
+};
+pub use client::{
+    Client, ClientBuildError, ClientBuilder, CustomResponse, HomeserverChange, LoopCtrl,
+    ReloginCredentials, SessionChange, SessionState,
+};
 #[cfg(feature = "image-proc")]
 pub use error::ImageError;
 pub use error::{
     Error, HttpError, HttpResult, NotificationSettingsError, RefreshTokenError, Result,
-    RumaApiError,
+    RoomCreationError, RoomSettingsError, RumaApiError,
 };
-pub use http_client::TransmissionProgress;
+#[cfg(all(not(target_arch = "wasm32"), feature = "rustls-tls"))]
+pub use http_client::CertificatePinFailure;
+pub use http_client::{HttpTransport, HttpTransportError, RateLimited, TransmissionProgress};
 #[cfg(all(feature = "e2e-encryption", feature = "sqlite"))]
 pub use matrix_sdk_sqlite::SqliteCryptoStore;
 pub use media::Media;
+pub use pushers::{PusherSettings, Pushers};
 pub use room::Room;
 pub use ruma::{IdParseError, OwnedServerName, ServerName};
 #[cfg(feature = "experimental-sliding-sync")]
@@ -79,6 +110,7 @@ pub use sliding_sync::{
     RoomListEntry, SlidingSync, SlidingSyncBuilder, SlidingSyncList, SlidingSyncListBuilder,
     SlidingSyncListLoadingState, SlidingSyncMode, SlidingSyncRoom, UpdateSummary,
 };
+pub use unread_counts::UnreadCounts;
 
 #[cfg(any(test, feature = "testing"))]
 pub mod test_utils;
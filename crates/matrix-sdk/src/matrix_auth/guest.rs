@@ -0,0 +1,224 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for [guest access].
+//!
+//! A guest account can read world-readable rooms and join rooms that allow
+//! guests, without having to sign up first. It can later be upgraded to a full
+//! account, keeping the same user ID, device and crypto identity.
+//!
+//! [guest access]: https://spec.matrix.org/v1.8/client-server-api/#guest-access
+
+use std::sync::atomic::Ordering;
+
+use matrix_sdk_base::SessionMeta;
+use ruma::{
+    api::client::account::register::{self, RegistrationKind},
+    assign,
+};
+use tracing::{error, info, instrument};
+
+use super::{MatrixAuth, MatrixSession, MatrixSessionTokens};
+use crate::{
+    authentication::SessionSaveReason,
+    client::{SessionChange, SessionState},
+    uiaa::{send_with_uiaa, UiaaHandler},
+    Error, Result,
+};
+
+/// The `register` endpoint, with the `guest_access_token` field used to
+/// upgrade a guest account, which is not supported by Ruma.
+mod upgrade_guest {
+    use std::time::Duration;
+
+    use ruma::{
+        api::{client::uiaa::AuthData, request, response, Metadata},
+        metadata, OwnedDeviceId, OwnedUserId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: None,
+        history: {
+            1.0 => "/_matrix/client/r0/register",
+            1.1 => "/_matrix/client/v3/register",
+        }
+    };
+
+    #[request(error = ruma::api::client::uiaa::UiaaResponse)]
+    pub struct Request {
+        pub guest_access_token: String,
+        pub username: String,
+        pub password: String,
+        pub device_id: OwnedDeviceId,
+        #[serde(default, skip_serializing_if = "ruma::serde::is_default")]
+        pub refresh_token: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub auth: Option<AuthData>,
+    }
+
+    #[response(error = ruma::api::client::uiaa::UiaaResponse)]
+    pub struct Response {
+        pub user_id: OwnedUserId,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub access_token: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub device_id: Option<OwnedDeviceId>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub refresh_token: Option<String>,
+        #[serde(
+            with = "ruma::serde::duration::opt_ms",
+            default,
+            skip_serializing_if = "Option::is_none",
+            rename = "expires_in_ms"
+        )]
+        pub expires_in: Option<Duration>,
+    }
+}
+
+impl MatrixAuth {
+    /// Register a new guest account on the homeserver and log into it.
+    ///
+    /// Guests have access to a limited set of endpoints. Notably, they can't
+    /// upload filters, so [`SyncSettings::filter_builder()`] sends the filter
+    /// along with each sync request instead.
+    ///
+    /// The session is saved with the [`SessionStoreDelegate`], if one is set.
+    /// It can also be obtained with [`MatrixAuth::session()`], and must be
+    /// restored with [`MatrixAuth::restore_guest_session()`].
+    ///
+    /// [`SyncSettings::filter_builder()`]: crate::config::SyncSettings::filter_builder
+    /// [`SessionStoreDelegate`]: crate::SessionStoreDelegate
+    #[instrument(skip_all)]
+    pub async fn register_guest(&self) -> Result<()> {
+        let homeserver = self.client.homeserver();
+        info!("Registering a guest account on {homeserver}");
+
+        let request = assign!(register::v3::Request::new(), { kind: RegistrationKind::Guest });
+        let response = self.client.send(request, None).await?;
+
+        let (Some(access_token), Some(device_id)) = (response.access_token, response.device_id)
+        else {
+            error!("The homeserver didn't log into the guest account");
+            return Err(Error::InconsistentState);
+        };
+
+        let session = MatrixSession {
+            meta: SessionMeta { user_id: response.user_id, device_id },
+            tokens: MatrixSessionTokens { access_token, refresh_token: response.refresh_token },
+        };
+
+        self.set_session(session).await?;
+        self.client.inner.auth_ctx.set_access_token_lifetime(response.expires_in);
+        self.set_is_guest(true);
+        self.client.save_session(SessionSaveReason::Login).await;
+
+        Ok(())
+    }
+
+    /// Restore a session of a guest account, previously obtained after
+    /// calling [`MatrixAuth::register_guest()`].
+    ///
+    /// This works like [`MatrixAuth::restore_session()`], with the limitations
+    /// of guest accounts.
+    #[instrument(skip_all)]
+    pub async fn restore_guest_session(&self, session: MatrixSession) -> Result<()> {
+        self.restore_session(session).await?;
+        self.set_is_guest(true);
+        Ok(())
+    }
+
+    /// Whether the client is logged into a guest account.
+    pub fn is_guest(&self) -> bool {
+        self.data().is_some_and(|data| data.is_guest.load(Ordering::SeqCst))
+    }
+
+    fn set_is_guest(&self, is_guest: bool) {
+        if let Some(data) = self.data() {
+            data.is_guest.store(is_guest, Ordering::SeqCst);
+        }
+    }
+
+    /// Upgrade the current guest account to a full account.
+    ///
+    /// The user ID, the device and the crypto identity of the guest are kept,
+    /// so the rooms it joined and the encrypted messages it received are still
+    /// available after the upgrade. Only the tokens of the session are
+    /// replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `username` - The username of the account. Homeservers might ignore it
+    ///   and keep the user ID of the guest.
+    ///
+    /// * `password` - The password of the account.
+    ///
+    /// * `uiaa_handler` - The handler for the stages of the user-interactive
+    ///   authentication required by the homeserver to register.
+    #[instrument(skip_all)]
+    pub async fn upgrade_guest(
+        &self,
+        username: &str,
+        password: &str,
+        uiaa_handler: &dyn UiaaHandler,
+    ) -> Result<()> {
+        let meta = self.client.session_meta().ok_or(Error::AuthenticationRequired)?.clone();
+        let tokens = self.session_tokens().ok_or(Error::AuthenticationRequired)?;
+
+        info!(user_id = ?meta.user_id, device_id = ?meta.device_id, "Upgrading guest account");
+
+        let response = send_with_uiaa(uiaa_handler, |auth| {
+            let request = upgrade_guest::Request {
+                guest_access_token: tokens.access_token.clone(),
+                username: username.to_owned(),
+                password: password.to_owned(),
+                device_id: meta.device_id.clone(),
+                refresh_token: tokens.refresh_token.is_some(),
+                auth,
+            };
+            self.client.send(request, None)
+        })
+        .await?;
+
+        let Some(access_token) = response.access_token else {
+            error!("The homeserver didn't log into the upgraded account");
+            return Err(Error::InconsistentState);
+        };
+
+        if response.user_id != meta.user_id
+            || response.device_id.is_some_and(|device_id| device_id != meta.device_id)
+        {
+            error!(
+                user_id = ?response.user_id,
+                "The homeserver upgraded the guest account to a different session"
+            );
+            return Err(Error::InconsistentState);
+        }
+
+        self.set_session_tokens(MatrixSessionTokens {
+            access_token,
+            refresh_token: response.refresh_token,
+        });
+        self.client.inner.auth_ctx.set_access_token_lifetime(response.expires_in);
+        self.client.inner.auth_ctx.session_state.set(SessionState::LoggedIn);
+        self.set_is_guest(false);
+
+        self.client.save_session(SessionSaveReason::Login).await;
+
+        _ = self.client.inner.auth_ctx.session_change_sender.send(SessionChange::TokensRefreshed);
+
+        Ok(())
+    }
+}
@@ -16,9 +16,12 @@
 
 //! Types to interact with the native Matrix authentication API.
 
-use std::fmt;
 #[cfg(feature = "sso-login")]
 use std::future::Future;
+use std::{
+    fmt,
+    sync::{atomic::AtomicBool, Arc},
+};
 
 use eyeball::SharedObservable;
 use futures_core::Stream;
@@ -35,18 +38,21 @@ use ruma::{
         },
         OutgoingRequest, SendAccessToken,
     },
+    assign,
     serde::JsonObject,
 };
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, info, instrument};
 
 use crate::{
-    authentication::AuthData,
-    client::SessionChange,
+    authentication::{AuthData, SessionSaveReason},
+    client::{ReloginCredentials, SessionChange, SessionState},
+    config::RequestConfig,
     error::{HttpError, HttpResult},
     Client, Error, RefreshTokenError, Result,
 };
 
+mod guest;
 mod login_builder;
 
 pub use self::login_builder::LoginBuilder;
@@ -56,6 +62,8 @@ pub use self::login_builder::SsoLoginBuilder;
 #[derive(Clone)]
 pub(crate) struct MatrixAuthData {
     pub(crate) tokens: SharedObservable<MatrixSessionTokens>,
+    /// Whether the session is the one of a guest account.
+    is_guest: Arc<AtomicBool>,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -482,14 +490,9 @@ impl MatrixAuth {
                 }
 
                 self.set_session_tokens(session_tokens);
+                self.client.inner.auth_ctx.set_access_token_lifetime(res.expires_in_ms);
 
-                if let Some(save_session_callback) =
-                    self.client.inner.auth_ctx.save_session_callback.get()
-                {
-                    if let Err(err) = save_session_callback(self.client.clone()).await {
-                        error!("when saving session after refresh: {err}");
-                    }
-                }
+                self.client.save_session(SessionSaveReason::TokensRefreshed).await;
 
                 _ = self
                     .client
@@ -551,7 +554,9 @@ impl MatrixAuth {
     /// Log out the current user.
     pub async fn logout(&self) -> HttpResult<logout::v3::Response> {
         let request = logout::v3::Request::new();
-        self.client.send(request, None).await
+        let response = self.client.send(request, None).await?;
+        self.client.inner.auth_ctx.session_state.set(SessionState::LoggedOut);
+        Ok(response)
     }
 
     /// Get the current access token and optional refresh token for this
@@ -581,7 +586,10 @@ impl MatrixAuth {
                 .inner
                 .auth_ctx
                 .auth_data
-                .set(AuthData::Matrix(MatrixAuthData { tokens: SharedObservable::new(tokens) }))
+                .set(AuthData::Matrix(MatrixAuthData {
+                    tokens: SharedObservable::new(tokens),
+                    is_guest: Default::default(),
+                }))
                 .expect("We just checked the value was not set");
         }
     }
@@ -822,12 +830,65 @@ impl MatrixAuth {
         self.client.maybe_update_login_well_known(response.well_known.as_ref());
 
         self.set_session(response.into()).await?;
+        self.client.inner.auth_ctx.set_access_token_lifetime(response.expires_in);
+        self.client.save_session(SessionSaveReason::Login).await;
+
+        Ok(())
+    }
+
+    /// Log back into the current session, with the same user and device ID.
+    ///
+    /// Only the tokens of the session are replaced, the session metadata and
+    /// the crypto identity are kept as-is.
+    pub(crate) async fn relogin(&self, credentials: ReloginCredentials) -> Result<()> {
+        let meta = self.client.session_meta().ok_or(Error::AuthenticationRequired)?.clone();
+        let had_refresh_token =
+            self.session_tokens().is_some_and(|tokens| tokens.refresh_token.is_some());
+
+        let login_info = match credentials {
+            ReloginCredentials::Password(password) => {
+                login::v3::LoginInfo::Password(login::v3::Password::new(
+                    UserIdentifier::UserIdOrLocalpart(meta.user_id.to_string()),
+                    password,
+                ))
+            }
+            ReloginCredentials::Token(token) => {
+                login::v3::LoginInfo::Token(login::v3::Token::new(token))
+            }
+        };
+
+        info!(user_id = ?meta.user_id, device_id = ?meta.device_id, "Logging back in");
+
+        let request = assign!(login::v3::Request::new(login_info), {
+            device_id: Some(meta.device_id.clone()),
+            refresh_token: had_refresh_token,
+        });
+        let response = self.client.send(request, Some(RequestConfig::short_retry())).await?;
+
+        if response.user_id != meta.user_id || response.device_id != meta.device_id {
+            error!(
+                user_id = ?response.user_id,
+                device_id = ?response.device_id,
+                "The server logged into a different session"
+            );
+            return Err(Error::InconsistentState);
+        }
+
+        self.set_session_tokens(MatrixSession::from(&response).tokens);
+        self.client.inner.auth_ctx.set_access_token_lifetime(response.expires_in);
+        self.client.inner.auth_ctx.session_state.set(SessionState::LoggedIn);
+
+        self.client.save_session(SessionSaveReason::SoftLogoutRecovered).await;
+
+        _ = self.client.inner.auth_ctx.session_change_sender.send(SessionChange::TokensRefreshed);
 
         Ok(())
     }
 
     async fn set_session(&self, session: MatrixSession) -> Result<()> {
         self.set_session_tokens(session.tokens);
+        // The lifetime of restored tokens is unknown.
+        self.client.inner.auth_ctx.set_access_token_lifetime(None);
         self.client.set_session_meta(session.meta).await?;
 
         #[cfg(feature = "e2e-encryption")]
@@ -17,18 +17,39 @@
 
 #[cfg(feature = "e2e-encryption")]
 use std::io::Read;
-use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex as StdMutex, RwLock as StdRwLock},
+    time::Duration,
+};
 #[cfg(not(target_arch = "wasm32"))]
 use std::{fmt, fs::File, io, path::Path};
 
 use eyeball::SharedObservable;
-use futures_util::future::try_join;
+use futures_util::future::{join_all, try_join};
+#[cfg(not(target_arch = "wasm32"))]
+use futures_util::{
+    io::{AsyncRead, AsyncReadExt},
+    TryStreamExt,
+};
 pub use matrix_sdk_base::media::*;
+use matrix_sdk_base::StateStoreDataKey;
+#[cfg(not(target_arch = "wasm32"))]
+use matrix_sdk_base::StateStoreDataValue;
+use matrix_sdk_common::{instant::Instant, time::Clock};
 use mime::Mime;
 #[cfg(not(target_arch = "wasm32"))]
 use mime2ext;
+#[cfg(not(target_arch = "wasm32"))]
+use ruma::serde::Base64;
 use ruma::{
-    api::client::media::{create_content, get_content, get_content_thumbnail},
+    api::{
+        client::{
+            error::ErrorKind,
+            media::{create_content, get_content, get_content_thumbnail, get_media_preview},
+        },
+        MatrixVersion,
+    },
     assign,
     events::room::{
         message::{
@@ -38,23 +59,58 @@ use ruma::{
         },
         ImageInfo, MediaSource, ThumbnailInfo,
     },
-    MxcUri,
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, UInt,
 };
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use sha2::{Digest, Sha256};
 #[cfg(not(target_arch = "wasm32"))]
 use tempfile::{Builder as TempFileBuilder, NamedTempFile, TempDir};
 #[cfg(not(target_arch = "wasm32"))]
-use tokio::{fs::File as TokioFile, io::AsyncWriteExt};
+use tokio::{
+    fs::File as TokioFile,
+    io::{AsyncReadExt as _, AsyncWriteExt},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio_util::compat::TokioAsyncReadCompatExt;
+use tracing::{debug, warn};
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::http_client::{BodyStream, ReaderStream};
 use crate::{
-    attachment::{AttachmentInfo, Thumbnail},
+    attachment::{AttachmentData, AttachmentInfo, Thumbnail},
     futures::SendRequest,
-    Client, Result, TransmissionProgress,
+    Client, HttpResult, Result, TransmissionProgress,
 };
 
 /// A conservative upload speed of 1Mbps
 const DEFAULT_UPLOAD_SPEED: u64 = 125_000;
 /// 5 min minimal upload request timeout, used to clamp the request timeout.
 const MIN_UPLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+/// The minimum delay between two automatic clean ups of the media cache.
+const CACHE_CLEAN_UP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The maximum number of URL previews kept in memory.
+const MAX_CACHED_URL_PREVIEWS: usize = 100;
+
+/// The state of the media cache of a client.
+#[derive(Debug)]
+pub(crate) struct MediaCacheState {
+    policy: StdRwLock<MediaRetentionPolicy>,
+    /// When the cache was last cleaned up.
+    last_clean_up: StdMutex<Option<Instant>>,
+    /// The URL previews fetched from the homeserver, by URL and timestamp.
+    url_previews: StdMutex<HashMap<(String, MilliSecondsSinceUnixEpoch), UrlPreview>>,
+}
+
+impl MediaCacheState {
+    pub(crate) fn new(policy: MediaRetentionPolicy) -> Self {
+        Self {
+            policy: StdRwLock::new(policy),
+            last_clean_up: Default::default(),
+            url_previews: Default::default(),
+        }
+    }
+}
 
 /// A high-level API to interact with the media API.
 #[derive(Debug, Clone)]
@@ -115,6 +171,42 @@ impl fmt::Display for PersistError {
     }
 }
 
+/// A preview of a URL, generated by the homeserver from the [Open Graph]
+/// metadata of the page.
+///
+/// [Open Graph]: https://ogp.me/
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UrlPreview {
+    /// The canonical URL of the page.
+    #[serde(rename = "og:url", skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The title of the page.
+    #[serde(rename = "og:title", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The description of the page.
+    #[serde(rename = "og:description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The name of the website.
+    #[serde(rename = "og:site_name", skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+    /// The image of the page, that the homeserver uploaded to its media
+    /// repository.
+    #[serde(rename = "og:image", skip_serializing_if = "Option::is_none")]
+    pub image: Option<OwnedMxcUri>,
+    /// The MIME type of the image.
+    #[serde(rename = "og:image:type", skip_serializing_if = "Option::is_none")]
+    pub image_mimetype: Option<String>,
+    /// The size of the image, in bytes.
+    #[serde(rename = "matrix:image:size", skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<UInt>,
+    /// The width of the image, in pixels.
+    #[serde(rename = "og:image:width", skip_serializing_if = "Option::is_none")]
+    pub image_width: Option<UInt>,
+    /// The height of the image, in pixels.
+    #[serde(rename = "og:image:height", skip_serializing_if = "Option::is_none")]
+    pub image_height: Option<UInt>,
+}
+
 /// `IntoFuture` returned by [`Media::upload`].
 pub type SendUploadRequest = SendRequest<create_content::v3::Request>;
 
@@ -123,6 +215,60 @@ impl Media {
         Self { client }
     }
 
+    /// Get the policy deciding which media content is kept in the cache.
+    pub fn media_retention_policy(&self) -> MediaRetentionPolicy {
+        *self.client.inner.media_cache.policy.read().unwrap()
+    }
+
+    /// Set the policy deciding which media content is kept in the cache.
+    ///
+    /// The new policy is applied the next time the cache is cleaned up, which
+    /// happens at most once an hour when content is added to the cache, or
+    /// when calling [`Media::clean_up_cache()`].
+    pub fn set_media_retention_policy(&self, policy: MediaRetentionPolicy) {
+        *self.client.inner.media_cache.policy.write().unwrap() = policy;
+    }
+
+    /// Remove the media content that doesn't respect the retention policy from
+    /// the cache.
+    pub async fn clean_up_cache(&self) -> Result<()> {
+        *self.client.inner.media_cache.last_clean_up.lock().unwrap() =
+            Some(self.client.inner.clock.now());
+
+        let policy = self.media_retention_policy();
+        Ok(self
+            .client
+            .store()
+            .clean_up_media_cache(policy, self.client.inner.clock.timestamp())
+            .await?)
+    }
+
+    /// Get the size of the media cache, in bytes.
+    pub async fn cache_size(&self) -> Result<usize> {
+        Ok(self.client.store().media_cache_size().await?)
+    }
+
+    /// Remove all the media content from the cache.
+    pub async fn clear_cache(&self) -> Result<()> {
+        Ok(self.client.store().clear_media_cache().await?)
+    }
+
+    /// Download the content of the given media and put it in the cache, if it
+    /// isn't already there.
+    ///
+    /// This can be used to fetch the thumbnails of the media that are about to
+    /// be displayed. The requests are sent concurrently, within the limits of
+    /// concurrent requests of the client, and failures are only logged.
+    pub async fn prefetch_media_content(&self, requests: impl IntoIterator<Item = MediaRequest>) {
+        let downloads = requests.into_iter().map(|request| async move {
+            if let Err(error) = self.get_media_content(&request, true).await {
+                warn!(uri = %request.uri(), "Couldn't prefetch media content: {error}");
+            }
+        });
+
+        join_all(downloads).await;
+    }
+
     /// Upload some media to the server.
     ///
     /// # Arguments
@@ -151,10 +297,7 @@ impl Media {
     /// # anyhow::Ok(()) };
     /// ```
     pub fn upload(&self, content_type: &Mime, data: Vec<u8>) -> SendUploadRequest {
-        let timeout = std::cmp::max(
-            Duration::from_secs(data.len() as u64 / DEFAULT_UPLOAD_SPEED),
-            MIN_UPLOAD_REQUEST_TIMEOUT,
-        );
+        let timeout = upload_timeout(data.len() as u64);
 
         let request = assign!(create_content::v3::Request::new(data), {
             content_type: Some(content_type.essence_str().to_owned()),
@@ -164,6 +307,358 @@ impl Media {
         self.client.send(request, Some(request_config))
     }
 
+    /// Upload some media to the server, reading it from `reader` as it is
+    /// sent.
+    ///
+    /// This is the same as [`Media::upload()`], except that the data is never
+    /// entirely loaded in memory, which allows to upload big files. The upload
+    /// is not retried if it fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    /// content-type header.
+    ///
+    /// * `reader` - The reader of the raw bytes of the media.
+    ///
+    /// * `size` - The size of the media, in bytes.
+    ///
+    /// * `send_progress` - The observable to report the progress of the upload
+    ///   to.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn upload_stream(
+        &self,
+        content_type: &Mime,
+        reader: impl AsyncRead + Send + Sync + Unpin + 'static,
+        size: u64,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<create_content::v3::Response> {
+        let (response, _) = self.upload_reader(content_type, reader, size, send_progress).await?;
+        Ok(response)
+    }
+
+    /// Upload the data read from `reader`, and get the reader back once all
+    /// its data was sent.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn upload_reader<R>(
+        &self,
+        content_type: &Mime,
+        reader: R,
+        size: u64,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<(create_content::v3::Response, R)>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let timeout = upload_timeout(size);
+
+        // The body of the request is replaced by the stream.
+        let request = assign!(create_content::v3::Request::new(Vec::new()), {
+            content_type: Some(content_type.essence_str().to_owned()),
+        });
+        let (body, reader_receiver) = ReaderStream::new(reader);
+        let access_token = self.client.access_token();
+
+        let response = self
+            .client
+            .inner
+            .http_client
+            .send_with_body_stream(
+                request,
+                body,
+                size,
+                Some(self.client.request_config().timeout(timeout)),
+                self.client.homeserver().to_string(),
+                access_token.as_deref(),
+                self.client.server_versions().await?,
+                send_progress,
+            )
+            .await?;
+
+        let reader = reader_receiver.await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The upload stopped before the end of the data",
+            )
+        })?;
+
+        Ok((response, reader))
+    }
+
+    /// Upload the media in the file at `path` to the server, in a way that
+    /// allows to resume the upload if it is interrupted.
+    ///
+    /// The content of the file is streamed, so it is never entirely loaded in
+    /// memory.
+    ///
+    /// When the homeserver supports asynchronous uploads, the content URI is
+    /// reserved before the content is uploaded, and it is persisted in the
+    /// store with the hash of the content and the path of the file. If the
+    /// upload fails, uploading the same content again reuses that URI, and if
+    /// the application is restarted, the uploads that didn't complete can be
+    /// resumed with [`Media::resume_upload()`]. Otherwise, this is the same as
+    /// [`Media::upload_stream()`].
+    ///
+    /// The homeservers can't continue an upload where it stopped, so resuming
+    /// an upload sends the whole content again.
+    ///
+    /// Returns the content URI of the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    /// content-type header.
+    ///
+    /// * `path` - The path of the file to upload. The file must not be
+    ///   modified until the upload is complete.
+    ///
+    /// * `send_progress` - The observable to report the progress of the upload
+    ///   to.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn upload_resumable(
+        &self,
+        content_type: &Mime,
+        path: impl AsRef<Path>,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<OwnedMxcUri> {
+        let path = path.as_ref();
+        let support = self.media_support().await.async_uploads;
+
+        if support == AsyncUploadSupport::Unsupported {
+            let file = TokioFile::open(path).await?;
+            let size = file.metadata().await?.len();
+            let response =
+                self.upload_stream(content_type, file.compat(), size, send_progress).await?;
+            return Ok(response.content_uri);
+        }
+
+        let (content_hash, size) = file_hash(path).await?;
+        let upload = self.reserve_upload(content_hash, size, content_type, path, support).await?;
+
+        self.upload_pending(&upload, path, support, send_progress).await
+    }
+
+    /// Resume an upload started with [`Media::upload_resumable()`] that didn't
+    /// complete, for example because the application was restarted.
+    ///
+    /// The content is read again from the file that was given to
+    /// [`Media::upload_resumable()`]. If the file was modified since, the
+    /// upload is dropped and an error is returned.
+    ///
+    /// Returns the content URI of the media.
+    ///
+    /// # Arguments
+    ///
+    /// * `upload` - The upload to resume, as returned by
+    ///   [`Media::pending_uploads()`].
+    ///
+    /// * `send_progress` - The observable to report the progress of the upload
+    ///   to.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn resume_upload(
+        &self,
+        upload: &PendingUpload,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<OwnedMxcUri> {
+        let Some(path) = &upload.source else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "The pending upload doesn't have a source file",
+            )
+            .into());
+        };
+
+        let (content_hash, size) = file_hash(path).await?;
+
+        if content_hash != upload.content_hash || size != upload.size {
+            self.remove_pending_upload(&upload.content_uri).await?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The source file of the pending upload was modified",
+            )
+            .into());
+        }
+
+        let support = match self.media_support().await.async_uploads {
+            AsyncUploadSupport::Stable => AsyncUploadSupport::Stable,
+            // The URI was reserved, so the homeserver supported asynchronous
+            // uploads at least with the unstable endpoints.
+            AsyncUploadSupport::Unstable | AsyncUploadSupport::Unsupported => {
+                AsyncUploadSupport::Unstable
+            }
+        };
+
+        self.upload_pending(upload, path, support, send_progress).await
+    }
+
+    /// Get the uploads started with [`Media::upload_resumable()`] that didn't
+    /// complete yet.
+    pub async fn pending_uploads(&self) -> Result<Vec<PendingUpload>> {
+        Ok(self
+            .client
+            .store()
+            .get_kv_data(StateStoreDataKey::PendingUploads)
+            .await?
+            .and_then(|value| value.into_pending_uploads())
+            .unwrap_or_default())
+    }
+
+    /// Stream the content of the file at `path` to the URI reserved for the
+    /// pending upload.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn upload_pending(
+        &self,
+        upload: &PendingUpload,
+        path: &Path,
+        support: AsyncUploadSupport,
+        send_progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<OwnedMxcUri> {
+        let content_uri = &upload.content_uri;
+
+        // The body of the request is replaced by the stream.
+        let request = async_upload::create_content_async::Request::from_url(
+            content_uri,
+            Vec::new(),
+            upload.content_type.clone(),
+        )?;
+        let file = TokioFile::open(path).await?;
+        let (body, _) = ReaderStream::new(file.compat());
+
+        let http_client = &self.client.inner.http_client;
+        let request_config =
+            Some(self.client.request_config().timeout(upload_timeout(upload.size)));
+        let homeserver = self.client.homeserver().to_string();
+        let access_token = self.client.access_token();
+        let server_versions = self.client.server_versions().await?;
+
+        let result = if support == AsyncUploadSupport::Stable {
+            http_client
+                .send_with_body_stream(
+                    request,
+                    body,
+                    upload.size,
+                    request_config,
+                    homeserver,
+                    access_token.as_deref(),
+                    server_versions,
+                    send_progress,
+                )
+                .await
+                .map(|_| ())
+        } else {
+            http_client
+                .send_with_body_stream(
+                    request.into_unstable(),
+                    body,
+                    upload.size,
+                    request_config,
+                    homeserver,
+                    access_token.as_deref(),
+                    server_versions,
+                    send_progress,
+                )
+                .await
+                .map(|_| ())
+        };
+
+        match result {
+            Ok(()) => {}
+            // A previous attempt already uploaded the content.
+            Err(error)
+                if error
+                    .client_api_error_kind()
+                    .is_some_and(|kind| kind.to_string() == "M_CANNOT_OVERWRITE_MEDIA") => {}
+            Err(error) => {
+                if error.client_api_error_kind() == Some(&ErrorKind::NotFound) {
+                    // The reservation expired, the next attempt needs a new one.
+                    self.remove_pending_upload(content_uri).await?;
+                }
+
+                return Err(error.into());
+            }
+        }
+
+        self.remove_pending_upload(content_uri).await?;
+
+        Ok(content_uri.clone())
+    }
+
+    /// Get the pending upload for the content with the given hash and size, or
+    /// reserve a new content URI for it.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn reserve_upload(
+        &self,
+        content_hash: String,
+        size: u64,
+        content_type: &Mime,
+        path: &Path,
+        support: AsyncUploadSupport,
+    ) -> Result<PendingUpload> {
+        let _guard = self.client.locks().pending_uploads_lock.lock().await;
+
+        let now = self.client.inner.clock.timestamp();
+        let mut pending_uploads = self.pending_uploads().await?;
+        pending_uploads.retain(|upload| !upload.has_expired(now));
+
+        if let Some(upload) = pending_uploads
+            .iter_mut()
+            .find(|upload| upload.content_hash == content_hash && upload.size == size)
+        {
+            debug!(content_uri = %upload.content_uri, "Resuming a pending upload");
+
+            // The same content might be read from another file this time.
+            upload.content_type = Some(content_type.essence_str().to_owned());
+            upload.source = Some(path.to_owned());
+            let upload = upload.clone();
+
+            self.save_pending_uploads(pending_uploads).await?;
+            return Ok(upload);
+        }
+
+        let request = async_upload::create_mxc_uri::Request::default();
+        let response = if support == AsyncUploadSupport::Stable {
+            self.client.send(request, None).await?
+        } else {
+            self.client.send(request.into_unstable(), None).await?
+        };
+
+        let upload = PendingUpload {
+            content_hash,
+            size,
+            content_uri: response.content_uri,
+            unused_expires_at: response.unused_expires_at,
+            content_type: Some(content_type.essence_str().to_owned()),
+            source: Some(path.to_owned()),
+        };
+        pending_uploads.push(upload.clone());
+        self.save_pending_uploads(pending_uploads).await?;
+
+        Ok(upload)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn remove_pending_upload(&self, content_uri: &MxcUri) -> Result<()> {
+        let _guard = self.client.locks().pending_uploads_lock.lock().await;
+
+        let mut pending_uploads = self.pending_uploads().await?;
+        pending_uploads.retain(|upload| &*upload.content_uri != content_uri);
+
+        self.save_pending_uploads(pending_uploads).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn save_pending_uploads(&self, pending_uploads: Vec<PendingUpload>) -> Result<()> {
+        self.client
+            .store()
+            .set_kv_data(
+                StateStoreDataKey::PendingUploads,
+                StateStoreDataValue::PendingUploads(pending_uploads),
+            )
+            .await?;
+
+        Ok(())
+    }
+
     /// Gets a media file by copying it to a temporary location on disk.
     ///
     /// The file won't be encrypted even if it is encrypted on the server.
@@ -193,8 +688,6 @@ impl Media {
         use_cache: bool,
         temp_dir: Option<String>,
     ) -> Result<MediaFileHandle> {
-        let data = self.get_media_content(request, use_cache).await?;
-
         let inferred_extension = mime2ext::mime2ext(content_type);
 
         let body_path = body.as_ref().map(Path::new);
@@ -242,13 +735,63 @@ impl Media {
         };
 
         let mut file = TokioFile::from_std(temp_file.reopen()?);
-        file.write_all(&data).await?;
+
+        if use_cache || matches!(request.format, MediaFormat::Thumbnail(_)) {
+            let data = self.get_media_content(request, use_cache).await?;
+            file.write_all(&data).await?;
+        } else {
+            // Write the file as it is downloaded, so it is never entirely in memory.
+            let mut reader = self.get_media_content_reader(&request.source).await?;
+            let mut buf = vec![0; 8192];
+
+            loop {
+                let read_bytes = reader.read(&mut buf).await?;
+                if read_bytes == 0 {
+                    break;
+                }
+                file.write_all(&buf[..read_bytes]).await?;
+            }
+        }
+
         // Make sure the file metadata is flushed to disk.
         file.sync_all().await?;
 
         Ok(MediaFileHandle { file: temp_file, _directory: temp_dir })
     }
 
+    /// Get a reader of the content of a media file, that is downloaded as it
+    /// is read.
+    ///
+    /// If the content is encrypted and encryption is enabled, the content is
+    /// decrypted as it is read. The media cache is not used, which allows to
+    /// download big files without loading them in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source of the media file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn get_media_content_reader(
+        &self,
+        source: &MediaSource,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        match source {
+            MediaSource::Encrypted(file) => {
+                let reader = self.download_content_stream(&file.url).await?.into_async_read();
+
+                #[cfg(feature = "e2e-encryption")]
+                let reader = matrix_sdk_base::crypto::AsyncAttachmentDecryptor::new(
+                    reader,
+                    file.as_ref().clone().into(),
+                )?;
+
+                Ok(Box::new(reader))
+            }
+            MediaSource::Plain(uri) => {
+                Ok(Box::new(self.download_content_stream(uri).await?.into_async_read()))
+            }
+        }
+    }
+
     /// Get a media file's content.
     ///
     /// If the content is encrypted and encryption is enabled, the content will
@@ -263,6 +806,29 @@ impl Media {
         &self,
         request: &MediaRequest,
         use_cache: bool,
+    ) -> Result<Vec<u8>> {
+        self.get_media_content_with_progress(request, use_cache, Default::default()).await
+    }
+
+    /// Get a media file's content, while reporting the progress of the
+    /// download.
+    ///
+    /// See [`Media::get_media_content()`] for more details. The progress is
+    /// only reported for the data received from the homeserver, it is not
+    /// updated when the content is read from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The `MediaRequest` of the content.
+    ///
+    /// * `use_cache` - If we should use the media cache for this request.
+    ///
+    /// * `progress` - The observable updated with the progress of the download.
+    pub async fn get_media_content_with_progress(
+        &self,
+        request: &MediaRequest,
+        use_cache: bool,
+        progress: SharedObservable<TransmissionProgress>,
     ) -> Result<Vec<u8>> {
         // Read from the cache.
         if use_cache {
@@ -273,8 +839,8 @@ impl Media {
 
         let content: Vec<u8> = match &request.source {
             MediaSource::Encrypted(file) => {
-                let request = get_content::v3::Request::from_url(&file.url)?;
-                let content: Vec<u8> = self.client.send(request, None).await?.file;
+                let content =
+                    self.download_content(&file.url, &MediaFormat::File, progress).await?;
 
                 #[cfg(feature = "e2e-encryption")]
                 let content = {
@@ -293,24 +859,235 @@ impl Media {
                 content
             }
             MediaSource::Plain(uri) => {
-                if let MediaFormat::Thumbnail(size) = &request.format {
-                    let request =
-                        get_content_thumbnail::v3::Request::from_url(uri, size.width, size.height)?;
-                    self.client.send(request, None).await?.file
-                } else {
-                    let request = get_content::v3::Request::from_url(uri)?;
-                    self.client.send(request, None).await?.file
-                }
+                self.download_content(uri, &request.format, progress).await?
             }
         };
 
         if use_cache {
-            self.client.store().add_media_content(request, content.clone()).await?;
+            self.add_media_content_to_cache(request, &content).await?;
         }
 
         Ok(content)
     }
 
+    /// Whether the homeserver supports the authenticated media endpoints.
+    ///
+    /// When it does, they are used to download media content instead of the
+    /// legacy unauthenticated endpoints.
+    pub async fn supports_authenticated_media(&self) -> bool {
+        self.authenticated_media_support().await != AuthenticatedMediaSupport::Unsupported
+    }
+
+    async fn authenticated_media_support(&self) -> AuthenticatedMediaSupport {
+        self.media_support().await.authenticated_media
+    }
+
+    async fn media_support(&self) -> MediaSupport {
+        let support = async {
+            let server_versions = self.client.server_versions().await?;
+            let unstable_features = self.client.unstable_features().await?;
+            HttpResult::Ok(MediaSupport::new(server_versions, unstable_features))
+        }
+        .await;
+
+        match support {
+            Ok(support) => support,
+            Err(error) => {
+                // Try again next time, the legacy endpoints should work in the meantime.
+                warn!("Couldn't check the support of the media endpoints: {error}");
+                MediaSupport {
+                    authenticated_media: AuthenticatedMediaSupport::Unsupported,
+                    async_uploads: AsyncUploadSupport::Unsupported,
+                }
+            }
+        }
+    }
+
+    /// Download the content of the given media from the homeserver, with the
+    /// authenticated endpoints if they are supported.
+    async fn download_content(
+        &self,
+        uri: &MxcUri,
+        format: &MediaFormat,
+        progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<Vec<u8>> {
+        let support = self.authenticated_media_support().await;
+
+        if support != AuthenticatedMediaSupport::Unsupported {
+            match self.download_authenticated_content(uri, format, support, progress.clone()).await
+            {
+                Err(error) if error.client_api_error_kind() == Some(&ErrorKind::Unrecognized) => {
+                    warn!(
+                        "The homeserver doesn't know the authenticated media endpoints, \
+                         falling back to the legacy endpoints"
+                    );
+                }
+                result => return result,
+            }
+        }
+
+        let content = if let MediaFormat::Thumbnail(size) = format {
+            let request =
+                get_content_thumbnail::v3::Request::from_url(uri, size.width, size.height)?;
+            self.client.send(request, None).with_send_progress_observable(progress).await?.file
+        } else {
+            let request = get_content::v3::Request::from_url(uri)?;
+            self.client.send(request, None).with_send_progress_observable(progress).await?.file
+        };
+
+        Ok(content)
+    }
+
+    /// Download the content of the given media file as a stream, with the
+    /// authenticated endpoints if they are supported.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn download_content_stream(&self, uri: &MxcUri) -> Result<BodyStream> {
+        let support = self.authenticated_media_support().await;
+        let http_client = &self.client.inner.http_client;
+        let homeserver = self.client.homeserver().to_string();
+        let access_token = self.client.access_token();
+        let server_versions = self.client.server_versions().await?;
+
+        if support != AuthenticatedMediaSupport::Unsupported {
+            let request = authenticated_media::get_content::Request::from_url(uri)?;
+            let result = if support == AuthenticatedMediaSupport::Stable {
+                http_client
+                    .send_with_response_stream(
+                        request,
+                        None,
+                        homeserver.clone(),
+                        access_token.as_deref(),
+                        server_versions,
+                    )
+                    .await
+            } else {
+                http_client
+                    .send_with_response_stream(
+                        request.into_unstable(),
+                        None,
+                        homeserver.clone(),
+                        access_token.as_deref(),
+                        server_versions,
+                    )
+                    .await
+            };
+
+            match result {
+                Err(error) if error.client_api_error_kind() == Some(&ErrorKind::Unrecognized) => {
+                    warn!(
+                        "The homeserver doesn't know the authenticated media endpoints, \
+                         falling back to the legacy endpoints"
+                    );
+                }
+                result => return Ok(result?),
+            }
+        }
+
+        let request = get_content::v3::Request::from_url(uri)?;
+        Ok(http_client
+            .send_with_response_stream(
+                request,
+                None,
+                homeserver,
+                access_token.as_deref(),
+                server_versions,
+            )
+            .await?)
+    }
+
+    async fn download_authenticated_content(
+        &self,
+        uri: &MxcUri,
+        format: &MediaFormat,
+        support: AuthenticatedMediaSupport,
+        progress: SharedObservable<TransmissionProgress>,
+    ) -> Result<Vec<u8>> {
+        let stable = support == AuthenticatedMediaSupport::Stable;
+
+        let content = match format {
+            MediaFormat::Thumbnail(size) => {
+                let request = authenticated_media::get_content_thumbnail::Request::from_url(
+                    uri,
+                    size.method.clone(),
+                    size.width,
+                    size.height,
+                )?;
+
+                if stable {
+                    self.client
+                        .send(request, None)
+                        .with_send_progress_observable(progress)
+                        .await?
+                        .file
+                } else {
+                    self.client
+                        .send(request.into_unstable(), None)
+                        .with_send_progress_observable(progress)
+                        .await?
+                        .file
+                }
+            }
+            MediaFormat::File => {
+                let request = authenticated_media::get_content::Request::from_url(uri)?;
+
+                if stable {
+                    self.client
+                        .send(request, None)
+                        .with_send_progress_observable(progress)
+                        .await?
+                        .file
+                } else {
+                    self.client
+                        .send(request.into_unstable(), None)
+                        .with_send_progress_observable(progress)
+                        .await?
+                        .file
+                }
+            }
+        };
+
+        Ok(content)
+    }
+
+    /// Add the content of a media file to the cache, if the retention policy
+    /// allows it, and clean up the cache if it wasn't done recently.
+    async fn add_media_content_to_cache(
+        &self,
+        request: &MediaRequest,
+        content: &[u8],
+    ) -> Result<()> {
+        if self.media_retention_policy().exceeds_max_file_size(content.len()) {
+            debug!(uri = %request.uri(), "Not caching media content that is too big");
+            return Ok(());
+        }
+
+        self.client.store().add_media_content(request, content.to_owned()).await?;
+
+        let should_clean_up = {
+            let mut last_clean_up = self.client.inner.media_cache.last_clean_up.lock().unwrap();
+            let now = self.client.inner.clock.now();
+            let should_clean_up =
+                last_clean_up.map_or(true, |instant| now - instant >= CACHE_CLEAN_UP_INTERVAL);
+
+            if should_clean_up {
+                *last_clean_up = Some(now);
+            }
+
+            should_clean_up
+        };
+
+        if should_clean_up {
+            let media = self.clone();
+            matrix_sdk_common::executor::spawn(async move {
+                if let Err(error) = media.clean_up_cache().await {
+                    warn!("Couldn't clean up the media cache: {error}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+
     /// Remove a media file's content from the store.
     ///
     /// # Arguments
@@ -434,13 +1211,77 @@ impl Media {
         Ok(())
     }
 
+    /// Get a preview of the given URL from the homeserver, with the
+    /// authenticated endpoints if they are supported.
+    ///
+    /// The previews are cached in memory, so they are only requested once per
+    /// URL and timestamp.
+    pub(crate) async fn get_url_preview(
+        &self,
+        url: &str,
+        ts: MilliSecondsSinceUnixEpoch,
+    ) -> Result<UrlPreview> {
+        let key = (url.to_owned(), ts);
+
+        if let Some(preview) = self.client.inner.media_cache.url_previews.lock().unwrap().get(&key)
+        {
+            return Ok(preview.clone());
+        }
+
+        let preview = self.fetch_url_preview(url, ts).await?;
+
+        let mut previews = self.client.inner.media_cache.url_previews.lock().unwrap();
+        if previews.len() >= MAX_CACHED_URL_PREVIEWS {
+            previews.clear();
+        }
+        previews.insert(key, preview.clone());
+
+        Ok(preview)
+    }
+
+    async fn fetch_url_preview(
+        &self,
+        url: &str,
+        ts: MilliSecondsSinceUnixEpoch,
+    ) -> Result<UrlPreview> {
+        let support = self.authenticated_media_support().await;
+
+        if support != AuthenticatedMediaSupport::Unsupported {
+            let request = authenticated_media::get_url_preview::Request::new(url.to_owned(), ts);
+            let result = if support == AuthenticatedMediaSupport::Stable {
+                self.client.send(request, None).await
+            } else {
+                self.client.send(request.into_unstable(), None).await
+            };
+
+            match result {
+                Err(error) if error.client_api_error_kind() == Some(&ErrorKind::Unrecognized) => {
+                    warn!(
+                        "The homeserver doesn't know the authenticated media endpoints, \
+                         falling back to the legacy endpoints"
+                    );
+                }
+                result => return Ok(result?.preview),
+            }
+        }
+
+        let request = get_media_preview::v3::Request::new(url.to_owned(), ts);
+        let response = self.client.send(request, None).await?;
+
+        Ok(response
+            .data
+            .map(|data| serde_json::from_str(data.get()))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
     /// Upload the file bytes in `data` and construct an attachment
     /// message with `body`, `content_type`, `info` and `thumbnail`.
     pub(crate) async fn prepare_attachment_message(
         &self,
         body: &str,
         content_type: &Mime,
-        data: Vec<u8>,
+        data: AttachmentData,
         info: Option<AttachmentInfo>,
         thumbnail: Option<Thumbnail>,
         send_progress: SharedObservable<TransmissionProgress>,
@@ -448,17 +1289,24 @@ impl Media {
         let upload_thumbnail = self.upload_thumbnail(thumbnail, send_progress.clone());
 
         let upload_attachment = async move {
-            self.upload(content_type, data)
-                .with_send_progress_observable(send_progress)
-                .await
-                .map_err(crate::Error::from)
+            match data {
+                AttachmentData::Bytes(data) => self
+                    .upload(content_type, data)
+                    .with_send_progress_observable(send_progress)
+                    .await
+                    .map(|response| response.content_uri)
+                    .map_err(crate::Error::from),
+                #[cfg(not(target_arch = "wasm32"))]
+                AttachmentData::Stream { reader, size } => self
+                    .upload_stream(content_type, reader, size, send_progress)
+                    .await
+                    .map(|response| response.content_uri),
+            }
         };
 
-        let ((thumbnail_source, thumbnail_info), response) =
+        let ((thumbnail_source, thumbnail_info), url) =
             try_join(upload_thumbnail, upload_attachment).await?;
 
-        let url = response.content_uri;
-
         Ok(match content_type.type_() {
             mime::IMAGE => {
                 let info = assign!(info.map(ImageInfo::from).unwrap_or_default(), {
@@ -529,6 +1377,483 @@ impl Media {
     }
 }
 
+/// Compute the SHA-256 hash of the content of the file at `path`, encoded as
+/// unpadded base64, and its size, without loading it entirely in memory.
+#[cfg(not(target_arch = "wasm32"))]
+async fn file_hash(path: &Path) -> io::Result<(String, u64)> {
+    let mut file = TokioFile::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 8192];
+    let mut size = 0;
+
+    loop {
+        let read_bytes = file.read(&mut buf).await?;
+        if read_bytes == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read_bytes]);
+        size += read_bytes as u64;
+    }
+
+    Ok((Base64::new(hasher.finalize().to_vec()).encode(), size))
+}
+
+/// The timeout of the request to upload media of the given size.
+fn upload_timeout(size: u64) -> Duration {
+    std::cmp::max(Duration::from_secs(size / DEFAULT_UPLOAD_SPEED), MIN_UPLOAD_REQUEST_TIMEOUT)
+}
+
+/// The support of the homeserver for the media endpoints that were added to
+/// the spec after the first versions.
+#[derive(Clone, Copy, Debug)]
+struct MediaSupport {
+    authenticated_media: AuthenticatedMediaSupport,
+    async_uploads: AsyncUploadSupport,
+}
+
+impl MediaSupport {
+    fn new(server_versions: &[MatrixVersion], unstable_features: &BTreeMap<String, bool>) -> Self {
+        let is_feature_enabled =
+            |feature: &str| unstable_features.get(feature).copied().unwrap_or(false);
+
+        // Ruma doesn't know Matrix 1.11 yet, so the stable endpoints are only
+        // detected with the feature that homeservers advertise along with it.
+        let authenticated_media = if is_feature_enabled("org.matrix.msc3916.stable") {
+            AuthenticatedMediaSupport::Stable
+        } else if is_feature_enabled("org.matrix.msc3916") {
+            AuthenticatedMediaSupport::Unstable
+        } else {
+            AuthenticatedMediaSupport::Unsupported
+        };
+
+        let supports_v1_7 = server_versions.iter().any(|version| *version >= MatrixVersion::V1_7);
+        let async_uploads = if supports_v1_7 {
+            AsyncUploadSupport::Stable
+        } else if is_feature_enabled("fi.mau.msc2246") {
+            AsyncUploadSupport::Unstable
+        } else {
+            AsyncUploadSupport::Unsupported
+        };
+
+        Self { authenticated_media, async_uploads }
+    }
+}
+
+/// The support of the homeserver for the authenticated media endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AuthenticatedMediaSupport {
+    /// The stable endpoints, added in Matrix 1.11.
+    Stable,
+    /// The unstable endpoints of MSC3916.
+    Unstable,
+    /// Only the legacy unauthenticated endpoints.
+    Unsupported,
+}
+
+/// The support of the homeserver for the asynchronous upload endpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AsyncUploadSupport {
+    /// The stable endpoints, added in Matrix 1.7.
+    Stable,
+    /// The unstable endpoints of MSC2246.
+    Unstable,
+    /// The content URI can't be reserved before uploading the content.
+    Unsupported,
+}
+
+/// The authenticated media endpoints of [MSC3916], that are not available in
+/// Ruma yet.
+///
+/// The version of the spec that stabilized them is not known by Ruma either,
+/// so the stable endpoints are only used when the server advertises them.
+///
+/// [MSC3916]: https://github.com/matrix-org/matrix-spec-proposals/pull/3916
+mod authenticated_media {
+    pub mod get_content {
+        use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, IdParseError, MxcUri, OwnedServerName,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: GET,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/client/v1/media/download/:server_name/:media_id",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            #[ruma_api(path)]
+            pub server_name: OwnedServerName,
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {
+            #[ruma_api(raw_body)]
+            pub file: Vec<u8>,
+            #[ruma_api(header = CONTENT_TYPE)]
+            pub content_type: Option<String>,
+            #[ruma_api(header = CONTENT_DISPOSITION)]
+            pub content_disposition: Option<String>,
+        }
+
+        impl Request {
+            pub fn from_url(url: &MxcUri) -> Result<Self, IdParseError> {
+                let (server_name, media_id) = url.parts()?;
+                Ok(Self { server_name: server_name.to_owned(), media_id: media_id.to_owned() })
+            }
+
+            pub fn into_unstable(self) -> unstable::Request {
+                unstable::Request { server_name: self.server_name, media_id: self.media_id }
+            }
+        }
+
+        pub mod unstable {
+            use ruma::{
+                api::{request, Metadata},
+                metadata, OwnedServerName,
+            };
+
+            pub use super::Response;
+
+            const METADATA: Metadata = metadata! {
+                method: GET,
+                rate_limited: true,
+                authentication: AccessToken,
+                history: {
+                    unstable => "/_matrix/client/unstable/org.matrix.msc3916/media/download/:server_name/:media_id",
+                }
+            };
+
+            #[request(error = ruma::api::client::Error)]
+            pub struct Request {
+                #[ruma_api(path)]
+                pub server_name: OwnedServerName,
+                #[ruma_api(path)]
+                pub media_id: String,
+            }
+        }
+    }
+
+    pub mod get_content_thumbnail {
+        use http::header::CONTENT_TYPE;
+        use ruma::{
+            api::{client::media::get_content_thumbnail::v3::Method, request, response, Metadata},
+            metadata, IdParseError, MxcUri, OwnedServerName, UInt,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: GET,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/client/v1/media/thumbnail/:server_name/:media_id",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            #[ruma_api(path)]
+            pub server_name: OwnedServerName,
+            #[ruma_api(path)]
+            pub media_id: String,
+            #[ruma_api(query)]
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub method: Option<Method>,
+            #[ruma_api(query)]
+            pub width: UInt,
+            #[ruma_api(query)]
+            pub height: UInt,
+        }
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {
+            #[ruma_api(raw_body)]
+            pub file: Vec<u8>,
+            #[ruma_api(header = CONTENT_TYPE)]
+            pub content_type: Option<String>,
+        }
+
+        impl Request {
+            pub fn from_url(
+                url: &MxcUri,
+                method: Method,
+                width: UInt,
+                height: UInt,
+            ) -> Result<Self, IdParseError> {
+                let (server_name, media_id) = url.parts()?;
+                Ok(Self {
+                    server_name: server_name.to_owned(),
+                    media_id: media_id.to_owned(),
+                    method: Some(method),
+                    width,
+                    height,
+                })
+            }
+
+            pub fn into_unstable(self) -> unstable::Request {
+                let Self { server_name, media_id, method, width, height } = self;
+                unstable::Request { server_name, media_id, method, width, height }
+            }
+        }
+
+        pub mod unstable {
+            use ruma::{
+                api::{client::media::get_content_thumbnail::v3::Method, request, Metadata},
+                metadata, OwnedServerName, UInt,
+            };
+
+            pub use super::Response;
+
+            const METADATA: Metadata = metadata! {
+                method: GET,
+                rate_limited: true,
+                authentication: AccessToken,
+                history: {
+                    unstable => "/_matrix/client/unstable/org.matrix.msc3916/media/thumbnail/:server_name/:media_id",
+                }
+            };
+
+            #[request(error = ruma::api::client::Error)]
+            pub struct Request {
+                #[ruma_api(path)]
+                pub server_name: OwnedServerName,
+                #[ruma_api(path)]
+                pub media_id: String,
+                #[ruma_api(query)]
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub method: Option<Method>,
+                #[ruma_api(query)]
+                pub width: UInt,
+                #[ruma_api(query)]
+                pub height: UInt,
+            }
+        }
+    }
+
+    pub mod get_url_preview {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, MilliSecondsSinceUnixEpoch,
+        };
+
+        use crate::media::UrlPreview;
+
+        const METADATA: Metadata = metadata! {
+            method: GET,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/client/v1/media/preview_url",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            #[ruma_api(query)]
+            pub url: String,
+            #[ruma_api(query)]
+            pub ts: MilliSecondsSinceUnixEpoch,
+        }
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {
+            #[ruma_api(body)]
+            pub preview: UrlPreview,
+        }
+
+        impl Request {
+            pub fn new(url: String, ts: MilliSecondsSinceUnixEpoch) -> Self {
+                Self { url, ts }
+            }
+
+            pub fn into_unstable(self) -> unstable::Request {
+                unstable::Request { url: self.url, ts: self.ts }
+            }
+        }
+
+        pub mod unstable {
+            use ruma::{
+                api::{request, Metadata},
+                metadata, MilliSecondsSinceUnixEpoch,
+            };
+
+            pub use super::Response;
+
+            const METADATA: Metadata = metadata! {
+                method: GET,
+                rate_limited: true,
+                authentication: AccessToken,
+                history: {
+                    unstable => "/_matrix/client/unstable/org.matrix.msc3916/media/preview_url",
+                }
+            };
+
+            #[request(error = ruma::api::client::Error)]
+            pub struct Request {
+                #[ruma_api(query)]
+                pub url: String,
+                #[ruma_api(query)]
+                pub ts: MilliSecondsSinceUnixEpoch,
+            }
+        }
+    }
+}
+
+/// The asynchronous upload endpoints of [MSC2246], that are not available in
+/// Ruma yet.
+///
+/// The stable endpoints are listed under the first version of the spec, so that
+/// only [`AsyncUploadSupport`] decides which ones are used.
+///
+/// [MSC2246]: https://github.com/matrix-org/matrix-spec-proposals/pull/2246
+mod async_upload {
+    pub mod create_mxc_uri {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, MilliSecondsSinceUnixEpoch, OwnedMxcUri,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: POST,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/media/v1/create",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Request {}
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {
+            pub content_uri: OwnedMxcUri,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub unused_expires_at: Option<MilliSecondsSinceUnixEpoch>,
+        }
+
+        impl Request {
+            pub fn into_unstable(self) -> unstable::Request {
+                unstable::Request {}
+            }
+        }
+
+        pub mod unstable {
+            use ruma::{
+                api::{request, Metadata},
+                metadata,
+            };
+
+            pub use super::Response;
+
+            const METADATA: Metadata = metadata! {
+                method: POST,
+                rate_limited: true,
+                authentication: AccessToken,
+                history: {
+                    unstable => "/_matrix/media/unstable/fi.mau.msc2246/create",
+                }
+            };
+
+            #[request(error = ruma::api::client::Error)]
+            pub struct Request {}
+        }
+    }
+
+    pub mod create_content_async {
+        use http::header::CONTENT_TYPE;
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata, IdParseError, MxcUri, OwnedServerName,
+        };
+
+        const METADATA: Metadata = metadata! {
+            method: PUT,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/media/v3/upload/:server_name/:media_id",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            #[ruma_api(path)]
+            pub server_name: OwnedServerName,
+            #[ruma_api(path)]
+            pub media_id: String,
+            #[ruma_api(raw_body)]
+            pub file: Vec<u8>,
+            #[ruma_api(header = CONTENT_TYPE)]
+            pub content_type: Option<String>,
+        }
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {}
+
+        impl Request {
+            pub fn from_url(
+                url: &MxcUri,
+                file: Vec<u8>,
+                content_type: Option<String>,
+            ) -> Result<Self, IdParseError> {
+                let (server_name, media_id) = url.parts()?;
+                Ok(Self {
+                    server_name: server_name.to_owned(),
+                    media_id: media_id.to_owned(),
+                    file,
+                    content_type,
+                })
+            }
+
+            pub fn into_unstable(self) -> unstable::Request {
+                let Self { server_name, media_id, file, content_type } = self;
+                unstable::Request { server_name, media_id, file, content_type }
+            }
+        }
+
+        pub mod unstable {
+            use http::header::CONTENT_TYPE;
+            use ruma::{
+                api::{request, Metadata},
+                metadata, OwnedServerName,
+            };
+
+            pub use super::Response;
+
+            const METADATA: Metadata = metadata! {
+                method: PUT,
+                rate_limited: true,
+                authentication: AccessToken,
+                history: {
+                    unstable => "/_matrix/media/unstable/fi.mau.msc2246/upload/:server_name/:media_id",
+                }
+            };
+
+            #[request(error = ruma::api::client::Error)]
+            pub struct Request {
+                #[ruma_api(path)]
+                pub server_name: OwnedServerName,
+                #[ruma_api(path)]
+                pub media_id: String,
+                #[ruma_api(raw_body)]
+                pub file: Vec<u8>,
+                #[ruma_api(header = CONTENT_TYPE)]
+                pub content_type: Option<String>,
+            }
+        }
+    }
+}
+
 pub(crate) fn update_audio_message_event(
     mut audio_message_event_content: AudioMessageEventContent,
     content_type: &Mime,
@@ -0,0 +1,431 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mentions of users and rooms in messages.
+//!
+//! When composing a message, [`find_mentions`] detects the mentions in the
+//! text written by the user, and [`ComposedMessage::new`] builds the
+//! `m.mentions` block and the HTML pills from them. When rendering a message,
+//! [`parse_pills`] finds the pills in its formatted body.
+
+use std::ops::Range;
+
+use ruma::{
+    assign,
+    events::{room::message::RoomMessageEventContent, Mentions},
+    OwnedRoomOrAliasId, OwnedUserId, RoomAliasId, UserId,
+};
+
+use crate::{
+    html::{escape_attribute, plain_text_to_html, unescape},
+    room::RoomMember,
+    uri::UriTarget,
+};
+
+/// The mention of the whole room.
+const AT_ROOM: &str = "@room";
+
+/// A user that can be mentioned in a message, usually a member of the room.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MentionCandidate {
+    /// The ID of the user.
+    pub user_id: OwnedUserId,
+    /// The display name of the user in the room, if any.
+    pub display_name: Option<String>,
+}
+
+impl From<&RoomMember> for MentionCandidate {
+    fn from(member: &RoomMember) -> Self {
+        Self {
+            user_id: member.user_id().to_owned(),
+            display_name: member.display_name().map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// What is mentioned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MentionTarget {
+    /// A user.
+    User(OwnedUserId),
+    /// A room, by ID or alias.
+    Room(OwnedRoomOrAliasId),
+    /// The whole room the message is sent to, with `@room`.
+    AtRoom,
+}
+
+/// A mention found in a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MentionSpan {
+    /// The byte range of the mention in the text it was found in.
+    pub range: Range<usize>,
+    /// What is mentioned.
+    pub target: MentionTarget,
+    /// The text of the mention to display, e.g. the display name of a user.
+    pub text: String,
+}
+
+/// Find the mentions in the given plain text.
+///
+/// A user is mentioned with `@` followed by their user ID or their display
+/// name, a room with its alias, and the whole room with `@room`. Only the
+/// given users can be mentioned, and the longest display name wins when
+/// several of them match.
+pub fn find_mentions(text: &str, candidates: &[MentionCandidate]) -> Vec<MentionSpan> {
+    let mut candidates_by_name: Vec<_> = candidates
+        .iter()
+        .filter_map(|candidate| Some((candidate.display_name.as_deref()?, candidate)))
+        .filter(|(name, _)| !name.is_empty())
+        .collect();
+    candidates_by_name.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    let mut mentions = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = text[pos..].find(['@', '#']) {
+        let start = pos + offset;
+        let is_at_word_start = text[..start].chars().next_back().map_or(true, |c| !is_word_char(c));
+
+        let mention = is_at_word_start
+            .then(|| find_mention_at(text, start, candidates, &candidates_by_name))
+            .flatten();
+
+        match mention {
+            Some(mention) => {
+                pos = mention.range.end;
+                mentions.push(mention);
+            }
+            None => pos = start + 1,
+        }
+    }
+
+    mentions
+}
+
+/// Find the mention starting with the `@` or `#` at the given position.
+fn find_mention_at(
+    text: &str,
+    start: usize,
+    candidates: &[MentionCandidate],
+    candidates_by_name: &[(&str, &MentionCandidate)],
+) -> Option<MentionSpan> {
+    let rest = &text[start..];
+    let token = trim_trailing_punctuation(rest.split(char::is_whitespace).next()?);
+
+    if rest.starts_with('#') {
+        let alias = <&RoomAliasId>::try_from(token).ok()?;
+        return Some(MentionSpan {
+            range: start..start + token.len(),
+            target: MentionTarget::Room(alias.to_owned().into()),
+            text: token.to_owned(),
+        });
+    }
+
+    if token == AT_ROOM {
+        return Some(MentionSpan {
+            range: start..start + AT_ROOM.len(),
+            target: MentionTarget::AtRoom,
+            text: AT_ROOM.to_owned(),
+        });
+    }
+
+    if let Ok(user_id) = <&UserId>::try_from(token) {
+        if let Some(candidate) = candidates.iter().find(|c| c.user_id == user_id) {
+            return Some(MentionSpan {
+                range: start..start + token.len(),
+                target: MentionTarget::User(candidate.user_id.clone()),
+                text: candidate.display_name.clone().unwrap_or_else(|| user_id.to_string()),
+            });
+        }
+    }
+
+    let after_at = &rest[1..];
+    candidates_by_name.iter().find_map(|(name, candidate)| {
+        let prefix = after_at.get(..name.len())?;
+        let is_at_word_end =
+            after_at[name.len()..].chars().next().map_or(true, |c| !is_word_char(c));
+
+        (prefix.eq_ignore_ascii_case(name) && is_at_word_end).then(|| MentionSpan {
+            range: start..start + 1 + name.len(),
+            target: MentionTarget::User(candidate.user_id.clone()),
+            text: (*name).to_owned(),
+        })
+    })
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Remove the punctuation that can follow a mention in a sentence.
+fn trim_trailing_punctuation(token: &str) -> &str {
+    token.trim_end_matches(['.', ',', '!', '?', ';', ':', ')', '"', '\''])
+}
+
+/// A message composed with mentions, ready to be sent.
+#[derive(Clone, Debug)]
+pub struct ComposedMessage {
+    /// The plain text body of the message.
+    pub body: String,
+    /// The HTML body of the message, where the mentions of users and rooms
+    /// are replaced by pills.
+    pub formatted_body: String,
+    /// The mentions of the message, for the `m.mentions` block.
+    pub mentions: Mentions,
+}
+
+impl ComposedMessage {
+    /// Compose a message from the text written by the user.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The plain text written by the user.
+    ///
+    /// * `candidates` - The users that can be mentioned, usually the members of
+    ///   the room.
+    pub fn new(text: &str, candidates: &[MentionCandidate]) -> Self {
+        let spans = find_mentions(text, candidates);
+
+        let mut formatted_body = String::with_capacity(text.len());
+        let mut mentions = Mentions::new();
+        let mut pos = 0;
+
+        for span in spans {
+            formatted_body.push_str(&plain_text_to_html(&text[pos..span.range.start]));
+
+            match &span.target {
+                MentionTarget::User(user_id) => {
+                    mentions.user_ids.insert(user_id.clone());
+                    push_pill(&mut formatted_body, &UriTarget::user(user_id.clone()), &span.text);
+                }
+                MentionTarget::Room(room) => {
+                    let target = UriTarget::Room { room: room.clone(), via: Vec::new() };
+                    push_pill(&mut formatted_body, &target, &span.text);
+                }
+                MentionTarget::AtRoom => {
+                    mentions.room = true;
+                    formatted_body.push_str(AT_ROOM);
+                }
+            }
+
+            pos = span.range.end;
+        }
+
+        formatted_body.push_str(&plain_text_to_html(&text[pos..]));
+
+        Self { body: text.to_owned(), formatted_body, mentions }
+    }
+
+    /// Convert this message into the content of an `m.room.message` event.
+    pub fn into_content(self) -> RoomMessageEventContent {
+        assign!(RoomMessageEventContent::text_html(self.body, self.formatted_body), {
+            mentions: Some(self.mentions),
+        })
+    }
+}
+
+fn push_pill(html: &mut String, target: &UriTarget, text: &str) {
+    html.push_str(&format!(
+        "<a href=\"{}\">{}</a>",
+        escape_attribute(&target.matrix_to_uri().to_string()),
+        plain_text_to_html(text)
+    ));
+}
+
+/// Find the pills in the given HTML formatted body.
+///
+/// A pill is a link to a `matrix.to` or `matrix:` URI of a user or a room.
+/// The range of each span covers the whole `<a>` element.
+pub fn parse_pills(html: &str) -> Vec<MentionSpan> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut pills = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = lowercase[pos..].find("<a") {
+        let start = pos + offset;
+        pos = start + 2;
+
+        // Make sure that this is an `<a>` tag and not e.g. an `<abbr>` tag.
+        if !lowercase[pos..].starts_with(|c: char| c.is_ascii_whitespace() || c == '>') {
+            continue;
+        }
+
+        let Some(tag_end) = lowercase[pos..].find('>').map(|i| pos + i + 1) else {
+            break;
+        };
+        let Some(close_start) = lowercase[tag_end..].find("</a>").map(|i| tag_end + i) else {
+            break;
+        };
+        let end = close_start + "</a>".len();
+        pos = end;
+
+        let Some(href) = attribute(&html[start..tag_end], "href") else {
+            continue;
+        };
+        let target = match UriTarget::parse(&unescape(href)) {
+            Some(UriTarget::User { user_id }) => MentionTarget::User(user_id),
+            Some(UriTarget::Room { room, .. }) => MentionTarget::Room(room),
+            _ => continue,
+        };
+
+        pills.push(MentionSpan {
+            range: start..end,
+            target,
+            text: unescape(&strip_tags(&html[tag_end..close_start])),
+        });
+    }
+
+    pills
+}
+
+/// Get the value of the attribute with the given name in an HTML start tag.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some(offset) = lowercase[pos..].find(name) {
+        let start = pos + offset;
+        pos = start + name.len();
+
+        let is_at_name_start = lowercase[..start].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = tag[pos..].trim_start();
+        if !is_at_name_start || !rest.starts_with('=') {
+            continue;
+        }
+
+        let value = rest[1..].trim_start();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next(),
+            _ => value.split(|c: char| c.is_ascii_whitespace() || c == '>').next(),
+        };
+    }
+
+    None
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_alias_id, owned_user_id};
+
+    use super::{
+        find_mentions, parse_pills, ComposedMessage, MentionCandidate, MentionSpan, MentionTarget,
+    };
+
+    fn candidates() -> Vec<MentionCandidate> {
+        vec![
+            MentionCandidate {
+                user_id: owned_user_id!("@alice:localhost"),
+                display_name: Some("Alice".to_owned()),
+            },
+            MentionCandidate {
+                user_id: owned_user_id!("@alice2:localhost"),
+                display_name: Some("Alice Smith".to_owned()),
+            },
+            MentionCandidate { user_id: owned_user_id!("@bob:localhost"), display_name: None },
+        ]
+    }
+
+    #[test]
+    fn test_find_mentions() {
+        let text = "Hi @Alice Smith, @alice and @bob:localhost! See #rust:localhost, @room.";
+        let mentions = find_mentions(text, &candidates());
+
+        assert_eq!(
+            mentions,
+            [
+                MentionSpan {
+                    range: 3..15,
+                    target: MentionTarget::User(owned_user_id!("@alice2:localhost")),
+                    text: "Alice Smith".to_owned(),
+                },
+                MentionSpan {
+                    range: 17..23,
+                    target: MentionTarget::User(owned_user_id!("@alice:localhost")),
+                    text: "Alice".to_owned(),
+                },
+                MentionSpan {
+                    range: 28..42,
+                    target: MentionTarget::User(owned_user_id!("@bob:localhost")),
+                    text: "@bob:localhost".to_owned(),
+                },
+                MentionSpan {
+                    range: 48..63,
+                    target: MentionTarget::Room(owned_room_alias_id!("#rust:localhost").into()),
+                    text: "#rust:localhost".to_owned(),
+                },
+                MentionSpan {
+                    range: 65..70,
+                    target: MentionTarget::AtRoom,
+                    text: "@room".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_mentions_ignores_unknown_users_and_words() {
+        let mentions = find_mentions("mail@alice.org, @carol:localhost, @Alicent", &candidates());
+        assert!(mentions.is_empty());
+    }
+
+    #[test]
+    fn test_composed_message() {
+        let message = ComposedMessage::new("<3 @Alice, @room", &candidates());
+
+        assert_eq!(message.body, "<3 @Alice, @room");
+        assert_eq!(
+            message.formatted_body,
+            "&lt;3 <a href=\"https://matrix.to/#/@alice:localhost\">Alice</a>, @room"
+        );
+        assert!(message.mentions.user_ids.contains(&owned_user_id!("@alice:localhost")));
+        assert!(message.mentions.room);
+    }
+
+    #[test]
+    fn test_parse_pills() {
+        let html = "Hi <a href=\"https://matrix.to/#/@alice:localhost\"><b>Alice</b></a>, \
+                    <abbr>see</abbr> <A HREF='matrix:r/rust:localhost'>#rust</A> \
+                    <a href=\"https://example.org\">not a pill</a>";
+        let pills = parse_pills(html);
+
+        assert_eq!(pills.len(), 2);
+        assert_eq!(pills[0].target, MentionTarget::User(owned_user_id!("@alice:localhost")));
+        assert_eq!(pills[0].text, "Alice");
+        assert_eq!(
+            &html[pills[0].range.clone()],
+            "<a href=\"https://matrix.to/#/@alice:localhost\"><b>Alice</b></a>"
+        );
+        assert_eq!(
+            pills[1].target,
+            MentionTarget::Room(owned_room_alias_id!("#rust:localhost").into())
+        );
+        assert_eq!(pills[1].text, "#rust");
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Search of messages with the server-side search API, optionally combined
+//! with a local index of the messages of encrypted rooms.
+//!
+//! The homeserver can't read the messages of encrypted rooms, so they are
+//! never part of the results of [`Client::search_messages()`]. The rooms that
+//! were skipped for this reason are listed in
+//! [`MessageSearchResponse::unsearched_encrypted_rooms`], and clients that keep
+//! an index of the decrypted messages can use
+//! [`Client::search_messages_with_local_index()`] to search both.
+
+use std::ops::Range;
+
+use async_trait::async_trait;
+use matrix_sdk_common::AsyncTraitDeps;
+use ruma::{
+    api::client::{
+        filter::RoomEventFilter,
+        search::search_events::v3::{
+            Categories, Criteria, OrderBy, Request as SearchRequest, ResultRoomEvents,
+        },
+    },
+    assign,
+    events::AnyTimelineEvent,
+    serde::Raw,
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId,
+};
+use serde::Deserialize;
+
+use crate::{BaseRoom, Client, Result};
+
+/// The maximum length of a snippet, in characters.
+const MAX_SNIPPET_LEN: usize = 200;
+
+/// The number of characters kept before the first highlight in a snippet that
+/// had to be shortened.
+const SNIPPET_CONTEXT_LEN: usize = 60;
+
+/// The order of the results of a search.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchOrder {
+    /// The results are ordered by relevance, as computed by the server.
+    #[default]
+    Rank,
+
+    /// The most recent results are returned first.
+    Recent,
+}
+
+impl From<SearchOrder> for OrderBy {
+    fn from(value: SearchOrder) -> Self {
+        match value {
+            SearchOrder::Rank => OrderBy::Rank,
+            SearchOrder::Recent => OrderBy::Recent,
+        }
+    }
+}
+
+/// Options for a search of messages.
+#[derive(Clone, Debug, Default)]
+pub struct MessageSearchOptions {
+    /// The order of the results.
+    pub order: SearchOrder,
+
+    /// A filter to apply to the events.
+    ///
+    /// Its `rooms` field is overridden by the rooms given to the search.
+    pub filter: RoomEventFilter,
+
+    /// The token to get the next page of results, from
+    /// [`MessageSearchResponse::next_batch`].
+    pub from: Option<String>,
+}
+
+/// A part of the body of a message matching a search, with the positions of
+/// the matches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchSnippet {
+    /// The text of the snippet.
+    ///
+    /// It is shortened with ellipses around the first match if the body of
+    /// the message is long.
+    pub text: String,
+
+    /// The byte ranges of `text` matching the search terms, in order.
+    pub highlights: Vec<Range<usize>>,
+}
+
+impl SearchSnippet {
+    /// Build the snippet of the given body, highlighting the given terms.
+    ///
+    /// The terms are matched case-insensitively.
+    pub fn new(body: &str, terms: &[String]) -> Self {
+        let ranges = find_terms(body, terms);
+
+        if body.chars().count() <= MAX_SNIPPET_LEN {
+            return Self { text: body.to_owned(), highlights: ranges };
+        }
+
+        let first_match = ranges.first().map_or(0, |range| range.start);
+        let start = body[..first_match]
+            .char_indices()
+            .rev()
+            .nth(SNIPPET_CONTEXT_LEN - 1)
+            .map_or(0, |(index, _)| index);
+        let end = body[start..]
+            .char_indices()
+            .nth(MAX_SNIPPET_LEN)
+            .map_or(body.len(), |(index, _)| start + index);
+
+        let mut text = String::new();
+        if start > 0 {
+            text.push('…');
+        }
+        let offset = text.len();
+        text.push_str(&body[start..end]);
+        if end < body.len() {
+            text.push('…');
+        }
+
+        let highlights = ranges
+            .into_iter()
+            .filter(|range| range.start >= start && range.end <= end)
+            .map(|range| range.start - start + offset..range.end - start + offset)
+            .collect();
+
+        Self { text, highlights }
+    }
+}
+
+/// Find the byte ranges of the given terms in the given text.
+///
+/// Overlapping matches are merged.
+fn find_terms(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+
+    for term in terms.iter().map(|term| term.trim()).filter(|term| !term.is_empty()) {
+        for (start, _) in text.char_indices() {
+            let end = start + term.len();
+            if text.get(start..end).is_some_and(|part| part.eq_ignore_ascii_case(term)) {
+                ranges.push(start..end);
+            }
+        }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// A message matching a search.
+#[derive(Clone, Debug)]
+pub struct MessageSearchResult {
+    /// The room of the message.
+    pub room_id: OwnedRoomId,
+
+    /// The ID of the message.
+    pub event_id: OwnedEventId,
+
+    /// The sender of the message.
+    pub sender: OwnedUserId,
+
+    /// When the message was sent.
+    pub origin_server_ts: MilliSecondsSinceUnixEpoch,
+
+    /// The rank of the message computed by the server, if any.
+    ///
+    /// A higher rank means a better match.
+    pub rank: Option<f64>,
+
+    /// The part of the body of the message matching the search, if it has a
+    /// body.
+    pub snippet: Option<SearchSnippet>,
+
+    /// The message.
+    pub event: Raw<AnyTimelineEvent>,
+}
+
+impl MessageSearchResult {
+    /// Build a search result from the given event.
+    ///
+    /// Returns `None` if the event is missing the fields needed to identify
+    /// it.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The message matching the search.
+    ///
+    /// * `rank` - The rank of the message, if it is known.
+    ///
+    /// * `highlights` - The terms to highlight in the snippet.
+    pub fn from_event(
+        event: Raw<AnyTimelineEvent>,
+        rank: Option<f64>,
+        highlights: &[String],
+    ) -> Option<Self> {
+        let parts = event.deserialize_as::<SearchResultParts>().ok()?;
+        let snippet = parts.content.body.map(|body| SearchSnippet::new(&body, highlights));
+
+        Some(Self {
+            room_id: parts.room_id,
+            event_id: parts.event_id,
+            sender: parts.sender,
+            origin_server_ts: parts.origin_server_ts,
+            rank,
+            snippet,
+            event,
+        })
+    }
+}
+
+/// The parts of an event that are needed to build a [`MessageSearchResult`].
+#[derive(Deserialize)]
+struct SearchResultParts {
+    room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+    sender: OwnedUserId,
+    origin_server_ts: MilliSecondsSinceUnixEpoch,
+    #[serde(default)]
+    content: SearchResultContentParts,
+}
+
+#[derive(Default, Deserialize)]
+struct SearchResultContentParts {
+    body: Option<String>,
+}
+
+/// A page of results of a search of messages.
+#[derive(Clone, Debug, Default)]
+pub struct MessageSearchResponse {
+    /// The messages matching the search, in the requested order.
+    pub results: Vec<MessageSearchResult>,
+
+    /// An approximation of the total number of results on the server, if it
+    /// is known.
+    pub count: Option<u64>,
+
+    /// The terms that should be highlighted in the results, as returned by
+    /// the server.
+    pub highlights: Vec<String>,
+
+    /// The token to get the next page of results, if there is one.
+    pub next_batch: Option<String>,
+
+    /// The encrypted rooms that were part of the search, whose messages can't
+    /// be searched by the server.
+    pub unsearched_encrypted_rooms: Vec<OwnedRoomId>,
+}
+
+/// A local index of the decrypted messages of encrypted rooms, used by
+/// [`Client::search_messages_with_local_index()`].
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait LocalMessageIndex: AsyncTraitDeps {
+    /// Search the messages of the given rooms.
+    ///
+    /// The results can be built with [`MessageSearchResult::from_event()`].
+    async fn search(&self, query: &str, rooms: &[OwnedRoomId]) -> Vec<MessageSearchResult>;
+}
+
+impl Client {
+    /// Search messages with the server-side search API.
+    ///
+    /// The messages of encrypted rooms can't be searched by the server, the
+    /// encrypted rooms that would have been part of the search are listed in
+    /// the response.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The terms to search.
+    ///
+    /// * `rooms` - The rooms to search, or `None` to search all the joined
+    ///   rooms.
+    ///
+    /// * `options` - The order, filter and pagination token of the search.
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        rooms: Option<Vec<OwnedRoomId>>,
+        options: MessageSearchOptions,
+    ) -> Result<MessageSearchResponse> {
+        let unsearched_encrypted_rooms = self.encrypted_rooms(rooms.as_deref());
+
+        let mut filter = options.filter;
+        filter.rooms = rooms;
+        let criteria = assign!(Criteria::new(query.to_owned()), {
+            filter,
+            order_by: Some(options.order.into()),
+        });
+        let request = assign!(SearchRequest::new(assign!(Categories::new(), { room_events: Some(criteria) })), {
+            next_batch: options.from,
+        });
+
+        let response = self.send(request, None).await?;
+        let ResultRoomEvents { count, next_batch, results, highlights, .. } =
+            response.search_categories.room_events;
+
+        let results = results
+            .into_iter()
+            .filter_map(|result| {
+                MessageSearchResult::from_event(result.result?, result.rank, &highlights)
+            })
+            .collect();
+
+        Ok(MessageSearchResponse {
+            results,
+            count: count.map(Into::into),
+            highlights,
+            next_batch,
+            unsearched_encrypted_rooms,
+        })
+    }
+
+    /// Search messages with the server-side search API, and the messages of
+    /// encrypted rooms with the given local index.
+    ///
+    /// The local index is only searched for the first page, when
+    /// `options.from` is `None`. Its results are merged with the ones of the
+    /// server according to the requested order, the ones without a rank
+    /// being last when ordering by rank.
+    ///
+    /// The arguments are the same as [`Client::search_messages()`].
+    pub async fn search_messages_with_local_index(
+        &self,
+        query: &str,
+        rooms: Option<Vec<OwnedRoomId>>,
+        options: MessageSearchOptions,
+        index: &dyn LocalMessageIndex,
+    ) -> Result<MessageSearchResponse> {
+        let is_first_page = options.from.is_none();
+        let order = options.order;
+        let mut response = self.search_messages(query, rooms, options).await?;
+
+        if !is_first_page || response.unsearched_encrypted_rooms.is_empty() {
+            return Ok(response);
+        }
+
+        let local_results = index.search(query, &response.unsearched_encrypted_rooms).await;
+        for result in local_results {
+            if !response.results.iter().any(|r| r.event_id == result.event_id) {
+                response.results.push(result);
+            }
+        }
+
+        match order {
+            SearchOrder::Rank => response.results.sort_by(|a, b| {
+                // Results without a rank go last, and the sort is stable so the
+                // order of the server is kept.
+                b.rank.unwrap_or(f64::NEG_INFINITY).total_cmp(&a.rank.unwrap_or(f64::NEG_INFINITY))
+            }),
+            SearchOrder::Recent => {
+                response.results.sort_by(|a, b| b.origin_server_ts.cmp(&a.origin_server_ts))
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// The encrypted rooms among the given ones, or among the joined rooms if
+    /// `rooms` is `None`.
+    fn encrypted_rooms(&self, rooms: Option<&[OwnedRoomId]>) -> Vec<OwnedRoomId> {
+        match rooms {
+            Some(rooms) => rooms
+                .iter()
+                .filter(|room_id| {
+                    self.base_client().get_room(room_id).is_some_and(|room| room.is_encrypted())
+                })
+                .cloned()
+                .collect(),
+            // Use the encryption state that is known locally, to avoid a request
+            // per room.
+            None => self
+                .joined_rooms()
+                .into_iter()
+                .filter(|room| BaseRoom::is_encrypted(room))
+                .map(|room| room.room_id().to_owned())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_terms, SearchSnippet};
+
+    #[test]
+    fn test_find_terms() {
+        let terms = ["rust".to_owned(), "Rust SDK".to_owned(), " ".to_owned()];
+        assert_eq!(find_terms("The RUST SDK is rusty", &terms), [4..12, 16..20]);
+        assert!(find_terms("Nothing here", &terms).is_empty());
+    }
+
+    #[test]
+    fn test_short_snippet() {
+        let snippet = SearchSnippet::new("Hello wörld, hello!", &["hello".to_owned()]);
+        assert_eq!(snippet.text, "Hello wörld, hello!");
+        assert_eq!(snippet.highlights, [0..5, 14..19]);
+    }
+
+    #[test]
+    fn test_long_snippet() {
+        let body = format!("{} needle {}", "a".repeat(300), "b".repeat(300));
+        let snippet = SearchSnippet::new(&body, &["needle".to_owned()]);
+
+        assert!(snippet.text.starts_with('…'));
+        assert!(snippet.text.ends_with('…'));
+        assert_eq!(snippet.highlights.len(), 1);
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "needle");
+    }
+}
@@ -6,7 +6,7 @@ use ruma::{
         Action, NewConditionalPushRule, NewPatternedPushRule, NewPushRule, NewSimplePushRule,
         PushCondition, RuleKind, Tweak,
     },
-    OwnedRoomId,
+    OwnedRoomId, OwnedUserId,
 };
 
 use crate::NotificationSettingsError;
@@ -20,6 +20,21 @@ pub(crate) enum Command {
     SetOverridePushRule { scope: RuleScope, rule_id: String, room_id: OwnedRoomId, notify: bool },
     /// Set a new push rule for a keyword.
     SetKeywordPushRule { scope: RuleScope, keyword: String },
+    /// Set a new `Override` push rule muting the events sent by a user, or
+    /// only the ones mentioning `mentioned_user_id` if it is set.
+    SetSenderMutePushRule {
+        scope: RuleScope,
+        rule_id: String,
+        user_id: OwnedUserId,
+        mentioned_user_id: Option<OwnedUserId>,
+    },
+    /// Set a new push rule built with a `PushRuleBuilder`
+    SetCustomPushRule {
+        scope: RuleScope,
+        rule: NewPushRule,
+        before: Option<String>,
+        after: Option<String>,
+    },
     /// Set whether a push rule is enabled
     SetPushRuleEnabled { scope: RuleScope, kind: RuleKind, rule_id: String, enabled: bool },
     /// Delete a push rule
@@ -69,6 +84,26 @@ impl Command {
                 Ok(NewPushRule::Content(new_rule))
             }
 
+            Self::SetSenderMutePushRule { scope: _, rule_id, user_id, mentioned_user_id } => {
+                // `Override` push rule matching the events sent by this `user_id`
+                let mut conditions = vec![PushCondition::EventMatch {
+                    key: "sender".to_owned(),
+                    pattern: user_id.to_string(),
+                }];
+
+                if let Some(mentioned_user_id) = mentioned_user_id {
+                    conditions.push(PushCondition::EventPropertyContains {
+                        key: r"content.m\.mentions.user_ids".to_owned(),
+                        value: mentioned_user_id.as_str().into(),
+                    });
+                }
+
+                let new_rule = NewConditionalPushRule::new(rule_id.clone(), conditions, vec![]);
+                Ok(NewPushRule::Override(new_rule))
+            }
+
+            Self::SetCustomPushRule { scope: _, rule, before: _, after: _ } => Ok(rule.clone()),
+
             Self::SetPushRuleEnabled { .. }
             | Self::DeletePushRule { .. }
             | Self::SetPushRuleActions { .. } => Err(NotificationSettingsError::InvalidParameter(
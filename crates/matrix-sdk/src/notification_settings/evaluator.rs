@@ -0,0 +1,223 @@
+//! Local evaluation of push rules
+
+use ruma::{
+    events::room::power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+    push::{Action, PushConditionRoomCtx, Ruleset},
+    serde::Raw,
+    UInt,
+};
+
+use crate::{Client, Result, Room};
+
+/// Evaluates the push rules of the current user against events, without
+/// involving the homeserver.
+///
+/// The homeserver can't look at the content of encrypted events, so it
+/// notifies for them according to the generic rules for encrypted events.
+/// Once such an event has been decrypted, this evaluator can decide whether it
+/// should really notify, by applying all the rules to its content: the
+/// `event_match`, `contains_display_name`, `room_member_count` and
+/// `sender_notification_permission` conditions, as well as the intentional
+/// mentions conditions.
+///
+/// Contrary to [`Room::event_push_actions`], the evaluation doesn't give up
+/// when the room state is incomplete, which often happens in a notification
+/// process: the missing values fall back to the ones the specification defines
+/// when the corresponding state events are absent.
+#[derive(Clone, Debug)]
+pub struct PushRuleEvaluator {
+    ruleset: Ruleset,
+}
+
+impl PushRuleEvaluator {
+    /// Create an evaluator for the given push rules.
+    pub fn new(ruleset: Ruleset) -> Self {
+        Self { ruleset }
+    }
+
+    /// Create an evaluator for the push rules of the current user, as they are
+    /// currently stored in the account data.
+    pub async fn from_account(client: &Client) -> Result<Self> {
+        Ok(Self::new(client.account().push_rules().await?))
+    }
+
+    /// Get the actions the push rules trigger for the event, in the given
+    /// context.
+    pub fn actions<T>(&self, event: &Raw<T>, context: &PushConditionRoomCtx) -> Vec<Action> {
+        self.ruleset.get_actions(event, context).to_owned()
+    }
+
+    /// Get the actions the push rules trigger for the event, with the current
+    /// state of the room it was sent in.
+    pub async fn room_actions<T>(&self, room: &Room, event: &Raw<T>) -> Result<Vec<Action>> {
+        let context = Self::room_context(room).await?;
+        Ok(self.actions(event, &context))
+    }
+
+    /// Whether the push rules trigger a notification for the event, with the
+    /// current state of the room it was sent in.
+    pub async fn should_notify<T>(&self, room: &Room, event: &Raw<T>) -> Result<bool> {
+        Ok(self.room_actions(room, event).await?.iter().any(Action::should_notify))
+    }
+
+    /// Build the context of the room needed to evaluate push rules.
+    ///
+    /// If the member event of the current user is unknown, the display name
+    /// falls back to the localpart of the user ID. If the power levels are
+    /// unknown, the defaults of the specification are used, with the creator
+    /// of the room as its only privileged member.
+    pub async fn room_context(room: &Room) -> Result<PushConditionRoomCtx> {
+        let user_id = room.own_user_id();
+
+        let user_display_name = match room.get_member_no_sync(user_id).await? {
+            Some(member) => member.name().to_owned(),
+            None => user_id.localpart().to_owned(),
+        };
+
+        let power_levels = match room
+            .get_state_event_static::<RoomPowerLevelsEventContent>()
+            .await?
+            .and_then(|event| event.deserialize().ok())
+        {
+            Some(event) => event.power_levels(),
+            None => {
+                let mut power_levels = RoomPowerLevels::from(RoomPowerLevelsEventContent::new());
+
+                if let Some(creator) = room.create_content().map(|content| content.creator) {
+                    power_levels.users.insert(creator, 100.into());
+                }
+
+                power_levels
+            }
+        };
+
+        Ok(PushConditionRoomCtx {
+            room_id: room.room_id().to_owned(),
+            member_count: UInt::new(room.active_members_count()).unwrap_or(UInt::MAX),
+            user_id: user_id.to_owned(),
+            user_display_name,
+            users_power_levels: power_levels.users,
+            default_power_level: power_levels.users_default,
+            notification_power_levels: power_levels.notifications,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::{
+        async_test, notification_settings::get_server_default_ruleset, test_json,
+        JoinedRoomBuilder, StateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+    };
+    use ruma::{
+        int,
+        push::{NotificationPowerLevels, PushConditionRoomCtx},
+        room_id,
+        serde::Raw,
+        user_id,
+    };
+    use serde_json::{json, Value as JsonValue};
+
+    use super::PushRuleEvaluator;
+    use crate::test_utils::logged_in_client;
+
+    fn context(member_count: u32) -> PushConditionRoomCtx {
+        PushConditionRoomCtx {
+            room_id: room_id!("!room:matrix.org").to_owned(),
+            member_count: member_count.into(),
+            user_id: user_id!("@user:matrix.org").to_owned(),
+            user_display_name: "User".to_owned(),
+            users_power_levels: Default::default(),
+            default_power_level: int!(0),
+            notification_power_levels: NotificationPowerLevels { room: int!(50) },
+        }
+    }
+
+    fn message(content: JsonValue) -> Raw<()> {
+        Raw::new(&json!({
+            "type": "m.room.message",
+            "event_id": "$event:matrix.org",
+            "room_id": "!room:matrix.org",
+            "sender": "@bob:matrix.org",
+            "origin_server_ts": 0,
+            "content": content,
+        }))
+        .unwrap()
+        .cast()
+    }
+
+    #[test]
+    fn test_actions() {
+        let evaluator = PushRuleEvaluator::new(get_server_default_ruleset());
+        let hello = message(json!({ "msgtype": "m.text", "body": "Hello" }));
+
+        // A regular message in a group notifies without sound nor highlight.
+        let actions = evaluator.actions(&hello, &context(3));
+        assert!(actions.iter().any(|a| a.should_notify()));
+        assert!(!actions.iter().any(|a| a.is_highlight()));
+        assert!(!actions.iter().any(|a| a.sound().is_some()));
+
+        // A message in a one-to-one room notifies with a sound.
+        let actions = evaluator.actions(&hello, &context(2));
+        assert!(actions.iter().any(|a| a.sound().is_some()));
+
+        // A message mentioning the user highlights.
+        let mention = message(json!({
+            "msgtype": "m.text",
+            "body": "Hello user",
+            "m.mentions": { "user_ids": ["@user:matrix.org"] },
+        }));
+        let actions = evaluator.actions(&mention, &context(3));
+        assert!(actions.iter().any(|a| a.is_highlight()));
+    }
+
+    #[async_test]
+    async fn test_room_context_fallbacks() {
+        let client = logged_in_client(None).await;
+
+        // Only the create event of the room is known.
+        let response = SyncResponseBuilder::default()
+            .add_joined_room(JoinedRoomBuilder::default().add_state_event(StateTestEvent::Create))
+            .build_sync_response();
+        client.base_client().receive_sync_response(response).await.unwrap();
+        let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+        let context = PushRuleEvaluator::room_context(&room).await.unwrap();
+
+        // The display name falls back to the localpart of the user.
+        assert_eq!(context.user_display_name, "example");
+        // The default power levels are used, with the creator at 100.
+        assert_eq!(context.users_power_levels.len(), 1);
+        assert_eq!(context.users_power_levels[user_id!("@example:localhost")], int!(100));
+        assert_eq!(context.default_power_level, int!(0));
+        assert_eq!(context.notification_power_levels.room, int!(50));
+    }
+
+    #[async_test]
+    async fn test_room_context_from_state() {
+        let client = logged_in_client(None).await;
+
+        let mut member = test_json::sync_events::MEMBER.to_owned();
+        member["content"]["displayname"] = "Example User".into();
+        let mut power_levels = test_json::sync_events::POWER_LEVELS.to_owned();
+        power_levels["content"]["users_default"] = 10.into();
+
+        let response = SyncResponseBuilder::default()
+            .add_joined_room(
+                JoinedRoomBuilder::default()
+                    .add_state_event(StateTestEvent::Create)
+                    .add_state_event(StateTestEvent::Custom(member))
+                    .add_state_event(StateTestEvent::Custom(power_levels)),
+            )
+            .build_sync_response();
+        client.base_client().receive_sync_response(response).await.unwrap();
+        let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+        let context = PushRuleEvaluator::room_context(&room).await.unwrap();
+
+        assert_eq!(context.user_display_name, "Example User");
+        assert_eq!(context.users_power_levels[user_id!("@example:localhost")], int!(100));
+        assert_eq!(context.users_power_levels[user_id!("@bob:localhost")], int!(0));
+        assert_eq!(context.default_power_level, int!(10));
+    }
+}
@@ -0,0 +1,55 @@
+//! Notifications for invites
+
+use ruma::{
+    exports::ruma_macros::EventContent,
+    push::{Action, PushCondition, Tweak},
+    UserId,
+};
+use serde::{Deserialize, Serialize};
+
+use super::PushRuleBuilder;
+
+/// The ID of the user-defined rule notifying for invites, on homeservers whose
+/// default rules don't include `.m.rule.invite_for_me`.
+pub(crate) const INVITE_FOR_ME_RULE_ID: &str = "org.matrix.custom.invite_for_me";
+
+/// Which invites generate notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InviteNotificationPolicy {
+    /// Notify for all the invites.
+    Always,
+    /// Only notify for the invites sent by users sharing a room with the
+    /// current user.
+    KnownUsersOnly,
+    /// Never notify for invites.
+    Never,
+}
+
+/// A custom global account data event storing whether invites only notify when
+/// they are sent by known users, which push rules can't express.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "m.org.matrix.custom.invite_notification_policy", kind = GlobalAccountData)]
+pub(crate) struct InviteNotificationPolicyContent {
+    #[serde(default)]
+    pub known_users_only: bool,
+}
+
+/// Build the rule notifying for the invites of the given user, with the same
+/// conditions and actions as the server-default `.m.rule.invite_for_me` rule.
+pub(crate) fn invite_for_me_rule(user_id: &UserId) -> PushRuleBuilder {
+    PushRuleBuilder::new_override(INVITE_FOR_ME_RULE_ID)
+        .condition(PushCondition::EventMatch {
+            key: "type".to_owned(),
+            pattern: "m.room.member".to_owned(),
+        })
+        .condition(PushCondition::EventMatch {
+            key: "content.membership".to_owned(),
+            pattern: "invite".to_owned(),
+        })
+        .condition(PushCondition::EventMatch {
+            key: "state_key".to_owned(),
+            pattern: user_id.to_string(),
+        })
+        .action(Action::Notify)
+        .action(Action::SetTweak(Tweak::Sound("default".to_owned())))
+}
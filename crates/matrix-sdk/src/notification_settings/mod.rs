@@ -1,35 +1,63 @@
 //! High-level push notification settings API
 
-use std::sync::Arc;
+use std::{collections::HashMap, pin::pin, sync::Arc, time::Duration};
 
+use futures_util::future::{select, Either};
 use indexmap::IndexSet;
+use matrix_sdk_common::time::Clock;
 use ruma::{
     api::client::push::{
         delete_pushrule, set_pushrule, set_pushrule_actions, set_pushrule_enabled,
     },
-    events::push_rules::PushRulesEvent,
-    push::{Action, PredefinedUnderrideRuleId, RuleKind, Ruleset, Tweak},
-    RoomId,
+    events::{
+        push_rules::{PushRulesEvent, PushRulesEventContent},
+        GlobalAccountDataEvent, GlobalAccountDataEventContent, StaticEventContent,
+    },
+    push::{Action, PredefinedOverrideRuleId, PredefinedUnderrideRuleId, RuleKind, Ruleset, Tweak},
+    serde::Raw,
+    MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, RoomId, UserId,
 };
+use serde::{Deserialize, Serialize};
 use tokio::sync::{
-    broadcast::{self, Receiver},
+    broadcast::{self, error::RecvError, Receiver},
     RwLock,
 };
-use tracing::{debug, error};
-
-use self::{command::Command, rule_commands::RuleCommands, rules::Rules};
+use tracing::{debug, error, warn};
+
+pub(crate) use self::rules::Rules;
+use self::{
+    command::Command,
+    invite_policy::{invite_for_me_rule, InviteNotificationPolicyContent, INVITE_FOR_ME_RULE_ID},
+    push_rule_builder::validate_rule_id,
+    rule_commands::RuleCommands,
+    rules::DEFAULT_SOUND,
+    temporary_mutes::{time_until, TemporaryMute, TemporaryMutesContent},
+};
+pub use self::{
+    evaluator::PushRuleEvaluator, invite_policy::InviteNotificationPolicy,
+    push_rule_builder::PushRuleBuilder,
+};
 
 mod command;
+mod evaluator;
+mod invite_policy;
+mod push_rule_builder;
 mod rule_commands;
 mod rules;
+mod temporary_mutes;
+
+/// The delay before trying again to restore the expired temporary mutes, after
+/// a failure.
+const RESTORE_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 use crate::{
     config::RequestConfig, error::NotificationSettingsError, event_handler::EventHandlerDropGuard,
-    Client, Result,
+    BaseRoom, Client, Result, StateChanges,
 };
 
 /// Enum representing the push notification modes for a room.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RoomNotificationMode {
     /// Receive notifications for all messages.
     AllMessages,
@@ -39,6 +67,18 @@ pub enum RoomNotificationMode {
     Mute,
 }
 
+/// The notification settings of a room.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoomNotificationSettings {
+    /// The notification mode of the room.
+    pub mode: RoomNotificationMode,
+    /// Whether the mode is the default one for this type of room.
+    pub is_default: bool,
+    /// The sound played for the notifications of the room, if it is not the
+    /// default one.
+    pub sound: Option<String>,
+}
+
 /// Whether or not a room is encrypted
 #[derive(Debug, Clone, Copy)]
 pub enum IsEncrypted {
@@ -86,6 +126,11 @@ pub struct NotificationSettings {
     rules: Arc<RwLock<Rules>>,
     /// Drop guard of event handler for push rules event.
     _push_rules_event_handler_guard: Arc<EventHandlerDropGuard>,
+    /// The rooms muted temporarily, or `None` if they haven't been loaded from
+    /// the store yet. They will be updated on sync.
+    temporary_mutes: Arc<RwLock<Option<TemporaryMutesContent>>>,
+    /// Drop guard of event handler for the temporary mutes event.
+    _temporary_mutes_event_handler_guard: Arc<EventHandlerDropGuard>,
     changes_sender: broadcast::Sender<()>,
 }
 
@@ -112,7 +157,27 @@ impl NotificationSettings {
         let _push_rules_event_handler_guard =
             client.event_handler_drop_guard(push_rules_event_handler_handle).into();
 
-        Self { client, rules, _push_rules_event_handler_guard, changes_sender }
+        // Listen for changes of the temporary mutes
+        let temporary_mutes = Arc::new(RwLock::new(None));
+        let temporary_mutes_event_handler_handle = client.add_event_handler({
+            let changes_sender = changes_sender.clone();
+            let temporary_mutes = Arc::clone(&temporary_mutes);
+            move |ev: GlobalAccountDataEvent<TemporaryMutesContent>| async move {
+                *temporary_mutes.write().await = Some(ev.content);
+                let _ = changes_sender.send(());
+            }
+        });
+        let _temporary_mutes_event_handler_guard =
+            client.event_handler_drop_guard(temporary_mutes_event_handler_handle).into();
+
+        Self {
+            client,
+            rules,
+            _push_rules_event_handler_guard,
+            temporary_mutes,
+            _temporary_mutes_event_handler_guard,
+            changes_sender,
+        }
     }
 
     /// Subscribe to changes in the `NotificationSettings`.
@@ -145,6 +210,47 @@ impl NotificationSettings {
         self.rules.read().await.get_default_room_notification_mode(is_encrypted, is_one_to_one)
     }
 
+    /// Get the notification settings of many rooms at once.
+    ///
+    /// The ruleset is only locked once, which is cheaper than getting the mode
+    /// of each room separately.
+    ///
+    /// The default mode of a room depends on whether it is encrypted and on
+    /// its number of members, as known locally. Rooms without a user-defined
+    /// mode that are unknown to the client are not included in the result.
+    pub async fn get_room_notification_modes(
+        &self,
+        room_ids: &[OwnedRoomId],
+    ) -> HashMap<OwnedRoomId, RoomNotificationSettings> {
+        let rules = self.rules.read().await;
+
+        room_ids
+            .iter()
+            .filter_map(|room_id| {
+                if let Some(mode) = rules.get_user_defined_room_notification_mode(room_id) {
+                    let sound = rules.get_room_sound(room_id);
+                    return Some((
+                        room_id.clone(),
+                        RoomNotificationSettings { mode, is_default: false, sound },
+                    ));
+                }
+
+                let room = self.client.get_room(room_id)?;
+                // From the point of view of notification settings, a `one-to-one` room is one
+                // that involves exactly two people.
+                let mode = rules.get_default_room_notification_mode(
+                    IsEncrypted::from(BaseRoom::is_encrypted(&room)),
+                    IsOneToOne::from(room.active_members_count() == 2),
+                );
+
+                Some((
+                    room_id.clone(),
+                    RoomNotificationSettings { mode, is_default: true, sound: None },
+                ))
+            })
+            .collect()
+    }
+
     /// Get all room IDs for which a user-defined rule exists.
     pub async fn get_rooms_with_user_defined_rules(&self, enabled: Option<bool>) -> Vec<String> {
         self.rules.read().await.get_rooms_with_user_defined_rules(enabled)
@@ -178,8 +284,7 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
 
         Ok(())
     }
@@ -254,17 +359,97 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
+
+        Ok(())
+    }
+
+    /// Get the sound played for the notifications of a room, if it is not the
+    /// default one.
+    pub async fn get_room_sound(&self, room_id: &RoomId) -> Option<String> {
+        self.rules.read().await.get_room_sound(room_id)
+    }
+
+    /// Set the sound played for the notifications of a room, or restore the
+    /// default sound with `None`.
+    ///
+    /// Only the rooms notifying for all messages play a sound, so if the room
+    /// uses the default mode, its mode is set to
+    /// [`RoomNotificationMode::AllMessages`]. Setting a sound for a room with
+    /// another user-defined mode fails.
+    pub async fn set_room_sound(
+        &self,
+        room_id: &RoomId,
+        sound: Option<String>,
+    ) -> Result<(), NotificationSettingsError> {
+        let rules = self.rules.read().await.clone();
+
+        let actions = |mut actions: Vec<Action>| {
+            actions.retain(|action| action.sound().is_none());
+            let sound = sound.clone().unwrap_or_else(|| DEFAULT_SOUND.to_owned());
+            actions.push(Action::SetTweak(Tweak::Sound(sound)));
+            actions
+        };
+
+        let mut rule_commands = RuleCommands::new(rules.ruleset.clone());
+        match rules.get_user_defined_room_notification_mode(room_id) {
+            Some(RoomNotificationMode::AllMessages) => {
+                if rules.get_room_sound(room_id) == sound {
+                    return Ok(());
+                }
+
+                let current_actions = rules
+                    .ruleset
+                    .room
+                    .iter()
+                    .find(|rule| &*rule.rule_id == room_id)
+                    .map(|rule| rule.actions.clone())
+                    .unwrap_or_default();
+                rule_commands.set_rule_actions(
+                    RuleKind::Room,
+                    room_id.as_str(),
+                    actions(current_actions),
+                )?;
+            }
+            None if sound.is_some() => {
+                rule_commands.insert_rule(RuleKind::Room, room_id, true)?;
+                rule_commands.set_rule_actions(
+                    RuleKind::Room,
+                    room_id.as_str(),
+                    actions(vec![Action::Notify]),
+                )?;
+            }
+            _ if sound.is_none() => return Ok(()),
+            _ => {
+                return Err(NotificationSettingsError::InvalidParameter(
+                    "the room doesn't notify for all messages".to_owned(),
+                ))
+            }
+        }
+
+        self.run_server_commands(&rule_commands).await?;
+
+        self.apply_rule_commands(rule_commands).await;
 
         Ok(())
     }
 
     /// Set the notification mode for a room.
+    ///
+    /// If the room was muted temporarily, the mute won't expire anymore.
     pub async fn set_room_notification_mode(
         &self,
         room_id: &RoomId,
         mode: RoomNotificationMode,
+    ) -> Result<(), NotificationSettingsError> {
+        self.apply_room_notification_mode(room_id, mode).await?;
+        self.forget_temporary_mute(room_id).await
+    }
+
+    async fn apply_room_notification_mode(
+        &self,
+        room_id: &RoomId,
+        mode: RoomNotificationMode,
     ) -> Result<(), NotificationSettingsError> {
         let rules = self.rules.read().await.clone();
 
@@ -307,16 +492,25 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
 
         Ok(())
     }
 
     /// Delete all user defined rules for a room.
+    ///
+    /// If the room was muted temporarily, the mute won't expire anymore.
     pub async fn delete_user_defined_room_rules(
         &self,
         room_id: &RoomId,
+    ) -> Result<(), NotificationSettingsError> {
+        self.apply_delete_user_defined_room_rules(room_id).await?;
+        self.forget_temporary_mute(room_id).await
+    }
+
+    async fn apply_delete_user_defined_room_rules(
+        &self,
+        room_id: &RoomId,
     ) -> Result<(), NotificationSettingsError> {
         let rules = self.rules.read().await.clone();
 
@@ -332,8 +526,7 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
 
         Ok(())
     }
@@ -372,6 +565,166 @@ impl NotificationSettings {
         }
     }
 
+    /// Mute a room until the given time.
+    ///
+    /// When the mute expires, the notification mode the room had before is
+    /// restored by [`NotificationSettings::watch_temporary_mutes`]. The mutes
+    /// are stored in the account data, so they can be restored by any
+    /// session.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The room to mute.
+    /// * `expires_at` - When the mute should expire.
+    pub async fn mute_room_until(
+        &self,
+        room_id: &RoomId,
+        expires_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<(), NotificationSettingsError> {
+        let mut temporary_mutes = self.temporary_mutes().await;
+
+        // If the room is already muted temporarily, keep the mode it had before the
+        // first mute.
+        let previous_mode = match temporary_mutes.mutes.get(room_id) {
+            Some(mute) => mute.previous_mode,
+            None => self.get_user_defined_room_notification_mode(room_id).await,
+        };
+
+        self.apply_room_notification_mode(room_id, RoomNotificationMode::Mute).await?;
+
+        temporary_mutes
+            .mutes
+            .insert(room_id.to_owned(), TemporaryMute { expires_at, previous_mode });
+        self.save_temporary_mutes(temporary_mutes).await?;
+
+        let _ = self.changes_sender.send(());
+
+        Ok(())
+    }
+
+    /// Get when the mute of a room expires, if it is muted temporarily.
+    pub async fn get_room_mute_expiry(
+        &self,
+        room_id: &RoomId,
+    ) -> Option<MilliSecondsSinceUnixEpoch> {
+        self.temporary_mutes().await.expiry(room_id)
+    }
+
+    /// Restore the notification mode the rooms had before being muted
+    /// temporarily, for the mutes which have expired.
+    ///
+    /// Returns the IDs of the rooms which have been restored.
+    pub async fn restore_expired_mutes(
+        &self,
+    ) -> Result<Vec<OwnedRoomId>, NotificationSettingsError> {
+        let mut temporary_mutes = self.temporary_mutes().await;
+
+        let expired = temporary_mutes.take_expired(self.client.inner.clock.timestamp());
+        if expired.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        for (room_id, mute) in &expired {
+            match mute.previous_mode {
+                Some(mode) => self.apply_room_notification_mode(room_id, mode).await?,
+                None => self.apply_delete_user_defined_room_rules(room_id).await?,
+            }
+        }
+
+        self.save_temporary_mutes(temporary_mutes).await?;
+
+        let _ = self.changes_sender.send(());
+
+        Ok(expired.into_iter().map(|(room_id, _)| room_id).collect())
+    }
+
+    /// Restore the notification mode of the rooms muted temporarily as soon
+    /// as their mutes expire.
+    ///
+    /// This future only ends when the `NotificationSettings` are dropped, so
+    /// it should be spawned in a task, which can be aborted to stop watching.
+    pub async fn watch_temporary_mutes(&self) {
+        let clock = &self.client.inner.clock;
+        let mut changes = self.subscribe_to_changes();
+
+        loop {
+            if let Err(error) = self.restore_expired_mutes().await {
+                warn!("Unable to restore the expired temporary mutes: {error}");
+                clock.sleep(RESTORE_RETRY_DELAY).await;
+                continue;
+            }
+
+            let next_expiry = self.temporary_mutes().await.next_expiry();
+
+            // Wait for the next expiry, or for a change which could add an earlier one.
+            let changed = match next_expiry {
+                Some(expires_at) => {
+                    let timeout = clock.sleep(time_until(expires_at, clock.timestamp()));
+                    let changed = pin!(changes.recv());
+                    match select(timeout, changed).await {
+                        Either::Left(_) => Ok(()),
+                        Either::Right((changed, _)) => changed,
+                    }
+                }
+                None => changes.recv().await,
+            };
+
+            if let Err(RecvError::Closed) = changed {
+                break;
+            }
+        }
+    }
+
+    /// Get the rooms muted temporarily, loading them from the store if needed.
+    async fn temporary_mutes(&self) -> TemporaryMutesContent {
+        if let Some(temporary_mutes) = &*self.temporary_mutes.read().await {
+            return temporary_mutes.clone();
+        }
+
+        let stored = match self.client.account().account_data::<TemporaryMutesContent>().await {
+            Ok(Some(raw)) => raw.deserialize().unwrap_or_else(|error| {
+                warn!("Unable to deserialize the temporary mutes: {error}");
+                TemporaryMutesContent::default()
+            }),
+            Ok(None) => TemporaryMutesContent::default(),
+            Err(error) => {
+                warn!("Unable to load the temporary mutes: {error}");
+                TemporaryMutesContent::default()
+            }
+        };
+
+        // The temporary mutes may have been received in the meantime.
+        self.temporary_mutes.write().await.get_or_insert(stored).clone()
+    }
+
+    async fn save_temporary_mutes(
+        &self,
+        temporary_mutes: TemporaryMutesContent,
+    ) -> Result<(), NotificationSettingsError> {
+        self.client.account().set_account_data(temporary_mutes.clone()).await.map_err(|error| {
+            error!("Unable to save the temporary mutes: {error}");
+            NotificationSettingsError::UnableToSaveTemporaryMutes
+        })?;
+
+        *self.temporary_mutes.write().await = Some(temporary_mutes);
+
+        Ok(())
+    }
+
+    /// Stop tracking the temporary mute of a room, if any.
+    async fn forget_temporary_mute(
+        &self,
+        room_id: &RoomId,
+    ) -> Result<(), NotificationSettingsError> {
+        let mut temporary_mutes = self.temporary_mutes().await;
+
+        if temporary_mutes.mutes.remove(room_id).is_some() {
+            self.save_temporary_mutes(temporary_mutes).await?;
+        }
+
+        Ok(())
+    }
+
     /// Get the keywords which have enabled rules.
     pub async fn enabled_keywords(&self) -> IndexSet<String> {
         self.rules.read().await.enabled_keywords()
@@ -404,8 +757,7 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
 
         Ok(())
     }
@@ -432,8 +784,294 @@ impl NotificationSettings {
 
         self.run_server_commands(&rule_commands).await?;
 
-        let rules = &mut *self.rules.write().await;
-        rules.apply(rule_commands);
+        self.apply_rule_commands(rule_commands).await;
+
+        Ok(())
+    }
+
+    /// Get the users whose events are all muted.
+    pub async fn muted_users(&self) -> IndexSet<OwnedUserId> {
+        self.rules.read().await.muted_users()
+    }
+
+    /// Get whether the events sent by the given user are all muted.
+    pub async fn is_user_muted(&self, user_id: &UserId) -> bool {
+        self.rules.read().await.is_sender_muted(user_id, None)
+    }
+
+    /// Set whether the events sent by the given user are all muted.
+    ///
+    /// This suppresses the notifications for these events, including the ones
+    /// mentioning the current user, but contrary to ignoring the user, the
+    /// events are still received and displayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The sender of the events to mute.
+    /// * `muted` - Whether the events should be muted.
+    pub async fn set_user_muted(
+        &self,
+        user_id: &UserId,
+        muted: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        self.set_sender_muted(user_id, None, muted).await
+    }
+
+    /// Get whether the mentions of the current user sent by the given user are
+    /// muted.
+    pub async fn is_user_mention_muted(&self, user_id: &UserId) -> bool {
+        let Some(own_user_id) = self.client.user_id() else { return false };
+        self.rules.read().await.is_sender_muted(user_id, Some(own_user_id))
+    }
+
+    /// Set whether the mentions of the current user sent by the given user are
+    /// muted.
+    ///
+    /// The other events sent by the user notify according to the other rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The sender of the mentions to mute.
+    /// * `muted` - Whether the mentions should be muted.
+    pub async fn set_user_mention_mute(
+        &self,
+        user_id: &UserId,
+        muted: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let own_user_id = self.client.user_id().ok_or_else(|| {
+            NotificationSettingsError::InvalidParameter("the client is not logged in".to_owned())
+        })?;
+
+        self.set_sender_muted(user_id, Some(own_user_id), muted).await
+    }
+
+    /// Add, enable or remove the rules muting the events sent by `user_id`, or
+    /// only the ones mentioning `mentioned_user_id` if it is set.
+    async fn set_sender_muted(
+        &self,
+        user_id: &UserId,
+        mentioned_user_id: Option<&UserId>,
+        muted: bool,
+    ) -> Result<(), NotificationSettingsError> {
+        let rules = self.rules.read().await.clone();
+
+        let mut rule_commands = RuleCommands::new(rules.clone().ruleset);
+
+        let existing_rules = rules.sender_mute_rules(user_id, mentioned_user_id);
+
+        if muted {
+            if existing_rules.is_empty() {
+                // Create a rule.
+                rule_commands.insert_sender_mute_rule(user_id, mentioned_user_id)?;
+            } else {
+                if existing_rules.iter().any(|r| r.enabled) {
+                    // Nothing to do.
+                    return Ok(());
+                }
+
+                // Enable one of the rules.
+                rule_commands.set_rule_enabled(
+                    RuleKind::Override,
+                    &existing_rules[0].rule_id,
+                    true,
+                )?;
+            }
+        } else {
+            if existing_rules.is_empty() {
+                return Ok(());
+            }
+
+            for rule in existing_rules {
+                rule_commands.delete_rule(RuleKind::Override, rule.rule_id.clone())?;
+            }
+        }
+
+        self.run_server_commands(&rule_commands).await?;
+
+        self.apply_rule_commands(rule_commands).await;
+
+        Ok(())
+    }
+
+    /// Get which invites generate notifications.
+    pub async fn invite_notification_policy(&self) -> InviteNotificationPolicy {
+        if !self.rules.read().await.are_invites_notified() {
+            InviteNotificationPolicy::Never
+        } else if self.invite_policy_content().await.known_users_only {
+            InviteNotificationPolicy::KnownUsersOnly
+        } else {
+            InviteNotificationPolicy::Always
+        }
+    }
+
+    /// Set which invites generate notifications.
+    ///
+    /// Whether invites notify at all is controlled by the
+    /// `.m.rule.invite_for_me` push rule, or by an equivalent user-defined rule
+    /// on homeservers whose default rules don't include it. Restricting the
+    /// notifications to known users can't be done with push rules, so it is
+    /// stored in the account data, and applied by the `NotificationClient` of
+    /// the `matrix-sdk-ui` crate.
+    pub async fn set_invite_notification_policy(
+        &self,
+        policy: InviteNotificationPolicy,
+    ) -> Result<(), NotificationSettingsError> {
+        let notify = policy != InviteNotificationPolicy::Never;
+
+        let rules = self.rules.read().await.clone();
+        let mut rule_commands = RuleCommands::new(rules.ruleset.clone());
+
+        if let Some(rule) =
+            rules.ruleset.get(RuleKind::Override, PredefinedOverrideRuleId::InviteForMe)
+        {
+            if rule.enabled() != notify {
+                rule_commands.set_rule_enabled(
+                    RuleKind::Override,
+                    PredefinedOverrideRuleId::InviteForMe.as_str(),
+                    notify,
+                )?;
+            }
+        } else {
+            let custom_rule = rules.ruleset.get(RuleKind::Override, INVITE_FOR_ME_RULE_ID);
+
+            match (custom_rule, notify) {
+                (None, true) => {
+                    let user_id = self.client.user_id().ok_or_else(|| {
+                        NotificationSettingsError::InvalidParameter(
+                            "the client is not logged in".to_owned(),
+                        )
+                    })?;
+                    rule_commands.insert_custom_rule(invite_for_me_rule(user_id))?;
+                }
+                (Some(rule), true) if !rule.enabled() => {
+                    rule_commands.set_rule_enabled(
+                        RuleKind::Override,
+                        INVITE_FOR_ME_RULE_ID,
+                        true,
+                    )?;
+                }
+                (Some(_), false) => {
+                    rule_commands
+                        .delete_rule(RuleKind::Override, INVITE_FOR_ME_RULE_ID.to_owned())?;
+                }
+                _ => {}
+            }
+        }
+
+        if !rule_commands.commands.is_empty() {
+            self.run_server_commands(&rule_commands).await?;
+            self.apply_rule_commands(rule_commands).await;
+        }
+
+        let known_users_only = policy == InviteNotificationPolicy::KnownUsersOnly;
+        if self.invite_policy_content().await.known_users_only != known_users_only {
+            let content = InviteNotificationPolicyContent { known_users_only };
+            self.client.account().set_account_data(content.clone()).await.map_err(|error| {
+                error!("Unable to save the invite notification policy: {error}");
+                NotificationSettingsError::UnableToSaveInviteNotificationPolicy
+            })?;
+
+            if let Err(error) = self.save_in_store(content).await {
+                warn!("Unable to persist the invite notification policy in the store: {error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the part of the invite notification policy stored in the account
+    /// data.
+    async fn invite_policy_content(&self) -> InviteNotificationPolicyContent {
+        match self.client.account().account_data::<InviteNotificationPolicyContent>().await {
+            Ok(Some(raw)) => raw.deserialize().unwrap_or_else(|error| {
+                warn!("Unable to deserialize the invite notification policy: {error}");
+                InviteNotificationPolicyContent::default()
+            }),
+            Ok(None) => InviteNotificationPolicyContent::default(),
+            Err(error) => {
+                warn!("Unable to load the invite notification policy: {error}");
+                InviteNotificationPolicyContent::default()
+            }
+        }
+    }
+
+    /// Add a custom push rule, or replace the user-defined rule with the same
+    /// kind and ID.
+    ///
+    /// The rule is validated before being sent to the server, see
+    /// [`PushRuleBuilder`] for the details.
+    pub async fn add_push_rule(
+        &self,
+        rule: PushRuleBuilder,
+    ) -> Result<(), NotificationSettingsError> {
+        let rules = self.rules.read().await.clone();
+
+        let mut rule_commands = RuleCommands::new(rules.ruleset);
+        rule_commands.insert_custom_rule(rule)?;
+
+        self.run_server_commands(&rule_commands).await?;
+
+        self.apply_rule_commands(rule_commands).await;
+
+        Ok(())
+    }
+
+    /// Remove a user-defined push rule.
+    ///
+    /// Server-default rules can't be removed, only disabled with
+    /// [`NotificationSettings::set_push_rule_enabled`].
+    pub async fn remove_push_rule(
+        &self,
+        kind: RuleKind,
+        rule_id: &str,
+    ) -> Result<(), NotificationSettingsError> {
+        validate_rule_id(rule_id)?;
+
+        let rules = self.rules.read().await.clone();
+
+        let mut rule_commands = RuleCommands::new(rules.ruleset);
+        rule_commands
+            .delete_rule(kind, rule_id.to_owned())
+            .map_err(|_| NotificationSettingsError::RuleNotFound(rule_id.to_owned()))?;
+
+        self.run_server_commands(&rule_commands).await?;
+
+        self.apply_rule_commands(rule_commands).await;
+
+        Ok(())
+    }
+
+    /// Apply commands which were run successfully on the server to the local
+    /// rules, and persist the resulting ruleset in the store.
+    ///
+    /// The server echoes the changes back on sync, but until then the store
+    /// would still contain the previous ruleset, which is what a new instance
+    /// would load.
+    async fn apply_rule_commands(&self, rule_commands: RuleCommands) {
+        let ruleset = {
+            let rules = &mut *self.rules.write().await;
+            rules.apply(rule_commands);
+            rules.ruleset.clone()
+        };
+
+        if let Err(error) = self.save_in_store(PushRulesEventContent::new(ruleset)).await {
+            warn!("Unable to persist the push rules in the store: {error}");
+        }
+    }
+
+    /// Save global account data in the store, without waiting for the server
+    /// to send it back on sync.
+    async fn save_in_store<C>(&self, content: C) -> Result<()>
+    where
+        C: GlobalAccountDataEventContent + StaticEventContent,
+    {
+        let event = GlobalAccountDataEvent { content };
+
+        let mut changes = StateChanges::default();
+        changes.account_data.insert(C::TYPE.into(), Raw::new(&event)?.cast());
+
+        let _sync_lock = self.client.base_client().sync_lock().read().await;
+        self.client.store().save_changes(&changes).await?;
 
         Ok(())
     }
@@ -473,6 +1111,28 @@ impl NotificationSettings {
                         NotificationSettingsError::UnableToAddPushRule
                     })?;
                 }
+                Command::SetSenderMutePushRule {
+                    scope,
+                    rule_id,
+                    user_id: _,
+                    mentioned_user_id: _,
+                } => {
+                    let push_rule = command.to_push_rule()?;
+                    let request = set_pushrule::v3::Request::new(scope.clone(), push_rule);
+                    self.client.send(request, request_config).await.map_err(|error| {
+                        error!("Unable to set sender push rule `{rule_id}`: {error}");
+                        NotificationSettingsError::UnableToAddPushRule
+                    })?;
+                }
+                Command::SetCustomPushRule { scope, rule, before, after } => {
+                    let mut request = set_pushrule::v3::Request::new(scope.clone(), rule.clone());
+                    request.before = before.clone();
+                    request.after = after.clone();
+                    self.client.send(request, request_config).await.map_err(|error| {
+                        error!("Unable to set custom push rule: {error}");
+                        NotificationSettingsError::UnableToAddPushRule
+                    })?;
+                }
                 Command::SetKeywordPushRule { scope, keyword: _ } => {
                     let push_rule = command.to_push_rule()?;
                     let request = set_pushrule::v3::Request::new(scope.clone(), push_rule);
@@ -526,11 +1186,12 @@ mod tests {
         test_json,
     };
     use ruma::{
+        owned_room_id,
         push::{
             Action, AnyPushRuleRef, NewPatternedPushRule, NewPushRule, PredefinedOverrideRuleId,
-            PredefinedUnderrideRuleId, RuleKind,
+            PredefinedUnderrideRuleId, PushCondition, RuleKind,
         },
-        OwnedRoomId, RoomId,
+        user_id, OwnedRoomId, RoomId,
     };
     use serde_json::json;
     use stream_assert::{assert_next_eq, assert_pending};
@@ -544,10 +1205,11 @@ mod tests {
         config::SyncSettings,
         error::NotificationSettingsError,
         notification_settings::{
-            IsEncrypted, IsOneToOne, NotificationSettings, RoomNotificationMode,
+            InviteNotificationPolicy, IsEncrypted, IsOneToOne, NotificationSettings,
+            PushRuleBuilder, RoomNotificationMode, RoomNotificationSettings,
         },
         test_utils::logged_in_client,
-        Client,
+        Client, RoomState,
     };
 
     fn get_test_room_id() -> OwnedRoomId {
@@ -621,6 +1283,46 @@ mod tests {
         assert_eq!(custom_rules[1], (RuleKind::Room, room_id.to_string()));
     }
 
+    #[async_test]
+    async fn test_get_room_notification_modes() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let muted_room_id = owned_room_id!("!muted:matrix.org");
+        let default_room_id = owned_room_id!("!default:matrix.org");
+        let unknown_room_id = owned_room_id!("!unknown:matrix.org");
+
+        client.base_client().get_or_create_room(&default_room_id, RoomState::Joined);
+
+        let settings =
+            from_insert_rules(&client, vec![(RuleKind::Override, &muted_room_id, false)]);
+        let modes = settings
+            .get_room_notification_modes(&[
+                muted_room_id.clone(),
+                default_room_id.clone(),
+                unknown_room_id.clone(),
+            ])
+            .await;
+
+        assert_eq!(modes.len(), 2);
+        assert_eq!(
+            modes[&muted_room_id],
+            RoomNotificationSettings {
+                mode: RoomNotificationMode::Mute,
+                is_default: false,
+                sound: None
+            }
+        );
+        assert_eq!(
+            modes[&default_room_id],
+            RoomNotificationSettings {
+                mode: RoomNotificationMode::AllMessages,
+                is_default: true,
+                sound: None
+            }
+        );
+        assert!(!modes.contains_key(&unknown_room_id));
+    }
+
     #[async_test]
     async fn test_get_user_defined_room_notification_mode_none() {
         let server = MockServer::start().await;
@@ -1031,6 +1733,44 @@ mod tests {
         assert!(updated_rules.get_custom_rules_for_room(&room_id_a).is_empty());
     }
 
+    #[async_test]
+    async fn test_set_room_sound() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("PUT")).respond_with(ResponseTemplate::new(200)).mount(&server).await;
+
+        let settings = client.notification_settings().await;
+        let room_id = get_test_room_id();
+        assert!(settings.get_room_sound(&room_id).await.is_none());
+
+        // Setting a sound for a room with the default mode makes it notify for all
+        // messages.
+        settings.set_room_sound(&room_id, Some("ding.ogg".to_owned())).await.unwrap();
+        assert_eq!(settings.get_room_sound(&room_id).await.as_deref(), Some("ding.ogg"));
+        assert_eq!(
+            settings.get_user_defined_room_notification_mode(&room_id).await,
+            Some(RoomNotificationMode::AllMessages)
+        );
+        let modes = settings.get_room_notification_modes(&[room_id.clone()]).await;
+        assert_eq!(modes[&room_id].sound.as_deref(), Some("ding.ogg"));
+
+        // Clearing the sound restores the default sound, and keeps the mode.
+        settings.set_room_sound(&room_id, None).await.unwrap();
+        assert!(settings.get_room_sound(&room_id).await.is_none());
+        assert_eq!(
+            settings.get_user_defined_room_notification_mode(&room_id).await,
+            Some(RoomNotificationMode::AllMessages)
+        );
+
+        // A muted room can't have a sound.
+        settings.set_room_notification_mode(&room_id, RoomNotificationMode::Mute).await.unwrap();
+        assert_matches!(
+            settings.set_room_sound(&room_id, Some("ding.ogg".to_owned())).await,
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+    }
+
     #[async_test]
     async fn test_unmute_room_not_muted() {
         let server = MockServer::start().await;
@@ -1531,6 +2271,279 @@ mod tests {
         settings.remove_keyword("banana").await.unwrap();
     }
 
+    #[async_test]
+    async fn test_set_user_muted() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = client.notification_settings().await;
+        let user_id = user_id!("@alice:example.org");
+
+        assert!(!settings.is_user_muted(user_id).await);
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/override/.*alice"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_user_muted(user_id, true).await.unwrap();
+
+        // The ruleset must have been updated.
+        assert!(settings.is_user_muted(user_id).await);
+        assert!(!settings.is_user_mention_muted(user_id).await);
+        assert_eq!(settings.muted_users().await.len(), 1);
+
+        // Muting again is a no-op.
+        settings.set_user_muted(user_id, true).await.unwrap();
+
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/override/.*alice"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_user_muted(user_id, false).await.unwrap();
+
+        assert!(!settings.is_user_muted(user_id).await);
+        assert!(settings.muted_users().await.is_empty());
+    }
+
+    #[async_test]
+    async fn test_set_user_mention_mute() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = client.notification_settings().await;
+        let user_id = user_id!("@alice:example.org");
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/override/.*alice.*mentions"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_user_mention_mute(user_id, true).await.unwrap();
+
+        // Only the mentions are muted.
+        assert!(settings.is_user_mention_muted(user_id).await);
+        assert!(!settings.is_user_muted(user_id).await);
+        assert!(settings.muted_users().await.is_empty());
+    }
+
+    #[async_test]
+    async fn test_rules_persisted_in_store() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = client.notification_settings().await;
+
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/content/banana"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.add_keyword("banana".to_owned()).await.unwrap();
+
+        // The change is persisted before the server echoes it back, so a new
+        // instance, as created on a restart, loads it without syncing.
+        let new_settings = client.notification_settings().await;
+        assert!(new_settings.enabled_keywords().await.contains("banana"));
+
+        let stored = client.account().push_rules().await.unwrap();
+        assert!(stored.get(RuleKind::Content, "banana").is_some());
+    }
+
+    #[async_test]
+    async fn test_set_invite_notification_policy() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = client.notification_settings().await;
+
+        assert_eq!(settings.invite_notification_policy().await, InviteNotificationPolicy::Always);
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"/account_data/m.org.matrix.custom.invite_notification_policy$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        // Restricting invites to known users doesn't change the push rules.
+        settings
+            .set_invite_notification_policy(InviteNotificationPolicy::KnownUsersOnly)
+            .await
+            .unwrap();
+        assert_eq!(
+            settings.invite_notification_policy().await,
+            InviteNotificationPolicy::KnownUsersOnly
+        );
+
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/.m.rule.invite_for_me/enabled"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_invite_notification_policy(InviteNotificationPolicy::Never).await.unwrap();
+        assert_eq!(settings.invite_notification_policy().await, InviteNotificationPolicy::Never);
+    }
+
+    #[async_test]
+    async fn test_set_invite_notification_policy_without_server_default_rule() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        let mut ruleset = get_server_default_ruleset();
+        ruleset
+            .override_
+            .retain(|rule| rule.rule_id != PredefinedOverrideRuleId::InviteForMe.as_str());
+        let settings = NotificationSettings::new(client, ruleset);
+
+        // Without any rule, invites don't notify.
+        assert_eq!(settings.invite_notification_policy().await, InviteNotificationPolicy::Never);
+
+        Mock::given(method("PUT"))
+            .and(path(
+                "/_matrix/client/r0/pushrules/global/override/org.matrix.custom.invite_for_me",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_invite_notification_policy(InviteNotificationPolicy::Always).await.unwrap();
+        assert_eq!(settings.invite_notification_policy().await, InviteNotificationPolicy::Always);
+
+        Mock::given(method("DELETE"))
+            .and(path(
+                "/_matrix/client/r0/pushrules/global/override/org.matrix.custom.invite_for_me",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.set_invite_notification_policy(InviteNotificationPolicy::Never).await.unwrap();
+        assert_eq!(settings.invite_notification_policy().await, InviteNotificationPolicy::Never);
+    }
+
+    #[async_test]
+    async fn test_mute_room_until() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let room_id = get_test_room_id();
+        let settings = from_insert_rules(&client, vec![(RuleKind::Room, &room_id, false)]);
+
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/override/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/room/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex(r"/account_data/m.org.matrix.custom.temporary_mutes$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        // Mute the room until a time which is already in the past.
+        let expires_at = MilliSecondsSinceUnixEpoch(uint!(1000));
+        settings.mute_room_until(&room_id, expires_at).await.unwrap();
+
+        assert_eq!(
+            settings.get_user_defined_room_notification_mode(&room_id).await,
+            Some(RoomNotificationMode::Mute)
+        );
+        assert_eq!(settings.get_room_mute_expiry(&room_id).await, Some(expires_at));
+
+        Mock::given(method("DELETE"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/override/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path_regex(r"^/_matrix/client/r0/pushrules/global/room/"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // The previous mode is restored.
+        let restored = settings.restore_expired_mutes().await.unwrap();
+        assert_eq!(restored, vec![room_id.clone()]);
+        assert_eq!(
+            settings.get_user_defined_room_notification_mode(&room_id).await,
+            Some(RoomNotificationMode::MentionsAndKeywordsOnly)
+        );
+        assert!(settings.get_room_mute_expiry(&room_id).await.is_none());
+
+        // Nothing else to restore.
+        assert!(settings.restore_expired_mutes().await.unwrap().is_empty());
+    }
+
+    #[async_test]
+    async fn test_add_and_remove_push_rule() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+        let settings = client.notification_settings().await;
+
+        Mock::given(method("PUT"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/bot"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let rule = PushRuleBuilder::new_override("bot").condition(PushCondition::EventMatch {
+            key: "sender".to_owned(),
+            pattern: "@bot:example.org".to_owned(),
+        });
+        settings.add_push_rule(rule).await.unwrap();
+
+        assert!(settings.is_push_rule_enabled(RuleKind::Override, "bot").await.unwrap());
+
+        // An invalid rule is not sent to the server.
+        let rule = PushRuleBuilder::new_override(".m.rule.bot");
+        assert_matches!(
+            settings.add_push_rule(rule).await,
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+
+        Mock::given(method("DELETE"))
+            .and(path("/_matrix/client/r0/pushrules/global/override/bot"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        settings.remove_push_rule(RuleKind::Override, "bot").await.unwrap();
+
+        assert_matches!(
+            settings.is_push_rule_enabled(RuleKind::Override, "bot").await,
+            Err(NotificationSettingsError::RuleNotFound(_))
+        );
+
+        // Server-default rules can't be removed.
+        assert_matches!(
+            settings
+                .remove_push_rule(RuleKind::Override, PredefinedOverrideRuleId::Master.as_str())
+                .await,
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+    }
+
     #[async_test]
     async fn test_set_default_room_notification_mode_missing_poll_start() {
         let server = MockServer::start().await;
@@ -0,0 +1,314 @@
+//! Builder of custom push rules
+
+use ruma::push::{
+    Action, NewConditionalPushRule, NewPatternedPushRule, NewPushRule, PushCondition, RuleKind,
+    Ruleset,
+};
+
+use crate::NotificationSettingsError;
+
+/// The position of a new rule relative to another user-defined rule of the
+/// same kind.
+#[derive(Clone, Debug)]
+enum Position {
+    Before(String),
+    After(String),
+}
+
+/// A builder for a custom push rule, to be added with
+/// [`NotificationSettings::add_push_rule`].
+///
+/// Only `Override`, `Underride` and `Content` rules can be built. The rule is
+/// validated when it is added: its ID must not be reserved for server-default
+/// rules, conditions are only allowed for `Override` and `Underride` rules, and
+/// the rule it is positioned against must be a user-defined rule of the same
+/// kind.
+///
+/// # Examples
+///
+/// ```no_run
+/// use matrix_sdk::{
+///     notification_settings::PushRuleBuilder, ruma::push::PushCondition,
+/// };
+///
+/// # async {
+/// # let client: matrix_sdk::Client = todo!();
+/// let settings = client.notification_settings().await;
+///
+/// // Never notify for the notices sent by a bridge bot.
+/// let rule = PushRuleBuilder::new_override("bridge_notices")
+///     .condition(PushCondition::EventMatch {
+///         key: "sender".to_owned(),
+///         pattern: "@bridge:example.org".to_owned(),
+///     })
+///     .condition(PushCondition::EventMatch {
+///         key: "content.msgtype".to_owned(),
+///         pattern: "m.notice".to_owned(),
+///     });
+///
+/// settings.add_push_rule(rule).await?;
+/// # anyhow::Ok(()) };
+/// ```
+///
+/// [`NotificationSettings::add_push_rule`]: super::NotificationSettings::add_push_rule
+#[derive(Clone, Debug)]
+pub struct PushRuleBuilder {
+    kind: RuleKind,
+    rule_id: String,
+    pattern: Option<String>,
+    conditions: Vec<PushCondition>,
+    actions: Vec<Action>,
+    position: Option<Position>,
+}
+
+impl PushRuleBuilder {
+    fn new(kind: RuleKind, rule_id: String, pattern: Option<String>) -> Self {
+        Self { kind, rule_id, pattern, conditions: vec![], actions: vec![], position: None }
+    }
+
+    /// Create a builder for an `Override` rule.
+    pub fn new_override(rule_id: impl Into<String>) -> Self {
+        Self::new(RuleKind::Override, rule_id.into(), None)
+    }
+
+    /// Create a builder for an `Underride` rule.
+    pub fn new_underride(rule_id: impl Into<String>) -> Self {
+        Self::new(RuleKind::Underride, rule_id.into(), None)
+    }
+
+    /// Create a builder for a `Content` rule, matching the body of messages
+    /// against the given glob-style pattern.
+    pub fn new_content(rule_id: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Self::new(RuleKind::Content, rule_id.into(), Some(pattern.into()))
+    }
+
+    /// Add a condition which must be fulfilled for the rule to apply.
+    ///
+    /// Only allowed for `Override` and `Underride` rules.
+    pub fn condition(mut self, condition: PushCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Add an action to perform when the rule applies.
+    ///
+    /// A rule without actions doesn't notify.
+    pub fn action(mut self, action: Action) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Insert the rule right before the given user-defined rule, i.e. with a
+    /// higher priority.
+    pub fn before(mut self, rule_id: impl Into<String>) -> Self {
+        self.position = Some(Position::Before(rule_id.into()));
+        self
+    }
+
+    /// Insert the rule right after the given user-defined rule, i.e. with a
+    /// lower priority.
+    pub fn after(mut self, rule_id: impl Into<String>) -> Self {
+        self.position = Some(Position::After(rule_id.into()));
+        self
+    }
+
+    /// The kind of the rule.
+    pub fn kind(&self) -> RuleKind {
+        self.kind.clone()
+    }
+
+    /// The ID of the rule.
+    pub fn rule_id(&self) -> &str {
+        &self.rule_id
+    }
+
+    /// Validate the rule against the given ruleset, and build it.
+    ///
+    /// Returns the rule, and the IDs of the rules it must be inserted before
+    /// and after.
+    pub(crate) fn build(
+        self,
+        ruleset: &Ruleset,
+    ) -> Result<(NewPushRule, Option<String>, Option<String>), NotificationSettingsError> {
+        validate_rule_id(&self.rule_id)?;
+
+        let (before, after) = match self.position {
+            Some(Position::Before(rule_id)) => (Some(rule_id), None),
+            Some(Position::After(rule_id)) => (None, Some(rule_id)),
+            None => (None, None),
+        };
+
+        if let Some(relative_id) = before.as_ref().or(after.as_ref()) {
+            if *relative_id == self.rule_id {
+                return Err(invalid("a rule cannot be positioned relative to itself"));
+            }
+
+            match ruleset.get(self.kind.clone(), relative_id) {
+                Some(rule) if !rule.is_server_default() => {}
+                Some(_) => {
+                    return Err(invalid(
+                        "a rule cannot be positioned relative to a server-default rule",
+                    ))
+                }
+                None => return Err(NotificationSettingsError::RuleNotFound(relative_id.clone())),
+            }
+        }
+
+        let rule = match self.kind {
+            RuleKind::Override => NewPushRule::Override(NewConditionalPushRule::new(
+                self.rule_id,
+                self.conditions,
+                self.actions,
+            )),
+            RuleKind::Underride => NewPushRule::Underride(NewConditionalPushRule::new(
+                self.rule_id,
+                self.conditions,
+                self.actions,
+            )),
+            RuleKind::Content => {
+                if !self.conditions.is_empty() {
+                    return Err(invalid("content rules cannot have conditions"));
+                }
+
+                let pattern = self.pattern.unwrap_or_default();
+                if pattern.is_empty() {
+                    return Err(invalid("content rules must have a non-empty pattern"));
+                }
+
+                NewPushRule::Content(NewPatternedPushRule::new(self.rule_id, pattern, self.actions))
+            }
+            _ => return Err(invalid("only override, underride and content rules can be built")),
+        };
+
+        Ok((rule, before, after))
+    }
+}
+
+/// Check that the rule ID can be used for a user-defined rule.
+pub(crate) fn validate_rule_id(rule_id: &str) -> Result<(), NotificationSettingsError> {
+    if rule_id.is_empty() {
+        Err(invalid("the rule ID cannot be empty"))
+    } else if rule_id.starts_with('.') {
+        Err(invalid("rule IDs starting with `.` are reserved for server-default rules"))
+    } else if rule_id.contains(['/', '\\']) {
+        Err(invalid("the rule ID cannot contain `/` or `\\`"))
+    } else {
+        Ok(())
+    }
+}
+
+fn invalid(message: &str) -> NotificationSettingsError {
+    NotificationSettingsError::InvalidParameter(message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use matrix_sdk_test::notification_settings::get_server_default_ruleset;
+    use ruma::push::{
+        Action, NewConditionalPushRule, NewPushRule, PredefinedOverrideRuleId, PushCondition,
+    };
+
+    use super::PushRuleBuilder;
+    use crate::NotificationSettingsError;
+
+    fn sender_condition() -> PushCondition {
+        PushCondition::EventMatch { key: "sender".to_owned(), pattern: "@bot:b.c".to_owned() }
+    }
+
+    #[test]
+    fn test_build_override_rule() {
+        let ruleset = get_server_default_ruleset();
+
+        let (rule, before, after) = PushRuleBuilder::new_override("bot")
+            .condition(sender_condition())
+            .action(Action::Notify)
+            .build(&ruleset)
+            .unwrap();
+
+        assert_matches!(rule, NewPushRule::Override(rule) => {
+            assert_eq!(rule.rule_id, "bot");
+            assert_eq!(rule.conditions.len(), 1);
+            assert_eq!(rule.actions.len(), 1);
+        });
+        assert!(before.is_none());
+        assert!(after.is_none());
+    }
+
+    #[test]
+    fn test_build_invalid_rule_id() {
+        let ruleset = get_server_default_ruleset();
+
+        for rule_id in ["", ".m.rule.custom", "a/b", "a\\b"] {
+            assert_matches!(
+                PushRuleBuilder::new_override(rule_id).build(&ruleset),
+                Err(NotificationSettingsError::InvalidParameter(_))
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_content_rule() {
+        let ruleset = get_server_default_ruleset();
+
+        assert_matches!(
+            PushRuleBuilder::new_content("banana", "banana").build(&ruleset),
+            Ok((NewPushRule::Content(_), None, None))
+        );
+        assert_matches!(
+            PushRuleBuilder::new_content("banana", "").build(&ruleset),
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+        assert_matches!(
+            PushRuleBuilder::new_content("banana", "banana")
+                .condition(sender_condition())
+                .build(&ruleset),
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+    }
+
+    #[test]
+    fn test_build_position() {
+        let mut ruleset = get_server_default_ruleset();
+        ruleset
+            .insert(
+                NewPushRule::Override(NewConditionalPushRule::new(
+                    "existing".to_owned(),
+                    vec![],
+                    vec![],
+                )),
+                None,
+                None,
+            )
+            .unwrap();
+
+        assert_matches!(
+            PushRuleBuilder::new_override("bot").before("existing").build(&ruleset),
+            Ok((_, Some(before), None)) => assert_eq!(before, "existing")
+        );
+        assert_matches!(
+            PushRuleBuilder::new_override("bot").after("existing").build(&ruleset),
+            Ok((_, None, Some(after))) => assert_eq!(after, "existing")
+        );
+
+        // The relative rule must exist, with the same kind.
+        assert_matches!(
+            PushRuleBuilder::new_underride("bot").after("existing").build(&ruleset),
+            Err(NotificationSettingsError::RuleNotFound(_))
+        );
+
+        // The relative rule must be user-defined.
+        assert_matches!(
+            PushRuleBuilder::new_override("bot")
+                .before(PredefinedOverrideRuleId::Master.as_str())
+                .build(&ruleset),
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+
+        // The relative rule must be another rule.
+        assert_matches!(
+            PushRuleBuilder::new_override("existing").after("existing").build(&ruleset),
+            Err(NotificationSettingsError::InvalidParameter(_))
+        );
+    }
+}
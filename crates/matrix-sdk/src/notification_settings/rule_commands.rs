@@ -4,10 +4,10 @@ use ruma::{
         Action, PredefinedContentRuleId, PredefinedOverrideRuleId, RemovePushRuleError, RuleKind,
         Ruleset,
     },
-    RoomId,
+    RoomId, UserId,
 };
 
-use super::command::Command;
+use super::{command::Command, push_rule_builder::PushRuleBuilder};
 use crate::NotificationSettingsError;
 
 /// A `RuleCommand` allows to generate a list of `Command` needed to modify a
@@ -68,6 +68,51 @@ impl RuleCommands {
         Ok(())
     }
 
+    /// Insert a new rule muting the events sent by a user, or only the ones
+    /// mentioning `mentioned_user_id` if it is set.
+    pub(crate) fn insert_sender_mute_rule(
+        &mut self,
+        user_id: &UserId,
+        mentioned_user_id: Option<&UserId>,
+    ) -> Result<(), NotificationSettingsError> {
+        // The ID is used in the path of the request, so it must not contain a
+        // slash.
+        let rule_id = match mentioned_user_id {
+            Some(_) => format!("{user_id}_mentions"),
+            None => user_id.to_string(),
+        };
+        let command = Command::SetSenderMutePushRule {
+            scope: RuleScope::Global,
+            rule_id,
+            user_id: user_id.to_owned(),
+            mentioned_user_id: mentioned_user_id.map(ToOwned::to_owned),
+        };
+
+        self.rules.insert(command.to_push_rule()?, None, None)?;
+        self.commands.push(command);
+
+        Ok(())
+    }
+
+    /// Insert a new rule built with a `PushRuleBuilder`, validated against the
+    /// current rules.
+    pub(crate) fn insert_custom_rule(
+        &mut self,
+        builder: PushRuleBuilder,
+    ) -> Result<(), NotificationSettingsError> {
+        let (rule, before, after) = builder.build(&self.rules)?;
+
+        self.rules.insert(rule.clone(), after.as_deref(), before.as_deref())?;
+        self.commands.push(Command::SetCustomPushRule {
+            scope: RuleScope::Global,
+            rule,
+            before,
+            after,
+        });
+
+        Ok(())
+    }
+
     /// Delete a rule
     pub(crate) fn delete_rule(
         &mut self,
@@ -279,6 +324,36 @@ mod tests {
         );
     }
 
+    #[async_test]
+    async fn test_insert_and_delete_sender_mute_rule_for_mentions() {
+        let user_id = UserId::parse("@bot:matrix.org").unwrap();
+        let own_user_id = UserId::parse("@user:matrix.org").unwrap();
+        let mut rule_commands = RuleCommands::new(get_server_default_ruleset());
+        rule_commands.insert_sender_mute_rule(&user_id, Some(&own_user_id)).unwrap();
+
+        // A rule must have been inserted in the ruleset, with an ID that can be
+        // used in the path of a request.
+        let rule_id = assert_matches!(
+            &rule_commands.commands[0],
+            Command::SetSenderMutePushRule { rule_id, .. } => rule_id.clone()
+        );
+        assert!(!rule_id.contains('/'));
+        assert!(rule_commands.rules.get(RuleKind::Override, &rule_id).is_some());
+
+        // The rule can be removed with its ID.
+        rule_commands.delete_rule(RuleKind::Override, rule_id.clone()).unwrap();
+        assert!(rule_commands.rules.get(RuleKind::Override, &rule_id).is_none());
+
+        assert_eq!(rule_commands.commands.len(), 2);
+        assert_matches!(&rule_commands.commands[1],
+            Command::DeletePushRule { scope, kind, rule_id: deleted_rule_id } => {
+                assert_eq!(scope, &RuleScope::Global);
+                assert_eq!(kind, &RuleKind::Override);
+                assert_eq!(deleted_rule_id, &rule_id);
+            }
+        );
+    }
+
     #[async_test]
     async fn test_delete_rule() {
         let room_id = get_test_room_id();
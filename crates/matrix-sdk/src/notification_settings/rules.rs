@@ -4,18 +4,25 @@ use imbl::HashSet;
 use indexmap::IndexSet;
 use ruma::{
     push::{
-        AnyPushRuleRef, PatternedPushRule, PredefinedContentRuleId, PredefinedOverrideRuleId,
-        PredefinedUnderrideRuleId, PushCondition, RuleKind, Ruleset,
+        Action, AnyPushRuleRef, ConditionalPushRule, PatternedPushRule, PredefinedContentRuleId,
+        PredefinedOverrideRuleId, PredefinedUnderrideRuleId, PushCondition, RuleKind, Ruleset,
+        ScalarJsonValue,
     },
-    RoomId,
+    OwnedUserId, RoomId, UserId,
 };
 
-use super::{command::Command, rule_commands::RuleCommands, RoomNotificationMode};
+use super::{
+    command::Command, invite_policy::INVITE_FOR_ME_RULE_ID, rule_commands::RuleCommands,
+    RoomNotificationMode,
+};
 use crate::{
     error::NotificationSettingsError,
     notification_settings::{IsEncrypted, IsOneToOne},
 };
 
+/// The value of the `sound` tweak playing the default sound of the device.
+pub(crate) const DEFAULT_SOUND: &str = "default";
+
 #[derive(Clone, Debug)]
 pub(crate) struct Rules {
     pub ruleset: Ruleset,
@@ -96,6 +103,17 @@ impl Rules {
         None
     }
 
+    /// Gets the sound played for the notifications of a room, if its `Room`
+    /// rule sets one other than the default sound.
+    pub(crate) fn get_room_sound(&self, room_id: &RoomId) -> Option<String> {
+        let rule = self.ruleset.room.iter().find(|rule| &*rule.rule_id == room_id)?;
+        rule.actions
+            .iter()
+            .find_map(Action::sound)
+            .filter(|sound| *sound != DEFAULT_SOUND)
+            .map(ToOwned::to_owned)
+    }
+
     /// Gets the default notification mode for a room.
     ///
     /// # Arguments
@@ -229,6 +247,63 @@ impl Rules {
         self.ruleset.content.iter().filter(|r| !r.default && r.pattern == keyword).collect()
     }
 
+    /// The `Override` rules muting the events sent by a user, or only the ones
+    /// mentioning `mentioned_user_id` if it is set.
+    pub(crate) fn sender_mute_rules(
+        &self,
+        user_id: &UserId,
+        mentioned_user_id: Option<&UserId>,
+    ) -> Vec<&ConditionalPushRule> {
+        self.ruleset
+            .override_
+            .iter()
+            .filter(|r| {
+                !r.default
+                    && !r.actions.iter().any(|a| a.should_notify())
+                    && is_sender_mute_rule(r, user_id, mentioned_user_id)
+            })
+            .collect()
+    }
+
+    /// Get whether an enabled rule mutes the events sent by a user, or only the
+    /// ones mentioning `mentioned_user_id` if it is set.
+    pub(crate) fn is_sender_muted(
+        &self,
+        user_id: &UserId,
+        mentioned_user_id: Option<&UserId>,
+    ) -> bool {
+        self.sender_mute_rules(user_id, mentioned_user_id).iter().any(|r| r.enabled)
+    }
+
+    /// Get whether invites for the current user notify, with the server-default
+    /// rule, or with the user-defined rule replacing it on homeservers without
+    /// it.
+    pub(crate) fn are_invites_notified(&self) -> bool {
+        match self.ruleset.get(RuleKind::Override, PredefinedOverrideRuleId::InviteForMe) {
+            Some(rule) => rule.enabled(),
+            None => self
+                .ruleset
+                .get(RuleKind::Override, INVITE_FOR_ME_RULE_ID)
+                .is_some_and(|rule| rule.enabled()),
+        }
+    }
+
+    /// The users whose events are all muted by an enabled rule.
+    pub(crate) fn muted_users(&self) -> IndexSet<OwnedUserId> {
+        self.ruleset
+            .override_
+            .iter()
+            .filter(|r| !r.default && r.enabled && r.conditions.len() == 1)
+            .filter_map(|r| match &r.conditions[0] {
+                PushCondition::EventMatch { key, pattern } if key == "sender" => {
+                    UserId::parse(pattern).ok()
+                }
+                _ => None,
+            })
+            .filter(|user_id| self.is_sender_muted(user_id, None))
+            .collect()
+    }
+
     /// Get whether a rule is enabled.
     pub(crate) fn is_enabled(
         &self,
@@ -258,11 +333,15 @@ impl Rules {
                 }
                 Command::SetRoomPushRule { .. }
                 | Command::SetOverridePushRule { .. }
-                | Command::SetKeywordPushRule { .. } => {
+                | Command::SetKeywordPushRule { .. }
+                | Command::SetSenderMutePushRule { .. } => {
                     if let Ok(push_rule) = command.to_push_rule() {
                         _ = self.ruleset.insert(push_rule, None, None);
                     }
                 }
+                Command::SetCustomPushRule { scope: _, rule, before, after } => {
+                    _ = self.ruleset.insert(rule, after.as_deref(), before.as_deref());
+                }
                 Command::SetPushRuleEnabled { scope: _, kind, rule_id, enabled } => {
                     _ = self.ruleset.set_enabled(kind, rule_id, enabled);
                 }
@@ -274,6 +353,39 @@ impl Rules {
     }
 }
 
+/// Whether the conditions of the rule match exactly the events sent by
+/// `user_id`, and mentioning `mentioned_user_id` if it is set.
+fn is_sender_mute_rule(
+    rule: &ConditionalPushRule,
+    user_id: &UserId,
+    mentioned_user_id: Option<&UserId>,
+) -> bool {
+    let matches_sender = |condition: &PushCondition| {
+        matches!(
+            condition,
+            PushCondition::EventMatch { key, pattern } if key == "sender" && pattern == user_id
+        )
+    };
+
+    match (rule.conditions.as_slice(), mentioned_user_id) {
+        ([sender], None) => matches_sender(sender),
+        ([first, second], Some(mentioned_user_id)) => {
+            let matches_mention = |condition: &PushCondition| {
+                matches!(
+                    condition,
+                    PushCondition::EventPropertyContains { key, value }
+                        if key == r"content.m\.mentions.user_ids"
+                            && *value == ScalarJsonValue::from(mentioned_user_id.as_str())
+                )
+            };
+
+            (matches_sender(first) && matches_mention(second))
+                || (matches_mention(first) && matches_sender(second))
+        }
+        _ => false,
+    }
+}
+
 /// Gets the `PredefinedUnderrideRuleId` for rooms corresponding to the given
 /// criteria.
 ///
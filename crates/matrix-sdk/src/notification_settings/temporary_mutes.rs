@@ -0,0 +1,101 @@
+//! Rooms muted until a given time
+
+use std::{collections::BTreeMap, time::Duration};
+
+use ruma::{exports::ruma_macros::EventContent, MilliSecondsSinceUnixEpoch, OwnedRoomId, RoomId};
+use serde::{Deserialize, Serialize};
+
+use super::RoomNotificationMode;
+
+/// A custom global account data event tracking the rooms which are muted
+/// temporarily, so the mutes can expire on any session.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "m.org.matrix.custom.temporary_mutes", kind = GlobalAccountData)]
+pub(crate) struct TemporaryMutesContent {
+    #[serde(default)]
+    pub mutes: BTreeMap<OwnedRoomId, TemporaryMute>,
+}
+
+/// A room muted until a given time.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct TemporaryMute {
+    /// When the mute expires.
+    pub expires_at: MilliSecondsSinceUnixEpoch,
+    /// The user-defined mode of the room before it was muted, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_mode: Option<RoomNotificationMode>,
+}
+
+impl TemporaryMutesContent {
+    /// When the mute of the given room expires, if it is muted temporarily.
+    pub(crate) fn expiry(&self, room_id: &RoomId) -> Option<MilliSecondsSinceUnixEpoch> {
+        self.mutes.get(room_id).map(|mute| mute.expires_at)
+    }
+
+    /// When the first mute expires.
+    pub(crate) fn next_expiry(&self) -> Option<MilliSecondsSinceUnixEpoch> {
+        self.mutes.values().map(|mute| mute.expires_at).min()
+    }
+
+    /// Remove and return the mutes which have expired at `now`.
+    pub(crate) fn take_expired(
+        &mut self,
+        now: MilliSecondsSinceUnixEpoch,
+    ) -> Vec<(OwnedRoomId, TemporaryMute)> {
+        let expired: Vec<OwnedRoomId> = self
+            .mutes
+            .iter()
+            .filter(|(_, mute)| mute.expires_at <= now)
+            .map(|(room_id, _)| room_id.clone())
+            .collect();
+
+        expired.into_iter().filter_map(|room_id| self.mutes.remove_entry(&room_id)).collect()
+    }
+}
+
+/// The time left from `now` until `expires_at`, or zero if it is in the past.
+pub(crate) fn time_until(
+    expires_at: MilliSecondsSinceUnixEpoch,
+    now: MilliSecondsSinceUnixEpoch,
+) -> Duration {
+    Duration::from_millis(u64::from(expires_at.0.saturating_sub(now.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_id, room_id, uint, MilliSecondsSinceUnixEpoch};
+
+    use super::{TemporaryMute, TemporaryMutesContent};
+    use crate::notification_settings::RoomNotificationMode;
+
+    fn mute(expires_at: u32) -> TemporaryMute {
+        TemporaryMute {
+            expires_at: MilliSecondsSinceUnixEpoch(expires_at.into()),
+            previous_mode: Some(RoomNotificationMode::AllMessages),
+        }
+    }
+
+    #[test]
+    fn test_take_expired() {
+        let mut content = TemporaryMutesContent {
+            mutes: [
+                (owned_room_id!("!a:b.c"), mute(10)),
+                (owned_room_id!("!d:e.f"), mute(20)),
+                (owned_room_id!("!g:h.i"), mute(30)),
+            ]
+            .into(),
+        };
+
+        assert_eq!(content.next_expiry(), Some(MilliSecondsSinceUnixEpoch(uint!(10))));
+
+        let expired = content.take_expired(MilliSecondsSinceUnixEpoch(uint!(20)));
+        assert_eq!(expired.len(), 2);
+        assert_eq!(expired[0].0, "!a:b.c");
+        assert_eq!(expired[1].0, "!d:e.f");
+
+        assert_eq!(content.mutes.len(), 1);
+        assert_eq!(content.next_expiry(), Some(MilliSecondsSinceUnixEpoch(uint!(30))));
+        assert_eq!(content.expiry(room_id!("!g:h.i")), Some(MilliSecondsSinceUnixEpoch(uint!(30))));
+        assert!(content.expiry(room_id!("!a:b.c")).is_none());
+    }
+}
@@ -210,6 +210,7 @@ impl OidcBackend for MockImpl {
             Ok(RefreshedSessionTokens {
                 access_token: next_tokens.access_token,
                 refresh_token: next_tokens.refresh_token,
+                expires_in: None,
             })
         }
     }
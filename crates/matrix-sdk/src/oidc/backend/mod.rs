@@ -16,6 +16,8 @@
 //!
 //! Used mostly for testing purposes.
 
+use std::time::Duration;
+
 use mas_oidc_client::{
     requests::authorization_code::{AuthorizationRequestData, AuthorizationValidationData},
     types::{
@@ -38,6 +40,7 @@ pub(crate) mod mock;
 pub(super) struct RefreshedSessionTokens {
     pub access_token: String,
     pub refresh_token: Option<String>,
+    pub expires_in: Option<Duration>,
 }
 
 #[async_trait::async_trait]
@@ -151,6 +151,7 @@ impl OidcBackend for OidcServer {
         .map(|(response, _id_token)| RefreshedSessionTokens {
             access_token: response.access_token,
             refresh_token: response.refresh_token,
+            expires_in: response.expires_in.and_then(|expires_in| expires_in.to_std().ok()),
         })
         .map_err(Into::into)
     }
@@ -232,8 +232,8 @@ pub enum CrossProcessRefreshLockError {
     #[error("the cross-process lock hasn't been set up with `enable_cross_process_refresh_lock")]
     MissingLock,
 
-    /// Cross-process lock was set, but without session callbacks.
-    #[error("reload session callback must be set with Client::set_session_callbacks() for the cross-process lock to work")]
+    /// Cross-process lock was set, but without a session store delegate.
+    #[error("a session store delegate must be set with Client::set_session_store_delegate() for the cross-process lock to work")]
     MissingReloadSession,
 
     /// Session tokens returned by the reload_session callback were not for
@@ -211,7 +211,11 @@ use self::{
         CrossProcessRefreshLockError, CrossProcessRefreshLockGuard, CrossProcessRefreshManager,
     },
 };
-use crate::{authentication::AuthData, client::SessionChange, Client, RefreshTokenError, Result};
+use crate::{
+    authentication::{AuthData, SessionSaveReason},
+    client::SessionChange,
+    Client, RefreshTokenError, Result,
+};
 
 pub(crate) struct OidcCtx {
     /// The authentication server info discovered from the homeserver.
@@ -767,15 +771,15 @@ impl Oidc {
     ) -> Result<(), CrossProcessRefreshLockError> {
         trace!("Handling hash mismatch.");
 
-        let callback = self
+        let delegate = self
             .client
             .inner
             .auth_ctx
-            .reload_session_callback
+            .session_store_delegate
             .get()
             .ok_or(CrossProcessRefreshLockError::MissingReloadSession)?;
 
-        match callback(self.client.clone()) {
+        match delegate.reload_session(&self.client) {
             Ok(tokens) => {
                 let crate::authentication::SessionTokens::Oidc(tokens) = tokens else {
                     return Err(CrossProcessRefreshLockError::InvalidSessionTokens);
@@ -917,6 +921,9 @@ impl Oidc {
         self.client.set_session_meta(session).await.map_err(crate::Error::from)?;
         // At this point the Olm machine has been set up.
 
+        // The session is complete now that the device ID is known.
+        self.client.save_session(SessionSaveReason::Login).await;
+
         // Enable the cross-process lock for refreshes, if needs be.
         self.deferred_enable_cross_process_refresh_lock().await?;
 
@@ -1103,17 +1110,12 @@ impl Oidc {
                     };
 
                     this.set_session_tokens(tokens.clone());
+                    this.client.inner.auth_ctx.set_access_token_lifetime(new_tokens.expires_in);
 
-                    // Call the save_session_callback if set, while the optional lock is being held.
-                    if let Some(save_session_callback) =
-                        this.client.inner.auth_ctx.save_session_callback.get()
-                    {
-                        // Satisfies the save_session_callback invariant: set_session_tokens has
-                        // been called just above.
-                        if let Err(err) = save_session_callback(this.client.clone()).await {
-                            error!("when saving session after refresh: {err}");
-                        }
-                    }
+                    // Save the session while the optional lock is being held. This satisfies
+                    // the invariant of `save_session`: set_session_tokens has been called just
+                    // above.
+                    this.client.save_session(SessionSaveReason::TokensRefreshed).await;
 
                     if let Some(mut lock) = lock {
                         lock.save_in_memory_and_db(&tokens).await?;
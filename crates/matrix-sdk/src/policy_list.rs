@@ -0,0 +1,706 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Moderation policy lists, as defined in [MSC2313].
+//!
+//! A policy list is a room whose `m.policy.rule.*` state events recommend
+//! actions against users, rooms or servers, like banning them. The
+//! [`PolicyLists`] type reads the rules of the lists the user subscribed to,
+//! and can apply them to the rooms the user moderates.
+//!
+//! [MSC2313]: https://github.com/matrix-org/matrix-spec-proposals/pull/2313
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    sync::{Arc, Mutex as StdMutex, RwLock as StdRwLock},
+};
+
+use matrix_sdk_base::deserialized_responses::RawAnySyncOrStrippedState;
+use matrix_sdk_common::time::Clock;
+use ruma::{
+    events::{
+        policy::rule::Recommendation,
+        room::member::{MembershipState, OriginalSyncRoomMemberEvent},
+        AnySyncMessageLikeEvent, AnySyncStateEvent, MessageLikeEventType, StateEventType,
+    },
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedServerName, OwnedUserId, RoomId,
+    RoomOrAliasId, ServerName, UserId,
+};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::{
+    event_handler::EventHandlerDropGuard, Client, Result, Room, RoomMemberships, RoomState,
+};
+
+/// The maximum number of actions kept in the audit log of a [`PolicyLists`].
+const AUDIT_LOG_CAPACITY: usize = 1000;
+
+/// The kind of entity a policy rule applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PolicyRuleKind {
+    /// The rule applies to users, with an `m.policy.rule.user` event.
+    User,
+    /// The rule applies to rooms, with an `m.policy.rule.room` event.
+    Room,
+    /// The rule applies to servers, with an `m.policy.rule.server` event.
+    Server,
+}
+
+impl PolicyRuleKind {
+    /// The event types of the rules of this kind, starting with the one of
+    /// the spec.
+    ///
+    /// The other ones were used before the rules were specified, and are
+    /// still found in old lists.
+    fn event_types(self) -> [&'static str; 3] {
+        match self {
+            Self::User => {
+                ["m.policy.rule.user", "m.room.rule.user", "org.matrix.mjolnir.rule.user"]
+            }
+            Self::Room => {
+                ["m.policy.rule.room", "m.room.rule.room", "org.matrix.mjolnir.rule.room"]
+            }
+            Self::Server => {
+                ["m.policy.rule.server", "m.room.rule.server", "org.matrix.mjolnir.rule.server"]
+            }
+        }
+    }
+
+    fn from_event_type(event_type: &str) -> Option<Self> {
+        [Self::User, Self::Room, Self::Server]
+            .into_iter()
+            .find(|kind| kind.event_types().contains(&event_type))
+    }
+}
+
+/// A rule of a policy list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyRule {
+    /// The ID of the policy list room that contains the rule.
+    pub list_room_id: OwnedRoomId,
+    /// The kind of entity the rule applies to.
+    pub kind: PolicyRuleKind,
+    /// The entity the rule applies to, which can contain the `*` and `?`
+    /// glob wildcards.
+    pub entity: String,
+    /// The action recommended against the entity.
+    pub recommendation: Recommendation,
+    /// The reason of the rule.
+    pub reason: String,
+}
+
+impl PolicyRule {
+    /// Whether the rule recommends banning the entity.
+    pub fn is_ban(&self) -> bool {
+        self.recommendation == Recommendation::Ban
+    }
+
+    /// Whether the rule applies to the given user, either directly or through
+    /// their server.
+    pub fn matches_user(&self, user_id: &UserId) -> bool {
+        match self.kind {
+            PolicyRuleKind::User => glob_matches(&self.entity, user_id.as_str()),
+            PolicyRuleKind::Server => self.matches_server(user_id.server_name()),
+            PolicyRuleKind::Room => false,
+        }
+    }
+
+    /// Whether the rule applies to the given room.
+    pub fn matches_room(&self, room_id: &RoomId) -> bool {
+        self.kind == PolicyRuleKind::Room && glob_matches(&self.entity, room_id.as_str())
+    }
+
+    /// Whether the rule applies to the given server.
+    pub fn matches_server(&self, server_name: &ServerName) -> bool {
+        self.kind == PolicyRuleKind::Server && glob_matches(&self.entity, server_name.as_str())
+    }
+
+    /// Parse the rule from a state event of a policy list.
+    ///
+    /// Returns `None` if the event is not a rule, or if the rule was removed
+    /// by replacing its content with an empty one.
+    fn from_raw(list_room_id: &RoomId, raw: &RawAnySyncOrStrippedState) -> Option<Self> {
+        let event: PolicyRuleEventParts = match raw {
+            RawAnySyncOrStrippedState::Sync(raw) => raw.deserialize_as().ok()?,
+            RawAnySyncOrStrippedState::Stripped(raw) => raw.deserialize_as().ok()?,
+        };
+
+        Some(Self {
+            list_room_id: list_room_id.to_owned(),
+            kind: PolicyRuleKind::from_event_type(&event.event_type)?,
+            entity: event.content.entity?,
+            recommendation: event.content.recommendation?.as_str().into(),
+            reason: event.content.reason.unwrap_or_default(),
+        })
+    }
+}
+
+/// The parts of a policy rule event that are needed for a [`PolicyRule`].
+#[derive(Deserialize)]
+struct PolicyRuleEventParts {
+    #[serde(rename = "type")]
+    event_type: String,
+    content: PolicyRuleContentParts,
+}
+
+/// A removed rule has an empty content.
+#[derive(Deserialize)]
+struct PolicyRuleContentParts {
+    entity: Option<String>,
+    recommendation: Option<String>,
+    reason: Option<String>,
+}
+
+/// Whether the given value matches the glob, where `*` matches any number of
+/// characters and `?` matches exactly one character.
+fn glob_matches(glob: &str, value: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let (mut g, mut v) = (0, 0);
+    // The position of the last `*` in the glob, and of the value when it was
+    // reached, to backtrack when the rest of the glob doesn't match.
+    let mut star = None;
+
+    while v < value.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, v));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                g += 1;
+                v += 1;
+            }
+            _ => match star {
+                Some((star_g, star_v)) => {
+                    g = star_g + 1;
+                    v = star_v + 1;
+                    star = Some((star_g, star_v + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// What to do with the rules of the policy lists in the protected rooms.
+#[derive(Clone, Debug, Default)]
+pub struct PolicyEnforcementOptions {
+    /// The rooms where the rules are applied.
+    pub protected_rooms: Vec<OwnedRoomId>,
+    /// Whether to ban the members of the protected rooms that are matched by
+    /// a ban rule.
+    pub ban_users: bool,
+    /// Whether to redact the new messages sent in the protected rooms by users
+    /// that are matched by a ban rule.
+    pub redact_messages: bool,
+}
+
+/// An action taken when applying a rule of a policy list.
+#[derive(Clone, Debug)]
+pub struct PolicyAction {
+    /// The room where the action was taken.
+    pub room_id: OwnedRoomId,
+    /// What was done.
+    pub kind: PolicyActionKind,
+    /// The rule that caused the action.
+    pub rule: PolicyRule,
+    /// The error returned by the homeserver, if the action failed.
+    pub error: Option<String>,
+    /// When the action was taken.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+}
+
+/// The kind of a [`PolicyAction`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyActionKind {
+    /// A user was banned.
+    Ban {
+        /// The banned user.
+        user_id: OwnedUserId,
+    },
+    /// A message was redacted.
+    Redact {
+        /// The redacted event.
+        event_id: OwnedEventId,
+        /// The sender of the redacted event.
+        sender: OwnedUserId,
+    },
+}
+
+/// The policy lists the current user subscribed to.
+///
+/// The subscriptions are only kept in memory, so they need to be restored
+/// every time a `PolicyLists` is created.
+#[derive(Clone, Debug)]
+pub struct PolicyLists {
+    inner: Arc<PolicyListsInner>,
+}
+
+#[derive(Debug)]
+struct PolicyListsInner {
+    client: Client,
+    list_room_ids: StdRwLock<BTreeSet<OwnedRoomId>>,
+    /// The ban rules of the lists, parsed once until the lists change.
+    ban_rules: StdMutex<BanRulesCache>,
+    /// The most recent actions, up to [`AUDIT_LOG_CAPACITY`].
+    audit_log: StdMutex<VecDeque<PolicyAction>>,
+    actions_sender: broadcast::Sender<PolicyAction>,
+}
+
+#[derive(Debug, Default)]
+struct BanRulesCache {
+    /// Incremented every time the cache is invalidated, so that rules read
+    /// before a change are not cached after it.
+    generation: u64,
+    rules: Option<Arc<[PolicyRule]>>,
+}
+
+impl PolicyLists {
+    /// Create a new `PolicyLists`, without any subscription.
+    pub fn new(client: Client) -> Self {
+        Self {
+            inner: Arc::new(PolicyListsInner {
+                client,
+                list_room_ids: Default::default(),
+                ban_rules: Default::default(),
+                audit_log: Default::default(),
+                actions_sender: broadcast::Sender::new(32),
+            }),
+        }
+    }
+
+    /// Subscribe to a policy list, joining its room if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The ID or an alias of the policy list room.
+    ///
+    /// * `via` - The servers to join the room through.
+    pub async fn subscribe(
+        &self,
+        room_id_or_alias: &RoomOrAliasId,
+        via: &[OwnedServerName],
+    ) -> Result<Room> {
+        let client = &self.inner.client;
+
+        let known_room = <&RoomId>::try_from(room_id_or_alias)
+            .ok()
+            .and_then(|room_id| client.get_room(room_id))
+            .filter(|room| room.state() == RoomState::Joined);
+
+        let room = match known_room {
+            Some(room) => room,
+            None => client.join_room_by_id_or_alias(room_id_or_alias, via).await?,
+        };
+
+        self.inner.list_room_ids.write().unwrap().insert(room.room_id().to_owned());
+        self.invalidate_ban_rules();
+
+        Ok(room)
+    }
+
+    /// Unsubscribe from a policy list.
+    ///
+    /// The room of the list is not left.
+    pub fn unsubscribe(&self, list_room_id: &RoomId) {
+        self.inner.list_room_ids.write().unwrap().remove(list_room_id);
+        self.invalidate_ban_rules();
+    }
+
+    /// The IDs of the rooms of the policy lists the user subscribed to.
+    pub fn list_room_ids(&self) -> Vec<OwnedRoomId> {
+        self.inner.list_room_ids.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Get the rules of all the policy lists the user subscribed to, from the
+    /// state of their rooms.
+    pub async fn rules(&self) -> Result<Vec<PolicyRule>> {
+        let mut rules = Vec::new();
+
+        for list_room_id in self.list_room_ids() {
+            let Some(room) = self.inner.client.get_room(&list_room_id) else {
+                continue;
+            };
+
+            for kind in [PolicyRuleKind::User, PolicyRuleKind::Room, PolicyRuleKind::Server] {
+                for event_type in kind.event_types() {
+                    let events = room.get_state_events(StateEventType::from(event_type)).await?;
+                    rules.extend(
+                        events.iter().filter_map(|raw| PolicyRule::from_raw(&list_room_id, raw)),
+                    );
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+
+    /// Get the rules that apply to the given user, either directly or through
+    /// their server.
+    pub async fn rules_for_user(&self, user_id: &UserId) -> Result<Vec<PolicyRule>> {
+        Ok(self.rules().await?.into_iter().filter(|rule| rule.matches_user(user_id)).collect())
+    }
+
+    /// Get the rules that apply to the given room.
+    pub async fn rules_for_room(&self, room_id: &RoomId) -> Result<Vec<PolicyRule>> {
+        Ok(self.rules().await?.into_iter().filter(|rule| rule.matches_room(room_id)).collect())
+    }
+
+    /// Get the rules that apply to the given server.
+    pub async fn rules_for_server(&self, server_name: &ServerName) -> Result<Vec<PolicyRule>> {
+        Ok(self
+            .rules()
+            .await?
+            .into_iter()
+            .filter(|rule| rule.matches_server(server_name))
+            .collect())
+    }
+
+    /// Ban the members of the protected rooms that are matched by a ban rule.
+    ///
+    /// Does nothing if [`PolicyEnforcementOptions::ban_users`] is not set.
+    /// Returns the actions that were taken, which are also added to the
+    /// [audit log](Self::audit_log).
+    pub async fn enforce(&self, options: &PolicyEnforcementOptions) -> Result<Vec<PolicyAction>> {
+        if !options.ban_users {
+            return Ok(Vec::new());
+        }
+
+        let ban_rules: Vec<_> = self.rules().await?.into_iter().filter(|r| r.is_ban()).collect();
+        let own_user_id = self.inner.client.user_id();
+        let mut actions = Vec::new();
+
+        for room_id in &options.protected_rooms {
+            let Some(room) = self.inner.client.get_room(room_id) else {
+                continue;
+            };
+
+            let members = room.members(RoomMemberships::JOIN | RoomMemberships::INVITE).await?;
+
+            for member in members {
+                let user_id = member.user_id();
+                if Some(user_id) == own_user_id {
+                    continue;
+                }
+
+                if let Some(rule) = ban_rules.iter().find(|rule| rule.matches_user(user_id)) {
+                    actions.push(self.ban(&room, user_id, rule).await);
+                }
+            }
+        }
+
+        Ok(actions)
+    }
+
+    /// Apply the rules of the policy lists automatically, until the returned
+    /// guard is dropped.
+    ///
+    /// The rules are applied to the new members and messages of the protected
+    /// rooms, and to all their members when a rule changes in a policy list.
+    pub fn auto_apply(&self, options: PolicyEnforcementOptions) -> PolicyAutoApplyGuard {
+        let client = &self.inner.client;
+        let options = Arc::new(options);
+
+        // The rules might have changed while they were not applied.
+        self.invalidate_ban_rules();
+
+        let members_handle = client.add_event_handler({
+            let policy_lists = self.clone();
+            let options = options.clone();
+            move |event: OriginalSyncRoomMemberEvent, room: Room| {
+                let policy_lists = policy_lists.clone();
+                let options = options.clone();
+                async move { policy_lists.handle_member_event(event, room, &options).await }
+            }
+        });
+
+        let messages_handle = client.add_event_handler({
+            let policy_lists = self.clone();
+            let options = options.clone();
+            move |event: AnySyncMessageLikeEvent, room: Room| {
+                let policy_lists = policy_lists.clone();
+                let options = options.clone();
+                async move { policy_lists.handle_message_event(event, room, &options).await }
+            }
+        });
+
+        let rules_handle = client.add_event_handler({
+            let policy_lists = self.clone();
+            move |event: AnySyncStateEvent, room: Room| {
+                let policy_lists = policy_lists.clone();
+                let options = options.clone();
+                async move { policy_lists.handle_state_event(event, room, &options).await }
+            }
+        });
+
+        PolicyAutoApplyGuard {
+            _guards: [members_handle, messages_handle, rules_handle]
+                .into_iter()
+                .map(|handle| client.event_handler_drop_guard(handle))
+                .collect(),
+        }
+    }
+
+    /// The actions taken when applying the rules, from the oldest to the
+    /// newest.
+    ///
+    /// Only the 1000 most recent actions are kept.
+    pub fn audit_log(&self) -> Vec<PolicyAction> {
+        self.inner.audit_log.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Subscribe to the actions taken when applying the rules.
+    pub fn subscribe_to_actions(&self) -> broadcast::Receiver<PolicyAction> {
+        self.inner.actions_sender.subscribe()
+    }
+
+    async fn handle_member_event(
+        &self,
+        event: OriginalSyncRoomMemberEvent,
+        room: Room,
+        options: &PolicyEnforcementOptions,
+    ) {
+        if !options.ban_users
+            || !options.protected_rooms.iter().any(|room_id| room_id == room.room_id())
+            || !matches!(event.content.membership, MembershipState::Join | MembershipState::Invite)
+        {
+            return;
+        }
+
+        let Ok(user_id) = UserId::parse(&event.state_key) else {
+            return;
+        };
+
+        match self.ban_rule_for_user(&user_id).await {
+            Ok(Some(rule)) => {
+                self.ban(&room, &user_id, &rule).await;
+            }
+            Ok(None) => {}
+            Err(error) => warn!("Could not get the rules of the policy lists: {error}"),
+        }
+    }
+
+    async fn handle_message_event(
+        &self,
+        event: AnySyncMessageLikeEvent,
+        room: Room,
+        options: &PolicyEnforcementOptions,
+    ) {
+        if !options.redact_messages
+            || !options.protected_rooms.iter().any(|room_id| room_id == room.room_id())
+            || event.original_content().is_none()
+            || event.event_type() == MessageLikeEventType::RoomRedaction
+        {
+            return;
+        }
+
+        let rule = match self.ban_rule_for_user(event.sender()).await {
+            Ok(Some(rule)) => rule,
+            Ok(None) => return,
+            Err(error) => {
+                warn!("Could not get the rules of the policy lists: {error}");
+                return;
+            }
+        };
+
+        let kind = PolicyActionKind::Redact {
+            event_id: event.event_id().to_owned(),
+            sender: event.sender().to_owned(),
+        };
+        let result = room.redact(event.event_id(), Some(&rule.reason), None).await;
+        self.record(room.room_id(), kind, rule, result.err().map(|e| e.to_string()));
+    }
+
+    async fn handle_state_event(
+        &self,
+        event: AnySyncStateEvent,
+        room: Room,
+        options: &PolicyEnforcementOptions,
+    ) {
+        let is_rule = PolicyRuleKind::from_event_type(&event.event_type().to_string()).is_some();
+        if !is_rule || !self.inner.list_room_ids.read().unwrap().contains(room.room_id()) {
+            return;
+        }
+
+        debug!(list_room_id = ?room.room_id(), "A policy rule changed, applying the rules");
+
+        self.invalidate_ban_rules();
+
+        if let Err(error) = self.enforce(options).await {
+            warn!("Could not apply the rules of the policy lists: {error}");
+        }
+    }
+
+    async fn ban_rule_for_user(&self, user_id: &UserId) -> Result<Option<PolicyRule>> {
+        if self.inner.client.user_id() == Some(user_id) {
+            return Ok(None);
+        }
+
+        Ok(self.ban_rules().await?.iter().find(|rule| rule.matches_user(user_id)).cloned())
+    }
+
+    /// Get the ban rules of all the policy lists, parsing them only if they
+    /// changed since the last call.
+    async fn ban_rules(&self) -> Result<Arc<[PolicyRule]>> {
+        let generation = {
+            let cache = self.inner.ban_rules.lock().unwrap();
+
+            if let Some(rules) = &cache.rules {
+                return Ok(rules.clone());
+            }
+
+            cache.generation
+        };
+
+        let rules: Arc<[PolicyRule]> =
+            self.rules().await?.into_iter().filter(|rule| rule.is_ban()).collect();
+
+        let mut cache = self.inner.ban_rules.lock().unwrap();
+        if cache.generation == generation {
+            cache.rules = Some(rules.clone());
+        }
+
+        Ok(rules)
+    }
+
+    /// Forget the cached ban rules, after the policy lists changed.
+    fn invalidate_ban_rules(&self) {
+        let mut cache = self.inner.ban_rules.lock().unwrap();
+        cache.generation += 1;
+        cache.rules = None;
+    }
+
+    async fn ban(&self, room: &Room, user_id: &UserId, rule: &PolicyRule) -> PolicyAction {
+        let result = room.ban_user(user_id, Some(&rule.reason)).await;
+        let kind = PolicyActionKind::Ban { user_id: user_id.to_owned() };
+        self.record(room.room_id(), kind, rule.clone(), result.err().map(|e| e.to_string()))
+    }
+
+    /// Add an action to the audit log and notify the subscribers.
+    fn record(
+        &self,
+        room_id: &RoomId,
+        kind: PolicyActionKind,
+        rule: PolicyRule,
+        error: Option<String>,
+    ) -> PolicyAction {
+        if let Some(error) = &error {
+            warn!(?room_id, ?kind, "Could not apply a policy rule: {error}");
+        }
+
+        let action = PolicyAction {
+            room_id: room_id.to_owned(),
+            kind,
+            rule,
+            error,
+            timestamp: self.inner.client.inner.clock.timestamp(),
+        };
+
+        let mut audit_log = self.inner.audit_log.lock().unwrap();
+        if audit_log.len() == AUDIT_LOG_CAPACITY {
+            audit_log.pop_front();
+        }
+        audit_log.push_back(action.clone());
+        drop(audit_log);
+
+        let _ = self.inner.actions_sender.send(action.clone());
+
+        action
+    }
+}
+
+/// A guard that stops applying the rules of the policy lists automatically
+/// when dropped.
+///
+/// Create one with [`PolicyLists::auto_apply()`].
+#[derive(Debug)]
+pub struct PolicyAutoApplyGuard {
+    _guards: Vec<EventHandlerDropGuard>,
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::async_test;
+    use ruma::{room_id, server_name, user_id};
+
+    use super::{
+        glob_matches, PolicyActionKind, PolicyLists, PolicyRule, PolicyRuleKind, AUDIT_LOG_CAPACITY,
+    };
+    use crate::test_utils::logged_in_client;
+
+    fn rule(kind: PolicyRuleKind, entity: &str) -> PolicyRule {
+        PolicyRule {
+            list_room_id: room_id!("!list:localhost").to_owned(),
+            kind,
+            entity: entity.to_owned(),
+            recommendation: "m.ban".into(),
+            reason: "spam".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("@spam:example.org", "@spam:example.org"));
+        assert!(!glob_matches("@spam:example.org", "@spam:example.com"));
+        assert!(glob_matches("@spam*:example.org", "@spammer:example.org"));
+        assert!(glob_matches("*.example.org", "evil.example.org"));
+        assert!(!glob_matches("*.example.org", "example.org"));
+        assert!(glob_matches("@spam?:*", "@spam1:localhost"));
+        assert!(!glob_matches("@spam?:*", "@spam:localhost"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b*c", "aXbYbZc"));
+    }
+
+    #[test]
+    fn test_rule_matches() {
+        let user_rule = rule(PolicyRuleKind::User, "@spam*:*");
+        assert!(user_rule.is_ban());
+        assert!(user_rule.matches_user(user_id!("@spammer:localhost")));
+        assert!(!user_rule.matches_user(user_id!("@alice:localhost")));
+        assert!(!user_rule.matches_room(room_id!("!spam:localhost")));
+
+        let server_rule = rule(PolicyRuleKind::Server, "*.evil.org");
+        assert!(server_rule.matches_user(user_id!("@alice:matrix.evil.org")));
+        assert!(server_rule.matches_server(server_name!("matrix.evil.org")));
+        assert!(!server_rule.matches_server(server_name!("localhost")));
+
+        let room_rule = rule(PolicyRuleKind::Room, "!spam:*");
+        assert!(room_rule.matches_room(room_id!("!spam:localhost")));
+        assert!(!room_rule.matches_user(user_id!("@spam:localhost")));
+    }
+
+    #[async_test]
+    async fn test_audit_log_is_capped() {
+        let policy_lists = PolicyLists::new(logged_in_client(None).await);
+        let room_id = room_id!("!protected:localhost");
+        let kind = PolicyActionKind::Ban { user_id: user_id!("@spammer:localhost").to_owned() };
+
+        for i in 0..AUDIT_LOG_CAPACITY + 10 {
+            let rule = rule(PolicyRuleKind::User, "@spammer:localhost");
+            policy_lists.record(room_id, kind.clone(), rule, Some(i.to_string()));
+        }
+
+        // The oldest actions were dropped.
+        let audit_log = policy_lists.audit_log();
+        assert_eq!(audit_log.len(), AUDIT_LOG_CAPACITY);
+        assert_eq!(audit_log[0].error.as_deref(), Some("10"));
+        assert_eq!(audit_log.last().unwrap().error, Some((AUDIT_LOG_CAPACITY + 9).to_string()));
+    }
+}
@@ -0,0 +1,364 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level API to manage the pushers of the account.
+
+use ruma::{
+    api::client::push::{set_pusher, Pusher, PusherIds, PusherInit, PusherKind},
+    push::{HttpPusherData, PushFormat},
+    DeviceId, OwnedDeviceId,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{Client, Result};
+
+/// A pusher, with the extra fields of [MSC3881].
+///
+/// [MSC3881]: https://github.com/matrix-org/matrix-spec-proposals/pull/3881
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PusherSettings {
+    /// The pusher.
+    #[serde(flatten)]
+    pub pusher: Pusher,
+
+    /// Whether the homeserver should send notifications to this pusher.
+    ///
+    /// Homeservers which don't support MSC3881 ignore this field, and always
+    /// consider the pusher as enabled.
+    #[serde(rename = "org.matrix.msc3881.enabled", default = "ruma::serde::default_true")]
+    pub enabled: bool,
+
+    /// The device which created the pusher.
+    ///
+    /// It is set by the homeserver, and only returned by the homeservers which
+    /// support MSC3881.
+    #[serde(rename = "org.matrix.msc3881.device_id", default, skip_serializing)]
+    pub device_id: Option<OwnedDeviceId>,
+}
+
+impl PusherSettings {
+    /// Create enabled settings for the given pusher.
+    pub fn new(pusher: Pusher) -> Self {
+        Self { pusher, enabled: true, device_id: None }
+    }
+
+    /// Create the settings of an HTTP pusher.
+    ///
+    /// * `ids` - The push key of the device, and the ID of the application.
+    ///
+    /// * `data` - The URL of the push gateway, and the format of the payload.
+    ///
+    /// * `app_display_name` - The name of the application, to show to the user.
+    ///
+    /// * `device_display_name` - The name of the device, to show to the user.
+    ///
+    /// * `lang` - The preferred language for the notifications, as an ISO
+    ///   language code.
+    pub fn http(
+        ids: PusherIds,
+        data: HttpPusherData,
+        app_display_name: String,
+        device_display_name: String,
+        lang: String,
+    ) -> Self {
+        Self::new(
+            PusherInit {
+                ids,
+                kind: PusherKind::Http(data),
+                app_display_name,
+                device_display_name,
+                profile_tag: None,
+                lang,
+            }
+            .into(),
+        )
+    }
+
+    /// Create the settings of an HTTP pusher which only receives the IDs of
+    /// the room and the event, like the push gateways of UnifiedPush or of
+    /// notification extensions, which fetch the content of the event
+    /// themselves.
+    ///
+    /// See [`PusherSettings::http()`] for the meaning of the arguments.
+    pub fn event_id_only(
+        ids: PusherIds,
+        url: String,
+        app_display_name: String,
+        device_display_name: String,
+        lang: String,
+    ) -> Self {
+        let mut data = HttpPusherData::new(url);
+        data.format = Some(PushFormat::EventIdOnly);
+
+        Self::http(ids, data, app_display_name, device_display_name, lang)
+    }
+
+    /// Set whether the homeserver should send notifications to this pusher.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+}
+
+/// A high-level API to manage the pushers of the client owner's account.
+///
+/// A pusher tells the homeserver where to send the notifications of a device,
+/// e.g. the push gateway of the application.
+#[derive(Debug, Clone)]
+pub struct Pushers {
+    /// The underlying HTTP client.
+    client: Client,
+}
+
+impl Pushers {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Get all the pushers of the account.
+    pub async fn list(&self) -> Result<Vec<PusherSettings>> {
+        let response = self.client.send(msc3881::get_pushers::Request::new(), None).await?;
+        Ok(response.pushers)
+    }
+
+    /// Get the pushers created by the given device.
+    ///
+    /// Only homeservers which support MSC3881 know the device of the pushers,
+    /// so this is always empty with the other homeservers.
+    pub async fn list_for_device(&self, device_id: &DeviceId) -> Result<Vec<PusherSettings>> {
+        let pushers = self.list().await?;
+        Ok(pushers.into_iter().filter(|p| p.device_id.as_deref() == Some(device_id)).collect())
+    }
+
+    /// Register a pusher, or update the pusher with the same IDs.
+    ///
+    /// The other users of the homeserver with a pusher with the same IDs lose
+    /// it, since the push key identifies a single device.
+    pub async fn set(&self, settings: PusherSettings) -> Result<()> {
+        let request = msc3881::set_pusher::Request::new(settings);
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+
+    /// Enable or disable the pusher with the given IDs.
+    ///
+    /// Returns `false` if the account doesn't have such a pusher.
+    pub async fn set_enabled(&self, ids: &PusherIds, enabled: bool) -> Result<bool> {
+        let Some(settings) = self
+            .list()
+            .await?
+            .into_iter()
+            .find(|p| p.pusher.ids.pushkey == ids.pushkey && p.pusher.ids.app_id == ids.app_id)
+        else {
+            return Ok(false);
+        };
+
+        self.set(settings.enabled(enabled)).await?;
+        Ok(true)
+    }
+
+    /// Delete the pusher with the given IDs.
+    pub async fn delete(&self, ids: PusherIds) -> Result<()> {
+        let request = set_pusher::v3::Request::delete(ids);
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+}
+
+/// The pushers endpoints, with the fields of MSC3881.
+mod msc3881 {
+    pub mod get_pushers {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata,
+        };
+
+        use crate::pushers::PusherSettings;
+
+        const METADATA: Metadata = metadata! {
+            method: GET,
+            rate_limited: false,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/client/r0/pushers",
+                1.1 => "/_matrix/client/v3/pushers",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Request {}
+
+        #[response(error = ruma::api::client::Error)]
+        pub struct Response {
+            pub pushers: Vec<PusherSettings>,
+        }
+
+        impl Request {
+            pub fn new() -> Self {
+                Self {}
+            }
+        }
+    }
+
+    pub mod set_pusher {
+        use ruma::{
+            api::{request, response, Metadata},
+            metadata,
+        };
+
+        use crate::pushers::PusherSettings;
+
+        const METADATA: Metadata = metadata! {
+            method: POST,
+            rate_limited: true,
+            authentication: AccessToken,
+            history: {
+                1.0 => "/_matrix/client/r0/pushers/set",
+                1.1 => "/_matrix/client/v3/pushers/set",
+            }
+        };
+
+        #[request(error = ruma::api::client::Error)]
+        pub struct Request {
+            #[serde(flatten)]
+            pub settings: PusherSettings,
+        }
+
+        #[response(error = ruma::api::client::Error)]
+        #[derive(Default)]
+        pub struct Response {}
+
+        impl Request {
+            pub fn new(settings: PusherSettings) -> Self {
+                Self { settings }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use matrix_sdk_test::async_test;
+    use ruma::{
+        api::client::push::{PusherIds, PusherKind},
+        device_id,
+        push::PushFormat,
+    };
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_partial_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::PusherSettings;
+    use crate::test_utils::logged_in_client;
+
+    fn ids() -> PusherIds {
+        PusherIds::new("pushkey".to_owned(), "org.example.app".to_owned())
+    }
+
+    #[async_test]
+    async fn test_set_event_id_only_pusher() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("POST"))
+            .and(path("/_matrix/client/r0/pushers/set"))
+            .and(body_partial_json(json!({
+                "pushkey": "pushkey",
+                "app_id": "org.example.app",
+                "kind": "http",
+                "data": {
+                    "url": "https://push.example.org/_matrix/push/v1/notify",
+                    "format": "event_id_only",
+                },
+                "org.matrix.msc3881.enabled": false,
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let settings = PusherSettings::event_id_only(
+            ids(),
+            "https://push.example.org/_matrix/push/v1/notify".to_owned(),
+            "App".to_owned(),
+            "Device".to_owned(),
+            "en".to_owned(),
+        )
+        .enabled(false);
+
+        client.pushers().set(settings).await.unwrap();
+    }
+
+    #[async_test]
+    async fn test_list_pushers() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/pushers"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "pushers": [
+                    {
+                        "pushkey": "pushkey",
+                        "app_id": "org.example.app",
+                        "app_display_name": "App",
+                        "device_display_name": "Device",
+                        "lang": "en",
+                        "kind": "http",
+                        "data": {
+                            "url": "https://push.example.org/_matrix/push/v1/notify",
+                            "format": "event_id_only",
+                        },
+                        "org.matrix.msc3881.enabled": false,
+                        "org.matrix.msc3881.device_id": "DEVICEID",
+                    },
+                    {
+                        "pushkey": "other_pushkey",
+                        "app_id": "org.example.app",
+                        "app_display_name": "App",
+                        "device_display_name": "Other device",
+                        "lang": "en",
+                        "kind": "email",
+                        "data": {},
+                    },
+                ]
+            })))
+            .mount(&server)
+            .await;
+
+        let pushers = client.pushers().list().await.unwrap();
+        assert_eq!(pushers.len(), 2);
+
+        assert_eq!(pushers[0].pusher.ids.pushkey, "pushkey");
+        assert!(!pushers[0].enabled);
+        assert_eq!(pushers[0].device_id.as_deref(), Some(device_id!("DEVICEID")));
+        assert_matches!(
+            &pushers[0].pusher.kind,
+            PusherKind::Http(data) => {
+                assert_eq!(data.format, Some(PushFormat::EventIdOnly));
+            }
+        );
+
+        // Pushers are enabled by default.
+        assert!(pushers[1].enabled);
+        assert!(pushers[1].device_id.is_none());
+
+        let pushers = client.pushers().list_for_device(device_id!("DEVICEID")).await.unwrap();
+        assert_eq!(pushers.len(), 1);
+    }
+}
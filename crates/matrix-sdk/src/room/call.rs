@@ -0,0 +1,238 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use matrix_sdk_common::time::Clock;
+use ruma::{
+    events::{
+        call::{invite::CallInviteEventContent, member::Membership},
+        AnySyncTimelineEvent, Mentions,
+    },
+    serde::Raw,
+    MilliSecondsSinceUnixEpoch, OwnedDeviceId, OwnedEventId, OwnedRoomId, OwnedUserId, OwnedVoipId,
+    RoomId, UInt, UserId,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue as RawJsonValue;
+
+/// How long a call membership sent by [`Room::join_room_call`] stays valid if
+/// it is not refreshed with [`Room::refresh_room_call_membership`].
+///
+/// [`Room::join_room_call`]: super::Room::join_room_call
+/// [`Room::refresh_room_call_membership`]: super::Room::refresh_room_call_membership
+pub const CALL_MEMBERSHIP_EXPIRATION: Duration = Duration::from_secs(60 * 60);
+
+/// A call ongoing in a room, as advertised by the `m.call.member` state events
+/// of its participants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveCall {
+    /// The participants of the call, ordered from the oldest membership to
+    /// the newest.
+    ///
+    /// A user appears once per device they joined the call with.
+    pub participants: Vec<CallParticipant>,
+}
+
+impl ActiveCall {
+    /// Build the call from the active room call memberships of a room.
+    ///
+    /// Returns `None` if there are no memberships.
+    pub(super) fn from_memberships<'a>(
+        memberships: impl IntoIterator<Item = (OwnedUserId, &'a Membership)>,
+    ) -> Option<Self> {
+        let participants: Vec<_> = memberships
+            .into_iter()
+            .map(|(user_id, membership)| CallParticipant::new(user_id, membership))
+            .collect();
+
+        (!participants.is_empty()).then_some(Self { participants })
+    }
+
+    /// When the call started, i.e. when its oldest participant joined.
+    pub fn started_at(&self) -> Option<MilliSecondsSinceUnixEpoch> {
+        self.participants.iter().filter_map(|p| p.joined_at).min()
+    }
+}
+
+/// A participant of an [`ActiveCall`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallParticipant {
+    /// The ID of the user.
+    pub user_id: OwnedUserId,
+    /// The ID of the device the user joined the call with.
+    pub device_id: OwnedDeviceId,
+    /// When the user joined the call.
+    pub joined_at: Option<MilliSecondsSinceUnixEpoch>,
+    /// When the membership of the user expires, unless it is refreshed.
+    pub expires_at: Option<MilliSecondsSinceUnixEpoch>,
+}
+
+impl CallParticipant {
+    fn new(user_id: OwnedUserId, membership: &Membership) -> Self {
+        let joined_at = membership.created_ts;
+        let expires_at = joined_at
+            .and_then(|ts| ts.to_system_time())
+            .and_then(|time| time.checked_add(membership.expires))
+            .and_then(MilliSecondsSinceUnixEpoch::from_system_time);
+
+        Self { user_id, device_id: membership.device_id.as_str().into(), joined_at, expires_at }
+    }
+}
+
+/// How long a ring notification makes the devices ring, if it doesn't specify
+/// its lifetime.
+const DEFAULT_RING_LIFETIME: Duration = Duration::from_secs(60);
+
+/// A call that should make the current device ring.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncomingCall {
+    /// The ID of the room of the call.
+    pub room_id: OwnedRoomId,
+    /// The ID of the event that started the ringing.
+    pub event_id: OwnedEventId,
+    /// The user calling.
+    pub caller: OwnedUserId,
+    /// The kind of call.
+    pub kind: IncomingCallKind,
+    /// When the event that started the ringing was sent.
+    pub sent_at: MilliSecondsSinceUnixEpoch,
+    /// When the device should stop ringing, if the call was not answered.
+    pub expires_at: MilliSecondsSinceUnixEpoch,
+}
+
+/// The kind of an [`IncomingCall`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncomingCallKind {
+    /// A 1:1 call started with an `m.call.invite` event.
+    Legacy {
+        /// The ID of the call.
+        call_id: OwnedVoipId,
+        /// Whether the offer of the call contains a video stream.
+        is_video: bool,
+    },
+
+    /// A MatrixRTC call of the room, announced with a ring notification as
+    /// defined in [MSC4075].
+    ///
+    /// [MSC4075]: https://github.com/matrix-org/matrix-spec-proposals/pull/4075
+    Ring {
+        /// The ID of the call, which is empty for the call of the room.
+        call_id: String,
+    },
+}
+
+impl IncomingCall {
+    /// Detect whether the given event should make the current device ring.
+    ///
+    /// Returns `None` if the event is not a call invite or a ring
+    /// notification, if it was sent by the current user, or if it is
+    /// addressed to other users.
+    ///
+    /// *Note*: This method doesn't check whether the call already expired,
+    /// use [`IncomingCall::is_expired()`] for that.
+    pub fn from_raw_event(
+        room_id: &RoomId,
+        event: &Raw<AnySyncTimelineEvent>,
+        own_user_id: &UserId,
+    ) -> Option<Self> {
+        let event = event.deserialize_as::<CallEventParts>().ok()?;
+
+        if &*event.sender == own_user_id {
+            return None;
+        }
+
+        let (kind, lifetime) = match event.event_type.as_str() {
+            "m.call.invite" => {
+                let content: CallInviteEventContent =
+                    serde_json::from_str(event.content.get()).ok()?;
+
+                if content.invitee.as_deref().is_some_and(|invitee| invitee != own_user_id) {
+                    return None;
+                }
+
+                let kind = IncomingCallKind::Legacy {
+                    is_video: content.offer.sdp.contains("m=video"),
+                    call_id: content.call_id,
+                };
+                (kind, Duration::from_millis(content.lifetime.into()))
+            }
+            "m.call.notify" | "org.matrix.msc4075.call.notify" => {
+                let content: CallNotifyContent = serde_json::from_str(event.content.get()).ok()?;
+
+                if content.application != "m.call" || content.notify_type != "ring" {
+                    return None;
+                }
+                if content.mentions.is_some_and(|mentions| {
+                    !mentions.room && !mentions.user_ids.contains(own_user_id)
+                }) {
+                    return None;
+                }
+
+                let lifetime = content.lifetime.map_or(DEFAULT_RING_LIFETIME, |lifetime| {
+                    Duration::from_millis(lifetime.into())
+                });
+                (IncomingCallKind::Ring { call_id: content.call_id }, lifetime)
+            }
+            _ => return None,
+        };
+
+        let lifetime = UInt::new_saturating(lifetime.as_millis().try_into().unwrap_or(u64::MAX));
+        let expires_at =
+            MilliSecondsSinceUnixEpoch(event.origin_server_ts.0.saturating_add(lifetime));
+
+        Some(Self {
+            room_id: room_id.to_owned(),
+            event_id: event.event_id,
+            caller: event.sender,
+            kind,
+            sent_at: event.origin_server_ts,
+            expires_at,
+        })
+    }
+
+    /// Whether the device should have stopped ringing for this call,
+    /// according to the given clock.
+    ///
+    /// The clock of the client is available with [`Client::clock()`].
+    ///
+    /// [`Client::clock()`]: crate::Client::clock
+    pub fn is_expired(&self, clock: &impl Clock) -> bool {
+        self.expires_at < clock.timestamp()
+    }
+}
+
+/// The parts of a timeline event needed to detect an incoming call.
+#[derive(Deserialize)]
+struct CallEventParts {
+    event_id: OwnedEventId,
+    sender: OwnedUserId,
+    origin_server_ts: MilliSecondsSinceUnixEpoch,
+    #[serde(rename = "type")]
+    event_type: String,
+    content: Box<RawJsonValue>,
+}
+
+/// The content of a call notification event, which is not supported by Ruma
+/// yet.
+#[derive(Deserialize)]
+struct CallNotifyContent {
+    application: String,
+    #[serde(default)]
+    call_id: String,
+    notify_type: String,
+    #[serde(rename = "m.mentions")]
+    mentions: Option<Mentions>,
+    lifetime: Option<UInt>,
+}
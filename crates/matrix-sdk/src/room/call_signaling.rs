@@ -0,0 +1,527 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signalling of 1:1 VoIP calls with `m.call.*` room events, following the
+//! version 1 semantics introduced by [MSC2746].
+//!
+//! [MSC2746]: https://github.com/matrix-org/matrix-spec-proposals/pull/2746
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use matrix_sdk_common::time::Clock;
+use ruma::{
+    events::{
+        call::{
+            answer::CallAnswerEventContent,
+            candidates::{CallCandidatesEventContent, Candidate},
+            hangup::{CallHangupEventContent, Reason},
+            invite::CallInviteEventContent,
+            reject::CallRejectEventContent,
+            select_answer::CallSelectAnswerEventContent,
+            SessionDescription,
+        },
+        AnySyncMessageLikeEvent, SyncMessageLikeEvent,
+    },
+    MilliSecondsSinceUnixEpoch, OwnedUserId, OwnedVoipId, UInt, UserId, VoipId, VoipVersionId,
+};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::{error::WrongCallState, event_handler::EventHandlerDropGuard, Error, Result, Room};
+
+/// The state of a 1:1 call, from the point of view of the current device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallSignalingState {
+    /// The current device sent an invite and waits for an answer.
+    Inviting,
+
+    /// The current device received an invite that was not answered yet.
+    Ringing,
+
+    /// The current device answered the invite and waits for the caller to
+    /// select an answer.
+    Answered,
+
+    /// The call is established.
+    Connected {
+        /// The party ID of the other side of the call, if it uses version 1
+        /// of the signalling.
+        opponent_party_id: Option<OwnedVoipId>,
+    },
+
+    /// Another device of the current user answered or rejected the call.
+    HandledElsewhere,
+
+    /// The call was rejected or hung up.
+    Ended,
+}
+
+impl CallSignalingState {
+    /// Whether the call is over for the current device.
+    pub fn is_terminated(&self) -> bool {
+        matches!(self, Self::HandledElsewhere | Self::Ended)
+    }
+}
+
+/// The content of a [`CallSignalingEvent`].
+#[derive(Clone, Debug)]
+pub enum CallSignalingEventContent {
+    /// An `m.call.invite` event.
+    Invite(CallInviteEventContent),
+    /// An `m.call.answer` event.
+    Answer(CallAnswerEventContent),
+    /// An `m.call.candidates` event.
+    Candidates(CallCandidatesEventContent),
+    /// An `m.call.select_answer` event.
+    SelectAnswer(CallSelectAnswerEventContent),
+    /// An `m.call.reject` event.
+    Reject(CallRejectEventContent),
+    /// An `m.call.hangup` event.
+    Hangup(CallHangupEventContent),
+}
+
+impl CallSignalingEventContent {
+    /// The ID of the call this event belongs to.
+    pub fn call_id(&self) -> &VoipId {
+        match self {
+            Self::Invite(c) => &c.call_id,
+            Self::Answer(c) => &c.call_id,
+            Self::Candidates(c) => &c.call_id,
+            Self::SelectAnswer(c) => &c.call_id,
+            Self::Reject(c) => &c.call_id,
+            Self::Hangup(c) => &c.call_id,
+        }
+    }
+
+    /// The party ID of the sender of this event, if it uses version 1 of the
+    /// signalling.
+    pub fn party_id(&self) -> Option<&VoipId> {
+        match self {
+            Self::Invite(c) => c.party_id.as_deref(),
+            Self::Answer(c) => c.party_id.as_deref(),
+            Self::Candidates(c) => c.party_id.as_deref(),
+            Self::SelectAnswer(c) => Some(&c.party_id),
+            Self::Reject(c) => Some(&c.party_id),
+            Self::Hangup(c) => c.party_id.as_deref(),
+        }
+    }
+
+    fn from_sync_event(event: &AnySyncMessageLikeEvent) -> Option<Self> {
+        Some(match event {
+            AnySyncMessageLikeEvent::CallInvite(SyncMessageLikeEvent::Original(ev)) => {
+                Self::Invite(ev.content.clone())
+            }
+            AnySyncMessageLikeEvent::CallAnswer(SyncMessageLikeEvent::Original(ev)) => {
+                Self::Answer(ev.content.clone())
+            }
+            AnySyncMessageLikeEvent::CallCandidates(SyncMessageLikeEvent::Original(ev)) => {
+                Self::Candidates(ev.content.clone())
+            }
+            AnySyncMessageLikeEvent::CallSelectAnswer(SyncMessageLikeEvent::Original(ev)) => {
+                Self::SelectAnswer(ev.content.clone())
+            }
+            AnySyncMessageLikeEvent::CallReject(SyncMessageLikeEvent::Original(ev)) => {
+                Self::Reject(ev.content.clone())
+            }
+            AnySyncMessageLikeEvent::CallHangup(SyncMessageLikeEvent::Original(ev)) => {
+                Self::Hangup(ev.content.clone())
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A signalling event received by a [`CallSignalingSession`].
+#[derive(Clone, Debug)]
+pub struct CallSignalingEvent {
+    /// The sender of the event.
+    pub sender: OwnedUserId,
+    /// The content of the event.
+    pub content: CallSignalingEventContent,
+    /// The state of the call after this event was processed.
+    pub state: CallSignalingState,
+}
+
+#[derive(Debug)]
+struct TrackedCall {
+    state: CallSignalingState,
+    version: VoipVersionId,
+}
+
+#[derive(Debug)]
+struct SessionInner {
+    room: Room,
+    party_id: OwnedVoipId,
+    calls: StdMutex<BTreeMap<OwnedVoipId, TrackedCall>>,
+    sender: broadcast::Sender<CallSignalingEvent>,
+}
+
+/// Tracks the 1:1 VoIP calls of a room and sends their signalling events.
+///
+/// The session listens to the `m.call.*` events received by the sync for as
+/// long as it is alive, updates the [`CallSignalingState`] of each call
+/// accordingly and forwards the relevant events to its subscribers. Events
+/// sent by the session itself, invites addressed to other users and expired
+/// invites are ignored.
+///
+/// When an answer to an invite of the session is received, the session
+/// selects it automatically.
+///
+/// Create one with [`Room::call_signaling_session()`].
+#[derive(Debug)]
+pub struct CallSignalingSession {
+    inner: Arc<SessionInner>,
+    _event_handler_guard: EventHandlerDropGuard,
+}
+
+impl CallSignalingSession {
+    pub(super) fn new(room: Room) -> Self {
+        let inner = Arc::new(SessionInner {
+            room,
+            party_id: VoipId::new(),
+            calls: Default::default(),
+            sender: broadcast::Sender::new(32),
+        });
+
+        let handler_inner = inner.clone();
+        let handle = inner.room.add_event_handler(move |event: AnySyncMessageLikeEvent| {
+            let inner = handler_inner.clone();
+            async move { inner.handle_event(event).await }
+        });
+        let guard = inner.room.client().event_handler_drop_guard(handle);
+
+        Self { inner, _event_handler_guard: guard }
+    }
+
+    /// The party ID of the current device in the calls of this session.
+    pub fn party_id(&self) -> &VoipId {
+        &self.inner.party_id
+    }
+
+    /// The state of the call with the given ID, if it is known to this
+    /// session.
+    pub fn state(&self, call_id: &VoipId) -> Option<CallSignalingState> {
+        self.inner.state(call_id)
+    }
+
+    /// Subscribe to the signalling events received for the calls of this
+    /// room.
+    pub fn subscribe(&self) -> broadcast::Receiver<CallSignalingEvent> {
+        self.inner.sender.subscribe()
+    }
+
+    /// Start a new call by sending an invite.
+    ///
+    /// Returns the ID of the new call.
+    ///
+    /// # Arguments
+    ///
+    /// * `invitee` - The user that should answer the call. If it is `None`, any
+    ///   member of the room can answer it.
+    ///
+    /// * `offer` - The SDP offer of the session.
+    ///
+    /// * `lifetime` - How long the invite is valid for.
+    pub async fn invite(
+        &self,
+        invitee: Option<OwnedUserId>,
+        offer: SessionDescription,
+        lifetime: Duration,
+    ) -> Result<OwnedVoipId> {
+        let call_id = VoipId::new();
+        let lifetime = UInt::new_saturating(lifetime.as_millis().try_into().unwrap_or(u64::MAX));
+
+        let mut content = CallInviteEventContent::version_1(
+            call_id.clone(),
+            self.inner.party_id.clone(),
+            lifetime,
+            offer,
+        );
+        content.invitee = invitee;
+
+        self.inner.set_state(&call_id, VoipVersionId::V1, CallSignalingState::Inviting);
+        self.inner.room.send(content).await?;
+
+        Ok(call_id)
+    }
+
+    /// Answer the call with the given ID.
+    ///
+    /// The call must be [`CallSignalingState::Ringing`].
+    pub async fn answer(&self, call_id: &VoipId, answer: SessionDescription) -> Result<()> {
+        let version = self.inner.ensure_state(call_id, "ringing", |state| {
+            matches!(state, CallSignalingState::Ringing)
+        })?;
+
+        let content = CallAnswerEventContent::version_1(
+            answer,
+            call_id.to_owned(),
+            self.inner.party_id.clone(),
+        );
+        self.inner.room.send(content).await?;
+
+        // There is no answer selection with version 0.
+        let state = if version == VoipVersionId::V0 {
+            CallSignalingState::Connected { opponent_party_id: None }
+        } else {
+            CallSignalingState::Answered
+        };
+        self.inner.set_state(call_id, version, state);
+
+        Ok(())
+    }
+
+    /// Reject the call with the given ID.
+    ///
+    /// The call must be [`CallSignalingState::Ringing`].
+    pub async fn reject(&self, call_id: &VoipId) -> Result<()> {
+        let version = self.inner.ensure_state(call_id, "ringing", |state| {
+            matches!(state, CallSignalingState::Ringing)
+        })?;
+
+        let content =
+            CallRejectEventContent::version_1(call_id.to_owned(), self.inner.party_id.clone());
+        self.inner.room.send(content).await?;
+        self.inner.set_state(call_id, version, CallSignalingState::Ended);
+
+        Ok(())
+    }
+
+    /// Send ICE candidates for the call with the given ID.
+    ///
+    /// The call must have been started or answered by this session, and must
+    /// not be terminated.
+    pub async fn send_candidates(
+        &self,
+        call_id: &VoipId,
+        candidates: Vec<Candidate>,
+    ) -> Result<()> {
+        self.inner.ensure_state(call_id, "inviting, answered or connected", |state| {
+            matches!(
+                state,
+                CallSignalingState::Inviting
+                    | CallSignalingState::Answered
+                    | CallSignalingState::Connected { .. }
+            )
+        })?;
+
+        let content = CallCandidatesEventContent::version_1(
+            call_id.to_owned(),
+            self.inner.party_id.clone(),
+            candidates,
+        );
+        self.inner.room.send(content).await?;
+
+        Ok(())
+    }
+
+    /// Hang up the call with the given ID.
+    ///
+    /// The call must not be terminated.
+    pub async fn hangup(&self, call_id: &VoipId, reason: Reason) -> Result<()> {
+        let version =
+            self.inner.ensure_state(call_id, "not terminated", |state| !state.is_terminated())?;
+
+        let content = CallHangupEventContent::version_1(
+            call_id.to_owned(),
+            self.inner.party_id.clone(),
+            reason,
+        );
+        self.inner.room.send(content).await?;
+        self.inner.set_state(call_id, version, CallSignalingState::Ended);
+
+        Ok(())
+    }
+}
+
+impl SessionInner {
+    fn state(&self, call_id: &VoipId) -> Option<CallSignalingState> {
+        self.calls.lock().unwrap().get(call_id).map(|call| call.state.clone())
+    }
+
+    fn set_state(&self, call_id: &VoipId, version: VoipVersionId, state: CallSignalingState) {
+        self.calls.lock().unwrap().insert(call_id.to_owned(), TrackedCall { state, version });
+    }
+
+    /// Check that the call with the given ID is in one of the expected states
+    /// and return its version.
+    fn ensure_state(
+        &self,
+        call_id: &VoipId,
+        expected: &'static str,
+        is_expected: impl FnOnce(&CallSignalingState) -> bool,
+    ) -> Result<VoipVersionId> {
+        let calls = self.calls.lock().unwrap();
+        let call = calls.get(call_id);
+
+        match call {
+            Some(call) if is_expected(&call.state) => Ok(call.version.clone()),
+            _ => Err(Error::WrongCallState(WrongCallState::new(
+                expected,
+                call.map(|call| call.state.clone()),
+            ))),
+        }
+    }
+
+    async fn handle_event(&self, event: AnySyncMessageLikeEvent) {
+        let Some(content) = CallSignalingEventContent::from_sync_event(&event) else {
+            return;
+        };
+
+        // Ignore the remote echoes of the events of this session.
+        if content.party_id() == Some(&*self.party_id) {
+            return;
+        }
+
+        let sender = event.sender();
+        let own_user_id = self.room.own_user_id();
+
+        let update = if let CallSignalingEventContent::Invite(invite) = &content {
+            self.process_invite(invite, sender, own_user_id, event.origin_server_ts())
+        } else {
+            self.process_call_event(&content, sender == own_user_id)
+        };
+        let Some((state, selected_party_id)) = update else {
+            return;
+        };
+
+        if let Some(selected_party_id) = selected_party_id {
+            let call_id = content.call_id();
+            let select_answer = CallSelectAnswerEventContent::version_1(
+                call_id.to_owned(),
+                self.party_id.clone(),
+                selected_party_id,
+            );
+
+            if let Err(error) = self.room.send(select_answer).await {
+                warn!(%call_id, "Failed to select the answer: {error}");
+            }
+        }
+
+        let _ = self.sender.send(CallSignalingEvent { sender: sender.to_owned(), content, state });
+    }
+
+    /// Start tracking the call of the given invite, if it is for the current
+    /// device.
+    fn process_invite(
+        &self,
+        invite: &CallInviteEventContent,
+        sender: &UserId,
+        own_user_id: &UserId,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+    ) -> Option<(CallSignalingState, Option<OwnedVoipId>)> {
+        // Invites sent by other devices of the current user, or addressed to
+        // other users, are not for this device.
+        if sender == own_user_id
+            || invite.invitee.as_deref().is_some_and(|invitee| invitee != own_user_id)
+        {
+            return None;
+        }
+
+        let expires_at = origin_server_ts.0.saturating_add(invite.lifetime);
+        if expires_at < self.room.client.inner.clock.timestamp().0 {
+            return None;
+        }
+
+        let mut calls = self.calls.lock().unwrap();
+        if calls.contains_key(&invite.call_id) {
+            return None;
+        }
+
+        let state = CallSignalingState::Ringing;
+        calls.insert(
+            invite.call_id.clone(),
+            TrackedCall { state: state.clone(), version: invite.version.clone() },
+        );
+
+        Some((state, None))
+    }
+
+    /// Update the state of a tracked call with the given event.
+    ///
+    /// Returns the new state of the call if the event is relevant for the
+    /// current device, and the party ID of the answer to select, if any.
+    fn process_call_event(
+        &self,
+        content: &CallSignalingEventContent,
+        from_own_user: bool,
+    ) -> Option<(CallSignalingState, Option<OwnedVoipId>)> {
+        let mut calls = self.calls.lock().unwrap();
+        let call = calls.get_mut(content.call_id())?;
+
+        if call.state.is_terminated() {
+            return None;
+        }
+
+        let mut selected_party_id = None;
+
+        let state = match (content, &call.state) {
+            // The first answer wins, the following ones are ignored.
+            (CallSignalingEventContent::Answer(answer), CallSignalingState::Inviting) => {
+                if answer.version != VoipVersionId::V0 {
+                    selected_party_id = answer.party_id.clone();
+                }
+                CallSignalingState::Connected { opponent_party_id: answer.party_id.clone() }
+            }
+            (CallSignalingEventContent::Answer(_), CallSignalingState::Ringing)
+                if from_own_user =>
+            {
+                CallSignalingState::HandledElsewhere
+            }
+
+            (CallSignalingEventContent::SelectAnswer(select), CallSignalingState::Answered) => {
+                if select.selected_party_id == self.party_id {
+                    CallSignalingState::Connected {
+                        opponent_party_id: Some(select.party_id.clone()),
+                    }
+                } else {
+                    CallSignalingState::HandledElsewhere
+                }
+            }
+            (CallSignalingEventContent::SelectAnswer(_), CallSignalingState::Ringing) => {
+                CallSignalingState::HandledElsewhere
+            }
+
+            (CallSignalingEventContent::Reject(_), CallSignalingState::Inviting)
+                if !from_own_user =>
+            {
+                CallSignalingState::Ended
+            }
+            (CallSignalingEventContent::Reject(_), CallSignalingState::Ringing)
+                if from_own_user =>
+            {
+                CallSignalingState::HandledElsewhere
+            }
+
+            (CallSignalingEventContent::Hangup(_), _) => CallSignalingState::Ended,
+
+            // Once the call is established, only the candidates of the
+            // selected party are relevant.
+            (
+                CallSignalingEventContent::Candidates(candidates),
+                CallSignalingState::Connected { opponent_party_id: Some(opponent_party_id) },
+            ) if candidates.party_id.as_ref() != Some(opponent_party_id) => return None,
+            (CallSignalingEventContent::Candidates(_), state) => state.clone(),
+
+            _ => return None,
+        };
+
+        call.state = state.clone();
+        Some((state, selected_party_id))
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Endpoints of [MSC4140] to send and update delayed events, which are not
+//! supported by Ruma yet.
+//!
+//! [MSC4140]: https://github.com/matrix-org/matrix-spec-proposals/pull/4140
+
+use serde::{Deserialize, Serialize};
+
+/// What to do with a delayed event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateDelayedEventAction {
+    /// Cancel the delayed event, so it is never sent.
+    Cancel,
+    /// Restart the timeout of the delayed event.
+    Restart,
+    /// Send the delayed event immediately.
+    Send,
+}
+
+pub(crate) mod send_message_event {
+    use ruma::{
+        api::{request, response, Metadata},
+        events::AnyMessageLikeEventContent,
+        metadata,
+        serde::Raw,
+        OwnedRoomId, OwnedTransactionId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: PUT,
+        rate_limited: false,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/r0/rooms/:room_id/send/:event_type/:txn_id",
+            1.1 => "/_matrix/client/v3/rooms/:room_id/send/:event_type/:txn_id",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id: OwnedRoomId,
+        #[ruma_api(path)]
+        pub event_type: String,
+        #[ruma_api(path)]
+        pub txn_id: OwnedTransactionId,
+        #[ruma_api(query)]
+        #[serde(rename = "org.matrix.msc4140.delay", skip_serializing_if = "Option::is_none")]
+        pub delay: Option<u64>,
+        #[ruma_api(query)]
+        #[serde(
+            rename = "org.matrix.msc4140.parent_delay_id",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub parent_delay_id: Option<String>,
+        #[ruma_api(body)]
+        pub body: Raw<AnyMessageLikeEventContent>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub delay_id: String,
+    }
+}
+
+pub(crate) mod send_state_event {
+    use ruma::{
+        api::{request, response, Metadata},
+        events::AnyStateEventContent,
+        metadata,
+        serde::Raw,
+        OwnedRoomId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: PUT,
+        rate_limited: false,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/r0/rooms/:room_id/state/:event_type/:state_key",
+            1.1 => "/_matrix/client/v3/rooms/:room_id/state/:event_type/:state_key",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id: OwnedRoomId,
+        #[ruma_api(path)]
+        pub event_type: String,
+        #[ruma_api(path)]
+        pub state_key: String,
+        #[ruma_api(query)]
+        #[serde(rename = "org.matrix.msc4140.delay", skip_serializing_if = "Option::is_none")]
+        pub delay: Option<u64>,
+        #[ruma_api(query)]
+        #[serde(
+            rename = "org.matrix.msc4140.parent_delay_id",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub parent_delay_id: Option<String>,
+        #[ruma_api(body)]
+        pub body: Raw<AnyStateEventContent>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub delay_id: String,
+    }
+}
+
+pub(crate) mod update {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata,
+    };
+
+    use super::UpdateDelayedEventAction;
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/client/unstable/org.matrix.msc4140/delayed_events/:delay_id",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub delay_id: String,
+        pub action: UpdateDelayedEventAction,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    #[derive(Default)]
+    pub struct Response {}
+}
@@ -0,0 +1,181 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for rooms joined with a partial state, with faster joins
+//! ([MSC3706]).
+//!
+//! When a homeserver joins a room over federation with a faster join, it only
+//! receives the state it needs to participate in the room and fetches the
+//! rest in the background. Until then, the member list of the room is
+//! incomplete, and requesting the members of the room only succeeds once the
+//! homeserver knows the full state.
+//!
+//! [MSC3706]: https://github.com/matrix-org/matrix-spec-proposals/pull/3706
+
+use std::time::Duration;
+
+use matrix_sdk_base::{RoomState, StateChanges};
+use matrix_sdk_common::{executor::spawn, time::Clock};
+use ruma::OwnedRoomId;
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use super::Room;
+use crate::{Client, Result};
+
+/// The delay after which a failed request of the members of a room with a
+/// partial state is retried.
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// The `join_room_by_id` endpoint, with the unstable field of the response
+/// telling whether the room was joined with a partial state, which is not
+/// supported by Ruma.
+pub(crate) mod join_room_by_id {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata, OwnedRoomId,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/r0/rooms/:room_id/join",
+            1.1 => "/_matrix/client/v3/rooms/:room_id/join",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id: OwnedRoomId,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub room_id: OwnedRoomId,
+        #[serde(rename = "org.matrix.msc3706.partial_state", default)]
+        pub partial_state: bool,
+    }
+}
+
+/// The `join_room_by_id_or_alias` endpoint, with the unstable field of the
+/// response telling whether the room was joined with a partial state, which
+/// is not supported by Ruma.
+pub(crate) mod join_room_by_id_or_alias {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            1.0 => "/_matrix/client/r0/join/:room_id_or_alias",
+            1.1 => "/_matrix/client/v3/join/:room_id_or_alias",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id_or_alias: OwnedRoomOrAliasId,
+        #[ruma_api(query)]
+        #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+        pub server_name: Vec<OwnedServerName>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub room_id: OwnedRoomId,
+        #[serde(rename = "org.matrix.msc3706.partial_state", default)]
+        pub partial_state: bool,
+    }
+}
+
+impl Client {
+    /// Get a receiver of the IDs of the rooms that were joined with a partial
+    /// state and whose full state is now known.
+    ///
+    /// The values that depend on the members of these rooms, like their
+    /// display name, should be refreshed when their ID is received.
+    pub fn subscribe_to_rooms_full_state(&self) -> broadcast::Receiver<OwnedRoomId> {
+        self.inner.room_full_state_sender.subscribe()
+    }
+}
+
+impl Room {
+    /// Mark this room as joined with a partial state, and wait in the
+    /// background for its full state.
+    pub(crate) async fn mark_partial_state(&self) -> Result<()> {
+        debug!(room_id = ?self.room_id(), "Room was joined with a partial state");
+        self.set_partial_state(true).await?;
+
+        let room = self.clone();
+        spawn(async move { room.wait_for_full_state().await });
+
+        Ok(())
+    }
+
+    /// Mark this room as having its full state, if it was joined with a
+    /// partial state.
+    pub(crate) async fn mark_full_state(&self) -> Result<()> {
+        if !self.is_partial_state() {
+            return Ok(());
+        }
+
+        debug!(room_id = ?self.room_id(), "Room has its full state");
+        self.set_partial_state(false).await?;
+
+        // Nobody might be listening, that's fine.
+        _ = self.client.inner.room_full_state_sender.send(self.room_id().to_owned());
+
+        Ok(())
+    }
+
+    async fn set_partial_state(&self, partial_state: bool) -> Result<()> {
+        let _sync_lock = self.client.base_client().sync_lock().read().await;
+
+        let mut room_info = self.clone_info();
+        if partial_state {
+            room_info.mark_partial_state();
+        } else {
+            room_info.mark_full_state();
+        }
+
+        let mut changes = StateChanges::default();
+        changes.add_room(room_info.clone());
+        self.client.store().save_changes(&changes).await?;
+        self.update_summary(room_info);
+
+        Ok(())
+    }
+
+    /// Request the members of the room until it succeeds, which marks the
+    /// room as having its full state.
+    async fn wait_for_full_state(self) {
+        while self.is_partial_state() && self.state() == RoomState::Joined {
+            if let Err(error) = self.request_members().await {
+                warn!(
+                    room_id = ?self.room_id(),
+                    "Failed to request the members of a room with a partial state: {error}"
+                );
+                self.client.inner.clock.sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
@@ -17,8 +17,6 @@
 #![deny(unreachable_pub)]
 
 use std::future::IntoFuture;
-#[cfg(feature = "image-proc")]
-use std::io::Cursor;
 
 use eyeball::SharedObservable;
 use matrix_sdk_common::boxed_into_future;
@@ -30,20 +28,21 @@ use ruma::{
     assign,
     events::{AnyMessageLikeEventContent, MessageLikeEventContent},
     serde::Raw,
-    OwnedTransactionId, TransactionId,
+    OwnedTransactionId, RoomVersionId, TransactionId,
 };
-use tracing::{debug, Instrument, Span};
+use tracing::{debug, warn, Instrument, Span};
 
-use super::Room;
-use crate::{
-    attachment::AttachmentConfig, utils::IntoRawMessageLikeEventContent, Result,
-    TransmissionProgress,
-};
+use super::{Room, RoomUpgradeOptions, RoomUpgradeProgress};
 #[cfg(feature = "image-proc")]
 use crate::{
-    attachment::{generate_image_thumbnail, Thumbnail},
+    attachment::{process_image, AttachmentInfo, BaseImageInfo, Thumbnail},
     error::ImageError,
 };
+use crate::{
+    attachment::{AttachmentConfig, AttachmentData},
+    utils::IntoRawMessageLikeEventContent,
+    Result, TransmissionProgress,
+};
 
 /// Future returned by [`Room::send`].
 #[allow(missing_debug_implementations)]
@@ -78,6 +77,10 @@ impl<'a> SendMessageLikeEvent<'a> {
     ///   corresponding [`SyncMessageLikeEvent`], but only for the *sending*
     ///   device. Other devices will not see it. This is then used to ignore
     ///   events sent by our own device and/or to implement local echo.
+    ///
+    /// The transaction IDs of the most recently sent events are persisted in
+    /// the store, so if an event was already sent with this transaction ID,
+    /// even before a restart of the client, it is not sent again.
     pub fn with_transaction_id(mut self, txn_id: &TransactionId) -> Self {
         self.transaction_id = Some(txn_id.to_owned());
         self
@@ -131,6 +134,10 @@ impl<'a> SendRawMessageLikeEvent<'a> {
     ///   corresponding [`SyncMessageLikeEvent`], but only for the *sending*
     ///   device. Other devices will not see it. This is then used to ignore
     ///   events sent by our own device and/or to implement local echo.
+    ///
+    /// The transaction IDs of the most recently sent events are persisted in
+    /// the store, so if an event was already sent with this transaction ID,
+    /// even before a restart of the client, it is not sent again.
     pub fn with_transaction_id(mut self, txn_id: &TransactionId) -> Self {
         self.transaction_id = Some(txn_id.to_owned());
         self
@@ -147,7 +154,25 @@ impl<'a> IntoFuture for SendRawMessageLikeEvent<'a> {
         let fut = async move {
             room.ensure_room_joined()?;
 
-            let txn_id = transaction_id.unwrap_or_else(TransactionId::new);
+            let txn_id = match transaction_id {
+                Some(txn_id) => {
+                    // The event might have been sent already, before a restart of the
+                    // client for example.
+                    if let Some(event_id) =
+                        room.client.event_id_for_transaction(room.room_id(), &txn_id).await?
+                    {
+                        debug!(
+                            ?txn_id,
+                            ?event_id,
+                            "Event was already sent with this transaction ID"
+                        );
+                        return Ok(send_message_event::v3::Response::new(event_id));
+                    }
+
+                    txn_id
+                }
+                None => TransactionId::new(),
+            };
             tracing::Span::current().record("transaction_id", tracing::field::debug(&txn_id));
 
             #[cfg(not(feature = "e2e-encryption"))]
@@ -195,12 +220,19 @@ impl<'a> IntoFuture for SendRawMessageLikeEvent<'a> {
 
             let request = send_message_event::v3::Request::new_raw(
                 room.room_id().to_owned(),
-                txn_id,
+                txn_id.clone(),
                 event_type.into(),
                 content,
             );
 
             let response = room.client.send(request, None).await?;
+
+            if let Err(error) =
+                room.client.add_sent_transaction(room.room_id(), &txn_id, &response.event_id).await
+            {
+                warn!("Failed to remember the transaction ID of the sent event: {error}");
+            }
+
             Ok(response)
         };
 
@@ -214,7 +246,7 @@ pub struct SendAttachment<'a> {
     room: &'a Room,
     body: &'a str,
     content_type: &'a Mime,
-    data: Vec<u8>,
+    data: AttachmentData,
     config: AttachmentConfig,
     tracing_span: Span,
     send_progress: SharedObservable<TransmissionProgress>,
@@ -225,7 +257,7 @@ impl<'a> SendAttachment<'a> {
         room: &'a Room,
         body: &'a str,
         content_type: &'a Mime,
-        data: Vec<u8>,
+        data: AttachmentData,
         config: AttachmentConfig,
     ) -> Self {
         Self {
@@ -263,54 +295,52 @@ impl<'a> IntoFuture for SendAttachment<'a> {
                     .await
             } else {
                 #[cfg(not(feature = "image-proc"))]
-                let thumbnail = None;
+                let (thumbnail, info) = (None, config.info);
 
                 #[cfg(feature = "image-proc")]
-                let data_slot;
-                #[cfg(feature = "image-proc")]
-                let (data, thumbnail) = if config.generate_thumbnail {
-                    let content_type = content_type.clone();
-                    let make_thumbnail = move |data| {
-                        let res = generate_image_thumbnail(
-                            &content_type,
-                            Cursor::new(&data),
-                            config.thumbnail_size,
-                        );
-                        (data, res)
-                    };
-
-                    #[cfg(not(target_arch = "wasm32"))]
-                    let (data, res) = tokio::task::spawn_blocking(move || make_thumbnail(data))
-                        .await
-                        .expect("Task join error");
-
-                    #[cfg(target_arch = "wasm32")]
-                    let (data, res) = make_thumbnail(data);
-
-                    let thumbnail = match res {
-                        Ok((thumbnail_data, thumbnail_info)) => {
-                            data_slot = thumbnail_data;
-                            Some(Thumbnail {
-                                data: data_slot,
-                                content_type: mime::IMAGE_JPEG,
-                                info: Some(thumbnail_info),
-                            })
-                        }
-                        Err(
-                            ImageError::ThumbnailBiggerThanOriginal
-                            | ImageError::FormatNotSupported,
-                        ) => None,
-                        Err(error) => return Err(error.into()),
-                    };
-
-                    (data, thumbnail)
-                } else {
-                    (data, None)
+                let (data, thumbnail, info) = match data {
+                    AttachmentData::Bytes(data) if config.generate_thumbnail => {
+                        let thumbnail_size = config.thumbnail_size;
+                        let process = {
+                            let content_type = content_type.clone();
+                            move |data: Vec<u8>| {
+                                let res = process_image(&content_type, &data, thumbnail_size);
+                                (data, res)
+                            }
+                        };
+
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let (data, res) = tokio::task::spawn_blocking(move || process(data))
+                            .await
+                            .expect("Task join error");
+
+                        #[cfg(target_arch = "wasm32")]
+                        let (data, res) = process(data);
+
+                        let (thumbnail, info) = match res {
+                            Ok((image_info, thumbnail)) => {
+                                let thumbnail =
+                                    thumbnail.map(|(thumbnail_data, thumbnail_info)| Thumbnail {
+                                        data: thumbnail_data,
+                                        content_type: content_type.clone(),
+                                        info: Some(thumbnail_info),
+                                    });
+
+                                (thumbnail, merge_image_info(config.info, image_info))
+                            }
+                            Err(ImageError::FormatNotSupported) => (None, config.info),
+                            Err(error) => return Err(error.into()),
+                        };
+
+                        (AttachmentData::Bytes(data), thumbnail, info)
+                    }
+                    // Thumbnails can't be generated from streamed data.
+                    data => (data, None, config.info),
                 };
 
                 let config = AttachmentConfig {
                     txn_id: config.txn_id,
-                    info: config.info,
+                    info,
                     thumbnail,
                     #[cfg(feature = "image-proc")]
                     generate_thumbnail: false,
@@ -326,3 +356,70 @@ impl<'a> IntoFuture for SendAttachment<'a> {
         Box::pin(fut.instrument(tracing_span))
     }
 }
+
+/// Future returned by [`Room::upgrade`].
+#[allow(missing_debug_implementations)]
+pub struct UpgradeRoom<'a> {
+    room: &'a Room,
+    new_version: RoomVersionId,
+    options: RoomUpgradeOptions,
+    tracing_span: Span,
+    progress: SharedObservable<RoomUpgradeProgress>,
+}
+
+impl<'a> UpgradeRoom<'a> {
+    pub(crate) fn new(
+        room: &'a Room,
+        new_version: RoomVersionId,
+        options: RoomUpgradeOptions,
+    ) -> Self {
+        Self {
+            room,
+            new_version,
+            options,
+            tracing_span: Span::current(),
+            progress: Default::default(),
+        }
+    }
+
+    /// Replace the default `SharedObservable` used for tracking the progress
+    /// of the upgrade.
+    pub fn with_progress_observable(
+        mut self,
+        progress: SharedObservable<RoomUpgradeProgress>,
+    ) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+impl<'a> IntoFuture for UpgradeRoom<'a> {
+    type Output = Result<Room>;
+    boxed_into_future!(extra_bounds: 'a);
+
+    fn into_future(self) -> Self::IntoFuture {
+        let Self { room, new_version, options, tracing_span, progress } = self;
+        let fut = room.upgrade_inner(new_version, options, progress);
+        Box::pin(fut.instrument(tracing_span))
+    }
+}
+
+/// Fill the metadata of an image that was not provided with the generated
+/// metadata.
+#[cfg(feature = "image-proc")]
+fn merge_image_info(
+    info: Option<AttachmentInfo>,
+    generated: BaseImageInfo,
+) -> Option<AttachmentInfo> {
+    match info {
+        None => Some(AttachmentInfo::Image(generated)),
+        Some(AttachmentInfo::Image(info)) => Some(AttachmentInfo::Image(BaseImageInfo {
+            height: info.height.or(generated.height),
+            width: info.width.or(generated.width),
+            size: info.size.or(generated.size),
+            blurhash: info.blurhash.or(generated.blurhash),
+        })),
+        // The metadata doesn't match the content type, it will be ignored.
+        info => info,
+    }
+}
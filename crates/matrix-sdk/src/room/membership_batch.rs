@@ -0,0 +1,79 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::BTreeSet, future::Future};
+
+use futures_util::{stream, StreamExt};
+use ruma::OwnedUserId;
+
+use crate::{Error, Result};
+
+/// The maximum number of membership changes that are sent at the same time.
+const MAX_CONCURRENT_CHANGES: usize = 5;
+
+/// The result of a membership change applied to several users, like
+/// [`Room::invite_users`] or [`Room::ban_users`].
+///
+/// The users are in the same order as in the request, without duplicates.
+///
+/// [`Room::invite_users`]: super::Room::invite_users
+/// [`Room::ban_users`]: super::Room::ban_users
+#[derive(Debug, Default)]
+pub struct MembershipChangeReport {
+    /// The users whose membership was changed.
+    pub succeeded: Vec<OwnedUserId>,
+    /// The users whose membership could not be changed, with the error
+    /// returned for each of them.
+    pub failed: Vec<(OwnedUserId, Error)>,
+}
+
+impl MembershipChangeReport {
+    /// Whether the membership of all the users was changed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Apply a membership change to each of the given users, a few at a time.
+///
+/// The duplicate users are ignored, and a failure for one user doesn't stop
+/// the changes for the others.
+pub(super) async fn change_memberships<F, Fut>(
+    user_ids: &[OwnedUserId],
+    change: F,
+) -> MembershipChangeReport
+where
+    F: Fn(OwnedUserId) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut seen = BTreeSet::new();
+    let change = &change;
+
+    let results: Vec<_> = stream::iter(user_ids.iter().filter(|user_id| seen.insert(*user_id)))
+        .map(|user_id| async move { (user_id.clone(), change(user_id.clone()).await) })
+        .buffered(MAX_CONCURRENT_CHANGES)
+        .collect()
+        .await;
+
+    let mut report = MembershipChangeReport::default();
+
+    for (user_id, result) in results {
+        match result {
+            Ok(()) => report.succeeded.push(user_id),
+            Err(error) => report.failed.push((user_id, error)),
+        }
+    }
+
+    report
+}
@@ -97,7 +97,7 @@ impl MessagesOptions {
         Self { from: from.into().map(ToOwned::to_owned), ..self }
     }
 
-    pub(super) fn into_request(self, room_id: &RoomId) -> get_message_events::v3::Request {
+    pub(crate) fn into_request(self, room_id: &RoomId) -> get_message_events::v3::Request {
         assign!(get_message_events::v3::Request::new(room_id.to_owned(), self.dir), {
             from: self.from,
             to: self.to,
@@ -1,96 +1,146 @@
 //! High-level room API
 
-use std::{borrow::Borrow, collections::BTreeMap, ops::Deref, time::Duration};
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    future::ready,
+    ops::Deref,
+    time::{Duration, SystemTime},
+};
 
 use eyeball::SharedObservable;
 use futures_core::Stream;
-use futures_util::stream::FuturesUnordered;
+use futures_util::{future::join_all, stream::FuturesUnordered, StreamExt};
 use matrix_sdk_base::{
     deserialized_responses::{
         RawAnySyncOrStrippedState, RawSyncOrStrippedState, SyncOrStrippedState, TimelineEvent,
     },
-    instant::Instant,
-    store::StateStoreExt,
-    RoomMemberships, StateChanges,
+    store::{ComposerDraft, ScheduledMessage, StateStoreExt},
+    RoomMemberships, RoomNotableTags, StateChanges, StateStoreDataKey, StateStoreDataValue,
 };
-use matrix_sdk_common::timeout::timeout;
+use matrix_sdk_common::{time::Clock, timeout::timeout};
 use mime::Mime;
 #[cfg(feature = "e2e-encryption")]
 use ruma::events::{
-    room::encrypted::OriginalSyncRoomEncryptedEvent, AnySyncMessageLikeEvent, AnySyncTimelineEvent,
-    SyncMessageLikeEvent,
+    room::encrypted::OriginalSyncRoomEncryptedEvent, AnySyncMessageLikeEvent, SyncMessageLikeEvent,
 };
 use ruma::{
     api::client::{
-        config::set_global_account_data,
+        config::{set_global_account_data, set_room_account_data},
         context,
         error::ErrorKind,
         filter::LazyLoadOptions,
         membership::{
             ban_user, forget_room, get_member_events,
             invite_user::{self, v3::InvitationRecipient},
-            join_room_by_id, kick_user, leave_room, unban_user, Invite3pid,
+            kick_user, leave_room, unban_user, Invite3pid, Invite3pidInit,
         },
         message::send_message_event,
         read_marker::set_read_marker,
         receipt::create_receipt,
         redact::redact_event,
-        room::get_room_event,
+        room::{
+            create_room::{self, v3::CreationContent},
+            get_room_event, report_content,
+        },
         state::{get_state_events_for_key, send_state_event},
         tag::{create_tag, delete_tag},
         typing::create_typing_event::{self, v3::Typing},
     },
     assign,
     events::{
+        call::member::{
+            Application, CallApplicationContent, CallMemberEventContent, CallScope, Focus,
+            Membership, MembershipInit,
+        },
         direct::DirectEventContent,
         receipt::{Receipt, ReceiptThread, ReceiptType},
         room::{
             avatar::{self, RoomAvatarEventContent},
+            create::PreviousRoom,
             encryption::RoomEncryptionEventContent,
-            history_visibility::HistoryVisibility,
+            guest_access::{GuestAccess, RoomGuestAccessEventContent},
+            history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+            join_rules::{JoinRule, RoomJoinRulesEventContent},
             message::RoomMessageEventContent,
             name::RoomNameEventContent,
+            pinned_events::RoomPinnedEventsEventContent,
             power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
             server_acl::RoomServerAclEventContent,
+            tombstone::RoomTombstoneEventContent,
             topic::RoomTopicEventContent,
             MediaSource,
         },
         space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
         tag::{TagInfo, TagName},
-        AnyRoomAccountDataEvent, AnyStateEvent, EmptyStateKey, MessageLikeEventContent,
+        AnyRoomAccountDataEvent, AnyStateEvent, AnyStateEventContent, AnySyncTimelineEvent,
+        AnyTimelineEvent, EmptyStateKey, InitialStateEvent, MessageLikeEventContent,
         MessageLikeEventType, RedactContent, RedactedStateEventContent, RoomAccountDataEvent,
         RoomAccountDataEventContent, RoomAccountDataEventType, StateEventContent, StateEventType,
-        StaticEventContent, StaticStateEventContent, SyncStateEvent,
+        StaticEventContent, StaticStateEventContent, SyncStateEvent, TimelineEventType,
     },
+    exports::ruma_macros::EventContent,
     push::{Action, PushConditionRoomCtx},
     serde::Raw,
-    uint, EventId, Int, MatrixToUri, MatrixUri, MxcUri, OwnedEventId, OwnedRoomId, OwnedServerName,
-    OwnedTransactionId, OwnedUserId, TransactionId, UInt, UserId,
+    thirdparty::Medium,
+    uint, EventId, Int, MatrixToUri, MatrixUri, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId,
+    OwnedRoomId, OwnedServerName, OwnedTransactionId, OwnedUserId, RoomVersionId, TransactionId,
+    UInt, UserId,
 };
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 use tokio::sync::broadcast;
 use tracing::{debug, info, instrument, warn};
 
-use self::futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent};
+use self::{
+    futures::{SendAttachment, SendMessageLikeEvent, SendRawMessageLikeEvent, UpgradeRoom},
+    relations::RelationsRequest,
+};
 use crate::{
-    attachment::AttachmentConfig,
-    error::WrongRoomState,
+    attachment::{AttachmentConfig, AttachmentData},
+    error::{RoomSettingsError, WrongRoomState},
+    event_cache::RoomEventCache,
     event_handler::{EventHandler, EventHandlerHandle, SyncEvent},
+    identity_server::IdentityServer,
     media::{MediaFormat, MediaRequest},
     notification_settings::{IsEncrypted, IsOneToOne, RoomNotificationMode},
     sync::RoomUpdate,
+    uri::UriTarget,
     utils::{IntoRawMessageLikeEventContent, IntoRawStateEventContent},
-    BaseRoom, Client, Error, HttpError, HttpResult, Result, RoomState, TransmissionProgress,
+    BaseRoom, BaseRoomMember, Client, Error, HttpError, HttpResult, Result, RoomState,
+    TransmissionProgress,
 };
 
+mod call;
+mod call_signaling;
+pub(crate) mod delayed_events;
+pub(crate) mod faster_joins;
 pub mod futures;
 mod member;
+mod membership_batch;
 mod messages;
+mod relations;
+mod space;
+mod third_party_invite;
+mod typing;
+mod upgrade;
 
 pub use self::{
+    call::{
+        ActiveCall, CallParticipant, IncomingCall, IncomingCallKind, CALL_MEMBERSHIP_EXPIRATION,
+    },
+    call_signaling::{
+        CallSignalingEvent, CallSignalingEventContent, CallSignalingSession, CallSignalingState,
+    },
+    delayed_events::UpdateDelayedEventAction,
     member::RoomMember,
+    membership_batch::MembershipChangeReport,
     messages::{Messages, MessagesOptions},
+    relations::{Relations, RelationsOptions},
+    space::{SpaceChild, SpaceChildOptions},
+    third_party_invite::PendingThirdPartyInvite,
+    typing::TypingNoticeGuard,
+    upgrade::{RoomUpgradeOptions, RoomUpgradeProgress},
 };
 
 /// A struct containing methods that are common for Joined, Invited and Left
@@ -125,7 +175,8 @@ impl Room {
 
     /// Leave this room.
     ///
-    /// Only invited and joined rooms can be left.
+    /// Only invited, knocked and joined rooms can be left. Leaving a knocked
+    /// room retracts the knock.
     #[doc(alias = "reject_invitation")]
     pub async fn leave(&self) -> Result<()> {
         let state = self.state();
@@ -157,10 +208,14 @@ impl Room {
                 false
             });
 
-        let request = join_room_by_id::v3::Request::new(self.inner.room_id().to_owned());
+        let request = faster_joins::join_room_by_id::Request { room_id: self.room_id().to_owned() };
         let response = self.client.send(request, None).await?;
         self.client.base_client().room_joined(&response.room_id).await?;
 
+        if response.partial_state {
+            self.mark_partial_state().await?;
+        }
+
         if mark_as_direct {
             self.set_is_direct(true).await?;
         }
@@ -374,6 +429,80 @@ impl Room {
         Ok(Some((TimelineEvent { event, encryption_info: None, push_actions }, response.state)))
     }
 
+    /// Fetch the events relating to the event with the given `EventId` in this
+    /// room, like its reactions, its edits or the events in its thread.
+    ///
+    /// With the encryption feature, the events are decrypted if possible. If
+    /// decryption fails for an individual event, that event is returned
+    /// undecrypted.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{room::RelationsOptions, Client};
+    /// # use matrix_sdk::ruma::{event_id, events::relation::RelationType, room_id};
+    /// # use url::Url;
+    ///
+    /// # let homeserver = Url::parse("http://example.com").unwrap();
+    /// # async {
+    /// let client = Client::new(homeserver).await.unwrap();
+    /// let room = client.get_room(room_id!("!roomid:example.com")).unwrap();
+    /// let event_id = event_id!("$event:example.com");
+    ///
+    /// let mut options = RelationsOptions::with_rel_type(RelationType::Annotation);
+    /// loop {
+    ///     let relations = room.relations(event_id, options.clone()).await.unwrap();
+    ///     // Show the reactions…
+    ///
+    ///     let Some(next_batch) = relations.next_batch else { break };
+    ///     options = options.from(next_batch.as_str());
+    /// }
+    /// # };
+    /// ```
+    #[instrument(skip_all, fields(room_id = ?self.inner.room_id(), ?event_id, ?options))]
+    pub async fn relations(
+        &self,
+        event_id: &EventId,
+        options: RelationsOptions,
+    ) -> Result<Relations> {
+        let (chunk, next_batch, prev_batch) = match options.into_request(self.room_id(), event_id) {
+            RelationsRequest::All(request) => {
+                let response = self.client.send(request, None).await?;
+                (response.chunk, response.next_batch, response.prev_batch)
+            }
+            RelationsRequest::WithRelType(request) => {
+                let response = self.client.send(request, None).await?;
+                (response.chunk, response.next_batch, response.prev_batch)
+            }
+            RelationsRequest::WithRelTypeAndEventType(request) => {
+                let response = self.client.send(request, None).await?;
+                (response.chunk, response.next_batch, response.prev_batch)
+            }
+        };
+
+        let mut relations =
+            Relations { chunk: Vec::with_capacity(chunk.len()), next_batch, prev_batch };
+
+        for event in chunk {
+            let event: Raw<AnyTimelineEvent> = event.cast();
+
+            #[cfg(feature = "e2e-encryption")]
+            if let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(
+                SyncMessageLikeEvent::Original(_),
+            ))) = event.deserialize_as::<AnySyncTimelineEvent>()
+            {
+                if let Ok(event) = self.decrypt_event(event.cast_ref()).await {
+                    relations.chunk.push(event);
+                    continue;
+                }
+            }
+
+            relations.chunk.push(TimelineEvent::new(event));
+        }
+
+        Ok(relations)
+    }
+
     pub(crate) async fn request_members(&self) -> Result<()> {
         self.client
             .locks()
@@ -386,6 +515,10 @@ impl Room {
                 Box::pin(self.client.base_client().receive_members(self.room_id(), &response))
                     .await?;
 
+                // The homeserver only returns the members once it knows the full state of
+                // the room.
+                self.mark_full_state().await?;
+
                 Ok(())
             })
             .await
@@ -592,6 +725,69 @@ impl Room {
             .collect())
     }
 
+    /// Get the members of this room with the given user IDs.
+    ///
+    /// *Note*: This method will not fetch the members from the homeserver, it
+    /// is meant to load the members in chunks, from the user IDs returned by
+    /// [`member_user_ids()`](#method.member_user_ids) after the member list
+    /// has been synchronized, e.g. with
+    /// [`sync_members()`](#method.sync_members).
+    pub async fn members_with_user_ids(&self, user_ids: &[OwnedUserId]) -> Result<Vec<RoomMember>> {
+        Ok(self
+            .inner
+            .members_with_user_ids(user_ids)
+            .await?
+            .into_iter()
+            .map(|member| RoomMember::new(self.client.clone(), member))
+            .collect())
+    }
+
+    /// Search the members of this room with the given memberships whose
+    /// display name or user ID starts with the given query, e.g. to
+    /// autocomplete mentions.
+    ///
+    /// The search is case-insensitive, and the query is also matched against
+    /// the start of each word of the display names and against the localpart
+    /// of the user IDs. The members are loaded from the store in chunks, until
+    /// `limit` matching members are found.
+    ///
+    /// *Note*: This method will fetch the members from the homeserver if the
+    /// member list isn't synchronized due to member lazy loading.
+    pub async fn search_members(
+        &self,
+        query: &str,
+        memberships: RoomMemberships,
+        limit: usize,
+    ) -> Result<Vec<RoomMember>> {
+        const CHUNK_SIZE: usize = 100;
+
+        let mut results = Vec::new();
+        if limit == 0 {
+            return Ok(results);
+        }
+
+        self.sync_members().await?;
+
+        // Mentions are often typed with the sigil of user IDs.
+        let query = query.trim_start_matches('@').to_lowercase();
+        let user_ids = self.inner.member_user_ids(memberships).await?;
+
+        for user_ids in user_ids.chunks(CHUNK_SIZE) {
+            for member in self.inner.members_with_user_ids(user_ids).await? {
+                if !member_matches(&member, &query) {
+                    continue;
+                }
+
+                results.push(RoomMember::new(self.client.clone(), member));
+                if results.len() == limit {
+                    return Ok(results);
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
     /// Get all state events of a given type in this room.
     pub async fn get_state_events(
         &self,
@@ -855,6 +1051,80 @@ impl Room {
         Ok(self.account_data(C::TYPE.into()).await?.map(Raw::cast))
     }
 
+    /// Whether previews should be shown for the URLs in this room.
+    ///
+    /// The choice of the user is used if they made one, with
+    /// [`Room::set_url_previews_enabled`] or in another client. Otherwise URL
+    /// previews are disabled in encrypted rooms, since fetching them reveals
+    /// the URLs to the homeserver, and enabled in other rooms.
+    pub async fn url_previews_enabled(&self) -> Result<bool> {
+        let disable = self
+            .account_data_static::<UrlPreviewsEventContent>()
+            .await?
+            .map(|event| event.deserialize())
+            .transpose()?
+            .and_then(|event| event.content.disable);
+
+        match disable {
+            Some(disable) => Ok(!disable),
+            None => Ok(!self.is_encrypted().await?),
+        }
+    }
+
+    /// Set whether previews should be shown for the URLs in this room.
+    ///
+    /// The choice is saved in the account data of the room, so it is shared
+    /// with the other clients of the user.
+    pub async fn set_url_previews_enabled(&self, enabled: bool) -> Result<()> {
+        let user_id =
+            self.client.user_id().ok_or_else(|| Error::from(HttpError::AuthenticationRequired))?;
+
+        let content = UrlPreviewsEventContent { disable: Some(!enabled) };
+        let request = set_room_account_data::v3::Request::new(
+            user_id.to_owned(),
+            self.room_id().to_owned(),
+            &content,
+        )?;
+
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+
+    /// Save the message being written in the composer of this room, to restore
+    /// it later with [`Room::load_draft`].
+    ///
+    /// The draft is persisted in the state store, so it survives restarts of
+    /// the application. It replaces the previous draft of the room, if any.
+    pub async fn save_draft(&self, draft: ComposerDraft) -> Result<()> {
+        self.client
+            .store()
+            .set_kv_data(
+                StateStoreDataKey::ComposerDraft(self.room_id()),
+                StateStoreDataValue::ComposerDraft(draft),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get the draft saved with [`Room::save_draft`], if any.
+    pub async fn load_draft(&self) -> Result<Option<ComposerDraft>> {
+        Ok(self
+            .client
+            .store()
+            .get_kv_data(StateStoreDataKey::ComposerDraft(self.room_id()))
+            .await?
+            .and_then(StateStoreDataValue::into_composer_draft))
+    }
+
+    /// Remove the draft of this room, e.g. once the message was sent.
+    pub async fn clear_draft(&self) -> Result<()> {
+        self.client
+            .store()
+            .remove_kv_data(StateStoreDataKey::ComposerDraft(self.room_id()))
+            .await?;
+        Ok(())
+    }
+
     /// Check if all members of this room are verified and all their devices are
     /// verified.
     ///
@@ -937,6 +1207,83 @@ impl Room {
         self.client.send(request, None).await
     }
 
+    /// Add or remove the `m.favourite` tag of this room.
+    ///
+    /// A room can't be both a favourite and low priority, so the
+    /// `m.lowpriority` tag is removed when the room is marked as a favourite.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_favourite` - Whether to mark this room as a favourite.
+    ///
+    /// * `tag_order` - The order of the room among the favourite rooms, as a
+    ///   number between 0 and 1.
+    pub async fn set_is_favourite(&self, is_favourite: bool, tag_order: Option<f64>) -> Result<()> {
+        if is_favourite {
+            let tag_info = assign!(TagInfo::new(), { order: tag_order });
+            self.set_tag(TagName::Favorite, tag_info).await?;
+
+            if self.is_low_priority() {
+                self.remove_tag(TagName::LowPriority).await?;
+            }
+        } else {
+            self.remove_tag(TagName::Favorite).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Add or remove the `m.lowpriority` tag of this room.
+    ///
+    /// A room can't be both a favourite and low priority, so the
+    /// `m.favourite` tag is removed when the room is marked as low priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_low_priority` - Whether to mark this room as low priority.
+    ///
+    /// * `tag_order` - The order of the room among the low priority rooms, as a
+    ///   number between 0 and 1.
+    pub async fn set_is_low_priority(
+        &self,
+        is_low_priority: bool,
+        tag_order: Option<f64>,
+    ) -> Result<()> {
+        if is_low_priority {
+            let tag_info = assign!(TagInfo::new(), { order: tag_order });
+            self.set_tag(TagName::LowPriority, tag_info).await?;
+
+            if self.is_favourite() {
+                self.remove_tag(TagName::Favorite).await?;
+            }
+        } else {
+            self.remove_tag(TagName::LowPriority).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Subscribe to the notable tags of this room.
+    ///
+    /// The stream yields a new value every time the `m.favourite` or
+    /// `m.lowpriority` tag is added or removed, as seen by the sync. It does
+    /// not yield the current value, which can be obtained with
+    /// [`BaseRoom::notable_tags()`].
+    pub fn subscribe_to_notable_tags(&self) -> impl Stream<Item = RoomNotableTags> {
+        let mut current = self.notable_tags();
+
+        self.subscribe_info().filter_map(move |info| {
+            let tags = info.notable_tags();
+
+            if tags == current {
+                ready(None)
+            } else {
+                current = tags;
+                ready(Some(tags))
+            }
+        })
+    }
+
     /// Sets whether this room is a DM.
     ///
     /// When setting this room as DM, it will be marked as DM for all active
@@ -985,6 +1332,166 @@ impl Room {
         Ok(())
     }
 
+    /// Change the join rule of this room.
+    ///
+    /// Returns an [`Error::RoomSettings`] if the join rule is restricted
+    /// without any allowed room, or if it is not supported by the version of
+    /// the room, and an [`Error::InsufficientPowerLevel`] if the current user
+    /// is not allowed to change it. In both cases, no request is sent.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::ruma::{
+    /// #     events::room::join_rules::{AllowRule, JoinRule, Restricted},
+    /// #     room_id,
+    /// # };
+    /// # async {
+    /// # let room: matrix_sdk::Room = todo!();
+    /// let space_id = room_id!("!space:example.org").to_owned();
+    /// let allow = vec![AllowRule::room_membership(space_id)];
+    ///
+    /// room.set_join_rule(JoinRule::Restricted(Restricted::new(allow))).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn set_join_rule(&self, join_rule: JoinRule) -> Result<()> {
+        if let JoinRule::Restricted(restricted) | JoinRule::KnockRestricted(restricted) = &join_rule
+        {
+            if restricted.allow.is_empty() {
+                return Err(RoomSettingsError::RestrictedWithoutAllowedRooms.into());
+            }
+        }
+
+        if let Some(room_version) = self.clone_info().room_version() {
+            if !supports_join_rule(room_version, &join_rule) {
+                return Err(RoomSettingsError::UnsupportedJoinRule(room_version.clone()).into());
+            }
+        }
+
+        self.ensure_can_send_state(StateEventType::RoomJoinRules).await?;
+        self.send_state_event(RoomJoinRulesEventContent::new(join_rule)).await?;
+        Ok(())
+    }
+
+    /// Change who can read the history of this room.
+    ///
+    /// The new visibility only applies to the events sent after the change.
+    ///
+    /// Returns an [`Error::InsufficientPowerLevel`] if the current user is not
+    /// allowed to change it, in which case no request is sent.
+    pub async fn set_history_visibility(&self, visibility: HistoryVisibility) -> Result<()> {
+        self.ensure_can_send_state(StateEventType::RoomHistoryVisibility).await?;
+        self.send_state_event(RoomHistoryVisibilityEventContent::new(visibility)).await?;
+        Ok(())
+    }
+
+    /// Change whether guest users can join this room.
+    ///
+    /// Returns an [`Error::InsufficientPowerLevel`] if the current user is not
+    /// allowed to change it, in which case no request is sent.
+    pub async fn set_guest_access(&self, guest_access: GuestAccess) -> Result<()> {
+        self.ensure_can_send_state(StateEventType::RoomGuestAccess).await?;
+        self.send_state_event(RoomGuestAccessEventContent::new(guest_access)).await?;
+        Ok(())
+    }
+
+    /// Check that the current user is allowed to send state events of the
+    /// given type in this room.
+    async fn ensure_can_send_state(&self, event_type: StateEventType) -> Result<()> {
+        if self.can_user_send_state(self.own_user_id(), event_type.clone()).await? {
+            Ok(())
+        } else {
+            Err(Error::InsufficientPowerLevel(event_type))
+        }
+    }
+
+    /// Pin the given event in this room.
+    ///
+    /// The event is added at the end of the pinned events. Returns `false` if
+    /// the event was already pinned, in which case no request is sent.
+    ///
+    /// Returns an [`Error::InsufficientPowerLevel`] if the current user is not
+    /// allowed to change the pinned events.
+    pub async fn pin_event(&self, event_id: &EventId) -> Result<bool> {
+        let mut pinned = self.pinned_event_ids();
+        if pinned.iter().any(|id| id == event_id) {
+            return Ok(false);
+        }
+
+        pinned.push(event_id.to_owned());
+        self.send_pinned_event_ids(pinned).await?;
+
+        Ok(true)
+    }
+
+    /// Unpin the given event in this room.
+    ///
+    /// Returns `false` if the event was not pinned, in which case no request
+    /// is sent.
+    ///
+    /// Returns an [`Error::InsufficientPowerLevel`] if the current user is not
+    /// allowed to change the pinned events.
+    pub async fn unpin_event(&self, event_id: &EventId) -> Result<bool> {
+        let mut pinned = self.pinned_event_ids();
+        let len = pinned.len();
+        pinned.retain(|id| id != event_id);
+
+        if pinned.len() == len {
+            return Ok(false);
+        }
+
+        self.send_pinned_event_ids(pinned).await?;
+
+        Ok(true)
+    }
+
+    async fn send_pinned_event_ids(&self, pinned: Vec<OwnedEventId>) -> Result<()> {
+        self.ensure_can_send_state(StateEventType::RoomPinnedEvents).await?;
+        self.send_state_event(RoomPinnedEventsEventContent::new(pinned)).await?;
+        Ok(())
+    }
+
+    /// Subscribe to the pinned events of this room.
+    ///
+    /// The stream yields the IDs of the pinned events every time they change,
+    /// as seen by the sync. It does not yield the current value, which can be
+    /// obtained with [`BaseRoom::pinned_event_ids()`].
+    pub fn subscribe_to_pinned_event_ids(&self) -> impl Stream<Item = Vec<OwnedEventId>> {
+        let mut current = self.pinned_event_ids();
+
+        self.subscribe_info().filter_map(move |info| {
+            let pinned = info.pinned_event_ids();
+
+            if pinned == current {
+                ready(None)
+            } else {
+                current = pinned.clone();
+                ready(Some(pinned))
+            }
+        })
+    }
+
+    /// Load the events pinned in this room, in order.
+    ///
+    /// The events that can't be loaded, because they were deleted or are not
+    /// visible to the current user, are skipped.
+    pub async fn load_pinned_events(&self) -> Vec<TimelineEvent> {
+        let pinned = self.pinned_event_ids();
+        let events = join_all(pinned.iter().map(|event_id| self.event(event_id))).await;
+
+        events
+            .into_iter()
+            .zip(pinned)
+            .filter_map(|(result, event_id)| match result {
+                Ok(event) => Some(event),
+                Err(error) => {
+                    warn!(%event_id, "Failed to load pinned event: {error}");
+                    None
+                }
+            })
+            .collect()
+    }
+
     /// Tries to decrypt a room event.
     ///
     /// # Arguments
@@ -1041,6 +1548,30 @@ impl Room {
         Ok(())
     }
 
+    /// Ban several users from this room.
+    ///
+    /// The bans are sent a few at a time, and the duplicate users are ignored.
+    /// A failure to ban a user doesn't stop the bans of the others: the
+    /// returned report lists which users were banned and the error for those
+    /// that weren't.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_ids` - The users to ban.
+    ///
+    /// * `reason` - The reason for banning the users.
+    #[instrument(skip_all)]
+    pub async fn ban_users(
+        &self,
+        user_ids: &[OwnedUserId],
+        reason: Option<&str>,
+    ) -> MembershipChangeReport {
+        membership_batch::change_memberships(user_ids, |user_id| async move {
+            self.ban_user(&user_id, reason).await
+        })
+        .await
+    }
+
     /// Unban the user with `UserId` from this room.
     ///
     /// # Arguments
@@ -1090,6 +1621,20 @@ impl Room {
         Ok(())
     }
 
+    /// Invite several users to this room.
+    ///
+    /// The invites are sent a few at a time, and the duplicate users are
+    /// ignored. A failure to invite a user doesn't stop the invites of the
+    /// others: the returned report lists which users were invited and the
+    /// error for those that weren't.
+    #[instrument(skip_all)]
+    pub async fn invite_users(&self, user_ids: &[OwnedUserId]) -> MembershipChangeReport {
+        membership_batch::change_memberships(user_ids, |user_id| async move {
+            self.invite_user_by_id(&user_id).await
+        })
+        .await
+    }
+
     /// Invite the specified user by third party id to this room.
     ///
     /// # Arguments
@@ -1104,22 +1649,115 @@ impl Room {
         Ok(())
     }
 
-    /// Activate typing notice for this room.
+    /// Invite the owner of the given email address to this room.
     ///
-    /// The typing notice remains active for 4s. It can be deactivate at any
-    /// point by setting typing to `false`. If this method is called while
-    /// the typing notice is active nothing will happen. This method can be
-    /// called on every key stroke, since it will do nothing while typing is
-    /// active.
+    /// The homeserver sends the invite to the address through the identity
+    /// server, unless the address is already bound to a Matrix user, who is
+    /// invited directly. The user must have accepted the policies of the
+    /// identity server, see [`IdentityServer::policies()`].
     ///
     /// # Arguments
     ///
-    /// * `typing` - Whether the user is typing or has stopped typing.
+    /// * `identity_server` - The identity server to use, usually the one
+    ///   returned by [`Client::identity_server()`].
     ///
-    /// # Examples
+    /// * `email` - The email address to invite.
+    pub async fn invite_user_by_email(
+        &self,
+        identity_server: &IdentityServer,
+        email: &str,
+    ) -> Result<()> {
+        let invite = Invite3pidInit {
+            id_server: identity_server.server_name(),
+            id_access_token: identity_server.access_token().await?,
+            medium: Medium::Email,
+            address: email.to_owned(),
+        };
+
+        self.invite_user_by_3pid(invite.into()).await
+    }
+
+    /// Get the invites to third-party identifiers, like email addresses, that
+    /// were not accepted yet.
+    pub async fn pending_third_party_invites(&self) -> Result<Vec<PendingThirdPartyInvite>> {
+        let invites = self.get_state_events(StateEventType::RoomThirdPartyInvite).await?;
+        let members = self.get_state_events(StateEventType::RoomMember).await?;
+        Ok(PendingThirdPartyInvite::from_state(&invites, &members))
+    }
+
+    /// Get the users who knocked on this room and are waiting for a
+    /// moderator to accept or decline their request.
+    pub async fn knock_requests(&self) -> Result<Vec<RoomMember>> {
+        self.members(RoomMemberships::KNOCK).await
+    }
+
+    /// Accept the knock of the user with the given `UserId`, by inviting them
+    /// to this room.
     ///
-    /// ```no_run
-    /// use std::time::Duration;
+    /// The current user needs to be allowed to invite users in this room.
+    pub async fn accept_knock(&self, user_id: &UserId) -> Result<()> {
+        self.invite_user_by_id(user_id).await
+    }
+
+    /// Decline the knock of the user with the given `UserId`, by kicking them
+    /// from this room.
+    ///
+    /// The current user needs to be allowed to kick users in this room.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The `UserId` of the user who knocked.
+    ///
+    /// * `reason` - The reason for declining the knock.
+    pub async fn decline_knock(&self, user_id: &UserId, reason: Option<&str>) -> Result<()> {
+        self.kick_user(user_id, reason).await
+    }
+
+    /// Create a [`TypingNoticeGuard`] to send the typing notices of a
+    /// composer for this room.
+    ///
+    /// It takes care of refreshing the typing notice while the user is typing,
+    /// and of stopping it when the user stops typing or when the guard is
+    /// dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, ruma::room_id};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let room = client.get_room(room_id!("!SVkFJHzfwvuaIEawgC:localhost")).unwrap();
+    /// let typing_guard = room.typing_notice_guard();
+    ///
+    /// // On every keystroke.
+    /// typing_guard.keystroke().await?;
+    ///
+    /// // When the message is sent or the composer is cleared.
+    /// typing_guard.stop().await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn typing_notice_guard(&self) -> TypingNoticeGuard {
+        TypingNoticeGuard::new(self.clone())
+    }
+
+    /// Activate typing notice for this room.
+    ///
+    /// The typing notice remains active for 4s. It can be deactivate at any
+    /// point by setting typing to `false`. If this method is called while
+    /// the typing notice is active nothing will happen. This method can be
+    /// called on every key stroke, since it will do nothing while typing is
+    /// active.
+    ///
+    /// # Arguments
+    ///
+    /// * `typing` - Whether the user is typing or has stopped typing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
     ///
     /// use matrix_sdk::ruma::api::client::typing::create_typing_event::v3::Typing;
     /// # use matrix_sdk::{
@@ -1146,11 +1784,13 @@ impl Room {
         let send = if let Some(typing_time) =
             self.client.inner.typing_notice_times.read().unwrap().get(self.room_id())
         {
-            if typing_time.elapsed() > TYPING_NOTICE_RESEND_TIMEOUT {
+            let elapsed = self.client.inner.clock.now() - *typing_time;
+
+            if elapsed > TYPING_NOTICE_RESEND_TIMEOUT {
                 // We always reactivate the typing notice if typing is true or
                 // we may need to deactivate it if it's
                 // currently active if typing is false
-                typing || typing_time.elapsed() <= TYPING_NOTICE_TIMEOUT
+                typing || elapsed <= TYPING_NOTICE_TIMEOUT
             } else {
                 // Only send a request when we need to deactivate typing
                 !typing
@@ -1176,7 +1816,7 @@ impl Room {
                 .typing_notice_times
                 .write()
                 .unwrap()
-                .insert(self.room_id().to_owned(), Instant::now());
+                .insert(self.room_id().to_owned(), self.client.inner.clock.now());
             Typing::Yes(TYPING_NOTICE_TIMEOUT)
         } else {
             self.client.inner.typing_notice_times.write().unwrap().remove(self.room_id());
@@ -1541,6 +2181,112 @@ impl Room {
         SendRawMessageLikeEvent::new(self, event_type, content)
     }
 
+    /// Get the cache of the timeline events of this room.
+    ///
+    /// The events of the room start being cached the first time this is
+    /// called.
+    pub async fn event_cache(&self) -> RoomEventCache {
+        self.client.event_cache().for_room(self).await
+    }
+
+    /// Schedule a message to be sent to this room at the given time.
+    ///
+    /// The message is persisted in the store and sent by the client when its
+    /// time comes. If the client isn't running at that time, the message is
+    /// sent as soon as the client syncs again.
+    ///
+    /// Returns the transaction ID that will be used to send the message, which
+    /// can be used to cancel it with [`Room::cancel_scheduled_message()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the message event.
+    ///
+    /// * `send_at` - When the message should be sent.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{Client, ruma::room_id};
+    /// # use url::Url;
+    /// # async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// # let room_id = room_id!("!test:localhost");
+    /// use matrix_sdk::ruma::{
+    ///     events::room::message::RoomMessageEventContent, uint,
+    ///     MilliSecondsSinceUnixEpoch,
+    /// };
+    ///
+    /// if let Some(room) = client.get_room(&room_id) {
+    ///     let now = MilliSecondsSinceUnixEpoch::now();
+    ///     let in_an_hour =
+    ///         MilliSecondsSinceUnixEpoch(now.0 + uint!(3_600_000));
+    ///     let content = RoomMessageEventContent::text_plain("Good morning!");
+    ///
+    ///     room.schedule_message(content, in_an_hour).await?;
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn schedule_message(
+        &self,
+        content: impl MessageLikeEventContent,
+        send_at: MilliSecondsSinceUnixEpoch,
+    ) -> Result<OwnedTransactionId> {
+        let transaction_id = TransactionId::new();
+
+        self.client
+            .add_scheduled_message(ScheduledMessage {
+                room_id: self.room_id().to_owned(),
+                transaction_id: transaction_id.clone(),
+                send_at,
+                event_type: content.event_type().to_string(),
+                content: Raw::new(&content)?.cast(),
+            })
+            .await?;
+
+        Ok(transaction_id)
+    }
+
+    /// Get the messages scheduled with [`Room::schedule_message()`] in this
+    /// room that were not sent yet.
+    pub async fn scheduled_messages(&self) -> Result<Vec<ScheduledMessage>> {
+        let mut messages = self.client.scheduled_messages().await?;
+        messages.retain(|message| *message.room_id == *self.room_id());
+        Ok(messages)
+    }
+
+    /// Cancel a message scheduled with [`Room::schedule_message()`].
+    ///
+    /// Returns `false` if there was no such scheduled message, for example
+    /// because it was already sent.
+    pub async fn cancel_scheduled_message(&self, transaction_id: &TransactionId) -> Result<bool> {
+        self.client.remove_scheduled_message(self.room_id(), transaction_id).await
+    }
+
+    /// Get the ID of the event that was sent by this client in this room with
+    /// the given transaction ID.
+    ///
+    /// Only the most recently sent events are remembered, and they are
+    /// persisted in the store so they survive a restart of the client.
+    pub async fn event_id_for_transaction(
+        &self,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<OwnedEventId>> {
+        self.client.event_id_for_transaction(self.room_id(), transaction_id).await
+    }
+
+    /// Get the transaction ID that was used by this client to send the given
+    /// event in this room.
+    ///
+    /// This is the reverse lookup of [`Room::event_id_for_transaction()`].
+    pub async fn transaction_id_for_event(
+        &self,
+        event_id: &EventId,
+    ) -> Result<Option<OwnedTransactionId>> {
+        self.client.transaction_id_for_event(self.room_id(), event_id).await
+    }
+
     /// Send an attachment to this room.
     ///
     /// This will upload the given data that the reader produces using the
@@ -1597,6 +2343,43 @@ impl Room {
         data: Vec<u8>,
         config: AttachmentConfig,
     ) -> SendAttachment<'a> {
+        SendAttachment::new(self, body, content_type, AttachmentData::Bytes(data), config)
+    }
+
+    /// Send an attachment to this room, reading its data from `reader` as it
+    /// is uploaded.
+    ///
+    /// This is the same as [`Room::send_attachment()`], except that the data
+    /// of the attachment is never entirely loaded in memory, which allows to
+    /// send big files like videos. If the room is encrypted, the data is
+    /// encrypted as it is read.
+    ///
+    /// A thumbnail can't be generated from the data, so it is only sent if it
+    /// is set in the `config`. The upload is not retried if it fails.
+    ///
+    /// # Arguments
+    /// * `body` - A textual representation of the media that is going to be
+    /// uploaded. Usually the file name.
+    ///
+    /// * `content_type` - The type of the media, this will be used as the
+    /// content-type header.
+    ///
+    /// * `reader` - The reader of the raw bytes of the media.
+    ///
+    /// * `size` - The size of the media, in bytes.
+    ///
+    /// * `config` - Metadata and configuration for the attachment.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[instrument(skip_all)]
+    pub fn send_attachment_stream<'a>(
+        &'a self,
+        body: &'a str,
+        content_type: &'a Mime,
+        reader: impl futures_util::io::AsyncRead + Send + Sync + Unpin + 'static,
+        size: u64,
+        config: AttachmentConfig,
+    ) -> SendAttachment<'a> {
+        let data = AttachmentData::Stream { reader: Box::new(reader), size };
         SendAttachment::new(self, body, content_type, data, config)
     }
 
@@ -1626,7 +2409,7 @@ impl Room {
         &'a self,
         body: &'a str,
         content_type: &'a Mime,
-        data: Vec<u8>,
+        data: AttachmentData,
         config: AttachmentConfig,
         send_progress: SharedObservable<TransmissionProgress>,
     ) -> Result<send_message_event::v3::Response> {
@@ -1691,7 +2474,7 @@ impl Room {
         &self,
         updates: Vec<(&UserId, Int)>,
     ) -> Result<send_state_event::v3::Response> {
-        let mut power_levels = self.get_room_power_levels().await?;
+        let mut power_levels = self.power_levels().await?;
 
         for (user_id, new_level) in updates {
             if new_level == power_levels.users_default {
@@ -1704,7 +2487,27 @@ impl Room {
         self.send_state_event(RoomPowerLevelsEventContent::from(power_levels)).await
     }
 
-    async fn get_room_power_levels(&self) -> Result<RoomPowerLevels> {
+    /// Update the power levels required to send events of the given types in
+    /// this room.
+    ///
+    /// Like [`Room::update_power_levels()`], this is based on the locally
+    /// known `power_levels` and doesn't check that the user is allowed to
+    /// change them.
+    pub async fn update_event_power_levels(
+        &self,
+        updates: Vec<(TimelineEventType, Int)>,
+    ) -> Result<send_state_event::v3::Response> {
+        let mut power_levels = self.power_levels().await?;
+        power_levels.events.extend(updates);
+
+        self.send_state_event(RoomPowerLevelsEventContent::from(power_levels)).await
+    }
+
+    /// Get the power levels of this room, as they are known locally.
+    ///
+    /// Fails with [`Error::InsufficientData`] if the `power_levels` state
+    /// event hasn't been received yet.
+    pub async fn power_levels(&self) -> Result<RoomPowerLevels> {
         Ok(self
             .get_state_event_static::<RoomPowerLevelsEventContent>()
             .await?
@@ -1713,6 +2516,145 @@ impl Room {
             .power_levels())
     }
 
+    /// Upgrade this room to the given room version.
+    ///
+    /// This creates a replacement room with the given version that copies the
+    /// state selected in the `options`, and sends a tombstone to this room to
+    /// point its members to the replacement room. The encryption settings of
+    /// this room are always copied.
+    ///
+    /// The current user needs to be allowed to send an `m.room.tombstone`
+    /// event in this room. Returns the replacement room.
+    ///
+    /// The progress of the upgrade can be followed with
+    /// [`UpgradeRoom::with_progress_observable()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{room::RoomUpgradeOptions, Room};
+    /// # use ruma::RoomVersionId;
+    /// # async {
+    /// # let room: Room = todo!();
+    /// let options =
+    ///     RoomUpgradeOptions { invite_members: true, ..Default::default() };
+    /// let new_room = room.upgrade(RoomVersionId::V10, options).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    #[instrument(skip_all, fields(room_id = ?self.room_id()))]
+    pub fn upgrade(
+        &self,
+        new_version: RoomVersionId,
+        options: RoomUpgradeOptions,
+    ) -> UpgradeRoom<'_> {
+        UpgradeRoom::new(self, new_version, options)
+    }
+
+    async fn upgrade_inner(
+        &self,
+        new_version: RoomVersionId,
+        options: RoomUpgradeOptions,
+        progress: SharedObservable<RoomUpgradeProgress>,
+    ) -> Result<Room> {
+        self.ensure_room_joined()?;
+        progress.set(RoomUpgradeProgress::CreatingRoom);
+
+        // The predecessor of the new room must point to the last known event of
+        // this room.
+        let messages =
+            self.messages(assign!(MessagesOptions::backward(), { limit: uint!(1) })).await?;
+        let last_event_id = messages
+            .chunk
+            .first()
+            .and_then(|event| event.event_id())
+            .ok_or(Error::InsufficientData)?;
+
+        let mut initial_state = Vec::new();
+
+        if let Some(content) = self.encryption_settings() {
+            initial_state.push(InitialStateEvent::new(content).to_raw_any());
+        }
+
+        if let Some(name) = self.name().filter(|_| options.copy_name) {
+            initial_state
+                .push(InitialStateEvent::new(RoomNameEventContent::new(name)).to_raw_any());
+        }
+
+        if let Some(topic) = self.topic().filter(|_| options.copy_topic) {
+            initial_state
+                .push(InitialStateEvent::new(RoomTopicEventContent::new(topic)).to_raw_any());
+        }
+
+        if let Some(url) = self.avatar_url().filter(|_| options.copy_avatar) {
+            let content = assign!(RoomAvatarEventContent::new(), { url: Some(url) });
+            initial_state.push(InitialStateEvent::new(content).to_raw_any());
+        }
+
+        if options.copy_space_parents {
+            for raw in self.get_state_events_static::<SpaceParentEventContent>().await? {
+                if let Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(event))) =
+                    raw.deserialize()
+                {
+                    let event =
+                        InitialStateEvent { content: event.content, state_key: event.state_key };
+                    initial_state.push(event.to_raw_any());
+                }
+            }
+        }
+
+        let power_level_content_override = if options.copy_power_levels {
+            let content = RoomPowerLevelsEventContent::from(self.power_levels().await?);
+            Some(Raw::new(&content)?)
+        } else {
+            None
+        };
+
+        let predecessor = PreviousRoom::new(self.room_id().to_owned(), last_event_id);
+        let creation_content = assign!(CreationContent::new(), { predecessor: Some(predecessor) });
+        let request = assign!(create_room::v3::Request::new(), {
+            room_version: Some(new_version),
+            creation_content: Some(Raw::new(&creation_content)?),
+            initial_state,
+            power_level_content_override,
+        });
+
+        let new_room = self.client.create_room(request).await?;
+
+        if options.copy_bans {
+            let banned = self.members(RoomMemberships::BAN).await?;
+            let total = banned.len();
+
+            for (done, member) in banned.iter().enumerate() {
+                progress.set(RoomUpgradeProgress::CopyingBans { done, total });
+                new_room.ban_user(member.user_id(), None).await?;
+            }
+        }
+
+        progress.set(RoomUpgradeProgress::SendingTombstone);
+        let content =
+            RoomTombstoneEventContent::new(options.tombstone_body, new_room.room_id().to_owned());
+        self.send_state_event(content).await?;
+
+        if options.invite_members {
+            let own_user_id = self.own_user_id();
+            let members: Vec<_> = self
+                .members(RoomMemberships::JOIN)
+                .await?
+                .into_iter()
+                .filter(|member| member.user_id() != own_user_id)
+                .collect();
+            let total = members.len();
+
+            for (done, member) in members.iter().enumerate() {
+                progress.set(RoomUpgradeProgress::InvitingMembers { done, total });
+                new_room.invite_user_by_id(member.user_id()).await?;
+            }
+        }
+
+        progress.set(RoomUpgradeProgress::Done);
+        Ok(new_room)
+    }
+
     /// Sets the name of this room.
     pub async fn set_name(&self, name: String) -> Result<send_state_event::v3::Response> {
         self.send_state_event(RoomNameEventContent::new(name)).await
@@ -1747,6 +2689,232 @@ impl Room {
         self.send_state_event(RoomAvatarEventContent::new()).await
     }
 
+    /// Adds a room to the children of this space.
+    ///
+    /// # Arguments
+    /// * `child_id` - The ID of the room to add
+    /// * `via` - The servers to try to join the room through, it must not be
+    ///   empty
+    /// * `suggested` - Whether the space suggests its members to join the room
+    pub async fn add_space_child(
+        &self,
+        child_id: &RoomId,
+        via: Vec<OwnedServerName>,
+        suggested: bool,
+    ) -> Result<send_state_event::v3::Response> {
+        let content = assign!(SpaceChildEventContent::new(via), { suggested });
+        self.send_state_event_for_key(child_id, content).await
+    }
+
+    /// Removes a room from the children of this space.
+    ///
+    /// The `m.space.child` state event of the room is replaced by one without
+    /// servers to join through, which makes it invalid.
+    pub async fn remove_space_child(
+        &self,
+        child_id: &RoomId,
+    ) -> Result<send_state_event::v3::Response> {
+        self.send_state_event_for_key(child_id, SpaceChildEventContent::new(Vec::new())).await
+    }
+
+    /// Add a room to the children of this space, or update its settings.
+    ///
+    /// If the current user is joined to the child and allowed to, this space
+    /// is also added to the parents of the child, unless it is already one.
+    ///
+    /// Returns whether the `m.space.parent` event was sent in the child.
+    /// Returns an [`Error::RoomSettings`] if the order is invalid, and an
+    /// [`Error::InsufficientPowerLevel`] if the current user is not allowed
+    /// to change the children of this space. In both cases, no request is
+    /// sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `child_id` - The ID of the room to add.
+    ///
+    /// * `options` - The settings of the child.
+    pub async fn set_space_child(
+        &self,
+        child_id: &RoomId,
+        options: SpaceChildOptions,
+    ) -> Result<bool> {
+        let SpaceChildOptions { via, order, suggested } = options;
+
+        if let Some(order) = &order {
+            if !space::is_valid_order(order) {
+                return Err(RoomSettingsError::InvalidSpaceChildOrder(order.clone()).into());
+            }
+        }
+
+        self.ensure_can_send_state(StateEventType::SpaceChild).await?;
+
+        let child = self.client.get_room(child_id).filter(|room| room.state() == RoomState::Joined);
+        let via = match (via.is_empty(), &child) {
+            (false, _) => via,
+            (true, Some(child)) => child.via_servers().await?,
+            (true, None) => vec![self.own_user_id().server_name().to_owned()],
+        };
+
+        let content = assign!(SpaceChildEventContent::new(via), { order, suggested });
+        self.send_state_event_for_key(child_id, content).await?;
+
+        let Some(child) = child else {
+            return Ok(false);
+        };
+
+        if child.has_space_parent(self.room_id()).await?
+            || !child.can_user_send_state(self.own_user_id(), StateEventType::SpaceParent).await?
+        {
+            return Ok(false);
+        }
+
+        let content = SpaceParentEventContent::new(self.via_servers().await?);
+        child.send_state_event_for_key(self.room_id(), content).await?;
+
+        Ok(true)
+    }
+
+    /// Add a space to the parents of this room.
+    ///
+    /// If the current user is joined to the space and allowed to, this room
+    /// is also added to the children of the space, unless it is already one.
+    ///
+    /// Returns whether the `m.space.child` event was sent in the space.
+    /// Returns an [`Error::InsufficientPowerLevel`] if the current user is not
+    /// allowed to change the parents of this room, in which case no request is
+    /// sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent_id` - The ID of the space to add.
+    ///
+    /// * `canonical` - Whether the space is the main parent of this room.
+    pub async fn set_space_parent(&self, parent_id: &RoomId, canonical: bool) -> Result<bool> {
+        self.ensure_can_send_state(StateEventType::SpaceParent).await?;
+
+        let parent =
+            self.client.get_room(parent_id).filter(|room| room.state() == RoomState::Joined);
+        let via = match &parent {
+            Some(parent) => parent.via_servers().await?,
+            None => vec![self.own_user_id().server_name().to_owned()],
+        };
+
+        let content = assign!(SpaceParentEventContent::new(via), { canonical });
+        self.send_state_event_for_key(parent_id, content).await?;
+
+        let Some(parent) = parent else {
+            return Ok(false);
+        };
+
+        if parent.has_space_child(self.room_id()).await?
+            || !parent.can_user_send_state(self.own_user_id(), StateEventType::SpaceChild).await?
+        {
+            return Ok(false);
+        }
+
+        let content = SpaceChildEventContent::new(self.via_servers().await?);
+        parent.send_state_event_for_key(self.room_id(), content).await?;
+
+        Ok(true)
+    }
+
+    /// Remove a space from the parents of this room.
+    ///
+    /// The `m.space.parent` state event of the space is replaced by one
+    /// without servers to join through, which makes it invalid.
+    pub async fn remove_space_parent(&self, parent_id: &RoomId) -> Result<()> {
+        self.ensure_can_send_state(StateEventType::SpaceParent).await?;
+        self.send_state_event_for_key(parent_id, SpaceParentEventContent::new(Vec::new())).await?;
+        Ok(())
+    }
+
+    /// Get the children of this space, as listed in its state.
+    ///
+    /// The children are sorted like in the [spec]: the ones with an order
+    /// first, and the others by the time they were added.
+    ///
+    /// [spec]: https://spec.matrix.org/v1.8/client-server-api/#ordering-of-children-within-a-space
+    pub async fn space_children(&self) -> Result<Vec<SpaceChild>> {
+        let mut children: Vec<_> = self
+            .get_state_events_static::<SpaceChildEventContent>()
+            .await?
+            .into_iter()
+            .filter_map(|raw| match raw.deserialize() {
+                Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev)))
+                    if !ev.content.via.is_empty() =>
+                {
+                    let child = SpaceChild {
+                        room_id: ev.state_key,
+                        via: ev.content.via,
+                        order: ev.content.order.filter(|order| space::is_valid_order(order)),
+                        suggested: ev.content.suggested,
+                    };
+                    Some((child, ev.origin_server_ts))
+                }
+                _ => None,
+            })
+            .collect();
+
+        space::sort_children(&mut children);
+
+        Ok(children.into_iter().map(|(child, _)| child).collect())
+    }
+
+    /// Get the IDs of the spaces this room lists as its parents.
+    ///
+    /// The canonical parent comes first. Unlike [`Room::parent_spaces()`],
+    /// this doesn't check whether the spaces list this room as their child.
+    pub async fn parent_space_ids(&self) -> Result<Vec<OwnedRoomId>> {
+        let mut parents: Vec<_> = self
+            .get_state_events_static::<SpaceParentEventContent>()
+            .await?
+            .into_iter()
+            .filter_map(|raw| match raw.deserialize() {
+                Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev)))
+                    if !ev.content.via.is_empty() =>
+                {
+                    Some((ev.state_key, ev.content.canonical))
+                }
+                Ok(SyncOrStrippedState::Stripped(ev)) => Some((ev.state_key, false)),
+                _ => None,
+            })
+            .collect();
+
+        parents.sort_by_key(|(_, canonical)| !canonical);
+
+        Ok(parents.into_iter().map(|(room_id, _)| room_id).collect())
+    }
+
+    /// Whether this space lists the given room as a child.
+    async fn has_space_child(&self, child_id: &RoomId) -> Result<bool> {
+        let raw =
+            self.get_state_event_static_for_key::<SpaceChildEventContent, _>(child_id).await?;
+        Ok(matches!(
+            raw.map(|raw| raw.deserialize()),
+            Some(Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev)))) if !ev.content.via.is_empty()
+        ))
+    }
+
+    /// Whether this room lists the given space as a parent.
+    async fn has_space_parent(&self, parent_id: &RoomId) -> Result<bool> {
+        let raw =
+            self.get_state_event_static_for_key::<SpaceParentEventContent, _>(parent_id).await?;
+        Ok(matches!(
+            raw.map(|raw| raw.deserialize()),
+            Some(Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev)))) if !ev.content.via.is_empty()
+        ))
+    }
+
+    /// The servers to join this room through, as computed by
+    /// [`Room::route()`], or the server of the current user if none was found.
+    async fn via_servers(&self) -> Result<Vec<OwnedServerName>> {
+        let mut via = self.route().await?;
+        if via.is_empty() {
+            via.push(self.own_user_id().server_name().to_owned());
+        }
+        Ok(via)
+    }
+
     /// Uploads a new avatar for this room.
     ///
     /// # Arguments
@@ -1931,6 +3099,78 @@ impl Room {
         Ok(self.client.send(request, None).await?)
     }
 
+    /// Send a delayed state event to this room.
+    ///
+    /// The homeserver only sends the event once `delay` has elapsed, unless
+    /// the delayed event is restarted or cancelled before with
+    /// [`Room::update_delayed_event()`]. This allows to have state reset by the
+    /// homeserver if the client stops restarting the delayed event, e.g.
+    /// because it crashed.
+    ///
+    /// Returns the ID of the delayed event.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_key` - A unique key which defines the overwriting semantics for
+    ///   this piece of room state.
+    ///
+    /// * `content` - The content of the state event.
+    ///
+    /// * `delay` - The delay after which the homeserver sends the event.
+    pub async fn send_delayed_state_event<C, K>(
+        &self,
+        state_key: &K,
+        content: C,
+        delay: Duration,
+    ) -> Result<String>
+    where
+        C: StateEventContent,
+        C::StateKey: Borrow<K>,
+        K: AsRef<str> + ?Sized,
+    {
+        let event_type = content.event_type().to_string();
+        let content: Raw<AnyStateEventContent> = Raw::new(&content)?.cast();
+        self.send_delayed_state_event_raw(&event_type, state_key.as_ref(), content, delay).await
+    }
+
+    /// Send a delayed state event with the given type to this room.
+    ///
+    /// This is the raw version of [`Room::send_delayed_state_event()`].
+    ///
+    /// Returns the ID of the delayed event.
+    pub async fn send_delayed_state_event_raw(
+        &self,
+        event_type: &str,
+        state_key: &str,
+        content: impl IntoRawStateEventContent,
+        delay: Duration,
+    ) -> Result<String> {
+        self.ensure_room_joined()?;
+
+        let request = delayed_events::send_state_event::Request {
+            room_id: self.room_id().to_owned(),
+            event_type: event_type.to_owned(),
+            state_key: state_key.to_owned(),
+            delay: Some(delay.as_millis().try_into().unwrap_or(u64::MAX)),
+            parent_delay_id: None,
+            body: content.into_raw_state_event_content(),
+        };
+
+        Ok(self.client.send(request, None).await?.delay_id)
+    }
+
+    /// Cancel, restart or immediately send the delayed event with the given
+    /// ID.
+    pub async fn update_delayed_event(
+        &self,
+        delay_id: &str,
+        action: UpdateDelayedEventAction,
+    ) -> Result<()> {
+        let request = delayed_events::update::Request { delay_id: delay_id.to_owned(), action };
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+
     /// Strips all information out of an event of the room.
     ///
     /// Returns the [`redact_event::v3::Response`] from the server.
@@ -1981,12 +3221,71 @@ impl Room {
         self.client.send(request, None).await
     }
 
+    /// Redact the most recent messages sent by a user in this room, e.g. to
+    /// clean up after a spammer.
+    ///
+    /// The history of the room is paginated backwards from its end, until
+    /// `limit` messages of the user are found or the start of the room is
+    /// reached. State events and messages that are already redacted are
+    /// skipped.
+    ///
+    /// Returns the IDs of the redacted events.
+    pub async fn redact_recent_messages(
+        &self,
+        user_id: &UserId,
+        limit: usize,
+        reason: Option<&str>,
+    ) -> Result<Vec<OwnedEventId>> {
+        let mut event_ids = Vec::new();
+        let mut from = None;
+
+        while event_ids.len() < limit {
+            let mut options = MessagesOptions::backward().from(from.as_deref());
+            options.limit = uint!(50);
+            options.filter.senders = Some(vec![user_id.to_owned()]);
+
+            let messages = self.messages(options).await?;
+
+            for event in &messages.chunk {
+                let Ok(AnySyncTimelineEvent::MessageLike(event)) =
+                    event.event.deserialize_as::<AnySyncTimelineEvent>()
+                else {
+                    continue;
+                };
+
+                if event.sender() != user_id
+                    || event.original_content().is_none()
+                    || event.event_type() == MessageLikeEventType::RoomRedaction
+                {
+                    continue;
+                }
+
+                event_ids.push(event.event_id().to_owned());
+                if event_ids.len() == limit {
+                    break;
+                }
+            }
+
+            // The end token is missing when the start of the room is reached.
+            match messages.end {
+                Some(end) if from.as_ref() != Some(&end) => from = Some(end),
+                _ => break,
+            }
+        }
+
+        for event_id in &event_ids {
+            self.redact(event_id, reason, None).await?;
+        }
+
+        Ok(event_ids)
+    }
+
     /// Returns true if the user with the given user_id is able to redact
     /// messages in the room.
     ///
     /// The call may fail if there is an error in getting the power levels.
     pub async fn can_user_redact(&self, user_id: &UserId) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_redact(user_id))
+        Ok(self.power_levels().await?.user_can_redact(user_id))
     }
 
     /// Returns true if the user with the given user_id is able to ban in the
@@ -1994,7 +3293,7 @@ impl Room {
     ///
     /// The call may fail if there is an error in getting the power levels.
     pub async fn can_user_ban(&self, user_id: &UserId) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_ban(user_id))
+        Ok(self.power_levels().await?.user_can_ban(user_id))
     }
 
     /// Returns true if the user with the given user_id is able to kick in the
@@ -2002,7 +3301,7 @@ impl Room {
     ///
     /// The call may fail if there is an error in getting the power levels.
     pub async fn can_user_invite(&self, user_id: &UserId) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_invite(user_id))
+        Ok(self.power_levels().await?.user_can_invite(user_id))
     }
 
     /// Returns true if the user with the given user_id is able to kick in the
@@ -2010,7 +3309,7 @@ impl Room {
     ///
     /// The call may fail if there is an error in getting the power levels.
     pub async fn can_user_kick(&self, user_id: &UserId) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_kick(user_id))
+        Ok(self.power_levels().await?.user_can_kick(user_id))
     }
 
     /// Returns true if the user with the given user_id is able to send a
@@ -2022,7 +3321,7 @@ impl Room {
         user_id: &UserId,
         state_event: StateEventType,
     ) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_send_state(user_id, state_event))
+        Ok(self.power_levels().await?.user_can_send_state(user_id, state_event))
     }
 
     /// Returns true if the user with the given user_id is able to send a
@@ -2034,7 +3333,7 @@ impl Room {
         user_id: &UserId,
         message: MessageLikeEventType,
     ) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_send_message(user_id, message))
+        Ok(self.power_levels().await?.user_can_send_message(user_id, message))
     }
 
     /// Returns true if the user with the given user_id is able to trigger a
@@ -2042,7 +3341,7 @@ impl Room {
     ///
     /// The call may fail if there is an error in getting the power levels.
     pub async fn can_user_trigger_room_notification(&self, user_id: &UserId) -> Result<bool> {
-        Ok(self.get_room_power_levels().await?.user_can_trigger_room_notification(user_id))
+        Ok(self.power_levels().await?.user_can_trigger_room_notification(user_id))
     }
 
     /// Get a list of servers that should know this room.
@@ -2111,12 +3410,7 @@ impl Room {
     ///
     /// [routing]: https://spec.matrix.org/v1.3/appendices/#routing
     pub async fn matrix_to_permalink(&self) -> Result<MatrixToUri> {
-        if let Some(alias) = self.canonical_alias().or_else(|| self.alt_aliases().pop()) {
-            return Ok(alias.matrix_to_uri());
-        }
-
-        let via = self.route().await?;
-        Ok(self.room_id().matrix_to_uri_via(via))
+        Ok(UriTarget::room(self).await?.matrix_to_uri())
     }
 
     /// Get a `matrix:` permalink to this room.
@@ -2130,12 +3424,7 @@ impl Room {
     ///
     /// [routing]: https://spec.matrix.org/v1.3/appendices/#routing
     pub async fn matrix_permalink(&self, join: bool) -> Result<MatrixUri> {
-        if let Some(alias) = self.canonical_alias().or_else(|| self.alt_aliases().pop()) {
-            return Ok(alias.matrix_uri(join));
-        }
-
-        let via = self.route().await?;
-        Ok(self.room_id().matrix_uri_via(via, join))
+        Ok(UriTarget::room(self).await?.matrix_uri(join))
     }
 
     /// Get a `matrix.to` permalink to an event in this room.
@@ -2155,10 +3444,7 @@ impl Room {
         &self,
         event_id: impl Into<OwnedEventId>,
     ) -> Result<MatrixToUri> {
-        // Don't use the alias because an event is tied to a room ID, but an
-        // alias might point to another room, e.g. after a room upgrade.
-        let via = self.route().await?;
-        Ok(self.room_id().matrix_to_event_uri_via(event_id, via))
+        Ok(UriTarget::event(self, event_id).await?.matrix_to_uri())
     }
 
     /// Get a `matrix:` permalink to an event in this room.
@@ -2178,10 +3464,7 @@ impl Room {
         &self,
         event_id: impl Into<OwnedEventId>,
     ) -> Result<MatrixUri> {
-        // Don't use the alias because an event is tied to a room ID, but an
-        // alias might point to another room, e.g. after a room upgrade.
-        let via = self.route().await?;
-        Ok(self.room_id().matrix_event_uri_via(event_id, via))
+        Ok(UriTarget::event(self, event_id).await?.matrix_uri(false))
     }
 
     /// Get the latest receipt of a user in this room.
@@ -2296,6 +3579,72 @@ impl Room {
         Ok(Invite { invitee, inviter })
     }
 
+    /// Report an event of this room to the administrators of the homeserver.
+    ///
+    /// # Arguments
+    ///
+    /// * `event_id` - The ID of the event to report.
+    ///
+    /// * `score` - How offensive the event is, from `-100` for the most
+    ///   offensive to `0` for inoffensive.
+    ///
+    /// * `reason` - The reason for reporting the event.
+    pub async fn report_event(
+        &self,
+        event_id: &EventId,
+        score: Option<Int>,
+        reason: Option<String>,
+    ) -> Result<()> {
+        let request = report_content::v3::Request::new(
+            self.room_id().to_owned(),
+            event_id.to_owned(),
+            score,
+            reason,
+        );
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+
+    /// Report this room to the administrators of the homeserver.
+    ///
+    /// See [`Client::report_room()`].
+    pub async fn report(&self, reason: String) -> Result<()> {
+        self.client.report_room(self.room_id(), reason).await
+    }
+
+    /// Decline the invite to this room and ignore the user who sent it, so
+    /// that they can't send other invites.
+    ///
+    /// If a `report_reason` is given, the invite is also reported to the
+    /// homeserver administrators before declining it. This is only possible
+    /// when the ID of the invite event is known, otherwise reporting is
+    /// skipped.
+    ///
+    /// The user who sent the invite is ignored first, so they are blocked
+    /// even if reporting or declining the invite fails. In that case, this
+    /// method can be called again to finish the other steps.
+    ///
+    /// Only invited rooms can be declined.
+    pub async fn decline_and_block(&self, report_reason: Option<String>) -> Result<()> {
+        let invite = self.invite_details().await?;
+        let invite_event = invite.invitee.event();
+        let inviter_id = invite_event.sender().to_owned();
+
+        self.client.account().ignore_user(&inviter_id).await?;
+
+        if let Some(reason) = report_reason {
+            if let Some(event_id) = invite_event.event_id() {
+                self.report_event(event_id, None, Some(reason)).await?;
+            } else {
+                warn!(room_id = ?self.room_id(), "Can't report the invite, its event ID is unknown");
+            }
+        }
+
+        self.leave().await?;
+
+        Ok(())
+    }
+
     /// Forget this room.
     ///
     /// This communicates to the homeserver that it should forget the room.
@@ -2360,6 +3709,219 @@ impl Room {
         // Get the user-defined mode if available
         notification_settings.get_user_defined_room_notification_mode(self.room_id()).await
     }
+
+    /// The call ongoing in this room, if any.
+    ///
+    /// Only the non expired memberships with application `m.call` and scope
+    /// `m.room` are considered.
+    pub fn active_call(&self) -> Option<ActiveCall> {
+        let memberships = self.active_room_call_memberships();
+        ActiveCall::from_memberships(memberships.iter().map(|(user_id, m)| (user_id.clone(), m)))
+    }
+
+    /// Subscribe to the call ongoing in this room.
+    ///
+    /// The stream yields a new value every time a call starts, ends, or its
+    /// participants change, as seen by the sync. It does not yield the
+    /// current value, which can be obtained with [`Room::active_call()`].
+    ///
+    /// Memberships that expire without being updated are only taken into
+    /// account on the next change of the room.
+    pub fn subscribe_to_active_call(&self) -> impl Stream<Item = Option<ActiveCall>> {
+        let mut current = self.active_call();
+
+        self.subscribe_info().filter_map(move |info| {
+            let call = ActiveCall::from_memberships(info.active_room_call_memberships());
+
+            if call == current {
+                ready(None)
+            } else {
+                current = call.clone();
+                ready(Some(call))
+            }
+        })
+    }
+
+    /// Join the call of this room with the current device.
+    ///
+    /// This sends an `m.call.member` state event advertising a membership
+    /// with application `m.call` and scope `m.room`, that expires after
+    /// [`CALL_MEMBERSHIP_EXPIRATION`]. It must be refreshed with
+    /// [`Room::refresh_room_call_membership()`] for as long as the device
+    /// stays in the call.
+    ///
+    /// The memberships of the other devices of the user are kept, and a
+    /// previous membership of the current device is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `foci_active` - The foci the device uses for the call.
+    pub async fn join_room_call(&self, foci_active: Vec<Focus>) -> Result<()> {
+        let device_id = self.client.device_id().ok_or(Error::AuthenticationRequired)?;
+        let mut memberships = self.own_call_memberships().await?;
+
+        memberships.retain(|m| !(m.device_id == device_id.as_str() && m.is_room_call()));
+        memberships.push(Membership::from(MembershipInit {
+            application: Application::Call(CallApplicationContent::new(
+                String::new(),
+                CallScope::Room,
+            )),
+            device_id: device_id.to_string(),
+            expires: CALL_MEMBERSHIP_EXPIRATION,
+            foci_active,
+            membership_id: TransactionId::new().to_string(),
+        }));
+
+        self.send_own_call_memberships(memberships).await
+    }
+
+    /// Refresh the membership of the current device in the call of this room.
+    ///
+    /// The membership is extended to expire [`CALL_MEMBERSHIP_EXPIRATION`]
+    /// from now. This should be called regularly while the device is in the
+    /// call, before its membership expires.
+    ///
+    /// Returns `false` if the current device has no active membership, in
+    /// which case nothing is sent.
+    pub async fn refresh_room_call_membership(&self) -> Result<bool> {
+        let device_id = self.client.device_id().ok_or(Error::AuthenticationRequired)?;
+        let mut memberships = self.own_call_memberships().await?;
+
+        let Some(membership) =
+            memberships.iter_mut().find(|m| m.device_id == device_id.as_str() && m.is_room_call())
+        else {
+            return Ok(false);
+        };
+
+        // The expiration is relative to the creation of the membership, which
+        // doesn't change.
+        let elapsed = membership
+            .created_ts
+            .and_then(|ts| ts.to_system_time())
+            .and_then(|created| SystemTime::now().duration_since(created).ok())
+            .unwrap_or_default();
+        membership.expires = elapsed + CALL_MEMBERSHIP_EXPIRATION;
+
+        self.send_own_call_memberships(memberships).await?;
+        Ok(true)
+    }
+
+    /// Leave the call of this room with the current device.
+    ///
+    /// This removes the membership of the current device from its
+    /// `m.call.member` state event. Nothing is sent if the current device
+    /// has no active membership.
+    pub async fn leave_room_call(&self) -> Result<()> {
+        let device_id = self.client.device_id().ok_or(Error::AuthenticationRequired)?;
+        let mut memberships = self.own_call_memberships().await?;
+
+        let len = memberships.len();
+        memberships.retain(|m| !(m.device_id == device_id.as_str() && m.is_room_call()));
+
+        if memberships.len() != len {
+            self.send_own_call_memberships(memberships).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Schedule the current device to leave the call of this room after the
+    /// given delay.
+    ///
+    /// This sends a delayed `m.call.member` state event without the membership
+    /// of the current device. It must be restarted with
+    /// [`Room::update_delayed_event()`] regularly while the device is in the
+    /// call, so the homeserver only removes the membership if the client stops
+    /// doing so, e.g. because it crashed. It should be cancelled when leaving
+    /// the call normally.
+    ///
+    /// Returns the ID of the delayed event.
+    pub async fn schedule_room_call_leave(&self, delay: Duration) -> Result<String> {
+        let device_id = self.client.device_id().ok_or(Error::AuthenticationRequired)?;
+        let mut memberships = self.own_call_memberships().await?;
+        memberships.retain(|m| !(m.device_id == device_id.as_str() && m.is_room_call()));
+
+        let content = CallMemberEventContent::new(memberships);
+        self.send_delayed_state_event(self.own_user_id(), content, delay).await
+    }
+
+    /// Start tracking the 1:1 VoIP calls of this room.
+    ///
+    /// The returned session listens to the `m.call.*` events of this room
+    /// until it is dropped. See [`CallSignalingSession`] for more details.
+    pub fn call_signaling_session(&self) -> CallSignalingSession {
+        CallSignalingSession::new(self.clone())
+    }
+
+    /// The active memberships in the `m.call.member` state event of the
+    /// current user.
+    async fn own_call_memberships(&self) -> Result<Vec<Membership>> {
+        let Some(raw_event) = self
+            .get_state_event_static_for_key::<CallMemberEventContent, _>(self.own_user_id())
+            .await?
+        else {
+            return Ok(Vec::new());
+        };
+
+        let SyncOrStrippedState::Sync(SyncStateEvent::Original(mut event)) =
+            raw_event.deserialize()?
+        else {
+            return Ok(Vec::new());
+        };
+
+        // Keep the creation time of the memberships, to preserve their order
+        // and expiration across updates.
+        event.content.set_created_ts_if_none(event.origin_server_ts);
+
+        Ok(event.content.active_memberships(None).into_iter().cloned().collect())
+    }
+
+    async fn send_own_call_memberships(&self, memberships: Vec<Membership>) -> Result<()> {
+        let content = CallMemberEventContent::new(memberships);
+        self.send_state_event_for_key(self.own_user_id(), content).await?;
+        Ok(())
+    }
+}
+
+/// Whether the given join rule can be used in a room with the given version.
+///
+/// Unknown room versions are assumed to support all the join rules.
+fn supports_join_rule(room_version: &RoomVersionId, join_rule: &JoinRule) -> bool {
+    let before_v7 = matches!(
+        room_version,
+        RoomVersionId::V1
+            | RoomVersionId::V2
+            | RoomVersionId::V3
+            | RoomVersionId::V4
+            | RoomVersionId::V5
+            | RoomVersionId::V6
+    );
+
+    match join_rule {
+        JoinRule::Knock => !before_v7,
+        JoinRule::Restricted(_) => !before_v7 && *room_version != RoomVersionId::V7,
+        JoinRule::KnockRestricted(_) => {
+            !before_v7
+                && !matches!(
+                    room_version,
+                    RoomVersionId::V7 | RoomVersionId::V8 | RoomVersionId::V9
+                )
+        }
+        _ => true,
+    }
+}
+
+/// Whether the display name or the user ID of the member starts with the
+/// lowercase query.
+fn member_matches(member: &BaseRoomMember, query: &str) -> bool {
+    if member.user_id().localpart().to_lowercase().starts_with(query) {
+        return true;
+    }
+
+    member.display_name().is_some_and(|name| {
+        let name = name.to_lowercase();
+        name.starts_with(query) || name.split_whitespace().any(|word| word.starts_with(query))
+    })
 }
 
 /// Details of the (latest) invite.
@@ -2454,18 +4016,33 @@ pub enum ParentSpace {
     Unverifiable(OwnedRoomId),
 }
 
+/// A custom room account data event storing whether URL previews are shown
+/// in a room, with the same type as other clients.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "org.matrix.room.preview_urls", kind = RoomAccountData)]
+struct UrlPreviewsEventContent {
+    /// Whether URL previews are disabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    disable: Option<bool>,
+}
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 mod tests {
     use matrix_sdk_base::SessionMeta;
     use matrix_sdk_test::{
         async_test, test_json, JoinedRoomBuilder, StateTestEvent, SyncResponseBuilder,
     };
-    use ruma::{device_id, user_id};
+    use ruma::{
+        device_id,
+        events::room::join_rules::{JoinRule, Restricted},
+        user_id, RoomVersionId,
+    };
     use wiremock::{
         matchers::{header, method, path_regex},
         Mock, MockServer, ResponseTemplate,
     };
 
+    use super::supports_join_rule;
     use crate::{
         config::RequestConfig,
         matrix_auth::{MatrixSession, MatrixSessionTokens},
@@ -2561,4 +4138,18 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[test]
+    fn test_supports_join_rule() {
+        let restricted = JoinRule::Restricted(Restricted::new(vec![]));
+        let knock_restricted = JoinRule::KnockRestricted(Restricted::new(vec![]));
+
+        assert!(supports_join_rule(&RoomVersionId::V1, &JoinRule::Invite));
+        assert!(!supports_join_rule(&RoomVersionId::V6, &JoinRule::Knock));
+        assert!(supports_join_rule(&RoomVersionId::V7, &JoinRule::Knock));
+        assert!(!supports_join_rule(&RoomVersionId::V7, &restricted));
+        assert!(supports_join_rule(&RoomVersionId::V8, &restricted));
+        assert!(!supports_join_rule(&RoomVersionId::V9, &knock_restricted));
+        assert!(supports_join_rule(&RoomVersionId::V10, &knock_restricted));
+    }
 }
@@ -0,0 +1,163 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use matrix_sdk_common::{debug::DebugStructExt as _, deserialized_responses::TimelineEvent};
+use ruma::{
+    api::{
+        client::relations::{
+            get_relating_events, get_relating_events_with_rel_type,
+            get_relating_events_with_rel_type_and_event_type,
+        },
+        Direction,
+    },
+    assign,
+    events::{relation::RelationType, TimelineEventType},
+    EventId, RoomId, UInt,
+};
+
+/// Options for [`relations`][super::Room::relations].
+///
+/// See that method and
+/// <https://spec.matrix.org/v1.8/client-server-api/#get_matrixclientv1roomsroomidrelationseventid>
+/// for details.
+#[non_exhaustive]
+#[derive(Clone)]
+pub struct RelationsOptions {
+    /// The token to start returning events from.
+    ///
+    /// This token can be obtained from the `next_batch` or `prev_batch` token
+    /// of a previous `relations` call.
+    pub from: Option<String>,
+
+    /// The token to stop returning events at.
+    pub to: Option<String>,
+
+    /// The direction to return events in.
+    ///
+    /// Default: `Backward`, which returns the most recent relations first.
+    pub dir: Direction,
+
+    /// The maximum number of events to return.
+    ///
+    /// The homeserver uses its own default if this is `None`.
+    pub limit: Option<UInt>,
+
+    /// Only return the events with this type of relation.
+    pub rel_type: Option<RelationType>,
+
+    /// Only return the events with this type.
+    ///
+    /// This is only used if `rel_type` is set, because the homeserver can't
+    /// filter by event type only.
+    pub event_type: Option<TimelineEventType>,
+}
+
+impl RelationsOptions {
+    /// Creates `RelationsOptions` that return all the relations of an event,
+    /// the most recent first.
+    pub fn new() -> Self {
+        Self {
+            from: None,
+            to: None,
+            dir: Direction::Backward,
+            limit: None,
+            rel_type: None,
+            event_type: None,
+        }
+    }
+
+    /// Creates `RelationsOptions` that only return the relations with the
+    /// given type.
+    pub fn with_rel_type(rel_type: RelationType) -> Self {
+        Self { rel_type: Some(rel_type), ..Self::new() }
+    }
+
+    /// Creates a new `RelationsOptions` from `self` with the `from` field set
+    /// to the given value.
+    pub fn from<'a>(self, from: impl Into<Option<&'a str>>) -> Self {
+        Self { from: from.into().map(ToOwned::to_owned), ..self }
+    }
+
+    pub(super) fn into_request(self, room_id: &RoomId, event_id: &EventId) -> RelationsRequest {
+        let room_id = room_id.to_owned();
+        let event_id = event_id.to_owned();
+
+        match (self.rel_type, self.event_type) {
+            (None, _) => RelationsRequest::All(assign!(
+                get_relating_events::v1::Request::new(room_id, event_id),
+                { from: self.from, to: self.to, dir: self.dir, limit: self.limit }
+            )),
+            (Some(rel_type), None) => RelationsRequest::WithRelType(assign!(
+                get_relating_events_with_rel_type::v1::Request::new(room_id, event_id, rel_type),
+                { from: self.from, to: self.to, dir: self.dir, limit: self.limit }
+            )),
+            (Some(rel_type), Some(event_type)) => {
+                RelationsRequest::WithRelTypeAndEventType(assign!(
+                    get_relating_events_with_rel_type_and_event_type::v1::Request::new(
+                        room_id, event_id, rel_type, event_type,
+                    ),
+                    { from: self.from, to: self.to, dir: self.dir, limit: self.limit }
+                ))
+            }
+        }
+    }
+}
+
+impl Default for RelationsOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for RelationsOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { from, to, dir, limit, rel_type, event_type } = self;
+
+        f.debug_struct("RelationsOptions")
+            .maybe_field("from", from)
+            .maybe_field("to", to)
+            .field("dir", dir)
+            .maybe_field("limit", limit)
+            .maybe_field("rel_type", rel_type)
+            .maybe_field("event_type", event_type)
+            .finish()
+    }
+}
+
+/// The request to send for the given [`RelationsOptions`], since the
+/// homeserver has a different endpoint for each filter.
+pub(super) enum RelationsRequest {
+    All(get_relating_events::v1::Request),
+    WithRelType(get_relating_events_with_rel_type::v1::Request),
+    WithRelTypeAndEventType(get_relating_events_with_rel_type_and_event_type::v1::Request),
+}
+
+/// The result of a `Room::relations` call.
+///
+/// In short, this is a possibly decrypted version of the response of a
+/// `rooms/relations` api call.
+#[derive(Debug)]
+pub struct Relations {
+    /// The events relating to the parent event.
+    pub chunk: Vec<TimelineEvent>,
+
+    /// The token to get the next page of results, if there are more.
+    pub next_batch: Option<String>,
+
+    /// The token to get the previous page of results, if any.
+    pub prev_batch: Option<String>,
+}
@@ -0,0 +1,134 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use ruma::{MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedServerName};
+
+/// The maximum length of the `order` of an `m.space.child` event.
+const MAX_ORDER_LENGTH: usize = 50;
+
+/// The settings of a child of a space, used by [`Room::set_space_child`].
+///
+/// [`Room::set_space_child`]: super::Room::set_space_child
+#[derive(Clone, Debug, Default)]
+pub struct SpaceChildOptions {
+    /// The servers to join the child through.
+    ///
+    /// If empty, the servers are computed from the state of the child if it
+    /// is known, or else the server of the current user is used.
+    pub via: Vec<OwnedServerName>,
+    /// The string used to sort the children of the space, in lexicographic
+    /// order.
+    ///
+    /// It must be at most 50 characters long, with only printable ASCII
+    /// characters.
+    pub order: Option<String>,
+    /// Whether the space suggests its members to join the child.
+    pub suggested: bool,
+}
+
+/// A child of a space, from its `m.space.child` event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpaceChild {
+    /// The ID of the child room.
+    pub room_id: OwnedRoomId,
+    /// The servers to join the child through.
+    pub via: Vec<OwnedServerName>,
+    /// The string used to sort the children of the space.
+    pub order: Option<String>,
+    /// Whether the space suggests its members to join the child.
+    pub suggested: bool,
+}
+
+/// Whether the given string can be used as the `order` of an `m.space.child`
+/// event.
+pub(super) fn is_valid_order(order: &str) -> bool {
+    order.len() <= MAX_ORDER_LENGTH && order.bytes().all(|b| (0x20..=0x7E).contains(&b))
+}
+
+/// Sort the children of a space, as defined in the [spec].
+///
+/// The children with an order come first, by lexicographic order, then the
+/// others by the timestamp of their `m.space.child` event. The room IDs are
+/// used as a tie-breaker.
+///
+/// [spec]: https://spec.matrix.org/v1.8/client-server-api/#ordering-of-children-within-a-space
+pub(super) fn sort_children(children: &mut [(SpaceChild, MilliSecondsSinceUnixEpoch)]) {
+    children.sort_by(|(left, left_ts), (right, right_ts)| {
+        let by_order = match (&left.order, &right.order) {
+            (Some(left), Some(right)) => left.cmp(right),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+
+        by_order.then(left_ts.cmp(right_ts)).then_with(|| left.room_id.cmp(&right.room_id))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_id, MilliSecondsSinceUnixEpoch};
+
+    use super::{is_valid_order, sort_children, SpaceChild};
+
+    fn child(
+        room_id: &str,
+        order: Option<&str>,
+        ts: u32,
+    ) -> (SpaceChild, MilliSecondsSinceUnixEpoch) {
+        let child = SpaceChild {
+            room_id: room_id.try_into().unwrap(),
+            via: Vec::new(),
+            order: order.map(ToOwned::to_owned),
+            suggested: false,
+        };
+
+        (child, MilliSecondsSinceUnixEpoch(ts.into()))
+    }
+
+    #[test]
+    fn test_is_valid_order() {
+        assert!(is_valid_order("a"));
+        assert!(is_valid_order(""));
+        assert!(!is_valid_order("\n"));
+        assert!(!is_valid_order("é"));
+        assert!(!is_valid_order(&"a".repeat(51)));
+    }
+
+    #[test]
+    fn test_sort_children() {
+        let mut children = vec![
+            child("!d:localhost", None, 2),
+            child("!c:localhost", None, 1),
+            child("!b:localhost", Some("b"), 0),
+            child("!a:localhost", Some("a"), 3),
+            child("!e:localhost", None, 1),
+        ];
+        sort_children(&mut children);
+
+        let room_ids: Vec<_> = children.into_iter().map(|(child, _)| child.room_id).collect();
+        assert_eq!(
+            room_ids,
+            [
+                owned_room_id!("!a:localhost"),
+                owned_room_id!("!b:localhost"),
+                owned_room_id!("!c:localhost"),
+                owned_room_id!("!e:localhost"),
+                owned_room_id!("!d:localhost"),
+            ]
+        );
+    }
+}
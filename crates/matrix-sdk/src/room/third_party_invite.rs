@@ -0,0 +1,89 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use matrix_sdk_base::deserialized_responses::RawAnySyncOrStrippedState;
+use ruma::OwnedUserId;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+/// An invite to a room sent to a third-party identifier, like an email
+/// address, that was not accepted yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PendingThirdPartyInvite {
+    /// The token of the invite, which is the state key of its
+    /// `m.room.third_party_invite` event.
+    pub token: String,
+    /// A user-friendly representation of the invited third-party
+    /// identifier, which is usually obfuscated.
+    pub display_name: String,
+    /// The user who sent the invite.
+    pub sender: OwnedUserId,
+}
+
+impl PendingThirdPartyInvite {
+    /// Get the pending invites from the `m.room.third_party_invite` and
+    /// `m.room.member` state events of a room.
+    ///
+    /// An invite is pending until a member event references its token, or
+    /// until it is revoked by replacing its content with an empty one.
+    pub(super) fn from_state(
+        invites: &[RawAnySyncOrStrippedState],
+        members: &[RawAnySyncOrStrippedState],
+    ) -> Vec<Self> {
+        let accepted_tokens: BTreeSet<String> = members
+            .iter()
+            .filter_map(|raw| deserialize_as::<JsonValue>(raw))
+            .filter_map(|event| {
+                event.pointer("/content/third_party_invite/signed/token")?.as_str().map(Into::into)
+            })
+            .collect();
+
+        invites
+            .iter()
+            .filter_map(|raw| deserialize_as::<ThirdPartyInviteEventParts>(raw))
+            .filter(|event| !accepted_tokens.contains(&event.state_key))
+            .filter_map(|event| {
+                Some(Self {
+                    display_name: event.content.display_name?,
+                    token: event.state_key,
+                    sender: event.sender,
+                })
+            })
+            .collect()
+    }
+}
+
+fn deserialize_as<T: for<'de> Deserialize<'de>>(raw: &RawAnySyncOrStrippedState) -> Option<T> {
+    match raw {
+        RawAnySyncOrStrippedState::Sync(raw) => raw.deserialize_as().ok(),
+        RawAnySyncOrStrippedState::Stripped(raw) => raw.deserialize_as().ok(),
+    }
+}
+
+/// The parts of an `m.room.third_party_invite` event that are needed for a
+/// [`PendingThirdPartyInvite`].
+#[derive(Deserialize)]
+struct ThirdPartyInviteEventParts {
+    state_key: String,
+    sender: OwnedUserId,
+    content: ThirdPartyInviteContentParts,
+}
+
+/// A revoked invite has an empty content.
+#[derive(Deserialize)]
+struct ThirdPartyInviteContentParts {
+    display_name: Option<String>,
+}
@@ -0,0 +1,167 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use matrix_sdk_common::{
+    executor::{spawn, JoinHandle},
+    instant::Instant,
+    time::Clock,
+};
+use tracing::warn;
+
+use super::{Room, TYPING_NOTICE_RESEND_TIMEOUT};
+use crate::Result;
+
+/// The time after the last keystroke after which the user is considered to
+/// have stopped typing.
+const TYPING_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A helper to send the typing notices of a composer, created with
+/// [`Room::typing_notice_guard()`].
+///
+/// Call [`TypingNoticeGuard::keystroke()`] every time the content of the
+/// composer changes. The typing notice is sent the first time, and refreshed
+/// in the background as long as keystrokes keep coming. It is stopped once the
+/// user hasn't typed anything for 5 seconds, when
+/// [`TypingNoticeGuard::stop()`] is called, for example because the composer
+/// was cleared or its message was sent, or when the guard is dropped.
+#[derive(Debug)]
+pub struct TypingNoticeGuard {
+    room: Room,
+    state: Arc<Mutex<TypingState>>,
+}
+
+#[derive(Debug, Default)]
+struct TypingState {
+    /// The time of the last keystroke, if the user is typing.
+    last_keystroke: Option<Instant>,
+    /// The task refreshing the typing notice while the user is typing.
+    refresh_task: Option<JoinHandle<()>>,
+}
+
+impl TypingState {
+    /// Mark the user as not typing anymore.
+    ///
+    /// Returns whether the user was typing.
+    fn stop(&mut self) -> bool {
+        if let Some(_task) = self.refresh_task.take() {
+            #[cfg(not(target_arch = "wasm32"))]
+            _task.abort();
+        }
+
+        self.last_keystroke.take().is_some()
+    }
+}
+
+impl TypingNoticeGuard {
+    pub(super) fn new(room: Room) -> Self {
+        Self { room, state: Default::default() }
+    }
+
+    /// Whether the user is currently considered to be typing.
+    pub fn is_typing(&self) -> bool {
+        self.state.lock().unwrap().last_keystroke.is_some()
+    }
+
+    /// Notify that the user typed something in the composer.
+    ///
+    /// This only sends a request if the user wasn't typing before, so it can
+    /// be called on every keystroke.
+    pub async fn keystroke(&self) -> Result<()> {
+        let started_typing = {
+            let mut state = self.state.lock().unwrap();
+            let now = self.room.client.inner.clock.now();
+            let was_typing = state.last_keystroke.replace(now).is_some();
+
+            if !was_typing {
+                state.refresh_task =
+                    Some(spawn(refresh_typing_notice(self.room.clone(), self.state.clone())));
+            }
+
+            !was_typing
+        };
+
+        if started_typing {
+            self.room.typing_notice(true).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Notify that the user stopped typing, for example because the composer
+    /// was cleared or its message was sent.
+    ///
+    /// This sends a request only if the user was typing.
+    pub async fn stop(&self) -> Result<()> {
+        let was_typing = self.state.lock().unwrap().stop();
+
+        if was_typing {
+            self.room.typing_notice(false).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TypingNoticeGuard {
+    fn drop(&mut self) {
+        let was_typing = self.state.lock().unwrap().stop();
+
+        if was_typing {
+            let room = self.room.clone();
+            spawn(async move {
+                if let Err(error) = room.typing_notice(false).await {
+                    warn!("Failed to stop the typing notice: {error}");
+                }
+            });
+        }
+    }
+}
+
+/// Refresh the typing notice until the user stops typing.
+async fn refresh_typing_notice(room: Room, state: Arc<Mutex<TypingState>>) {
+    let clock = room.client.inner.clock.clone();
+
+    loop {
+        clock.sleep(TYPING_NOTICE_RESEND_TIMEOUT).await;
+
+        // Keep the handle of this task until it is done when the user stopped
+        // typing, because dropping it cancels the task on WASM.
+        let (typing, _own_task) = {
+            let mut state = state.lock().unwrap();
+            match state.last_keystroke {
+                Some(last_keystroke) if clock.now() - last_keystroke < TYPING_IDLE_TIMEOUT => {
+                    (true, None)
+                }
+                Some(_) => {
+                    state.last_keystroke = None;
+                    (false, state.refresh_task.take())
+                }
+                None => return,
+            }
+        };
+
+        if let Err(error) = room.typing_notice(typing).await {
+            warn!("Failed to refresh the typing notice: {error}");
+        }
+
+        if !typing {
+            return;
+        }
+    }
+}
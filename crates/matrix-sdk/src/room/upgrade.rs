@@ -0,0 +1,86 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The state to copy to the replacement room in [`Room::upgrade`], and what
+/// to do once it is created.
+///
+/// By default, all the state is copied and the members are not invited.
+///
+/// [`Room::upgrade`]: super::Room::upgrade
+#[derive(Clone, Debug)]
+pub struct RoomUpgradeOptions {
+    /// Whether to copy the name of the room.
+    pub copy_name: bool,
+    /// Whether to copy the topic of the room.
+    pub copy_topic: bool,
+    /// Whether to copy the avatar of the room.
+    pub copy_avatar: bool,
+    /// Whether to copy the power levels of the room.
+    pub copy_power_levels: bool,
+    /// Whether to ban the users that are banned from the room.
+    pub copy_bans: bool,
+    /// Whether to copy the `m.space.parent` events of the room.
+    pub copy_space_parents: bool,
+    /// Whether to invite the joined members of the room to the replacement
+    /// room.
+    pub invite_members: bool,
+    /// The message shown to the members of the room to point them to the
+    /// replacement room.
+    pub tombstone_body: String,
+}
+
+impl Default for RoomUpgradeOptions {
+    fn default() -> Self {
+        Self {
+            copy_name: true,
+            copy_topic: true,
+            copy_avatar: true,
+            copy_power_levels: true,
+            copy_bans: true,
+            copy_space_parents: true,
+            invite_members: false,
+            tombstone_body: "This room has been replaced".to_owned(),
+        }
+    }
+}
+
+/// The progress of a [`Room::upgrade`].
+///
+/// [`Room::upgrade`]: super::Room::upgrade
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RoomUpgradeProgress {
+    /// The upgrade has not started.
+    #[default]
+    NotStarted,
+    /// The replacement room is being created with the copied state.
+    CreatingRoom,
+    /// The banned users are being banned from the replacement room.
+    CopyingBans {
+        /// The number of users banned so far.
+        done: usize,
+        /// The number of users to ban.
+        total: usize,
+    },
+    /// The tombstone is being sent to the room.
+    SendingTombstone,
+    /// The members of the room are being invited to the replacement room.
+    InvitingMembers {
+        /// The number of users invited so far.
+        done: usize,
+        /// The number of users to invite.
+        total: usize,
+    },
+    /// The upgrade is done.
+    Done,
+}
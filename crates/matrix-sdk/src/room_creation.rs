@@ -0,0 +1,404 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Creation of rooms with the most common settings in one call.
+
+use matrix_sdk_base::RoomState;
+use ruma::{
+    api::client::{
+        membership::{invite_user, invite_user::v3::InvitationRecipient},
+        room::{
+            create_room::{self, v3::RoomPreset},
+            Visibility,
+        },
+    },
+    assign,
+    events::{
+        room::{
+            avatar::RoomAvatarEventContent, encryption::RoomEncryptionEventContent,
+            power_levels::RoomPowerLevelsEventContent,
+        },
+        space::parent::SpaceParentEventContent,
+        AnyInitialStateEvent, InitialStateEvent, StateEventType,
+    },
+    serde::Raw,
+    OwnedMxcUri, OwnedRoomId, OwnedUserId, RoomVersionId,
+};
+use serde_json::json;
+use tracing::error;
+
+use crate::{error::RoomCreationError, Client, Error, Result, Room};
+
+/// A template for the power levels of a new room.
+///
+/// It only changes the levels needed for the actions, the creator of the
+/// room keeps the highest power level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerLevelsPreset {
+    /// Only moderators can send messages and invite users, the other members
+    /// can only read.
+    Announcement,
+
+    /// All members can send messages and invite users, and moderators can
+    /// change the state of the room and remove messages and users.
+    Community,
+}
+
+impl PowerLevelsPreset {
+    fn content_override(self) -> serde_json::Result<Raw<RoomPowerLevelsEventContent>> {
+        let content = match self {
+            Self::Announcement => json!({
+                "events_default": 50,
+                "invite": 50,
+            }),
+            Self::Community => json!({
+                "events_default": 0,
+                "invite": 0,
+                "state_default": 50,
+                "kick": 50,
+                "ban": 50,
+                "redact": 50,
+            }),
+        };
+
+        Ok(Raw::new(&content)?.cast())
+    }
+}
+
+/// Builder to create a room, obtained with [`Client::build_room()`].
+///
+/// All the settings are validated before the room is created. After the room
+/// is created, the users that are invited with a reason are invited and the
+/// room is added to its parent space, if any.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use matrix_sdk::{room_creation::PowerLevelsPreset, Client};
+/// # use ruma::user_id;
+/// # async {
+/// # let client: Client = todo!();
+/// let room = client
+///     .build_room()
+///     .name("Announcements")
+///     .encrypted(true)
+///     .power_levels_preset(PowerLevelsPreset::Announcement)
+///     .invite_with_reason(
+///         user_id!("@alice:example.org").to_owned(),
+///         "Welcome!".to_owned(),
+///     )
+///     .create()
+///     .await?;
+/// # anyhow::Ok(()) };
+/// ```
+#[derive(Debug)]
+pub struct RoomCreationBuilder {
+    client: Client,
+    name: Option<String>,
+    topic: Option<String>,
+    avatar_url: Option<OwnedMxcUri>,
+    alias_localpart: Option<String>,
+    visibility: Visibility,
+    preset: Option<RoomPreset>,
+    room_version: Option<RoomVersionId>,
+    encrypted: bool,
+    is_direct: bool,
+    invites: Vec<(OwnedUserId, Option<String>)>,
+    initial_state: Vec<Raw<AnyInitialStateEvent>>,
+    power_levels_preset: Option<PowerLevelsPreset>,
+    power_level_content_override: Option<Raw<RoomPowerLevelsEventContent>>,
+    space_parent: Option<OwnedRoomId>,
+}
+
+impl RoomCreationBuilder {
+    /// Create a new `RoomCreationBuilder` with the default settings of the
+    /// homeserver.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            name: None,
+            topic: None,
+            avatar_url: None,
+            alias_localpart: None,
+            visibility: Visibility::Private,
+            preset: None,
+            room_version: None,
+            encrypted: false,
+            is_direct: false,
+            invites: Vec::new(),
+            initial_state: Vec::new(),
+            power_levels_preset: None,
+            power_level_content_override: None,
+            space_parent: None,
+        }
+    }
+
+    /// Set the name of the room.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the topic of the room.
+    pub fn topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = Some(topic.into());
+        self
+    }
+
+    /// Set the avatar of the room.
+    pub fn avatar_url(mut self, avatar_url: OwnedMxcUri) -> Self {
+        self.avatar_url = Some(avatar_url);
+        self
+    }
+
+    /// Create an alias for the room with the given localpart, on the server
+    /// of the current user.
+    ///
+    /// For example, `rust` creates the alias `#rust:example.org`.
+    pub fn alias_localpart(mut self, localpart: impl Into<String>) -> Self {
+        self.alias_localpart = Some(localpart.into());
+        self
+    }
+
+    /// Set whether the room is published in the room directory of the
+    /// server.
+    ///
+    /// Defaults to [`Visibility::Private`].
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Set the preset used by the homeserver for the join rules and history
+    /// visibility of the room.
+    pub fn preset(mut self, preset: RoomPreset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    /// Set the version of the room.
+    pub fn room_version(mut self, room_version: RoomVersionId) -> Self {
+        self.room_version = Some(room_version);
+        self
+    }
+
+    /// Set whether the room is encrypted, with the recommended settings.
+    pub fn encrypted(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Set whether the room is a direct message.
+    ///
+    /// A direct room needs at least one invited user, and is added to the
+    /// direct rooms in the account data.
+    pub fn direct(mut self, is_direct: bool) -> Self {
+        self.is_direct = is_direct;
+        self
+    }
+
+    /// Invite the given user to the room.
+    pub fn invite(mut self, user_id: OwnedUserId) -> Self {
+        self.invites.push((user_id, None));
+        self
+    }
+
+    /// Invite the given user to the room, with a reason.
+    ///
+    /// The invite is sent once the room has been created.
+    pub fn invite_with_reason(mut self, user_id: OwnedUserId, reason: String) -> Self {
+        self.invites.push((user_id, Some(reason)));
+        self
+    }
+
+    /// Add an event to the initial state of the room.
+    ///
+    /// The events added by the other settings of this builder override the
+    /// ones with the same type and state key.
+    pub fn initial_state(mut self, event: Raw<AnyInitialStateEvent>) -> Self {
+        self.initial_state.push(event);
+        self
+    }
+
+    /// Use the given template for the power levels of the room.
+    ///
+    /// Can't be combined with
+    /// [`RoomCreationBuilder::power_level_content_override()`].
+    pub fn power_levels_preset(mut self, preset: PowerLevelsPreset) -> Self {
+        self.power_levels_preset = Some(preset);
+        self
+    }
+
+    /// Override fields of the default power levels of the room.
+    ///
+    /// Can't be combined with [`RoomCreationBuilder::power_levels_preset()`].
+    pub fn power_level_content_override(
+        mut self,
+        content: Raw<RoomPowerLevelsEventContent>,
+    ) -> Self {
+        self.power_level_content_override = Some(content);
+        self
+    }
+
+    /// Add the room to the given space.
+    ///
+    /// The current user must be joined to the space and be allowed to add
+    /// rooms to it.
+    pub fn space_parent(mut self, space_id: OwnedRoomId) -> Self {
+        self.space_parent = Some(space_id);
+        self
+    }
+
+    /// Validate the settings and create the room.
+    ///
+    /// Returns an [`Error::RoomCreation`] if the settings are invalid, in
+    /// which case no request is sent.
+    pub async fn create(self) -> Result<Room> {
+        let own_user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        let space = self.validate(&own_user_id).await?;
+
+        let Self {
+            client,
+            name,
+            topic,
+            avatar_url,
+            alias_localpart,
+            visibility,
+            preset,
+            room_version,
+            encrypted,
+            is_direct,
+            invites,
+            mut initial_state,
+            power_levels_preset,
+            power_level_content_override,
+            space_parent,
+        } = self;
+
+        if encrypted {
+            initial_state.push(
+                InitialStateEvent::new(RoomEncryptionEventContent::with_recommended_defaults())
+                    .to_raw_any(),
+            );
+        }
+
+        if let Some(url) = avatar_url {
+            let content = assign!(RoomAvatarEventContent::new(), { url: Some(url) });
+            initial_state.push(InitialStateEvent::new(content).to_raw_any());
+        }
+
+        let via = vec![own_user_id.server_name().to_owned()];
+        if let Some(space_id) = &space_parent {
+            let content = assign!(SpaceParentEventContent::new(via.clone()), { canonical: true });
+            initial_state
+                .push(InitialStateEvent { content, state_key: space_id.clone() }.to_raw_any());
+        }
+
+        let power_level_content_override = match power_levels_preset {
+            Some(preset) => Some(preset.content_override()?),
+            None => power_level_content_override,
+        };
+
+        let invite = invites
+            .iter()
+            .filter(|(_, reason)| reason.is_none())
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+
+        let request = assign!(create_room::v3::Request::new(), {
+            name,
+            topic,
+            room_alias_name: alias_localpart,
+            visibility,
+            preset,
+            room_version,
+            is_direct,
+            invite,
+            initial_state,
+            power_level_content_override,
+        });
+
+        let response = client.send(request, None).await?;
+        let base_room =
+            client.base_client().get_or_create_room(&response.room_id, RoomState::Joined);
+        let room = Room::new(client.clone(), base_room);
+
+        for (user_id, reason) in &invites {
+            let Some(reason) = reason else {
+                continue;
+            };
+
+            let recipient = InvitationRecipient::UserId { user_id: user_id.clone() };
+            let request = assign!(invite_user::v3::Request::new(room.room_id().to_owned(), recipient), {
+                reason: Some(reason.clone()),
+            });
+            client.send(request, None).await?;
+        }
+
+        if let Some(space) = space {
+            space.add_space_child(room.room_id(), via, false).await?;
+        }
+
+        if is_direct {
+            let invitees: Vec<_> = invites.into_iter().map(|(user_id, _)| user_id).collect();
+            if let Err(error) = client.account().mark_as_dm(room.room_id(), &invitees).await {
+                // FIXME: Retry in the background
+                error!("Failed to mark room as DM: {error}");
+            }
+        }
+
+        Ok(room)
+    }
+
+    /// Check that the settings are consistent.
+    ///
+    /// Returns the parent space, if any.
+    async fn validate(&self, own_user_id: &OwnedUserId) -> Result<Option<Room>> {
+        if let Some(localpart) = &self.alias_localpart {
+            if localpart.is_empty()
+                || localpart.contains(|c: char| c == ':' || c == '#' || c.is_whitespace())
+            {
+                return Err(RoomCreationError::InvalidAliasLocalpart(localpart.clone()).into());
+            }
+        }
+
+        if self.is_direct && self.invites.is_empty() {
+            return Err(RoomCreationError::DirectRoomWithoutInvitee.into());
+        }
+
+        if self.invites.iter().any(|(user_id, _)| user_id == own_user_id) {
+            return Err(RoomCreationError::InviteSelf.into());
+        }
+
+        if self.power_levels_preset.is_some() && self.power_level_content_override.is_some() {
+            return Err(RoomCreationError::ConflictingPowerLevels.into());
+        }
+
+        let Some(space_id) = &self.space_parent else {
+            return Ok(None);
+        };
+
+        let space = self
+            .client
+            .get_room(space_id)
+            .filter(|room| room.state() == RoomState::Joined && room.is_space())
+            .ok_or_else(|| RoomCreationError::UnknownSpace(space_id.clone()))?;
+
+        if !space.can_user_send_state(own_user_id, StateEventType::SpaceChild).await? {
+            return Err(RoomCreationError::CannotAddToSpace(space_id.clone()).into());
+        }
+
+        Ok(Some(space))
+    }
+}
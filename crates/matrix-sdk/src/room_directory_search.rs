@@ -0,0 +1,269 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Paginated search in the public room directory of a homeserver.
+
+use eyeball_im::{ObservableVector, VectorDiff};
+use futures_core::Stream;
+use imbl::Vector;
+use ruma::{
+    api::client::directory::get_public_rooms_filtered::v3::Request as PublicRoomsFilterRequest,
+    assign,
+    directory::{Filter, PublicRoomJoinRule, PublicRoomsChunk, RoomNetwork},
+    OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedServerName,
+};
+
+use crate::{Client, Result};
+
+/// A room published in the room directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomDescription {
+    /// The ID of the room.
+    pub room_id: OwnedRoomId,
+    /// The name of the room, if any.
+    pub name: Option<String>,
+    /// The topic of the room, if any.
+    pub topic: Option<String>,
+    /// The canonical alias of the room, if any.
+    pub alias: Option<OwnedRoomAliasId>,
+    /// The avatar of the room, if any.
+    pub avatar_url: Option<OwnedMxcUri>,
+    /// The rule to join the room.
+    pub join_rule: PublicRoomJoinRule,
+    /// Whether the history of the room can be read without joining it.
+    pub is_world_readable: bool,
+    /// The number of members who joined the room.
+    pub joined_members: u64,
+}
+
+impl From<PublicRoomsChunk> for RoomDescription {
+    fn from(value: PublicRoomsChunk) -> Self {
+        Self {
+            room_id: value.room_id,
+            name: value.name,
+            topic: value.topic,
+            alias: value.canonical_alias,
+            avatar_url: value.avatar_url,
+            join_rule: value.join_rule,
+            is_world_readable: value.world_readable,
+            joined_members: value.num_joined_members.into(),
+        }
+    }
+}
+
+/// A search in the room directory, whose results are loaded page by page.
+///
+/// The results of all the loaded pages are kept in an observable list, so they
+/// can be displayed as they are loaded with [`RoomDirectorySearch::results`].
+///
+/// # Example
+///
+/// ```no_run
+/// # use matrix_sdk::{room_directory_search::RoomDirectorySearch, Client};
+/// # use ruma::directory::RoomNetwork;
+/// # async {
+/// # let client: Client = todo!();
+/// let mut search = RoomDirectorySearch::new(client);
+/// search
+///     .search(Some("rust".to_owned()), 20, None, RoomNetwork::Matrix)
+///     .await?;
+///
+/// while !search.is_at_last_page() {
+///     search.next_page().await?;
+/// }
+/// # anyhow::Ok(()) };
+/// ```
+#[derive(Debug)]
+pub struct RoomDirectorySearch {
+    client: Client,
+    filter: Option<String>,
+    batch_size: u32,
+    server: Option<OwnedServerName>,
+    network: RoomNetwork,
+    next_token: Option<String>,
+    is_at_last_page: bool,
+    results: ObservableVector<RoomDescription>,
+}
+
+impl RoomDirectorySearch {
+    /// Create a new search, which doesn't have any result until
+    /// [`RoomDirectorySearch::search`] is called.
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            filter: None,
+            batch_size: 0,
+            server: None,
+            network: RoomNetwork::Matrix,
+            next_token: None,
+            is_at_last_page: false,
+            results: ObservableVector::new(),
+        }
+    }
+
+    /// Start a new search, replacing the results of the previous one, and load
+    /// its first page.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The term to search for in the names, topics and aliases of
+    ///   the rooms, or `None` to list all the rooms.
+    ///
+    /// * `batch_size` - The number of rooms to load with each page.
+    ///
+    /// * `server` - The server whose directory should be searched, or `None`
+    ///   for the homeserver of the client.
+    ///
+    /// * `network` - The network to search in, which can be a third-party
+    ///   network bridged by the server.
+    pub async fn search(
+        &mut self,
+        filter: Option<String>,
+        batch_size: u32,
+        server: Option<OwnedServerName>,
+        network: RoomNetwork,
+    ) -> Result<()> {
+        self.filter = filter;
+        self.batch_size = batch_size;
+        self.server = server;
+        self.network = network;
+        self.next_token = None;
+        self.is_at_last_page = false;
+        self.results.clear();
+
+        self.next_page().await
+    }
+
+    /// Load the next page of results, and append it to the results.
+    ///
+    /// Does nothing if the last page has already been loaded.
+    pub async fn next_page(&mut self) -> Result<()> {
+        if self.is_at_last_page {
+            return Ok(());
+        }
+
+        let request = assign!(PublicRoomsFilterRequest::new(), {
+            filter: assign!(Filter::new(), { generic_search_term: self.filter.clone() }),
+            limit: Some(self.batch_size.into()),
+            since: self.next_token.clone(),
+            server: self.server.clone(),
+            room_network: self.network.clone(),
+        });
+        let response = self.client.public_rooms_filtered(request).await?;
+
+        self.next_token = response.next_batch;
+        self.is_at_last_page = self.next_token.is_none();
+        self.results.append(response.chunk.into_iter().map(Into::into).collect());
+
+        Ok(())
+    }
+
+    /// Get the results loaded so far, and a stream of their updates.
+    pub fn results(
+        &self,
+    ) -> (Vector<RoomDescription>, impl Stream<Item = Vec<VectorDiff<RoomDescription>>>) {
+        ((*self.results).clone(), self.results.subscribe().into_batched_stream())
+    }
+
+    /// Whether all the results have been loaded.
+    pub fn is_at_last_page(&self) -> bool {
+        self.is_at_last_page
+    }
+
+    /// The server whose directory is searched, if it isn't the homeserver of
+    /// the client.
+    pub fn server(&self) -> Option<&OwnedServerName> {
+        self.server.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use eyeball_im::VectorDiff;
+    use futures_util::{pin_mut, StreamExt};
+    use matrix_sdk_test::async_test;
+    use ruma::{directory::RoomNetwork, room_id};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_partial_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::RoomDirectorySearch;
+    use crate::test_utils::logged_in_client;
+
+    fn public_room(room_id: &str, name: &str) -> serde_json::Value {
+        json!({
+            "room_id": room_id,
+            "name": name,
+            "num_joined_members": 3,
+            "world_readable": true,
+            "guest_can_join": false,
+        })
+    }
+
+    #[async_test]
+    async fn test_search_pages() {
+        let server = MockServer::start().await;
+        let client = logged_in_client(Some(server.uri())).await;
+
+        Mock::given(method("POST"))
+            .and(path("/_matrix/client/r0/publicRooms"))
+            .and(body_partial_json(json!({ "since": "page2" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "chunk": [public_room("!b:localhost", "Rust Two")],
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/_matrix/client/r0/publicRooms"))
+            .and(body_partial_json(json!({
+                "limit": 1,
+                "filter": { "generic_search_term": "rust" },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "chunk": [public_room("!a:localhost", "Rust One")],
+                "next_batch": "page2",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut search = RoomDirectorySearch::new(client);
+        search.search(Some("rust".to_owned()), 1, None, RoomNetwork::Matrix).await.unwrap();
+        assert!(!search.is_at_last_page());
+
+        let (results, stream) = search.results();
+        pin_mut!(stream);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].room_id, room_id!("!a:localhost"));
+        assert_eq!(results[0].name.as_deref(), Some("Rust One"));
+        assert_eq!(results[0].joined_members, 3);
+
+        search.next_page().await.unwrap();
+        assert!(search.is_at_last_page());
+
+        let diffs = stream.next().await.unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_matches!(&diffs[0], VectorDiff::Append { values } => {
+            assert_eq!(values[0].room_id, room_id!("!b:localhost"));
+        });
+
+        // The last page is already loaded, no request is sent.
+        search.next_page().await.unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Preview of a room that the user didn't join, to display it in invites or
+//! links to the room.
+
+use matrix_sdk_base::deserialized_responses::TimelineEvent;
+use ruma::{
+    api::client::{
+        error::ErrorKind,
+        space::{get_hierarchy, SpaceHierarchyRoomsChunk},
+        state::get_state_events,
+    },
+    assign,
+    events::AnyStateEvent,
+    room::RoomType,
+    serde::Raw,
+    space::SpaceRoomJoinRule,
+    OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedServerName, RoomId, RoomOrAliasId,
+};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::{debug, warn};
+
+use crate::{
+    room::{Messages, MessagesOptions},
+    Client, Result, RoomState,
+};
+
+/// The information about a room that can be displayed before joining it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RoomPreview {
+    /// The ID of the room.
+    pub room_id: OwnedRoomId,
+    /// The canonical alias of the room, if any.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+    /// The name of the room, if any.
+    pub name: Option<String>,
+    /// The topic of the room, if any.
+    pub topic: Option<String>,
+    /// The avatar of the room, if any.
+    pub avatar_url: Option<OwnedMxcUri>,
+    /// The number of members who joined the room.
+    pub num_joined_members: u64,
+    /// The type of the room, e.g. a space.
+    pub room_type: Option<RoomType>,
+    /// The rule to join the room.
+    pub join_rule: SpaceRoomJoinRule,
+    /// Whether the history of the room can be read without joining it.
+    pub is_world_readable: bool,
+    /// Whether the room is encrypted, if known.
+    ///
+    /// The room hierarchy API doesn't give this information, so it is unknown
+    /// when the homeserver doesn't support the room summary API.
+    pub is_encrypted: Option<bool>,
+    /// The state of the current user in the room, if it is known locally.
+    pub state: Option<RoomState>,
+}
+
+impl RoomPreview {
+    fn from_summary(response: get_summary::Response) -> Self {
+        Self {
+            room_id: response.room_id,
+            canonical_alias: response.canonical_alias,
+            name: response.name,
+            topic: response.topic,
+            avatar_url: response.avatar_url,
+            num_joined_members: response.num_joined_members,
+            room_type: response.room_type,
+            join_rule: response.join_rule,
+            is_world_readable: response.world_readable,
+            is_encrypted: Some(response.encryption.is_some()),
+            state: None,
+        }
+    }
+
+    fn from_hierarchy(chunk: SpaceHierarchyRoomsChunk) -> Self {
+        Self {
+            room_id: chunk.room_id,
+            canonical_alias: chunk.canonical_alias,
+            name: chunk.name,
+            topic: chunk.topic,
+            avatar_url: chunk.avatar_url,
+            num_joined_members: chunk.num_joined_members.into(),
+            room_type: chunk.room_type,
+            join_rule: chunk.join_rule,
+            is_world_readable: chunk.world_readable,
+            is_encrypted: None,
+            state: None,
+        }
+    }
+
+    /// Build a preview from the state events of a room, as returned when
+    /// peeking into it.
+    fn from_state(room_id: OwnedRoomId, state: &[Raw<AnyStateEvent>]) -> Self {
+        let mut preview = Self {
+            room_id,
+            canonical_alias: None,
+            name: None,
+            topic: None,
+            avatar_url: None,
+            num_joined_members: 0,
+            room_type: None,
+            join_rule: SpaceRoomJoinRule::Invite,
+            is_world_readable: false,
+            is_encrypted: Some(false),
+            state: None,
+        };
+
+        for event in state.iter().filter_map(|raw| raw.deserialize_as::<StateEventParts>().ok()) {
+            let content = &event.content;
+            let string = |key: &str| content.get(key).and_then(JsonValue::as_str);
+
+            match event.event_type.as_str() {
+                "m.room.create" => preview.room_type = string("type").map(Into::into),
+                "m.room.name" => preview.name = string("name").map(ToOwned::to_owned),
+                "m.room.topic" => preview.topic = string("topic").map(ToOwned::to_owned),
+                "m.room.avatar" => preview.avatar_url = string("url").map(Into::into),
+                "m.room.canonical_alias" => {
+                    preview.canonical_alias = string("alias").and_then(|a| a.try_into().ok());
+                }
+                "m.room.join_rules" => {
+                    if let Some(join_rule) = string("join_rule") {
+                        preview.join_rule = join_rule.into();
+                    }
+                }
+                "m.room.history_visibility" => {
+                    preview.is_world_readable =
+                        string("history_visibility") == Some("world_readable");
+                }
+                "m.room.encryption" => preview.is_encrypted = Some(true),
+                "m.room.member" if string("membership") == Some("join") => {
+                    preview.num_joined_members += 1;
+                }
+                _ => {}
+            }
+        }
+
+        preview
+    }
+}
+
+/// The parts of a state event that are needed to build a [`RoomPreview`].
+#[derive(Deserialize)]
+struct StateEventParts {
+    #[serde(rename = "type")]
+    event_type: String,
+    content: JsonValue,
+}
+
+impl Client {
+    /// Get a preview of a room, which doesn't need to be joined.
+    ///
+    /// The preview is loaded with the room summary API defined in [MSC3266].
+    /// If the homeserver doesn't support it, the room hierarchy API is used,
+    /// and, if the homeserver doesn't know the room either, its state is
+    /// loaded by peeking into it, which only works if its history is world
+    /// readable.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The ID or an alias of the room.
+    ///
+    /// * `via` - The servers to get the information about the room through, if
+    ///   the homeserver of the user isn't in the room.
+    ///
+    /// [MSC3266]: https://github.com/matrix-org/matrix-spec-proposals/pull/3266
+    pub async fn get_room_preview(
+        &self,
+        room_id_or_alias: &RoomOrAliasId,
+        via: Vec<OwnedServerName>,
+    ) -> Result<RoomPreview> {
+        let request = get_summary::Request { room_id_or_alias: room_id_or_alias.to_owned(), via };
+
+        let mut preview = match self.send(request, None).await {
+            Ok(response) => RoomPreview::from_summary(response),
+            Err(error) if error.client_api_error_kind() == Some(&ErrorKind::Unrecognized) => {
+                warn!(
+                    "The homeserver doesn't support the room summary API, \
+                     falling back to the room hierarchy"
+                );
+
+                let room_id = match <&RoomId>::try_from(room_id_or_alias) {
+                    Ok(room_id) => room_id.to_owned(),
+                    Err(alias) => self.resolve_room_alias(alias).await?.room_id,
+                };
+
+                self.get_room_preview_fallback(room_id).await?
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        preview.state = self.get_room(&preview.room_id).map(|room| room.state());
+
+        Ok(preview)
+    }
+
+    /// Get a preview of a room with the room hierarchy API, or by peeking into
+    /// it.
+    async fn get_room_preview_fallback(&self, room_id: OwnedRoomId) -> Result<RoomPreview> {
+        let request = assign!(get_hierarchy::v1::Request::new(room_id.clone()), {
+            max_depth: Some(0u32.into()),
+        });
+
+        match self.send(request, None).await {
+            Ok(response) => {
+                if let Some(chunk) =
+                    response.rooms.into_iter().find(|chunk| chunk.room_id == room_id)
+                {
+                    return Ok(RoomPreview::from_hierarchy(chunk));
+                }
+            }
+            Err(error) => {
+                debug!("Could not get the room from the room hierarchy: {error}");
+            }
+        }
+
+        let request = get_state_events::v3::Request::new(room_id.clone());
+        let response = self.send(request, None).await?;
+
+        Ok(RoomPreview::from_state(room_id, &response.room_state))
+    }
+
+    /// Get the current state of a room without joining it.
+    ///
+    /// This only works if the history of the room is world readable, which
+    /// can be checked with [`RoomPreview::is_world_readable`]. It is also
+    /// available to guest accounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room to peek into.
+    pub async fn peek_room_state(&self, room_id: &RoomId) -> Result<Vec<Raw<AnyStateEvent>>> {
+        let request = get_state_events::v3::Request::new(room_id.to_owned());
+        Ok(self.send(request, None).await?.room_state)
+    }
+
+    /// Get the messages of a room without joining it.
+    ///
+    /// Like [`Client::peek_room_state()`], this only works if the history of
+    /// the room is world readable. The encrypted events are not decrypted,
+    /// since the keys are only shared with the members of the room.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The ID of the room to peek into.
+    ///
+    /// * `options` - The options for the pagination of the messages, like for
+    ///   [`Room::messages()`].
+    ///
+    /// [`Room::messages()`]: crate::Room::messages
+    pub async fn peek_room_messages(
+        &self,
+        room_id: &RoomId,
+        options: MessagesOptions,
+    ) -> Result<Messages> {
+        let request = options.into_request(room_id);
+        let response = self.send(request, None).await?;
+
+        Ok(Messages {
+            start: response.start,
+            end: response.end,
+            chunk: response.chunk.into_iter().map(TimelineEvent::new).collect(),
+            state: response.state,
+        })
+    }
+}
+
+/// Get the summary of a room, as defined in [MSC3266].
+///
+/// [MSC3266]: https://github.com/matrix-org/matrix-spec-proposals/pull/3266
+mod get_summary {
+    use ruma::{
+        api::{request, response, Metadata},
+        metadata,
+        room::RoomType,
+        space::SpaceRoomJoinRule,
+        EventEncryptionAlgorithm, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedRoomOrAliasId,
+        OwnedServerName,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: GET,
+        rate_limited: false,
+        authentication: AccessTokenOptional,
+        history: {
+            unstable => "/_matrix/client/unstable/im.nheko.summary/rooms/:room_id_or_alias/summary",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id_or_alias: OwnedRoomOrAliasId,
+        #[ruma_api(query)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub via: Vec<OwnedServerName>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub room_id: OwnedRoomId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub canonical_alias: Option<OwnedRoomAliasId>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub topic: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub avatar_url: Option<OwnedMxcUri>,
+        pub num_joined_members: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub room_type: Option<RoomType>,
+        pub join_rule: SpaceRoomJoinRule,
+        pub world_readable: bool,
+        #[serde(
+            default,
+            rename = "im.nheko.summary.encryption",
+            alias = "encryption",
+            skip_serializing_if = "Option::is_none"
+        )]
+        pub encryption: Option<EventEncryptionAlgorithm>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{owned_room_id, serde::Raw, space::SpaceRoomJoinRule};
+    use serde_json::json;
+
+    use super::RoomPreview;
+
+    #[test]
+    fn test_preview_from_state() {
+        let state = [
+            json!({ "type": "m.room.name", "state_key": "", "content": { "name": "Room" } }),
+            json!({ "type": "m.room.join_rules", "state_key": "", "content": { "join_rule": "public" } }),
+            json!({
+                "type": "m.room.history_visibility",
+                "state_key": "",
+                "content": { "history_visibility": "world_readable" },
+            }),
+            json!({ "type": "m.room.member", "state_key": "@a:localhost", "content": { "membership": "join" } }),
+            json!({ "type": "m.room.member", "state_key": "@b:localhost", "content": { "membership": "leave" } }),
+        ]
+        .map(|event| Raw::new(&event).unwrap().cast());
+
+        let preview = RoomPreview::from_state(owned_room_id!("!room:localhost"), &state);
+
+        assert_eq!(preview.name.as_deref(), Some("Room"));
+        assert_eq!(preview.join_rule, SpaceRoomJoinRule::Public);
+        assert!(preview.is_world_readable);
+        assert_eq!(preview.is_encrypted, Some(false));
+        assert_eq!(preview.num_joined_members, 1);
+    }
+}
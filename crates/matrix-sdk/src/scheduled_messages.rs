@@ -0,0 +1,216 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Messages persisted in the store to be sent at a later time.
+//!
+//! Scheduled messages are sent by the client itself, so they are only sent
+//! while the client is running. Messages whose time passed while the client
+//! was not running are sent as soon as the client syncs again.
+
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use matrix_sdk_base::{
+    store::{ScheduledMessage, StateStoreDataKey, StateStoreDataValue},
+    RoomState,
+};
+use matrix_sdk_common::{executor::spawn, time::Clock};
+use ruma::{MilliSecondsSinceUnixEpoch, RoomId, TransactionId};
+use tracing::{debug, warn};
+
+use crate::{client::ClientInner, Client, Result};
+
+/// The delay after which the sending of a scheduled message that failed is
+/// retried.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+impl Client {
+    /// Get all the messages scheduled with [`Room::schedule_message()`] that
+    /// were not sent yet.
+    ///
+    /// [`Room::schedule_message()`]: crate::Room::schedule_message
+    pub async fn scheduled_messages(&self) -> Result<Vec<ScheduledMessage>> {
+        Ok(self
+            .store()
+            .get_kv_data(StateStoreDataKey::ScheduledMessages)
+            .await?
+            .and_then(|value| value.into_scheduled_messages())
+            .unwrap_or_default())
+    }
+
+    /// Persist a new scheduled message and make sure it will be sent in time.
+    pub(crate) async fn add_scheduled_message(&self, message: ScheduledMessage) -> Result<()> {
+        {
+            let _guard = self.locks().scheduled_messages_lock.lock().await;
+
+            let mut messages = self.scheduled_messages().await?;
+            messages.push(message);
+            self.save_scheduled_messages(messages).await?;
+        }
+
+        self.arm_scheduled_messages_timer(Duration::ZERO);
+
+        Ok(())
+    }
+
+    /// Remove the scheduled message with the given transaction ID.
+    ///
+    /// Returns whether a message was removed.
+    pub(crate) async fn remove_scheduled_message(
+        &self,
+        room_id: &RoomId,
+        transaction_id: &TransactionId,
+    ) -> Result<bool> {
+        let _guard = self.locks().scheduled_messages_lock.lock().await;
+
+        let mut messages = self.scheduled_messages().await?;
+        let len = messages.len();
+        messages.retain(|message| {
+            &*message.room_id != room_id || &*message.transaction_id != transaction_id
+        });
+
+        if messages.len() == len {
+            return Ok(false);
+        }
+
+        self.save_scheduled_messages(messages).await?;
+        Ok(true)
+    }
+
+    async fn save_scheduled_messages(&self, messages: Vec<ScheduledMessage>) -> Result<()> {
+        self.store()
+            .set_kv_data(
+                StateStoreDataKey::ScheduledMessages,
+                StateStoreDataValue::ScheduledMessages(messages),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Check the store for scheduled messages the first time this is called,
+    /// to send the messages whose time passed while the client was not
+    /// running.
+    pub(crate) fn maybe_load_scheduled_messages(&self) {
+        let is_armed = self.inner.scheduled_messages_task.lock().unwrap().is_some();
+
+        if !is_armed {
+            self.arm_scheduled_messages_timer(Duration::ZERO);
+        }
+    }
+
+    /// Send the scheduled messages whose time has come, in the order they
+    /// were scheduled, and wait for the next one.
+    async fn dispatch_scheduled_messages(&self) -> Result<()> {
+        let _guard = self.locks().scheduled_messages_lock.lock().await;
+
+        let now = self.inner.clock.timestamp();
+        let mut remaining = Vec::new();
+        let mut has_failures = false;
+
+        for message in self.scheduled_messages().await? {
+            if message.send_at > now {
+                remaining.push(message);
+                continue;
+            }
+
+            let Some(room) = self.get_room(&message.room_id) else {
+                warn!(room_id = ?message.room_id, "Dropping scheduled message for unknown room");
+                continue;
+            };
+
+            if room.state() != RoomState::Joined {
+                warn!(
+                    room_id = ?message.room_id,
+                    "Dropping scheduled message for a room that is not joined anymore"
+                );
+                continue;
+            }
+
+            debug!(
+                room_id = ?message.room_id,
+                transaction_id = ?message.transaction_id,
+                "Sending scheduled message"
+            );
+
+            // The transaction ID is kept in the store, so if the message was
+            // sent but we failed to remove it from the store, the homeserver
+            // will deduplicate it the next time we try to send it.
+            if let Err(error) = room
+                .send_raw(&message.event_type, &message.content)
+                .with_transaction_id(&message.transaction_id)
+                .await
+            {
+                warn!(
+                    room_id = ?message.room_id,
+                    transaction_id = ?message.transaction_id,
+                    "Failed to send scheduled message: {error}"
+                );
+                has_failures = true;
+                remaining.push(message);
+            }
+        }
+
+        let next_delay = if has_failures {
+            Some(RETRY_DELAY)
+        } else {
+            remaining.iter().map(|message| delay_until(message.send_at, now)).min()
+        };
+
+        self.save_scheduled_messages(remaining).await?;
+
+        if let Some(delay) = next_delay {
+            self.arm_scheduled_messages_timer(delay);
+        }
+
+        Ok(())
+    }
+
+    /// Replace the task waiting for the next scheduled message with one that
+    /// dispatches the scheduled messages after the given delay.
+    fn arm_scheduled_messages_timer(&self, delay: Duration) {
+        let weak_inner = Arc::downgrade(&self.inner);
+        let clock = self.inner.clock.clone();
+        let task = spawn(async move {
+            if !delay.is_zero() {
+                clock.sleep(delay).await;
+            }
+
+            let Some(client) = upgrade(&weak_inner) else {
+                return;
+            };
+
+            if let Err(error) = client.dispatch_scheduled_messages().await {
+                warn!("Failed to dispatch the scheduled messages: {error}");
+                client.arm_scheduled_messages_timer(RETRY_DELAY);
+            }
+        });
+
+        if let Some(_previous) = self.inner.scheduled_messages_task.lock().unwrap().replace(task) {
+            #[cfg(not(target_arch = "wasm32"))]
+            _previous.abort();
+        }
+    }
+}
+
+fn upgrade(weak_inner: &Weak<ClientInner>) -> Option<Client> {
+    weak_inner.upgrade().map(|inner| Client { inner })
+}
+
+/// The time to wait from `now` until `send_at`.
+fn delay_until(send_at: MilliSecondsSinceUnixEpoch, now: MilliSecondsSinceUnixEpoch) -> Duration {
+    Duration::from_millis(u64::from(send_at.0).saturating_sub(u64::from(now.0)))
+}
@@ -0,0 +1,101 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The transaction IDs of the events recently sent by this client.
+//!
+//! They are persisted in the store, so a send that is retried with the same
+//! transaction ID after a restart returns the event that was already sent,
+//! and a remote echo can be matched with its local echo in a new process.
+
+use matrix_sdk_base::store::{SentTransaction, StateStoreDataKey, StateStoreDataValue};
+use matrix_sdk_common::time::Clock;
+use ruma::{EventId, OwnedEventId, OwnedTransactionId, RoomId, TransactionId};
+
+use crate::{Client, Result};
+
+/// The maximum number of sent transactions to remember.
+///
+/// The oldest ones are forgotten first.
+const MAX_SENT_TRANSACTIONS: usize = 200;
+
+impl Client {
+    async fn sent_transactions(&self) -> Result<Vec<SentTransaction>> {
+        Ok(self
+            .store()
+            .get_kv_data(StateStoreDataKey::SentTransactions)
+            .await?
+            .and_then(|value| value.into_sent_transactions())
+            .unwrap_or_default())
+    }
+
+    /// Get the ID of the event that was sent in the given room with the given
+    /// transaction ID, if it is known.
+    pub(crate) async fn event_id_for_transaction(
+        &self,
+        room_id: &RoomId,
+        transaction_id: &TransactionId,
+    ) -> Result<Option<OwnedEventId>> {
+        Ok(self.sent_transactions().await?.into_iter().find_map(|sent| {
+            (*sent.room_id == *room_id && *sent.transaction_id == *transaction_id)
+                .then_some(sent.event_id)
+        }))
+    }
+
+    /// Get the transaction ID that was used to send the given event in the
+    /// given room, if it is known.
+    pub(crate) async fn transaction_id_for_event(
+        &self,
+        room_id: &RoomId,
+        event_id: &EventId,
+    ) -> Result<Option<OwnedTransactionId>> {
+        Ok(self.sent_transactions().await?.into_iter().find_map(|sent| {
+            (*sent.room_id == *room_id && *sent.event_id == *event_id)
+                .then_some(sent.transaction_id)
+        }))
+    }
+
+    /// Remember that the given event was sent with the given transaction ID.
+    pub(crate) async fn add_sent_transaction(
+        &self,
+        room_id: &RoomId,
+        transaction_id: &TransactionId,
+        event_id: &EventId,
+    ) -> Result<()> {
+        let _guard = self.locks().sent_transactions_lock.lock().await;
+
+        let mut sent_transactions = self.sent_transactions().await?;
+        sent_transactions
+            .retain(|sent| *sent.room_id != *room_id || *sent.transaction_id != *transaction_id);
+        sent_transactions.push(SentTransaction {
+            room_id: room_id.to_owned(),
+            transaction_id: transaction_id.to_owned(),
+            event_id: event_id.to_owned(),
+            sent_at: self.inner.clock.timestamp(),
+        });
+
+        if sent_transactions.len() > MAX_SENT_TRANSACTIONS {
+            let excess = sent_transactions.len() - MAX_SENT_TRANSACTIONS;
+            sent_transactions.drain(..excess);
+        }
+
+        self.store()
+            .set_kv_data(
+                StateStoreDataKey::SentTransactions,
+                StateStoreDataValue::SentTransactions(sent_transactions),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
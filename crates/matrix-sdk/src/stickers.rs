@@ -0,0 +1,208 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sticker and custom emoji packs, as defined in [MSC2545].
+//!
+//! The pack of the user is stored in their global account data, and can be
+//! read and written with [`Account::image_pack()`] and
+//! [`Account::set_image_pack()`].
+//!
+//! [MSC2545]: https://github.com/matrix-org/matrix-spec-proposals/pull/2545
+//! [`Account::image_pack()`]: crate::Account::image_pack
+//! [`Account::set_image_pack()`]: crate::Account::set_image_pack
+
+use std::collections::BTreeMap;
+
+use ruma::{
+    events::{room::ImageInfo, sticker::StickerEventContent},
+    exports::ruma_macros::EventContent,
+    OwnedMxcUri,
+};
+use serde::{Deserialize, Serialize};
+
+/// The content of the `im.ponies.user_emotes` global account data event,
+/// the personal image pack of the user.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, EventContent)]
+#[ruma_event(type = "im.ponies.user_emotes", kind = GlobalAccountData)]
+pub struct ImagePackEventContent {
+    /// The images of the pack, by shortcode.
+    #[serde(default)]
+    pub images: BTreeMap<String, PackImage>,
+
+    /// Information about the pack, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack: Option<PackInfo>,
+}
+
+impl ImagePackEventContent {
+    /// The usages of the pack.
+    ///
+    /// If the pack doesn't specify any, it can be used for both emoticons and
+    /// stickers.
+    fn usage(&self) -> &[PackUsage] {
+        match &self.pack {
+            Some(pack) if !pack.usage.is_empty() => &pack.usage,
+            _ => &[PackUsage::Emoticon, PackUsage::Sticker],
+        }
+    }
+
+    /// Whether the given image of this pack can be used with the given usage.
+    pub fn image_has_usage(&self, image: &PackImage, usage: &PackUsage) -> bool {
+        if image.usage.is_empty() {
+            self.usage().contains(usage)
+        } else {
+            image.usage.contains(usage)
+        }
+    }
+
+    /// The images of this pack that can be sent as stickers, by shortcode.
+    pub fn stickers(&self) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images_with_usage(PackUsage::Sticker)
+    }
+
+    /// The images of this pack that can be used as custom emojis, by
+    /// shortcode.
+    pub fn emoticons(&self) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images_with_usage(PackUsage::Emoticon)
+    }
+
+    fn images_with_usage(&self, usage: PackUsage) -> impl Iterator<Item = (&str, &PackImage)> {
+        self.images
+            .iter()
+            .filter(move |(_, image)| self.image_has_usage(image, &usage))
+            .map(|(shortcode, image)| (shortcode.as_str(), image))
+    }
+}
+
+/// Information about an image pack.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct PackInfo {
+    /// The name of the pack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// The avatar of the pack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<OwnedMxcUri>,
+
+    /// What the images of the pack can be used for, by default.
+    ///
+    /// If this is empty, they can be used for everything.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<PackUsage>,
+
+    /// The attribution of the pack.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+}
+
+/// An image in an image pack.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PackImage {
+    /// The URL of the image.
+    pub url: OwnedMxcUri,
+
+    /// The description of the image, used as the body of stickers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+
+    /// Metadata about the image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ImageInfo>,
+
+    /// What the image can be used for.
+    ///
+    /// If this is empty, the usage of the pack applies.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<PackUsage>,
+}
+
+impl PackImage {
+    /// Create a new `PackImage` with the given URL.
+    pub fn new(url: OwnedMxcUri) -> Self {
+        Self { url, body: None, info: None, usage: Vec::new() }
+    }
+
+    /// Build the content of an `m.sticker` event for this image.
+    ///
+    /// The shortcode is used as the body if the image doesn't have one.
+    pub fn to_sticker_content(&self, shortcode: &str) -> StickerEventContent {
+        StickerEventContent::new(
+            self.body.clone().unwrap_or_else(|| shortcode.to_owned()),
+            self.info.clone().unwrap_or_default(),
+            self.url.clone(),
+        )
+    }
+}
+
+/// What an image pack or an image can be used for.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum PackUsage {
+    /// The image can be used as a custom emoji.
+    Emoticon,
+
+    /// The image can be sent as a sticker.
+    Sticker,
+
+    /// An unknown usage.
+    #[doc(hidden)]
+    #[serde(other)]
+    _Custom,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value as from_json_value, json};
+
+    use super::{ImagePackEventContent, PackUsage};
+
+    #[test]
+    fn test_image_usages() {
+        let pack: ImagePackEventContent = from_json_value(json!({
+            "images": {
+                "cat": { "url": "mxc://localhost/cat", "body": "A cat" },
+                "smile": { "url": "mxc://localhost/smile", "usage": ["emoticon"] },
+                "wave": { "url": "mxc://localhost/wave", "usage": ["sticker", "unknown"] },
+            },
+            "pack": { "display_name": "Mine", "usage": ["sticker"] },
+        }))
+        .unwrap();
+
+        let stickers: Vec<_> = pack.stickers().map(|(shortcode, _)| shortcode).collect();
+        assert_eq!(stickers, ["cat", "wave"]);
+        let emoticons: Vec<_> = pack.emoticons().map(|(shortcode, _)| shortcode).collect();
+        assert_eq!(emoticons, ["smile"]);
+
+        let wave = &pack.images["wave"];
+        assert_eq!(wave.usage, [PackUsage::Sticker, PackUsage::_Custom]);
+
+        let content = pack.images["cat"].to_sticker_content("cat");
+        assert_eq!(content.body, "A cat");
+        assert_eq!(content.url.as_str(), "mxc://localhost/cat");
+        assert_eq!(wave.to_sticker_content("wave").body, "wave");
+    }
+
+    #[test]
+    fn test_pack_without_usage() {
+        let pack: ImagePackEventContent = from_json_value(json!({
+            "images": { "cat": { "url": "mxc://localhost/cat" } },
+        }))
+        .unwrap();
+
+        assert_eq!(pack.stickers().count(), 1);
+        assert_eq!(pack.emoticons().count(), 1);
+    }
+}
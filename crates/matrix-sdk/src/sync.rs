@@ -22,15 +22,19 @@ use std::{
 
 pub use matrix_sdk_base::sync::*;
 use matrix_sdk_base::{
-    debug::{DebugInvitedRoom, DebugListOfRawEventsNoId, DebugNotificationMap},
+    debug::{DebugInvitedRoom, DebugKnockedRoom, DebugListOfRawEventsNoId, DebugNotificationMap},
     deserialized_responses::AmbiguityChanges,
     instant::Instant,
     sync::SyncResponse as BaseSyncResponse,
 };
+use matrix_sdk_common::time::Clock;
 use ruma::{
     api::client::{
         push::get_notifications::v3::Notification,
-        sync::sync_events::{self, v3::InvitedRoom},
+        sync::sync_events::{
+            self,
+            v3::{InvitedRoom, KnockedRoom},
+        },
     },
     events::{presence::PresenceEvent, AnyGlobalAccountDataEvent, AnyToDeviceEvent},
     serde::Raw,
@@ -121,6 +125,13 @@ pub enum RoomUpdate {
         /// Updates to the room.
         updates: InvitedRoom,
     },
+    /// Updates to a room the user knocked on.
+    Knocked {
+        /// Room object with general information on the room.
+        room: Room,
+        /// Updates to the room.
+        updates: KnockedRoom,
+    },
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -138,6 +149,11 @@ impl fmt::Debug for RoomUpdate {
                 .field("room", room)
                 .field("updates", &DebugInvitedRoom(updates))
                 .finish(),
+            Self::Knocked { room, updates } => f
+                .debug_struct("Knocked")
+                .field("room", room)
+                .field("updates", &DebugKnockedRoom(updates))
+                .finish(),
         }
     }
 }
@@ -232,6 +248,21 @@ impl Client {
             self.handle_sync_events(HandlerKind::StrippedState, Some(&room), invite_state).await?;
         }
 
+        for (room_id, room_info) in &rooms.knock {
+            let Some(room) = self.get_room(room_id) else {
+                error!(?room_id, "Can't call event handler, room not found");
+                continue;
+            };
+
+            self.send_room_update(room_id, || RoomUpdate::Knocked {
+                room: room.clone(),
+                updates: room_info.clone(),
+            });
+
+            let knock_state = &room_info.knock_state.events;
+            self.handle_sync_events(HandlerKind::StrippedState, Some(&room), knock_state).await?;
+        }
+
         debug!("Ran event handlers in {:?}", now.elapsed());
 
         let now = Instant::now();
@@ -259,6 +290,9 @@ impl Client {
 
         debug!("Ran notification handlers in {:?}", now.elapsed());
 
+        self.update_unread_counts().await;
+        self.maybe_load_scheduled_messages();
+
         Ok(())
     }
 
@@ -275,14 +309,6 @@ impl Client {
         }
     }
 
-    async fn sleep() {
-        #[cfg(target_arch = "wasm32")]
-        gloo_timers::future::TimeoutFuture::new(1_000).await;
-
-        #[cfg(not(target_arch = "wasm32"))]
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
-
     pub(crate) async fn sync_loop_helper(
         &self,
         sync_settings: &mut crate::config::SyncSettings,
@@ -301,15 +327,15 @@ impl Client {
         }
     }
 
-    pub(crate) async fn delay_sync(last_sync_time: &mut Option<Instant>) {
-        let now = Instant::now();
+    pub(crate) async fn delay_sync(&self, last_sync_time: &mut Option<Instant>) {
+        let now = self.inner.clock.now();
 
         // If the last sync happened less than a second ago, sleep for a
         // while to not hammer out requests if the server doesn't respect
         // the sync timeout.
         if let Some(t) = last_sync_time {
             if now - *t <= Duration::from_secs(1) {
-                Self::sleep().await;
+                self.inner.clock.sleep(Duration::from_secs(1)).await;
             }
         }
 
@@ -0,0 +1,75 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Completion of the [user-interactive authentication] required by sensitive
+//! operations, like deleting devices or changing the password.
+//!
+//! [user-interactive authentication]: https://spec.matrix.org/v1.8/client-server-api/#user-interactive-authentication-api
+
+use std::future::IntoFuture;
+
+use async_trait::async_trait;
+use matrix_sdk_common::AsyncTraitDeps;
+use ruma::api::client::uiaa::{AuthData, UiaaInfo};
+
+use crate::HttpResult;
+
+/// A handler providing the authentication data of the user-interactive
+/// authentication.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait UiaaHandler: AsyncTraitDeps {
+    /// Get the authentication data for the next stage of the authentication.
+    ///
+    /// This is called every time the server responds with the remaining
+    /// stages. The `session` of the returned data must be the one of
+    /// `uiaa_info`.
+    ///
+    /// Returns `None` to cancel the operation, in which case the error of the
+    /// server is returned.
+    async fn auth_data(&self, uiaa_info: &UiaaInfo) -> Option<AuthData>;
+}
+
+/// Send a request until it succeeds, completing the stages of the
+/// authentication required by the server with the handler.
+///
+/// `send_request` is called with the authentication data to put in the
+/// request, which is `None` the first time.
+pub(crate) async fn send_with_uiaa<T, F, Fut>(
+    uiaa_handler: &dyn UiaaHandler,
+    mut send_request: F,
+) -> HttpResult<T>
+where
+    F: FnMut(Option<AuthData>) -> Fut,
+    Fut: IntoFuture<Output = HttpResult<T>>,
+{
+    let mut auth_data = None;
+
+    loop {
+        match send_request(auth_data.take()).await {
+            Err(error) => {
+                let Some(uiaa_info) = error.as_uiaa_response() else {
+                    return Err(error);
+                };
+
+                auth_data = uiaa_handler.auth_data(uiaa_info).await;
+
+                if auth_data.is_none() {
+                    return Err(error);
+                }
+            }
+            res => return res,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregation of the unread counts of all the joined rooms.
+
+use matrix_sdk_base::{Room as BaseRoom, RoomStateFilter};
+use tracing::warn;
+
+use crate::{
+    notification_settings::{RoomNotificationMode, Rules},
+    Client,
+};
+
+/// Unread counts aggregated over all the rooms the user has joined.
+///
+/// Rooms that are muted are not taken into account. For encrypted rooms, the
+/// counts computed client-side are used, since the server can't know which
+/// events should notify.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnreadCounts {
+    /// The number of rooms that have at least one unread notification.
+    pub num_rooms_with_notifications: u64,
+    /// The total number of unread notifications.
+    pub num_notifications: u64,
+    /// The total number of unread mentions, that is notifications with the
+    /// highlight flag set.
+    pub num_mentions: u64,
+    /// The total number of unread messages.
+    pub num_unread_messages: u64,
+}
+
+impl UnreadCounts {
+    /// Add the counts of another set of rooms, like the ones of another
+    /// account.
+    pub(crate) fn add_counts(&mut self, other: &UnreadCounts) {
+        self.num_rooms_with_notifications =
+            self.num_rooms_with_notifications.saturating_add(other.num_rooms_with_notifications);
+        self.num_notifications = self.num_notifications.saturating_add(other.num_notifications);
+        self.num_mentions = self.num_mentions.saturating_add(other.num_mentions);
+        self.num_unread_messages =
+            self.num_unread_messages.saturating_add(other.num_unread_messages);
+    }
+
+    /// Add the counts of the given room, using the given notification mode.
+    fn add_room(&mut self, room: &BaseRoom, mode: RoomNotificationMode) {
+        if mode == RoomNotificationMode::Mute {
+            return;
+        }
+
+        let (notifications, mentions, unread_messages) = if room.is_encrypted() {
+            (
+                room.num_unread_notifications(),
+                room.num_unread_mentions(),
+                room.num_unread_messages(),
+            )
+        } else {
+            let counts = room.unread_notification_counts();
+            (
+                counts.notification_count,
+                counts.highlight_count,
+                room.num_unread_messages().max(counts.notification_count),
+            )
+        };
+
+        if notifications > 0 {
+            self.num_rooms_with_notifications += 1;
+        }
+
+        self.num_notifications = self.num_notifications.saturating_add(notifications);
+        self.num_mentions = self.num_mentions.saturating_add(mentions);
+        self.num_unread_messages = self.num_unread_messages.saturating_add(unread_messages);
+    }
+}
+
+impl Client {
+    /// Recompute the aggregated unread counts and publish them to the
+    /// subscribers of [`Client::unread_counts_stream`] if they changed.
+    pub(crate) async fn update_unread_counts(&self) {
+        if self.user_id().is_none() {
+            return;
+        }
+
+        let rules = match self.account().push_rules().await {
+            Ok(ruleset) => Rules::new(ruleset),
+            Err(e) => {
+                warn!("Couldn't load the push rules to compute the unread counts: {e}");
+                return;
+            }
+        };
+
+        let mut counts = UnreadCounts::default();
+
+        for room in self.base_client().get_rooms_filtered(RoomStateFilter::JOINED) {
+            let mode = rules
+                .get_user_defined_room_notification_mode(room.room_id())
+                .unwrap_or_else(|| {
+                    rules.get_default_room_notification_mode(
+                        room.is_encrypted().into(),
+                        (room.active_members_count() == 2).into(),
+                    )
+                });
+
+            counts.add_room(&room, mode);
+        }
+
+        self.inner.unread_counts.set_if_not_eq(counts);
+    }
+}
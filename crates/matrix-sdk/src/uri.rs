@@ -0,0 +1,255 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsing and construction of `matrix.to` links and `matrix:` URIs.
+//!
+//! A [`UriTarget`] can be parsed from both kinds of URIs with
+//! [`UriTarget::parse`], and converted back with [`UriTarget::matrix_to_uri`]
+//! and [`UriTarget::matrix_uri`].
+
+use ruma::{
+    matrix_uri::MatrixId, MatrixToUri, MatrixUri, OwnedEventId, OwnedRoomOrAliasId,
+    OwnedServerName, OwnedUserId, RoomAliasId, RoomId, RoomOrAliasId,
+};
+
+use crate::{Result, Room};
+
+/// The target of a `matrix.to` link or a `matrix:` URI.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UriTarget {
+    /// A room.
+    Room {
+        /// The ID or alias of the room.
+        room: OwnedRoomOrAliasId,
+        /// Servers that should know the room, to be able to join it over
+        /// federation.
+        via: Vec<OwnedServerName>,
+    },
+
+    /// An event in a room.
+    Event {
+        /// The ID or alias of the room of the event.
+        room: OwnedRoomOrAliasId,
+        /// The ID of the event.
+        event_id: OwnedEventId,
+        /// Servers that should know the room, to be able to join it over
+        /// federation.
+        via: Vec<OwnedServerName>,
+    },
+
+    /// A user.
+    User {
+        /// The ID of the user.
+        user_id: OwnedUserId,
+    },
+}
+
+impl UriTarget {
+    /// Parse a `matrix.to` link or a `matrix:` URI.
+    ///
+    /// Returns `None` if the string is not a valid URI of either kind.
+    pub fn parse(uri: &str) -> Option<Self> {
+        if let Ok(matrix_to) = MatrixToUri::parse(uri) {
+            return Self::from_id(matrix_to.id(), matrix_to.via());
+        }
+
+        let matrix_uri = MatrixUri::parse(uri).ok()?;
+        Self::from_id(matrix_uri.id(), matrix_uri.via())
+    }
+
+    fn from_id(id: &MatrixId, via: &[OwnedServerName]) -> Option<Self> {
+        let via = via.to_vec();
+
+        match id {
+            MatrixId::Room(room_id) => Some(Self::Room { room: room_id.clone().into(), via }),
+            MatrixId::RoomAlias(alias) => Some(Self::Room { room: alias.clone().into(), via }),
+            MatrixId::Event(room, event_id) => {
+                Some(Self::Event { room: room.clone(), event_id: event_id.clone(), via })
+            }
+            MatrixId::User(user_id) => Some(Self::User { user_id: user_id.clone() }),
+            _ => None,
+        }
+    }
+
+    /// The target for the given room.
+    ///
+    /// If the room has an alias, it is used. Otherwise, the synced members of
+    /// the room are used for [routing] the room ID.
+    ///
+    /// [routing]: https://spec.matrix.org/v1.3/appendices/#routing
+    pub async fn room(room: &Room) -> Result<Self> {
+        if let Some(alias) = room.canonical_alias().or_else(|| room.alt_aliases().pop()) {
+            return Ok(Self::Room { room: alias.into(), via: Vec::new() });
+        }
+
+        let via = room.route().await?;
+        Ok(Self::Room { room: room.room_id().to_owned().into(), via })
+    }
+
+    /// The target for the given event in the given room.
+    ///
+    /// The synced members of the room are used for [routing] the room ID.
+    ///
+    /// *Note*: This method does not check if the given event ID is actually
+    /// part of the room.
+    ///
+    /// [routing]: https://spec.matrix.org/v1.3/appendices/#routing
+    pub async fn event(room: &Room, event_id: impl Into<OwnedEventId>) -> Result<Self> {
+        // Don't use the alias because an event is tied to a room ID, but an
+        // alias might point to another room, e.g. after a room upgrade.
+        let via = room.route().await?;
+        Ok(Self::Event { room: room.room_id().to_owned().into(), event_id: event_id.into(), via })
+    }
+
+    /// The target for the given user.
+    pub fn user(user_id: impl Into<OwnedUserId>) -> Self {
+        Self::User { user_id: user_id.into() }
+    }
+
+    /// Construct the `matrix.to` link to this target.
+    pub fn matrix_to_uri(&self) -> MatrixToUri {
+        match self {
+            Self::Room { room, via } => match split_room(room) {
+                Ok(room_id) => room_id.matrix_to_uri_via(via.clone()),
+                Err(alias) => alias.matrix_to_uri(),
+            },
+            Self::Event { room, event_id, via } => match split_room(room) {
+                Ok(room_id) => room_id.matrix_to_event_uri_via(event_id.clone(), via.clone()),
+                Err(alias) => alias.matrix_to_event_uri(event_id.clone()),
+            },
+            Self::User { user_id } => user_id.matrix_to_uri(),
+        }
+    }
+
+    /// Construct the `matrix:` URI to this target.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - For a room, whether the user should join it. For a user,
+    ///   whether a chat should be started with them. Ignored for an event.
+    pub fn matrix_uri(&self, action: bool) -> MatrixUri {
+        match self {
+            Self::Room { room, via } => match split_room(room) {
+                Ok(room_id) => room_id.matrix_uri_via(via.clone(), action),
+                Err(alias) => alias.matrix_uri(action),
+            },
+            Self::Event { room, event_id, via } => match split_room(room) {
+                Ok(room_id) => room_id.matrix_event_uri_via(event_id.clone(), via.clone()),
+                Err(alias) => alias.matrix_event_uri(event_id.clone()),
+            },
+            Self::User { user_id } => user_id.matrix_uri(action),
+        }
+    }
+}
+
+fn split_room(room: &RoomOrAliasId) -> Result<&RoomId, &RoomAliasId> {
+    room.try_into()
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{event_id, owned_server_name, room_alias_id, room_id, user_id};
+
+    use super::UriTarget;
+
+    #[test]
+    fn test_parse_matrix_to() {
+        assert_eq!(
+            UriTarget::parse("https://matrix.to/#/!ruma:notareal.hs?via=notareal.hs&via=other.hs"),
+            Some(UriTarget::Room {
+                room: room_id!("!ruma:notareal.hs").to_owned().into(),
+                via: vec![owned_server_name!("notareal.hs"), owned_server_name!("other.hs")],
+            })
+        );
+        assert_eq!(
+            UriTarget::parse("https://matrix.to/#/%23ruma:notareal.hs/$event:notareal.hs"),
+            Some(UriTarget::Event {
+                room: room_alias_id!("#ruma:notareal.hs").to_owned().into(),
+                event_id: event_id!("$event:notareal.hs").to_owned(),
+                via: Vec::new(),
+            })
+        );
+        assert_eq!(
+            UriTarget::parse("https://matrix.to/#/@jplatte:notareal.hs"),
+            Some(UriTarget::user(user_id!("@jplatte:notareal.hs")))
+        );
+    }
+
+    #[test]
+    fn test_parse_matrix_uri() {
+        assert_eq!(
+            UriTarget::parse("matrix:roomid/ruma:notareal.hs/e/event:notareal.hs?via=notareal.hs"),
+            Some(UriTarget::Event {
+                room: room_id!("!ruma:notareal.hs").to_owned().into(),
+                event_id: event_id!("$event:notareal.hs").to_owned(),
+                via: vec![owned_server_name!("notareal.hs")],
+            })
+        );
+        assert_eq!(
+            UriTarget::parse("matrix:r/ruma:notareal.hs"),
+            Some(UriTarget::Room {
+                room: room_alias_id!("#ruma:notareal.hs").to_owned().into(),
+                via: Vec::new(),
+            })
+        );
+        assert_eq!(
+            UriTarget::parse("matrix:u/jplatte:notareal.hs?action=chat"),
+            Some(UriTarget::user(user_id!("@jplatte:notareal.hs")))
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(UriTarget::parse("https://example.org/#/!ruma:notareal.hs"), None);
+        assert_eq!(UriTarget::parse("matrix:unknown/ruma:notareal.hs"), None);
+        assert_eq!(UriTarget::parse("not a uri"), None);
+    }
+
+    #[test]
+    fn test_construct() {
+        let room = UriTarget::Room {
+            room: room_id!("!ruma:notareal.hs").to_owned().into(),
+            via: vec![owned_server_name!("notareal.hs")],
+        };
+        assert_eq!(
+            room.matrix_to_uri().to_string(),
+            "https://matrix.to/#/!ruma:notareal.hs?via=notareal.hs"
+        );
+        assert_eq!(
+            room.matrix_uri(true).to_string(),
+            "matrix:roomid/ruma:notareal.hs?via=notareal.hs&action=join"
+        );
+
+        let event = UriTarget::Event {
+            room: room_alias_id!("#ruma:notareal.hs").to_owned().into(),
+            event_id: event_id!("$event:notareal.hs").to_owned(),
+            via: Vec::new(),
+        };
+        assert_eq!(
+            event.matrix_to_uri().to_string(),
+            "https://matrix.to/#/%23ruma:notareal.hs/$event:notareal.hs"
+        );
+        assert_eq!(
+            event.matrix_uri(false).to_string(),
+            "matrix:r/ruma:notareal.hs/e/event:notareal.hs"
+        );
+
+        let user = UriTarget::user(user_id!("@jplatte:notareal.hs"));
+        assert_eq!(user.matrix_to_uri().to_string(), "https://matrix.to/#/@jplatte:notareal.hs");
+        assert_eq!(user.matrix_uri(false).to_string(), "matrix:u/jplatte:notareal.hs");
+
+        // Round trip.
+        assert_eq!(UriTarget::parse(&event.matrix_uri(false).to_string()), Some(event));
+    }
+}
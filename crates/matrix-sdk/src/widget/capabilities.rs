@@ -18,28 +18,38 @@
 use std::fmt;
 
 use async_trait::async_trait;
-use ruma::{events::AnyTimelineEvent, serde::Raw};
-use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
-use tracing::{debug, error};
+use ruma::{
+    events::{AnyTimelineEvent, ToDeviceEventType},
+    serde::Raw,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::{debug, error, warn};
 
 use super::{
     filter::MatrixEventFilterInput, EventFilter, MessageLikeEventFilter, StateEventFilter,
 };
+use crate::{Client, Result};
 
 /// Must be implemented by a component that provides functionality of deciding
 /// whether a widget is allowed to use certain capabilities (typically by
 /// providing a prompt to the user).
+///
+/// The decisions are remembered per widget, so the provider is only asked
+/// about the capabilities that were never approved or denied for the widget
+/// before. They can be reset with [`forget_capabilities`].
 #[async_trait]
 pub trait CapabilitiesProvider: Send + Sync + 'static {
     /// Receives a request for given capabilities and returns the actual
     /// capabilities that the clients grants to a given widget (usually by
     /// prompting the user).
+    ///
+    /// [`Capabilities::iter`] can be used to present the request as a list of
+    /// [`Capability`].
     async fn acquire_capabilities(&self, capabilities: Capabilities) -> Capabilities;
 }
 
 /// Capabilities that a widget can request from a client.
-#[derive(Clone, Debug, Default)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Capabilities {
     /// Types of the messages that a widget wants to be able to fetch.
     pub read: Vec<EventFilter>,
@@ -51,6 +61,14 @@ pub struct Capabilities {
     /// This means clients should not offer to open the widget in a separate
     /// browser/tab/webview that is not connected to the postmessage widget-api.
     pub requires_client: bool,
+    /// Types of the to-device messages that a widget wants to be able to send.
+    pub send_to_device: Vec<ToDeviceEventType>,
+    /// Whether a widget wants to be able to send delayed events, i.e. events
+    /// that the homeserver only sends after a timeout.
+    pub send_delayed_event: bool,
+    /// Whether a widget wants to be able to cancel, restart or immediately
+    /// send its delayed events.
+    pub update_delayed_event: bool,
 }
 
 impl Capabilities {
@@ -66,6 +84,84 @@ impl Capabilities {
 
         self.read.iter().any(|f| f.matches(&filter_in))
     }
+
+    /// Iterate over the individual capabilities of this set.
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        let requires_client = self.requires_client.then_some(Capability::RequiresClient);
+        let send_delayed_event = self.send_delayed_event.then_some(Capability::SendDelayedEvent);
+        let update_delayed_event =
+            self.update_delayed_event.then_some(Capability::UpdateDelayedEvent);
+
+        requires_client
+            .into_iter()
+            .chain(self.read.iter().cloned().map(Capability::Read))
+            .chain(self.send.iter().cloned().map(Capability::Send))
+            .chain(self.send_to_device.iter().cloned().map(Capability::SendToDevice))
+            .chain(send_delayed_event)
+            .chain(update_delayed_event)
+    }
+
+    /// Whether this set contains the given capability.
+    pub fn contains(&self, capability: &Capability) -> bool {
+        match capability {
+            Capability::RequiresClient => self.requires_client,
+            Capability::Read(filter) => self.read.contains(filter),
+            Capability::Send(filter) => self.send.contains(filter),
+            Capability::SendToDevice(event_type) => self.send_to_device.contains(event_type),
+            Capability::SendDelayedEvent => self.send_delayed_event,
+            Capability::UpdateDelayedEvent => self.update_delayed_event,
+        }
+    }
+
+    /// Whether this set contains no capability.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Add the given capability to this set, if it is not already in it.
+    pub fn insert(&mut self, capability: Capability) {
+        if self.contains(&capability) {
+            return;
+        }
+
+        match capability {
+            Capability::RequiresClient => self.requires_client = true,
+            Capability::Read(filter) => self.read.push(filter),
+            Capability::Send(filter) => self.send.push(filter),
+            Capability::SendToDevice(event_type) => self.send_to_device.push(event_type),
+            Capability::SendDelayedEvent => self.send_delayed_event = true,
+            Capability::UpdateDelayedEvent => self.update_delayed_event = true,
+        }
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<T: IntoIterator<Item = Capability>>(iter: T) -> Self {
+        let mut capabilities = Self::default();
+        for capability in iter {
+            capabilities.insert(capability);
+        }
+        capabilities
+    }
+}
+
+/// A single capability that a widget can request from a client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Capability {
+    /// The widget can not operate separately from the matrix client.
+    ///
+    /// See [`Capabilities::requires_client`].
+    RequiresClient,
+    /// The widget can read the events matching the filter.
+    Read(EventFilter),
+    /// The widget can send the events matching the filter.
+    Send(EventFilter),
+    /// The widget can send to-device messages of the given type.
+    SendToDevice(ToDeviceEventType),
+    /// The widget can send delayed events.
+    SendDelayedEvent,
+    /// The widget can cancel, restart or immediately send its delayed events.
+    UpdateDelayedEvent,
 }
 
 const SEND_EVENT: &str = "org.matrix.msc2762.send.event";
@@ -73,12 +169,13 @@ const READ_EVENT: &str = "org.matrix.msc2762.receive.event";
 const SEND_STATE: &str = "org.matrix.msc2762.send.state_event";
 const READ_STATE: &str = "org.matrix.msc2762.receive.state_event";
 const REQUIRES_CLIENT: &str = "io.element.requires_client";
+const SEND_TO_DEVICE: &str = "org.matrix.msc3819.send.to_device";
+const SEND_DELAYED_EVENT: &str = "org.matrix.msc4157.send.delayed_event";
+const UPDATE_DELAYED_EVENT: &str = "org.matrix.msc4157.update_delayed_event";
 
-impl Serialize for Capabilities {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
+/// Formats the capability in its serialized form.
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         struct PrintEventFilter<'a>(&'a EventFilter);
         impl fmt::Display for PrintEventFilter<'_> {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -117,74 +214,34 @@ impl Serialize for Capabilities {
             }
         }
 
-        let seq_len = self.requires_client as usize + self.read.len() + self.send.len();
-        let mut seq = serializer.serialize_seq(Some(seq_len))?;
-
-        if self.requires_client {
-            seq.serialize_element(REQUIRES_CLIENT)?;
-        }
-        for filter in &self.read {
-            let name = match filter {
-                EventFilter::MessageLike(_) => READ_EVENT,
-                EventFilter::State(_) => READ_STATE,
-            };
-            seq.serialize_element(&format!("{name}:{}", PrintEventFilter(filter)))?;
-        }
-        for filter in &self.send {
-            let name = match filter {
-                EventFilter::MessageLike(_) => SEND_EVENT,
-                EventFilter::State(_) => SEND_STATE,
-            };
-            seq.serialize_element(&format!("{name}:{}", PrintEventFilter(filter)))?;
+        match self {
+            Self::RequiresClient => f.write_str(REQUIRES_CLIENT),
+            Self::Read(filter) => {
+                let name = match filter {
+                    EventFilter::MessageLike(_) => READ_EVENT,
+                    EventFilter::State(_) => READ_STATE,
+                };
+                write!(f, "{name}:{}", PrintEventFilter(filter))
+            }
+            Self::Send(filter) => {
+                let name = match filter {
+                    EventFilter::MessageLike(_) => SEND_EVENT,
+                    EventFilter::State(_) => SEND_STATE,
+                };
+                write!(f, "{name}:{}", PrintEventFilter(filter))
+            }
+            Self::SendToDevice(event_type) => write!(f, "{SEND_TO_DEVICE}:{event_type}"),
+            Self::SendDelayedEvent => f.write_str(SEND_DELAYED_EVENT),
+            Self::UpdateDelayedEvent => f.write_str(UPDATE_DELAYED_EVENT),
         }
-
-        seq.end()
     }
 }
 
-impl<'de> Deserialize<'de> for Capabilities {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        enum Permission {
-            RequiresClient,
-            Read(EventFilter),
-            Send(EventFilter),
-            Unknown,
-        }
-
-        impl<'de> Deserialize<'de> for Permission {
-            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where
-                D: Deserializer<'de>,
-            {
-                let s = ruma::serde::deserialize_cow_str(deserializer)?;
-                if s == REQUIRES_CLIENT {
-                    return Ok(Self::RequiresClient);
-                }
-
-                match s.split_once(':') {
-                    Some((READ_EVENT, filter_s)) => Ok(Permission::Read(EventFilter::MessageLike(
-                        parse_message_event_filter(filter_s),
-                    ))),
-                    Some((SEND_EVENT, filter_s)) => Ok(Permission::Send(EventFilter::MessageLike(
-                        parse_message_event_filter(filter_s),
-                    ))),
-                    Some((READ_STATE, filter_s)) => {
-                        Ok(Permission::Read(EventFilter::State(parse_state_event_filter(filter_s))))
-                    }
-                    Some((SEND_STATE, filter_s)) => {
-                        Ok(Permission::Send(EventFilter::State(parse_state_event_filter(filter_s))))
-                    }
-                    _ => {
-                        debug!("Unknown capability `{s}`");
-                        Ok(Self::Unknown)
-                    }
-                }
-            }
-        }
-
+impl Capability {
+    /// Parse a capability from its serialized form.
+    ///
+    /// Returns `None` if the capability is unknown.
+    fn parse(s: &str) -> Option<Self> {
         fn parse_message_event_filter(s: &str) -> MessageLikeEventFilter {
             match s.strip_prefix("m.room.message#") {
                 Some(msgtype) => MessageLikeEventFilter::RoomMessageWithMsgtype(msgtype.to_owned()),
@@ -203,21 +260,131 @@ impl<'de> Deserialize<'de> for Capabilities {
             }
         }
 
-        let mut capabilities = Capabilities::default();
-        for capability in Vec::<Permission>::deserialize(deserializer)? {
-            match capability {
-                Permission::RequiresClient => capabilities.requires_client = true,
-                Permission::Read(filter) => capabilities.read.push(filter),
-                Permission::Send(filter) => capabilities.send.push(filter),
-                // ignore unknown capabilities
-                Permission::Unknown => {}
+        match s {
+            REQUIRES_CLIENT => return Some(Self::RequiresClient),
+            SEND_DELAYED_EVENT => return Some(Self::SendDelayedEvent),
+            UPDATE_DELAYED_EVENT => return Some(Self::UpdateDelayedEvent),
+            _ => {}
+        }
+
+        match s.split_once(':') {
+            Some((READ_EVENT, filter_s)) => {
+                Some(Self::Read(EventFilter::MessageLike(parse_message_event_filter(filter_s))))
+            }
+            Some((SEND_EVENT, filter_s)) => {
+                Some(Self::Send(EventFilter::MessageLike(parse_message_event_filter(filter_s))))
             }
+            Some((READ_STATE, filter_s)) => {
+                Some(Self::Read(EventFilter::State(parse_state_event_filter(filter_s))))
+            }
+            Some((SEND_STATE, filter_s)) => {
+                Some(Self::Send(EventFilter::State(parse_state_event_filter(filter_s))))
+            }
+            Some((SEND_TO_DEVICE, event_type)) => Some(Self::SendToDevice(event_type.into())),
+            _ => None,
         }
+    }
+}
+
+impl Serialize for Capabilities {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.iter().map(|capability| capability.to_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Capabilities {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let capabilities = Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .filter_map(|s| {
+                let capability = Capability::parse(&s);
+                if capability.is_none() {
+                    // ignore unknown capabilities
+                    debug!("Unknown capability `{s}`");
+                }
+                capability
+            })
+            .collect();
 
         Ok(capabilities)
     }
 }
 
+/// The capabilities that were approved and denied for a widget, persisted in
+/// the state store.
+#[derive(Default, Deserialize, Serialize)]
+struct CapabilitiesDecisions {
+    approved: Capabilities,
+    denied: Capabilities,
+}
+
+fn decisions_key(widget_id: &str) -> String {
+    format!("widget_capabilities:{widget_id}")
+}
+
+/// Acquire the requested capabilities, asking the provider only about the ones
+/// that have no remembered decision for the widget.
+pub(super) async fn acquire_remembered_capabilities(
+    client: &Client,
+    widget_id: &str,
+    provider: &impl CapabilitiesProvider,
+    requested: Capabilities,
+) -> Capabilities {
+    let key = decisions_key(widget_id);
+    let mut decisions = match client.store().get_custom_value(key.as_bytes()).await {
+        Ok(Some(bytes)) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            warn!("Failed to deserialize the capabilities decisions of widget {widget_id}: {e}");
+            CapabilitiesDecisions::default()
+        }),
+        Ok(None) => CapabilitiesDecisions::default(),
+        Err(e) => {
+            warn!("Failed to load the capabilities decisions of widget {widget_id}: {e}");
+            CapabilitiesDecisions::default()
+        }
+    };
+
+    let undecided: Capabilities = requested
+        .iter()
+        .filter(|c| !decisions.approved.contains(c) && !decisions.denied.contains(c))
+        .collect();
+
+    if !undecided.is_empty() {
+        let granted = provider.acquire_capabilities(undecided.clone()).await;
+
+        for capability in undecided.iter() {
+            if granted.contains(&capability) {
+                decisions.approved.insert(capability);
+            } else {
+                decisions.denied.insert(capability);
+            }
+        }
+
+        let stored = match serde_json::to_vec(&decisions) {
+            Ok(value) => client.store().set_custom_value(key.as_bytes(), value).await.map(|_| ()),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = stored {
+            warn!("Failed to store the capabilities decisions of widget {widget_id}: {e}");
+        }
+    }
+
+    requested.iter().filter(|c| decisions.approved.contains(c)).collect()
+}
+
+/// Forget the capabilities that were approved and denied for the widget with
+/// the given ID, so the [`CapabilitiesProvider`] is asked about them again the
+/// next time they are requested.
+pub async fn forget_capabilities(client: &Client, widget_id: &str) -> Result<()> {
+    client.store().remove_custom_value(decisions_key(widget_id).as_bytes()).await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use ruma::events::StateEventType;
@@ -257,6 +424,7 @@ mod tests {
                 )),
             ],
             requires_client: true,
+            ..Default::default()
         };
 
         assert_eq!(parsed, expected);
@@ -285,10 +453,55 @@ mod tests {
                 )),
             ],
             requires_client: true,
+            ..Default::default()
         };
 
         let capabilities_str = serde_json::to_string(&capabilities).unwrap();
         let parsed = serde_json::from_str::<Capabilities>(&capabilities_str).unwrap();
         assert_eq!(parsed, capabilities);
     }
+
+    #[test]
+    fn deserialization_of_call_capabilities() {
+        let capabilities_str = r#"[
+            "org.matrix.msc3819.send.to_device:io.element.call.encryption_keys",
+            "org.matrix.msc4157.send.delayed_event",
+            "org.matrix.msc4157.update_delayed_event"
+        ]"#;
+
+        let parsed = serde_json::from_str::<Capabilities>(capabilities_str).unwrap();
+        let expected = Capabilities {
+            send_to_device: vec!["io.element.call.encryption_keys".into()],
+            send_delayed_event: true,
+            update_delayed_event: true,
+            ..Default::default()
+        };
+
+        assert_eq!(parsed, expected);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::from_str::<serde_json::Value>(capabilities_str).unwrap()
+        );
+    }
+
+    #[test]
+    fn capabilities_from_iter_deduplicates() {
+        let read_member = Capability::Read(EventFilter::State(StateEventFilter::WithType(
+            StateEventType::RoomMember,
+        )));
+        let capabilities: Capabilities = [
+            Capability::RequiresClient,
+            read_member.clone(),
+            Capability::RequiresClient,
+            read_member.clone(),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(capabilities.contains(&read_member));
+        assert_eq!(
+            capabilities.iter().collect::<Vec<_>>(),
+            [Capability::RequiresClient, read_member]
+        );
+    }
 }
@@ -18,8 +18,7 @@ use ruma::events::{MessageLikeEventType, StateEventType, TimelineEventType};
 use serde::Deserialize;
 
 /// Different kinds of filters for timeline events.
-#[derive(Clone, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum EventFilter {
     /// Filter for message-like events.
     MessageLike(MessageLikeEventFilter),
@@ -57,8 +56,7 @@ impl EventFilter {
 }
 
 /// Filter for message-like events.
-#[derive(Clone, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum MessageLikeEventFilter {
     /// Matches message-like events with the given `type`.
     WithType(MessageLikeEventType),
@@ -95,8 +93,7 @@ impl MessageLikeEventFilter {
 }
 
 /// Filter for state events.
-#[derive(Clone, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Clone, Debug, PartialEq)]
 pub enum StateEventFilter {
     /// Matches state events with the given `type`, regardless of `state_key`.
     WithType(StateEventType),
@@ -14,20 +14,27 @@
 
 //! A high-level API for requests that we send to the matrix driver.
 
-use std::marker::PhantomData;
+use std::{collections::BTreeMap, marker::PhantomData};
 
 use ruma::{
-    api::client::account::request_openid_token,
-    events::{AnyTimelineEvent, MessageLikeEventType, StateEventType, TimelineEventType},
+    api::client::{account::request_openid_token, to_device::send_event_to_device},
+    events::{
+        AnyTimelineEvent, AnyToDeviceEventContent, MessageLikeEventType, StateEventType,
+        TimelineEventType, ToDeviceEventType,
+    },
     serde::Raw,
-    OwnedEventId,
+    to_device::DeviceIdOrAllDevices,
+    OwnedEventId, OwnedUserId,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::value::RawValue as RawJsonValue;
 use tracing::error;
 
 use super::{incoming::MatrixDriverResponse, Action, MatrixDriverRequestMeta, WidgetMachine};
-use crate::widget::{Capabilities, StateKeySelector};
+use crate::{
+    room::UpdateDelayedEventAction,
+    widget::{Capabilities, StateKeySelector},
+};
 
 #[derive(Clone, Debug)]
 pub(crate) enum MatrixDriverRequestData {
@@ -49,6 +56,12 @@ pub(crate) enum MatrixDriverRequestData {
 
     /// Send matrix event that corresponds to the given description.
     SendMatrixEvent(SendEventRequest),
+
+    /// Send to-device messages.
+    SendToDevice(SendToDeviceRequest),
+
+    /// Cancel, restart or send a delayed event.
+    UpdateDelayedEvent(UpdateDelayedEventRequest),
 }
 
 /// A handle to a pending `toWidget` request.
@@ -217,6 +230,12 @@ pub(crate) struct SendEventRequest {
     pub(crate) state_key: Option<String>,
     /// Raw content of an event.
     pub(crate) content: Box<RawJsonValue>,
+    /// The delay before the homeserver sends the event, in milliseconds, to
+    /// send it as a delayed event.
+    pub(crate) delay: Option<u64>,
+    /// The ID of the delayed event that the homeserver sends this delayed
+    /// event with.
+    pub(crate) parent_delay_id: Option<String>,
 }
 
 impl From<SendEventRequest> for MatrixDriverRequestData {
@@ -226,10 +245,21 @@ impl From<SendEventRequest> for MatrixDriverRequestData {
 }
 
 impl MatrixDriverRequest for SendEventRequest {
-    type Response = OwnedEventId;
+    type Response = SentEvent;
 }
 
-impl FromMatrixDriverResponse for OwnedEventId {
+/// The result of a [`SendEventRequest`].
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct SentEvent {
+    /// The ID of the event, if it was sent immediately.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) event_id: Option<OwnedEventId>,
+    /// The ID of the delayed event, if it was scheduled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) delay_id: Option<String>,
+}
+
+impl FromMatrixDriverResponse for SentEvent {
     fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
         match ev {
             MatrixDriverResponse::MatrixEventSent(response) => Some(response),
@@ -240,3 +270,74 @@ impl FromMatrixDriverResponse for OwnedEventId {
         }
     }
 }
+
+/// Ask the client to send to-device messages.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct SendToDeviceRequest {
+    /// The type of the messages.
+    #[serde(rename = "type")]
+    pub(crate) event_type: ToDeviceEventType,
+    /// Whether the messages should be encrypted.
+    #[serde(default)]
+    pub(crate) encrypted: bool,
+    /// The raw contents of the messages, by user ID and device ID.
+    pub(crate) messages:
+        BTreeMap<OwnedUserId, BTreeMap<DeviceIdOrAllDevices, Raw<AnyToDeviceEventContent>>>,
+}
+
+impl From<SendToDeviceRequest> for MatrixDriverRequestData {
+    fn from(value: SendToDeviceRequest) -> Self {
+        MatrixDriverRequestData::SendToDevice(value)
+    }
+}
+
+impl MatrixDriverRequest for SendToDeviceRequest {
+    type Response = send_event_to_device::v3::Response;
+}
+
+impl FromMatrixDriverResponse for send_event_to_device::v3::Response {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::ToDeviceSent(response) => Some(response),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
+
+/// Ask the client to cancel, restart or send a delayed event.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct UpdateDelayedEventRequest {
+    /// What to do with the delayed event.
+    pub(crate) action: UpdateDelayedEventAction,
+    /// The ID of the delayed event.
+    pub(crate) delay_id: String,
+}
+
+impl From<UpdateDelayedEventRequest> for MatrixDriverRequestData {
+    fn from(value: UpdateDelayedEventRequest) -> Self {
+        MatrixDriverRequestData::UpdateDelayedEvent(value)
+    }
+}
+
+impl MatrixDriverRequest for UpdateDelayedEventRequest {
+    type Response = DelayedEventUpdated;
+}
+
+/// The result of an [`UpdateDelayedEventRequest`].
+#[derive(Clone, Debug)]
+pub(crate) struct DelayedEventUpdated;
+
+impl FromMatrixDriverResponse for DelayedEventUpdated {
+    fn from_response(ev: MatrixDriverResponse) -> Option<Self> {
+        match ev {
+            MatrixDriverResponse::DelayedEventUpdated => Some(DelayedEventUpdated),
+            _ => {
+                error!("bug in MatrixDriver, received wrong event response");
+                None
+            }
+        }
+    }
+}
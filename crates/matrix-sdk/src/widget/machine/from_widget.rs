@@ -17,12 +17,15 @@ use std::fmt;
 use ruma::{
     events::{AnyTimelineEvent, MessageLikeEventType, StateEventType},
     serde::Raw,
-    OwnedEventId, RoomId,
+    RoomId,
 };
 use serde::{Deserialize, Serialize};
 
-use super::SendEventRequest;
-use crate::widget::StateKeySelector;
+use super::{
+    driver_req::{SendToDeviceRequest, SentEvent, UpdateDelayedEventRequest},
+    SendEventRequest,
+};
+use crate::widget::{Capabilities, StateKeySelector};
 
 #[derive(Deserialize)]
 #[serde(tag = "action", rename_all = "snake_case", content = "data")]
@@ -34,6 +37,11 @@ pub(super) enum FromWidgetRequest {
     #[serde(rename = "org.matrix.msc2876.read_events")]
     ReadEvent(ReadEventRequest),
     SendEvent(SendEventRequest),
+    SendToDevice(SendToDeviceRequest),
+    #[serde(rename = "org.matrix.msc2974.request_capabilities")]
+    RenegotiateCapabilities(RenegotiateCapabilitiesRequest),
+    #[serde(rename = "org.matrix.msc4157.update_delayed_event")]
+    UpdateDelayedEvent(UpdateDelayedEventRequest),
 }
 
 #[derive(Serialize)]
@@ -65,7 +73,9 @@ impl SupportedApiVersionsResponse {
                 ApiVersion::V0_0_2,
                 ApiVersion::MSC2762,
                 ApiVersion::MSC2871,
+                ApiVersion::MSC2974,
                 ApiVersion::MSC3819,
+                ApiVersion::MSC4157,
             ],
         }
     }
@@ -109,6 +119,10 @@ pub(super) enum ApiVersion {
     /// Supports access to the TURN servers.
     #[serde(rename = "town.robin.msc3846")]
     MSC3846,
+
+    /// Supports sending delayed events.
+    #[serde(rename = "org.matrix.msc4157")]
+    MSC4157,
 }
 
 #[derive(Deserialize)]
@@ -135,5 +149,11 @@ pub(super) struct ReadEventResponse {
 #[derive(Serialize)]
 pub(super) struct SendEventResponse<'a> {
     pub(super) room_id: &'a RoomId,
-    pub(super) event_id: OwnedEventId,
+    #[serde(flatten)]
+    pub(super) sent: SentEvent,
+}
+
+#[derive(Deserialize)]
+pub(super) struct RenegotiateCapabilitiesRequest {
+    pub(super) capabilities: Capabilities,
 }
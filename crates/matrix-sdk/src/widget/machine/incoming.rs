@@ -13,13 +13,15 @@
 // limitations under the License.
 
 use ruma::{
-    api::client::account::request_openid_token, events::AnyTimelineEvent, serde::Raw, OwnedEventId,
+    api::client::{account::request_openid_token, to_device::send_event_to_device},
+    events::AnyTimelineEvent,
+    serde::Raw,
 };
 use serde::{de, Deserialize, Deserializer};
 use serde_json::value::RawValue as RawJsonValue;
 use uuid::Uuid;
 
-use super::{from_widget::FromWidgetRequest, to_widget::ToWidgetResponse};
+use super::{driver_req::SentEvent, from_widget::FromWidgetRequest, to_widget::ToWidgetResponse};
 use crate::widget::Capabilities;
 
 /// Incoming event that the client API must process.
@@ -54,9 +56,16 @@ pub(crate) enum MatrixDriverResponse {
     /// Client read some matrix event(s).
     /// A response to an `Action::ReadMatrixEvent` commands.
     MatrixEventRead(Vec<Raw<AnyTimelineEvent>>),
-    /// Client sent some matrix event. The response contains the event ID.
+    /// Client sent some matrix event. The response contains the event ID, or
+    /// the delay ID for a delayed event.
     /// A response to an `Action::SendMatrixEvent` command.
-    MatrixEventSent(OwnedEventId),
+    MatrixEventSent(SentEvent),
+    /// Client sent some to-device messages.
+    /// A response to an `Action::SendToDevice` command.
+    ToDeviceSent(send_event_to_device::v3::Response),
+    /// Client updated a delayed event.
+    /// A response to an `Action::UpdateDelayedEvent` command.
+    DelayedEventUpdated,
 }
 
 pub(super) struct IncomingWidgetMessage {
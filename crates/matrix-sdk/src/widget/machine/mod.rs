@@ -19,6 +19,7 @@
 use std::{fmt, iter, time::Duration};
 
 use indexmap::IndexMap;
+use matrix_sdk_common::time::SharedClock;
 use ruma::{
     serde::{JsonObject, Raw},
     OwnedRoomId,
@@ -35,7 +36,7 @@ use self::{
     },
     from_widget::{
         FromWidgetErrorResponse, FromWidgetRequest, ReadEventRequest, ReadEventResponse,
-        SendEventResponse, SupportedApiVersionsResponse,
+        RenegotiateCapabilitiesRequest, SendEventResponse, SupportedApiVersionsResponse,
     },
     incoming::{IncomingWidgetMessage, IncomingWidgetMessageKind},
     openid::{OpenIdResponse, OpenIdState},
@@ -63,7 +64,10 @@ mod tests;
 mod to_widget;
 
 pub(crate) use self::{
-    driver_req::{MatrixDriverRequestData, ReadStateEventRequest, SendEventRequest},
+    driver_req::{
+        MatrixDriverRequestData, ReadStateEventRequest, SendEventRequest, SendToDeviceRequest,
+        SentEvent, UpdateDelayedEventRequest,
+    },
     incoming::{IncomingMessage, MatrixDriverResponse},
 };
 
@@ -119,6 +123,7 @@ impl WidgetMachine {
         room_id: OwnedRoomId,
         init_on_content_load: bool,
         limits: Option<RequestLimits>,
+        clock: SharedClock,
     ) -> (Self, Vec<Action>) {
         let limits = limits.unwrap_or_else(|| RequestLimits {
             max_pending_requests: 15,
@@ -128,8 +133,8 @@ impl WidgetMachine {
         let mut machine = Self {
             widget_id,
             room_id,
-            pending_to_widget_requests: PendingRequests::new(limits.clone()),
-            pending_matrix_driver_requests: PendingRequests::new(limits),
+            pending_to_widget_requests: PendingRequests::new(limits.clone(), clock.clone()),
+            pending_matrix_driver_requests: PendingRequests::new(limits, clock),
             capabilities: CapabilitiesState::Unset,
         };
 
@@ -226,6 +231,20 @@ impl WidgetMachine {
                 .map(|a| vec![a])
                 .unwrap_or_default(),
 
+            FromWidgetRequest::SendToDevice(req) => self
+                .process_send_to_device_request(req, raw_request)
+                .map(|a| vec![a])
+                .unwrap_or_default(),
+
+            FromWidgetRequest::RenegotiateCapabilities(req) => {
+                self.process_renegotiate_capabilities_request(req, raw_request)
+            }
+
+            FromWidgetRequest::UpdateDelayedEvent(req) => self
+                .process_update_delayed_event_request(req, raw_request)
+                .map(|a| vec![a])
+                .unwrap_or_default(),
+
             FromWidgetRequest::GetOpenId {} => {
                 let (request, request_action) = self.send_matrix_driver_request(RequestOpenId);
                 request.then(|res, machine| {
@@ -342,15 +361,87 @@ impl WidgetMachine {
             return Some(self.send_from_widget_error_response(raw_request, "Not allowed"));
         }
 
+        let is_delayed = request.delay.is_some() || request.parent_delay_id.is_some();
+        if is_delayed && !capabilities.send_delayed_event {
+            return Some(self.send_from_widget_error_response(raw_request, "Not allowed"));
+        }
+
         let (request, action) = self.send_matrix_driver_request(request);
         request.then(|result, machine| {
             let room_id = &machine.room_id;
-            let response = result.map(|event_id| SendEventResponse { event_id, room_id });
+            let response = result.map(|sent| SendEventResponse { room_id, sent });
+            vec![machine.send_from_widget_result_response(raw_request, response)]
+        });
+        action
+    }
+
+    fn process_send_to_device_request(
+        &mut self,
+        request: SendToDeviceRequest,
+        raw_request: Raw<FromWidgetRequest>,
+    ) -> Option<Action> {
+        let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+            error!("Received send to-device request before capabilities negotiation");
+            return None;
+        };
+
+        if !capabilities.send_to_device.contains(&request.event_type) {
+            return Some(self.send_from_widget_error_response(raw_request, "Not allowed"));
+        }
+
+        if request.encrypted {
+            let text = "Sending encrypted to-device messages is not supported";
+            return Some(self.send_from_widget_error_response(raw_request, text));
+        }
+
+        let (request, action) = self.send_matrix_driver_request(request);
+        request.then(|result, machine| {
+            let response = result.map(|_| JsonObject::new());
+            vec![machine.send_from_widget_result_response(raw_request, response)]
+        });
+        action
+    }
+
+    fn process_update_delayed_event_request(
+        &mut self,
+        request: UpdateDelayedEventRequest,
+        raw_request: Raw<FromWidgetRequest>,
+    ) -> Option<Action> {
+        let CapabilitiesState::Negotiated(capabilities) = &self.capabilities else {
+            error!("Received update delayed event request before capabilities negotiation");
+            return None;
+        };
+
+        if !capabilities.update_delayed_event {
+            return Some(self.send_from_widget_error_response(raw_request, "Not allowed"));
+        }
+
+        let (request, action) = self.send_matrix_driver_request(request);
+        request.then(|result, machine| {
+            let response = result.map(|_| JsonObject::new());
             vec![machine.send_from_widget_result_response(raw_request, response)]
         });
         action
     }
 
+    fn process_renegotiate_capabilities_request(
+        &mut self,
+        request: RenegotiateCapabilitiesRequest,
+        raw_request: Raw<FromWidgetRequest>,
+    ) -> Vec<Action> {
+        let CapabilitiesState::Negotiated(current) = &self.capabilities else {
+            let text = "Received capabilities renegotiation before initial negotiation";
+            return vec![self.send_from_widget_error_response(raw_request, text)];
+        };
+
+        // The widget asks for capabilities in addition to the ones it already has.
+        let subscribed = !current.read.is_empty();
+        let requested = current.iter().chain(request.capabilities.iter()).collect();
+
+        let response = self.send_from_widget_response(raw_request, JsonObject::new());
+        iter::once(response).chain(self.acquire_capabilities(requested, subscribed)).collect()
+    }
+
     #[instrument(skip_all, fields(?request_id))]
     fn process_to_widget_response(
         &mut self,
@@ -492,31 +583,42 @@ impl WidgetMachine {
         self.capabilities = CapabilitiesState::Negotiating;
 
         let (request, action) = self.send_to_widget_request(RequestCapabilities {});
-        request.then(|response, machine| {
-            let requested = response.capabilities;
-            let (request, action) = machine.send_matrix_driver_request(AcquireCapabilities {
-                desired_capabilities: requested.clone(),
-            });
-
-            request.then(|result, machine| {
-                let approved = result.unwrap_or_else(|e| {
-                    error!("Acquiring capabilities failed: {e}");
-                    Capabilities::default()
-                });
+        request
+            .then(|response, machine| machine.acquire_capabilities(response.capabilities, false));
 
-                let subscribe_required = !approved.read.is_empty();
-                machine.capabilities = CapabilitiesState::Negotiated(approved.clone());
+        unsubscribe_required.then(|| Action::Unsubscribe).into_iter().chain(action).collect()
+    }
 
-                let update = NotifyCapabilitiesChanged { approved, requested };
-                let (_request, action) = machine.send_to_widget_request(update);
+    /// Acquire the requested capabilities from the matrix driver and notify the
+    /// widget of the approved ones.
+    ///
+    /// `subscribed` is whether the machine is currently subscribed to the
+    /// events of the room.
+    fn acquire_capabilities(&mut self, requested: Capabilities, subscribed: bool) -> Vec<Action> {
+        let (request, action) = self.send_matrix_driver_request(AcquireCapabilities {
+            desired_capabilities: requested.clone(),
+        });
 
-                (subscribe_required).then(|| Action::Subscribe).into_iter().chain(action).collect()
+        request.then(move |result, machine| {
+            let approved = result.unwrap_or_else(|e| {
+                error!("Acquiring capabilities failed: {e}");
+                Capabilities::default()
             });
 
-            action.map(|a| vec![a]).unwrap_or_default()
+            let subscription = match (subscribed, !approved.read.is_empty()) {
+                (false, true) => Some(Action::Subscribe),
+                (true, false) => Some(Action::Unsubscribe),
+                _ => None,
+            };
+            machine.capabilities = CapabilitiesState::Negotiated(approved.clone());
+
+            let update = NotifyCapabilitiesChanged { approved, requested };
+            let (_request, action) = machine.send_to_widget_request(update);
+
+            subscription.into_iter().chain(action).collect()
         });
 
-        unsubscribe_required.then(|| Action::Unsubscribe).into_iter().chain(action).collect()
+        action.map(|a| vec![a]).unwrap_or_default()
     }
 }
 
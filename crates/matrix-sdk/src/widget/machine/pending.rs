@@ -15,9 +15,13 @@
 //! A wrapper around a hash map that tracks pending requests and makes sure
 //! that expired requests are removed.
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use indexmap::{map::Entry, IndexMap};
+use matrix_sdk_common::{
+    instant::Instant,
+    time::{Clock, SharedClock},
+};
 use tracing::warn;
 use uuid::Uuid;
 
@@ -43,11 +47,12 @@ pub(crate) struct RequestLimits {
 pub(super) struct PendingRequests<T> {
     requests: IndexMap<Uuid, Expirable<T>>,
     limits: RequestLimits,
+    clock: SharedClock,
 }
 
 impl<T> PendingRequests<T> {
-    pub(super) fn new(limits: RequestLimits) -> Self {
-        Self { requests: IndexMap::with_capacity(limits.max_pending_requests), limits }
+    pub(super) fn new(limits: RequestLimits, clock: SharedClock) -> Self {
+        Self { requests: IndexMap::with_capacity(limits.max_pending_requests), limits, clock }
     }
 
     /// Inserts a new request into the map.
@@ -62,7 +67,7 @@ impl<T> PendingRequests<T> {
             panic!("uuid collision");
         };
 
-        let expirable = Expirable::new(value, Instant::now() + self.limits.response_timeout);
+        let expirable = Expirable::new(value, self.clock.now() + self.limits.response_timeout);
         let inserted = entry.insert(expirable);
         Some(&mut inserted.value)
     }
@@ -72,13 +77,14 @@ impl<T> PendingRequests<T> {
     /// Returns `None` if the value is not present or expired.
     pub(super) fn extract(&mut self, key: &Uuid) -> Result<T, &'static str> {
         let value = self.requests.remove(key).ok_or("Received response for an unknown request")?;
-        value.value().ok_or("Dropping response for an expired request")
+        value.value(self.clock.now()).ok_or("Dropping response for an expired request")
     }
 
     /// Removes all expired requests from the map.
     pub(super) fn remove_expired(&mut self) {
+        let now = self.clock.now();
         self.requests.retain(|id, req| {
-            let expired = req.expired();
+            let expired = req.expired(now);
             if expired {
                 warn!(?id, "Dropping response for an expired request");
             }
@@ -97,12 +103,12 @@ impl<T> Expirable<T> {
         Self { value, expires_at }
     }
 
-    fn value(self) -> Option<T> {
-        (!self.expired()).then_some(self.value)
+    fn value(self, now: Instant) -> Option<T> {
+        (!self.expired(now)).then_some(self.value)
     }
 
-    fn expired(&self) -> bool {
-        Instant::now() >= self.expires_at
+    fn expired(&self, now: Instant) -> bool {
+        now >= self.expires_at
     }
 }
 
@@ -110,6 +116,7 @@ impl<T> Expirable<T> {
 mod tests {
     use std::time::Duration;
 
+    use matrix_sdk_common::time::SharedClock;
     use uuid::Uuid;
 
     use super::{PendingRequests, RequestLimits};
@@ -118,10 +125,10 @@ mod tests {
 
     #[test]
     fn insertion_limits_for_pending_requests_work() {
-        let mut pending: PendingRequests<Dummy> = PendingRequests::new(RequestLimits {
-            max_pending_requests: 1,
-            response_timeout: Duration::from_secs(10),
-        });
+        let mut pending: PendingRequests<Dummy> = PendingRequests::new(
+            RequestLimits { max_pending_requests: 1, response_timeout: Duration::from_secs(10) },
+            SharedClock::default(),
+        );
 
         // First insert is ok.
         let first = Uuid::new_v4();
@@ -147,10 +154,10 @@ mod tests {
 
     #[test]
     fn time_limits_for_pending_requests_work() {
-        let mut pending: PendingRequests<Dummy> = PendingRequests::new(RequestLimits {
-            max_pending_requests: 10,
-            response_timeout: Duration::from_secs(1),
-        });
+        let mut pending: PendingRequests<Dummy> = PendingRequests::new(
+            RequestLimits { max_pending_requests: 10, response_timeout: Duration::from_secs(1) },
+            SharedClock::default(),
+        );
 
         // Insert a request, it's fine, limits are high.
         let key = Uuid::new_v4();
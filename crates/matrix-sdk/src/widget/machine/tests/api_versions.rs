@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use assert_matches2::assert_let;
+use matrix_sdk_common::time::SharedClock;
 use ruma::owned_room_id;
 use serde_json::{json, Value as JsonValue};
 
@@ -26,6 +27,7 @@ fn get_supported_api_versions() {
         owned_room_id!("!a98sd12bjh:example.org"),
         true,
         None,
+        SharedClock::default(),
     );
 
     let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
@@ -53,7 +55,9 @@ fn get_supported_api_versions() {
                     "0.0.2",
                     "org.matrix.msc2762",
                     "org.matrix.msc2871",
+                    "org.matrix.msc2974",
                     "org.matrix.msc3819",
+                    "org.matrix.msc4157",
                 ]
             },
         }),
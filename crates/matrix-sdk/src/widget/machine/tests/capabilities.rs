@@ -14,6 +14,7 @@
 
 use assert_matches::assert_matches;
 use assert_matches2::assert_let;
+use matrix_sdk_common::time::SharedClock;
 use ruma::owned_room_id;
 use serde_json::{from_value, json};
 
@@ -25,14 +26,16 @@ use crate::widget::machine::{
 #[test]
 fn machine_can_negotiate_capabilities_immediately() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(&mut machine, actions, None);
 }
 
 #[test]
 fn machine_can_request_capabilities_on_content_load() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, true, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, true, None, SharedClock::default());
     assert!(actions.is_empty());
 
     // Content loaded event processed.
@@ -69,7 +72,8 @@ fn machine_can_request_capabilities_on_content_load() {
 #[test]
 fn capabilities_failure_results_into_empty_capabilities() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
 
     // Ask widget to provide desired capabilities.
     let actions = {
@@ -136,6 +140,67 @@ fn capabilities_failure_results_into_empty_capabilities() {
     );
 }
 
+#[test]
+fn capabilities_can_be_renegotiated() {
+    let room_id = owned_room_id!("!a98sd12bjh:example.org");
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
+    assert_capabilities_dance(&mut machine, actions, None);
+
+    let read_member = "org.matrix.msc2762.receive.state_event:m.room.member";
+    let send_delayed = "org.matrix.msc4157.send.delayed_event";
+
+    // The widget asks for an additional capability.
+    let mut actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "renegotiate-request-id",
+        "action": "org.matrix.msc2974.request_capabilities",
+        "data": {
+            "capabilities": [send_delayed],
+        },
+    })));
+
+    let action = actions.remove(0);
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, request_id) = parse_msg(&msg);
+    assert_eq!(request_id, "renegotiate-request-id");
+    assert_eq!(msg["response"], json!({}));
+
+    // The previously approved capabilities are requested along with the new one.
+    let actions = {
+        let [action]: [Action; 1] = actions.try_into().unwrap();
+        assert_let!(
+            Action::MatrixDriverRequest {
+                request_id,
+                data: MatrixDriverRequestData::AcquireCapabilities(data)
+            } = action
+        );
+        let capabilities = data.desired_capabilities;
+        assert_eq!(capabilities, from_value(json!([read_member, send_delayed])).unwrap());
+
+        let response = Ok(MatrixDriverResponse::CapabilitiesAcquired(capabilities));
+        machine.process(IncomingMessage::MatrixDriverResponse { request_id, response })
+    };
+
+    // The machine is already subscribed, so it only notifies the widget.
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, _request_id) = parse_msg(&msg);
+    assert_eq!(
+        msg,
+        json!({
+            "api": "toWidget",
+            "widgetId": WIDGET_ID,
+            "action": "notify_capabilities",
+            "data": {
+                "requested": [read_member, send_delayed],
+                "approved": [read_member, send_delayed],
+            },
+        }),
+    );
+}
+
 /// Performs a capability "dance", if no capability is specified, we assume that
 /// it's: `org.matrix.msc2762.receive.state_event:m.room.member`.
 pub(super) fn assert_capabilities_dance(
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use assert_matches2::assert_let;
+use matrix_sdk_common::time::SharedClock;
 use ruma::owned_room_id;
 use serde_json::json;
 
@@ -22,7 +23,8 @@ use crate::widget::machine::{Action, IncomingMessage, WidgetMachine};
 #[test]
 fn machine_sends_error_for_unknown_request() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, _) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, true, None);
+    let (mut machine, _) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, true, None, SharedClock::default());
 
     let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
         "api": "fromWidget",
@@ -52,6 +54,7 @@ fn read_messages_without_capabilities() {
         owned_room_id!("!a98sd12bjh:example.org"),
         true,
         None,
+        SharedClock::default(),
     );
 
     let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
@@ -79,7 +82,8 @@ fn read_messages_without_capabilities() {
 #[test]
 fn read_request_for_non_allowed_message_like_events() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(&mut machine, actions, None);
 
     let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
@@ -104,7 +108,8 @@ fn read_request_for_non_allowed_message_like_events() {
 #[test]
 fn read_request_for_non_allowed_state_events() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(&mut machine, actions, None);
 
     let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
@@ -130,7 +135,8 @@ fn read_request_for_non_allowed_state_events() {
 #[test]
 fn send_request_for_non_allowed_state_events() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(
         &mut machine,
         actions,
@@ -162,7 +168,8 @@ fn send_request_for_non_allowed_state_events() {
 #[test]
 fn send_request_for_non_allowed_message_like_events() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(
         &mut machine,
         actions,
@@ -194,7 +201,8 @@ fn send_request_for_non_allowed_message_like_events() {
 #[test]
 fn read_request_for_message_like_with_disallowed_msg_type_fails() {
     let room_id = owned_room_id!("!a98sd12bjh:example.org");
-    let (mut machine, actions) = WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None);
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
     assert_capabilities_dance(
         &mut machine,
         actions,
@@ -220,3 +228,34 @@ fn read_request_for_message_like_with_disallowed_msg_type_fails() {
     assert_eq!(msg["action"], "org.matrix.msc2876.read_events");
     assert_eq!(msg["response"]["error"]["message"].as_str().unwrap(), "Not allowed");
 }
+
+#[test]
+fn send_request_for_non_allowed_delayed_event() {
+    let room_id = owned_room_id!("!a98sd12bjh:example.org");
+    let (mut machine, actions) =
+        WidgetMachine::new(WIDGET_ID.to_owned(), room_id, false, None, SharedClock::default());
+    assert_capabilities_dance(
+        &mut machine,
+        actions,
+        Some("org.matrix.msc2762.send.event:org.matrix.rageshake_request"),
+    );
+
+    let actions = machine.process(IncomingMessage::WidgetMessage(json_string!({
+        "api": "fromWidget",
+        "widgetId": WIDGET_ID,
+        "requestId": "send-me-a-delayed-message",
+        "action": "send_event",
+        "data": {
+            "type": "org.matrix.rageshake_request",
+            "content": {},
+            "delay": 10000,
+        },
+    })));
+
+    let [action]: [Action; 1] = actions.try_into().unwrap();
+    assert_let!(Action::SendToWidget(msg) = action);
+    let (msg, request_id) = parse_msg(&msg);
+    assert_eq!(request_id, "send-me-a-delayed-message");
+    assert_eq!(msg["action"], "send_event");
+    assert_eq!(msg["response"]["error"]["message"].as_str().unwrap(), "Not allowed");
+}
@@ -15,6 +15,7 @@
 use std::time::Duration;
 
 use assert_matches2::assert_let;
+use matrix_sdk_common::time::SharedClock;
 use ruma::{
     api::client::account::request_openid_token, authentication::TokenType, owned_room_id,
     ServerName,
@@ -33,6 +34,7 @@ fn openid_request_handling_works() {
         owned_room_id!("!a98sd12bjh:example.org"),
         true,
         None,
+        SharedClock::default(),
     );
 
     // Widget requests an open ID token, since we don't have any caching yet,
@@ -118,6 +120,7 @@ fn openid_fail_results_in_response_blocked() {
         owned_room_id!("!a98sd12bjh:example.org"),
         true,
         None,
+        SharedClock::default(),
     );
 
     // Widget requests an open ID token, since we don't have any caching yet,
@@ -22,22 +22,26 @@ use ruma::{
     api::client::{
         account::request_openid_token::v3::{Request as OpenIdRequest, Response as OpenIdResponse},
         filter::RoomEventFilter,
+        to_device::send_event_to_device,
     },
     assign,
     events::{
-        AnySyncTimelineEvent, AnyTimelineEvent, MessageLikeEventType, StateEventType,
-        TimelineEventType,
+        AnySyncTimelineEvent, AnyTimelineEvent, AnyToDeviceEventContent, MessageLikeEventType,
+        StateEventType, TimelineEventType, ToDeviceEventType,
     },
     serde::Raw,
-    OwnedEventId, RoomId,
+    to_device::DeviceIdOrAllDevices,
+    OwnedUserId, RoomId, TransactionId,
 };
 use serde_json::value::RawValue as RawJsonValue;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use tracing::error;
 
-use super::StateKeySelector;
+use super::{machine::SentEvent, StateKeySelector};
 use crate::{
-    event_handler::EventHandlerDropGuard, room::MessagesOptions, HttpResult, Result, Room,
+    event_handler::EventHandlerDropGuard,
+    room::{delayed_events, MessagesOptions, UpdateDelayedEventAction},
+    HttpResult, Result, Room,
 };
 
 /// Thin wrapper around a [`Room`] that provides functionality relevant for
@@ -107,17 +111,80 @@ impl MatrixDriver {
     }
 
     /// Sends a given `event` to the room.
+    ///
+    /// If a `delay` or a `parent_delay_id` is given, the event is sent as a
+    /// delayed event.
     pub(crate) async fn send(
         &self,
         event_type: TimelineEventType,
         state_key: Option<String>,
         content: Box<RawJsonValue>,
-    ) -> Result<OwnedEventId> {
+        delay: Option<u64>,
+        parent_delay_id: Option<String>,
+    ) -> Result<SentEvent> {
         let type_str = event_type.to_string();
-        Ok(match state_key {
-            Some(key) => self.room.send_state_event_raw(&type_str, &key, content).await?.event_id,
-            None => self.room.send_raw(&type_str, content).await?.event_id,
-        })
+
+        if delay.is_none() && parent_delay_id.is_none() {
+            let event_id = match state_key {
+                Some(key) => {
+                    self.room.send_state_event_raw(&type_str, &key, content).await?.event_id
+                }
+                None => self.room.send_raw(&type_str, content).await?.event_id,
+            };
+            return Ok(SentEvent { event_id: Some(event_id), delay_id: None });
+        }
+
+        let room_id = self.room.room_id().to_owned();
+        let body = Raw::from_json(content);
+        let delay_id = match state_key {
+            Some(state_key) => {
+                let request = delayed_events::send_state_event::Request {
+                    room_id,
+                    event_type: type_str,
+                    state_key,
+                    delay,
+                    parent_delay_id,
+                    body,
+                };
+                self.room.client.send(request, None).await?.delay_id
+            }
+            None => {
+                let request = delayed_events::send_message_event::Request {
+                    room_id,
+                    event_type: type_str,
+                    txn_id: TransactionId::new(),
+                    delay,
+                    parent_delay_id,
+                    body,
+                };
+                self.room.client.send(request, None).await?.delay_id
+            }
+        };
+
+        Ok(SentEvent { event_id: None, delay_id: Some(delay_id) })
+    }
+
+    /// Sends unencrypted to-device messages of the given type.
+    pub(crate) async fn send_to_device(
+        &self,
+        event_type: ToDeviceEventType,
+        messages: BTreeMap<
+            OwnedUserId,
+            BTreeMap<DeviceIdOrAllDevices, Raw<AnyToDeviceEventContent>>,
+        >,
+    ) -> HttpResult<send_event_to_device::v3::Response> {
+        let request =
+            send_event_to_device::v3::Request::new_raw(event_type, TransactionId::new(), messages);
+        self.room.client.send(request, None).await
+    }
+
+    /// Cancels, restarts or sends the delayed event with the given ID.
+    pub(crate) async fn update_delayed_event(
+        &self,
+        delay_id: String,
+        action: UpdateDelayedEventAction,
+    ) -> Result<()> {
+        self.room.update_delayed_event(&delay_id, action).await
     }
 
     /// Starts forwarding new room events. Once the returned `EventReceiver`
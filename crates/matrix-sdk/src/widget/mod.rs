@@ -22,13 +22,14 @@ use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use tokio_util::sync::{CancellationToken, DropGuard};
 
 use self::{
+    capabilities::acquire_remembered_capabilities,
     machine::{
         Action, IncomingMessage, MatrixDriverRequestData, MatrixDriverResponse, SendEventRequest,
-        WidgetMachine,
+        SendToDeviceRequest, UpdateDelayedEventRequest, WidgetMachine,
     },
     matrix::MatrixDriver,
 };
-use crate::{room::Room, Result};
+use crate::{room::Room, Client, Result};
 
 mod capabilities;
 mod filter;
@@ -37,10 +38,12 @@ mod matrix;
 mod settings;
 
 pub use self::{
-    capabilities::{Capabilities, CapabilitiesProvider},
+    capabilities::{forget_capabilities, Capabilities, CapabilitiesProvider, Capability},
     filter::{EventFilter, MessageLikeEventFilter, StateEventFilter},
     settings::{
-        ClientProperties, EncryptionSystem, VirtualElementCallWidgetOptions, WidgetSettings,
+        element_call_capabilities, ClientProperties, ElementCallOptions, ElementCallWidget,
+        EncryptionSystem, JitsiWidgetData, RoomWidget, VirtualElementCallWidgetOptions,
+        WidgetEventContent, WidgetSettings, LEGACY_WIDGET_EVENT_TYPE, WIDGET_EVENT_TYPE,
     },
 };
 
@@ -143,10 +146,13 @@ impl WidgetDriver {
             room.room_id().to_owned(),
             self.settings.init_on_content_load(),
             None,
+            room.client().clock().clone(),
         );
 
         // The environment for the processing of actions from the widget machine.
         let mut ctx = ProcessingContext {
+            widget_id: self.settings.widget_id().to_owned(),
+            client: room.client(),
             widget_machine: client_api,
             matrix_driver: MatrixDriver::new(room.clone()),
             event_forwarding_guard: None,
@@ -171,6 +177,8 @@ impl WidgetDriver {
 
 /// A small wrapper of all the data that we need to process an incoming event.
 struct ProcessingContext<T> {
+    widget_id: String,
+    client: Client,
     widget_machine: WidgetMachine,
     matrix_driver: MatrixDriver,
     event_forwarding_guard: Option<DropGuard>,
@@ -196,10 +204,13 @@ impl<T: CapabilitiesProvider> ProcessingContext<T> {
             Action::MatrixDriverRequest { request_id, data } => {
                 let response = match data {
                     MatrixDriverRequestData::AcquireCapabilities(cmd) => {
-                        let obtained = self
-                            .capabilities_provider
-                            .acquire_capabilities(cmd.desired_capabilities)
-                            .await;
+                        let obtained = acquire_remembered_capabilities(
+                            &self.client,
+                            &self.widget_id,
+                            &self.capabilities_provider,
+                            cmd.desired_capabilities,
+                        )
+                        .await;
                         Ok(MatrixDriverResponse::CapabilitiesAcquired(obtained))
                     }
 
@@ -225,13 +236,37 @@ impl<T: CapabilitiesProvider> ProcessingContext<T> {
                         .map_err(|e| e.to_string()),
 
                     MatrixDriverRequestData::SendMatrixEvent(req) => {
-                        let SendEventRequest { event_type, state_key, content } = req;
+                        let SendEventRequest {
+                            event_type,
+                            state_key,
+                            content,
+                            delay,
+                            parent_delay_id,
+                        } = req;
                         self.matrix_driver
-                            .send(event_type, state_key, content)
+                            .send(event_type, state_key, content, delay, parent_delay_id)
                             .await
                             .map(MatrixDriverResponse::MatrixEventSent)
                             .map_err(|e| e.to_string())
                     }
+
+                    MatrixDriverRequestData::SendToDevice(req) => {
+                        let SendToDeviceRequest { event_type, messages, .. } = req;
+                        self.matrix_driver
+                            .send_to_device(event_type, messages)
+                            .await
+                            .map(MatrixDriverResponse::ToDeviceSent)
+                            .map_err(|e| e.to_string())
+                    }
+
+                    MatrixDriverRequestData::UpdateDelayedEvent(req) => {
+                        let UpdateDelayedEventRequest { action, delay_id } = req;
+                        self.matrix_driver
+                            .update_delayed_event(delay_id, action)
+                            .await
+                            .map(|_| MatrixDriverResponse::DelayedEventUpdated)
+                            .map_err(|e| e.to_string())
+                    }
                 };
 
                 self.events_tx
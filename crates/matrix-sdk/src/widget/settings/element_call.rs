@@ -19,10 +19,20 @@
 // TODO: The goal is to have not any Element Call specific code
 // in the rust sdk. Find a better solution for this.
 
+use async_trait::async_trait;
+use ruma::events::StateEventType;
 use serde::Serialize;
+use tracing::warn;
 use url::Url;
 
-use super::{url_params, WidgetSettings};
+use super::{url_params, ClientProperties, WidgetSettings};
+use crate::{
+    widget::{
+        Capabilities, CapabilitiesProvider, EventFilter, MessageLikeEventFilter, StateEventFilter,
+        WidgetDriver, WidgetDriverHandle,
+    },
+    Room,
+};
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -141,8 +151,8 @@ pub struct VirtualElementCallWidgetOptions {
 }
 
 impl WidgetSettings {
-    /// `WidgetSettings` are usually created from a state event.
-    /// (currently unimplemented)
+    /// `WidgetSettings` are usually created from a state event, with
+    /// [`RoomWidget::settings`](super::RoomWidget::settings).
     ///
     /// In some cases the client wants to create custom `WidgetSettings`
     /// for specific rooms based on other conditions.
@@ -202,6 +212,187 @@ impl WidgetSettings {
     }
 }
 
+/// The capabilities that Element Call needs to work.
+pub fn element_call_capabilities() -> Capabilities {
+    let rageshake_request = || {
+        EventFilter::MessageLike(MessageLikeEventFilter::WithType(
+            "org.matrix.rageshake_request".into(),
+        ))
+    };
+    let encryption_keys = || {
+        EventFilter::MessageLike(MessageLikeEventFilter::WithType(
+            "io.element.call.encryption_keys".into(),
+        ))
+    };
+
+    Capabilities {
+        read: vec![
+            EventFilter::State(StateEventFilter::WithType(StateEventType::CallMember)),
+            EventFilter::State(StateEventFilter::WithType(StateEventType::RoomMember)),
+            rageshake_request(),
+            encryption_keys(),
+        ],
+        send: vec![
+            EventFilter::State(StateEventFilter::WithType(StateEventType::CallMember)),
+            rageshake_request(),
+            encryption_keys(),
+        ],
+        requires_client: true,
+        send_to_device: vec!["io.element.call.encryption_keys".into()],
+        send_delayed_event: true,
+        update_delayed_event: true,
+    }
+}
+
+/// Grants the capabilities of [`element_call_capabilities`], without prompting
+/// the user.
+struct ElementCallCapabilitiesProvider;
+
+#[async_trait]
+impl CapabilitiesProvider for ElementCallCapabilitiesProvider {
+    async fn acquire_capabilities(&self, _capabilities: Capabilities) -> Capabilities {
+        element_call_capabilities()
+    }
+}
+
+/// Options to embed Element Call in a room with [`ElementCallWidget::new`].
+#[derive(Debug)]
+pub struct ElementCallOptions {
+    /// The url to the app.
+    ///
+    /// E.g. <https://call.element.io>, <https://call.element.dev>
+    pub element_call_url: String,
+
+    /// The widget id.
+    pub widget_id: String,
+
+    /// The url that is used as the target for the PostMessages sent
+    /// by the widget (to the client).
+    ///
+    /// Defaults to `element_call_url` for the non-iframe (dedicated webview)
+    /// usecase.
+    pub parent_url: Option<String>,
+
+    /// The font scale which will be used inside element call.
+    ///
+    /// Default: `1`
+    pub font_scale: Option<f64>,
+
+    /// The font to use, to adapt to the system font.
+    pub font: Option<String>,
+
+    /// Don't show the lobby and join the call immediately.
+    ///
+    /// Default: `false`
+    pub skip_lobby: Option<bool>,
+
+    /// The PostHog id to pass to element call.
+    ///
+    /// Must be `None` if the user opted out of analytics, so no id is passed
+    /// in the url.
+    pub analytics_id: Option<String>,
+
+    /// The encryption system to use.
+    ///
+    /// Default: `EncryptionSystem::PerParticipantKeys` in encrypted rooms, and
+    /// `EncryptionSystem::Unencrypted` in other rooms.
+    pub encryption: Option<EncryptionSystem>,
+}
+
+/// An Element Call widget for a room, ready to be embedded in a webview or an
+/// IFrame.
+///
+/// The messages between the widget and the client must be forwarded with the
+/// [`WidgetDriverHandle`] of [`ElementCallWidget::handle`], while
+/// [`ElementCallWidget::run`] is running.
+#[derive(Debug)]
+pub struct ElementCallWidget {
+    room: Room,
+    settings: WidgetSettings,
+    url: Url,
+    driver: WidgetDriver,
+    handle: WidgetDriverHandle,
+}
+
+impl ElementCallWidget {
+    /// Generate the settings and the url of an Element Call widget for the
+    /// given room.
+    ///
+    /// # Arguments
+    ///
+    /// * `room` - The room of the call.
+    /// * `options` - The configuration of the widget.
+    /// * `props` - Properties from the client that are passed to the widget,
+    ///   e.g. the language or the theme.
+    pub async fn new(
+        room: &Room,
+        options: ElementCallOptions,
+        props: ClientProperties,
+    ) -> Result<Self, url::ParseError> {
+        let encryption = match options.encryption {
+            Some(encryption) => encryption,
+            None => default_encryption(room).await,
+        };
+
+        let settings =
+            WidgetSettings::new_virtual_element_call_widget(VirtualElementCallWidgetOptions {
+                element_call_url: options.element_call_url,
+                widget_id: options.widget_id,
+                parent_url: options.parent_url,
+                hide_header: None,
+                preload: None,
+                font_scale: options.font_scale,
+                app_prompt: None,
+                skip_lobby: options.skip_lobby,
+                confine_to_room: None,
+                font: options.font,
+                analytics_id: options.analytics_id,
+                encryption,
+            })?;
+        let url = settings.generate_webview_url(room, props).await?;
+        let (driver, handle) = WidgetDriver::new(settings.clone());
+
+        Ok(Self { room: room.clone(), settings, url, driver, handle })
+    }
+
+    /// The settings of the widget.
+    pub fn settings(&self) -> &WidgetSettings {
+        &self.settings
+    }
+
+    /// The url to load in the webview or the IFrame.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The handle to forward the messages between the widget and the client.
+    pub fn handle(&self) -> WidgetDriverHandle {
+        self.handle.clone()
+    }
+
+    /// Run the widget driver, granting the capabilities that Element Call
+    /// needs.
+    ///
+    /// Returns once the widget is disconnected or a terminal error occurs.
+    pub async fn run(self) -> Result<(), ()> {
+        self.driver.run(self.room, ElementCallCapabilitiesProvider).await
+    }
+}
+
+async fn default_encryption(room: &Room) -> EncryptionSystem {
+    let is_encrypted = room.is_encrypted().await.unwrap_or_else(|error| {
+        // Don't risk starting an unencrypted call in an encrypted room.
+        warn!("Couldn't check whether the room is encrypted: {error}");
+        true
+    });
+
+    if is_encrypted {
+        EncryptionSystem::PerParticipantKeys
+    } else {
+        EncryptionSystem::Unencrypted
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeSet;
@@ -19,9 +19,19 @@ use url::Url;
 use crate::Room;
 
 mod element_call;
+mod room_widget;
 mod url_params;
 
-pub use self::element_call::{EncryptionSystem, VirtualElementCallWidgetOptions};
+pub use self::{
+    element_call::{
+        element_call_capabilities, ElementCallOptions, ElementCallWidget, EncryptionSystem,
+        VirtualElementCallWidgetOptions,
+    },
+    room_widget::{
+        JitsiWidgetData, RoomWidget, WidgetEventContent, LEGACY_WIDGET_EVENT_TYPE,
+        WIDGET_EVENT_TYPE,
+    },
+};
 
 /// Settings of the widget.
 #[derive(Debug, Clone)]
@@ -82,9 +92,6 @@ impl WidgetSettings {
     /// * `room` - A matrix room which is used to query the logged in username
     /// * `props` - Properties from the client that can be used by a widget to
     ///   adapt to the client. e.g. language, font-scale...
-    //
-    // TODO: add `From<WidgetStateEvent>`, so that `WidgetSettings` can be built
-    // by using the room state.
     pub async fn generate_webview_url(
         &self,
         room: &Room,
@@ -0,0 +1,253 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use matrix_sdk_base::deserialized_responses::RawAnySyncOrStrippedState;
+use ruma::{
+    serde::{default_true, is_true, JsonObject},
+    OwnedMxcUri, OwnedUserId,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use url::Url;
+
+use super::{url_params, ClientProperties, WidgetSettings};
+use crate::{Result, Room};
+
+/// The type of the state events of the widgets of a room, as defined in
+/// [MSC1236].
+///
+/// [MSC1236]: https://github.com/matrix-org/matrix-spec-proposals/issues/1236
+pub const WIDGET_EVENT_TYPE: &str = "m.widget";
+
+/// The type of the state events of the widgets of a room used by most
+/// clients.
+pub const LEGACY_WIDGET_EVENT_TYPE: &str = "im.vector.modular.widgets";
+
+/// The content of the state event of a widget of a room.
+///
+/// The state key of the event is the ID of the widget. A widget is removed
+/// from the room by sending an event with an empty content.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WidgetEventContent {
+    /// The type of the widget, e.g. `m.jitsi` or `m.custom`.
+    #[serde(rename = "type")]
+    pub widget_type: String,
+
+    /// The URL of the widget.
+    ///
+    /// It can contain template variables, like `$matrix_user_id`, and the
+    /// keys of [`WidgetEventContent::data`] prefixed with `$`.
+    pub url: String,
+
+    /// The name of the widget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The avatar of the widget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<OwnedMxcUri>,
+
+    /// Data specific to the type of the widget.
+    #[serde(default, skip_serializing_if = "JsonObject::is_empty")]
+    pub data: JsonObject,
+
+    /// Whether the client should wait for the widget to be loaded before
+    /// starting the widget API, instead of waiting for the widget to send a
+    /// `content_loaded` action.
+    #[serde(
+        rename = "waitForIframeLoad",
+        default = "default_true",
+        skip_serializing_if = "is_true"
+    )]
+    pub wait_for_iframe_load: bool,
+
+    /// The user who created the widget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator_user_id: Option<OwnedUserId>,
+}
+
+impl WidgetEventContent {
+    /// Create a new `WidgetEventContent` with the given type and URL.
+    pub fn new(widget_type: String, url: String) -> Self {
+        Self {
+            widget_type,
+            url,
+            name: None,
+            avatar_url: None,
+            data: JsonObject::new(),
+            wait_for_iframe_load: true,
+            creator_user_id: None,
+        }
+    }
+
+    /// Whether this is a Jitsi conference widget.
+    pub fn is_jitsi(&self) -> bool {
+        matches!(self.widget_type.as_str(), "m.jitsi" | "jitsi")
+    }
+
+    /// The data of the Jitsi conference, if this is a Jitsi conference widget.
+    pub fn jitsi_data(&self) -> Option<JitsiWidgetData> {
+        if !self.is_jitsi() {
+            return None;
+        }
+
+        serde_json::from_value(JsonValue::Object(self.data.clone())).ok()
+    }
+
+    /// The URL of the widget, with the template variables of its data
+    /// resolved.
+    ///
+    /// The template variables that are provided by the client, like
+    /// `$matrix_user_id`, are kept.
+    pub fn url_with_data(&self) -> Result<Url, url::ParseError> {
+        Url::parse(&url_params::replace_data_properties(&self.url, &self.data))
+    }
+}
+
+/// The data of a Jitsi conference widget.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JitsiWidgetData {
+    /// The domain of the Jitsi server, e.g. `meet.element.io`.
+    pub domain: String,
+
+    /// The ID of the conference on the Jitsi server.
+    pub conference_id: String,
+
+    /// Whether the conference is audio only.
+    #[serde(default)]
+    pub is_audio_only: bool,
+
+    /// The name of the room, to display in the conference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_name: Option<String>,
+
+    /// The kind of authentication required by the Jitsi server, e.g.
+    /// `openidtoken-jwt`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+}
+
+impl JitsiWidgetData {
+    /// The URL of the conference on the Jitsi server, to open it without the
+    /// widget.
+    pub fn conference_url(&self) -> Result<Url, url::ParseError> {
+        Url::parse(&format!("https://{}/", self.domain))?.join(&self.conference_id)
+    }
+}
+
+/// A widget of a room, as defined by its state event.
+#[derive(Clone, Debug)]
+pub struct RoomWidget {
+    /// The ID of the widget.
+    pub id: String,
+    /// The user who sent the state event of the widget.
+    pub sender: OwnedUserId,
+    /// The content of the state event of the widget.
+    pub content: WidgetEventContent,
+}
+
+impl RoomWidget {
+    /// Get the widgets of the given room, ordered by ID.
+    ///
+    /// The state events of both [`WIDGET_EVENT_TYPE`] and
+    /// [`LEGACY_WIDGET_EVENT_TYPE`] are used. If a widget is defined with both
+    /// types, the former wins. Removed widgets and state events that can't be
+    /// parsed are ignored.
+    pub async fn list(room: &Room) -> Result<Vec<Self>> {
+        let mut widgets = BTreeMap::new();
+
+        for event_type in [LEGACY_WIDGET_EVENT_TYPE, WIDGET_EVENT_TYPE] {
+            for raw in room.get_state_events(event_type.into()).await? {
+                if let Some(widget) = Self::from_raw(&raw) {
+                    widgets.insert(widget.id.clone(), widget);
+                }
+            }
+        }
+
+        Ok(widgets.into_values().collect())
+    }
+
+    /// Add a widget to the given room, or replace the widget with the same ID.
+    ///
+    /// The state event is sent with the [`LEGACY_WIDGET_EVENT_TYPE`], which is
+    /// understood by most clients.
+    pub async fn add(room: &Room, widget_id: &str, content: WidgetEventContent) -> Result<()> {
+        let content = serde_json::to_value(content)?;
+        room.send_state_event_raw(LEGACY_WIDGET_EVENT_TYPE, widget_id, content).await?;
+        Ok(())
+    }
+
+    /// Remove the widget with the given ID from the given room.
+    ///
+    /// Does nothing if the room has no such widget.
+    pub async fn remove(room: &Room, widget_id: &str) -> Result<()> {
+        for event_type in [LEGACY_WIDGET_EVENT_TYPE, WIDGET_EVENT_TYPE] {
+            let raw = room.get_state_event(event_type.into(), widget_id).await?;
+
+            if raw.as_ref().and_then(Self::from_raw).is_some() {
+                room.send_state_event_raw(event_type, widget_id, json!({})).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The settings to run this widget with a [`WidgetDriver`].
+    ///
+    /// The template variables of the data of the widget are resolved in the
+    /// raw URL of the settings.
+    ///
+    /// [`WidgetDriver`]: crate::widget::WidgetDriver
+    pub fn settings(&self) -> Result<WidgetSettings, url::ParseError> {
+        let raw_url = self.content.url_with_data()?;
+        WidgetSettings::new(self.id.clone(), !self.content.wait_for_iframe_load, raw_url.as_str())
+    }
+
+    /// The URL to load in the webview or the IFrame of this widget, with all
+    /// the template variables resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `room` - The room of the widget.
+    /// * `props` - Properties from the client that are passed to the widget,
+    ///   e.g. the language or the theme.
+    pub async fn generate_webview_url(
+        &self,
+        room: &Room,
+        props: ClientProperties,
+    ) -> Result<Url, url::ParseError> {
+        self.settings()?.generate_webview_url(room, props).await
+    }
+
+    fn from_raw(raw: &RawAnySyncOrStrippedState) -> Option<Self> {
+        let event = match raw {
+            RawAnySyncOrStrippedState::Sync(raw) => raw.deserialize_as::<WidgetStateEvent>(),
+            RawAnySyncOrStrippedState::Stripped(raw) => raw.deserialize_as::<WidgetStateEvent>(),
+        };
+        let event = event.ok()?;
+
+        Some(Self { id: event.state_key, sender: event.sender, content: event.content })
+    }
+}
+
+/// The parts of a widget state event that are needed for a [`RoomWidget`].
+#[derive(Deserialize)]
+struct WidgetStateEvent {
+    state_key: String,
+    sender: OwnedUserId,
+    content: WidgetEventContent,
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use ruma::serde::JsonObject;
+use serde_json::Value as JsonValue;
 use url::Url;
 use urlencoding::encode;
 
@@ -83,11 +85,37 @@ pub fn replace_properties(url: &mut Url, props: QueryProperties) {
     *url = Url::parse(&result).unwrap();
 }
 
+/// Replace the template variables defined by the `data` of a widget, i.e. its
+/// keys prefixed with `$`, in the given url.
+///
+/// Only string, number and boolean values are used.
+pub fn replace_data_properties(url: &str, data: &JsonObject) -> String {
+    let mut replace_list: Vec<_> = data
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                JsonValue::String(s) => s.clone(),
+                JsonValue::Number(n) => n.to_string(),
+                JsonValue::Bool(b) => b.to_string(),
+                _ => return None,
+            };
+            Some((format!("${key}"), encode(&value).into_owned()))
+        })
+        .collect();
+
+    // Replace the longest variables first, so a variable that is the prefix of
+    // another one doesn't break it.
+    replace_list.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+
+    replace_list.into_iter().fold(url.to_owned(), |url, (old, new)| url.replace(&old, &new))
+}
+
 #[cfg(test)]
 mod tests {
+    use serde_json::json;
     use url::Url;
 
-    use super::{replace_properties, QueryProperties};
+    use super::{replace_data_properties, replace_properties, QueryProperties};
 
     const EXAMPLE_URL: &str = "\
         https://my.widget.org/custom/path/using/$matrix_display_name/in/it\
@@ -141,4 +169,23 @@ mod tests {
         replace_properties(&mut url, get_example_props());
         assert_eq!(url.as_str(), CONVERTED_URL);
     }
+
+    #[test]
+    fn replace_data() {
+        let data = json!({
+            "domain": "meet.example.org",
+            "conferenceId": "Conference Id",
+            "conference": "wrong",
+            "isAudioOnly": true,
+            "nested": { "ignored": true },
+        });
+        let url = "https://example.org/jitsi.html?confId=$conferenceId&domain=$domain\
+            &isAudioOnly=$isAudioOnly&nested=$nested&userId=$matrix_user_id";
+
+        assert_eq!(
+            replace_data_properties(url, data.as_object().unwrap()),
+            "https://example.org/jitsi.html?confId=Conference%20Id&domain=meet.example.org\
+            &isAudioOnly=true&nested=$nested&userId=$matrix_user_id"
+        );
+    }
 }
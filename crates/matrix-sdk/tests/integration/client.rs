@@ -1,41 +1,69 @@
-use std::{collections::BTreeMap, time::Duration};
+use std::{collections::BTreeMap, io::Write, time::Duration};
 
-use assert_matches2::assert_let;
-use futures_util::FutureExt;
+use assert_matches2::{assert_let, assert_matches};
+use eyeball::SharedObservable;
+use futures_util::{pin_mut, FutureExt, StreamExt};
 use matrix_sdk::{
-    config::SyncSettings,
-    media::{MediaFormat, MediaRequest, MediaThumbnailSize},
+    async_trait,
+    config::{RequestConfig, SyncFilterBuilder, SyncSettings},
+    devices::DeviceVerificationState,
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    media::{MediaFormat, MediaRequest, MediaRetentionPolicy, MediaThumbnailSize},
+    message_search::{LocalMessageIndex, MessageSearchOptions, MessageSearchResult, SearchOrder},
+    room::{IncomingCallKind, MessagesOptions},
+    room_creation::PowerLevelsPreset,
     sync::RoomUpdate,
+    uiaa::UiaaHandler,
+    Client, Error, HttpError, RoomCreationError, TransmissionProgress, UnreadCounts,
 };
-use matrix_sdk_base::RoomState;
-use matrix_sdk_test::{async_test, test_json, DEFAULT_TEST_ROOM_ID};
+use matrix_sdk_base::{
+    store::{HomeserverDiscovery, MemoryStore, StoreConfig},
+    RoomState, SessionMeta, StateStore, StateStoreDataKey, StateStoreDataValue,
+};
+use matrix_sdk_test::{
+    async_test, sync_timeline_event, test_json, GlobalAccountDataTestEvent, JoinedRoomBuilder,
+    MockClock, StateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+};
+use matrix_sdk_test_server::MockServerBuilder;
 use ruma::{
-    api::client::{
-        directory::{
-            get_public_rooms,
-            get_public_rooms_filtered::{self, v3::Request as PublicRoomsFilterRequest},
+    api::{
+        client::{
+            account::whoami,
+            directory::{
+                get_public_rooms,
+                get_public_rooms_filtered::{self, v3::Request as PublicRoomsFilterRequest},
+            },
+            discovery::get_supported_versions,
+            media::get_content_thumbnail::v3::Method,
+            uiaa,
         },
-        media::get_content_thumbnail::v3::Method,
-        uiaa,
+        MatrixVersion,
     },
     assign, device_id,
     directory::Filter,
     events::{
         direct::DirectEventContent,
-        room::{message::ImageMessageEventContent, ImageInfo, MediaSource},
+        room::{
+            message::{ImageMessageEventContent, RoomMessageEventContent},
+            ImageInfo, MediaSource,
+        },
         AnyInitialStateEvent,
     },
-    mxc_uri, room_id,
+    mxc_uri,
+    room::RoomType,
+    room_alias_id, room_id,
     serde::Raw,
-    uint, user_id, OwnedUserId,
+    space::SpaceRoomJoinRule,
+    uint, user_id, ClientSecret, MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedUserId, ServerName,
+    SessionId,
 };
 use serde_json::{json, Value as JsonValue};
 use wiremock::{
-    matchers::{header, method, path, path_regex},
-    Mock, Request, ResponseTemplate,
+    matchers::{body_bytes, body_partial_json, header, method, path, path_regex, query_param},
+    Mock, MockServer, Request, ResponseTemplate,
 };
 
-use crate::{logged_in_client, mock_sync, no_retry_test_client};
+use crate::{logged_in_client, mock_sync, no_retry_test_client, test_client_builder};
 
 #[async_test]
 async fn sync() {
@@ -60,7 +88,124 @@ async fn devices() {
         .mount(&server)
         .await;
 
-    client.devices().await.unwrap();
+    let devices = client.devices().await.unwrap();
+
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[0].device_id, "BNYQQWUMXO");
+    assert_eq!(devices[0].display_name.as_deref(), Some("Client 1"));
+    assert_eq!(devices[0].last_seen_ts, Some(MilliSecondsSinceUnixEpoch(uint!(1596117733037))));
+    assert!(!devices[0].is_current);
+    assert_eq!(devices[0].verification_state, DeviceVerificationState::Unknown);
+}
+
+#[derive(Debug)]
+struct PasswordUiaaHandler;
+
+#[async_trait]
+impl UiaaHandler for PasswordUiaaHandler {
+    async fn auth_data(&self, uiaa_info: &uiaa::UiaaInfo) -> Option<uiaa::AuthData> {
+        Some(uiaa::AuthData::Password(assign!(
+            uiaa::Password::new(
+                uiaa::UserIdentifier::UserIdOrLocalpart("example".to_owned()),
+                "wordpass".to_owned(),
+            ), {
+                session: uiaa_info.session.clone(),
+            }
+        )))
+    }
+}
+
+#[async_test]
+async fn delete_devices_with_uiaa() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/delete_devices"))
+        .and(body_partial_json(json!({
+            "auth": {
+                "type": "m.login.password",
+                "password": "wordpass",
+                "session": "vBslorikviAjxzYBASOBGfPp",
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/delete_devices"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.password"] }],
+            "params": {},
+            "session": "vBslorikviAjxzYBASOBGfPp",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let devices = &[device_id!("DEVICEID").to_owned()];
+    client.delete_devices_with_uiaa(devices, &PasswordUiaaHandler).await.unwrap();
+}
+
+#[async_test]
+async fn change_password_with_uiaa() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/password"))
+        .and(body_partial_json(json!({
+            "new_password": "newpass",
+            "auth": { "type": "m.login.password", "session": "abcdef" },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/password"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.password"] }],
+            "params": {},
+            "session": "abcdef",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.account().change_password_with_uiaa("newpass", &PasswordUiaaHandler).await.unwrap();
+}
+
+#[async_test]
+async fn submit_3pid_token() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/identity/v2/validate/email/submitToken"))
+        .and(body_partial_json(json!({
+            "sid": "sid123",
+            "client_secret": "secret",
+            "token": "123456",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "success": true })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let submit_url = format!("{}/_matrix/identity/v2/validate/email/submitToken", server.uri());
+    let client_secret = ClientSecret::parse("secret").unwrap();
+    let success = client
+        .account()
+        .submit_3pid_token(
+            &submit_url,
+            &client_secret,
+            &SessionId::parse("sid123").unwrap(),
+            "123456",
+        )
+        .await
+        .unwrap();
+    assert!(success);
 }
 
 #[async_test]
@@ -211,6 +356,134 @@ async fn join_room_by_id_or_alias() {
     );
 }
 
+#[async_test]
+async fn join_room_with_partial_state() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/join"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "room_id": *DEFAULT_TEST_ROOM_ID,
+            "org.matrix.msc3706.partial_state": true,
+        })))
+        .mount(&server)
+        .await;
+
+    // The homeserver only answers once it knows the full state of the room.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(&*test_json::MEMBERS)
+                .set_delay(Duration::from_millis(100)),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut full_state_rooms = client.subscribe_to_rooms_full_state();
+
+    let room = client.join_room_by_id(&DEFAULT_TEST_ROOM_ID).await.unwrap();
+    assert!(room.is_partial_state());
+
+    let room_id = tokio::time::timeout(Duration::from_secs(1), full_state_rooms.recv())
+        .await
+        .expect("the full state of the room should be received")
+        .unwrap();
+    assert_eq!(room_id, *DEFAULT_TEST_ROOM_ID);
+    assert!(!room.is_partial_state());
+    assert!(room.are_members_synced());
+}
+
+#[async_test]
+async fn knock() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/knock/"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "reason": "Let me in" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::ROOM_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client
+        .knock(
+            (&**DEFAULT_TEST_ROOM_ID).into(),
+            Some("Let me in".to_owned()),
+            vec!["server.com".try_into().unwrap()],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(room.room_id(), *DEFAULT_TEST_ROOM_ID);
+    assert_eq!(room.state(), RoomState::Knocked);
+    assert_eq!(client.knocked_rooms().len(), 1);
+    assert!(client.invited_rooms().is_empty());
+}
+
+#[async_test]
+async fn report_room_and_user() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.10"],
+            "unstable_features": { "org.matrix.msc4260": true },
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/v3/rooms/.*/report$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "reason": "Spam room" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/v3/users/.*spammer:localhost/report$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "reason": "Spammer" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.report_room(&DEFAULT_TEST_ROOM_ID, "Spam room".to_owned()).await.unwrap();
+    client.report_user(user_id!("@spammer:localhost"), "Spammer".to_owned()).await.unwrap();
+}
+
+#[async_test]
+async fn report_user_unsupported() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.10"],
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/v3/users/.*/report$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    // The request is not sent if the homeserver doesn't advertise the feature.
+    assert_matches!(
+        client.report_user(user_id!("@spammer:localhost"), "Spammer".to_owned()).await,
+        Err(Error::UnsupportedFeature(_))
+    );
+}
+
 #[async_test]
 async fn room_search_all() {
     let (client, server) = no_retry_test_client().await;
@@ -347,84 +620,550 @@ async fn get_media_content() {
 }
 
 #[async_test]
-async fn get_media_file() {
+async fn media_cache_retention_policy() {
     let (client, server) = logged_in_client().await;
 
-    let event_content = ImageMessageEventContent::plain(
-        "filename.jpg".into(),
-        mxc_uri!("mxc://example.org/image").to_owned(),
-    )
-    .info(Box::new(assign!(ImageInfo::new(), {
-        height: Some(uint!(398)),
-        width: Some(uint!(394)),
-        mimetype: Some("image/jpeg".into()),
-        size: Some(uint!(31037)),
-    })));
+    let media = client.media();
+    media.set_media_retention_policy(MediaRetentionPolicy::empty().with_max_file_size(Some(10)));
+
+    let small_request = MediaRequest {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/small").to_owned()),
+        format: MediaFormat::File,
+    };
+    let big_request = MediaRequest {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/big").to_owned()),
+        format: MediaFormat::File,
+    };
 
     Mock::given(method("GET"))
-        .and(path("/_matrix/media/r0/download/example.org/image"))
-        .respond_with(ResponseTemplate::new(200).set_body_raw("binaryjpegdata", "image/jpeg"))
-        .named("get_file")
+        .and(path("/_matrix/media/r0/download/localhost/small"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello!"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/media/r0/download/localhost/big"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello, World!"))
+        .expect(2)
         .mount(&server)
         .await;
 
-    client.media().get_file(event_content.clone(), false).await.unwrap();
+    media.prefetch_media_content([small_request.clone(), big_request.clone()]).await;
+
+    // Only the small file is cached.
+    assert_eq!(media.cache_size().await.unwrap(), 6);
+    assert_eq!(media.get_media_content(&small_request, true).await.unwrap(), b"Hello!");
+    assert_eq!(media.get_media_content(&big_request, true).await.unwrap(), b"Hello, World!");
+
+    media.clear_cache().await.unwrap();
+    assert_eq!(media.cache_size().await.unwrap(), 0);
+}
+
+#[async_test]
+async fn get_media_content_with_progress() {
+    let (client, server) = logged_in_client().await;
+
+    let request = MediaRequest {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/textfile").to_owned()),
+        format: MediaFormat::File,
+    };
 
+    let expected_content = "Hello, World!";
     Mock::given(method("GET"))
-        .and(path("/_matrix/media/r0/thumbnail/example.org/image"))
-        .respond_with(
-            ResponseTemplate::new(200).set_body_raw("smallerbinaryjpegdata", "image/jpeg"),
-        )
+        .and(path("/_matrix/media/r0/download/localhost/textfile"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(expected_content))
         .expect(1)
-        .named("get_thumbnail")
         .mount(&server)
         .await;
 
-    client
-        .media()
-        .get_thumbnail(
-            event_content,
-            MediaThumbnailSize { method: Method::Scale, width: uint!(100), height: uint!(100) },
-            false,
-        )
-        .await
-        .unwrap();
+    let progress = SharedObservable::<TransmissionProgress>::default();
+    let _subscriber = progress.subscribe();
+
+    assert_eq!(
+        client
+            .media()
+            .get_media_content_with_progress(&request, false, progress.clone())
+            .await
+            .unwrap(),
+        expected_content.as_bytes()
+    );
+
+    let progress = progress.get();
+    assert_eq!(progress.current, expected_content.len());
+    assert_eq!(progress.total, expected_content.len());
 }
 
 #[async_test]
-async fn whoami() {
+async fn get_media_content_authenticated() {
     let (client, server) = logged_in_client().await;
 
     Mock::given(method("GET"))
-        .and(path("/_matrix/client/r0/account/whoami"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.10", "v1.11"],
+            "unstable_features": { "org.matrix.msc3916.stable": true },
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v1/media/download/localhost/textfile"))
         .and(header("authorization", "Bearer 1234"))
-        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello, World!"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v1/media/thumbnail/localhost/textfile"))
+        .and(query_param("method", "scale"))
+        .and(query_param("width", "100"))
+        .and(query_param("height", "100"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello"))
+        .expect(1)
         .mount(&server)
         .await;
 
-    let user_id = user_id!("@joe:example.org");
+    let media = client.media();
+    assert!(media.supports_authenticated_media().await);
+
+    let source = MediaSource::Plain(mxc_uri!("mxc://localhost/textfile").to_owned());
+    let file_request = MediaRequest { source: source.clone(), format: MediaFormat::File };
+    let thumbnail_request = MediaRequest {
+        source,
+        format: MediaFormat::Thumbnail(MediaThumbnailSize {
+            method: Method::Scale,
+            width: uint!(100),
+            height: uint!(100),
+        }),
+    };
 
-    assert_eq!(client.whoami().await.unwrap().user_id, user_id);
+    assert_eq!(media.get_media_content(&file_request, false).await.unwrap(), b"Hello, World!");
+    assert_eq!(media.get_media_content(&thumbnail_request, false).await.unwrap(), b"Hello");
 }
 
 #[async_test]
-async fn room_update_channel() {
+async fn get_media_content_authenticated_fallback() {
     let (client, server) = logged_in_client().await;
 
-    let mut rx = client.subscribe_to_room_updates(&DEFAULT_TEST_ROOM_ID);
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.10"],
+            "unstable_features": { "org.matrix.msc3916": true },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/unstable/org.matrix.msc3916/media/download/localhost/textfile"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_UNRECOGNIZED",
+            "error": "Unrecognized request",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/media/r0/download/localhost/textfile"))
+        .respond_with(ResponseTemplate::new(200).set_body_string("Hello, World!"))
+        .expect(1)
+        .mount(&server)
+        .await;
 
-    mock_sync(&server, &*test_json::SYNC, None).await;
-    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
-    client.sync_once(sync_settings).await.unwrap();
+    let request = MediaRequest {
+        source: MediaSource::Plain(mxc_uri!("mxc://localhost/textfile").to_owned()),
+        format: MediaFormat::File,
+    };
 
-    let update = rx.recv().now_or_never().unwrap().unwrap();
-    assert_let!(RoomUpdate::Joined { updates, .. } = update);
+    assert_eq!(client.media().get_media_content(&request, false).await.unwrap(), b"Hello, World!");
+}
 
-    assert_eq!(updates.account_data.len(), 1);
-    assert_eq!(updates.ephemeral.len(), 1);
-    assert_eq!(updates.state.len(), 9);
+#[async_test]
+async fn get_url_preview() {
+    let (client, server) = logged_in_client().await;
 
-    assert!(updates.timeline.limited);
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.11"],
+            "unstable_features": { "org.matrix.msc3916.stable": true },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v1/media/preview_url"))
+        .and(query_param("url", "https://matrix.org/"))
+        .and(query_param("ts", "1000"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "og:title": "Matrix.org",
+            "og:description": "An open network for secure, decentralised communication",
+            "og:image": "mxc://localhost/image",
+            "og:image:type": "image/png",
+            "og:image:width": 1200,
+            "og:image:height": 630,
+            "matrix:image:size": 12345,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ts = MilliSecondsSinceUnixEpoch(uint!(1000));
+    let preview = client.get_url_preview("https://matrix.org/", ts).await.unwrap();
+
+    assert_eq!(preview.title.as_deref(), Some("Matrix.org"));
+    assert_eq!(
+        preview.description.as_deref(),
+        Some("An open network for secure, decentralised communication")
+    );
+    assert_eq!(preview.image.as_deref(), Some(mxc_uri!("mxc://localhost/image")));
+    assert_eq!(preview.image_mimetype.as_deref(), Some("image/png"));
+    assert_eq!(preview.image_width, Some(uint!(1200)));
+    assert_eq!(preview.image_height, Some(uint!(630)));
+    assert_eq!(preview.image_size, Some(uint!(12345)));
+    assert_eq!(preview.url, None);
+
+    // The preview is cached, so the endpoint is only called once.
+    let preview = client.get_url_preview("https://matrix.org/", ts).await.unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Matrix.org"));
+}
+
+#[async_test]
+async fn get_url_preview_legacy() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.10"],
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/media/.*/preview_url"))
+        .and(query_param("url", "https://matrix.org/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "og:title": "Matrix.org",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let ts = MilliSecondsSinceUnixEpoch(uint!(1000));
+    let preview = client.get_url_preview("https://matrix.org/", ts).await.unwrap();
+
+    assert_eq!(preview.title.as_deref(), Some("Matrix.org"));
+    assert_eq!(preview.image, None);
+}
+
+#[async_test]
+async fn upload_resumable() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.6"],
+            "unstable_features": { "fi.mau.msc2246": true },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/media/unstable/fi.mau.msc2246/create"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content_uri": "mxc://localhost/reserved",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/media/unstable/fi.mau.msc2246/upload/localhost/reserved"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let media = client.media();
+    let data = b"Hello, World!";
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+
+    media.upload_resumable(&mime::TEXT_PLAIN, file.path(), Default::default()).await.unwrap_err();
+
+    // The reservation is kept with the source of the content to resume the
+    // upload.
+    let pending_uploads = media.pending_uploads().await.unwrap();
+    assert_eq!(pending_uploads.len(), 1);
+    assert_eq!(pending_uploads[0].content_uri, "mxc://localhost/reserved");
+    assert_eq!(pending_uploads[0].size, data.len() as u64);
+    assert_eq!(pending_uploads[0].content_type.as_deref(), Some("text/plain"));
+    assert_eq!(pending_uploads[0].source.as_deref(), Some(file.path()));
+
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/media/unstable/fi.mau.msc2246/upload/localhost/reserved"))
+        .and(header("content-type", "text/plain"))
+        .and(body_bytes(data.to_vec()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The content is streamed again from the file to the reserved URI.
+    let content_uri = media.resume_upload(&pending_uploads[0], Default::default()).await.unwrap();
+    assert_eq!(content_uri, "mxc://localhost/reserved");
+    assert!(media.pending_uploads().await.unwrap().is_empty());
+}
+
+#[async_test]
+async fn resume_upload_of_modified_file() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/versions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "versions": ["v1.6"],
+            "unstable_features": { "fi.mau.msc2246": true },
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/media/unstable/fi.mau.msc2246/create"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content_uri": "mxc://localhost/reserved",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/media/unstable/fi.mau.msc2246/upload/localhost/reserved"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let media = client.media();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(b"Hello, World!").unwrap();
+
+    media.upload_resumable(&mime::TEXT_PLAIN, file.path(), Default::default()).await.unwrap_err();
+
+    let pending_uploads = media.pending_uploads().await.unwrap();
+    assert_eq!(pending_uploads.len(), 1);
+
+    // The content doesn't match the reservation anymore, so the upload is
+    // dropped.
+    file.write_all(b" Goodbye!").unwrap();
+    media.resume_upload(&pending_uploads[0], Default::default()).await.unwrap_err();
+    assert!(media.pending_uploads().await.unwrap().is_empty());
+}
+
+#[async_test]
+async fn get_media_file() {
+    let (client, server) = logged_in_client().await;
+
+    let event_content = ImageMessageEventContent::plain(
+        "filename.jpg".into(),
+        mxc_uri!("mxc://example.org/image").to_owned(),
+    )
+    .info(Box::new(assign!(ImageInfo::new(), {
+        height: Some(uint!(398)),
+        width: Some(uint!(394)),
+        mimetype: Some("image/jpeg".into()),
+        size: Some(uint!(31037)),
+    })));
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/media/r0/download/example.org/image"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw("binaryjpegdata", "image/jpeg"))
+        .named("get_file")
+        .mount(&server)
+        .await;
+
+    client.media().get_file(event_content.clone(), false).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/media/r0/thumbnail/example.org/image"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_raw("smallerbinaryjpegdata", "image/jpeg"),
+        )
+        .expect(1)
+        .named("get_thumbnail")
+        .mount(&server)
+        .await;
+
+    client
+        .media()
+        .get_thumbnail(
+            event_content,
+            MediaThumbnailSize { method: Method::Scale, width: uint!(100), height: uint!(100) },
+            false,
+        )
+        .await
+        .unwrap();
+}
+
+#[async_test]
+async fn whoami() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/account/whoami"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+        .mount(&server)
+        .await;
+
+    let user_id = user_id!("@joe:example.org");
+
+    assert_eq!(client.whoami().await.unwrap().user_id, user_id);
+}
+
+#[async_test]
+async fn endpoint_timeout() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .endpoint_timeout::<whoami::v3::Request>(Duration::from_millis(100))
+        .build()
+        .await
+        .unwrap();
+    let session = MatrixSession {
+        meta: SessionMeta {
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        },
+        tokens: MatrixSessionTokens { access_token: "1234".to_owned(), refresh_token: None },
+    };
+    client.restore_session(session).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/account/whoami"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(&*test_json::WHOAMI)
+                .set_delay(Duration::from_secs(2)),
+        )
+        .mount(&server)
+        .await;
+
+    assert_let!(Err(HttpError::Reqwest(error)) = client.whoami().await);
+    assert!(error.is_timeout());
+}
+
+#[async_test]
+async fn retry_delay_uses_the_clock() {
+    let clock = MockClock::new();
+    let (client, server) =
+        MockServerBuilder::new().logged_in().with_retry().clock(clock.clone()).build().await;
+
+    server
+        .mock_authenticated("GET", r"^/_matrix/client/r0/account/whoami")
+        .respond_with(
+            ResponseTemplate::new(500)
+                .set_body_json(json!({ "errcode": "M_UNKNOWN", "error": "Try again" })),
+        )
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    server
+        .mock_authenticated("GET", r"^/_matrix/client/r0/account/whoami")
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+        .expect(1)
+        .mount(server.server())
+        .await;
+
+    let request = tokio::spawn(async move { client.whoami().await });
+
+    // The request is not retried while the time doesn't move forward.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!request.is_finished());
+
+    // The first retry happens after less than a second.
+    clock.advance(Duration::from_secs(1));
+    let response = tokio::time::timeout(Duration::from_secs(1), request)
+        .await
+        .expect("the request should be retried")
+        .unwrap()
+        .unwrap();
+    assert_eq!(response.user_id, user_id!("@joe:example.org"));
+}
+
+#[async_test]
+async fn cached_homeserver_discovery() {
+    let server = MockServer::start().await;
+    let server_url = server.uri();
+    let server_name = <&ServerName>::try_from(server_url.strip_prefix("http://").unwrap()).unwrap();
+
+    let store = MemoryStore::new();
+    store
+        .set_kv_data(
+            StateStoreDataKey::HomeserverDiscovery,
+            StateStoreDataValue::HomeserverDiscovery(HomeserverDiscovery {
+                server_url: server_url.clone(),
+                homeserver_url: "https://old.example.org".to_owned(),
+                sliding_sync_proxy: None,
+                authentication_issuer: None,
+                authentication_account: None,
+                validated_at: MilliSecondsSinceUnixEpoch::now(),
+            }),
+        )
+        .await
+        .unwrap();
+
+    // The well-known file is only fetched when revalidating.
+    Mock::given(method("GET"))
+        .and(path("/.well-known/matrix/client"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "m.homeserver": { "base_url": server_url },
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = Client::builder()
+        .insecure_server_name_no_tls(server_name)
+        .store_config(StoreConfig::new().state_store(store))
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(client.homeserver().as_str(), "https://old.example.org/");
+
+    let mut homeserver_changes = client.subscribe_to_homeserver_changes();
+    assert!(client.revalidate_homeserver().await.unwrap());
+
+    let change = homeserver_changes.recv().await.unwrap();
+    assert_eq!(change.previous.as_str(), "https://old.example.org/");
+    assert_eq!(change.new, client.homeserver());
+    assert_eq!(client.homeserver().as_str(), format!("{server_url}/"));
+
+    assert_let!(
+        Ok(Some(StateStoreDataValue::HomeserverDiscovery(discovery))) =
+            client.store().get_kv_data(StateStoreDataKey::HomeserverDiscovery).await
+    );
+    assert_eq!(discovery.homeserver_url, server_url);
+}
+
+#[async_test]
+async fn room_update_channel() {
+    let (client, server) = logged_in_client().await;
+
+    let mut rx = client.subscribe_to_room_updates(&DEFAULT_TEST_ROOM_ID);
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+    client.sync_once(sync_settings).await.unwrap();
+
+    let update = rx.recv().now_or_never().unwrap().unwrap();
+    assert_let!(RoomUpdate::Joined { updates, .. } = update);
+
+    assert_eq!(updates.account_data.len(), 1);
+    assert_eq!(updates.ephemeral.len(), 1);
+    assert_eq!(updates.state.len(), 9);
+
+    assert!(updates.timeline.limited);
     assert_eq!(updates.timeline.events.len(), 1);
     assert_eq!(updates.timeline.prev_batch, Some("t392-516_47314_0_7_1_1_1_11444_1".to_owned()));
 
@@ -888,13 +1627,853 @@ async fn create_dm_encrypted() {
 }
 
 #[async_test]
-async fn create_dm_error() {
-    let (client, _server) = logged_in_client().await;
-    let user_id = user_id!("@invitee:localhost");
+async fn build_room() {
+    let (client, server) = logged_in_client().await;
+    let bob = user_id!("@bob:localhost");
+    let alice = user_id!("@alice:localhost");
 
-    // The endpoint is not mocked so we encounter a 404.
-    let error = client.create_dm(user_id).await.unwrap_err();
-    let client_api_error = error.as_client_api_error().unwrap();
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .and(body_partial_json(json!({
+            "name": "Announcements",
+            "room_alias_name": "news",
+            "invite": [bob],
+            "power_level_content_override": {
+                "events_default": 50,
+                "invite": 50,
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!sefiuhWgwghwWgh:example.com"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
 
-    assert_eq!(client_api_error.status_code, 404);
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/rooms/!sefiuhWgwghwWgh:example.com/invite"))
+        .and(body_partial_json(json!({
+            "user_id": alice,
+            "reason": "You asked for the news",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client
+        .build_room()
+        .name("Announcements")
+        .alias_localpart("news")
+        .invite(bob.to_owned())
+        .invite_with_reason(alice.to_owned(), "You asked for the news".to_owned())
+        .power_levels_preset(PowerLevelsPreset::Announcement)
+        .create()
+        .await
+        .unwrap();
+
+    assert_eq!(room.room_id(), room_id!("!sefiuhWgwghwWgh:example.com"));
+}
+
+#[async_test]
+async fn build_room_validation() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!sefiuhWgwghwWgh:example.com"
+        })))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let error = client.build_room().direct(true).create().await.unwrap_err();
+    assert_matches!(error, Error::RoomCreation(RoomCreationError::DirectRoomWithoutInvitee));
+
+    let error = client.build_room().alias_localpart("bad alias").create().await.unwrap_err();
+    assert_matches!(error, Error::RoomCreation(RoomCreationError::InvalidAliasLocalpart(_)));
+
+    let error = client
+        .build_room()
+        .power_levels_preset(PowerLevelsPreset::Community)
+        .power_level_content_override(Raw::new(&json!({ "ban": 100 })).unwrap().cast())
+        .create()
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::RoomCreation(RoomCreationError::ConflictingPowerLevels));
+
+    let error = client
+        .build_room()
+        .space_parent(room_id!("!unknown:localhost").to_owned())
+        .create()
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::RoomCreation(RoomCreationError::UnknownSpace(_)));
+}
+
+#[async_test]
+async fn create_dm_error() {
+    let (client, _server) = logged_in_client().await;
+    let user_id = user_id!("@invitee:localhost");
+
+    // The endpoint is not mocked so we encounter a 404.
+    let error = client.create_dm(user_id).await.unwrap_err();
+    let client_api_error = error.as_client_api_error().unwrap();
+
+    assert_eq!(client_api_error.status_code, 404);
+}
+
+#[async_test]
+async fn unread_counts_stream() {
+    let (client, server) = logged_in_client().await;
+    let other_room_id = room_id!("!jEsUZKDJdhlrceRyVU:localhost");
+
+    let mut unread_counts = client.unread_counts_stream();
+    assert_eq!(client.unread_counts(), UnreadCounts::default());
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).set_unread_notifications_count(
+            json!({ "notification_count": 3, "highlight_count": 1 }),
+        ),
+    );
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(other_room_id).set_unread_notifications_count(
+            json!({ "notification_count": 2, "highlight_count": 0 }),
+        ),
+    );
+    ev_builder.add_global_account_data_event(GlobalAccountDataTestEvent::PushRules);
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    let expected = UnreadCounts {
+        num_rooms_with_notifications: 2,
+        num_notifications: 5,
+        num_mentions: 1,
+        num_unread_messages: 5,
+    };
+    assert_eq!(client.unread_counts(), expected);
+    assert_eq!(unread_counts.next().now_or_never(), Some(Some(expected)));
+}
+
+#[async_test]
+async fn ignore_user_updates_local_state() {
+    let (client, server) = logged_in_client().await;
+    let spammer = user_id!("@spammer:localhost");
+
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/client/r0/user/@example:localhost/account_data/m.ignored_user_list"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let mut ignore_user_list_changes = client.subscribe_to_ignore_user_list_changes();
+    assert!(client.account().ignored_users().await.unwrap().is_empty());
+
+    // The change is visible right away, without syncing.
+    client.account().ignore_user(spammer).await.unwrap();
+    assert_eq!(client.account().ignored_users().await.unwrap(), [spammer.to_owned()]);
+    assert_eq!(ignore_user_list_changes.next().now_or_never(), Some(Some(())));
+
+    client.account().unignore_user(spammer).await.unwrap();
+    assert!(client.account().ignored_users().await.unwrap().is_empty());
+    assert_eq!(ignore_user_list_changes.next().now_or_never(), Some(Some(())));
+}
+
+#[async_test]
+async fn incoming_calls_stream() {
+    let (client, server) = logged_in_client().await;
+    let now = MilliSecondsSinceUnixEpoch::now();
+
+    let incoming_calls = client.incoming_calls_stream();
+    pin_mut!(incoming_calls);
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_timeline_bulk([
+        // Addressed to another user.
+        sync_timeline_event!({
+            "content": {
+                "call_id": "bob_call",
+                "party_id": "alice_party",
+                "invitee": "@bob:localhost",
+                "lifetime": 60_000,
+                "offer": { "type": "offer", "sdp": "m=audio" },
+                "version": "1",
+            },
+            "event_id": "$bob_invite",
+            "origin_server_ts": now,
+            "sender": "@alice:localhost",
+            "type": "m.call.invite",
+        }),
+        // Expired.
+        sync_timeline_event!({
+            "content": {
+                "call_id": "old_call",
+                "lifetime": 60_000,
+                "offer": { "type": "offer", "sdp": "m=audio" },
+                "version": 0,
+            },
+            "event_id": "$old_invite",
+            "origin_server_ts": 1_000,
+            "sender": "@alice:localhost",
+            "type": "m.call.invite",
+        }),
+        sync_timeline_event!({
+            "content": {
+                "call_id": "call",
+                "party_id": "alice_party",
+                "lifetime": 60_000,
+                "offer": { "type": "offer", "sdp": "m=audio\nm=video" },
+                "version": "1",
+            },
+            "event_id": "$invite",
+            "origin_server_ts": now,
+            "sender": "@alice:localhost",
+            "type": "m.call.invite",
+        }),
+        // Sent by the current user.
+        sync_timeline_event!({
+            "content": {
+                "application": "m.call",
+                "call_id": "",
+                "notify_type": "ring",
+                "m.mentions": { "room": true },
+            },
+            "event_id": "$own_notify",
+            "origin_server_ts": now,
+            "sender": "@example:localhost",
+            "type": "org.matrix.msc4075.call.notify",
+        }),
+        sync_timeline_event!({
+            "content": {
+                "application": "m.call",
+                "call_id": "",
+                "notify_type": "ring",
+                "m.mentions": { "user_ids": ["@example:localhost"] },
+            },
+            "event_id": "$notify",
+            "origin_server_ts": now,
+            "sender": "@bob:localhost",
+            "type": "org.matrix.msc4075.call.notify",
+        }),
+    ]));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    let call = incoming_calls.next().now_or_never().unwrap().unwrap();
+    assert_eq!(call.room_id.as_str(), DEFAULT_TEST_ROOM_ID.as_str());
+    assert_eq!(call.event_id, "$invite");
+    assert_eq!(call.caller, "@alice:localhost");
+    assert_let!(IncomingCallKind::Legacy { call_id, is_video } = call.kind);
+    assert_eq!(call_id.as_str(), "call");
+    assert!(is_video);
+    assert_eq!(call.sent_at, now);
+    assert!(!call.is_expired(client.clock()));
+
+    let call = incoming_calls.next().now_or_never().unwrap().unwrap();
+    assert_eq!(call.event_id, "$notify");
+    assert_eq!(call.caller, "@bob:localhost");
+    assert_eq!(call.kind, IncomingCallKind::Ring { call_id: String::new() });
+
+    assert!(incoming_calls.next().now_or_never().is_none());
+}
+
+#[async_test]
+async fn dm_with_existing_room() {
+    let (client, server) = logged_in_client().await;
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder
+        .add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID))
+        .add_global_account_data_event(GlobalAccountDataTestEvent::Direct);
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!sefiuhWgwghwWgh:example.com"
+        })))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let room = client.dm_with(user_id!("@invited:localhost")).await.unwrap();
+    assert_eq!(room.room_id(), *DEFAULT_TEST_ROOM_ID);
+}
+
+#[async_test]
+async fn dm_with_creates_room() {
+    let (client, server) = logged_in_client().await;
+    let bob = user_id!("@bob:localhost");
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    // Our room is the only new DM with Bob after its creation.
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "@bob:localhost": ["!sefiuhWgwghwWgh:example.com"],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .and(body_partial_json(json!({ "is_direct": true, "invite": [bob] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!sefiuhWgwghwWgh:example.com"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client.dm_with(bob).await.unwrap();
+    assert_eq!(room.room_id(), room_id!("!sefiuhWgwghwWgh:example.com"));
+}
+
+#[async_test]
+async fn dm_with_joins_unsynced_room() {
+    let (client, server) = logged_in_client().await;
+    let bob = user_id!("@bob:localhost");
+
+    // Another device created the DM with Bob, but it wasn't synced yet.
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "@bob:localhost": ["!unsynced:localhost"],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/join/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!unsynced:localhost"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!sefiuhWgwghwWgh:example.com"
+        })))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let room = client.dm_with(bob).await.unwrap();
+    assert_eq!(room.room_id(), room_id!("!unsynced:localhost"));
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn dm_with_concurrent_creation() {
+    let (client, server) = logged_in_client().await;
+    let bob = user_id!("@bob:localhost");
+
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    // Another device created a DM with Bob at the same time, with a smaller
+    // room ID.
+    Mock::given(method("GET"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "@bob:localhost": ["!zzz:localhost", "!aaa:localhost"],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path("_matrix/client/r0/user/@example:localhost/account_data/m.direct"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!zzz:localhost"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/join/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "room_id": "!aaa:localhost"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/leave"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client.dm_with(bob).await.unwrap();
+    assert_eq!(room.room_id(), room_id!("!aaa:localhost"));
+
+    server.verify().await;
+}
+
+#[async_test]
+async fn get_room_preview_with_summary() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/unstable/im.nheko.summary/rooms/.*alias.*/summary$"))
+        .and(query_param("via", "example.org"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "room_id": "!room:localhost",
+            "canonical_alias": "#alias:localhost",
+            "name": "Room",
+            "num_joined_members": 3,
+            "join_rule": "knock",
+            "world_readable": false,
+            "guest_can_join": false,
+            "im.nheko.summary.encryption": "m.megolm.v1.aes-sha2",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let preview = client
+        .get_room_preview(
+            room_alias_id!("#alias:localhost").into(),
+            vec![ServerName::parse("example.org").unwrap()],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(preview.room_id, "!room:localhost");
+    assert_eq!(preview.name.as_deref(), Some("Room"));
+    assert_eq!(preview.num_joined_members, 3);
+    assert_eq!(preview.join_rule, SpaceRoomJoinRule::Knock);
+    assert_eq!(preview.is_encrypted, Some(true));
+    assert_eq!(preview.state, None);
+}
+
+#[async_test]
+async fn get_room_preview_falls_back_to_hierarchy() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/unstable/im.nheko.summary/rooms/.*/summary$"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_UNRECOGNIZED",
+            "error": "Unrecognized request",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/v1/rooms/.*/hierarchy$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rooms": [{
+                "room_id": "!room:localhost",
+                "name": "Space",
+                "num_joined_members": 5,
+                "join_rule": "public",
+                "world_readable": true,
+                "guest_can_join": false,
+                "room_type": "m.space",
+                "children_state": [],
+            }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let preview =
+        client.get_room_preview(room_id!("!room:localhost").into(), Vec::new()).await.unwrap();
+
+    assert_eq!(preview.name.as_deref(), Some("Space"));
+    assert_eq!(preview.num_joined_members, 5);
+    assert_eq!(preview.join_rule, SpaceRoomJoinRule::Public);
+    assert_eq!(preview.room_type, Some(RoomType::Space));
+    assert!(preview.is_world_readable);
+    // The hierarchy doesn't say whether the room is encrypted.
+    assert_eq!(preview.is_encrypted, None);
+}
+
+#[async_test]
+async fn search_messages() {
+    let (client, server) = logged_in_client().await;
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder
+        .add_joined_room(JoinedRoomBuilder::new(room_id!("!public:localhost")))
+        .add_joined_room(
+            JoinedRoomBuilder::new(room_id!("!secret:localhost"))
+                .add_state_event(StateTestEvent::Encryption),
+        );
+    mock_sync(&server, sync_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/.*/search$"))
+        .and(body_partial_json(json!({
+            "search_categories": {
+                "room_events": { "search_term": "rust", "order_by": "recent" },
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "search_categories": {
+                "room_events": {
+                    "count": 1,
+                    "highlights": ["rust"],
+                    "next_batch": "next",
+                    "results": [{
+                        "rank": 0.5,
+                        "result": {
+                            "content": { "body": "I love Rust", "msgtype": "m.text" },
+                            "event_id": "$server",
+                            "origin_server_ts": 1,
+                            "room_id": "!public:localhost",
+                            "sender": "@alice:localhost",
+                            "type": "m.room.message",
+                        },
+                    }],
+                },
+            },
+        })))
+        .mount(&server)
+        .await;
+
+    struct Index;
+
+    #[async_trait]
+    impl LocalMessageIndex for Index {
+        async fn search(&self, query: &str, rooms: &[OwnedRoomId]) -> Vec<MessageSearchResult> {
+            assert_eq!(rooms, [room_id!("!secret:localhost").to_owned()]);
+
+            let event = Raw::new(&json!({
+                "content": { "body": "Rust is secret", "msgtype": "m.text" },
+                "event_id": "$local",
+                "origin_server_ts": 2,
+                "room_id": "!secret:localhost",
+                "sender": "@bob:localhost",
+                "type": "m.room.message",
+            }))
+            .unwrap()
+            .cast();
+            MessageSearchResult::from_event(event, None, &[query.to_owned()]).into_iter().collect()
+        }
+    }
+
+    let options = MessageSearchOptions { order: SearchOrder::Recent, ..Default::default() };
+    let response = client.search_messages("rust", None, options.clone()).await.unwrap();
+
+    assert_eq!(response.unsearched_encrypted_rooms, [room_id!("!secret:localhost").to_owned()]);
+    assert_eq!(response.next_batch.as_deref(), Some("next"));
+    assert_eq!(response.results.len(), 1);
+    let snippet = response.results[0].snippet.as_ref().unwrap();
+    assert_eq!(&snippet.text[snippet.highlights[0].clone()], "Rust");
+
+    let response =
+        client.search_messages_with_local_index("rust", None, options, &Index).await.unwrap();
+    let event_ids: Vec<_> = response.results.iter().map(|r| r.event_id.as_str()).collect();
+    assert_eq!(event_ids, ["$local", "$server"]);
+}
+
+#[async_test]
+async fn read_only_client_blocks_writes() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .read_only()
+        .build()
+        .await
+        .unwrap();
+    client
+        .restore_session(MatrixSession {
+            meta: SessionMeta {
+                user_id: user_id!("@example:localhost").to_owned(),
+                device_id: device_id!("DEVICEID").to_owned(),
+            },
+            tokens: MatrixSessionTokens { access_token: "1234".to_owned(), refresh_token: None },
+        })
+        .await
+        .unwrap();
+    assert!(client.is_read_only());
+
+    Mock::given(method("PUT"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    // Syncing still works.
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    client.sync_once(SyncSettings::default()).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let content = RoomMessageEventContent::text_plain("Hello");
+    assert_matches!(room.send(content).await, Err(Error::Http(HttpError::ReadOnly)));
+    assert_matches!(room.typing_notice(true).await, Err(Error::Http(HttpError::ReadOnly)));
+    assert_matches!(
+        client.account().set_display_name(Some("Alice")).await,
+        Err(Error::Http(HttpError::ReadOnly))
+    );
+}
+
+#[async_test]
+async fn sync_filter_builder_uploads_filter_once() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/user/@example:localhost/filter"))
+        .and(body_partial_json(json!({
+            "room": {
+                "state": { "lazy_load_members": true },
+                "timeline": { "limit": 10, "not_types": ["m.call.invite"] },
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "filter_id": "lazy" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(query_param("filter", "lazy"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::SYNC))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let builder = SyncFilterBuilder::new()
+        .lazy_load_members(true)
+        .timeline_limit(10)
+        .not_event_types(["m.call.invite"]);
+
+    // The filter ID is cached after the first sync, so the filter is only
+    // uploaded once.
+    for _ in 0..2 {
+        client.sync_once(SyncSettings::new().filter_builder(builder.clone())).await.unwrap();
+    }
+}
+
+#[async_test]
+async fn guest_registration_sync_and_upgrade() {
+    let (client, server) = no_retry_test_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(query_param("kind", "guest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "device_id": "GUESTDEVICE",
+            "access_token": "guest_token",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let auth = client.matrix_auth();
+    auth.register_guest().await.unwrap();
+    assert!(auth.is_guest());
+    assert_eq!(client.user_id().unwrap(), "@guest:localhost");
+    assert_eq!(client.device_id().unwrap(), "GUESTDEVICE");
+
+    // Guests can't upload filters, so the filter is sent with the request.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/user/.*/filter"))
+        .respond_with(ResponseTemplate::new(403))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(header("authorization", "Bearer guest_token"))
+        .and(|request: &Request| {
+            request
+                .url
+                .query_pairs()
+                .any(|(key, value)| key == "filter" && value.contains("lazy_load_members"))
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::SYNC))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let builder = SyncFilterBuilder::new().lazy_load_members(true);
+    client.sync_once(SyncSettings::new().filter_builder(builder)).await.unwrap();
+
+    // The upgrade keeps the same device.
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(body_partial_json(json!({
+            "guest_access_token": "guest_token",
+            "username": "example",
+            "password": "wordpass",
+            "device_id": "GUESTDEVICE",
+            "auth": { "type": "m.login.password", "session": "upgrade" },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "device_id": "GUESTDEVICE",
+            "access_token": "full_token",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.password"] }],
+            "params": {},
+            "session": "upgrade",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    auth.upgrade_guest("example", "wordpass", &PasswordUiaaHandler).await.unwrap();
+    assert!(!auth.is_guest());
+    assert_eq!(auth.access_token().unwrap(), "full_token");
+    assert_eq!(client.device_id().unwrap(), "GUESTDEVICE");
+}
+
+#[async_test]
+async fn peek_world_readable_room() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!world_readable:localhost");
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/rooms/!world_readable:localhost/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "start",
+            "end": "end",
+            "chunk": [{
+                "type": "m.room.message",
+                "event_id": "$message:localhost",
+                "sender": "@alice:localhost",
+                "origin_server_ts": 0,
+                "content": { "msgtype": "m.text", "body": "Hello world" },
+            }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let messages = client.peek_room_messages(room_id, MessagesOptions::backward()).await.unwrap();
+    assert_eq!(messages.end.as_deref(), Some("end"));
+    assert_eq!(messages.chunk.len(), 1);
+    let event = messages.chunk[0].event.deserialize().unwrap();
+    assert_eq!(event.event_id(), "$message:localhost");
+
+    // The room was not joined.
+    assert!(client.get_room(room_id).is_none());
+}
+
+#[async_test]
+async fn test_proxy_selection() {
+    let proxy = MockServer::start().await;
+    let homeserver = MockServer::start().await;
+
+    let mock_versions = || {
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/versions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "versions": ["v1.1"] })))
+    };
+    let get_versions = |client: Client| async move {
+        client.send(get_supported_versions::Request::new(), None).await.unwrap();
+    };
+    // The proxy is the only server that can be reached with this URL.
+    let unreachable_url = "http://homeserver.invalid";
+
+    // The proxy for the `http` URLs is used for an `http` URL.
+    {
+        let _guard = mock_versions().expect(1).mount_as_scoped(&proxy).await;
+        let client = Client::builder()
+            .homeserver_url(unreachable_url)
+            .server_versions([MatrixVersion::V1_0])
+            .http_proxy(proxy.uri())
+            .build()
+            .await
+            .unwrap();
+        get_versions(client).await;
+    }
+
+    // The proxy for the `https` URLs isn't used for an `http` URL.
+    {
+        let _proxy_guard = mock_versions().expect(0).mount_as_scoped(&proxy).await;
+        let _guard = mock_versions().expect(1).mount_as_scoped(&homeserver).await;
+        let client = Client::builder()
+            .homeserver_url(homeserver.uri())
+            .server_versions([MatrixVersion::V1_0])
+            .https_proxy(proxy.uri())
+            .build()
+            .await
+            .unwrap();
+        get_versions(client).await;
+    }
+
+    // The proxy for all the URLs is used for an `http` URL.
+    {
+        let _guard = mock_versions().expect(1).mount_as_scoped(&proxy).await;
+        let client = Client::builder()
+            .homeserver_url(unreachable_url)
+            .server_versions([MatrixVersion::V1_0])
+            .proxy(proxy.uri())
+            .build()
+            .await
+            .unwrap();
+        get_versions(client).await;
+    }
+
+    // The proxy isn't used for the hosts of the no-proxy list.
+    {
+        let _proxy_guard = mock_versions().expect(0).mount_as_scoped(&proxy).await;
+        let _guard = mock_versions().expect(1).mount_as_scoped(&homeserver).await;
+        let client = Client::builder()
+            .homeserver_url(homeserver.uri())
+            .server_versions([MatrixVersion::V1_0])
+            .proxy(proxy.uri())
+            .no_proxy([homeserver.address().ip().to_string()])
+            .build()
+            .await
+            .unwrap();
+        get_versions(client).await;
+    }
 }
@@ -4,7 +4,7 @@ use assert_matches::assert_matches;
 use matrix_sdk::{
     config::RequestConfig,
     matrix_auth::{MatrixSession, MatrixSessionTokens},
-    AuthApi, AuthSession, Client, RumaApiError,
+    AuthApi, AuthSession, Client, ReloginCredentials, RumaApiError, SessionState,
 };
 use matrix_sdk_base::SessionMeta;
 use matrix_sdk_test::{async_test, test_json};
@@ -27,7 +27,7 @@ use ruma::{
 use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
 use url::Url;
 use wiremock::{
-    matchers::{method, path},
+    matchers::{body_partial_json, method, path},
     Mock, MockServer, Request, ResponseTemplate,
 };
 
@@ -223,6 +223,49 @@ async fn test_login_error() {
     }
 }
 
+#[async_test]
+async fn test_relogin_after_soft_logout() {
+    let (client, server) = logged_in_client().await;
+    assert_eq!(client.session_state(), SessionState::LoggedIn);
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/account/whoami"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "errcode": "M_UNKNOWN_TOKEN",
+            "error": "Soft logged out",
+            "soft_logout": true,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.whoami().await.unwrap_err();
+    assert_eq!(client.session_state(), SessionState::SoftLoggedOut);
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .and(body_partial_json(json!({
+            "type": "m.login.password",
+            "identifier": { "type": "m.id.user", "user": "@example:localhost" },
+            "password": "wordpass",
+            "device_id": "DEVICEID",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "5678",
+            "device_id": "DEVICEID",
+            "user_id": "@example:localhost",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.relogin(ReloginCredentials::Password("wordpass".to_owned())).await.unwrap();
+
+    assert_eq!(client.session_state(), SessionState::LoggedIn);
+    assert_eq!(client.access_token().as_deref(), Some("5678"));
+    assert_eq!(client.device_id(), Some(device_id!("DEVICEID")));
+}
+
 #[async_test]
 async fn test_register_error() {
     let (client, server) = no_retry_test_client().await;
@@ -0,0 +1,104 @@
+use matrix_sdk::{
+    config::SyncSettings,
+    policy_list::{PolicyActionKind, PolicyEnforcementOptions, PolicyLists, PolicyRuleKind},
+};
+use matrix_sdk_test::{
+    async_test, test_json, JoinedRoomBuilder, StateTestEvent, SyncResponseBuilder,
+    DEFAULT_TEST_ROOM_ID,
+};
+use ruma::{room_id, user_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path_regex},
+    Mock, ResponseTemplate,
+};
+
+use crate::{mock_sync, synced_client};
+
+#[async_test]
+async fn enforce_ban_rules() {
+    let (client, server) = synced_client().await;
+    let list_room_id = room_id!("!list:localhost");
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(list_room_id)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "entity": "evil.org",
+                    "recommendation": "m.ban",
+                    "reason": "spam",
+                },
+                "event_id": "$server_rule",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "rule:evil.org",
+                "type": "m.policy.rule.server",
+            })))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {},
+                "event_id": "$removed_rule",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "rule:@alice:localhost",
+                "type": "m.policy.rule.user",
+            }))),
+    );
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let policy_lists = PolicyLists::new(client.clone());
+    // The room of the list is already joined, so no request is sent.
+    policy_lists.subscribe(list_room_id.into(), &[]).await.unwrap();
+
+    // The removed rule is ignored.
+    let rules = policy_lists.rules().await.unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].kind, PolicyRuleKind::Server);
+    assert!(rules[0].is_ban());
+    assert!(policy_lists.rules_for_user(user_id!("@alice:localhost")).await.unwrap().is_empty());
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [
+                {
+                    "content": { "membership": "join" },
+                    "event_id": "$spammer",
+                    "origin_server_ts": 151800140,
+                    "room_id": *DEFAULT_TEST_ROOM_ID,
+                    "sender": "@spammer:evil.org",
+                    "state_key": "@spammer:evil.org",
+                    "type": "m.room.member",
+                },
+            ],
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/ban$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "user_id": "@spammer:evil.org", "reason": "spam" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = PolicyEnforcementOptions {
+        protected_rooms: vec![DEFAULT_TEST_ROOM_ID.to_owned()],
+        ban_users: true,
+        redact_messages: false,
+    };
+    let actions = policy_lists.enforce(&options).await.unwrap();
+
+    assert_eq!(actions.len(), 1);
+    assert_eq!(
+        actions[0].kind,
+        PolicyActionKind::Ban { user_id: user_id!("@spammer:evil.org").to_owned() }
+    );
+    assert_eq!(actions[0].error, None);
+    assert_eq!(policy_lists.audit_log().len(), 1);
+}
@@ -7,10 +7,11 @@ use assert_matches::assert_matches;
 use assert_matches2::assert_let;
 use futures_util::StreamExt;
 use matrix_sdk::{
+    async_trait,
     config::RequestConfig,
     executor::spawn,
     matrix_auth::{MatrixSession, MatrixSessionTokens},
-    HttpError, RefreshTokenError, SessionChange,
+    Client, HttpError, RefreshTokenError, SessionChange, SessionSaveReason, SessionStoreDelegate,
 };
 use matrix_sdk_base::SessionMeta;
 use matrix_sdk_test::{async_test, test_json};
@@ -43,6 +44,72 @@ fn session() -> MatrixSession {
     }
 }
 
+/// A session store recording the access token every time the session is saved.
+#[derive(Debug, Default)]
+struct RecordingSessionStore {
+    saved: Arc<Mutex<Vec<(SessionSaveReason, String)>>>,
+}
+
+#[async_trait]
+impl SessionStoreDelegate for RecordingSessionStore {
+    async fn save_session(
+        &self,
+        client: &Client,
+        reason: SessionSaveReason,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let access_token = client.access_token().ok_or("no access token")?;
+        self.saved.lock().unwrap().push((reason, access_token));
+        Ok(())
+    }
+}
+
+#[async_test]
+async fn test_session_store_delegate_saves_login_and_refresh() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .server_versions([MatrixVersion::V1_3])
+        .build()
+        .await
+        .unwrap();
+
+    let store = RecordingSessionStore::default();
+    let saved = store.saved.clone();
+    client.set_session_store_delegate(store).unwrap();
+
+    // Only one delegate can be set.
+    assert!(client.set_session_store_delegate(RecordingSessionStore::default()).is_err());
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/login"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(&*test_json::LOGIN_WITH_REFRESH_TOKEN),
+        )
+        .mount(&server)
+        .await;
+
+    client.matrix_auth().login_username("example", "wordpass").send().await.unwrap();
+    assert_eq!(*saved.lock().unwrap(), [(SessionSaveReason::Login, "abc123".to_owned())]);
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/refresh"))
+        .and(body_partial_json(json!({
+            "refresh_token": "zyx987",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::REFRESH_TOKEN))
+        .mount(&server)
+        .await;
+
+    client.refresh_access_token().await.unwrap();
+    assert_eq!(
+        *saved.lock().unwrap(),
+        [
+            (SessionSaveReason::Login, "abc123".to_owned()),
+            (SessionSaveReason::TokensRefreshed, "5678".to_owned()),
+        ]
+    );
+}
+
 #[async_test]
 async fn test_login_username_refresh_token() {
     let (client, server) = no_retry_test_client().await;
@@ -332,6 +399,59 @@ async fn refresh_token_handled_success() {
     changed_join_handle.await.unwrap();
 }
 
+#[async_test]
+async fn refresh_token_before_expiration() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .server_versions([MatrixVersion::V1_3])
+        .handle_refresh_tokens()
+        .build()
+        .await
+        .unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "1234",
+            "device_id": "DEVICEID",
+            "user_id": "@example:localhost",
+            "expires_in_ms": 1,
+            "refresh_token": "abcd",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/refresh"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::REFRESH_TOKEN))
+        .expect(1)
+        .named("`POST /refresh`")
+        .mount(&server)
+        .await;
+
+    // The request is only sent with the new token.
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/v3/account/whoami"))
+        .and(header(http::header::AUTHORIZATION, "Bearer 5678"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+        .expect(1)
+        .named("`GET /whoami` good token")
+        .mount(&server)
+        .await;
+
+    client
+        .matrix_auth()
+        .login_username("example", "wordpass")
+        .request_refresh_token()
+        .send()
+        .await
+        .unwrap();
+
+    client.whoami().await.unwrap();
+}
+
 #[async_test]
 async fn refresh_token_handled_failure() {
     let (builder, server) = test_client_builder().await;
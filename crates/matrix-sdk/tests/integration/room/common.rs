@@ -1,26 +1,30 @@
 use std::time::Duration;
 
 use assert_matches2::assert_let;
-use matrix_sdk::{config::SyncSettings, room::RoomMember, DisplayName, RoomMemberships};
+use matrix_sdk::{
+    config::SyncSettings,
+    room::{RelationsOptions, RoomMember},
+    DisplayName, RoomMemberships,
+};
 use matrix_sdk_test::{
     async_test, bulk_room_members, sync_timeline_event, test_json, JoinedRoomBuilder,
-    StateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+    RoomAccountDataTestEvent, StateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
 };
 use ruma::{
     event_id,
     events::{
-        room::member::MembershipState, AnyStateEvent, AnySyncStateEvent, AnyTimelineEvent,
-        StateEventType,
+        relation::RelationType, room::member::MembershipState, AnyMessageLikeEvent, AnyStateEvent,
+        AnySyncStateEvent, AnyTimelineEvent, StateEventType,
     },
     room_id,
 };
 use serde_json::json;
 use wiremock::{
-    matchers::{header, method, path_regex},
+    matchers::{body_json, header, method, path_regex, query_param},
     Mock, ResponseTemplate,
 };
 
-use crate::{logged_in_client, mock_sync};
+use crate::{logged_in_client, mock_encryption_state, mock_sync};
 
 #[async_test]
 async fn user_presence() {
@@ -46,6 +50,65 @@ async fn user_presence() {
     // assert!(room.power_levels.is_some())
 }
 
+#[async_test]
+async fn search_members() {
+    let (client, server) = logged_in_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+
+    let member = |user_id: &str, display_name: Option<&str>| {
+        json!({
+            "content": {
+                "displayname": display_name,
+                "membership": "join",
+            },
+            "event_id": format!("${}", &user_id[1..]),
+            "origin_server_ts": 151800140,
+            "room_id": *DEFAULT_TEST_ROOM_ID,
+            "sender": user_id,
+            "state_key": user_id,
+            "type": "m.room.member",
+        })
+    };
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [
+                member("@alice:localhost", Some("Alice Smith")),
+                member("@bob:localhost", Some("Bob")),
+                member("@charlie:localhost", None),
+            ],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.sync_once(SyncSettings::new()).await.unwrap();
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let search = |query: &'static str, limit| {
+        let room = room.clone();
+        async move {
+            room.search_members(query, RoomMemberships::JOIN, limit)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|member| member.user_id().to_string())
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // Any word of the display name matches, ignoring the case.
+    assert_eq!(search("smi", 10).await, ["@alice:localhost"]);
+    // The localpart of the user ID matches, with or without the sigil.
+    assert_eq!(search("@bo", 10).await, ["@bob:localhost"]);
+    assert_eq!(search("CHAR", 10).await, ["@charlie:localhost"]);
+    assert!(search("dave", 10).await.is_empty());
+    // The number of results is limited.
+    assert_eq!(search("", 2).await.len(), 2);
+}
+
 #[async_test]
 async fn calculate_room_names_from_summary() {
     let (client, server) = logged_in_client().await;
@@ -553,3 +616,125 @@ async fn event() {
     assert!(push_actions.iter().any(|a| a.is_highlight()));
     assert!(push_actions.iter().any(|a| a.should_notify()));
 }
+
+#[async_test]
+async fn relations() {
+    let event_id = event_id!("$original");
+
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(sync_settings).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let reaction = |id: &str, key: &str| {
+        json!({
+            "content": {
+                "m.relates_to": {
+                    "event_id": event_id,
+                    "key": key,
+                    "rel_type": "m.annotation",
+                },
+            },
+            "event_id": id,
+            "origin_server_ts": 152039280,
+            "sender": "@bob:localhost",
+            "type": "m.reaction",
+            "room_id": *DEFAULT_TEST_ROOM_ID,
+        })
+    };
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/v1/rooms/.*/relations/.*/m.annotation$"))
+        .and(query_param("from", "page2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [reaction("$reaction2", "👎")],
+        })))
+        .expect(1)
+        .named("relations_page_2")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/v1/rooms/.*/relations/.*/m.annotation$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [reaction("$reaction1", "👍")],
+            "next_batch": "page2",
+        })))
+        .expect(1)
+        .named("relations_page_1")
+        .mount(&server)
+        .await;
+
+    let options = RelationsOptions::with_rel_type(RelationType::Annotation);
+    let relations = room.relations(event_id, options.clone()).await.unwrap();
+    assert_eq!(relations.chunk.len(), 1);
+    assert_let!(
+        AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::Reaction(event)) =
+            relations.chunk[0].event.deserialize().unwrap()
+    );
+    assert_eq!(event.event_id(), event_id!("$reaction1"));
+    assert_eq!(relations.next_batch.as_deref(), Some("page2"));
+
+    let options = options.from(relations.next_batch.as_deref());
+    let relations = room.relations(event_id, options).await.unwrap();
+    assert_eq!(relations.chunk.len(), 1);
+    assert_let!(
+        AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::Reaction(event)) =
+            relations.chunk[0].event.deserialize().unwrap()
+    );
+    assert_eq!(event.event_id(), event_id!("$reaction2"));
+    assert!(relations.next_batch.is_none());
+}
+
+#[async_test]
+async fn url_previews_enabled() {
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    // URL previews are disabled by default in encrypted rooms.
+    mock_encryption_state(&server, true).await;
+    assert!(!room.url_previews_enabled().await.unwrap());
+
+    Mock::given(method("PUT"))
+        .and(path_regex(
+            r"^/_matrix/client/r0/user/.*/rooms/.*/account_data/org.matrix.room.preview_urls$",
+        ))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "disable": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.set_url_previews_enabled(true).await.unwrap();
+
+    // The choice of the user is used once it is synced.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_account_data(
+        RoomAccountDataTestEvent::Custom(json!({
+            "type": "org.matrix.room.preview_urls",
+            "content": { "disable": false },
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(sync_settings).await.unwrap();
+
+    assert!(room.url_previews_enabled().await.unwrap());
+}
@@ -0,0 +1,184 @@
+use std::time::Duration;
+
+use matrix_sdk::{config::SyncSettings, Client, Room};
+use matrix_sdk_base::{RoomState, StateChanges, StateStore as _};
+use matrix_sdk_test::{
+    async_test, test_json, InvitedRoomBuilder, StrippedStateTestEvent, SyncResponseBuilder,
+};
+use ruma::{events::AnySyncStateEvent, room_id, serde::Raw, user_id, RoomId};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_json, header, method, path, path_regex},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{logged_in_client, mock_sync};
+
+/// Sync an invite to the given room, and save the full invite event with its
+/// ID in the store, like if the room had been synced before.
+///
+/// The invite state of a sync doesn't contain the IDs of the events, so it's
+/// the only way to know the ID of the invite event to report it.
+async fn invited_room_with_event_id(
+    client: &Client,
+    server: &MockServer,
+    room_id: &RoomId,
+) -> Room {
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_invited_room(InvitedRoomBuilder::new(room_id));
+    mock_sync(server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let raw_event: Raw<AnySyncStateEvent> = Raw::new(&json!({
+        "content": {
+            "membership": "invite",
+        },
+        "event_id": "$invite",
+        "origin_server_ts": 151800140,
+        "sender": "@spammer:localhost",
+        "state_key": "@example:localhost",
+        "type": "m.room.member",
+    }))
+    .unwrap()
+    .cast();
+    let mut changes = StateChanges::default();
+    changes.add_state_event(room_id, raw_event.deserialize().unwrap(), raw_event);
+    client.store().save_changes(&changes).await.unwrap();
+
+    let room = client.get_room(room_id).unwrap();
+    assert_eq!(room.state(), RoomState::Invited);
+    room
+}
+
+async fn mock_ignore_spammer(server: &MockServer) {
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/client/r0/user/@example:localhost/account_data/m.ignored_user_list"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({
+            "ignored_users": {
+                "@spammer:localhost": {},
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+#[async_test]
+async fn decline_and_block() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!invited:localhost");
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_invited_room(InvitedRoomBuilder::new(room_id).add_state_event(
+        StrippedStateTestEvent::Custom(json!({
+            "content": {
+                "membership": "invite",
+            },
+            "sender": "@spammer:localhost",
+            "state_key": "@example:localhost",
+            "type": "m.room.member",
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+    client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(room_id).unwrap();
+    assert_eq!(room.state(), RoomState::Invited);
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/leave$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    mock_ignore_spammer(&server).await;
+
+    // The invite event has no ID, so it can't be reported, but the invite is
+    // still declined.
+    room.decline_and_block(Some("Spam".to_owned())).await.unwrap();
+
+    assert_eq!(room.state(), RoomState::Left);
+}
+
+#[async_test]
+async fn decline_and_block_reports_invite() {
+    let (client, server) = logged_in_client().await;
+    let room = invited_room_with_event_id(&client, &server, room_id!("!invited:localhost")).await;
+
+    mock_ignore_spammer(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/report/.*invite$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "reason": "Spam" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/leave$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.decline_and_block(Some("Spam".to_owned())).await.unwrap();
+
+    assert_eq!(room.state(), RoomState::Left);
+    server.verify().await;
+}
+
+#[async_test]
+async fn decline_and_block_blocks_before_reporting() {
+    let (client, server) = logged_in_client().await;
+    let room = invited_room_with_event_id(&client, &server, room_id!("!invited:localhost")).await;
+
+    mock_ignore_spammer(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/report/.*invite$"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(json!({
+            "errcode": "M_UNKNOWN",
+            "error": "Internal server error",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/leave$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    // The report failed, but the inviter is blocked already.
+    room.decline_and_block(Some("Spam".to_owned())).await.unwrap_err();
+
+    assert_eq!(room.state(), RoomState::Invited);
+    let ignored_users = client.account().ignored_users().await.unwrap();
+    assert_eq!(ignored_users, [user_id!("@spammer:localhost").to_owned()]);
+    server.verify().await;
+}
+
+#[async_test]
+async fn decline_and_block_not_invited() {
+    let (client, server) = logged_in_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+    client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(&matrix_sdk_test::DEFAULT_TEST_ROOM_ID).unwrap();
+    assert_eq!(room.state(), RoomState::Joined);
+
+    room.decline_and_block(None).await.unwrap_err();
+}
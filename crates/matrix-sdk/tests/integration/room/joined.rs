@@ -1,25 +1,58 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use futures_util::future::join_all;
+use assert_matches2::{assert_let, assert_matches};
+use eyeball::SharedObservable;
+use eyeball_im::VectorDiff;
+use futures_util::{future::join_all, pin_mut, FutureExt, StreamExt};
 use matrix_sdk::{
     attachment::{
         AttachmentConfig, AttachmentInfo, BaseImageInfo, BaseThumbnailInfo, BaseVideoInfo,
         Thumbnail,
     },
     config::SyncSettings,
-    room::Receipts,
+    event_cache::RoomEventCacheUpdate,
+    identity_server::IdentityServer,
+    room::{
+        CallSignalingState, Receipts, RoomUpgradeOptions, RoomUpgradeProgress, SpaceChildOptions,
+        UpdateDelayedEventAction,
+    },
+    ComposerDraft, ComposerDraftType, Error, RoomSettingsError,
+};
+use matrix_sdk_base::{
+    store::{MemoryStore, StoreConfig},
+    RoomNotableTags, RoomState,
 };
-use matrix_sdk_base::RoomState;
-use matrix_sdk_test::{async_test, test_json, DEFAULT_TEST_ROOM_ID};
+use matrix_sdk_test::{
+    async_test, sync_timeline_event, test_json, JoinedRoomBuilder, MockClock,
+    RoomAccountDataTestEvent, StateTestEvent, SyncResponseBuilder, DEFAULT_TEST_ROOM_ID,
+};
+use matrix_sdk_test_server::{default_session, MockServerBuilder};
 use ruma::{
     api::client::{membership::Invite3pidInit, receipt::create_receipt::v3::ReceiptType},
     assign, event_id,
-    events::{receipt::ReceiptThread, room::message::RoomMessageEventContent},
-    mxc_uri, thirdparty, uint, user_id, TransactionId,
+    events::{
+        call::{hangup::Reason, SessionDescription},
+        receipt::ReceiptThread,
+        room::{
+            guest_access::GuestAccess,
+            history_visibility::HistoryVisibility,
+            join_rules::{AllowRule, JoinRule, Restricted},
+            message::RoomMessageEventContent,
+            topic::RoomTopicEventContent,
+        },
+        EmptyStateKey, StateEventType, TimelineEventType,
+    },
+    int, mxc_uri, room_id,
+    serde::Raw,
+    server_name, thirdparty, uint, user_id, MilliSecondsSinceUnixEpoch, RoomVersionId,
+    TransactionId, VoipId,
 };
 use serde_json::json;
+use url::Url;
 use wiremock::{
-    matchers::{body_json, body_partial_json, header, method, path, path_regex},
+    matchers::{
+        body_json, body_partial_json, body_string, header, method, path, path_regex, query_param,
+    },
     Mock, ResponseTemplate,
 };
 
@@ -48,6 +81,53 @@ async fn invite_user_by_id() {
     room.invite_user_by_id(user).await.unwrap();
 }
 
+#[async_test]
+async fn invite_users_continues_past_failures() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(body_partial_json(json!({ "user_id": "@alice:localhost" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(body_partial_json(json!({ "user_id": "@bob:localhost" })))
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "@bob:localhost is banned from the room",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(body_partial_json(json!({ "user_id": "@carol:localhost" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let alice = user_id!("@alice:localhost").to_owned();
+    let bob = user_id!("@bob:localhost").to_owned();
+    let carol = user_id!("@carol:localhost").to_owned();
+
+    // Alice is only invited once.
+    let report =
+        room.invite_users(&[alice.clone(), bob.clone(), alice.clone(), carol.clone()]).await;
+
+    assert!(!report.is_success());
+    assert_eq!(report.succeeded, [alice, carol]);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0, bob);
+    assert_matches!(report.failed[0].1, Error::Http(_));
+}
+
 #[async_test]
 async fn invite_user_by_3pid() {
     let (client, server) = logged_in_client().await;
@@ -244,6 +324,96 @@ async fn typing_notice() {
     room.typing_notice(true).await.unwrap();
 }
 
+#[async_test]
+async fn typing_notice_guard() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .named("typing_true")
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .named("typing_false")
+        .mount(&server)
+        .await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let _response = client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let typing_guard = room.typing_notice_guard();
+    assert!(!typing_guard.is_typing());
+
+    // Only the first keystroke sends a request.
+    typing_guard.keystroke().await.unwrap();
+    typing_guard.keystroke().await.unwrap();
+    assert!(typing_guard.is_typing());
+
+    typing_guard.stop().await.unwrap();
+    assert!(!typing_guard.is_typing());
+
+    // Nothing is sent when the guard is dropped if the user is not typing.
+    drop(typing_guard);
+}
+
+#[async_test]
+async fn typing_notice_guard_refresh_and_idle() {
+    let clock = MockClock::new();
+    let (client, server) = MockServerBuilder::new()
+        .logged_in()
+        .synced(&*test_json::SYNC)
+        .clock(clock.clone())
+        .build()
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(2)
+        .named("typing_true")
+        .mount(server.server())
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .named("typing_false")
+        .mount(server.server())
+        .await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let typing_guard = room.typing_notice_guard();
+
+    typing_guard.keystroke().await.unwrap();
+    clock.advance(Duration::from_secs(2));
+    typing_guard.keystroke().await.unwrap();
+
+    // The user is still typing when the notice needs to be refreshed.
+    clock.advance(Duration::from_millis(1500));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(typing_guard.is_typing());
+
+    // The user stopped typing 5 seconds ago.
+    clock.advance(Duration::from_millis(3500));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!typing_guard.is_typing());
+}
+
 #[async_test]
 async fn room_state_event_send() {
     use ruma::events::room::member::{MembershipState, RoomMemberEventContent};
@@ -349,6 +519,61 @@ async fn room_attachment_send() {
     assert_eq!(event_id!("$h29iv0s8:example.com"), response.event_id)
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+#[async_test]
+async fn room_attachment_send_stream() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/send/.*"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "url": "mxc://example.com/AQwafuaFswefuhsfAFAgsw",
+            "info": {
+                "mimetype": "video/mp4",
+            }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/media/r0/upload"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(header("content-type", "video/mp4"))
+        .and(header("content-length", "11"))
+        .and(body_string("Hello world"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "content_uri": "mxc://example.com/AQwafuaFswefuhsfAFAgsw"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    mock_encryption_state(&server, false).await;
+
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let _response = client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let content_type: mime::Mime = "video/mp4".parse().unwrap();
+
+    let response = room
+        .send_attachment_stream(
+            "video",
+            &content_type,
+            futures_util::io::Cursor::new(b"Hello world".to_vec()),
+            11,
+            AttachmentConfig::new(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(event_id!("$h29iv0s8:example.com"), response.event_id)
+}
+
 #[async_test]
 async fn room_attachment_send_info() {
     let (client, server) = logged_in_client().await;
@@ -543,6 +768,157 @@ async fn room_redact() {
     assert_eq!(event_id!("$h29iv0s8:example.com"), response.event_id)
 }
 
+#[async_test]
+async fn room_redact_recent_messages() {
+    let (client, server) = synced_client().await;
+    let spammer = user_id!("@spammer:localhost");
+
+    let message = |event_id: &str| {
+        json!({
+            "type": "m.room.message",
+            "event_id": event_id,
+            "sender": spammer,
+            "origin_server_ts": 0,
+            "content": { "msgtype": "m.text", "body": "Spam" },
+        })
+    };
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("from", "first"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "first",
+            "end": "second",
+            "chunk": [
+                message("$spam1:localhost"),
+                {
+                    "type": "m.room.member",
+                    "event_id": "$member:localhost",
+                    "sender": spammer,
+                    "state_key": spammer,
+                    "origin_server_ts": 0,
+                    "content": { "membership": "join" },
+                },
+                {
+                    "type": "m.room.message",
+                    "event_id": "$redacted:localhost",
+                    "sender": spammer,
+                    "origin_server_ts": 0,
+                    "content": {},
+                    "unsigned": {
+                        "redacted_because": {
+                            "type": "m.room.redaction",
+                            "event_id": "$redaction:localhost",
+                            "redacts": "$redacted:localhost",
+                            "sender": "@example:localhost",
+                            "origin_server_ts": 0,
+                            "content": {},
+                        },
+                    },
+                },
+            ],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("from", "second"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "second",
+            "end": "third",
+            "chunk": [message("$spam2:localhost"), message("$spam3:localhost")],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The first request has no `from` token.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "latest",
+            "end": "first",
+            "chunk": [],
+        })))
+        .with_priority(10)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/redact/.*?/.*?"))
+        .and(body_json(json!({ "reason": "Spam" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let event_ids = room.redact_recent_messages(spammer, 2, Some("Spam")).await.unwrap();
+    assert_eq!(
+        event_ids,
+        [event_id!("$spam1:localhost").to_owned(), event_id!("$spam2:localhost").to_owned()]
+    );
+}
+
+#[async_test]
+async fn knock_requests() {
+    let (client, server) = synced_client().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [
+                {
+                    "content": {
+                        "displayname": "Bob",
+                        "membership": "knock",
+                        "reason": "Hi, it's Bob",
+                    },
+                    "event_id": "$knock",
+                    "origin_server_ts": 151800140,
+                    "room_id": *DEFAULT_TEST_ROOM_ID,
+                    "sender": "@bob:example.org",
+                    "state_key": "@bob:example.org",
+                    "type": "m.room.member",
+                },
+            ],
+        })))
+        .mount(&server)
+        .await;
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let requests = room.knock_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].user_id(), "@bob:example.org");
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(body_partial_json(json!({ "user_id": "@bob:example.org" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.accept_knock(user_id!("@bob:example.org")).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/kick$"))
+        .and(body_partial_json(json!({
+            "user_id": "@carol:example.org",
+            "reason": "Not now",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.decline_knock(user_id!("@carol:example.org"), Some("Not now")).await.unwrap();
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
 async fn fetch_members_deduplication() {
@@ -609,3 +985,969 @@ async fn set_name() {
 
     room.set_name(name.to_owned()).await.unwrap();
 }
+
+#[async_test]
+async fn add_and_remove_space_child() {
+    let (client, server) = synced_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    let sync_settings = SyncSettings::new();
+    client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let child_id = room_id!("!child:localhost");
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.child/.*child.*$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "via": ["localhost"],
+            "suggested": true,
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .named("add_space_child")
+        .mount(&server)
+        .await;
+
+    room.add_space_child(child_id, vec![server_name!("localhost").to_owned()], true).await.unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.child/.*child.*$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "via": [] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .named("remove_space_child")
+        .mount(&server)
+        .await;
+
+    room.remove_space_child(child_id).await.unwrap();
+}
+
+#[async_test]
+async fn update_event_power_levels() {
+    let (client, server) = synced_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    let sync_settings = SyncSettings::new();
+    client.sync_once(sync_settings).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let power_levels = room.power_levels().await.unwrap();
+    assert_eq!(power_levels.events[&TimelineEventType::RoomPowerLevels], int!(100));
+    assert!(!power_levels.events.contains_key(&TimelineEventType::RoomTopic));
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "events": {
+                "m.room.power_levels": 100,
+                "m.room.topic": 50,
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.update_event_power_levels(vec![(TimelineEventType::RoomTopic, int!(50))]).await.unwrap();
+}
+
+#[async_test]
+async fn active_call_and_leave() {
+    let (client, server) = logged_in_client().await;
+    let now = MilliSecondsSinceUnixEpoch::now();
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_state_event(
+        StateTestEvent::Custom(json!({
+            "content": {
+                "memberships": [{
+                    "application": "m.call",
+                    "call_id": "",
+                    "scope": "m.room",
+                    "device_id": "DEVICEID",
+                    "expires": 3_600_000,
+                    "foci_active": [],
+                    "membership_id": "0",
+                }],
+            },
+            "event_id": "$call_member",
+            "origin_server_ts": now,
+            "sender": "@example:localhost",
+            "state_key": "@example:localhost",
+            "type": "org.matrix.msc3401.call.member",
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let call = room.active_call().unwrap();
+    assert_eq!(call.started_at(), Some(now));
+    assert_let!([participant] = call.participants.as_slice());
+    assert_eq!(participant.user_id, user_id!("@example:localhost"));
+    assert_eq!(participant.device_id, "DEVICEID");
+    assert_eq!(participant.joined_at, Some(now));
+    assert!(participant.expires_at.unwrap() > now);
+
+    Mock::given(method("PUT"))
+        .and(path_regex(
+            r"^/_matrix/client/r0/rooms/.*/state/org.matrix.msc3401.call.member/.*example:localhost$",
+        ))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "memberships": [] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.leave_room_call().await.unwrap();
+}
+
+#[async_test]
+async fn send_and_update_delayed_state_event() {
+    let (client, server) = synced_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.topic/$"))
+        .and(query_param("org.matrix.msc4140.delay", "30000"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "topic": "Delayed topic" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "delay_id": "abcdef" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let delay_id = room
+        .send_delayed_state_event(
+            &EmptyStateKey,
+            RoomTopicEventContent::new("Delayed topic".to_owned()),
+            Duration::from_secs(30),
+        )
+        .await
+        .unwrap();
+    assert_eq!(delay_id, "abcdef");
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/unstable/org.matrix.msc4140/delayed_events/abcdef"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "action": "restart" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.update_delayed_event(&delay_id, UpdateDelayedEventAction::Restart).await.unwrap();
+}
+
+#[async_test]
+async fn call_signaling_session_answer_and_hangup() {
+    let (client, server) = logged_in_client().await;
+    let room_id = &*DEFAULT_TEST_ROOM_ID;
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(room_id).unwrap();
+    let session = room.call_signaling_session();
+    let mut events = session.subscribe();
+
+    // An invite for another user is ignored.
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_timeline_event(sync_timeline_event!({
+                "content": {
+                    "call_id": "other_call",
+                    "party_id": "alice_party",
+                    "invitee": "@bob:localhost",
+                    "lifetime": 60_000,
+                    "offer": { "type": "offer", "sdp": "v=0" },
+                    "version": "1",
+                },
+                "event_id": "$invite_bob",
+                "origin_server_ts": MilliSecondsSinceUnixEpoch::now(),
+                "sender": "@alice:localhost",
+                "type": "m.call.invite",
+            }))
+            .add_timeline_event(sync_timeline_event!({
+                "content": {
+                    "call_id": "call",
+                    "party_id": "alice_party",
+                    "invitee": "@example:localhost",
+                    "lifetime": 60_000,
+                    "offer": { "type": "offer", "sdp": "v=0" },
+                    "version": "1",
+                },
+                "event_id": "$invite",
+                "origin_server_ts": MilliSecondsSinceUnixEpoch::now(),
+                "sender": "@alice:localhost",
+                "type": "m.call.invite",
+            })),
+    );
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.sender, user_id!("@alice:localhost"));
+    assert_eq!(event.content.call_id().as_str(), "call");
+    assert_eq!(event.state, CallSignalingState::Ringing);
+    assert!(events.try_recv().is_err());
+
+    let call_id = <&VoipId>::from("call");
+    assert_eq!(session.state(call_id), Some(CallSignalingState::Ringing));
+    assert_eq!(session.state(<&VoipId>::from("other_call")), None);
+
+    mock_encryption_state(&server, false).await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/send/m.call.answer/"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "call_id": "call",
+            "party_id": session.party_id(),
+            "answer": { "type": "answer", "sdp": "v=0" },
+            "version": "1",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    session
+        .answer(call_id, SessionDescription::new("answer".to_owned(), "v=0".to_owned()))
+        .await
+        .unwrap();
+    assert_eq!(session.state(call_id), Some(CallSignalingState::Answered));
+
+    // The call can't be answered twice.
+    session
+        .answer(call_id, SessionDescription::new("answer".to_owned(), "v=0".to_owned()))
+        .await
+        .unwrap_err();
+
+    // The caller selects our answer.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        sync_timeline_event!({
+            "content": {
+                "call_id": "call",
+                "party_id": "alice_party",
+                "selected_party_id": session.party_id(),
+                "version": "1",
+            },
+            "event_id": "$select_answer",
+            "origin_server_ts": MilliSecondsSinceUnixEpoch::now(),
+            "sender": "@alice:localhost",
+            "type": "m.call.select_answer",
+        }),
+    ));
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let connected = CallSignalingState::Connected { opponent_party_id: Some("alice_party".into()) };
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.state, connected);
+    assert_eq!(session.state(call_id), Some(connected));
+
+    mock_encryption_state(&server, false).await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/send/m.call.hangup/"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "call_id": "call",
+            "party_id": session.party_id(),
+            "reason": "user_hangup",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    session.hangup(call_id, Reason::UserHangup).await.unwrap();
+    assert_eq!(session.state(call_id), Some(CallSignalingState::Ended));
+}
+
+#[async_test]
+async fn upgrade() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "latest",
+            "end": "first",
+            "chunk": [{
+                "type": "m.room.message",
+                "event_id": "$last:localhost",
+                "sender": "@example:localhost",
+                "origin_server_ts": 0,
+                "content": { "msgtype": "m.text", "body": "Goodbye" },
+            }],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/createRoom"))
+        .and(body_partial_json(json!({
+            "room_version": "10",
+            "creation_content": {
+                "predecessor": {
+                    "room_id": *DEFAULT_TEST_ROOM_ID,
+                    "event_id": "$last:localhost",
+                },
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "room_id": "!replacement:localhost",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.tombstone/$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({
+            "body": "This room has been replaced",
+            "replacement_room": "!replacement:localhost",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let progress = SharedObservable::new(RoomUpgradeProgress::default());
+    let options = RoomUpgradeOptions { copy_bans: false, ..Default::default() };
+    let new_room = room
+        .upgrade(RoomVersionId::V10, options)
+        .with_progress_observable(progress.clone())
+        .await
+        .unwrap();
+
+    assert_eq!(new_room.room_id(), room_id!("!replacement:localhost"));
+    assert_eq!(progress.get(), RoomUpgradeProgress::Done);
+}
+
+#[async_test]
+async fn report_event() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/report/.*spam:localhost$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "score": -100, "reason": "Spam" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.report_event(event_id!("$spam:localhost"), Some(int!(-100)), Some("Spam".to_owned()))
+        .await
+        .unwrap();
+}
+
+#[async_test]
+async fn invite_user_by_email() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let identity_server = IdentityServer::new(client.clone(), Url::parse(&server.uri()).unwrap());
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/user/.*/openid/request_token$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "openid_token",
+            "token_type": "Bearer",
+            "matrix_server_name": "localhost",
+            "expires_in": 3600,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/identity/v2/account/register"))
+        .and(body_partial_json(json!({
+            "access_token": "openid_token",
+            "matrix_server_name": "localhost",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "token": "is_token" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({
+            "id_server": identity_server.server_name(),
+            "id_access_token": "is_token",
+            "medium": "email",
+            "address": "alice@example.org",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    room.invite_user_by_email(&identity_server, "alice@example.org").await.unwrap();
+    // The access token of the identity server is reused.
+    room.invite_user_by_email(&identity_server, "alice@example.org").await.unwrap();
+}
+
+#[async_test]
+async fn pending_third_party_invites() {
+    let (client, server) = logged_in_client().await;
+
+    let third_party_invite = |token: &str, content: serde_json::Value| {
+        StateTestEvent::Custom(json!({
+            "content": content,
+            "event_id": format!("${token}"),
+            "origin_server_ts": 0,
+            "sender": "@example:localhost",
+            "state_key": token,
+            "type": "m.room.third_party_invite",
+        }))
+    };
+    let invite_content = |display_name: &str| {
+        json!({
+            "display_name": display_name,
+            "key_validity_url": "https://identity.localhost/_matrix/identity/v2/pubkey/isvalid",
+            "public_key": "abc",
+        })
+    };
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+            .add_state_event(third_party_invite("pending", invite_content("a...@e...")))
+            .add_state_event(third_party_invite("accepted", invite_content("b...@e...")))
+            .add_state_event(third_party_invite("revoked", json!({})))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "membership": "invite",
+                    "third_party_invite": {
+                        "display_name": "b...@e...",
+                        "signed": {
+                            "mxid": "@bob:localhost",
+                            "signatures": {},
+                            "token": "accepted",
+                        },
+                    },
+                },
+                "event_id": "$bob_invite",
+                "origin_server_ts": 0,
+                "sender": "@example:localhost",
+                "state_key": "@bob:localhost",
+                "type": "m.room.member",
+            }))),
+    );
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    let invites = room.pending_third_party_invites().await.unwrap();
+
+    assert_let!([invite] = invites.as_slice());
+    assert_eq!(invite.token, "pending");
+    assert_eq!(invite.display_name, "a...@e...");
+    assert_eq!(invite.sender, user_id!("@example:localhost"));
+}
+
+#[async_test]
+async fn set_is_favourite() {
+    let (client, server) = logged_in_client().await;
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_account_data(
+        RoomAccountDataTestEvent::Custom(json!({
+            "content": {
+                "tags": { "m.lowpriority": { "order": 0.2 } },
+            },
+            "type": "m.tag",
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    assert!(room.is_low_priority());
+    assert_eq!(room.low_priority_order(), Some(0.2));
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/user/.*/rooms/.*/tags/m.favourite$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "order": 0.5 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The room can't be both a favourite and low priority.
+    Mock::given(method("DELETE"))
+        .and(path_regex(r"^/_matrix/client/r0/user/.*/rooms/.*/tags/m.lowpriority$"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.set_is_favourite(true, Some(0.5)).await.unwrap();
+    server.verify().await;
+
+    let tags = room.subscribe_to_notable_tags();
+    pin_mut!(tags);
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID).add_account_data(
+        RoomAccountDataTestEvent::Custom(json!({
+            "content": {
+                "tags": { "m.favourite": { "order": 0.5 } },
+            },
+            "type": "m.tag",
+        })),
+    ));
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert_eq!(tags.next().now_or_never(), Some(Some(RoomNotableTags::FAVOURITE)));
+    assert!(room.is_favourite());
+    assert!(!room.is_low_priority());
+    assert_eq!(room.favourite_order(), Some(0.5));
+}
+
+#[async_test]
+async fn pin_and_unpin_event() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+    assert!(room.pinned_event_ids().is_empty());
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.pinned_events/?$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "pinned": ["$a"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert!(room.pin_event(event_id!("$a")).await.unwrap());
+    // Unpinning an event that is not pinned doesn't send a request.
+    assert!(!room.unpin_event(event_id!("$b")).await.unwrap());
+    server.verify().await;
+
+    let pinned_event_ids = room.subscribe_to_pinned_event_ids();
+    pin_mut!(pinned_event_ids);
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": { "pinned": ["$a"] },
+                "event_id": "$pinned",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "",
+                "type": "m.room.pinned_events",
+            })))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "state_default": 50,
+                    "users": { "@example:localhost": 0 },
+                },
+                "event_id": "$power_levels",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "",
+                "type": "m.room.power_levels",
+            }))),
+    );
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert_eq!(
+        pinned_event_ids.next().now_or_never(),
+        Some(Some(vec![event_id!("$a").to_owned()]))
+    );
+    assert_eq!(room.pinned_event_ids(), [event_id!("$a").to_owned()]);
+
+    // The current user is not allowed to change the pinned events anymore.
+    let error = room.unpin_event(event_id!("$a")).await.unwrap_err();
+    assert_matches!(error, Error::InsufficientPowerLevel(StateEventType::RoomPinnedEvents));
+}
+
+#[async_test]
+async fn set_join_rule_history_visibility_and_guest_access() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.join_rules/?$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "join_rule": "invite" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.history_visibility/?$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "history_visibility": "joined" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.guest_access/?$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({ "guest_access": "forbidden" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    room.set_join_rule(JoinRule::Invite).await.unwrap();
+    room.set_history_visibility(HistoryVisibility::Joined).await.unwrap();
+    room.set_guest_access(GuestAccess::Forbidden).await.unwrap();
+
+    // A restricted join rule needs allowed rooms.
+    let error =
+        room.set_join_rule(JoinRule::Restricted(Restricted::new(vec![]))).await.unwrap_err();
+    assert_matches!(error, Error::RoomSettings(RoomSettingsError::RestrictedWithoutAllowedRooms));
+
+    // The room uses version 1, which doesn't support restricted join rules.
+    let allow = vec![AllowRule::room_membership(room_id!("!space:localhost").to_owned())];
+    let error = room.set_join_rule(JoinRule::Restricted(Restricted::new(allow))).await.unwrap_err();
+    assert_matches!(
+        error,
+        Error::RoomSettings(RoomSettingsError::UnsupportedJoinRule(RoomVersionId::V1))
+    );
+}
+
+#[async_test]
+async fn set_space_child_and_list_children() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.child/.*child.*$"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_json(json!({
+            "via": ["localhost"],
+            "order": "b",
+            "suggested": true,
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The child is not known, so the parent can't be set in it.
+    let options = assign!(SpaceChildOptions::default(), {
+        order: Some("b".to_owned()),
+        suggested: true,
+    });
+    assert!(!room.set_space_child(room_id!("!child:localhost"), options).await.unwrap());
+
+    // The order must only contain printable ASCII characters.
+    let options = assign!(SpaceChildOptions::default(), { order: Some("\n".to_owned()) });
+    let error = room.set_space_child(room_id!("!child:localhost"), options).await.unwrap_err();
+    assert_matches!(error, Error::RoomSettings(RoomSettingsError::InvalidSpaceChildOrder(_)));
+    server.verify().await;
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(&DEFAULT_TEST_ROOM_ID)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": { "via": ["localhost"] },
+                "event_id": "$child_a",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "!a:localhost",
+                "type": "m.space.child",
+            })))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": { "via": ["localhost"], "order": "b", "suggested": true },
+                "event_id": "$child_b",
+                "origin_server_ts": 151393756,
+                "sender": "@example:localhost",
+                "state_key": "!b:localhost",
+                "type": "m.space.child",
+            })))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": { "via": [] },
+                "event_id": "$child_c",
+                "origin_server_ts": 151393757,
+                "sender": "@example:localhost",
+                "state_key": "!c:localhost",
+                "type": "m.space.child",
+            }))),
+    );
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    // The children with an order come first, and the removed ones are skipped.
+    let children = room.space_children().await.unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].room_id, "!b:localhost");
+    assert_eq!(children[0].order.as_deref(), Some("b"));
+    assert!(children[0].suggested);
+    assert_eq!(children[1].room_id, "!a:localhost");
+    assert!(!children[1].suggested);
+}
+
+#[async_test]
+async fn save_load_and_clear_draft() {
+    let (client, _server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    assert_eq!(room.load_draft().await.unwrap(), None);
+
+    let draft = ComposerDraft {
+        plain_text: "Hello".to_owned(),
+        html_text: None,
+        draft_type: ComposerDraftType::Edit { event_id: event_id!("$edited").to_owned() },
+    };
+    room.save_draft(draft.clone()).await.unwrap();
+    assert_eq!(room.load_draft().await.unwrap(), Some(draft));
+
+    room.clear_draft().await.unwrap();
+    assert_eq!(room.load_draft().await.unwrap(), None);
+}
+
+#[async_test]
+async fn schedule_list_and_cancel_messages() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    let now = MilliSecondsSinceUnixEpoch::now();
+    let later = MilliSecondsSinceUnixEpoch(now.0 + uint!(3_600_000));
+    let later_txn_id =
+        room.schedule_message(RoomMessageEventContent::text_plain("Later"), later).await.unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/send/m.room.message/"))
+        .and(body_partial_json(json!({ "body": "Overdue" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // A message whose time already passed is sent right away.
+    let past = MilliSecondsSinceUnixEpoch(now.0 - uint!(1_000));
+    let past_txn_id =
+        room.schedule_message(RoomMessageEventContent::text_plain("Overdue"), past).await.unwrap();
+
+    let mut scheduled = room.scheduled_messages().await.unwrap();
+    for _ in 0..50 {
+        if scheduled.len() == 1 {
+            break;
+        }
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        scheduled = room.scheduled_messages().await.unwrap();
+    }
+
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(scheduled[0].transaction_id, later_txn_id);
+    assert_eq!(scheduled[0].send_at, later);
+    assert_eq!(client.scheduled_messages().await.unwrap().len(), 1);
+
+    // The sent message can't be cancelled anymore.
+    assert!(!room.cancel_scheduled_message(&past_txn_id).await.unwrap());
+
+    assert!(room.cancel_scheduled_message(&later_txn_id).await.unwrap());
+    assert!(room.scheduled_messages().await.unwrap().is_empty());
+}
+
+#[async_test]
+async fn event_cache_fills_gaps_without_duplicates() {
+    let (client, server) = logged_in_client().await;
+    let room_id = &*DEFAULT_TEST_ROOM_ID;
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(room_id).unwrap();
+    let event_cache = room.event_cache().await;
+    let (events, mut updates) = event_cache.subscribe().await;
+    assert!(events.is_empty());
+
+    let message = |event_id: &str| {
+        json!({
+            "content": { "body": event_id, "msgtype": "m.text" },
+            "event_id": event_id,
+            "origin_server_ts": 152037280,
+            "room_id": room_id,
+            "sender": "@alice:localhost",
+            "type": "m.room.message",
+        })
+    };
+
+    // A limited sync leaves a gap before its events.
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_timeline_bulk(
+                [message("$c"), message("$d")].map(|event| Raw::new(&event).unwrap().cast()),
+            )
+            .set_timeline_limited()
+            .set_timeline_prev_batch("gap".to_owned()),
+    );
+    server.reset().await;
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert_let!(
+        Ok(Ok(RoomEventCacheUpdate::UpdateEvents { diffs })) =
+            tokio::time::timeout(Duration::from_secs(1), updates.recv()).await
+    );
+    assert_eq!(diffs.len(), 2);
+    assert_eq!(event_cache.num_gaps().await, 1);
+
+    // The pagination returns an event that is already known, which is not
+    // duplicated.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("from", "gap"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "gap",
+            "chunk": [message("$c"), message("$b"), message("$a")],
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let outcome = event_cache.paginate_backwards(10).await.unwrap();
+    assert!(outcome.reached_start);
+    assert_eq!(event_cache.num_gaps().await, 0);
+
+    let event_ids = event_cache
+        .events()
+        .await
+        .iter()
+        .map(|event| event.event_id().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(event_ids, ["$a", "$b", "$c", "$d"]);
+
+    assert_let!(Ok(RoomEventCacheUpdate::UpdateEvents { diffs }) = updates.recv().await);
+    assert_matches!(&diffs[0], VectorDiff::Remove { index: 0 });
+    assert_eq!(diffs.len(), 4);
+}
+
+#[async_test]
+async fn event_cache_is_restored_after_restart() {
+    let store = Arc::new(MemoryStore::new());
+    let room_id = &*DEFAULT_TEST_ROOM_ID;
+
+    let (client_builder, server) = MockServerBuilder::new().build_client_builder().await;
+    let client = client_builder
+        .store_config(StoreConfig::new().state_store(store.clone()))
+        .build()
+        .await
+        .unwrap();
+    client.restore_session(default_session()).await.unwrap();
+
+    let mut ev_builder = SyncResponseBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+    server.mock_sync(ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let event_cache = client.get_room(room_id).unwrap().event_cache().await;
+    let (_, mut updates) = event_cache.subscribe().await;
+
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_timeline_event(sync_timeline_event!({
+                "content": { "body": "Hello", "msgtype": "m.text" },
+                "event_id": "$a",
+                "origin_server_ts": 152037280,
+                "sender": "@alice:localhost",
+                "type": "m.room.message",
+            }))
+            .set_timeline_limited()
+            .set_timeline_prev_batch("gap".to_owned()),
+    );
+    server.server().reset().await;
+    server.mock_sync(ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert_let!(
+        Ok(Ok(RoomEventCacheUpdate::UpdateEvents { .. })) =
+            tokio::time::timeout(Duration::from_secs(1), updates.recv()).await
+    );
+
+    // A new client using the same store gets the events and the gap back.
+    let (client_builder, _server) = MockServerBuilder::new().build_client_builder().await;
+    let client =
+        client_builder.store_config(StoreConfig::new().state_store(store)).build().await.unwrap();
+    client.restore_session(default_session()).await.unwrap();
+
+    let event_cache = client.get_room(room_id).unwrap().event_cache().await;
+    let event_ids = event_cache
+        .events()
+        .await
+        .iter()
+        .map(|event| event.event_id().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(event_ids, ["$a"]);
+    assert_eq!(event_cache.num_gaps().await, 1);
+}
+
+#[async_test]
+async fn retried_send_with_same_transaction_id_is_not_sent_again() {
+    let (client, server) = synced_client().await;
+    let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/send/m.room.message/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let event_id = event_id!("$h29iv0s8:example.com");
+    let txn_id = TransactionId::new();
+    assert_eq!(room.event_id_for_transaction(&txn_id).await.unwrap(), None);
+
+    let response = room
+        .send(RoomMessageEventContent::text_plain("Hello"))
+        .with_transaction_id(&txn_id)
+        .await
+        .unwrap();
+    assert_eq!(response.event_id, event_id);
+
+    assert_eq!(room.event_id_for_transaction(&txn_id).await.unwrap().as_deref(), Some(event_id));
+    assert_eq!(room.transaction_id_for_event(event_id).await.unwrap(), Some(txn_id.clone()));
+
+    // Retrying the send returns the same event without sending it again.
+    let response = room
+        .send(RoomMessageEventContent::text_plain("Hello"))
+        .with_transaction_id(&txn_id)
+        .await
+        .unwrap();
+    assert_eq!(response.event_id, event_id);
+}
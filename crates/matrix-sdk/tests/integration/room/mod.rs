@@ -1,4 +1,5 @@
 mod common;
+mod invited;
 mod joined;
 mod left;
 mod notification_mode;
@@ -20,7 +20,9 @@ use futures_util::FutureExt;
 use matrix_sdk::{
     config::SyncSettings,
     widget::{
-        Capabilities, CapabilitiesProvider, WidgetDriver, WidgetDriverHandle, WidgetSettings,
+        Capabilities, CapabilitiesProvider, ClientProperties, ElementCallOptions,
+        ElementCallWidget, JitsiWidgetData, RoomWidget, WidgetDriver, WidgetDriverHandle,
+        WidgetSettings,
     },
     Client,
 };
@@ -37,7 +39,7 @@ use ruma::{
         topic::RoomTopicEventContent,
     },
     owned_room_id,
-    serde::JsonObject,
+    serde::{JsonObject, Raw},
     user_id, OwnedRoomId,
 };
 use serde::Serialize;
@@ -600,6 +602,157 @@ async fn send_room_name() {
     mock_server.verify().await;
 }
 
+#[async_test]
+async fn element_call_widget_url() {
+    let (client, mock_server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder.add_joined_room(JoinedRoomBuilder::new(&ROOM_ID));
+
+    mock_sync(&mock_server, sync_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings).await.unwrap();
+    mock_server.reset().await;
+
+    mock_encryption_state(&mock_server, true).await;
+
+    let room = client.get_room(&ROOM_ID).unwrap();
+    let options = ElementCallOptions {
+        element_call_url: "https://call.element.io".to_owned(),
+        widget_id: WIDGET_ID.to_owned(),
+        parent_url: None,
+        font_scale: None,
+        font: None,
+        skip_lobby: None,
+        analytics_id: None,
+        encryption: None,
+    };
+    let widget = ElementCallWidget::new(
+        &room,
+        options,
+        ClientProperties::new("io.example.client", None, None),
+    )
+    .await
+    .unwrap();
+
+    let url = widget.url();
+    assert_eq!(url.path(), "/room");
+
+    // The parameters are in the fragment, so they are not sent to the server.
+    assert_eq!(url.query(), None);
+    let params: Vec<(String, String)> =
+        serde_html_form::from_str(url.fragment().unwrap().trim_start_matches('?')).unwrap();
+    let param =
+        |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+
+    assert_eq!(param("roomId"), Some(ROOM_ID.as_str()));
+    assert_eq!(param("userId"), Some("@example:localhost"));
+    assert_eq!(param("widgetId"), Some(WIDGET_ID));
+    assert_eq!(param("clientId"), Some("io.example.client"));
+    // The room is encrypted, so the call is too.
+    assert_eq!(param("perParticipantE2EE"), Some("true"));
+    // The user opted out of analytics.
+    assert_eq!(param("analyticsId"), None);
+}
+
+#[async_test]
+async fn list_and_remove_room_widgets() {
+    let (client, mock_server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let state_events: Vec<Raw<_>> = [
+        json!({
+            "content": {
+                "type": "jitsi",
+                "url": "https://app.example.org/jitsi.html?confId=$conferenceId\
+                    &domain=$domain&userId=$matrix_user_id",
+                "name": "Jitsi",
+                "data": {
+                    "domain": "meet.example.org",
+                    "conferenceId": "JitsiConference",
+                    "isAudioOnly": false,
+                },
+            },
+            "event_id": "$jitsi",
+            "origin_server_ts": 151800000,
+            "sender": "@alice:example.org",
+            "state_key": "jitsi",
+            "type": "im.vector.modular.widgets",
+        }),
+        json!({
+            "content": {},
+            "event_id": "$removed",
+            "origin_server_ts": 151800001,
+            "sender": "@alice:example.org",
+            "state_key": "removed",
+            "type": "im.vector.modular.widgets",
+        }),
+        json!({
+            "content": {
+                "type": "m.custom",
+                "url": "https://custom.example.org/",
+                "waitForIframeLoad": false,
+            },
+            "event_id": "$custom",
+            "origin_server_ts": 151800002,
+            "sender": "@bob:example.org",
+            "state_key": "custom",
+            "type": "m.widget",
+        }),
+    ]
+    .into_iter()
+    .map(|event| serde_json::from_value(event).unwrap())
+    .collect();
+
+    let mut sync_builder = SyncResponseBuilder::new();
+    sync_builder.add_joined_room(JoinedRoomBuilder::new(&ROOM_ID).add_state_bulk(state_events));
+
+    mock_sync(&mock_server, sync_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings).await.unwrap();
+    mock_server.reset().await;
+
+    let room = client.get_room(&ROOM_ID).unwrap();
+    let widgets = RoomWidget::list(&room).await.unwrap();
+    assert_eq!(widgets.len(), 2);
+
+    let custom = &widgets[0];
+    assert_eq!(custom.id, "custom");
+    assert_eq!(custom.sender, "@bob:example.org");
+    assert!(!custom.content.is_jitsi());
+    assert!(custom.settings().unwrap().init_on_content_load());
+
+    let jitsi = &widgets[1];
+    assert_eq!(jitsi.id, "jitsi");
+    assert_eq!(jitsi.content.name.as_deref(), Some("Jitsi"));
+    assert_eq!(
+        jitsi.content.jitsi_data(),
+        Some(JitsiWidgetData {
+            domain: "meet.example.org".to_owned(),
+            conference_id: "JitsiConference".to_owned(),
+            is_audio_only: false,
+            room_name: None,
+            auth: None,
+        })
+    );
+    assert_eq!(
+        jitsi.content.url_with_data().unwrap().as_str(),
+        "https://app.example.org/jitsi.html?confId=JitsiConference\
+            &domain=meet.example.org&userId=$matrix_user_id"
+    );
+    assert!(!jitsi.settings().unwrap().init_on_content_load());
+
+    // Only the widget type with an existing widget is emptied.
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/im.vector.modular.widgets/jitsi$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$foobar" })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    RoomWidget::remove(&room, "jitsi").await.unwrap();
+    mock_server.verify().await;
+}
+
 async fn negotiate_capabilities(driver_handle: &WidgetDriverHandle, caps: JsonValue) {
     {
         // Receive toWidget capabilities request
@@ -0,0 +1,377 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A mock homeserver to write integration tests with the Matrix SDK.
+//!
+//! [`MockServerBuilder`] starts a [`wiremock`] server and builds a [`Client`]
+//! connected to it, optionally logged in and synced. The returned
+//! [`MatrixMockServer`] has helpers to mock the most common endpoints, and
+//! the underlying [`MockServer`] can be used to mock any other endpoint.
+//!
+//! ```no_run
+//! use matrix_sdk::ruma::events::room::message::RoomMessageEventContent;
+//! use matrix_sdk_test::{test_json, DEFAULT_TEST_ROOM_ID};
+//! use matrix_sdk_test_server::MockServerBuilder;
+//!
+//! # async {
+//! let (client, server) = MockServerBuilder::new().logged_in().build().await;
+//!
+//! server.mock_sync(&*test_json::SYNC, None).await;
+//! client.sync_once(Default::default()).await?;
+//!
+//! server.mock_room_send(&*test_json::EVENT_ID).await;
+//! let room = client.get_room(&DEFAULT_TEST_ROOM_ID).unwrap();
+//! room.send(RoomMessageEventContent::text_plain("Hello world")).await?;
+//! # matrix_sdk::Result::Ok(()) };
+//! ```
+#![cfg(not(target_arch = "wasm32"))]
+#![warn(missing_docs, missing_debug_implementations)]
+
+use matrix_sdk::{
+    config::{RequestConfig, SyncSettings},
+    matrix_auth::{MatrixSession, MatrixSessionTokens},
+    Client, ClientBuilder, SessionMeta,
+};
+use matrix_sdk_test::{test_json, MockClock};
+use ruma::{api::MatrixVersion, device_id, user_id};
+use serde::Serialize;
+pub use wiremock;
+use wiremock::{
+    matchers::{header, method, path, path_regex, query_param, query_param_is_missing},
+    Mock, MockBuilder, MockServer, ResponseTemplate,
+};
+
+/// The access token of the session of [`MockServerBuilder::logged_in()`].
+pub const DEFAULT_ACCESS_TOKEN: &str = "1234";
+
+/// The session used by [`MockServerBuilder::logged_in()`].
+///
+/// The user is `@example:localhost`, with the device `DEVICEID` and the
+/// [`DEFAULT_ACCESS_TOKEN`].
+pub fn default_session() -> MatrixSession {
+    MatrixSession {
+        meta: SessionMeta {
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        },
+        tokens: MatrixSessionTokens {
+            access_token: DEFAULT_ACCESS_TOKEN.to_owned(),
+            refresh_token: None,
+        },
+    }
+}
+
+/// Builder for a mock homeserver and a [`Client`] connected to it.
+#[derive(Debug)]
+pub struct MockServerBuilder {
+    server_versions: Vec<MatrixVersion>,
+    retry: bool,
+    session: Option<MatrixSession>,
+    initial_sync: Option<serde_json::Value>,
+    clock: Option<MockClock>,
+}
+
+impl MockServerBuilder {
+    /// Create a new `MockServerBuilder`.
+    ///
+    /// By default, the homeserver only supports Matrix 1.0, the requests of
+    /// the client are not retried and the client is not logged in.
+    pub fn new() -> Self {
+        Self {
+            server_versions: vec![MatrixVersion::V1_0],
+            retry: false,
+            session: None,
+            initial_sync: None,
+            clock: None,
+        }
+    }
+
+    /// Set the versions of the Matrix specification supported by the
+    /// homeserver.
+    pub fn server_versions(mut self, versions: impl IntoIterator<Item = MatrixVersion>) -> Self {
+        self.server_versions = versions.into_iter().collect();
+        self
+    }
+
+    /// Retry the requests of the client that fail, with the default
+    /// [`RequestConfig`].
+    pub fn with_retry(mut self) -> Self {
+        self.retry = true;
+        self
+    }
+
+    /// Log the client in with the [`default_session()`].
+    pub fn logged_in(self) -> Self {
+        self.session(default_session())
+    }
+
+    /// Log the client in with the given session.
+    pub fn session(mut self, session: MatrixSession) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// Sync the client once with the given response, after logging in.
+    ///
+    /// The client must be logged in.
+    pub fn synced(mut self, response_body: impl Serialize) -> Self {
+        self.initial_sync =
+            Some(serde_json::to_value(response_body).expect("sync response should serialize"));
+        self
+    }
+
+    /// Use the given clock for the timers of the client, so the test can move
+    /// time forward with [`MockClock::advance()`].
+    pub fn clock(mut self, clock: MockClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Start the homeserver and build a [`ClientBuilder`] connected to it, to
+    /// configure the client further.
+    ///
+    /// The session and the initial sync are ignored.
+    pub async fn build_client_builder(&self) -> (ClientBuilder, MatrixMockServer) {
+        let server = MockServer::start().await;
+        let mut builder = Client::builder()
+            .homeserver_url(server.uri())
+            .server_versions(self.server_versions.clone());
+
+        if !self.retry {
+            builder = builder.request_config(RequestConfig::new().disable_retry());
+        }
+
+        if let Some(clock) = &self.clock {
+            builder = builder.clock(clock.clone());
+        }
+
+        let access_token = self
+            .session
+            .as_ref()
+            .map_or(DEFAULT_ACCESS_TOKEN, |session| &session.tokens.access_token)
+            .to_owned();
+
+        (builder, MatrixMockServer { server, access_token })
+    }
+
+    /// Start the homeserver and build a [`Client`] connected to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client can't be built, logged in or synced.
+    pub async fn build(self) -> (Client, MatrixMockServer) {
+        let (builder, server) = self.build_client_builder().await;
+        let client = builder.build().await.expect("client should build");
+
+        if let Some(session) = self.session {
+            client.restore_session(session).await.expect("session should be restored");
+        }
+
+        if let Some(response_body) = self.initial_sync {
+            server.mock_sync(response_body, None).await;
+            client.sync_once(SyncSettings::new()).await.expect("initial sync should succeed");
+        }
+
+        (client, server)
+    }
+}
+
+impl Default for MockServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A mock homeserver, with helpers to mock the endpoints used by a logged-in
+/// client.
+///
+/// The mocks only match the requests authenticated with the access token of
+/// the session of the [`MockServerBuilder`].
+#[derive(Debug)]
+pub struct MatrixMockServer {
+    server: MockServer,
+    access_token: String,
+}
+
+impl MatrixMockServer {
+    /// The underlying [`MockServer`], to mock other endpoints.
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Get the underlying [`MockServer`].
+    pub fn into_server(self) -> MockServer {
+        self.server
+    }
+
+    /// The URI of the homeserver.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Start mocking an authenticated request with the given method and path
+    /// regex.
+    pub fn mock_authenticated(&self, http_method: &str, path_pattern: &str) -> MockBuilder {
+        Mock::given(method(http_method))
+            .and(path_regex(path_pattern))
+            .and(header("authorization", format!("Bearer {}", self.access_token).as_str()))
+    }
+
+    /// Mock the `GET /sync` endpoint, with an optional `since` param, to
+    /// respond with the given body.
+    pub async fn mock_sync(&self, response_body: impl Serialize, since: Option<String>) {
+        mock_sync_with_token(&self.server, &self.access_token, response_body, since).await;
+    }
+
+    /// Mock the `GET /rooms/{roomId}/state/m.room.encryption` endpoint, to
+    /// respond with an encryption event or a 404.
+    pub async fn mock_encryption_state(&self, is_encrypted: bool) {
+        mock_encryption_state_with_token(&self.server, &self.access_token, is_encrypted).await;
+    }
+
+    /// Mock the `PUT /rooms/{roomId}/send/{eventType}/{txnId}` endpoint, to
+    /// respond with the given body, like [`test_json::EVENT_ID`].
+    pub async fn mock_room_send(&self, response_body: impl Serialize) {
+        self.mock_authenticated("PUT", r"^/_matrix/client/r0/rooms/.*/send/.*")
+            .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `PUT /rooms/{roomId}/state/{eventType}/{stateKey}` endpoint,
+    /// to respond with the given body, like [`test_json::EVENT_ID`].
+    pub async fn mock_room_send_state(&self, response_body: impl Serialize) {
+        self.mock_authenticated("PUT", r"^/_matrix/client/r0/rooms/.*/state/.*")
+            .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `GET /rooms/{roomId}/messages` endpoint, to respond with the
+    /// given body.
+    pub async fn mock_room_messages(&self, response_body: impl Serialize) {
+        self.mock_authenticated("GET", r"^/_matrix/client/r0/rooms/.*/messages$")
+            .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `GET /rooms/{roomId}/members` endpoint, to respond with the
+    /// given body, like [`test_json::MEMBERS`].
+    pub async fn mock_room_members(&self, response_body: impl Serialize) {
+        self.mock_authenticated("GET", r"^/_matrix/client/r0/rooms/.*/members")
+            .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `GET /rooms/{roomId}/event/{eventId}` endpoint, to respond
+    /// with the given event.
+    pub async fn mock_room_event(&self, event: impl Serialize) {
+        self.mock_authenticated("GET", r"^/_matrix/client/r0/rooms/.*/event/.*")
+            .respond_with(ResponseTemplate::new(200).set_body_json(event))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `POST /join/{roomIdOrAlias}` and
+    /// `POST /rooms/{roomId}/join` endpoints, to respond with the given body,
+    /// like [`test_json::ROOM_ID`].
+    pub async fn mock_join_room(&self, response_body: impl Serialize) {
+        let response = ResponseTemplate::new(200).set_body_json(response_body);
+
+        self.mock_authenticated("POST", r"^/_matrix/client/r0/join/")
+            .respond_with(response.clone())
+            .mount(&self.server)
+            .await;
+        self.mock_authenticated("POST", r"^/_matrix/client/r0/rooms/.*/join")
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Mock the `POST /rooms/{roomId}/leave` endpoint, to respond with an
+    /// empty body.
+    pub async fn mock_leave_room(&self) {
+        self.mock_authenticated("POST", r"^/_matrix/client/r0/rooms/.*/leave$")
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+            .mount(&self.server)
+            .await;
+    }
+}
+
+/// Mount a mock on the given server to handle the `GET /sync` endpoint with an
+/// optional `since` param, that returns a 200 status code with the given
+/// response body.
+///
+/// The request must be authenticated with the [`DEFAULT_ACCESS_TOKEN`].
+pub async fn mock_sync(server: &MockServer, response_body: impl Serialize, since: Option<String>) {
+    mock_sync_with_token(server, DEFAULT_ACCESS_TOKEN, response_body, since).await;
+}
+
+/// Mount a mock on the given server to handle the `GET
+/// /rooms/.../state/m.room.encryption` endpoint with an option whether it
+/// should return an encryption event or not.
+///
+/// The request must be authenticated with the [`DEFAULT_ACCESS_TOKEN`].
+pub async fn mock_encryption_state(server: &MockServer, is_encrypted: bool) {
+    mock_encryption_state_with_token(server, DEFAULT_ACCESS_TOKEN, is_encrypted).await;
+}
+
+async fn mock_sync_with_token(
+    server: &MockServer,
+    access_token: &str,
+    response_body: impl Serialize,
+    since: Option<String>,
+) {
+    let mut builder = Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(header("authorization", format!("Bearer {access_token}").as_str()));
+
+    if let Some(since) = since {
+        builder = builder.and(query_param("since", since));
+    } else {
+        builder = builder.and(query_param_is_missing("since"));
+    }
+
+    builder
+        .respond_with(ResponseTemplate::new(200).set_body_json(response_body))
+        .mount(server)
+        .await;
+}
+
+async fn mock_encryption_state_with_token(
+    server: &MockServer,
+    access_token: &str,
+    is_encrypted: bool,
+) {
+    let builder = Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.*room.*encryption.?"))
+        .and(header("authorization", format!("Bearer {access_token}").as_str()));
+
+    if is_encrypted {
+        builder
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(&*test_json::sync_events::ENCRYPTION_CONTENT),
+            )
+            .mount(server)
+            .await;
+    } else {
+        builder
+            .respond_with(ResponseTemplate::new(404).set_body_json(&*test_json::NOT_FOUND))
+            .mount(server)
+            .await;
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use matrix_sdk_common::{
+    instant::Instant,
+    time::{Clock, SleepFuture},
+};
+use ruma::{MilliSecondsSinceUnixEpoch, UInt};
+
+/// A [`Clock`] whose time only moves forward when [`MockClock::advance()`] is
+/// called.
+///
+/// The clock can be cloned, and all the clones share the same time, so one of
+/// them can be given to the client and another one kept by the test.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    inner: Arc<MockClockInner>,
+}
+
+#[derive(Debug)]
+struct MockClockInner {
+    /// The monotonic time when the clock was created.
+    start: Instant,
+    /// The timestamp when the clock was created.
+    start_timestamp: MilliSecondsSinceUnixEpoch,
+    /// The time elapsed since the clock was created.
+    elapsed: Mutex<Duration>,
+    /// The wakers of the pending sleeps.
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl MockClock {
+    /// Create a new `MockClock`, starting at the current time of the system.
+    pub fn new() -> Self {
+        Self::with_timestamp(MilliSecondsSinceUnixEpoch::now())
+    }
+
+    /// Create a new `MockClock`, starting at the given timestamp.
+    pub fn with_timestamp(start_timestamp: MilliSecondsSinceUnixEpoch) -> Self {
+        Self {
+            inner: Arc::new(MockClockInner {
+                start: Instant::now(),
+                start_timestamp,
+                elapsed: Default::default(),
+                wakers: Default::default(),
+            }),
+        }
+    }
+
+    /// The time elapsed since the clock was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.inner.elapsed.lock().unwrap()
+    }
+
+    /// Move the time forward by the given duration, and wake up the sleeps
+    /// that are over.
+    ///
+    /// The tasks that were sleeping still need to be polled by the runtime,
+    /// so tests should yield afterwards before checking their effects.
+    pub fn advance(&self, duration: Duration) {
+        *self.inner.elapsed.lock().unwrap() += duration;
+
+        for waker in self.inner.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.inner.start + self.elapsed()
+    }
+
+    fn timestamp(&self) -> MilliSecondsSinceUnixEpoch {
+        let elapsed = UInt::try_from(self.elapsed().as_millis()).unwrap_or(UInt::MAX);
+        MilliSecondsSinceUnixEpoch(self.inner.start_timestamp.0.saturating_add(elapsed))
+    }
+
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        Box::pin(MockSleep { clock: self.clone(), deadline: self.elapsed() + duration })
+    }
+}
+
+/// The future returned by [`MockClock::sleep()`].
+struct MockSleep {
+    clock: MockClock,
+    /// The elapsed time of the clock at which the sleep is over.
+    deadline: Duration,
+}
+
+impl Future for MockSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Hold the lock of the wakers while checking the time, to not miss a
+        // call to `advance()` in between.
+        let mut wakers = self.clock.inner.wakers.lock().unwrap();
+
+        if self.clock.elapsed() >= self.deadline {
+            Poll::Ready(())
+        } else {
+            wakers.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
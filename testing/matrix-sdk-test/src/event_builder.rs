@@ -23,11 +23,13 @@ use ruma::{
     events::{
         receipt::{Receipt, ReceiptEventContent, ReceiptThread, ReceiptType},
         relation::Annotation,
-        AnySyncTimelineEvent, AnyTimelineEvent, MessageLikeEventContent,
-        RedactedMessageLikeEventContent, RedactedStateEventContent, StateEventContent,
+        AnyStrippedStateEvent, AnySyncEphemeralRoomEvent, AnySyncTimelineEvent, AnyTimelineEvent,
+        MessageLikeEventContent, RedactedMessageLikeEventContent, RedactedStateEventContent,
+        StateEventContent,
     },
     serde::Raw,
-    server_name, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId, UserId,
+    server_name, EventId, MilliSecondsSinceUnixEpoch, MxcUri, OwnedEventId, OwnedUserId, RoomId,
+    UserId,
 };
 use serde_json::{json, Value as JsonValue};
 
@@ -255,6 +257,340 @@ impl EventBuilder {
         })
     }
 
+    /// Make an `m.room.redaction` event with a reason.
+    pub fn make_redaction_event_with_reason(
+        &self,
+        sender: &UserId,
+        redacts: &EventId,
+        reason: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.room.redaction",
+            "content": {
+                "reason": reason,
+            },
+            "redacts": redacts,
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.sticker` event with the given description and image URL.
+    pub fn make_sync_sticker(
+        &self,
+        sender: &UserId,
+        body: &str,
+        url: &MxcUri,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.sticker",
+            "content": {
+                "body": body,
+                "info": {
+                    "h": 256,
+                    "w": 256,
+                    "mimetype": "image/png",
+                    "size": 4096,
+                },
+                "url": url,
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an unstable poll start event, with a single selection allowed and
+    /// the given answers, whose IDs are their index.
+    pub fn make_sync_poll_start(
+        &self,
+        sender: &UserId,
+        question: &str,
+        answers: &[&str],
+    ) -> Raw<AnySyncTimelineEvent> {
+        let answers: Vec<_> = answers
+            .iter()
+            .enumerate()
+            .map(|(id, answer)| {
+                json!({
+                    "id": id.to_string(),
+                    "org.matrix.msc1767.text": answer,
+                })
+            })
+            .collect();
+
+        sync_timeline_event!({
+            "type": "org.matrix.msc3381.poll.start",
+            "content": {
+                "org.matrix.msc3381.poll.start": {
+                    "question": {
+                        "org.matrix.msc1767.text": question,
+                    },
+                    "kind": "org.matrix.msc3381.poll.undisclosed",
+                    "max_selections": 1,
+                    "answers": answers,
+                },
+                "org.matrix.msc1767.text": question,
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an unstable poll response event, voting for the answers with the
+    /// given IDs.
+    pub fn make_sync_poll_response(
+        &self,
+        sender: &UserId,
+        poll_start_id: &EventId,
+        answers: &[&str],
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "org.matrix.msc3381.poll.response",
+            "content": {
+                "org.matrix.msc3381.poll.response": {
+                    "answers": answers,
+                },
+                "m.relates_to": {
+                    "rel_type": "m.reference",
+                    "event_id": poll_start_id,
+                },
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an unstable poll end event.
+    pub fn make_sync_poll_end(
+        &self,
+        sender: &UserId,
+        poll_start_id: &EventId,
+        text: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "org.matrix.msc3381.poll.end",
+            "content": {
+                "org.matrix.msc3381.poll.end": {},
+                "org.matrix.msc1767.text": text,
+                "m.relates_to": {
+                    "rel_type": "m.reference",
+                    "event_id": poll_start_id,
+                },
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.call.invite` event for a 1:1 call, with a lifetime of one
+    /// minute.
+    pub fn make_sync_call_invite(
+        &self,
+        sender: &UserId,
+        call_id: &str,
+        invitee: Option<&UserId>,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.call.invite",
+            "content": {
+                "call_id": call_id,
+                "party_id": format!("{sender}_party"),
+                "invitee": invitee,
+                "lifetime": 60_000,
+                "offer": { "type": "offer", "sdp": "v=0" },
+                "version": "1",
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.call.answer` event for a 1:1 call.
+    pub fn make_sync_call_answer(
+        &self,
+        sender: &UserId,
+        call_id: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.call.answer",
+            "content": {
+                "call_id": call_id,
+                "party_id": format!("{sender}_party"),
+                "answer": { "type": "answer", "sdp": "v=0" },
+                "version": "1",
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.call.hangup` event for a 1:1 call.
+    pub fn make_sync_call_hangup(
+        &self,
+        sender: &UserId,
+        call_id: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.call.hangup",
+            "content": {
+                "call_id": call_id,
+                "party_id": format!("{sender}_party"),
+                "reason": "user_hangup",
+                "version": "1",
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an unstable live location sharing state event of the sender,
+    /// which is live for the given timeout in milliseconds.
+    pub fn make_sync_beacon_info(
+        &self,
+        sender: &UserId,
+        description: Option<&str>,
+        timeout_ms: u64,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "org.matrix.msc3672.beacon_info",
+            "state_key": sender,
+            "content": {
+                "description": description,
+                "live": true,
+                "timeout": timeout_ms,
+                "org.matrix.msc3488.ts": self.next_server_ts(),
+                "org.matrix.msc3488.asset": { "type": "m.self" },
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an unstable location event of a live location sharing, with the
+    /// given `geo:` URI.
+    pub fn make_sync_beacon(
+        &self,
+        sender: &UserId,
+        beacon_info_id: &EventId,
+        geo_uri: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "org.matrix.msc3672.beacon",
+            "content": {
+                "m.relates_to": {
+                    "rel_type": "m.reference",
+                    "event_id": beacon_info_id,
+                },
+                "org.matrix.msc3488.location": { "uri": geo_uri },
+                "org.matrix.msc3488.ts": self.next_server_ts(),
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.room.encrypted` event encrypted with the Megolm session
+    /// with the given ID.
+    ///
+    /// The ciphertext is not valid, so the event can't be decrypted, which is
+    /// useful to test the handling of undecryptable events.
+    pub fn make_sync_encrypted_event(
+        &self,
+        sender: &UserId,
+        session_id: &str,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.room.encrypted",
+            "content": {
+                "algorithm": "m.megolm.v1.aes-sha2",
+                "ciphertext": "AwgAEnACgAkLmt6qF84IK++J7UDH2Za1YVchHyprqTqsg2yyOwAtHaZTwyNg37afzg8f3r9IsN9r4RNFg7MaZencUJe4qvELiDiopUjy5wYVDAtqdBzer5bWRD9ldxp1FLgbQvBcjkkywYjCsmsq6+hArLd9oAQZnGKn/qLsK+5uNX3PaWzDRC9wZPQvWYYPCTov3jCwXKTPsLKIiTrcCXDqMvnn8m+T3zF/I2zqxg158tnUwWWIw51UO",
+                "device_id": "DEVICEID",
+                "sender_key": "aV9BpqYFqJpKYmgERyGv/6QyKMcgLqxM05V0gvzg9Yk",
+                "session_id": session_id,
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make an `m.room.member` event of the sender knocking on the room.
+    pub fn make_sync_knock(
+        &self,
+        sender: &UserId,
+        reason: Option<&str>,
+    ) -> Raw<AnySyncTimelineEvent> {
+        sync_timeline_event!({
+            "type": "m.room.member",
+            "state_key": sender,
+            "content": {
+                "membership": "knock",
+                "reason": reason,
+            },
+            "event_id": EventId::new(server_name!("dummy.server")),
+            "sender": sender,
+            "origin_server_ts": self.next_server_ts(),
+        })
+    }
+
+    /// Make the stripped `m.room.member` event of the sender knocking on the
+    /// room, as received in the state of a knocked room.
+    pub fn make_stripped_knock(&self, sender: &UserId) -> Raw<AnyStrippedStateEvent> {
+        Raw::new(&json!({
+            "type": "m.room.member",
+            "state_key": sender,
+            "content": {
+                "membership": "knock",
+            },
+            "sender": sender,
+        }))
+        .unwrap()
+        .cast()
+    }
+
+    /// Make an `m.receipt` ephemeral event with the given content.
+    ///
+    /// The content can be created with
+    /// [`EventBuilder::make_receipt_event_content()`].
+    pub fn make_receipt_event(
+        &self,
+        content: ReceiptEventContent,
+    ) -> Raw<AnySyncEphemeralRoomEvent> {
+        Raw::new(&json!({
+            "type": "m.receipt",
+            "content": content,
+        }))
+        .unwrap()
+        .cast()
+    }
+
+    /// Make an `m.typing` ephemeral event with the given users typing.
+    pub fn make_typing_event<'a>(
+        &self,
+        user_ids: impl IntoIterator<Item = &'a UserId>,
+    ) -> Raw<AnySyncEphemeralRoomEvent> {
+        let user_ids: Vec<_> = user_ids.into_iter().collect();
+
+        Raw::new(&json!({
+            "type": "m.typing",
+            "content": {
+                "user_ids": user_ids,
+            },
+        }))
+        .unwrap()
+        .cast()
+    }
+
     fn make_redacted_unsigned(&self, sender: &UserId) -> JsonValue {
         json!({
             "redacted_because": {
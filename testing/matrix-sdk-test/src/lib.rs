@@ -85,17 +85,21 @@ pub mod __macro_support {
     pub use tracing_subscriber;
 }
 
+mod clock;
 mod event_builder;
+mod load;
 pub mod notification_settings;
 mod sync_builder;
 pub mod test_json;
 
 pub use self::{
+    clock::MockClock,
     event_builder::EventBuilder,
+    load::{bulk_keys_query_response, SyncLoadGenerator},
     sync_builder::{
         bulk_room_members, EphemeralTestEvent, GlobalAccountDataTestEvent, InvitedRoomBuilder,
-        JoinedRoomBuilder, LeftRoomBuilder, PresenceTestEvent, RoomAccountDataTestEvent,
-        StateTestEvent, StrippedStateTestEvent, SyncResponseBuilder,
+        JoinedRoomBuilder, KnockedRoomBuilder, LeftRoomBuilder, PresenceTestEvent,
+        RoomAccountDataTestEvent, StateTestEvent, StrippedStateTestEvent, SyncResponseBuilder,
     },
 };
 
@@ -0,0 +1,416 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generators of large responses, to benchmark the SDK or to check that it
+//! still performs reasonably in tests.
+
+use ruma::{
+    api::client::sync::sync_events::v3::Response as SyncResponse,
+    events::{room::member::MembershipState, AnySyncStateEvent, AnySyncTimelineEvent},
+    serde::Raw,
+    CanonicalJsonValue, OwnedRoomId, OwnedUserId, UserId,
+};
+use serde_json::{from_value as from_json_value, json, Map as JsonMap, Value as JsonValue};
+use vodozemac::{Curve25519PublicKey, Curve25519SecretKey, Ed25519SecretKey};
+
+use crate::{EphemeralTestEvent, JoinedRoomBuilder, SyncResponseBuilder};
+
+/// The timestamp of the first generated event.
+const START_TS: u64 = 1_700_000_000_000;
+
+/// A generator of large sync responses with joined rooms.
+///
+/// The first response contains the full state of the rooms, with their
+/// members and a first chunk of their timeline. Each following response
+/// contains new messages in every room and, optionally, members that leave
+/// and join again and read receipts.
+///
+/// The generated data is deterministic, so the same generator settings always
+/// produce the same responses.
+///
+/// ```ignore
+/// let mut generator =
+///     SyncLoadGenerator::new(user_id!("@alice:example.org")).rooms(100).events_per_room(50);
+///
+/// // Full state of all the rooms.
+/// client.receive_sync_response(generator.build_sync_response()).await?;
+/// // Only new events.
+/// client.receive_sync_response(generator.build_sync_response()).await?;
+/// ```
+pub struct SyncLoadGenerator {
+    own_user_id: OwnedUserId,
+    rooms: usize,
+    members_per_room: usize,
+    events_per_room: usize,
+    membership_churn: usize,
+    receipts_per_room: usize,
+    /// The number of responses generated so far.
+    batch: usize,
+    builder: SyncResponseBuilder,
+}
+
+impl SyncLoadGenerator {
+    /// Create a new `SyncLoadGenerator` for the given logged-in user.
+    ///
+    /// The other users and the rooms are created on the server of this user.
+    /// By default, there are 10 rooms with 10 other members each, and 10 new
+    /// messages per room in every response, without membership changes nor
+    /// receipts.
+    pub fn new(own_user_id: &UserId) -> Self {
+        Self {
+            own_user_id: own_user_id.to_owned(),
+            rooms: 10,
+            members_per_room: 10,
+            events_per_room: 10,
+            membership_churn: 0,
+            receipts_per_room: 0,
+            batch: 0,
+            builder: SyncResponseBuilder::new(),
+        }
+    }
+
+    /// Set the number of joined rooms.
+    pub fn rooms(mut self, rooms: usize) -> Self {
+        self.rooms = rooms;
+        self
+    }
+
+    /// Set the number of members of every room, besides the logged-in user.
+    ///
+    /// All the rooms share the same members, see
+    /// [`SyncLoadGenerator::member_ids()`].
+    pub fn members_per_room(mut self, members: usize) -> Self {
+        self.members_per_room = members;
+        self
+    }
+
+    /// Set the number of messages added to the timeline of every room in
+    /// each response.
+    pub fn events_per_room(mut self, events: usize) -> Self {
+        self.events_per_room = events;
+        self
+    }
+
+    /// Set the number of members of every room that leave and join again in
+    /// each response after the first one.
+    ///
+    /// A different set of members is picked for each response.
+    pub fn membership_churn(mut self, members: usize) -> Self {
+        self.membership_churn = members;
+        self
+    }
+
+    /// Set the number of members of every room that send a read receipt for
+    /// the latest message in each response.
+    ///
+    /// It is capped to the number of members.
+    pub fn receipts_per_room(mut self, receipts: usize) -> Self {
+        self.receipts_per_room = receipts;
+        self
+    }
+
+    /// The IDs of the joined rooms.
+    pub fn room_ids(&self) -> Vec<OwnedRoomId> {
+        (0..self.rooms).map(|idx| self.room_id(idx)).collect()
+    }
+
+    /// The IDs of the members of the rooms, besides the logged-in user.
+    pub fn member_ids(&self) -> Vec<OwnedUserId> {
+        (0..self.members_per_room).map(|idx| self.member_id(idx)).collect()
+    }
+
+    /// Build the next sync response, as JSON.
+    pub fn build_json_sync_response(&mut self) -> JsonValue {
+        self.add_rooms();
+        self.builder.build_json_sync_response()
+    }
+
+    /// Build the next sync response.
+    pub fn build_sync_response(&mut self) -> SyncResponse {
+        self.add_rooms();
+        self.builder.build_sync_response()
+    }
+
+    /// Add the rooms of the next batch to the builder.
+    fn add_rooms(&mut self) {
+        for room_idx in 0..self.rooms {
+            let room = self.build_room(room_idx);
+            self.builder.add_joined_room(room);
+        }
+
+        self.batch += 1;
+    }
+
+    fn build_room(&self, room_idx: usize) -> JoinedRoomBuilder {
+        let mut room = JoinedRoomBuilder::new(&self.room_id(room_idx));
+
+        if self.batch == 0 {
+            let own_member = self.member_event(
+                format!("$load_own_member_{room_idx}"),
+                &self.own_user_id,
+                MembershipState::Join,
+            );
+            let members = (0..self.members_per_room).map(|idx| {
+                self.member_event(
+                    format!("$load_join_{room_idx}_{idx}"),
+                    &self.member_id(idx),
+                    MembershipState::Join,
+                )
+            });
+
+            room = room
+                .add_state_bulk([self.create_event(room_idx), self.name_event(room_idx)])
+                .add_state_bulk([own_member].into_iter().chain(members));
+        } else if self.membership_churn > 0 && self.members_per_room > 0 {
+            let churn = self.churned_members();
+            let leaves = churn.clone().map(|idx| {
+                self.member_event(
+                    format!("$load_leave_{room_idx}_{}_{idx}", self.batch),
+                    &self.member_id(idx),
+                    MembershipState::Leave,
+                )
+            });
+            let joins = churn.map(|idx| {
+                self.member_event(
+                    format!("$load_rejoin_{room_idx}_{}_{idx}", self.batch),
+                    &self.member_id(idx),
+                    MembershipState::Join,
+                )
+            });
+
+            room = room.add_timeline_state_bulk(leaves.chain(joins));
+        }
+
+        room = room.add_timeline_bulk(
+            (0..self.events_per_room).map(|idx| self.message_event(room_idx, idx)),
+        );
+
+        if self.receipts_per_room > 0 && self.members_per_room > 0 && self.events_per_room > 0 {
+            room = room.add_ephemeral_event(self.receipt_event(room_idx));
+        }
+
+        room
+    }
+
+    fn room_id(&self, room_idx: usize) -> OwnedRoomId {
+        let server_name = self.own_user_id.server_name();
+        format!("!load_room_{room_idx}:{server_name}").try_into().unwrap()
+    }
+
+    fn member_id(&self, idx: usize) -> OwnedUserId {
+        let server_name = self.own_user_id.server_name();
+        format!("@user_{idx}:{server_name}").try_into().unwrap()
+    }
+
+    /// The members that leave and join again in the current batch.
+    ///
+    /// They wrap around the list of members, and are capped to its size.
+    fn churned_members(&self) -> impl Iterator<Item = usize> + Clone {
+        let count = self.membership_churn.min(self.members_per_room);
+        let start = (self.batch - 1) * count;
+        let members = self.members_per_room;
+        (start..start + count).map(move |idx| idx % members)
+    }
+
+    /// A timestamp that increases with the batch and the index of the event in
+    /// the room.
+    fn timestamp(&self, idx: usize) -> u64 {
+        START_TS + (self.batch * 100_000 + idx) as u64
+    }
+
+    fn create_event(&self, room_idx: usize) -> Raw<AnySyncStateEvent> {
+        from_json_value(json!({
+            "content": {
+                "creator": self.own_user_id,
+                "room_version": "10",
+            },
+            "event_id": format!("$load_create_{room_idx}"),
+            "origin_server_ts": self.timestamp(0),
+            "sender": self.own_user_id,
+            "state_key": "",
+            "type": "m.room.create",
+        }))
+        .unwrap()
+    }
+
+    fn name_event(&self, room_idx: usize) -> Raw<AnySyncStateEvent> {
+        from_json_value(json!({
+            "content": {
+                "name": format!("Room {room_idx}"),
+            },
+            "event_id": format!("$load_name_{room_idx}"),
+            "origin_server_ts": self.timestamp(1),
+            "sender": self.own_user_id,
+            "state_key": "",
+            "type": "m.room.name",
+        }))
+        .unwrap()
+    }
+
+    fn member_event(
+        &self,
+        event_id: String,
+        user_id: &UserId,
+        membership: MembershipState,
+    ) -> Raw<AnySyncStateEvent> {
+        from_json_value(json!({
+            "content": {
+                "membership": membership,
+            },
+            "event_id": event_id,
+            "origin_server_ts": self.timestamp(2),
+            "sender": user_id,
+            "state_key": user_id,
+            "type": "m.room.member",
+        }))
+        .unwrap()
+    }
+
+    fn message_event(&self, room_idx: usize, idx: usize) -> Raw<AnySyncTimelineEvent> {
+        let sender = if self.members_per_room == 0 {
+            self.own_user_id.clone()
+        } else {
+            self.member_id((room_idx + idx) % self.members_per_room)
+        };
+
+        from_json_value(json!({
+            "content": {
+                "body": format!("Message {idx} of batch {}", self.batch),
+                "msgtype": "m.text",
+            },
+            "event_id": self.message_event_id(room_idx, idx),
+            "origin_server_ts": self.timestamp(10 + idx),
+            "sender": sender,
+            "type": "m.room.message",
+        }))
+        .unwrap()
+    }
+
+    fn message_event_id(&self, room_idx: usize, idx: usize) -> String {
+        format!("$load_message_{room_idx}_{}_{idx}", self.batch)
+    }
+
+    /// A read receipt for the latest message of the current batch.
+    fn receipt_event(&self, room_idx: usize) -> EphemeralTestEvent {
+        let event_id = self.message_event_id(room_idx, self.events_per_room - 1);
+        let ts = self.timestamp(10 + self.events_per_room);
+
+        let readers: JsonMap<String, JsonValue> =
+            (0..self.receipts_per_room.min(self.members_per_room))
+                .map(|idx| (self.member_id(idx).to_string(), json!({ "ts": ts })))
+                .collect();
+
+        EphemeralTestEvent::Custom(json!({
+            "content": {
+                event_id: {
+                    "m.read": readers,
+                },
+            },
+            "type": "m.receipt",
+        }))
+    }
+}
+
+/// Generate a `/keys/query` response with the keys of the given users.
+///
+/// Every user gets the given number of devices, with the IDs `LOADDEVICE0`,
+/// `LOADDEVICE1`…, a master key and a self-signing key. The keys are generated
+/// randomly, and all the signatures are valid, so the devices are verified by
+/// the cross-signing identity of their user.
+///
+/// Generating and signing the keys takes time, so benchmarks should generate
+/// the response outside of the measured code.
+pub fn bulk_keys_query_response(
+    user_ids: impl IntoIterator<Item = OwnedUserId>,
+    devices_per_user: usize,
+) -> JsonValue {
+    let mut device_keys = JsonMap::new();
+    let mut master_keys = JsonMap::new();
+    let mut self_signing_keys = JsonMap::new();
+
+    for user_id in user_ids {
+        let master_key = Ed25519SecretKey::new();
+        let self_signing_key = Ed25519SecretKey::new();
+
+        let master_key_id = format!("ed25519:{}", master_key.public_key().to_base64());
+        let self_signing_key_id = format!("ed25519:{}", self_signing_key.public_key().to_base64());
+
+        let master = json!({
+            "user_id": user_id,
+            "usage": ["master"],
+            "keys": {
+                &master_key_id: master_key.public_key().to_base64(),
+            },
+        });
+
+        let mut self_signing = json!({
+            "user_id": user_id,
+            "usage": ["self_signing"],
+            "keys": {
+                &self_signing_key_id: self_signing_key.public_key().to_base64(),
+            },
+        });
+        let signature = sign_json(&master_key, &self_signing);
+        self_signing["signatures"] = json!({ user_id.as_str(): { &master_key_id: signature } });
+
+        let devices: JsonMap<String, JsonValue> = (0..devices_per_user)
+            .map(|idx| {
+                let device_id = format!("LOADDEVICE{idx}");
+                let device_key = Ed25519SecretKey::new();
+                let curve_key = Curve25519PublicKey::from(&Curve25519SecretKey::new());
+
+                let mut device = json!({
+                    "algorithms": ["m.olm.v1.curve25519-aes-sha2", "m.megolm.v1.aes-sha2"],
+                    "device_id": device_id,
+                    "keys": {
+                        format!("curve25519:{device_id}"): curve_key.to_base64(),
+                        format!("ed25519:{device_id}"): device_key.public_key().to_base64(),
+                    },
+                    "user_id": user_id,
+                });
+
+                let device_signature = sign_json(&device_key, &device);
+                let cross_signing_signature = sign_json(&self_signing_key, &device);
+                device["signatures"] = json!({
+                    user_id.as_str(): {
+                        format!("ed25519:{device_id}"): device_signature,
+                        &self_signing_key_id: cross_signing_signature,
+                    },
+                });
+
+                (device_id, device)
+            })
+            .collect();
+
+        device_keys.insert(user_id.to_string(), devices.into());
+        master_keys.insert(user_id.to_string(), master);
+        self_signing_keys.insert(user_id.to_string(), self_signing);
+    }
+
+    json!({
+        "device_keys": device_keys,
+        "failures": {},
+        "master_keys": master_keys,
+        "self_signing_keys": self_signing_keys,
+        "user_signing_keys": {},
+    })
+}
+
+/// Sign the canonical form of the given JSON object, and return the signature
+/// encoded as base64.
+fn sign_json(key: &Ed25519SecretKey, value: &JsonValue) -> String {
+    let canonical_json: CanonicalJsonValue =
+        value.clone().try_into().expect("the JSON object should be canonicalizable");
+    key.sign(canonical_json.to_string().as_bytes()).to_base64()
+}